@@ -30,8 +30,10 @@ use clap::Parser;
 use console::style;
 use jmap_client::client::{Client, Credentials};
 use modules::{
+    account::cmd_account,
     cli::{Cli, Commands},
     database::cmd_database,
+    dkim::cmd_dkim,
     export::cmd_export,
     get,
     import::cmd_import,
@@ -71,12 +73,16 @@ async fn main() -> std::io::Result<()> {
                 cmd_export(build_client(&args.url, credentials).await, command).await
             }
             Commands::Database(command) => cmd_database(&args.url, credentials, command).await,
-            Commands::Queue(_) | Commands::Report(_) => unreachable!(),
+            Commands::Account(_) | Commands::Queue(_) | Commands::Report(_) | Commands::Dkim(_) => {
+                unreachable!()
+            }
         }
     } else {
         match args.command {
+            Commands::Account(command) => cmd_account(&args.url, credentials, command).await,
             Commands::Queue(command) => cmd_queue(&args.url, credentials, command).await,
             Commands::Report(command) => cmd_report(&args.url, credentials, command).await,
+            Commands::Dkim(command) => cmd_dkim(&args.url, credentials, command).await,
             _ => unreachable!(),
         }
     }
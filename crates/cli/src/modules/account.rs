@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_client::client::Credentials;
+use prettytable::{Attr, Cell, Row, Table};
+use reqwest::{header::AUTHORIZATION, Method};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use super::{cli::AccountCommands, is_localhost, UnwrapResult};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Principal {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quota: u32,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub member_of: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub app_passwords: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub totp_enabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Response<T> {
+    Data { data: T },
+    Error { title: String, detail: String },
+}
+
+pub async fn cmd_account(url: &str, credentials: Credentials, command: AccountCommands) {
+    match command {
+        AccountCommands::Create {
+            name,
+            password,
+            description,
+            quota,
+            r#type,
+            addresses,
+            member_of,
+        } => {
+            let principal = Principal {
+                name,
+                typ: r#type.id().to_string(),
+                secrets: password.into_iter().collect(),
+                description,
+                quota: quota.unwrap_or_default(),
+                emails: addresses,
+                member_of,
+            };
+            manage_request::<String>(
+                Method::POST,
+                &format!("{url}/admin/principal"),
+                &credentials,
+                Some(serde_json::to_value(&principal).unwrap()),
+            )
+            .await;
+            eprintln!("Successfully created account {:?}.", principal.name);
+        }
+        AccountCommands::Update {
+            name,
+            password,
+            description,
+            quota,
+            r#type,
+        } => {
+            let mut changes = Vec::new();
+            if let Some(password) = password {
+                changes.push(json!({"action": "setSecrets", "value": [password]}));
+            }
+            if let Some(description) = description {
+                changes.push(json!({"action": "setDescription", "value": description}));
+            }
+            if let Some(quota) = quota {
+                changes.push(json!({"action": "setQuota", "value": quota}));
+            }
+            if let Some(typ) = r#type {
+                changes.push(json!({"action": "setType", "value": typ.id()}));
+            }
+            if !changes.is_empty() {
+                update_principal(url, &credentials, &name, changes).await;
+            } else {
+                eprintln!("No changes to apply.");
+            }
+        }
+        AccountCommands::AddEmail { name, addresses } => {
+            let changes = addresses
+                .into_iter()
+                .map(|address| json!({"action": "addEmail", "value": address}))
+                .collect();
+            update_principal(url, &credentials, &name, changes).await;
+        }
+        AccountCommands::RemoveEmail { name, addresses } => {
+            let changes = addresses
+                .into_iter()
+                .map(|address| json!({"action": "removeEmail", "value": address}))
+                .collect();
+            update_principal(url, &credentials, &name, changes).await;
+        }
+        AccountCommands::AddToGroup { name, groups } => {
+            let changes = groups
+                .into_iter()
+                .map(|group| json!({"action": "addMemberOf", "value": group}))
+                .collect();
+            update_principal(url, &credentials, &name, changes).await;
+        }
+        AccountCommands::RemoveFromGroup { name, groups } => {
+            let changes = groups
+                .into_iter()
+                .map(|group| json!({"action": "removeMemberOf", "value": group}))
+                .collect();
+            update_principal(url, &credentials, &name, changes).await;
+        }
+        AccountCommands::AddAppPassword {
+            name,
+            app_name,
+            password,
+            protocols,
+        } => {
+            update_principal(
+                url,
+                &credentials,
+                &name,
+                vec![json!({"action": "addAppPassword", "value": {
+                    "name": app_name,
+                    "protocols": protocols,
+                    "secret": password,
+                }})],
+            )
+            .await;
+        }
+        AccountCommands::RemoveAppPassword { name, app_name } => {
+            update_principal(
+                url,
+                &credentials,
+                &name,
+                vec![json!({"action": "removeAppPassword", "value": app_name})],
+            )
+            .await;
+        }
+        AccountCommands::EnableTotp { name } => {
+            let key = manage_request::<String>(
+                Method::PATCH,
+                &format!("{url}/admin/principal/{name}"),
+                &credentials,
+                Some(json!([{"action": "enableTotp"}])),
+            )
+            .await;
+            eprintln!("Two-factor authentication enabled for {name:?}, add this key to an authenticator app:\n");
+            println!("{key}");
+        }
+        AccountCommands::DisableTotp { name } => {
+            update_principal(
+                url,
+                &credentials,
+                &name,
+                vec![json!({"action": "disableTotp"})],
+            )
+            .await;
+        }
+        AccountCommands::Delete { name } => {
+            manage_request::<String>(
+                Method::DELETE,
+                &format!("{url}/admin/principal/{name}"),
+                &credentials,
+                None,
+            )
+            .await;
+            eprintln!("Successfully deleted account {name:?}.");
+        }
+        AccountCommands::Display { name } => {
+            let principal = manage_request::<Principal>(
+                Method::GET,
+                &format!("{url}/admin/principal/{name}"),
+                &credentials,
+                None,
+            )
+            .await;
+            let mut table = Table::new();
+            for (title, value) in [
+                ("Name", principal.name),
+                ("Type", principal.typ),
+                ("Description", principal.description.unwrap_or_default()),
+                ("Quota", principal.quota.to_string()),
+                ("E-mail", principal.emails.join("\n")),
+                ("Member of", principal.member_of.join("\n")),
+                ("App passwords", principal.app_passwords.join("\n")),
+                (
+                    "Two-factor",
+                    if principal.totp_enabled {
+                        "Enabled"
+                    } else {
+                        "Disabled"
+                    }
+                    .to_string(),
+                ),
+            ] {
+                table.add_row(Row::new(vec![
+                    Cell::new(title).with_style(Attr::Bold),
+                    Cell::new(&value),
+                ]));
+            }
+            eprintln!();
+            table.printstd();
+            eprintln!();
+        }
+        AccountCommands::List {} => {
+            let names = manage_request::<Vec<String>>(
+                Method::GET,
+                &format!("{url}/admin/principal"),
+                &credentials,
+                None,
+            )
+            .await;
+            for name in &names {
+                println!("{name}");
+            }
+            eprintln!("\n{} account(s) found.", names.len());
+        }
+    }
+}
+
+async fn update_principal(
+    url: &str,
+    credentials: &Credentials,
+    name: &str,
+    changes: Vec<serde_json::Value>,
+) {
+    manage_request::<String>(
+        Method::PATCH,
+        &format!("{url}/admin/principal/{name}"),
+        credentials,
+        Some(serde_json::Value::Array(changes)),
+    )
+    .await;
+    eprintln!("Successfully updated account {name:?}.");
+}
+
+async fn manage_request<T: DeserializeOwned>(
+    method: Method,
+    url: &str,
+    credentials: &Credentials,
+    body: Option<serde_json::Value>,
+) -> T {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(is_localhost(url))
+        .build()
+        .unwrap_or_default()
+        .request(method, url)
+        .header(
+            AUTHORIZATION,
+            match credentials {
+                Credentials::Basic(s) => format!("Basic {s}"),
+                Credentials::Bearer(s) => format!("Bearer {s}"),
+            },
+        );
+    if let Some(body) = body {
+        request = request.body(body.to_string());
+    }
+
+    match serde_json::from_slice::<Response<T>>(
+        &request
+            .send()
+            .await
+            .unwrap_result("send request")
+            .bytes()
+            .await
+            .unwrap_result("fetch bytes"),
+    )
+    .unwrap_result("deserialize response")
+    {
+        Response::Data { data } => data,
+        Response::Error { title, detail } => {
+            eprintln!("Request failed: {detail} ({title})");
+            std::process::exit(1);
+        }
+    }
+}
@@ -49,6 +49,10 @@ pub enum Commands {
     #[clap(subcommand)]
     Export(ExportCommands),
 
+    /// Manage user accounts and groups
+    #[clap(subcommand)]
+    Account(AccountCommands),
+
     /// Manage JMAP database
     #[clap(subcommand)]
     Database(DatabaseCommands),
@@ -57,9 +61,13 @@ pub enum Commands {
     #[clap(subcommand)]
     Queue(QueueCommands),
 
-    /// Manage SMTP DMARC/TLS report queue
+    /// Manage SMTP DMARC/TLS report queue and received reports
     #[clap(subcommand)]
     Report(ReportCommands),
+
+    /// Manage DKIM signing keys
+    #[clap(subcommand)]
+    Dkim(DkimCommands),
 }
 
 #[derive(Subcommand)]
@@ -110,6 +118,152 @@ pub enum ExportCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum AccountCommands {
+    /// Create a new user account or group
+    Create {
+        /// Account name
+        name: String,
+        /// Account password
+        #[clap(short, long)]
+        password: Option<String>,
+        /// Account description
+        #[clap(short, long)]
+        description: Option<String>,
+        /// Quota in bytes
+        #[clap(short, long)]
+        quota: Option<u32>,
+        /// Account type
+        #[clap(short, long)]
+        #[clap(value_enum)]
+        #[clap(default_value_t = AccountType::Individual)]
+        r#type: AccountType,
+        /// E-mail addresses, the first one is used as the primary address
+        #[clap(short, long)]
+        addresses: Vec<String>,
+        /// Groups this account is a member of
+        #[clap(short, long)]
+        member_of: Vec<String>,
+    },
+
+    /// Update an existing user account or group
+    Update {
+        /// Account name
+        name: String,
+        /// Set account password
+        #[clap(short, long)]
+        password: Option<String>,
+        /// Set account description
+        #[clap(short, long)]
+        description: Option<String>,
+        /// Set quota in bytes
+        #[clap(short, long)]
+        quota: Option<u32>,
+        /// Set account type
+        #[clap(short, long)]
+        #[clap(value_enum)]
+        r#type: Option<AccountType>,
+    },
+
+    /// Add e-mail aliases to an account
+    AddEmail {
+        /// Account name
+        name: String,
+        /// E-mail addresses to add
+        #[clap(required = true)]
+        addresses: Vec<String>,
+    },
+
+    /// Remove e-mail aliases from an account
+    RemoveEmail {
+        /// Account name
+        name: String,
+        /// E-mail addresses to remove
+        #[clap(required = true)]
+        addresses: Vec<String>,
+    },
+
+    /// Add an account to one or multiple groups
+    AddToGroup {
+        /// Account name
+        name: String,
+        /// Groups to add the account to
+        #[clap(required = true)]
+        groups: Vec<String>,
+    },
+
+    /// Remove an account from one or multiple groups
+    RemoveFromGroup {
+        /// Account name
+        name: String,
+        /// Groups to remove the account from
+        #[clap(required = true)]
+        groups: Vec<String>,
+    },
+
+    /// Add an application-specific password to an account
+    AddAppPassword {
+        /// Account name
+        name: String,
+        /// Application password name
+        app_name: String,
+        /// Application password
+        password: String,
+        /// Restrict the password to these protocols (smtp, imap, jmap, sieve, http)
+        #[clap(short, long)]
+        protocols: Vec<String>,
+    },
+
+    /// Revoke an application-specific password
+    RemoveAppPassword {
+        /// Account name
+        name: String,
+        /// Application password name
+        app_name: String,
+    },
+
+    /// Enable TOTP two-factor authentication for an account
+    EnableTotp {
+        /// Account name
+        name: String,
+    },
+
+    /// Disable TOTP two-factor authentication for an account
+    DisableTotp {
+        /// Account name
+        name: String,
+    },
+
+    /// Delete an existing user account or group
+    Delete {
+        /// Account name to delete
+        name: String,
+    },
+
+    /// Display an existing user account or group
+    Display {
+        /// Account name to display
+        name: String,
+    },
+
+    /// List all user accounts and groups
+    List {},
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum AccountType {
+    /// Individual user account
+    Individual,
+    /// Group
+    Group,
+    /// Resource
+    Resource,
+    /// Location
+    Location,
+    /// Superuser account
+    Superuser,
+}
+
 #[derive(Subcommand)]
 pub enum DatabaseCommands {
     /// Delete a JMAP account
@@ -212,6 +366,13 @@ pub enum QueueCommands {
         // Cancel one or multiple message ids
         ids: Vec<String>,
     },
+
+    /// Shows remote hosts being slowed down after deferring messages
+    Throttle {
+        /// Filter by MX hostname
+        #[clap(short, long)]
+        mx: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -241,11 +402,115 @@ pub enum ReportCommands {
         #[clap(required = true)]
         ids: Vec<String>,
     },
+
+    /// Shows received DMARC/TLS reports
+    Received {
+        /// Filter by report domain
+        #[clap(short, long)]
+        domain: Option<String>,
+        /// Filter by source IP address
+        #[clap(short, long)]
+        ip: Option<String>,
+        /// Filter by report type
+        #[clap(short, long)]
+        #[clap(value_enum)]
+        format: Option<ReportFormat>,
+        /// Filter reports starting before a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        before: Option<DateTime>,
+        /// Filter reports starting after a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        after: Option<DateTime>,
+        /// Number of items to show per page
+        #[clap(short, long)]
+        page_size: Option<usize>,
+    },
+
+    /// Displays the contents of a received report
+    Show {
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+
+    /// Summarizes received reports by source IP and reporting organization
+    Summary {
+        /// Filter by report domain
+        #[clap(short, long)]
+        domain: Option<String>,
+        /// Filter by source IP address
+        #[clap(short, long)]
+        ip: Option<String>,
+        /// Filter by report type
+        #[clap(short, long)]
+        #[clap(value_enum)]
+        format: Option<ReportFormat>,
+        /// Filter reports starting before a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        before: Option<DateTime>,
+        /// Filter reports starting after a certain datetime
+        #[clap(short, long)]
+        #[arg(value_parser = parse_datetime)]
+        after: Option<DateTime>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DkimCommands {
+    /// Shows managed DKIM keys
+    List {
+        /// Filter by domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+
+    /// Generates a new DKIM key, replacing the active key after the overlap period
+    Generate {
+        /// Domain name
+        domain: String,
+        /// Key algorithm, defaults to the configured algorithm
+        #[clap(short, long)]
+        #[clap(value_enum)]
+        algorithm: Option<DkimAlgorithm>,
+        /// DKIM selector, defaults to a date based selector
+        #[clap(short, long)]
+        selector: Option<String>,
+    },
+
+    /// Shows the DNS records to publish for managed DKIM keys
+    Dns {
+        /// Filter by domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+
+    /// Deletes a DKIM key
+    Delete {
+        #[clap(required = true)]
+        ids: Vec<u32>,
+    },
+}
+
+impl AccountType {
+    pub fn id(&self) -> &'static str {
+        match self {
+            AccountType::Individual => "individual",
+            AccountType::Group => "group",
+            AccountType::Resource => "resource",
+            AccountType::Location => "location",
+            AccountType::Superuser => "superuser",
+        }
+    }
 }
 
 impl Commands {
     pub fn is_jmap(&self) -> bool {
-        !matches!(self, Commands::Queue(_) | Commands::Report(_))
+        !matches!(
+            self,
+            Commands::Account(_) | Commands::Queue(_) | Commands::Report(_) | Commands::Dkim(_)
+        )
     }
 }
 
@@ -259,6 +524,25 @@ pub enum ReportFormat {
     Tls,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+pub enum DkimAlgorithm {
+    /// RSA-SHA256
+    #[serde(rename = "rsa")]
+    Rsa,
+    /// Ed25519-SHA256
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+impl DkimAlgorithm {
+    pub fn id(&self) -> &'static str {
+        match self {
+            DkimAlgorithm::Rsa => "rsa",
+            DkimAlgorithm::Ed25519 => "ed25519",
+        }
+    }
+}
+
 fn parse_datetime(arg: &str) -> Result<DateTime, &'static str> {
     if arg.contains('T') {
         DateTime::parse_rfc3339(arg).ok_or("Failed to parse RFC3339 datetime")
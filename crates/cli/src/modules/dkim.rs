@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use super::cli::{DkimAlgorithm, DkimCommands};
+use crate::modules::queue::{
+    deserialize_datetime, deserialize_maybe_datetime, smtp_manage_request,
+};
+use jmap_client::client::Credentials;
+use mail_parser::DateTime;
+use prettytable::{Attr, Cell, Row, Table};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct DkimKey {
+    pub id: u32,
+    pub domain: String,
+    pub selector: String,
+    pub algorithm: DkimAlgorithm,
+    pub status: String,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub created: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub active_from: DateTime,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    pub retire_at: Option<DateTime>,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    pub remove_at: Option<DateTime>,
+    pub dns_record: DkimDnsRecord,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DkimDnsRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub value: String,
+}
+
+pub async fn cmd_dkim(url: &str, credentials: Credentials, command: DkimCommands) {
+    match command {
+        DkimCommands::List { domain } => {
+            let mut query = form_urlencoded::Serializer::new(format!("{url}/admin/dkim/list?"));
+            if let Some(domain) = &domain {
+                query.append_pair("domain", domain);
+            }
+            let keys = smtp_manage_request::<Vec<DkimKey>>(&query.finish(), &credentials).await;
+
+            if !keys.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    [
+                        "ID",
+                        "Domain",
+                        "Selector",
+                        "Algorithm",
+                        "Status",
+                        "Active From",
+                        "Retire At",
+                        "Remove At",
+                    ]
+                    .iter()
+                    .map(|p| Cell::new(p).with_style(Attr::Bold))
+                    .collect(),
+                ));
+                for key in &keys {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&key.id.to_string()),
+                        Cell::new(&key.domain),
+                        Cell::new(&key.selector),
+                        Cell::new(key.algorithm.id()),
+                        Cell::new(&key.status),
+                        Cell::new(&key.active_from.to_rfc822()),
+                        Cell::new(
+                            &key.retire_at
+                                .as_ref()
+                                .map_or_else(|| "-".to_string(), |dt| dt.to_rfc822()),
+                        ),
+                        Cell::new(
+                            &key.remove_at
+                                .as_ref()
+                                .map_or_else(|| "-".to_string(), |dt| dt.to_rfc822()),
+                        ),
+                    ]));
+                }
+
+                eprintln!();
+                table.printstd();
+                eprintln!();
+            }
+            eprintln!("\n{} key(s) found.", keys.len());
+        }
+        DkimCommands::Generate {
+            domain,
+            algorithm,
+            selector,
+        } => {
+            let mut query = form_urlencoded::Serializer::new(format!("{url}/admin/dkim/generate?"));
+            query.append_pair("domain", &domain);
+            if let Some(algorithm) = &algorithm {
+                query.append_pair("algorithm", algorithm.id());
+            }
+            if let Some(selector) = &selector {
+                query.append_pair("selector", selector);
+            }
+            let key = smtp_manage_request::<DkimKey>(&query.finish(), &credentials).await;
+
+            eprintln!(
+                "\nGenerated {} key with selector {:?} for domain {:?}, signing starts on {}.",
+                key.algorithm.id(),
+                key.selector,
+                key.domain,
+                key.active_from.to_rfc822()
+            );
+            eprintln!("Publish the following DNS record before that date:\n");
+            println!(
+                "{} IN {} \"{}\"",
+                key.dns_record.name, key.dns_record.type_, key.dns_record.value
+            );
+        }
+        DkimCommands::Dns { domain } => {
+            let mut query = form_urlencoded::Serializer::new(format!("{url}/admin/dkim/dns?"));
+            if let Some(domain) = &domain {
+                query.append_pair("domain", domain);
+            }
+            let records =
+                smtp_manage_request::<Vec<DkimDnsRecord>>(&query.finish(), &credentials).await;
+
+            for record in &records {
+                println!("{} IN {} \"{}\"", record.name, record.type_, record.value);
+            }
+            eprintln!("\n{} record(s) found.", records.len());
+        }
+        DkimCommands::Delete { ids } => {
+            let mut success_count = 0;
+            let mut failed_list = vec![];
+            for id in ids {
+                let success = smtp_manage_request::<bool>(
+                    &format!("{url}/admin/dkim/delete?id={id}"),
+                    &credentials,
+                )
+                .await;
+                if success {
+                    success_count += 1;
+                } else {
+                    failed_list.push(id.to_string());
+                }
+            }
+            eprint!("\nSuccessfully deleted {success_count} key(s).");
+            if !failed_list.is_empty() {
+                eprint!(" Unable to delete id(s): {}.", failed_list.join(", "));
+            }
+            eprintln!();
+        }
+    }
+}
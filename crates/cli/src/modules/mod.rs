@@ -31,8 +31,10 @@ use jmap_client::{
     },
 };
 
+pub mod account;
 pub mod cli;
 pub mod database;
+pub mod dkim;
 pub mod export;
 pub mod import;
 pub mod queue;
@@ -64,6 +64,18 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct Throttle {
+    pub mx: String,
+    pub delay: u64,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    pub next_attempt: Option<DateTime>,
+    pub deferrals: u64,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub last_deferral: DateTime,
+    pub last_response: String,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub enum Status {
     #[serde(rename = "scheduled")]
@@ -394,6 +406,43 @@ pub async fn cmd_queue(url: &str, credentials: Credentials, command: QueueComman
             }
             eprintln!();
         }
+        QueueCommands::Throttle { mx } => {
+            let mut query =
+                form_urlencoded::Serializer::new(format!("{url}/admin/queue/throttle?"));
+            if let Some(mx) = &mx {
+                query.append_pair("mx", mx);
+            }
+            let hosts = smtp_manage_request::<Vec<Throttle>>(&query.finish(), &credentials).await;
+
+            if !hosts.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["MX", "Delay", "Next Attempt", "Deferrals", "Last Response"]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for host in &hosts {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&host.mx),
+                        Cell::new(&format!("{}s", host.delay)),
+                        Cell::new(
+                            &host
+                                .next_attempt
+                                .as_ref()
+                                .map_or_else(|| "Now".to_string(), |dt| dt.to_rfc822()),
+                        ),
+                        Cell::new(&host.deferrals.to_string()),
+                        Cell::new(&host.last_response),
+                    ]));
+                }
+
+                eprintln!();
+                table.printstd();
+                eprintln!();
+            }
+            eprintln!("\n{} throttled host(s) found.", hosts.len());
+        }
     }
 }
 
@@ -461,7 +510,7 @@ async fn query_messages(
     smtp_manage_request::<Vec<u64>>(&query.finish(), credentials).await
 }
 
-fn deserialize_maybe_datetime<'de, D>(deserializer: D) -> Result<Option<DateTime>, D::Error>
+pub fn deserialize_maybe_datetime<'de, D>(deserializer: D) -> Result<Option<DateTime>, D::Error>
 where
     D: Deserializer<'de>,
 {
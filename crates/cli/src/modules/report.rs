@@ -42,6 +42,71 @@ pub struct Report {
     pub size: usize,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct IncomingReport {
+    #[serde(rename = "type")]
+    pub type_: ReportFormat,
+    pub from: String,
+    pub organization: String,
+    pub domains: Vec<String>,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub received: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub range_from: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub range_to: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportAggregate {
+    pub reports: usize,
+    pub dmarc: DmarcAggregate,
+    pub tls: TlsAggregate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DmarcAggregate {
+    pub messages: u64,
+    pub dmarc_pass: u64,
+    pub dmarc_quarantine: u64,
+    pub dmarc_reject: u64,
+    pub dmarc_none: u64,
+    pub dkim_pass: u64,
+    pub dkim_fail: u64,
+    pub spf_pass: u64,
+    pub spf_fail: u64,
+    pub sources: Vec<DmarcSource>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DmarcSource {
+    pub ip: String,
+    pub messages: u64,
+    pub dkim_fail: u64,
+    pub spf_fail: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TlsAggregate {
+    pub sessions_success: u64,
+    pub sessions_failure: u64,
+    pub reporters: Vec<TlsReporter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TlsReporter {
+    pub organization: String,
+    pub sessions_success: u64,
+    pub sessions_failure: u64,
+    pub failures: Vec<TlsFailure>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TlsFailure {
+    pub result_type: String,
+    pub sessions: u64,
+}
+
 pub async fn cmd_report(url: &str, credentials: Credentials, command: ReportCommands) {
     match command {
         ReportCommands::List {
@@ -186,9 +251,250 @@ pub async fn cmd_report(url: &str, credentials: Credentials, command: ReportComm
             }
             eprintln!();
         }
+        ReportCommands::Received {
+            domain,
+            ip,
+            format,
+            before,
+            after,
+            page_size,
+        } => {
+            let stdout = Term::buffered_stdout();
+            let ids = smtp_manage_request::<Vec<u32>>(
+                &build_incoming_query(
+                    url,
+                    "/admin/incoming/list?",
+                    &domain,
+                    &ip,
+                    &format,
+                    &before,
+                    &after,
+                ),
+                &credentials,
+            )
+            .await;
+            let ids_len = ids.len();
+            let page_size = page_size.map(|p| std::cmp::max(p, 1)).unwrap_or(20);
+            let pages_total = (ids_len as f64 / page_size as f64).ceil() as usize;
+            for (page_num, chunk) in ids.chunks(page_size).enumerate() {
+                // Build table
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    [
+                        "ID",
+                        "Type",
+                        "Domains",
+                        "Organization",
+                        "From Date",
+                        "To Date",
+                    ]
+                    .iter()
+                    .map(|p| Cell::new(p).with_style(Attr::Bold))
+                    .collect(),
+                ));
+                for (report, id) in smtp_manage_request::<Vec<Option<IncomingReport>>>(
+                    &format!(
+                        "{url}/admin/incoming/get?ids={}",
+                        chunk
+                            .iter()
+                            .map(|id| id.to_string())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    ),
+                    &credentials,
+                )
+                .await
+                .into_iter()
+                .zip(chunk)
+                {
+                    if let Some(report) = report {
+                        table.add_row(Row::new(vec![
+                            Cell::new(&id.to_string()),
+                            Cell::new(report.type_.name()),
+                            Cell::new(&report.domains.join("\n")),
+                            Cell::new(&report.organization),
+                            Cell::new(&report.range_from.to_rfc822()),
+                            Cell::new(&report.range_to.to_rfc822()),
+                        ]));
+                    }
+                }
+
+                eprintln!();
+                table.printstd();
+                eprintln!();
+                if page_num + 1 != pages_total {
+                    eprintln!("\n--- Press any key to continue or 'q' to exit ---");
+                    if let Ok('q' | 'Q') = stdout.read_char() {
+                        break;
+                    }
+                }
+            }
+            eprintln!("\n{ids_len} received report(s) found.")
+        }
+        ReportCommands::Show { ids } => {
+            for (report, id) in smtp_manage_request::<Vec<Option<serde_json::Value>>>(
+                &format!("{url}/admin/incoming/get?ids={}", ids.join(",")),
+                &credentials,
+            )
+            .await
+            .into_iter()
+            .zip(&ids)
+            {
+                eprintln!("\n--- Report {id} ---\n");
+                if let Some(report) = report {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&report).unwrap_or_default()
+                    );
+                } else {
+                    eprintln!("-- Not found --");
+                }
+            }
+        }
+        ReportCommands::Summary {
+            domain,
+            ip,
+            format,
+            before,
+            after,
+        } => {
+            let aggregate = smtp_manage_request::<ReportAggregate>(
+                &build_incoming_query(
+                    url,
+                    "/admin/incoming/aggregate?",
+                    &domain,
+                    &ip,
+                    &format,
+                    &before,
+                    &after,
+                ),
+                &credentials,
+            )
+            .await;
+
+            if aggregate.dmarc.messages > 0 {
+                let dmarc = &aggregate.dmarc;
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["DMARC", "Pass", "Fail", "Quarantine", "Reject", "None"]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                table.add_row(Row::new(vec![
+                    Cell::new("Disposition").with_style(Attr::Bold),
+                    Cell::new(&dmarc.dmarc_pass.to_string()),
+                    Cell::new(""),
+                    Cell::new(&dmarc.dmarc_quarantine.to_string()),
+                    Cell::new(&dmarc.dmarc_reject.to_string()),
+                    Cell::new(&dmarc.dmarc_none.to_string()),
+                ]));
+                table.add_row(Row::new(vec![
+                    Cell::new("DKIM").with_style(Attr::Bold),
+                    Cell::new(&dmarc.dkim_pass.to_string()),
+                    Cell::new(&dmarc.dkim_fail.to_string()),
+                    Cell::new(""),
+                    Cell::new(""),
+                    Cell::new(""),
+                ]));
+                table.add_row(Row::new(vec![
+                    Cell::new("SPF").with_style(Attr::Bold),
+                    Cell::new(&dmarc.spf_pass.to_string()),
+                    Cell::new(&dmarc.spf_fail.to_string()),
+                    Cell::new(""),
+                    Cell::new(""),
+                    Cell::new(""),
+                ]));
+                eprintln!();
+                table.printstd();
+
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["Source IP", "Messages", "DKIM Failures", "SPF Failures"]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for source in &dmarc.sources {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&source.ip),
+                        Cell::new(&source.messages.to_string()),
+                        Cell::new(&source.dkim_fail.to_string()),
+                        Cell::new(&source.spf_fail.to_string()),
+                    ]));
+                }
+                eprintln!();
+                table.printstd();
+            }
+
+            if !aggregate.tls.reporters.is_empty() {
+                let mut table = Table::new();
+                table.add_row(Row::new(
+                    ["Organization", "Successful", "Failed", "Failure Types"]
+                        .iter()
+                        .map(|p| Cell::new(p).with_style(Attr::Bold))
+                        .collect(),
+                ));
+                for reporter in &aggregate.tls.reporters {
+                    table.add_row(Row::new(vec![
+                        Cell::new(&reporter.organization),
+                        Cell::new(&reporter.sessions_success.to_string()),
+                        Cell::new(&reporter.sessions_failure.to_string()),
+                        Cell::new(
+                            &reporter
+                                .failures
+                                .iter()
+                                .map(|f| format!("{} ({})", f.result_type, f.sessions))
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        ),
+                    ]));
+                }
+                table.add_row(Row::new(vec![
+                    Cell::new("Total").with_style(Attr::Bold),
+                    Cell::new(&aggregate.tls.sessions_success.to_string()),
+                    Cell::new(&aggregate.tls.sessions_failure.to_string()),
+                    Cell::new(""),
+                ]));
+                eprintln!();
+                table.printstd();
+            }
+
+            eprintln!("\n{} received report(s) found.", aggregate.reports);
+        }
     }
 }
 
+fn build_incoming_query(
+    url: &str,
+    path: &str,
+    domain: &Option<String>,
+    ip: &Option<String>,
+    format: &Option<ReportFormat>,
+    before: &Option<DateTime>,
+    after: &Option<DateTime>,
+) -> String {
+    let mut query = form_urlencoded::Serializer::new(format!("{url}{path}"));
+
+    if let Some(domain) = domain {
+        query.append_pair("domain", domain);
+    }
+    if let Some(ip) = ip {
+        query.append_pair("ip", ip);
+    }
+    if let Some(format) = format {
+        query.append_pair("type", format.id());
+    }
+    if let Some(before) = before {
+        query.append_pair("before", &before.to_rfc3339());
+    }
+    if let Some(after) = after {
+        query.append_pair("after", &after.to_rfc3339());
+    }
+
+    query.finish()
+}
+
 impl ReportFormat {
     fn id(&self) -> &'static str {
         match self {
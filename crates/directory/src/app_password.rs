@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use crate::Protocol;
+
+const APP_PASSWORD_PREFIX: &str = "$app$";
+
+// Application-specific passwords are stored alongside the principal's secrets as
+// $app$<name>$<protocols>$<hash>, where protocols is a comma separated list or '*'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppPassword {
+    pub name: String,
+    pub protocols: Vec<Protocol>,
+    pub hash: String,
+}
+
+impl AppPassword {
+    pub fn new(name: impl Into<String>, protocols: Vec<Protocol>, hash: impl Into<String>) -> Self {
+        AppPassword {
+            name: name.into(),
+            protocols,
+            hash: hash.into(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.strip_prefix(APP_PASSWORD_PREFIX)?.splitn(3, '$');
+        let name = parts.next().filter(|name| !name.is_empty())?;
+        let protocols = parts.next()?;
+        let hash = parts.next().filter(|hash| !hash.is_empty())?;
+
+        Some(AppPassword {
+            name: name.to_string(),
+            protocols: if protocols != "*" {
+                protocols
+                    .split(',')
+                    .map(Protocol::parse)
+                    .collect::<Option<Vec<_>>>()?
+            } else {
+                vec![]
+            },
+            hash: hash.to_string(),
+        })
+    }
+
+    pub fn is_app_password(value: &str) -> bool {
+        value.starts_with(APP_PASSWORD_PREFIX)
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.contains(['$', ',']) && name.len() <= 64
+    }
+
+    // Application passwords are never accepted for interactive logins
+    pub fn is_allowed(&self, protocol: Protocol) -> bool {
+        !protocol.is_interactive()
+            && (self.protocols.is_empty() || self.protocols.contains(&protocol))
+    }
+}
+
+impl Display for AppPassword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}$", APP_PASSWORD_PREFIX, self.name)?;
+        if !self.protocols.is_empty() {
+            for (pos, protocol) in self.protocols.iter().enumerate() {
+                if pos > 0 {
+                    f.write_str(",")?;
+                }
+                f.write_str(protocol.as_str())?;
+            }
+        } else {
+            f.write_str("*")?;
+        }
+        write!(f, "${}", self.hash)
+    }
+}
@@ -23,7 +23,7 @@
 
 use mail_send::Credentials;
 
-use crate::{Directory, Principal};
+use crate::{Directory, DirectoryError, DirectoryWrite, Principal, PrincipalUpdate, Protocol};
 
 use super::CachedDirectory;
 
@@ -32,8 +32,9 @@ impl<T: Directory> Directory for CachedDirectory<T> {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
-        self.inner.authenticate(credentials).await
+        self.inner.authenticate(credentials, protocol).await
     }
 
     async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
@@ -89,4 +90,61 @@ impl<T: Directory> Directory for CachedDirectory<T> {
             Ok(false)
         }
     }
+
+    fn writer(&self) -> Option<&dyn DirectoryWrite> {
+        if self.inner.writer().is_some() {
+            Some(self)
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Directory> DirectoryWrite for CachedDirectory<T> {
+    async fn create_principal(
+        &self,
+        principal: Principal,
+        emails: Vec<String>,
+    ) -> crate::Result<()> {
+        self.inner_writer()?
+            .create_principal(principal, emails)
+            .await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn update_principal(
+        &self,
+        name: &str,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        self.inner_writer()?.update_principal(name, changes).await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn delete_principal(&self, name: &str) -> crate::Result<()> {
+        self.inner_writer()?.delete_principal(name).await?;
+        self.clear_cache();
+        Ok(())
+    }
+
+    async fn list_principals(&self) -> crate::Result<Vec<String>> {
+        self.inner_writer()?.list_principals().await
+    }
+}
+
+impl<T: Directory> CachedDirectory<T> {
+    fn inner_writer(&self) -> crate::Result<&dyn DirectoryWrite> {
+        self.inner
+            .writer()
+            .ok_or_else(|| DirectoryError::unsupported(self.inner.type_name(), "write"))
+    }
+
+    fn clear_cache(&self) {
+        // Addresses and domains may have been added or removed
+        self.cached_rcpts.lock().clear();
+        self.cached_domains.lock().clear();
+    }
 }
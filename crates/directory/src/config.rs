@@ -32,42 +32,75 @@ use utils::config::{utils::AsKey, Config};
 
 use ahash::{AHashMap, AHashSet};
 
+use store::Store;
+
 use crate::{
-    imap::ImapDirectory, ldap::LdapDirectory, memory::MemoryDirectory, smtp::SmtpDirectory,
-    sql::SqlDirectory, DirectoryConfig, DirectoryOptions, Lookup,
+    imap::ImapDirectory, internal::InternalDirectory, ldap::LdapDirectory, memory::MemoryDirectory,
+    oidc::OidcDirectory, smtp::SmtpDirectory, sql::SqlDirectory, DirectoryConfig, DirectoryOptions,
+    Lookup,
 };
 
 pub trait ConfigDirectory {
     fn parse_directory(&self) -> utils::config::Result<DirectoryConfig>;
+    fn parse_directory_with_store(
+        &self,
+        store: Option<&Arc<Store>>,
+    ) -> utils::config::Result<DirectoryConfig>;
     fn parse_lookup_list(&self, key: impl AsKey) -> utils::config::Result<AHashSet<String>>;
 }
 
 impl ConfigDirectory for Config {
     fn parse_directory(&self) -> utils::config::Result<DirectoryConfig> {
+        self.parse_directory_with_store(None)
+    }
+
+    fn parse_directory_with_store(
+        &self,
+        store: Option<&Arc<Store>>,
+    ) -> utils::config::Result<DirectoryConfig> {
         let mut config = DirectoryConfig {
             directories: AHashMap::new(),
             lookups: AHashMap::new(),
         };
+        let mut oidc_ids = Vec::new();
         for id in self.sub_keys("directory") {
             // Parse directory
             let protocol = self.value_require(("directory", id, "type"))?;
             let prefix = ("directory", id);
             let directory = match protocol {
+                "oidc" => {
+                    // OpenID Connect directories might refer to other directories
+                    oidc_ids.push(id);
+                    continue;
+                }
                 "ldap" => LdapDirectory::from_config(self, prefix)?,
                 "sql" => SqlDirectory::from_config(self, prefix)?,
                 "imap" => ImapDirectory::from_config(self, prefix)?,
                 "smtp" => SmtpDirectory::from_config(self, prefix, false)?,
                 "lmtp" => SmtpDirectory::from_config(self, prefix, true)?,
                 "memory" => MemoryDirectory::from_config(self, prefix)?,
+                "internal" => InternalDirectory::from_config(
+                    self,
+                    prefix,
+                    store
+                        .ok_or_else(|| {
+                            format!("Directory {id:?} requires a store but none is available.")
+                        })?
+                        .clone(),
+                )?,
                 unknown => {
                     return Err(format!("Unknown directory type: {unknown:?}"));
                 }
             };
 
             // Add queries/filters as lookups
-            let is_directory = ["sql", "ldap"].contains(&protocol);
+            let is_directory = ["sql", "ldap", "internal"].contains(&protocol);
             if is_directory {
-                let name = if protocol == "sql" { "query" } else { "filter" };
+                let name = if protocol == "ldap" {
+                    "filter"
+                } else {
+                    "query"
+                };
                 for lookup_id in self.sub_keys(("directory", id, name)) {
                     config.lookups.insert(
                         format!("{id}/{lookup_id}"),
@@ -103,6 +136,12 @@ impl ConfigDirectory for Config {
             config.directories.insert(id.to_string(), directory);
         }
 
+        for id in oidc_ids {
+            let directory =
+                OidcDirectory::from_config(self, ("directory", id), &config.directories)?;
+            config.directories.insert(id.to_string(), directory);
+        }
+
         Ok(config)
     }
 
@@ -24,7 +24,7 @@
 use mail_send::Credentials;
 use smtp_proto::{AUTH_CRAM_MD5, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
 
-use crate::{Directory, DirectoryError, Principal};
+use crate::{Directory, DirectoryError, Principal, Protocol};
 
 use super::{ImapDirectory, ImapError};
 
@@ -33,6 +33,7 @@ impl Directory for ImapDirectory {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        _protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
         let mut client = self.pool.get().await?;
         let mechanism = match credentials {
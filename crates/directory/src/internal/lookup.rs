@@ -0,0 +1,247 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use mail_send::Credentials;
+use store::{write::assert::HashedValue, CustomValueKey, Deserialize};
+
+use crate::{
+    to_catch_all_address, unwrap_subaddress, Directory, DirectoryError, DirectoryWrite, Principal,
+    Protocol, Type,
+};
+
+use super::{
+    deserialize_principal, domain_counter, email_key, key_range, principal_key, EmailRecord,
+    EmailType, InternalDirectory, PrincipalRecord, DIR_EMAIL, DIR_PRINCIPAL, KEY_PREFIX_LEN,
+};
+
+#[async_trait::async_trait]
+impl Directory for InternalDirectory {
+    async fn authenticate(
+        &self,
+        credentials: &Credentials<String>,
+        protocol: Protocol,
+    ) -> crate::Result<Option<Principal>> {
+        let (username, secret) = match credentials {
+            Credentials::Plain { username, secret } => (username, secret),
+            Credentials::OAuthBearer { token } => (token, token),
+            Credentials::XOauth2 { username, secret } => (username, secret),
+        };
+        match self.principal(username).await? {
+            Some(principal) if principal.verify_secret(secret, protocol).await => {
+                Ok(Some(principal))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
+        Ok(self
+            .get_principal(name)
+            .await?
+            .map(|record| self.to_principal(name, record.principal)))
+    }
+
+    async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
+        Ok(self
+            .get_principal(name)
+            .await?
+            .map(|record| record.emails)
+            .unwrap_or_default())
+    }
+
+    async fn names_by_email(&self, address: &str) -> crate::Result<Vec<String>> {
+        let mut record = self
+            .get_email(unwrap_subaddress(address, self.opt.subaddressing).as_ref())
+            .await?;
+        if record.is_none() && self.opt.catch_all {
+            record = self.get_email(&to_catch_all_address(address)).await?;
+        }
+
+        let mut names = Vec::new();
+        for entry in record.map(|r| r.entries).unwrap_or_default() {
+            if !names.contains(&entry.name) {
+                names.push(entry.name);
+            }
+        }
+        Ok(names)
+    }
+
+    async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        Ok(self
+            .get_email(unwrap_subaddress(address, self.opt.subaddressing).as_ref())
+            .await?
+            .is_some()
+            || (self.opt.catch_all
+                && self
+                    .get_email(&to_catch_all_address(address))
+                    .await?
+                    .is_some()))
+    }
+
+    async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        let address = unwrap_subaddress(address, self.opt.subaddressing).into_owned();
+        let (from_key, to_key) = key_range(DIR_EMAIL);
+        self.store
+            .iterate(
+                Vec::new(),
+                from_key,
+                to_key,
+                false,
+                true,
+                move |result, key, value| {
+                    let email = std::str::from_utf8(key.get(KEY_PREFIX_LEN..).unwrap_or_default())
+                        .unwrap_or_default();
+                    if email.contains(&address)
+                        && EmailRecord::deserialize(value)?
+                            .entries
+                            .iter()
+                            .any(|e| e.typ == EmailType::Primary)
+                    {
+                        result.push(email.to_string());
+                    }
+                    Ok(true)
+                },
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
+        let mut result = Vec::new();
+        if let Some(record) = self
+            .get_email(unwrap_subaddress(address, self.opt.subaddressing).as_ref())
+            .await?
+        {
+            for entry in record.entries {
+                if entry.typ == EmailType::List {
+                    if let Some(email) = self
+                        .get_principal(&entry.name)
+                        .await?
+                        .and_then(|p| p.emails.into_iter().next())
+                    {
+                        result.push(email);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    async fn query(&self, query: &str, params: &[&str]) -> crate::Result<bool> {
+        let param = params.first().copied().unwrap_or_default();
+        match query {
+            "domains" => self.is_local_domain(param).await,
+            "recipients" => self.rcpt(param).await,
+            "names" => self.get_principal(param).await.map(|p| p.is_some()),
+            "groups" => self
+                .get_principal(param)
+                .await
+                .map(|p| p.map_or(false, |p| p.principal.typ == Type::Group)),
+            _ => Err(DirectoryError::unsupported("internal", query)),
+        }
+    }
+
+    fn writer(&self) -> Option<&dyn DirectoryWrite> {
+        Some(self)
+    }
+
+    async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
+        let domain = domain.to_lowercase();
+        Ok(self.store.get_counter(domain_counter(&domain)).await? > 0)
+    }
+}
+
+impl InternalDirectory {
+    pub(super) async fn get_principal(&self, name: &str) -> crate::Result<Option<PrincipalRecord>> {
+        self.store
+            .get_value::<PrincipalRecord>(CustomValueKey {
+                value: principal_key(name),
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    pub(super) async fn get_principal_hashed(
+        &self,
+        name: &str,
+    ) -> crate::Result<Option<HashedValue<PrincipalRecord>>> {
+        self.store
+            .get_value::<HashedValue<PrincipalRecord>>(CustomValueKey {
+                value: principal_key(name),
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    pub(super) async fn get_email(&self, email: &str) -> crate::Result<Option<EmailRecord>> {
+        self.store
+            .get_value::<EmailRecord>(CustomValueKey {
+                value: email_key(email),
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    // Members of the superuser group are reported as superusers
+    fn to_principal(&self, name: &str, mut principal: Principal) -> Principal {
+        principal.name = name.to_string();
+        if let Some(pos) = principal
+            .member_of
+            .iter()
+            .position(|group| group.eq_ignore_ascii_case(&self.opt.superuser_group))
+        {
+            principal.member_of.remove(pos);
+            principal.typ = Type::Superuser;
+        }
+        principal
+    }
+
+    pub(super) async fn principal_names(
+        &self,
+        filter: impl Fn(&PrincipalRecord) -> bool + Sync + Send + 'static,
+    ) -> crate::Result<Vec<String>> {
+        let (from_key, to_key) = key_range(DIR_PRINCIPAL);
+        self.store
+            .iterate(
+                Vec::new(),
+                from_key,
+                to_key,
+                false,
+                true,
+                move |names, key, value| {
+                    let record = deserialize_principal(&mut value.iter()).ok_or_else(|| {
+                        store::Error::InternalError("Failed to deserialize principal".to_string())
+                    })?;
+                    if filter(&record) {
+                        names.push(
+                            String::from_utf8_lossy(key.get(KEY_PREFIX_LEN..).unwrap_or_default())
+                                .into_owned(),
+                        );
+                    }
+                    Ok(true)
+                },
+            )
+            .await
+            .map_err(Into::into)
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{slice::Iter, sync::Arc};
+
+use store::{
+    write::{key::KeySerializer, DeserializeFrom, SerializeInto},
+    CounterKey, CustomValueKey, Deserialize, Store,
+};
+use utils::codec::leb128::{Leb128Iterator, Leb128Vec};
+
+use crate::{DirectoryOptions, Principal, Type};
+
+pub mod config;
+pub mod lookup;
+pub mod write;
+
+pub const DIRECTORY_COLLECTION: u8 = u8::MAX - 2;
+
+const DIR_PRINCIPAL: u8 = 5;
+const DIR_EMAIL: u8 = 6;
+const DIR_DOMAIN: u8 = 7;
+
+pub struct InternalDirectory {
+    store: Arc<Store>,
+    opt: DirectoryOptions,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct PrincipalRecord {
+    principal: Principal,
+    emails: Vec<String>,
+    lists: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct EmailRecord {
+    entries: Vec<EmailEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmailType {
+    Primary = 0,
+    Alias = 1,
+    List = 2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EmailEntry {
+    typ: EmailType,
+    name: String,
+}
+
+impl PrincipalRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(128);
+        buf.push(match self.principal.typ {
+            Type::Individual => 0,
+            Type::Group => 1,
+            Type::Resource => 2,
+            Type::Location => 3,
+            Type::Other => 4,
+            Type::Superuser => 5,
+        });
+        buf.push_leb128(self.principal.quota);
+        if let Some(description) = &self.principal.description {
+            buf.push(1);
+            description.serialize_into(&mut buf);
+        } else {
+            buf.push(0);
+        }
+        for list in [
+            &self.principal.secrets,
+            &self.principal.member_of,
+            &self.emails,
+            &self.lists,
+        ] {
+            buf.push_leb128(list.len());
+            for item in list {
+                item.serialize_into(&mut buf);
+            }
+        }
+        buf
+    }
+
+    // Returns all addresses owned by this principal along with their type
+    fn addresses(&self) -> impl Iterator<Item = (&str, EmailType)> {
+        self.emails
+            .iter()
+            .enumerate()
+            .map(|(pos, email)| {
+                (
+                    email.as_str(),
+                    if pos == 0 {
+                        EmailType::Primary
+                    } else {
+                        EmailType::Alias
+                    },
+                )
+            })
+            .chain(
+                self.lists
+                    .iter()
+                    .map(|list| (list.as_str(), EmailType::List)),
+            )
+    }
+}
+
+impl Deserialize for PrincipalRecord {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        deserialize_principal(&mut bytes.iter()).ok_or_else(|| {
+            store::Error::InternalError("Failed to deserialize principal".to_string())
+        })
+    }
+}
+
+fn deserialize_principal(bytes: &mut Iter<'_, u8>) -> Option<PrincipalRecord> {
+    let typ = match *bytes.next()? {
+        0 => Type::Individual,
+        1 => Type::Group,
+        2 => Type::Resource,
+        3 => Type::Location,
+        5 => Type::Superuser,
+        _ => Type::Other,
+    };
+    let quota = bytes.next_leb128()?;
+    let description = if *bytes.next()? == 1 {
+        Some(String::deserialize_from(bytes)?)
+    } else {
+        None
+    };
+    let mut lists: [Vec<String>; 4] = Default::default();
+    for list in lists.iter_mut() {
+        let len: usize = bytes.next_leb128()?;
+        for _ in 0..len {
+            list.push(String::deserialize_from(bytes)?);
+        }
+    }
+    let [secrets, member_of, emails, email_lists] = lists;
+
+    Some(PrincipalRecord {
+        principal: Principal {
+            name: String::new(),
+            secrets,
+            typ,
+            description,
+            quota,
+            member_of,
+        },
+        emails,
+        lists: email_lists,
+    })
+}
+
+impl EmailRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.entries.len() * 16);
+        for entry in &self.entries {
+            buf.push(entry.typ as u8);
+            entry.name.serialize_into(&mut buf);
+        }
+        buf
+    }
+
+    fn has_owner(&self) -> bool {
+        self.entries.iter().any(|e| e.typ != EmailType::List)
+    }
+}
+
+impl Deserialize for EmailRecord {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        let mut entries = Vec::new();
+        let mut bytes = bytes.iter();
+        while let Some(typ) = bytes.next() {
+            entries.push(EmailEntry {
+                typ: match typ {
+                    0 => EmailType::Primary,
+                    1 => EmailType::Alias,
+                    _ => EmailType::List,
+                },
+                name: String::deserialize_from(&mut bytes).ok_or_else(|| {
+                    store::Error::InternalError("Failed to deserialize e-mail entry".to_string())
+                })?,
+            });
+        }
+        Ok(EmailRecord { entries })
+    }
+}
+
+fn principal_key(name: &str) -> Vec<u8> {
+    directory_key(DIR_PRINCIPAL, name)
+}
+
+fn email_key(email: &str) -> Vec<u8> {
+    directory_key(DIR_EMAIL, email)
+}
+
+fn directory_key(typ: u8, value: &str) -> Vec<u8> {
+    KeySerializer::new(value.len() + KEY_PREFIX_LEN)
+        .write(u32::MAX)
+        .write(typ)
+        .write(value)
+        .finalize()
+}
+
+fn domain_counter(domain: &str) -> CounterKey<Vec<u8>> {
+    CounterKey {
+        account_id: u32::MAX,
+        collection: DIRECTORY_COLLECTION,
+        key: domain_key(domain),
+    }
+}
+
+fn domain_key(domain: &str) -> Vec<u8> {
+    KeySerializer::new(domain.len() + 1)
+        .write(DIR_DOMAIN)
+        .write(domain)
+        .finalize()
+}
+
+// Key range used to iterate over all principals or e-mail addresses
+fn key_range(typ: u8) -> (CustomValueKey, CustomValueKey) {
+    (
+        CustomValueKey {
+            value: KeySerializer::new(KEY_PREFIX_LEN)
+                .write(u32::MAX)
+                .write(typ)
+                .finalize(),
+        },
+        CustomValueKey {
+            value: KeySerializer::new(KEY_PREFIX_LEN + 1)
+                .write(u32::MAX)
+                .write(typ)
+                .write(u8::MAX)
+                .finalize(),
+        },
+    )
+}
+
+const KEY_PREFIX_LEN: usize = std::mem::size_of::<u32>() + 1;
@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use ahash::AHashMap;
+use store::{
+    write::{assert::HashedValue, BatchBuilder, Operation, ValueClass},
+    CustomValueKey,
+};
+
+use crate::{secret::hash_secrets, DirectoryError, DirectoryWrite, Principal, PrincipalUpdate};
+
+use super::{
+    domain_key, email_key, principal_key, EmailEntry, EmailRecord, EmailType, InternalDirectory,
+    PrincipalRecord, DIRECTORY_COLLECTION,
+};
+
+const MAX_WRITE_RETRIES: usize = 5;
+
+#[async_trait::async_trait]
+impl DirectoryWrite for InternalDirectory {
+    async fn create_principal(
+        &self,
+        mut principal: Principal,
+        emails: Vec<String>,
+    ) -> crate::Result<()> {
+        let name = std::mem::take(&mut principal.name);
+        if self.get_principal(&name).await?.is_some() {
+            return Err(DirectoryError::already_exists(&name));
+        }
+        self.validate_groups(&name, &principal.member_of).await?;
+        principal.secrets = hash_secrets(std::mem::take(&mut principal.secrets)).await?;
+
+        let mut record = PrincipalRecord {
+            principal,
+            emails: Vec::with_capacity(emails.len()),
+            lists: Vec::new(),
+        };
+        for email in emails {
+            let email = email.to_lowercase();
+            if !record.emails.contains(&email) {
+                record.emails.push(email);
+            }
+        }
+
+        if self.write_principal(&name, None, Some(record)).await? {
+            Ok(())
+        } else {
+            Err(DirectoryError::already_exists(&name))
+        }
+    }
+
+    async fn update_principal(
+        &self,
+        name: &str,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        // Hash secrets and validate groups once, before attempting to write
+        let mut updates = Vec::with_capacity(changes.len());
+        for change in changes {
+            updates.push(match change {
+                PrincipalUpdate::Secrets(secrets) => {
+                    PrincipalUpdate::Secrets(hash_secrets(secrets).await?)
+                }
+                PrincipalUpdate::AddMemberOf(group) => {
+                    self.validate_groups(name, std::slice::from_ref(&group))
+                        .await?;
+                    PrincipalUpdate::AddMemberOf(group)
+                }
+                change => change,
+            });
+        }
+
+        for _ in 0..MAX_WRITE_RETRIES {
+            let current = self
+                .get_principal_hashed(name)
+                .await?
+                .ok_or_else(|| DirectoryError::not_found(name))?;
+            let mut record = current.inner.clone();
+            for update in &updates {
+                record.apply(update);
+            }
+            if record == current.inner
+                || self
+                    .write_principal(name, Some(&current), Some(record))
+                    .await?
+            {
+                return Ok(());
+            }
+        }
+
+        Err(DirectoryError::Store(store::Error::AssertValueFailed))
+    }
+
+    async fn delete_principal(&self, name: &str) -> crate::Result<()> {
+        let mut deleted = false;
+        for _ in 0..MAX_WRITE_RETRIES {
+            let current = self
+                .get_principal_hashed(name)
+                .await?
+                .ok_or_else(|| DirectoryError::not_found(name))?;
+            if self.write_principal(name, Some(&current), None).await? {
+                deleted = true;
+                break;
+            }
+        }
+        if !deleted {
+            return Err(DirectoryError::Store(store::Error::AssertValueFailed));
+        }
+
+        // Remove group memberships
+        let group = name.to_string();
+        for member in self
+            .principal_names(move |record| record.principal.member_of.contains(&group))
+            .await?
+        {
+            match self
+                .update_principal(
+                    &member,
+                    vec![PrincipalUpdate::RemoveMemberOf(name.to_string())],
+                )
+                .await
+            {
+                Ok(_) | Err(DirectoryError::NotFound(_)) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list_principals(&self) -> crate::Result<Vec<String>> {
+        self.principal_names(|_| true).await
+    }
+}
+
+impl InternalDirectory {
+    // Writes a principal along with its e-mail addresses, returns false if
+    // the principal or any of its addresses were modified concurrently.
+    async fn write_principal(
+        &self,
+        name: &str,
+        current: Option<&HashedValue<PrincipalRecord>>,
+        new: Option<PrincipalRecord>,
+    ) -> crate::Result<bool> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(DIRECTORY_COLLECTION);
+        let principal_class = ValueClass::Custom {
+            bytes: principal_key(name),
+        };
+        if let Some(current) = current {
+            batch.assert_value(principal_class, current);
+        } else {
+            batch.assert_value(principal_class, ());
+        }
+
+        // Obtain the addresses that were added or removed
+        let old_addresses = current
+            .map(|current| current.inner.addresses().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let new_addresses = new
+            .as_ref()
+            .map(|new| new.addresses().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut changes: AHashMap<&str, (Vec<EmailType>, Vec<EmailType>)> = AHashMap::new();
+        for (address, typ) in &old_addresses {
+            if !new_addresses.contains(&(*address, *typ)) {
+                changes.entry(*address).or_default().0.push(*typ);
+                if let Some((_, domain)) = address.rsplit_once('@') {
+                    batch.counter(domain_key(domain), -1);
+                }
+            }
+        }
+        for (address, typ) in &new_addresses {
+            if !old_addresses.contains(&(*address, *typ)) {
+                changes.entry(*address).or_default().1.push(*typ);
+                if let Some((_, domain)) = address.rsplit_once('@') {
+                    batch.counter(domain_key(domain), 1);
+                }
+            }
+        }
+
+        // Update e-mail address mappings
+        for (address, (removed, added)) in changes {
+            let current_record = self
+                .store
+                .get_value::<HashedValue<EmailRecord>>(CustomValueKey {
+                    value: email_key(address),
+                })
+                .await?;
+            let mut record = current_record
+                .as_ref()
+                .map(|record| record.inner.clone())
+                .unwrap_or_default();
+            record
+                .entries
+                .retain(|entry| !(entry.name == name && removed.contains(&entry.typ)));
+            for typ in added {
+                if typ != EmailType::List && record.has_owner() {
+                    return Err(DirectoryError::already_exists(address));
+                }
+                record.entries.push(EmailEntry {
+                    typ,
+                    name: name.to_string(),
+                });
+            }
+
+            let class = ValueClass::Custom {
+                bytes: email_key(address),
+            };
+            if let Some(current_record) = &current_record {
+                batch.assert_value(class, current_record);
+            } else {
+                batch.assert_value(class, ());
+            }
+            batch.op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: email_key(address),
+                },
+                set: if !record.entries.is_empty() {
+                    Some(record.serialize())
+                } else {
+                    None
+                },
+            });
+        }
+
+        batch.op(Operation::Value {
+            class: ValueClass::Custom {
+                bytes: principal_key(name),
+            },
+            set: new.as_ref().map(|new| new.serialize()),
+        });
+
+        match self.store.write(batch.build()).await {
+            Ok(_) => Ok(true),
+            Err(store::Error::AssertValueFailed) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn validate_groups(&self, name: &str, groups: &[String]) -> crate::Result<()> {
+        for group in groups {
+            if group == name || self.get_principal(group).await?.is_none() {
+                return Err(DirectoryError::not_found(group));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PrincipalRecord {
+    fn apply(&mut self, update: &PrincipalUpdate) {
+        match update {
+            PrincipalUpdate::Secrets(secrets) => self.principal.secrets = secrets.clone(),
+            PrincipalUpdate::Description(description) => {
+                self.principal.description = description.clone()
+            }
+            PrincipalUpdate::Type(typ) => self.principal.typ = *typ,
+            PrincipalUpdate::Quota(quota) => self.principal.quota = *quota,
+            PrincipalUpdate::AddEmail(email) => {
+                let email = email.to_lowercase();
+                if !self.emails.contains(&email) {
+                    self.emails.push(email);
+                }
+            }
+            PrincipalUpdate::RemoveEmail(email) => {
+                let email = email.to_lowercase();
+                self.emails.retain(|e| e != &email);
+            }
+            PrincipalUpdate::AddList(email) => {
+                let email = email.to_lowercase();
+                if !self.lists.contains(&email) {
+                    self.lists.push(email);
+                }
+            }
+            PrincipalUpdate::RemoveList(email) => {
+                let email = email.to_lowercase();
+                self.lists.retain(|e| e != &email);
+            }
+            PrincipalUpdate::AddMemberOf(group) => {
+                if !self.principal.member_of.contains(group) {
+                    self.principal.member_of.push(group.clone());
+                }
+            }
+            PrincipalUpdate::RemoveMemberOf(group) => {
+                self.principal.member_of.retain(|g| g != group)
+            }
+        }
+    }
+}
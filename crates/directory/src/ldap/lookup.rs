@@ -24,7 +24,7 @@
 use ldap3::{ResultEntry, Scope, SearchEntry};
 use mail_send::Credentials;
 
-use crate::{to_catch_all_address, unwrap_subaddress, Directory, Principal, Type};
+use crate::{to_catch_all_address, unwrap_subaddress, Directory, Principal, Protocol, Type};
 
 use super::{LdapDirectory, LdapMappings};
 
@@ -33,6 +33,7 @@ impl Directory for LdapDirectory {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
         let (username, secret) = match credentials {
             Credentials::Plain { username, secret } => (username, secret),
@@ -44,7 +45,7 @@ impl Directory for LdapDirectory {
             .await
         {
             Ok(Some(principal)) => {
-                if principal.verify_secret(secret).await {
+                if principal.verify_secret(secret, protocol).await {
                     Ok(Some(principal))
                 } else {
                     Ok(None)
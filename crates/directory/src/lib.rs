@@ -29,14 +29,19 @@ use imap::ImapError;
 use ldap3::LdapError;
 use mail_send::Credentials;
 
+pub mod app_password;
 pub mod cache;
 pub mod config;
 pub mod imap;
+pub mod internal;
 pub mod ldap;
 pub mod memory;
+pub mod oidc;
+pub mod scram;
 pub mod secret;
 pub mod smtp;
 pub mod sql;
+pub mod totp;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Principal {
@@ -59,19 +64,38 @@ pub enum Type {
     Superuser,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Smtp,
+    Imap,
+    Jmap,
+    ManageSieve,
+    Http,
+    OAuth,
+}
+
 #[derive(Debug)]
 pub enum DirectoryError {
     Ldap(LdapError),
     Sql(sqlx::Error),
     Imap(ImapError),
     Smtp(mail_send::Error),
+    Store(store::Error),
+    Http(String),
+    Hash(String),
     TimedOut,
     Unsupported,
+    NotFound(String),
+    AlreadyExists(String),
 }
 
 #[async_trait::async_trait]
 pub trait Directory: Sync + Send {
-    async fn authenticate(&self, credentials: &Credentials<String>) -> Result<Option<Principal>>;
+    async fn authenticate(
+        &self,
+        credentials: &Credentials<String>,
+        protocol: Protocol,
+    ) -> Result<Option<Principal>>;
     async fn principal(&self, name: &str) -> Result<Option<Principal>>;
     async fn emails_by_name(&self, name: &str) -> Result<Vec<String>>;
     async fn names_by_email(&self, email: &str) -> Result<Vec<String>>;
@@ -84,6 +108,32 @@ pub trait Directory: Sync + Send {
     fn type_name(&self) -> &'static str {
         std::any::type_name::<Self>()
     }
+
+    fn writer(&self) -> Option<&dyn DirectoryWrite> {
+        None
+    }
+}
+
+#[async_trait::async_trait]
+pub trait DirectoryWrite: Sync + Send {
+    async fn create_principal(&self, principal: Principal, emails: Vec<String>) -> Result<()>;
+    async fn update_principal(&self, name: &str, changes: Vec<PrincipalUpdate>) -> Result<()>;
+    async fn delete_principal(&self, name: &str) -> Result<()>;
+    async fn list_principals(&self) -> Result<Vec<String>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrincipalUpdate {
+    Secrets(Vec<String>),
+    Description(Option<String>),
+    Type(Type),
+    Quota(u32),
+    AddEmail(String),
+    RemoveEmail(String),
+    AddList(String),
+    RemoveList(String),
+    AddMemberOf(String),
+    RemoveMemberOf(String),
 }
 
 #[derive(Clone)]
@@ -166,6 +216,48 @@ impl Type {
             Self::Other => "other",
         }
     }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "individual" | "person" | "user" => Some(Self::Individual),
+            "group" => Some(Self::Group),
+            "resource" => Some(Self::Resource),
+            "location" => Some(Self::Location),
+            "other" => Some(Self::Other),
+            "superuser" | "admin" => Some(Self::Superuser),
+            _ => None,
+        }
+    }
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Smtp => "smtp",
+            Self::Imap => "imap",
+            Self::Jmap => "jmap",
+            Self::ManageSieve => "sieve",
+            Self::Http => "http",
+            Self::OAuth => "oauth",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "smtp" | "submission" => Some(Self::Smtp),
+            "imap" => Some(Self::Imap),
+            "jmap" => Some(Self::Jmap),
+            "sieve" | "managesieve" => Some(Self::ManageSieve),
+            "http" => Some(Self::Http),
+            "oauth" => Some(Self::OAuth),
+            _ => None,
+        }
+    }
+
+    // Interactive logins require a second factor when TOTP is enabled
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Self::OAuth)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -266,6 +358,26 @@ impl From<mail_send::Error> for DirectoryError {
     }
 }
 
+impl From<store::Error> for DirectoryError {
+    fn from(error: store::Error) -> Self {
+        tracing::warn!(
+            context = "directory",
+            event = "error",
+            protocol = "internal",
+            reason = %error,
+            "Internal directory error"
+        );
+
+        DirectoryError::Store(error)
+    }
+}
+
+impl From<reqwest::Error> for DirectoryError {
+    fn from(error: reqwest::Error) -> Self {
+        DirectoryError::http(error.to_string())
+    }
+}
+
 impl DirectoryError {
     pub fn unsupported(protocol: &str, method: &str) -> Self {
         tracing::warn!(
@@ -278,6 +390,36 @@ impl DirectoryError {
         DirectoryError::Unsupported
     }
 
+    pub fn not_found(name: &str) -> Self {
+        DirectoryError::NotFound(name.to_string())
+    }
+
+    pub fn already_exists(name: &str) -> Self {
+        DirectoryError::AlreadyExists(name.to_string())
+    }
+
+    pub fn http(reason: String) -> Self {
+        tracing::warn!(
+            context = "directory",
+            event = "error",
+            protocol = "oidc",
+            reason = %reason,
+            "OpenID Connect provider error"
+        );
+        DirectoryError::Http(reason)
+    }
+
+    pub fn hash(reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        tracing::warn!(
+            context = "directory",
+            event = "error",
+            reason = %reason,
+            "Failed to hash secret"
+        );
+        DirectoryError::Hash(reason)
+    }
+
     pub fn timeout(protocol: &str) -> Self {
         tracing::warn!(
             context = "directory",
@@ -23,11 +23,12 @@
 
 use std::sync::Arc;
 
+use parking_lot::RwLock;
 use utils::config::{utils::AsKey, Config};
 
 use crate::{config::ConfigDirectory, Directory, DirectoryOptions, Principal, Type};
 
-use super::{EmailType, MemoryDirectory};
+use super::{EmailType, MemoryData, MemoryDirectory};
 
 impl MemoryDirectory {
     pub fn from_config(
@@ -35,10 +36,8 @@ impl MemoryDirectory {
         prefix: impl AsKey,
     ) -> utils::config::Result<Arc<dyn Directory>> {
         let prefix = prefix.as_key();
-        let mut directory = MemoryDirectory {
-            opt: DirectoryOptions::from_config(config, prefix.clone())?,
-            ..Default::default()
-        };
+        let opt = DirectoryOptions::from_config(config, prefix.clone())?;
+        let mut directory = MemoryData::default();
 
         for lookup_id in config.sub_keys((prefix.as_str(), "users")) {
             let name = config
@@ -48,7 +47,7 @@ impl MemoryDirectory {
             let mut member_of = Vec::new();
 
             for (_, group) in config.values((prefix.as_str(), "users", lookup_id, "member-of")) {
-                if !group.eq_ignore_ascii_case(&directory.opt.superuser_group) {
+                if !group.eq_ignore_ascii_case(&opt.superuser_group) {
                     member_of.push(group.to_string());
                 } else {
                     typ = Type::Superuser;
@@ -140,6 +139,9 @@ impl MemoryDirectory {
             .domains
             .extend(config.parse_lookup_list((&prefix, "lookup.domains"))?);
 
-        Ok(Arc::new(directory))
+        Ok(Arc::new(MemoryDirectory {
+            data: RwLock::new(directory),
+            opt,
+        }))
     }
 }
@@ -23,7 +23,10 @@
 
 use mail_send::Credentials;
 
-use crate::{to_catch_all_address, unwrap_subaddress, Directory, DirectoryError, Principal};
+use crate::{
+    to_catch_all_address, unwrap_subaddress, Directory, DirectoryError, DirectoryWrite, Principal,
+    Protocol,
+};
 
 use super::{EmailType, MemoryDirectory};
 
@@ -32,25 +35,29 @@ impl Directory for MemoryDirectory {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
         let (username, secret) = match credentials {
             Credentials::Plain { username, secret } => (username, secret),
             Credentials::OAuthBearer { token } => (token, token),
             Credentials::XOauth2 { username, secret } => (username, secret),
         };
-        match self.principals.get(username) {
-            Some(principal) if principal.verify_secret(secret).await => Ok(Some(principal.clone())),
+        let principal = self.data.read().principals.get(username).cloned();
+        match principal {
+            Some(principal) if principal.verify_secret(secret, protocol).await => {
+                Ok(Some(principal))
+            }
             _ => Ok(None),
         }
     }
 
     async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
-        Ok(self.principals.get(name).cloned())
+        Ok(self.data.read().principals.get(name).cloned())
     }
 
     async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
         let mut result = Vec::new();
-        if let Some(emails) = self.names_to_email.get(name) {
+        if let Some(emails) = self.data.read().names_to_email.get(name) {
             for email in emails {
                 match email {
                     EmailType::Primary(email) | EmailType::Alias(email) => {
@@ -65,12 +72,13 @@ impl Directory for MemoryDirectory {
     }
 
     async fn names_by_email(&self, address: &str) -> crate::Result<Vec<String>> {
-        Ok(self
+        let data = self.data.read();
+        Ok(data
             .emails_to_names
             .get(unwrap_subaddress(address, self.opt.subaddressing).as_ref())
             .or_else(|| {
                 if self.opt.catch_all {
-                    self.emails_to_names.get(&to_catch_all_address(address))
+                    data.emails_to_names.get(&to_catch_all_address(address))
                 } else {
                     None
                 }
@@ -89,16 +97,17 @@ impl Directory for MemoryDirectory {
     }
 
     async fn rcpt(&self, address: &str) -> crate::Result<bool> {
-        Ok(self
+        let data = self.data.read();
+        Ok(data
             .emails_to_names
             .contains_key(unwrap_subaddress(address, self.opt.subaddressing).as_ref())
-            || (self.opt.catch_all && self.domains.contains(&to_catch_all_address(address))))
+            || (self.opt.catch_all && data.domains.contains(&to_catch_all_address(address))))
     }
 
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
         let mut result = Vec::new();
         let address = unwrap_subaddress(address, self.opt.subaddressing);
-        for (key, value) in &self.emails_to_names {
+        for (key, value) in &self.data.read().emails_to_names {
             if key.contains(address.as_ref())
                 && value.iter().any(|t| matches!(t, EmailType::Primary(_)))
             {
@@ -111,11 +120,12 @@ impl Directory for MemoryDirectory {
     async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
         let mut result = Vec::new();
         let address = unwrap_subaddress(address, self.opt.subaddressing);
-        for (key, value) in &self.emails_to_names {
+        let data = self.data.read();
+        for (key, value) in &data.emails_to_names {
             if key == address.as_ref() {
                 for item in value {
                     if let EmailType::List(name) = item {
-                        for addr in self.names_to_email.get(name).unwrap() {
+                        for addr in data.names_to_email.get(name).into_iter().flatten() {
                             if let EmailType::Primary(addr) = addr {
                                 result.push(addr.clone())
                             }
@@ -131,7 +141,11 @@ impl Directory for MemoryDirectory {
         Err(DirectoryError::unsupported("memory", "query"))
     }
 
+    fn writer(&self) -> Option<&dyn DirectoryWrite> {
+        Some(self)
+    }
+
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
-        Ok(self.domains.contains(domain))
+        Ok(self.data.read().domains.contains(domain))
     }
 }
@@ -22,19 +22,26 @@
 */
 
 use ahash::{AHashMap, AHashSet};
+use parking_lot::RwLock;
 
 use crate::{DirectoryOptions, Principal};
 
 pub mod config;
 pub mod lookup;
+pub mod write;
 
 #[derive(Default)]
 pub struct MemoryDirectory {
+    data: RwLock<MemoryData>,
+    opt: DirectoryOptions,
+}
+
+#[derive(Default)]
+struct MemoryData {
     principals: AHashMap<String, Principal>,
     emails_to_names: AHashMap<String, Vec<EmailType>>,
     names_to_email: AHashMap<String, Vec<EmailType>>,
     domains: AHashSet<String>,
-    opt: DirectoryOptions,
 }
 
 enum EmailType {
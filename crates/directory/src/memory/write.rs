@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use ahash::AHashSet;
+
+use crate::{secret::hash_secrets, DirectoryError, DirectoryWrite, Principal, PrincipalUpdate};
+
+use super::{EmailType, MemoryData, MemoryDirectory};
+
+#[async_trait::async_trait]
+impl DirectoryWrite for MemoryDirectory {
+    async fn create_principal(
+        &self,
+        mut principal: Principal,
+        emails: Vec<String>,
+    ) -> crate::Result<()> {
+        principal.secrets = hash_secrets(std::mem::take(&mut principal.secrets)).await?;
+
+        let mut data = self.data.write();
+        if data.principals.contains_key(&principal.name) {
+            return Err(DirectoryError::already_exists(&principal.name));
+        }
+        for group in &principal.member_of {
+            if !data.principals.contains_key(group) {
+                return Err(DirectoryError::not_found(group));
+            }
+        }
+        let emails = emails
+            .into_iter()
+            .map(|email| email.to_lowercase())
+            .collect::<Vec<_>>();
+        for email in &emails {
+            if data.is_email_taken(email) {
+                return Err(DirectoryError::already_exists(email));
+            }
+        }
+
+        let name = principal.name.clone();
+        data.principals.insert(name.clone(), principal);
+        data.names_to_email.insert(name.clone(), Vec::new());
+        for email in emails {
+            data.add_email(&name, email);
+        }
+
+        Ok(())
+    }
+
+    async fn update_principal(
+        &self,
+        name: &str,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        // Secrets are hashed before the lock is taken
+        let mut updates = Vec::with_capacity(changes.len());
+        for change in changes {
+            updates.push(match change {
+                PrincipalUpdate::Secrets(secrets) => {
+                    PrincipalUpdate::Secrets(hash_secrets(secrets).await?)
+                }
+                PrincipalUpdate::AddEmail(email) => PrincipalUpdate::AddEmail(email.to_lowercase()),
+                change => change,
+            });
+        }
+
+        let mut data = self.data.write();
+        if !data.principals.contains_key(name) {
+            return Err(DirectoryError::not_found(name));
+        }
+
+        // Validate all changes before applying any of them
+        let mut added_emails = AHashSet::new();
+        for change in &updates {
+            match change {
+                PrincipalUpdate::AddEmail(email) => {
+                    if data.is_email_taken(email) || !added_emails.insert(email) {
+                        return Err(DirectoryError::already_exists(email));
+                    }
+                }
+                PrincipalUpdate::AddMemberOf(group) => {
+                    if !data.principals.contains_key(group) {
+                        return Err(DirectoryError::not_found(group));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for change in updates {
+            match change {
+                PrincipalUpdate::AddEmail(email) => {
+                    data.add_email(name, email);
+                }
+                PrincipalUpdate::RemoveEmail(email) => {
+                    data.remove_email(name, &email.to_lowercase());
+                }
+                PrincipalUpdate::AddList(email) => {
+                    let email = email.to_lowercase();
+                    if let Some((_, domain)) = email.rsplit_once('@') {
+                        data.domains.insert(domain.to_string());
+                    }
+                    data.emails_to_names
+                        .entry(email.clone())
+                        .or_default()
+                        .push(EmailType::List(name.to_string()));
+                    data.names_to_email
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(EmailType::List(email));
+                }
+                PrincipalUpdate::RemoveList(email) => {
+                    let email = email.to_lowercase();
+                    if let Some(emails) = data.names_to_email.get_mut(name) {
+                        emails.retain(|t| !matches!(t, EmailType::List(e) if e == &email));
+                    }
+                    if let Some(names) = data.emails_to_names.get_mut(&email) {
+                        names.retain(|t| !matches!(t, EmailType::List(n) if n == name));
+                        if names.is_empty() {
+                            data.emails_to_names.remove(&email);
+                        }
+                    }
+                }
+                PrincipalUpdate::AddMemberOf(group) => {
+                    let principal = data.principals.get_mut(name).unwrap();
+                    if !principal.member_of.contains(&group) {
+                        principal.member_of.push(group);
+                    }
+                }
+                change => {
+                    let principal = data.principals.get_mut(name).unwrap();
+                    match change {
+                        PrincipalUpdate::Secrets(secrets) => principal.secrets = secrets,
+                        PrincipalUpdate::Description(description) => {
+                            principal.description = description
+                        }
+                        PrincipalUpdate::Type(typ) => principal.typ = typ,
+                        PrincipalUpdate::Quota(quota) => principal.quota = quota,
+                        PrincipalUpdate::RemoveMemberOf(group) => {
+                            principal.member_of.retain(|g| g != &group)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_principal(&self, name: &str) -> crate::Result<()> {
+        let mut data = self.data.write();
+        if data.principals.remove(name).is_none() {
+            return Err(DirectoryError::not_found(name));
+        }
+
+        // Remove addresses and group memberships
+        for email in data.names_to_email.remove(name).unwrap_or_default() {
+            let (EmailType::Primary(email) | EmailType::Alias(email) | EmailType::List(email)) =
+                email;
+            data.unlink_email(name, &email);
+        }
+        for principal in data.principals.values_mut() {
+            principal.member_of.retain(|group| group != name);
+        }
+
+        Ok(())
+    }
+
+    async fn list_principals(&self) -> crate::Result<Vec<String>> {
+        let mut names = self
+            .data
+            .read()
+            .principals
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        Ok(names)
+    }
+}
+
+impl MemoryData {
+    fn is_email_taken(&self, email: &str) -> bool {
+        self.emails_to_names.get(email).map_or(false, |names| {
+            names
+                .iter()
+                .any(|t| matches!(t, EmailType::Primary(_) | EmailType::Alias(_)))
+        })
+    }
+
+    fn add_email(&mut self, name: &str, email: String) {
+        let emails = self.names_to_email.entry(name.to_string()).or_default();
+        let is_primary = !emails.iter().any(|t| matches!(t, EmailType::Primary(_)));
+        if let Some((_, domain)) = email.rsplit_once('@') {
+            self.domains.insert(domain.to_string());
+        }
+        self.emails_to_names
+            .entry(email.clone())
+            .or_default()
+            .push(if is_primary {
+                EmailType::Primary(name.to_string())
+            } else {
+                EmailType::Alias(name.to_string())
+            });
+        emails.push(if is_primary {
+            EmailType::Primary(email)
+        } else {
+            EmailType::Alias(email)
+        });
+    }
+
+    fn remove_email(&mut self, name: &str, email: &str) {
+        self.unlink_email(name, email);
+
+        let emails = self.names_to_email.entry(name.to_string()).or_default();
+        let was_primary = emails
+            .iter()
+            .any(|t| matches!(t, EmailType::Primary(e) if e == email));
+        emails.retain(|t| !matches!(t, EmailType::Primary(e) | EmailType::Alias(e) if e == email));
+
+        // Promote the first alias to primary address
+        if was_primary {
+            if let Some(pos) = emails.iter().position(|t| matches!(t, EmailType::Alias(_))) {
+                if let EmailType::Alias(alias) = emails.remove(pos) {
+                    for t in self.emails_to_names.entry(alias.clone()).or_default() {
+                        if matches!(t, EmailType::Alias(n) if n == name) {
+                            *t = EmailType::Primary(name.to_string());
+                        }
+                    }
+                    emails.insert(0, EmailType::Primary(alias));
+                }
+            }
+        }
+    }
+
+    fn unlink_email(&mut self, name: &str, email: &str) {
+        if let Some(names) = self.emails_to_names.get_mut(email) {
+            names.retain(|t| {
+                !matches!(t, EmailType::Primary(n) | EmailType::Alias(n) | EmailType::List(n) if n == name)
+            });
+            if names.is_empty() {
+                self.emails_to_names.remove(email);
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use ahash::AHashMap;
+use parking_lot::{Mutex, RwLock};
+use utils::config::{utils::AsKey, Config};
+
+use crate::{config::ConfigDirectory, Directory, DirectoryOptions};
+
+use super::{ClaimNames, OidcDirectory, Validation};
+
+impl OidcDirectory {
+    pub fn from_config(
+        config: &Config,
+        prefix: impl AsKey,
+        directories: &AHashMap<String, Arc<dyn Directory>>,
+    ) -> utils::config::Result<Arc<dyn Directory>> {
+        let prefix = prefix.as_key();
+        let validation = if let Some(url) = config.value((&prefix, "jwks.url")) {
+            Validation::Jwks {
+                url: url.to_string(),
+                ttl: config.property_or_static((&prefix, "jwks.cache-ttl"), "1h")?,
+                keys: RwLock::new(None),
+            }
+        } else if let Some(url) = config.value((&prefix, "introspect.url")) {
+            Validation::Introspect {
+                url: url.to_string(),
+                client_id: config
+                    .value((&prefix, "introspect.client-id"))
+                    .map(|v| v.to_string()),
+                client_secret: config
+                    .value((&prefix, "introspect.client-secret"))
+                    .map(|v| v.to_string()),
+            }
+        } else {
+            return Err(format!(
+                "Missing 'jwks.url' or 'introspect.url' for OpenID Connect directory {prefix:?}."
+            ));
+        };
+        let backend = if let Some(backend) = config.value((&prefix, "backend")) {
+            directories
+                .get(backend)
+                .ok_or_else(|| {
+                    format!("Backend directory {backend:?} not found for directory {prefix:?}.")
+                })?
+                .clone()
+                .into()
+        } else {
+            None
+        };
+
+        Ok(Arc::new(OidcDirectory {
+            issuer: config.value_require((&prefix, "issuer"))?.to_string(),
+            audiences: config
+                .values((&prefix, "audience"))
+                .map(|(_, v)| v.to_string())
+                .collect(),
+            validation,
+            claims: ClaimNames {
+                username: config
+                    .value((&prefix, "claims.username"))
+                    .unwrap_or("preferred_username")
+                    .to_string(),
+                email: config
+                    .value((&prefix, "claims.email"))
+                    .unwrap_or("email")
+                    .to_string(),
+                email_verified: config
+                    .value((&prefix, "claims.email-verified"))
+                    .unwrap_or("email_verified")
+                    .to_string(),
+                name: config
+                    .value((&prefix, "claims.name"))
+                    .unwrap_or("name")
+                    .to_string(),
+                groups: config
+                    .value((&prefix, "claims.groups"))
+                    .unwrap_or("groups")
+                    .to_string(),
+            },
+            leeway: config
+                .property_or_static::<Duration>((&prefix, "leeway"), "60s")?
+                .as_secs(),
+            client: reqwest::Client::builder()
+                .timeout(config.property_or_static((&prefix, "timeout"), "15s")?)
+                .danger_accept_invalid_certs(
+                    config.property_or_static((&prefix, "tls.allow-invalid-certs"), "false")?,
+                )
+                .build()
+                .map_err(|err| format!("Failed to build HTTP client for {prefix:?}: {err}"))?,
+            backend,
+            domains: config
+                .parse_lookup_list((&prefix, "lookup.domains"))?
+                .into_iter()
+                .map(|domain| domain.to_lowercase())
+                .collect(),
+            principals: Mutex::new(lru_cache::LruCache::with_hasher(
+                config.property_or_static((&prefix, "principal-cache.entries"), "1024")?,
+                ahash::RandomState::new(),
+            )),
+            principals_ttl: config.property_or_static((&prefix, "principal-cache.ttl"), "1d")?,
+            opt: DirectoryOptions::from_config(config, prefix)?,
+        }))
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use mail_parser::decoders::base64::base64_decode;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    #[serde(rename = "use")]
+    pub use_: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+    pub crv: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JwtHeader {
+    pub alg: String,
+    pub kid: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Jwt<'x> {
+    pub header: JwtHeader,
+    pub claims: Map<String, Value>,
+    pub signed_part: &'x str,
+    pub signature: Vec<u8>,
+}
+
+impl<'x> Jwt<'x> {
+    pub fn parse(token: &'x str) -> Option<Self> {
+        let (signed_part, signature) = token.rsplit_once('.')?;
+        let (header, claims) = signed_part.split_once('.')?;
+
+        Some(Jwt {
+            header: serde_json::from_slice(&base64url_decode(header)?).ok()?,
+            claims: serde_json::from_slice(&base64url_decode(claims)?).ok()?,
+            signed_part,
+            signature: base64url_decode(signature)?,
+        })
+    }
+
+    pub fn verify(&self, keys: &[Jwk]) -> bool {
+        keys.iter()
+            .filter(|key| match (&self.header.kid, &key.kid) {
+                (Some(kid), Some(key_kid)) => kid == key_kid,
+                _ => true,
+            })
+            .any(|key| {
+                key.verify(
+                    &self.header.alg,
+                    self.signed_part.as_bytes(),
+                    &self.signature,
+                )
+            })
+    }
+
+    pub fn has_key(&self, keys: &[Jwk]) -> bool {
+        match &self.header.kid {
+            Some(kid) => keys.iter().any(|key| key.kid.as_ref() == Some(kid)),
+            None => !keys.is_empty(),
+        }
+    }
+}
+
+impl Jwk {
+    pub fn verify(&self, alg: &str, message: &[u8], signature: &[u8]) -> bool {
+        if self.use_.as_ref().map_or(false, |use_| use_ != "sig")
+            || self.alg.as_ref().map_or(false, |key_alg| key_alg != alg)
+        {
+            return false;
+        }
+
+        match (alg, self.kty.as_str()) {
+            ("RS256" | "RS384" | "RS512", "RSA") => {
+                let key = match (
+                    self.n.as_deref().and_then(base64url_decode),
+                    self.e.as_deref().and_then(base64url_decode),
+                ) {
+                    (Some(n), Some(e)) => {
+                        match RsaPublicKey::new(
+                            BigUint::from_bytes_be(&n),
+                            BigUint::from_bytes_be(&e),
+                        ) {
+                            Ok(key) => key,
+                            Err(_) => return false,
+                        }
+                    }
+                    _ => return false,
+                };
+                match alg {
+                    "RS256" => key.verify(
+                        Pkcs1v15Sign::new::<Sha256>(),
+                        &Sha256::digest(message),
+                        signature,
+                    ),
+                    "RS384" => key.verify(
+                        Pkcs1v15Sign::new::<Sha384>(),
+                        &Sha384::digest(message),
+                        signature,
+                    ),
+                    _ => key.verify(
+                        Pkcs1v15Sign::new::<Sha512>(),
+                        &Sha512::digest(message),
+                        signature,
+                    ),
+                }
+                .is_ok()
+            }
+            ("ES256", "EC") if self.crv.as_deref() == Some("P-256") => {
+                match (
+                    self.x.as_deref().and_then(base64url_decode),
+                    self.y.as_deref().and_then(base64url_decode),
+                ) {
+                    (Some(x), Some(y)) if x.len() == 32 && y.len() == 32 => {
+                        let point = p256::EncodedPoint::from_affine_coordinates(
+                            x.as_slice().into(),
+                            y.as_slice().into(),
+                            false,
+                        );
+                        match (
+                            VerifyingKey::from_encoded_point(&point),
+                            Signature::from_slice(signature),
+                        ) {
+                            (Ok(key), Ok(signature)) => key.verify(message, &signature).is_ok(),
+                            _ => false,
+                        }
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn base64url_decode(value: &str) -> Option<Vec<u8>> {
+    let mut value = value.replace('-', "+").replace('_', "/");
+    while value.len() % 4 != 0 {
+        value.push('=');
+    }
+    base64_decode(value.as_bytes())
+}
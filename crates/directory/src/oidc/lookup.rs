@@ -0,0 +1,363 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use mail_send::Credentials;
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+
+use crate::{unwrap_subaddress, Directory, DirectoryError, Principal, Protocol};
+
+use super::{
+    jwt::{Jwk, JwkSet, Jwt},
+    OidcDirectory, Validation,
+};
+
+// Minimum time between forced JWKS refreshes caused by unknown key ids
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+#[async_trait::async_trait]
+impl Directory for OidcDirectory {
+    async fn authenticate(
+        &self,
+        credentials: &Credentials<String>,
+        protocol: Protocol,
+    ) -> crate::Result<Option<Principal>> {
+        let (token, username) = match credentials {
+            Credentials::OAuthBearer { token } => (bearer_token(token), None),
+            Credentials::XOauth2 { username, secret } => (secret.as_str(), Some(username)),
+            Credentials::Plain { .. } => {
+                return if let Some(backend) = &self.backend {
+                    backend.authenticate(credentials, protocol).await
+                } else {
+                    Ok(None)
+                };
+            }
+        };
+
+        let claims = if let Some(claims) = self.validate_token(token).await? {
+            claims
+        } else {
+            return Ok(None);
+        };
+        let mut cached = if let Some(cached) = self.principal_from_claims(&claims) {
+            cached
+        } else {
+            tracing::debug!(
+                context = "directory",
+                event = "invalid",
+                protocol = "oidc",
+                claim = self.claims.username.as_str(),
+                "Token does not contain a username claim."
+            );
+            return Ok(None);
+        };
+
+        // Addresses on domains that are not local cannot be claimed by the provider
+        let mut emails = Vec::with_capacity(cached.emails.len());
+        for email in std::mem::take(&mut cached.emails) {
+            let domain = email.rsplit_once('@').map_or("", |(_, domain)| domain);
+            if !domain.is_empty() && self.is_local_domain(domain).await? {
+                emails.push(email);
+            } else {
+                tracing::debug!(
+                    context = "directory",
+                    event = "invalid",
+                    protocol = "oidc",
+                    email = email.as_str(),
+                    "Ignoring email address on a non-local domain."
+                );
+            }
+        }
+        cached.emails = emails;
+
+        // XOAUTH2 includes the username, make sure it matches the token
+        if let Some(username) = username.filter(|username| !username.is_empty()) {
+            if !username.eq_ignore_ascii_case(&cached.principal.name)
+                && !cached
+                    .emails
+                    .iter()
+                    .any(|email| email.eq_ignore_ascii_case(username))
+            {
+                tracing::debug!(
+                    context = "directory",
+                    event = "invalid",
+                    protocol = "oidc",
+                    username = username.as_str(),
+                    subject = cached.principal.name.as_str(),
+                    "Username does not match the token subject."
+                );
+                return Ok(None);
+            }
+        }
+
+        let principal = if let Some(backend) = &self.backend {
+            backend.principal(&cached.principal.name).await?
+        } else {
+            None
+        }
+        .unwrap_or_else(|| cached.principal.clone());
+        self.cache_principal(cached);
+
+        Ok(Some(principal))
+    }
+
+    async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
+        if let Some(backend) = &self.backend {
+            if let Some(principal) = backend.principal(name).await? {
+                return Ok(Some(principal));
+            }
+        }
+        Ok(self.cached_principal(name).map(|c| c.principal.clone()))
+    }
+
+    async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
+        if let Some(backend) = &self.backend {
+            let emails = backend.emails_by_name(name).await?;
+            if !emails.is_empty() {
+                return Ok(emails);
+            }
+        }
+        Ok(self
+            .cached_principal(name)
+            .map(|c| c.emails.clone())
+            .unwrap_or_default())
+    }
+
+    async fn names_by_email(&self, address: &str) -> crate::Result<Vec<String>> {
+        if let Some(backend) = &self.backend {
+            let names = backend.names_by_email(address).await?;
+            if !names.is_empty() {
+                return Ok(names);
+            }
+        }
+        Ok(self.cached_names_by_email(
+            unwrap_subaddress(&address.to_lowercase(), self.opt.subaddressing).as_ref(),
+        ))
+    }
+
+    async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        if let Some(backend) = &self.backend {
+            if backend.rcpt(address).await? {
+                return Ok(true);
+            }
+        }
+        Ok(!self
+            .cached_names_by_email(
+                unwrap_subaddress(&address.to_lowercase(), self.opt.subaddressing).as_ref(),
+            )
+            .is_empty())
+    }
+
+    async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        if let Some(backend) = &self.backend {
+            backend.vrfy(address).await
+        } else {
+            Err(DirectoryError::unsupported("oidc", "vrfy"))
+        }
+    }
+
+    async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
+        if let Some(backend) = &self.backend {
+            backend.expn(address).await
+        } else {
+            Err(DirectoryError::unsupported("oidc", "expn"))
+        }
+    }
+
+    async fn query(&self, query: &str, params: &[&str]) -> crate::Result<bool> {
+        if let Some(backend) = &self.backend {
+            backend.query(query, params).await
+        } else {
+            Err(DirectoryError::unsupported("oidc", "query"))
+        }
+    }
+
+    async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
+        if let Some(backend) = &self.backend {
+            if backend.is_local_domain(domain).await? {
+                return Ok(true);
+            }
+        }
+        Ok(self.domains.contains(&domain.to_lowercase()))
+    }
+}
+
+impl OidcDirectory {
+    // Returns the claims of a valid token issued by the configured provider
+    async fn validate_token(&self, token: &str) -> crate::Result<Option<Map<String, Value>>> {
+        let (claims, is_introspected) = match &self.validation {
+            Validation::Jwks { url, ttl, keys } => {
+                let jwt = if let Some(jwt) = Jwt::parse(token) {
+                    jwt
+                } else {
+                    tracing::debug!(
+                        context = "directory",
+                        event = "invalid",
+                        protocol = "oidc",
+                        "Failed to parse JWT."
+                    );
+                    return Ok(None);
+                };
+
+                // Fetch the key set again when the signing key is unknown, the provider might
+                // have rotated its keys
+                let mut key_set = self.fetch_jwks(url, *ttl, keys, false).await?;
+                if !jwt.has_key(&key_set) {
+                    key_set = self.fetch_jwks(url, *ttl, keys, true).await?;
+                }
+                if !jwt.verify(&key_set) {
+                    tracing::debug!(
+                        context = "directory",
+                        event = "invalid",
+                        protocol = "oidc",
+                        alg = jwt.header.alg.as_str(),
+                        "Failed to verify JWT signature."
+                    );
+                    return Ok(None);
+                }
+                (jwt.claims, false)
+            }
+            Validation::Introspect {
+                url,
+                client_id,
+                client_secret,
+            } => {
+                let mut request = self
+                    .client
+                    .post(url)
+                    .form(&[("token", token), ("token_type_hint", "access_token")]);
+                if let Some(client_id) = client_id {
+                    request = request.basic_auth(client_id, client_secret.as_ref());
+                }
+                let response = request.send().await?.error_for_status()?.bytes().await?;
+                let claims = serde_json::from_slice::<Map<String, Value>>(&response)
+                    .map_err(|err| DirectoryError::http(format!("Invalid response: {err}")))?;
+                if !claims
+                    .get("active")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false)
+                {
+                    tracing::debug!(
+                        context = "directory",
+                        event = "invalid",
+                        protocol = "oidc",
+                        "Token is not active."
+                    );
+                    return Ok(None);
+                }
+                (claims, true)
+            }
+        };
+
+        // Validate registered claims, introspection responses might omit some of them
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let issuer = claims.get("iss").and_then(|v| v.as_str());
+        let expires = claims.get("exp").and_then(|v| v.as_u64());
+        let not_before = claims.get("nbf").and_then(|v| v.as_u64());
+        let reason = if issuer.map_or(!is_introspected, |issuer| issuer != self.issuer) {
+            "Invalid issuer."
+        } else if expires.map_or(!is_introspected, |exp| exp + self.leeway < now) {
+            "Token has expired."
+        } else if not_before.map_or(false, |nbf| nbf > now + self.leeway) {
+            "Token is not yet valid."
+        } else if !self.audiences.is_empty()
+            && !match claims.get("aud") {
+                Some(Value::String(aud)) => self.audiences.contains(aud),
+                Some(Value::Array(auds)) => auds
+                    .iter()
+                    .filter_map(|aud| aud.as_str())
+                    .any(|aud| self.audiences.iter().any(|a| a == aud)),
+                _ => false,
+            }
+        {
+            "Invalid audience."
+        } else {
+            return Ok(Some(claims));
+        };
+
+        tracing::debug!(
+            context = "directory",
+            event = "invalid",
+            protocol = "oidc",
+            reason = reason,
+            "Token validation failed."
+        );
+
+        Ok(None)
+    }
+
+    async fn fetch_jwks(
+        &self,
+        url: &str,
+        ttl: Duration,
+        keys: &RwLock<Option<(Instant, Arc<Vec<Jwk>>)>>,
+        refresh: bool,
+    ) -> crate::Result<Arc<Vec<Jwk>>> {
+        if let Some((fetched_at, keys)) = keys.read().as_ref() {
+            let elapsed = fetched_at.elapsed();
+            if elapsed < ttl && (!refresh || elapsed < JWKS_MIN_REFRESH) {
+                return Ok(keys.clone());
+            }
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let key_set = Arc::new(
+            serde_json::from_slice::<JwkSet>(&response)
+                .map_err(|err| DirectoryError::http(format!("Invalid key set: {err}")))?
+                .keys,
+        );
+        *keys.write() = Some((Instant::now(), key_set.clone()));
+
+        Ok(key_set)
+    }
+}
+
+// Obtains the bearer token from an OAUTHBEARER (RFC 7628) client response
+fn bearer_token(response: &str) -> &str {
+    response
+        .split('\x01')
+        .find_map(|part| {
+            part.strip_prefix("auth=").map(|value| {
+                value
+                    .split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                    .map_or(value, |(_, token)| token)
+            })
+        })
+        .unwrap_or(response)
+        .trim()
+}
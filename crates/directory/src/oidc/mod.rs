@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ahash::AHashSet;
+use parking_lot::{Mutex, RwLock};
+use serde_json::{Map, Value};
+
+use crate::{Directory, DirectoryOptions, Principal, Type};
+
+use self::jwt::Jwk;
+
+pub mod config;
+pub mod jwt;
+pub mod lookup;
+
+pub struct OidcDirectory {
+    issuer: String,
+    audiences: Vec<String>,
+    validation: Validation,
+    claims: ClaimNames,
+    leeway: u64,
+    client: reqwest::Client,
+    backend: Option<Arc<dyn Directory>>,
+    domains: AHashSet<String>,
+    principals: Mutex<lru_cache::LruCache<String, Arc<CachedPrincipal>, ahash::RandomState>>,
+    principals_ttl: Duration,
+    opt: DirectoryOptions,
+}
+
+enum Validation {
+    Jwks {
+        url: String,
+        ttl: Duration,
+        keys: RwLock<Option<(Instant, Arc<Vec<Jwk>>)>>,
+    },
+    Introspect {
+        url: String,
+        client_id: Option<String>,
+        client_secret: Option<String>,
+    },
+}
+
+struct ClaimNames {
+    username: String,
+    email: String,
+    email_verified: String,
+    name: String,
+    groups: String,
+}
+
+struct CachedPrincipal {
+    principal: Principal,
+    emails: Vec<String>,
+    valid_until: Instant,
+}
+
+impl OidcDirectory {
+    // Builds a principal from the claims of a validated token
+    fn principal_from_claims(&self, claims: &Map<String, Value>) -> Option<CachedPrincipal> {
+        let name = claims
+            .get(&self.claims.username)
+            .and_then(|v| v.as_str())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())?;
+        let mut typ = Type::Individual;
+        let mut member_of = Vec::new();
+        if let Some(groups) = claims.get(&self.claims.groups).and_then(|v| v.as_array()) {
+            for group in groups.iter().filter_map(|v| v.as_str()) {
+                // Keycloak returns group paths (i.e. "/sales")
+                let group = group.trim_start_matches('/');
+                if group.eq_ignore_ascii_case(&self.opt.superuser_group) {
+                    typ = Type::Superuser;
+                } else if !group.is_empty() {
+                    member_of.push(group.to_string());
+                }
+            }
+        }
+
+        Some(CachedPrincipal {
+            principal: Principal {
+                name: name.to_string(),
+                typ,
+                description: claims
+                    .get(&self.claims.name)
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string()),
+                member_of,
+                ..Default::default()
+            },
+            emails: claims
+                .get(&self.claims.email)
+                .and_then(|v| v.as_str())
+                .filter(|_| {
+                    // Providers that allow users to change their address do not
+                    // vouch for unverified ones
+                    claims
+                        .get(&self.claims.email_verified)
+                        .map_or(false, |v| v.as_bool() == Some(true))
+                })
+                .map(|v| vec![v.trim().to_lowercase()])
+                .unwrap_or_default(),
+            valid_until: Instant::now() + self.principals_ttl,
+        })
+    }
+
+    fn cache_principal(&self, principal: CachedPrincipal) {
+        self.principals
+            .lock()
+            .insert(principal.principal.name.clone(), Arc::new(principal));
+    }
+
+    fn cached_principal(&self, name: &str) -> Option<Arc<CachedPrincipal>> {
+        let mut principals = self.principals.lock();
+        let principal = principals.get_mut(name)?.clone();
+        if principal.valid_until >= Instant::now() {
+            Some(principal)
+        } else {
+            principals.remove(name);
+            None
+        }
+    }
+
+    fn cached_names_by_email(&self, email: &str) -> Vec<String> {
+        let now = Instant::now();
+        self.principals
+            .lock()
+            .iter()
+            .filter(|(_, p)| p.valid_until >= now && p.emails.iter().any(|e| e == email))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,394 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use hmac::{Hmac, Mac};
+use mail_builder::encoders::base64::base64_encode;
+use mail_parser::decoders::base64::base64_decode;
+use sha2::{Digest, Sha256};
+
+use crate::{totp::Totp, Directory, Principal};
+
+const SCRAM_PREFIX: &str = "SCRAM-SHA-256$";
+const SCRAM_ITERATIONS: u32 = 4096;
+const CHANNEL_BINDING: &str = "p=tls-server-end-point";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramSecret {
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+pub struct ScramServer {
+    state: State,
+    is_plus: bool,
+    channel_binding: Option<Vec<u8>>,
+}
+
+pub enum ScramResult {
+    Continue(Vec<u8>),
+    Success {
+        principal: Principal,
+        server_final: Vec<u8>,
+    },
+    Failure(&'static str),
+}
+
+enum State {
+    ClientFirst,
+    ClientFinal {
+        principal: Option<Principal>,
+        secret: ScramSecret,
+        gs2_header: String,
+        nonce: String,
+        auth_message: String,
+    },
+    Done,
+}
+
+impl ScramSecret {
+    pub fn generate(secret: &str) -> Self {
+        Self::derive(
+            secret,
+            rand::random::<[u8; 16]>().to_vec(),
+            SCRAM_ITERATIONS,
+        )
+    }
+
+    fn derive(secret: &str, salt: Vec<u8>, iterations: u32) -> Self {
+        let mut salted_password = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), &salt, iterations, &mut salted_password);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+
+        ScramSecret {
+            iterations,
+            salt,
+            stored_key: Sha256::digest(client_key).to_vec(),
+            server_key: hmac_sha256(&salted_password, b"Server Key"),
+        }
+    }
+
+    // Parses secrets in the RFC 5803 format:
+    // SCRAM-SHA-256$<iterations>:<salt>$<stored key>:<server key>
+    pub fn parse(value: &str) -> Option<Self> {
+        let (params, keys) = value.strip_prefix(SCRAM_PREFIX)?.split_once('$')?;
+        let (iterations, salt) = params.split_once(':')?;
+        let (stored_key, server_key) = keys.split_once(':')?;
+
+        Some(ScramSecret {
+            iterations: iterations.parse().ok()?,
+            salt: base64_decode(salt.as_bytes())?,
+            stored_key: base64_decode(stored_key.as_bytes())?,
+            server_key: base64_decode(server_key.as_bytes())?,
+        })
+    }
+
+    pub fn is_scram_secret(value: &str) -> bool {
+        value.starts_with(SCRAM_PREFIX)
+    }
+
+    pub fn verify(&self, secret: &str) -> bool {
+        Self::derive(secret, self.salt.clone(), self.iterations).stored_key == self.stored_key
+    }
+
+    // Random credentials used to avoid disclosing whether an account exists
+    fn unknown() -> Self {
+        ScramSecret {
+            iterations: SCRAM_ITERATIONS,
+            salt: rand::random::<[u8; 16]>().to_vec(),
+            stored_key: rand::random::<[u8; 32]>().to_vec(),
+            server_key: rand::random::<[u8; 32]>().to_vec(),
+        }
+    }
+}
+
+impl Display for ScramSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}:{}${}:{}",
+            SCRAM_PREFIX,
+            self.iterations,
+            encode(&self.salt),
+            encode(&self.stored_key),
+            encode(&self.server_key)
+        )
+    }
+}
+
+impl Principal {
+    // Returns the stored SCRAM credentials, or derives them from a plain-text secret.
+    // Principals with 2FA enabled can only authenticate using application passwords.
+    pub fn scram_secret(&self) -> Option<ScramSecret> {
+        if self
+            .secrets
+            .iter()
+            .any(|secret| Totp::is_totp_secret(secret))
+        {
+            return None;
+        }
+
+        self.secrets
+            .iter()
+            .find_map(|secret| ScramSecret::parse(secret))
+            .or_else(|| {
+                self.secrets
+                    .iter()
+                    .find(|secret| {
+                        !secret.starts_with(['$', '{', '_'])
+                            && !ScramSecret::is_scram_secret(secret)
+                    })
+                    .map(|secret| ScramSecret::generate(secret))
+            })
+    }
+}
+
+impl ScramServer {
+    pub fn new(is_plus: bool, channel_binding: Option<Vec<u8>>) -> Self {
+        ScramServer {
+            state: State::ClientFirst,
+            is_plus,
+            channel_binding,
+        }
+    }
+
+    pub fn is_new(&self) -> bool {
+        matches!(self.state, State::ClientFirst)
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    pub async fn next(
+        &mut self,
+        directory: &dyn Directory,
+        message: &[u8],
+    ) -> crate::Result<ScramResult> {
+        let message = if let Ok(message) = std::str::from_utf8(message) {
+            message
+        } else {
+            return Ok(self.fail("Invalid SCRAM message."));
+        };
+
+        match std::mem::replace(&mut self.state, State::Done) {
+            State::ClientFirst => self.client_first(directory, message).await,
+            State::ClientFinal {
+                principal,
+                secret,
+                gs2_header,
+                nonce,
+                auth_message,
+            } => Ok(self.client_final(message, principal, secret, gs2_header, nonce, auth_message)),
+            State::Done => Ok(ScramResult::Failure("SCRAM exchange already completed.")),
+        }
+    }
+
+    async fn client_first(
+        &mut self,
+        directory: &dyn Directory,
+        message: &str,
+    ) -> crate::Result<ScramResult> {
+        // Parse GS2 header
+        let mut parts = message.splitn(3, ',');
+        let (cbind_flag, authzid, client_first_bare) =
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(cbind_flag), Some(authzid), Some(client_first_bare)) => {
+                    (cbind_flag, authzid, client_first_bare)
+                }
+                _ => return Ok(self.fail("Invalid SCRAM client-first message.")),
+            };
+        let gs2_header = &message[..message.len() - client_first_bare.len()];
+        match cbind_flag {
+            "n" if !self.is_plus => (),
+            "y" if !self.is_plus && self.channel_binding.is_none() => (),
+            CHANNEL_BINDING if self.is_plus && self.channel_binding.is_some() => (),
+            _ => return Ok(self.fail("Channel binding mismatch.")),
+        }
+
+        // Parse username and nonce
+        let mut username = None;
+        let mut client_nonce = None;
+        for attribute in client_first_bare.split(',') {
+            match attribute.split_once('=') {
+                Some(("n", value)) => username = decode_saslname(value),
+                Some(("r", value)) if !value.is_empty() => client_nonce = Some(value),
+                Some(("m", _)) => return Ok(self.fail("Unsupported SCRAM extension.")),
+                _ => (),
+            }
+        }
+        let (username, client_nonce) = match (username, client_nonce) {
+            (Some(username), Some(client_nonce)) if !username.is_empty() => {
+                (username, client_nonce)
+            }
+            _ => return Ok(self.fail("Invalid SCRAM client-first message.")),
+        };
+        if let Some(authzid) = authzid.strip_prefix("a=") {
+            if decode_saslname(authzid).map_or(true, |authzid| authzid != username) {
+                return Ok(self.fail("Authorization identity not supported."));
+            }
+        } else if !authzid.is_empty() {
+            return Ok(self.fail("Invalid SCRAM client-first message."));
+        }
+
+        // Obtain credentials
+        let principal = directory.principal(&username).await?;
+        let (principal, secret) = match principal
+            .as_ref()
+            .and_then(|principal| principal.scram_secret())
+        {
+            Some(secret) => (principal, secret),
+            None => (None, ScramSecret::unknown()),
+        };
+
+        let nonce = format!("{}{}", client_nonce, encode(&rand::random::<[u8; 18]>()));
+        let server_first = format!(
+            "r={},s={},i={}",
+            nonce,
+            encode(&secret.salt),
+            secret.iterations
+        );
+        let auth_message = format!("{client_first_bare},{server_first}");
+        self.state = State::ClientFinal {
+            principal,
+            secret,
+            gs2_header: gs2_header.to_string(),
+            nonce,
+            auth_message,
+        };
+
+        Ok(ScramResult::Continue(server_first.into_bytes()))
+    }
+
+    fn client_final(
+        &mut self,
+        message: &str,
+        principal: Option<Principal>,
+        secret: ScramSecret,
+        gs2_header: String,
+        nonce: String,
+        auth_message: String,
+    ) -> ScramResult {
+        let (client_final_without_proof, proof) = match message
+            .rsplit_once(",p=")
+            .and_then(|(message, proof)| Some((message, base64_decode(proof.as_bytes())?)))
+        {
+            Some(result) => result,
+            None => return self.fail("Invalid SCRAM client-final message."),
+        };
+
+        // Validate channel binding and nonce
+        let mut expected_binding = gs2_header.into_bytes();
+        if expected_binding.starts_with(CHANNEL_BINDING.as_bytes()) {
+            expected_binding.extend_from_slice(self.channel_binding.as_deref().unwrap_or_default());
+        }
+        let mut has_binding = false;
+        let mut has_nonce = false;
+        for attribute in client_final_without_proof.split(',') {
+            match attribute.split_once('=') {
+                Some(("c", value)) => {
+                    has_binding = base64_decode(value.as_bytes())
+                        .map_or(false, |binding| binding == expected_binding);
+                }
+                Some(("r", value)) => {
+                    has_nonce = value == nonce;
+                }
+                _ => (),
+            }
+        }
+        if !has_binding {
+            return self.fail("Channel binding mismatch.");
+        } else if !has_nonce {
+            return self.fail("Nonce mismatch.");
+        }
+
+        // Verify client proof
+        let auth_message = format!("{auth_message},{client_final_without_proof}");
+        let client_signature = hmac_sha256(&secret.stored_key, auth_message.as_bytes());
+        if proof.len() != client_signature.len() {
+            return self.fail("Invalid client proof.");
+        }
+        let client_key = proof
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        let is_valid = constant_time_eq(Sha256::digest(client_key).as_slice(), &secret.stored_key);
+        match principal {
+            Some(principal) if is_valid => ScramResult::Success {
+                principal,
+                server_final: format!(
+                    "v={}",
+                    encode(&hmac_sha256(&secret.server_key, auth_message.as_bytes()))
+                )
+                .into_bytes(),
+            },
+            _ => self.fail("Authentication failed."),
+        }
+    }
+
+    fn fail(&mut self, reason: &'static str) -> ScramResult {
+        self.state = State::Done;
+        ScramResult::Failure(reason)
+    }
+}
+
+fn decode_saslname(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '=' {
+            match (chars.next(), chars.next()) {
+                (Some('2'), Some('C')) => result.push(','),
+                (Some('3'), Some('D')) => result.push('='),
+                _ => return None,
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    Some(result)
+}
+
+// Compares without short-circuiting so the timing does not reveal the matching prefix
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && std::hint::black_box(
+            a.iter()
+                .zip(b.iter())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b)),
+        ) == 0
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
@@ -24,7 +24,7 @@
 use argon2::Argon2;
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
-use password_hash::PasswordHash;
+use password_hash::{PasswordHash, PasswordHasher, SaltString};
 use pbkdf2::Pbkdf2;
 use pwhash::{bcrypt, bsdi_crypt, md5_crypt, sha1_crypt, sha256_crypt, sha512_crypt, unix_crypt};
 use scrypt::Scrypt;
@@ -34,17 +34,140 @@ use sha2::Sha256;
 use sha2::Sha512;
 use tokio::sync::oneshot;
 
-use crate::Principal;
+use crate::{
+    app_password::AppPassword, scram::ScramSecret, totp::Totp, DirectoryError, Principal, Protocol,
+};
 
 impl Principal {
-    pub async fn verify_secret(&self, secret: &str) -> bool {
+    pub async fn verify_secret(&self, secret: &str, protocol: Protocol) -> bool {
+        // Application passwords are accepted regardless of whether 2FA is enabled
+        for app_password in self.secrets.iter().filter_map(|s| AppPassword::parse(s)) {
+            if app_password.is_allowed(protocol)
+                && verify_secret_hash(&app_password.hash, secret).await
+            {
+                return true;
+            }
+        }
+
+        match self.totp() {
+            Some(totp) if protocol.is_interactive() => {
+                // Interactive logins append the TOTP code to the password
+                match secret
+                    .rsplit_once('$')
+                    .and_then(|(secret, code)| Some((secret, totp.verify_step(code)?)))
+                {
+                    Some((secret, step)) => {
+                        self.verify_primary_secret(secret).await
+                            && Totp::accept_step(&self.name, step)
+                    }
+                    None => false,
+                }
+            }
+            Some(_) => false,
+            None => self.verify_primary_secret(secret).await,
+        }
+    }
+
+    async fn verify_primary_secret(&self, secret: &str) -> bool {
         for hashed_secret in &self.secrets {
-            if verify_secret_hash(hashed_secret, secret).await {
+            if !AppPassword::is_app_password(hashed_secret)
+                && !Totp::is_totp_secret(hashed_secret)
+                && verify_secret_hash(hashed_secret, secret).await
+            {
                 return true;
             }
         }
         false
     }
+
+    pub fn totp(&self) -> Option<Totp> {
+        self.secrets.iter().find_map(|secret| Totp::parse(secret))
+    }
+
+    pub fn app_passwords(&self) -> impl Iterator<Item = AppPassword> + '_ {
+        self.secrets
+            .iter()
+            .filter_map(|secret| AppPassword::parse(secret))
+    }
+}
+
+// Hashes a plain-text secret using Argon2, secrets that are already hashed are returned as is
+pub async fn hash_secret(secret: String) -> crate::Result<String> {
+    if is_hashed_secret(&secret) {
+        return Ok(secret);
+    }
+
+    let (tx, rx) = oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+            .expect("Salt length is within bounds");
+        tx.send(
+            Argon2::default()
+                .hash_password(secret.as_bytes(), &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|err| DirectoryError::hash(err.to_string())),
+        )
+        .ok();
+    });
+
+    rx.await
+        .unwrap_or_else(|_| Err(DirectoryError::hash("Hashing task was cancelled")))
+}
+
+// Plain-text secrets are stored as an Argon2 hash along with SCRAM-SHA-256 keys
+pub(crate) async fn hash_secrets(secrets: Vec<String>) -> crate::Result<Vec<String>> {
+    let mut hashed = Vec::with_capacity(secrets.len() * 2);
+    for secret in secrets {
+        let hash = hash_secret(secret.clone()).await?;
+        if hash != secret {
+            hashed.push(hash);
+            hashed.push(ScramSecret::generate(&secret).to_string());
+        } else {
+            hashed.push(hash);
+        }
+    }
+    Ok(hashed)
+}
+
+// Returns whether a secret uses one of the hash formats accepted by verify_secret_hash,
+// anything else is a plain-text secret even if it looks like a hash
+fn is_hashed_secret(secret: &str) -> bool {
+    if AppPassword::is_app_password(secret)
+        || ScramSecret::is_scram_secret(secret)
+        || Totp::is_totp_secret(secret)
+    {
+        true
+    } else if secret.starts_with('$') {
+        is_hash_prefix(secret)
+    } else if let Some((algo, hash)) = secret
+        .strip_prefix('{')
+        .and_then(|secret| secret.split_once('}'))
+    {
+        match algo {
+            "ARGON2" | "ARGON2I" | "ARGON2ID" | "PBKDF2" => is_hash_prefix(hash),
+            "CRYPT" | "crypt" => !hash.starts_with('$') || is_hash_prefix(hash),
+            "SHA" | "SSHA" | "SHA256" | "SSHA256" | "SHA512" | "SSHA512" | "MD5" | "PLAIN"
+            | "plain" | "CLEAR" | "clear" => true,
+            _ => false,
+        }
+    } else {
+        false
+    }
+}
+
+fn is_hash_prefix(secret: &str) -> bool {
+    if secret.starts_with("$argon2")
+        || secret.starts_with("$pbkdf2")
+        || secret.starts_with("$scrypt")
+    {
+        PasswordHash::new(secret).is_ok()
+    } else {
+        [
+            "$2a$", "$2b$", "$2x$", "$2y$", "$6$", "$5$", "$sha1$", "$1$",
+        ]
+        .iter()
+        .any(|prefix| secret.starts_with(prefix))
+    }
 }
 
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
@@ -112,6 +235,9 @@ async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
 async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
     if hashed_secret.starts_with('$') {
         verify_hash_prefix(hashed_secret, secret).await
+    } else if ScramSecret::is_scram_secret(hashed_secret) {
+        // SCRAM-SHA-256 stored keys
+        ScramSecret::parse(hashed_secret).map_or(false, |scram| scram.verify(secret))
     } else if hashed_secret.starts_with('_') {
         // Enhanced DES-based hash
         bsdi_crypt::verify(secret, hashed_secret)
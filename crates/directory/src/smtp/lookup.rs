@@ -24,7 +24,7 @@
 use mail_send::{smtp::AssertReply, Credentials};
 use smtp_proto::Severity;
 
-use crate::{Directory, DirectoryError, Principal};
+use crate::{Directory, DirectoryError, Principal, Protocol};
 
 use super::{SmtpClient, SmtpDirectory};
 
@@ -33,6 +33,7 @@ impl Directory for SmtpDirectory {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        _protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
         self.pool.get().await?.authenticate(credentials).await
     }
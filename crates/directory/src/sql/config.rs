@@ -102,6 +102,50 @@ impl SqlDirectory {
                 .value((&prefix, "columns.type"))
                 .unwrap_or_default()
                 .to_string(),
+            write_list: config
+                .value((&prefix, "write.list"))
+                .unwrap_or_default()
+                .to_string(),
+            write_insert_principal: config
+                .value((&prefix, "write.insert-principal"))
+                .unwrap_or_default()
+                .to_string(),
+            write_update_principal: config
+                .value((&prefix, "write.update-principal"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_principal: config
+                .value((&prefix, "write.delete-principal"))
+                .unwrap_or_default()
+                .to_string(),
+            write_insert_email: config
+                .value((&prefix, "write.insert-email"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_email: config
+                .value((&prefix, "write.delete-email"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_emails: config
+                .value((&prefix, "write.delete-emails"))
+                .unwrap_or_default()
+                .to_string(),
+            write_insert_member: config
+                .value((&prefix, "write.insert-member"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_member: config
+                .value((&prefix, "write.delete-member"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_members: config
+                .value((&prefix, "write.delete-members"))
+                .unwrap_or_default()
+                .to_string(),
+            write_delete_group_members: config
+                .value((&prefix, "write.delete-group-members"))
+                .unwrap_or_default()
+                .to_string(),
         };
 
         CachedDirectory::try_from_config(
@@ -24,7 +24,9 @@
 use mail_send::Credentials;
 use sqlx::{any::AnyRow, Column, Row};
 
-use crate::{to_catch_all_address, unwrap_subaddress, Directory, Principal, Type};
+use crate::{
+    to_catch_all_address, unwrap_subaddress, Directory, DirectoryWrite, Principal, Protocol, Type,
+};
 
 use super::{SqlDirectory, SqlMappings};
 
@@ -33,6 +35,7 @@ impl Directory for SqlDirectory {
     async fn authenticate(
         &self,
         credentials: &Credentials<String>,
+        protocol: Protocol,
     ) -> crate::Result<Option<Principal>> {
         let (username, secret) = match credentials {
             Credentials::Plain { username, secret } => (username, secret),
@@ -41,7 +44,9 @@ impl Directory for SqlDirectory {
         };
 
         match self.principal(username).await {
-            Ok(Some(principal)) if principal.verify_secret(secret).await => Ok(Some(principal)),
+            Ok(Some(principal)) if principal.verify_secret(secret, protocol).await => {
+                Ok(Some(principal))
+            }
             Ok(_) => Ok(None),
             Err(err) => Err(err),
         }
@@ -159,6 +164,14 @@ impl Directory for SqlDirectory {
             .map(|id| id.is_some())
             .map_err(Into::into)
     }
+
+    fn writer(&self) -> Option<&dyn DirectoryWrite> {
+        if !self.mappings.write_insert_principal.is_empty() {
+            Some(self)
+        } else {
+            None
+        }
+    }
 }
 
 impl SqlMappings {
@@ -171,8 +184,13 @@ impl SqlMappings {
             if name.eq_ignore_ascii_case(&self.column_name) {
                 principal.name = row.try_get::<String, _>(idx)?;
             } else if name.eq_ignore_ascii_case(&self.column_secret) {
-                if let Ok(secret) = row.try_get::<String, _>(idx) {
-                    principal.secrets.push(secret);
+                if let Ok(secrets) = row.try_get::<String, _>(idx) {
+                    principal.secrets.extend(
+                        secrets
+                            .lines()
+                            .filter(|secret| !secret.is_empty())
+                            .map(String::from),
+                    );
                 }
             } else if name.eq_ignore_ascii_case(&self.column_type) {
                 match row.try_get::<String, _>(idx)?.as_str() {
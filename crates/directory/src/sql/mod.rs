@@ -27,6 +27,7 @@ use crate::DirectoryOptions;
 
 pub mod config;
 pub mod lookup;
+pub mod write;
 
 pub struct SqlDirectory {
     pool: Pool<Any>,
@@ -48,4 +49,15 @@ pub(crate) struct SqlMappings {
     column_secret: String,
     column_quota: String,
     column_type: String,
+    write_list: String,
+    write_insert_principal: String,
+    write_update_principal: String,
+    write_delete_principal: String,
+    write_insert_email: String,
+    write_delete_email: String,
+    write_delete_emails: String,
+    write_insert_member: String,
+    write_delete_member: String,
+    write_delete_members: String,
+    write_delete_group_members: String,
 }
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use sqlx::{Any, Transaction};
+
+use crate::{
+    secret::hash_secrets, Directory, DirectoryError, DirectoryWrite, Principal, PrincipalUpdate,
+    Type,
+};
+
+use super::SqlDirectory;
+
+#[async_trait::async_trait]
+impl DirectoryWrite for SqlDirectory {
+    async fn create_principal(
+        &self,
+        mut principal: Principal,
+        emails: Vec<String>,
+    ) -> crate::Result<()> {
+        if self.principal(&principal.name).await?.is_some() {
+            return Err(DirectoryError::already_exists(&principal.name));
+        }
+        for group in &principal.member_of {
+            self.validate_group(group).await?;
+        }
+        let emails = emails
+            .into_iter()
+            .map(|email| email.to_lowercase())
+            .collect::<Vec<_>>();
+        for email in &emails {
+            self.validate_email(email).await?;
+        }
+        principal.secrets = hash_secrets(std::mem::take(&mut principal.secrets)).await?;
+
+        let mut trx = self.pool.begin().await?;
+        sqlx::query(&self.mappings.write_insert_principal)
+            .bind(&principal.name)
+            .bind(principal.typ.to_jmap())
+            .bind(join_secrets(&principal.secrets))
+            .bind(&principal.description)
+            .bind(principal.quota as i64)
+            .execute(&mut *trx)
+            .await?;
+        for (pos, email) in emails.iter().enumerate() {
+            sqlx::query(&self.mappings.write_insert_email)
+                .bind(&principal.name)
+                .bind(email)
+                .bind(if pos == 0 { "primary" } else { "alias" })
+                .execute(&mut *trx)
+                .await?;
+        }
+        for group in &principal.member_of {
+            self.insert_member(&mut trx, &principal.name, group).await?;
+        }
+        if principal.typ == Type::Superuser {
+            self.insert_member(&mut trx, &principal.name, &self.opt.superuser_group)
+                .await?;
+        }
+        trx.commit().await.map_err(Into::into)
+    }
+
+    async fn update_principal(
+        &self,
+        name: &str,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        let mut principal = self
+            .principal(name)
+            .await?
+            .ok_or_else(|| DirectoryError::not_found(name))?;
+        let mut has_primary = !self.emails_by_name(name).await?.is_empty();
+        let was_superuser = principal.typ == Type::Superuser;
+        let mut update_principal = false;
+
+        // Hash secrets and validate changes before opening the transaction
+        let mut updates = Vec::with_capacity(changes.len());
+        for change in changes {
+            updates.push(match change {
+                PrincipalUpdate::Secrets(secrets) => {
+                    PrincipalUpdate::Secrets(hash_secrets(secrets).await?)
+                }
+                PrincipalUpdate::AddEmail(email) => {
+                    let email = email.to_lowercase();
+                    self.validate_email(&email).await?;
+                    PrincipalUpdate::AddEmail(email)
+                }
+                PrincipalUpdate::AddMemberOf(group) => {
+                    self.validate_group(&group).await?;
+                    PrincipalUpdate::AddMemberOf(group)
+                }
+                change => change,
+            });
+        }
+
+        let mut trx = self.pool.begin().await?;
+        for change in updates {
+            match change {
+                PrincipalUpdate::Secrets(secrets) => {
+                    principal.secrets = secrets;
+                    update_principal = true;
+                }
+                PrincipalUpdate::Description(description) => {
+                    principal.description = description;
+                    update_principal = true;
+                }
+                PrincipalUpdate::Type(typ) => {
+                    principal.typ = typ;
+                    update_principal = true;
+                }
+                PrincipalUpdate::Quota(quota) => {
+                    principal.quota = quota;
+                    update_principal = true;
+                }
+                PrincipalUpdate::AddEmail(email) => {
+                    sqlx::query(&self.mappings.write_insert_email)
+                        .bind(name)
+                        .bind(email)
+                        .bind(if has_primary { "alias" } else { "primary" })
+                        .execute(&mut *trx)
+                        .await?;
+                    has_primary = true;
+                }
+                PrincipalUpdate::RemoveEmail(email) | PrincipalUpdate::RemoveList(email) => {
+                    sqlx::query(&self.mappings.write_delete_email)
+                        .bind(name)
+                        .bind(email.to_lowercase())
+                        .execute(&mut *trx)
+                        .await?;
+                }
+                PrincipalUpdate::AddList(email) => {
+                    sqlx::query(&self.mappings.write_insert_email)
+                        .bind(name)
+                        .bind(email.to_lowercase())
+                        .bind("list")
+                        .execute(&mut *trx)
+                        .await?;
+                }
+                PrincipalUpdate::AddMemberOf(group) => {
+                    if !principal.member_of.contains(&group) {
+                        self.insert_member(&mut trx, name, &group).await?;
+                        principal.member_of.push(group);
+                    }
+                }
+                PrincipalUpdate::RemoveMemberOf(group) => {
+                    if let Some(pos) = principal.member_of.iter().position(|g| g == &group) {
+                        self.delete_member(&mut trx, name, &group).await?;
+                        principal.member_of.swap_remove(pos);
+                    }
+                }
+            }
+        }
+
+        if update_principal {
+            sqlx::query(&self.mappings.write_update_principal)
+                .bind(principal.typ.to_jmap())
+                .bind(join_secrets(&principal.secrets))
+                .bind(&principal.description)
+                .bind(principal.quota as i64)
+                .bind(name)
+                .execute(&mut *trx)
+                .await?;
+
+            // Superusers are members of the superuser group
+            match (was_superuser, principal.typ == Type::Superuser) {
+                (false, true) => {
+                    self.insert_member(&mut trx, name, &self.opt.superuser_group)
+                        .await?;
+                }
+                (true, false) => {
+                    self.delete_member(&mut trx, name, &self.opt.superuser_group)
+                        .await?;
+                }
+                _ => (),
+            }
+        }
+
+        trx.commit().await.map_err(Into::into)
+    }
+
+    async fn delete_principal(&self, name: &str) -> crate::Result<()> {
+        let mut trx = self.pool.begin().await?;
+        if sqlx::query(&self.mappings.write_delete_principal)
+            .bind(name)
+            .execute(&mut *trx)
+            .await?
+            .rows_affected()
+            == 0
+        {
+            return Err(DirectoryError::not_found(name));
+        }
+        for query in [
+            &self.mappings.write_delete_emails,
+            &self.mappings.write_delete_members,
+            &self.mappings.write_delete_group_members,
+        ] {
+            if !query.is_empty() {
+                sqlx::query(query).bind(name).execute(&mut *trx).await?;
+            }
+        }
+        trx.commit().await.map_err(Into::into)
+    }
+
+    async fn list_principals(&self) -> crate::Result<Vec<String>> {
+        sqlx::query_scalar::<_, String>(&self.mappings.write_list)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+impl SqlDirectory {
+    async fn validate_group(&self, group: &str) -> crate::Result<()> {
+        if self.principal(group).await?.is_some() {
+            Ok(())
+        } else {
+            Err(DirectoryError::not_found(group))
+        }
+    }
+
+    async fn validate_email(&self, email: &str) -> crate::Result<()> {
+        // Catch-all and subaddress expansion do not apply here, only exact matches
+        if sqlx::query_scalar::<_, String>(&self.mappings.query_recipients)
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?
+            .is_none()
+        {
+            Ok(())
+        } else {
+            Err(DirectoryError::already_exists(email))
+        }
+    }
+
+    async fn insert_member(
+        &self,
+        trx: &mut Transaction<'_, Any>,
+        name: &str,
+        group: &str,
+    ) -> crate::Result<()> {
+        sqlx::query(&self.mappings.write_insert_member)
+            .bind(name)
+            .bind(group)
+            .execute(&mut **trx)
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    async fn delete_member(
+        &self,
+        trx: &mut Transaction<'_, Any>,
+        name: &str,
+        group: &str,
+    ) -> crate::Result<()> {
+        sqlx::query(&self.mappings.write_delete_member)
+            .bind(name)
+            .bind(group)
+            .execute(&mut **trx)
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+}
+
+// Secrets share a single column, one per line
+fn join_secrets(secrets: &[String]) -> Option<String> {
+    if !secrets.is_empty() {
+        Some(secrets.join("\n"))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,254 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    fmt::Display,
+    sync::OnceLock,
+    time::{Duration, SystemTime},
+};
+
+use ahash::AHashMap;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use parking_lot::Mutex;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+const TOTP_PREFIX: &str = "otpauth://totp/";
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Number of time steps before and after the current one that are accepted
+const SKEW: i64 = 1;
+
+// Last time step accepted for each principal, codes cannot be used twice
+static LAST_STEPS: OnceLock<Mutex<AHashMap<String, u64>>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totp {
+    pub label: String,
+    pub issuer: Option<String>,
+    pub secret: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    pub period: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Totp {
+    pub fn generate(label: &str, issuer: Option<&str>) -> Self {
+        Totp {
+            label: label.to_string(),
+            issuer: issuer.map(|issuer| issuer.to_string()),
+            secret: rand::random::<[u8; 20]>().to_vec(),
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            period: 30,
+        }
+    }
+
+    // Parses a Key URI, as used by Google Authenticator and most other clients:
+    // otpauth://totp/<label>?secret=<base32>&issuer=<issuer>&algorithm=SHA1&digits=6&period=30
+    pub fn parse(value: &str) -> Option<Self> {
+        let uri = value.strip_prefix(TOTP_PREFIX)?;
+        let (label, params) = uri.split_once('?').unwrap_or((uri, ""));
+        let mut totp = Totp {
+            label: label.to_string(),
+            issuer: None,
+            secret: vec![],
+            algorithm: Algorithm::Sha1,
+            digits: 6,
+            period: 30,
+        };
+
+        for param in params.split('&') {
+            match param.split_once('=')? {
+                ("secret", value) => totp.secret = base32_decode(value)?,
+                ("issuer", value) => totp.issuer = value.to_string().into(),
+                ("algorithm", value) => {
+                    totp.algorithm = match value.to_ascii_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => return None,
+                    }
+                }
+                ("digits", value) => {
+                    totp.digits = value.parse().ok().filter(|d| (6..=8).contains(d))?
+                }
+                ("period", value) => totp.period = value.parse().ok().filter(|p| *p > 0)?,
+                _ => (),
+            }
+        }
+
+        if !totp.secret.is_empty() {
+            Some(totp)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_totp_secret(value: &str) -> bool {
+        value.starts_with(TOTP_PREFIX)
+    }
+
+    pub fn verify(&self, code: &str) -> bool {
+        self.verify_step(code).is_some()
+    }
+
+    // Returns the time step of a valid code
+    pub fn verify_step(&self, code: &str) -> Option<u64> {
+        if code.len() != self.digits as usize || !code.chars().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+
+        let step = (now() / self.period) as i64;
+        (-SKEW..=SKEW)
+            .map(|offset| (step + offset) as u64)
+            .find(|step| self.generate_code(*step) == code)
+    }
+
+    // Records the time step of a code used by a principal, returns false if a code
+    // for the same or a later step was already accepted
+    pub fn accept_step(principal: &str, step: u64) -> bool {
+        let mut last_steps = LAST_STEPS.get_or_init(Default::default).lock();
+        match last_steps.get_mut(principal) {
+            Some(last_step) if *last_step >= step => false,
+            Some(last_step) => {
+                *last_step = step;
+                true
+            }
+            None => {
+                last_steps.insert(principal.to_string(), step);
+                true
+            }
+        }
+    }
+
+    pub fn current_code(&self) -> String {
+        self.code_at(now())
+    }
+
+    pub fn code_at(&self, timestamp: u64) -> String {
+        self.generate_code(timestamp / self.period)
+    }
+
+    // RFC 6238 code generation with dynamic truncation from RFC 4226
+    fn generate_code(&self, step: u64) -> String {
+        let counter = step.to_be_bytes();
+        let hash = match self.algorithm {
+            Algorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(&self.secret, &counter),
+            Algorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(&self.secret, &counter),
+            Algorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(&self.secret, &counter),
+        };
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let code = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]) % 10u32.pow(self.digits);
+
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+}
+
+impl Display for Totp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}?secret={}",
+            TOTP_PREFIX,
+            self.label,
+            base32_encode(&self.secret)
+        )?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={issuer}")?;
+        }
+        write!(
+            f,
+            "&algorithm={}&digits={}&period={}",
+            match self.algorithm {
+                Algorithm::Sha1 => "SHA1",
+                Algorithm::Sha256 => "SHA256",
+                Algorithm::Sha512 => "SHA512",
+            },
+            self.digits,
+            self.period
+        )
+    }
+}
+
+fn hmac_digest<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() * 8 + 4) / 5);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buf = (buf << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buf >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buf << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+fn base32_decode(value: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(value.len() * 5 / 8);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for ch in value.bytes() {
+        let ch = ch.to_ascii_uppercase();
+        if ch == b'=' {
+            break;
+        }
+        buf = (buf << 5) | BASE32_ALPHABET.iter().position(|&b| b == ch)? as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buf >> bits) as u8);
+        }
+    }
+    Some(result)
+}
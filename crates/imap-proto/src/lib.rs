@@ -89,6 +89,21 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 9208
+    GetQuota,
+    GetQuotaRoot,
+    SetQuota,
+
+    // RFC 4978
+    Compress,
+
+    // RFC 5464
+    GetMetadata,
+    SetMetadata,
+
+    // RFC 5465
+    Notify,
 }
 
 impl Command {
@@ -167,6 +182,22 @@ pub enum ResponseCode {
 
     // USEATTR
     UseAttr,
+
+    // COMPRESS
+    CompressionActive,
+
+    // METADATA
+    MetadataLongEntries {
+        size: usize,
+    },
+    MetadataMaxSize {
+        size: usize,
+    },
+    MetadataTooMany,
+    MetadataNoPrivate,
+
+    // NOTIFY
+    BadEvent,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
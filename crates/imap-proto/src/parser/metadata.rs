@@ -0,0 +1,295 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    protocol::{
+        metadata::{self, Depth, Entry},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+use super::parse_number;
+
+/*
+
+   getmetadata     = "GETMETADATA" [SP getmetadata-options]
+                     SP mailbox SP entries
+
+   getmetadata-options = "(" getmetadata-option
+                         *(SP getmetadata-option) ")"
+
+   getmetadata-option  = "MAXSIZE" SP number / "DEPTH" SP ("0" / "1" / "infinity")
+
+   entries         = entry / "(" entry *(SP entry) ")"
+
+   setmetadata     = "SETMETADATA" SP mailbox SP "(" entry-value *(SP entry-value) ")"
+
+   entry-value     = entry SP value
+
+   value           = nstring / literal8
+
+*/
+
+impl Request<Command> {
+    pub fn parse_metadata(self, version: ProtocolVersion) -> crate::Result<metadata::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+        let mut max_size = None;
+        let mut depth = Depth::Zero;
+
+        // Parse options
+        if self.command == Command::GetMetadata
+            && tokens
+                .peek()
+                .map_or(false, |token| token.is_parenthesis_open())
+        {
+            tokens.next();
+            loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"MAXSIZE") => {
+                        max_size = parse_number::<usize>(
+                            &tokens
+                                .next()
+                                .ok_or((self.tag.as_str(), "Missing MAXSIZE value."))?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into();
+                    }
+                    Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"DEPTH") => {
+                        depth = match tokens
+                            .next()
+                            .ok_or((self.tag.as_str(), "Missing DEPTH value."))?
+                            .unwrap_bytes()
+                            .as_slice()
+                        {
+                            b"0" => Depth::Zero,
+                            b"1" => Depth::One,
+                            value if value.eq_ignore_ascii_case(b"infinity") => Depth::Infinity,
+                            _ => {
+                                return Err((self.tag.as_str(), "Invalid DEPTH value.").into());
+                            }
+                        };
+                    }
+                    _ => {
+                        return Err((self.tag.as_str(), "Invalid GETMETADATA option.").into());
+                    }
+                }
+            }
+        }
+
+        // Parse mailbox name
+        let mailbox_name = utf7_maybe_decode(
+            tokens
+                .next()
+                .ok_or((self.tag.as_str(), "Missing mailbox name."))?
+                .unwrap_string()
+                .map_err(|v| (self.tag.as_str(), v))?,
+            version,
+        );
+
+        // Parse entries
+        let mut entries = Vec::new();
+        match tokens.next() {
+            Some(Token::ParenthesisOpen) => loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(name)) => {
+                        let name = parse_entry_name(name).map_err(|v| (self.tag.as_str(), v))?;
+                        let value = if self.command == Command::SetMetadata {
+                            if matches!(name.as_str(), "/private" | "/shared") {
+                                return Err((self.tag.as_str(), "Invalid entry name.").into());
+                            }
+                            match tokens.next() {
+                                Some(Token::Argument(value)) => Some(value),
+                                Some(Token::Nil) => None,
+                                _ => {
+                                    return Err((self.tag.as_str(), "Missing entry value.").into());
+                                }
+                            }
+                        } else {
+                            None
+                        };
+                        entries.push(Entry { name, value });
+                    }
+                    _ => {
+                        return Err((self.tag.as_str(), "Invalid entry list.").into());
+                    }
+                }
+            },
+            Some(Token::Argument(name)) if self.command == Command::GetMetadata => {
+                entries.push(Entry {
+                    name: parse_entry_name(name).map_err(|v| (self.tag.as_str(), v))?,
+                    value: None,
+                });
+            }
+            _ => {
+                return Err((self.tag.as_str(), "Expected an entry or a list of entries.").into());
+            }
+        }
+
+        if entries.is_empty() {
+            Err((self.tag, "At least one entry must be specified.").into())
+        } else if tokens.next().is_none() {
+            Ok(metadata::Arguments {
+                tag: self.tag,
+                mailbox_name,
+                entries,
+                max_size,
+                depth,
+            })
+        } else {
+            Err((self.tag, "Too many arguments.").into())
+        }
+    }
+}
+
+fn parse_entry_name(value: Vec<u8>) -> super::Result<String> {
+    let name = String::from_utf8(value)
+        .map_err(|_| "Invalid UTF-8 in entry name.")?
+        .to_ascii_lowercase();
+
+    if (name.starts_with("/private/")
+        || name.starts_with("/shared/")
+        || name == "/private"
+        || name == "/shared")
+        && !name.ends_with('/')
+        && !name.contains("//")
+        && name
+            .bytes()
+            .all(|ch| (0x20..0x7f).contains(&ch) && ch != b'*' && ch != b'%')
+    {
+        Ok(name)
+    } else {
+        Err(format!("Invalid entry name '{}'.", name).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            metadata::{self, Depth, Entry},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_metadata() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A001 GETMETADATA \"\" /shared/comment\r\n",
+                metadata::Arguments {
+                    tag: "A001".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec![Entry::new("/shared/comment", None)],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "A002 GETMETADATA (MAXSIZE 1024 DEPTH infinity) INBOX (/Shared/Comment /private/vendor)\r\n",
+                metadata::Arguments {
+                    tag: "A002".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry::new("/shared/comment", None),
+                        Entry::new("/private/vendor", None),
+                    ],
+                    max_size: Some(1024),
+                    depth: Depth::Infinity,
+                },
+            ),
+            (
+                "A003 GETMETADATA (DEPTH 1) INBOX /private/vendor\r\n",
+                metadata::Arguments {
+                    tag: "A003".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![Entry::new("/private/vendor", None)],
+                    max_size: None,
+                    depth: Depth::One,
+                },
+            ),
+            (
+                "A004 SETMETADATA INBOX (/private/comment \"My new comment\" /shared/comment NIL)\r\n",
+                metadata::Arguments {
+                    tag: "A004".to_string(),
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry::new("/private/comment", Some(b"My new comment".to_vec())),
+                        Entry::new("/shared/comment", None),
+                    ],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+            (
+                "A005 SETMETADATA \"\" (/shared/vendor/foo {5+}\r\nhello)\r\n",
+                metadata::Arguments {
+                    tag: "A005".to_string(),
+                    mailbox_name: "".to_string(),
+                    entries: vec![Entry::new("/shared/vendor/foo", Some(b"hello".to_vec()))],
+                    max_size: None,
+                    depth: Depth::Zero,
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_metadata(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{:?}",
+                command
+            );
+        }
+
+        for command in [
+            "A006 GETMETADATA INBOX /comment\r\n",
+            "A007 GETMETADATA INBOX /private/\r\n",
+            "A008 GETMETADATA INBOX /shared//comment\r\n",
+            "A009 GETMETADATA INBOX /shared/*\r\n",
+            "A010 SETMETADATA INBOX /shared/comment \"value\"\r\n",
+            "A011 GETMETADATA (DEPTH 2) INBOX /shared/comment\r\n",
+            "A012 SETMETADATA INBOX (/shared \"value\")\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_metadata(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{:?}",
+                command
+            );
+        }
+    }
+}
@@ -0,0 +1,394 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    protocol::{
+        notify::{self, Event, EventGroup, Filter},
+        ProtocolVersion,
+    },
+    receiver::{Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+/*
+
+   notify          = "NOTIFY" SP
+                     (notify-set / notify-none)
+
+   notify-set      = "SET" [status-indicator] SP event-groups
+
+   status-indicator = SP "STATUS"
+
+   notify-none     = "NONE"
+
+   event-groups    = event-group *(SP event-group)
+
+   event-group     = "(" filter-mailboxes SP events ")"
+
+   filter-mailboxes = "selected" / "selected-delayed" / "inboxes" /
+                      "personal" / "subscribed" /
+                      ( "subtree" SP one-or-more-mailbox ) /
+                      ( "mailboxes" SP one-or-more-mailbox )
+
+   one-or-more-mailbox = mailbox / many-mailboxes
+
+   many-mailboxes  = "(" mailbox *(SP mailbox) ")"
+
+   events          = ( "(" event *(SP event) ")" ) / "NONE"
+
+*/
+
+impl Request<Command> {
+    pub fn parse_notify(self, version: ProtocolVersion) -> crate::Result<notify::Arguments> {
+        let mut tokens = self.tokens.into_iter().peekable();
+
+        match tokens.next() {
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => {
+                return if tokens.next().is_none() {
+                    Ok(notify::Arguments {
+                        tag: self.tag,
+                        status: false,
+                        groups: Vec::new(),
+                    })
+                } else {
+                    Err((self.tag, "Too many arguments.").into())
+                };
+            }
+            Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"SET") => (),
+            _ => {
+                return Err((self.tag.as_str(), "Expected 'SET' or 'NONE'.").into());
+            }
+        }
+
+        let status = if tokens
+            .peek()
+            .map_or(false, |token| token.eq_ignore_ascii_case(b"STATUS"))
+        {
+            tokens.next();
+            true
+        } else {
+            false
+        };
+
+        let mut groups: Vec<EventGroup> = Vec::new();
+        loop {
+            match tokens.next() {
+                Some(Token::ParenthesisOpen) => {
+                    let group = parse_event_group(&mut tokens, &self.tag, version)
+                        .map_err(|v| (self.tag.as_str(), v))?;
+                    if group.filter.is_selected() && groups.iter().any(|g| g.filter.is_selected()) {
+                        return Err((
+                            self.tag.as_str(),
+                            "The selected filter can only be specified once.",
+                        )
+                            .into());
+                    }
+                    groups.push(group);
+                }
+                None if !groups.is_empty() => break,
+                _ => {
+                    return Err((self.tag.as_str(), "Expected an event group.").into());
+                }
+            }
+        }
+
+        Ok(notify::Arguments {
+            tag: self.tag,
+            status,
+            groups,
+        })
+    }
+}
+
+fn parse_event_group(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    tag: &str,
+    version: ProtocolVersion,
+) -> super::Result<EventGroup> {
+    // Parse filter
+    let filter = tokens
+        .next()
+        .ok_or("Missing mailbox filter.")?
+        .unwrap_bytes();
+    let filter = if filter.eq_ignore_ascii_case(b"selected") {
+        Filter::Selected
+    } else if filter.eq_ignore_ascii_case(b"selected-delayed") {
+        Filter::SelectedDelayed
+    } else if filter.eq_ignore_ascii_case(b"inboxes") {
+        Filter::Inboxes
+    } else if filter.eq_ignore_ascii_case(b"personal") {
+        Filter::Personal
+    } else if filter.eq_ignore_ascii_case(b"subscribed") {
+        Filter::Subscribed
+    } else if filter.eq_ignore_ascii_case(b"subtree") || filter.eq_ignore_ascii_case(b"mailboxes") {
+        let mut mailboxes = Vec::new();
+        match tokens.next() {
+            Some(Token::ParenthesisOpen) => loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) if !mailboxes.is_empty() => break,
+                    Some(token @ (Token::Argument(_) | Token::Nil)) => {
+                        mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+                    }
+                    _ => return Err("Invalid mailbox list.".into()),
+                }
+            },
+            Some(token @ Token::Argument(_)) => {
+                mailboxes.push(utf7_maybe_decode(token.unwrap_string()?, version));
+            }
+            _ => return Err("Expected a mailbox name or a list of mailboxes.".into()),
+        }
+        if filter.eq_ignore_ascii_case(b"subtree") {
+            Filter::Subtree(mailboxes)
+        } else {
+            Filter::Mailboxes(mailboxes)
+        }
+    } else {
+        return Err(format!(
+            "Invalid mailbox filter '{}'.",
+            String::from_utf8_lossy(&filter)
+        )
+        .into());
+    };
+
+    // Parse events
+    let mut events = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => loop {
+            match tokens.next() {
+                Some(Token::ParenthesisClose) if !events.is_empty() => break,
+                Some(Token::Argument(value)) => {
+                    let event = if value.eq_ignore_ascii_case(b"MessageNew") {
+                        let mut attributes = Vec::new();
+                        if tokens
+                            .peek()
+                            .map_or(false, |token| token.is_parenthesis_open())
+                        {
+                            attributes = parse_fetch_attributes(tokens, tag)?;
+                        }
+                        Event::MessageNew(attributes)
+                    } else if value.eq_ignore_ascii_case(b"MessageExpunge") {
+                        Event::MessageExpunge
+                    } else if value.eq_ignore_ascii_case(b"FlagChange") {
+                        Event::FlagChange
+                    } else if value.eq_ignore_ascii_case(b"AnnotationChange") {
+                        Event::AnnotationChange
+                    } else if value.eq_ignore_ascii_case(b"MailboxName") {
+                        Event::MailboxName
+                    } else if value.eq_ignore_ascii_case(b"SubscriptionChange") {
+                        Event::SubscriptionChange
+                    } else if value.eq_ignore_ascii_case(b"MailboxMetadataChange") {
+                        Event::MailboxMetadataChange
+                    } else if value.eq_ignore_ascii_case(b"ServerMetadataChange") {
+                        Event::ServerMetadataChange
+                    } else {
+                        return Err(format!(
+                            "Invalid event '{}'.",
+                            String::from_utf8_lossy(&value)
+                        )
+                        .into());
+                    };
+                    events.push(event);
+                }
+                _ => return Err("Invalid event list.".into()),
+            }
+        },
+        Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"NONE") => (),
+        _ => return Err("Expected a list of events or 'NONE'.".into()),
+    }
+    if tokens
+        .next()
+        .map_or(true, |token| !token.is_parenthesis_close())
+    {
+        return Err("Expected ')' after event list.".into());
+    }
+
+    // MessageNew and MessageExpunge must be specified together,
+    // and FlagChange/AnnotationChange require both.
+    let group = EventGroup { filter, events };
+    let has_new = group.has_event(&Event::MessageNew(Vec::new()));
+    let has_expunge = group.has_event(&Event::MessageExpunge);
+    if has_new != has_expunge
+        || (!has_new
+            && (group.has_event(&Event::FlagChange) || group.has_event(&Event::AnnotationChange)))
+    {
+        return Err(
+            "MessageNew and MessageExpunge must be specified together with FlagChange.".into(),
+        );
+    }
+
+    Ok(group)
+}
+
+fn parse_fetch_attributes(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    tag: &str,
+) -> super::Result<Vec<crate::protocol::fetch::Attribute>> {
+    // Collect the attribute list and parse it as a FETCH request
+    let mut fetch_tokens = vec![Token::Argument(b"1".to_vec())];
+    let mut depth = 0;
+    for token in tokens.by_ref() {
+        match &token {
+            Token::ParenthesisOpen => {
+                depth += 1;
+            }
+            Token::ParenthesisClose => {
+                depth -= 1;
+            }
+            _ => (),
+        }
+        fetch_tokens.push(token);
+        if depth == 0 {
+            break;
+        }
+    }
+
+    Request {
+        tag: tag.to_string(),
+        command: Command::Fetch(false),
+        tokens: fetch_tokens,
+    }
+    .parse_fetch()
+    .map(|arguments| arguments.attributes)
+    .map_err(|response| response.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            fetch::{Attribute, Section},
+            notify::{self, Event, EventGroup, Filter},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_notify() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A001 NOTIFY NONE\r\n",
+                notify::Arguments {
+                    tag: "A001".to_string(),
+                    status: false,
+                    groups: vec![],
+                },
+            ),
+            (
+                concat!(
+                    "A002 NOTIFY SET STATUS (selected (MessageExpunge MessageNew ",
+                    "(UID BODY.PEEK[HEADER.FIELDS (From Subject)]) FlagChange)) ",
+                    "(subtree (INBOX \"Shared Folders\") (MessageNew MessageExpunge)) ",
+                    "(personal (MailboxName SubscriptionChange))\r\n"
+                ),
+                notify::Arguments {
+                    tag: "A002".to_string(),
+                    status: true,
+                    groups: vec![
+                        EventGroup {
+                            filter: Filter::Selected,
+                            events: vec![
+                                Event::MessageExpunge,
+                                Event::MessageNew(vec![
+                                    Attribute::Uid,
+                                    Attribute::BodySection {
+                                        peek: true,
+                                        sections: vec![Section::HeaderFields {
+                                            not: false,
+                                            fields: vec!["From".to_string(), "Subject".to_string()],
+                                        }],
+                                        partial: None,
+                                    },
+                                ]),
+                                Event::FlagChange,
+                            ],
+                        },
+                        EventGroup {
+                            filter: Filter::Subtree(vec![
+                                "INBOX".to_string(),
+                                "Shared Folders".to_string(),
+                            ]),
+                            events: vec![Event::MessageNew(vec![]), Event::MessageExpunge],
+                        },
+                        EventGroup {
+                            filter: Filter::Personal,
+                            events: vec![Event::MailboxName, Event::SubscriptionChange],
+                        },
+                    ],
+                },
+            ),
+            (
+                "A003 NOTIFY SET (mailboxes Drafts NONE) (inboxes (MessageNew MessageExpunge))\r\n",
+                notify::Arguments {
+                    tag: "A003".to_string(),
+                    status: false,
+                    groups: vec![
+                        EventGroup {
+                            filter: Filter::Mailboxes(vec!["Drafts".to_string()]),
+                            events: vec![],
+                        },
+                        EventGroup {
+                            filter: Filter::Inboxes,
+                            events: vec![Event::MessageNew(vec![]), Event::MessageExpunge],
+                        },
+                    ],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .unwrap(),
+                arguments,
+                "{:?}",
+                command
+            );
+        }
+
+        for command in [
+            "A004 NOTIFY\r\n",
+            "A005 NOTIFY SET\r\n",
+            "A006 NOTIFY SET (personal (MessageNew))\r\n",
+            "A007 NOTIFY SET (personal (FlagChange))\r\n",
+            "A008 NOTIFY SET (selected (MessageNew MessageExpunge)) (selected-delayed NONE)\r\n",
+            "A009 NOTIFY SET (everything (MailboxName))\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_notify(ProtocolVersion::Rev2)
+                    .is_err(),
+                "{:?}",
+                command
+            );
+        }
+    }
+}
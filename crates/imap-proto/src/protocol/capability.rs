@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use super::{authenticate::Mechanism, ImapResponse};
+use super::{authenticate::Mechanism, quota::QuotaResource, ImapResponse};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
@@ -64,6 +64,12 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    Quota,
+    QuotaRes(QuotaResource), //QUOTA=RES-*
+    CompressDeflate,         //COMPRESS=DEFLATE
+    Metadata,
+    MetadataServer, //METADATA-SERVER
+    Notify,
     Auth(Mechanism),
 }
 
@@ -75,6 +81,11 @@ impl Capability {
                 mechanism.serialize(buf);
                 return;
             }
+            Capability::QuotaRes(resource) => {
+                buf.extend_from_slice(b"QUOTA=RES-");
+                resource.serialize(buf);
+                return;
+            }
             Capability::IMAP4rev2 => b"IMAP4rev2",
             Capability::IMAP4rev1 => b"IMAP4rev1",
             Capability::StartTLS => b"STARTTLS",
@@ -109,6 +120,11 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::Quota => b"QUOTA",
+            Capability::CompressDeflate => b"COMPRESS=DEFLATE",
+            Capability::Metadata => b"METADATA",
+            Capability::MetadataServer => b"METADATA-SERVER",
+            Capability::Notify => b"NOTIFY",
         });
     }
 
@@ -150,12 +166,23 @@ impl Capability {
                 Capability::StatusSize,
                 Capability::ObjectId,
                 Capability::Preview,
+                Capability::Quota,
+                Capability::QuotaRes(QuotaResource::Storage),
+                Capability::QuotaRes(QuotaResource::Message),
+                Capability::CompressDeflate,
+                Capability::Metadata,
+                Capability::MetadataServer,
+                Capability::Notify,
             ]);
         } else {
             capabilties.extend([
                 Capability::Auth(Mechanism::OAuthBearer),
                 Capability::Auth(Mechanism::Plain),
+                Capability::Auth(Mechanism::ScramSha256),
             ]);
+            if is_tls {
+                capabilties.push(Capability::Auth(Mechanism::ScramSha256Plus));
+            }
         }
         if !is_tls {
             capabilties.push(Capability::StartTLS);
@@ -0,0 +1,210 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::utf7::utf7_encode;
+
+use super::quoted_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Depth {
+    #[default]
+    Zero,
+    One,
+    Infinity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub mailbox_name: String,
+    pub entries: Vec<Entry>,
+    pub max_size: Option<usize>,
+    pub depth: Depth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub value: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataResponse {
+    pub mailbox_name: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Entry {
+    pub fn new(name: impl Into<String>, value: Option<Vec<u8>>) -> Self {
+        Entry {
+            name: name.into(),
+            value,
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.name.starts_with("/private/")
+    }
+}
+
+impl Depth {
+    pub fn matches(&self, requested: &str, name: &str) -> bool {
+        if requested == name {
+            true
+        } else if let Some(child) = name
+            .strip_prefix(requested)
+            .and_then(|name| name.strip_prefix('/'))
+        {
+            match self {
+                Depth::Zero => false,
+                Depth::One => !child.contains('/'),
+                Depth::Infinity => true,
+            }
+        } else {
+            false
+        }
+    }
+}
+
+impl MetadataResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            self.mailbox_name.len()
+                + 16
+                + self
+                    .entries
+                    .iter()
+                    .map(|e| e.name.len() + e.value.as_ref().map_or(3, |v| v.len() + 8))
+                    .sum::<usize>(),
+        );
+        buf.extend_from_slice(b"* METADATA ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        buf.extend_from_slice(b" (");
+        for (pos, entry) in self.entries.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            quoted_string(&mut buf, &entry.name);
+            buf.push(b' ');
+            if let Some(value) = &entry.value {
+                serialize_value(&mut buf, value);
+            } else {
+                buf.extend_from_slice(b"NIL");
+            }
+        }
+        buf.extend_from_slice(b")\r\n");
+        buf
+    }
+}
+
+fn serialize_value(buf: &mut Vec<u8>, value: &[u8]) {
+    if value
+        .iter()
+        .all(|&ch| (0x20..0x7f).contains(&ch) && ch != b'"' && ch != b'\\')
+    {
+        buf.push(b'"');
+        buf.extend_from_slice(value);
+        buf.push(b'"');
+    } else {
+        if value.contains(&0) {
+            buf.push(b'~');
+        }
+        buf.push(b'{');
+        buf.extend_from_slice(value.len().to_string().as_bytes());
+        buf.extend_from_slice(b"}\r\n");
+        buf.extend_from_slice(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::metadata::{Depth, Entry, MetadataResponse};
+
+    #[test]
+    fn serialize_metadata() {
+        assert_eq!(
+            String::from_utf8(
+                MetadataResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    entries: vec![
+                        Entry::new("/private/comment", Some(b"My own comment".to_vec())),
+                        Entry::new("/shared/comment", Some(b"Line 1\r\nLine 2".to_vec())),
+                        Entry::new("/shared/vendor/example", None),
+                    ],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            concat!(
+                "* METADATA \"INBOX\" (\"/private/comment\" \"My own comment\" ",
+                "\"/shared/comment\" {14}\r\nLine 1\r\nLine 2 ",
+                "\"/shared/vendor/example\" NIL)\r\n"
+            )
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                MetadataResponse {
+                    mailbox_name: "".to_string(),
+                    entries: vec![Entry::new("/shared/admin", Some(b"\0".to_vec()))],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            "* METADATA \"\" (\"/shared/admin\" ~{1}\r\n\0)\r\n"
+        );
+    }
+
+    #[test]
+    fn metadata_depth() {
+        for (depth, requested, name, expected) in [
+            (Depth::Zero, "/shared/comment", "/shared/comment", true),
+            (Depth::Zero, "/shared/vendor", "/shared/vendor/a", false),
+            (Depth::One, "/shared/vendor", "/shared/vendor/a", true),
+            (Depth::One, "/shared/vendor", "/shared/vendor/a/b", false),
+            (Depth::One, "/shared/vendor", "/shared/vendorx", false),
+            (
+                Depth::Infinity,
+                "/shared/vendor",
+                "/shared/vendor/a/b",
+                true,
+            ),
+            (
+                Depth::Infinity,
+                "/shared/vendor",
+                "/private/vendor/a",
+                false,
+            ),
+        ] {
+            assert_eq!(
+                depth.matches(requested, name),
+                expected,
+                "{depth:?} {requested} {name}"
+            );
+        }
+    }
+}
@@ -33,6 +33,7 @@ pub mod acl;
 pub mod append;
 pub mod authenticate;
 pub mod capability;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -41,7 +42,10 @@ pub mod expunge;
 pub mod fetch;
 pub mod list;
 pub mod login;
+pub mod metadata;
 pub mod namespace;
+pub mod notify;
+pub mod quota;
 pub mod rename;
 pub mod search;
 pub mod select;
@@ -377,6 +381,22 @@ impl ResponseCode {
                 return;
             }
             ResponseCode::UseAttr => b"USEATTR",
+            ResponseCode::CompressionActive => b"COMPRESSIONACTIVE",
+            ResponseCode::MetadataLongEntries { size } => {
+                buf.extend_from_slice(b"METADATA LONGENTRIES ");
+                buf.extend_from_slice(size.to_string().as_bytes());
+                return;
+            }
+            ResponseCode::MetadataMaxSize { size } => {
+                buf.extend_from_slice(b"METADATA MAXSIZE ");
+                buf.extend_from_slice(size.to_string().as_bytes());
+                return;
+            }
+            ResponseCode::MetadataTooMany => b"METADATA TOOMANY",
+            ResponseCode::MetadataNoPrivate => b"METADATA NOPRIVATE",
+            ResponseCode::BadEvent => {
+                b"BADEVENT (MessageNew MessageExpunge FlagChange MailboxName SubscriptionChange)"
+            }
         });
     }
 }
@@ -505,6 +525,13 @@ impl Display for Command {
             Command::MyRights => write!(f, "MYRIGHTS"),
             Command::Unauthenticate => write!(f, "UNAUTHENTICATE"),
             Command::Id => write!(f, "ID"),
+            Command::GetQuota => write!(f, "GETQUOTA"),
+            Command::GetQuotaRoot => write!(f, "GETQUOTAROOT"),
+            Command::SetQuota => write!(f, "SETQUOTA"),
+            Command::Compress => write!(f, "COMPRESS"),
+            Command::GetMetadata => write!(f, "GETMETADATA"),
+            Command::SetMetadata => write!(f, "SETMETADATA"),
+            Command::Notify => write!(f, "NOTIFY"),
         }
     }
 }
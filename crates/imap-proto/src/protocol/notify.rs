@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use super::fetch;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub status: bool,
+    pub groups: Vec<EventGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventGroup {
+    pub filter: Filter,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Selected,
+    SelectedDelayed,
+    Inboxes,
+    Personal,
+    Subscribed,
+    Subtree(Vec<String>),
+    Mailboxes(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    MessageNew(Vec<fetch::Attribute>),
+    MessageExpunge,
+    FlagChange,
+    AnnotationChange,
+    MailboxName,
+    SubscriptionChange,
+    MailboxMetadataChange,
+    ServerMetadataChange,
+}
+
+impl Event {
+    pub fn is_message_event(&self) -> bool {
+        matches!(
+            self,
+            Event::MessageNew(_)
+                | Event::MessageExpunge
+                | Event::FlagChange
+                | Event::AnnotationChange
+        )
+    }
+
+    pub fn is_supported(&self) -> bool {
+        !matches!(
+            self,
+            Event::AnnotationChange | Event::MailboxMetadataChange | Event::ServerMetadataChange
+        )
+    }
+
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            Event::MessageNew(_) => b"MessageNew",
+            Event::MessageExpunge => b"MessageExpunge",
+            Event::FlagChange => b"FlagChange",
+            Event::AnnotationChange => b"AnnotationChange",
+            Event::MailboxName => b"MailboxName",
+            Event::SubscriptionChange => b"SubscriptionChange",
+            Event::MailboxMetadataChange => b"MailboxMetadataChange",
+            Event::ServerMetadataChange => b"ServerMetadataChange",
+        });
+    }
+}
+
+impl EventGroup {
+    pub fn has_message_events(&self) -> bool {
+        self.events.iter().any(|e| e.is_message_event())
+    }
+
+    pub fn has_event(&self, event: &Event) -> bool {
+        self.events
+            .iter()
+            .any(|e| std::mem::discriminant(e) == std::mem::discriminant(event))
+    }
+}
+
+impl Filter {
+    pub fn is_selected(&self) -> bool {
+        matches!(self, Filter::Selected | Filter::SelectedDelayed)
+    }
+}
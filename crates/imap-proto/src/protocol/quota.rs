@@ -0,0 +1,182 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::utf7::utf7_encode;
+
+use super::quoted_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaResource {
+    Storage,
+    Message,
+    Mailbox,
+    AnnotationStorage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub name: String,
+    pub limits: Vec<(QuotaResource, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaItem {
+    pub resource: QuotaResource,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResponse {
+    pub quota_root: String,
+    pub items: Vec<QuotaItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaRootResponse {
+    pub mailbox_name: String,
+    pub quotas: Vec<QuotaResponse>,
+}
+
+impl QuotaResource {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(match self {
+            QuotaResource::Storage => b"STORAGE",
+            QuotaResource::Message => b"MESSAGE",
+            QuotaResource::Mailbox => b"MAILBOX",
+            QuotaResource::AnnotationStorage => b"ANNOTATION-STORAGE",
+        });
+    }
+}
+
+impl QuotaResponse {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"* QUOTA ");
+        quoted_string(buf, &self.quota_root);
+        buf.extend_from_slice(b" (");
+        for (pos, item) in self.items.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            item.resource.serialize(buf);
+            buf.push(b' ');
+            buf.extend_from_slice(item.usage.to_string().as_bytes());
+            buf.push(b' ');
+            buf.extend_from_slice(item.limit.to_string().as_bytes());
+        }
+        buf.extend_from_slice(b")\r\n");
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.quota_root.len() + 10 + self.items.len() * 20);
+        self.serialize(&mut buf);
+        buf
+    }
+}
+
+impl QuotaRootResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.mailbox_name.len() + 16 + self.quotas.len() * 64);
+        buf.extend_from_slice(b"* QUOTAROOT ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        for quota in &self.quotas {
+            buf.push(b' ');
+            quoted_string(&mut buf, &quota.quota_root);
+        }
+        buf.extend_from_slice(b"\r\n");
+        for quota in &self.quotas {
+            quota.serialize(&mut buf);
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::quota::{QuotaItem, QuotaResource, QuotaResponse, QuotaRootResponse};
+
+    #[test]
+    fn serialize_quota() {
+        assert_eq!(
+            String::from_utf8(
+                QuotaResponse {
+                    quota_root: "".to_string(),
+                    items: vec![QuotaItem {
+                        resource: QuotaResource::Storage,
+                        usage: 10,
+                        limit: 512,
+                    }],
+                }
+                .into_bytes()
+            )
+            .unwrap(),
+            "* QUOTA \"\" (STORAGE 10 512)\r\n"
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                QuotaRootResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    quotas: vec![QuotaResponse {
+                        quota_root: "".to_string(),
+                        items: vec![
+                            QuotaItem {
+                                resource: QuotaResource::Storage,
+                                usage: 10,
+                                limit: 512,
+                            },
+                            QuotaItem {
+                                resource: QuotaResource::Message,
+                                usage: 3,
+                                limit: 100,
+                            }
+                        ],
+                    }],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            concat!(
+                "* QUOTAROOT \"INBOX\" \"\"\r\n",
+                "* QUOTA \"\" (STORAGE 10 512 MESSAGE 3 100)\r\n"
+            )
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                QuotaRootResponse {
+                    mailbox_name: "Drafts".to_string(),
+                    quotas: vec![],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            "* QUOTAROOT \"Drafts\"\r\n"
+        );
+    }
+}
@@ -39,6 +39,7 @@ pub enum Status {
     UidValidity,
     Unseen,
     Deleted,
+    DeletedStorage,
     Size,
     Recent,
     HighestModSeq,
@@ -77,6 +78,7 @@ impl StatusItem {
                 Status::UidValidity => b"UIDVALIDITY ",
                 Status::Unseen => b"UNSEEN ",
                 Status::Deleted => b"DELETED ",
+                Status::DeletedStorage => b"DELETED-STORAGE ",
                 Status::Size => b"SIZE ",
                 Status::HighestModSeq => b"HIGHESTMODSEQ ",
                 Status::MailboxId => b"MAILBOXID ",
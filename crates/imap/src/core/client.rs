@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use std::{iter::Peekable, sync::Arc, vec::IntoIter};
+use std::{borrow::Cow, iter::Peekable, sync::Arc, vec::IntoIter};
 
 use imap_proto::{
     receiver::{self, Request},
@@ -36,6 +36,28 @@ use super::{SelectedMailbox, Session, SessionData, State, IMAP};
 
 impl<T: AsyncRead> Session<T> {
     pub async fn ingest(&mut self, bytes: &[u8]) -> crate::Result<bool> {
+        let mut bytes = Cow::Borrowed(bytes);
+
+        loop {
+            let was_compressed = self.inflater.is_some();
+            match self.ingest_requests(&bytes).await? {
+                (false, Some(remaining)) if !remaining.is_empty() => {
+                    bytes = match &mut self.inflater {
+                        // Data received along with COMPRESS is already deflated
+                        Some(inflater) if !was_compressed => {
+                            Cow::Owned(inflater.inflate(&remaining).map_err(|err| {
+                                tracing::debug!(parent: &self.span, event = "error", reason = %err, "Failed to decompress IMAP stream.");
+                            })?)
+                        }
+                        _ => Cow::Owned(remaining),
+                    };
+                }
+                (is_starttls, _) => return Ok(is_starttls),
+            }
+        }
+    }
+
+    async fn ingest_requests(&mut self, bytes: &[u8]) -> crate::Result<(bool, Option<Vec<u8>>)> {
         /*for line in String::from_utf8_lossy(bytes).split("\r\n") {
             let c = println!("<- {:?}", &line[..std::cmp::min(line.len(), 100)]);
         }*/
@@ -48,12 +70,20 @@ impl<T: AsyncRead> Session<T> {
         let mut bytes = bytes.iter();
         let mut requests = Vec::with_capacity(2);
         let mut needs_literal = None;
+        let mut remaining = None;
 
         loop {
             match self.receiver.parse(&mut bytes) {
                 Ok(request) => match self.is_allowed(request) {
                     Ok(request) => {
+                        // Stop parsing after COMPRESS, the bytes that follow
+                        // have to go through the inflater first.
+                        let is_compress = request.command == Command::Compress;
                         requests.push(request);
+                        if is_compress {
+                            remaining = bytes.as_slice().to_vec().into();
+                            break;
+                        }
                     }
                     Err(response) => {
                         self.write_bytes(response.into_bytes()).await?;
@@ -161,7 +191,7 @@ impl<T: AsyncRead> Session<T> {
                                 .into_bytes(),
                         )
                         .await
-                        .map(|_| true);
+                        .map(|_| (true, None));
                 }
                 Command::Noop => {
                     self.handle_noop(request).await?;
@@ -193,6 +223,27 @@ impl<T: AsyncRead> Session<T> {
                 Command::Id => {
                     self.handle_id(request).await?;
                 }
+                Command::GetQuota => {
+                    self.handle_get_quota(request).await?;
+                }
+                Command::GetQuotaRoot => {
+                    self.handle_get_quota_root(request).await?;
+                }
+                Command::SetQuota => {
+                    self.handle_set_quota(request).await?;
+                }
+                Command::Compress => {
+                    self.handle_compress(request).await?;
+                }
+                Command::GetMetadata => {
+                    self.handle_get_metadata(request).await?;
+                }
+                Command::SetMetadata => {
+                    self.handle_set_metadata(request).await?;
+                }
+                Command::Notify => {
+                    self.handle_notify(request).await?;
+                }
             }
         }
 
@@ -201,7 +252,7 @@ impl<T: AsyncRead> Session<T> {
                 .await?;
         }
 
-        Ok(false)
+        Ok((false, remaining))
     }
 }
 
@@ -242,10 +293,13 @@ impl<T: AsyncRead> Session<T> {
         match &request.command {
             Command::Capability | Command::Noop | Command::Logout | Command::Id => Ok(request),
             Command::StartTls => {
-                if !self.is_tls {
-                    Ok(request)
-                } else {
+                if self.is_tls {
                     Err(StatusResponse::no("Already in TLS mode.").with_tag(request.tag))
+                } else if self.inflater.is_some() {
+                    Err(StatusResponse::no("TLS cannot be started after COMPRESS.")
+                        .with_tag(request.tag))
+                } else {
+                    Ok(request)
                 }
             }
             Command::Authenticate => {
@@ -288,6 +342,13 @@ impl<T: AsyncRead> Session<T> {
             | Command::GetAcl
             | Command::ListRights
             | Command::MyRights
+            | Command::GetQuota
+            | Command::GetQuotaRoot
+            | Command::SetQuota
+            | Command::Compress
+            | Command::GetMetadata
+            | Command::SetMetadata
+            | Command::Notify
             | Command::Unauthenticate => {
                 if let State::Authenticated { .. } | State::Selected { .. } = state {
                     Ok(request)
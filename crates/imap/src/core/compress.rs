@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+// RFC 4978 mandates raw deflate streams (no zlib header) with a sync
+// flush after each chunk so the peer can process it immediately.
+
+pub struct Deflater {
+    inner: Compress,
+}
+
+pub struct Inflater {
+    inner: Decompress,
+    max_size: usize,
+}
+
+impl Deflater {
+    pub fn new() -> Self {
+        Deflater {
+            inner: Compress::new(Compression::default(), false),
+        }
+    }
+
+    pub fn deflate(&mut self, mut bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(bytes.len() / 2 + 64);
+
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(1024);
+            }
+            let total_in = self.inner.total_in();
+            self.inner
+                .compress_vec(bytes, &mut output, FlushCompress::Sync)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            bytes = &bytes[(self.inner.total_in() - total_in) as usize..];
+
+            if bytes.is_empty() && output.len() < output.capacity() {
+                return Ok(output);
+            }
+        }
+    }
+}
+
+impl Inflater {
+    // Output is limited to max_size bytes per call, a small frame can otherwise
+    // expand into a huge allocation before any request limits are checked.
+    pub fn new(max_size: usize) -> Self {
+        Inflater {
+            inner: Decompress::new(false),
+            max_size,
+        }
+    }
+
+    pub fn inflate(&mut self, mut bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        // Leave room for one extra byte to detect output over the limit
+        let max_capacity = self.max_size.saturating_add(1);
+        let mut output = Vec::with_capacity(std::cmp::min(bytes.len() * 4 + 64, max_capacity));
+
+        loop {
+            if output.len() == output.capacity() {
+                if output.len() > self.max_size {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Decompressed data exceeds the maximum request size",
+                    ));
+                }
+                output.reserve_exact(std::cmp::min(
+                    output.capacity(),
+                    max_capacity - output.len(),
+                ));
+            }
+            let total_in = self.inner.total_in();
+            let total_out = output.len();
+            let status = self
+                .inner
+                .decompress_vec(bytes, &mut output, FlushDecompress::Sync)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            let bytes_read = (self.inner.total_in() - total_in) as usize;
+            bytes = &bytes[bytes_read..];
+
+            match status {
+                Status::StreamEnd if output.len() <= self.max_size => {
+                    return Ok(output);
+                }
+                _ if bytes.is_empty() && output.len() < output.capacity() => {
+                    return Ok(output);
+                }
+                Status::BufError if bytes_read == 0 && output.len() == total_out => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Deflate stream stalled",
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Default for Deflater {
+    fn default() -> Self {
+        Self::new()
+    }
+}
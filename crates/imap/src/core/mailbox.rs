@@ -388,6 +388,7 @@ impl SessionData {
                         if account.account_id == account_id {
                             account.mailbox_state.values_mut().for_each(|v| {
                                 v.total_deleted = None;
+                                v.total_deleted_storage = None;
                                 v.total_unseen = None;
                                 v.total_messages = None;
                                 v.size = None;
@@ -454,6 +455,22 @@ impl SessionData {
                                     {
                                         changes.changed.push(mailbox_name.to_string());
                                     }
+                                    if mailbox.is_subscribed != old_mailbox.is_subscribed {
+                                        changes.subscriptions.push((
+                                            mailbox_name.to_string(),
+                                            mailbox.is_subscribed,
+                                        ));
+                                    }
+                                }
+                                if let Some((old_name, _)) = old_account
+                                    .mailbox_names
+                                    .iter()
+                                    .find(|(_, old_id)| *old_id == mailbox_id)
+                                    .filter(|(old_name, _)| *old_name != mailbox_name)
+                                {
+                                    changes
+                                        .renamed
+                                        .push((old_name.to_string(), mailbox_name.to_string()));
                                 }
                             } else {
                                 changes.added.push(mailbox_name.to_string());
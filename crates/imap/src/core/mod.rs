@@ -29,8 +29,9 @@ use std::{
 
 use ahash::AHashMap;
 use dashmap::DashMap;
+use directory::{scram::ScramServer, Principal};
 use imap_proto::{
-    protocol::{list::Attribute, ProtocolVersion},
+    protocol::{list::Attribute, notify::EventGroup, ProtocolVersion},
     receiver::Receiver,
     Command, ResponseCode, StatusResponse,
 };
@@ -41,6 +42,7 @@ use jmap::{
     },
     JMAP,
 };
+use jmap_proto::types::state::StateChange;
 use parking_lot::Mutex;
 use tokio::{
     io::{AsyncRead, ReadHalf},
@@ -52,6 +54,7 @@ use utils::{
 };
 
 pub mod client;
+pub mod compress;
 pub mod mailbox;
 pub mod message;
 pub mod session;
@@ -76,6 +79,9 @@ pub struct IMAP {
     pub name_all: String,
     pub allow_plain_auth: bool,
 
+    pub metadata_max_size: usize,
+    pub metadata_max_entries: usize,
+
     pub timeout_auth: Duration,
     pub timeout_unauth: Duration,
     pub timeout_idle: Duration,
@@ -96,15 +102,29 @@ pub struct Session<T: AsyncRead> {
     pub version: ProtocolVersion,
     pub state: State,
     pub is_tls: bool,
+    pub tls_end_point: Option<Vec<u8>>,
     pub is_condstore: bool,
     pub is_qresync: bool,
     pub writer: mpsc::Sender<writer::Event>,
     pub stream_rx: ReadHalf<T>,
+    pub inflater: Option<Box<compress::Inflater>>,
+    pub notifier: Option<Box<Notifier>>,
+    pub scram: Option<Box<ScramExchange>>,
     pub in_flight: InFlight,
     pub remote_addr: RemoteAddress,
     pub span: tracing::Span,
 }
 
+pub struct ScramExchange {
+    pub server: ScramServer,
+    pub principal: Option<Principal>,
+}
+
+pub struct Notifier {
+    pub change_rx: mpsc::Receiver<StateChange>,
+    pub groups: Vec<EventGroup>,
+}
+
 pub struct SessionData {
     pub account_id: u32,
     pub jmap: Arc<JMAP>,
@@ -124,6 +144,7 @@ pub struct Mailbox {
     pub total_messages: Option<u32>,
     pub total_unseen: Option<u32>,
     pub total_deleted: Option<u32>,
+    pub total_deleted_storage: Option<u32>,
     pub uid_validity: Option<u32>,
     pub uid_next: Option<u32>,
     pub size: Option<u32>,
@@ -176,6 +197,8 @@ pub struct MailboxSync {
     pub added: Vec<String>,
     pub changed: Vec<String>,
     pub deleted: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub subscriptions: Vec<(String, bool)>,
 }
 
 pub enum SavedSearch {
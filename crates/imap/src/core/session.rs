@@ -21,6 +21,8 @@
  * for more details.
 */
 
+use std::borrow::Cow;
+
 use imap_proto::{protocol::ProtocolVersion, receiver::Receiver};
 use jmap::auth::rate_limit::RemoteAddress;
 use tokio::{
@@ -31,6 +33,8 @@ use tokio::{
 use tokio_rustls::server::TlsStream;
 use utils::listener::{SessionData, SessionManager};
 
+use crate::op::notify::next_state_change;
+
 use super::{writer, ImapSessionManager, Session, State};
 
 impl SessionManager for ImapSessionManager {
@@ -70,7 +74,18 @@ impl<T: AsyncRead> Session<T> {
                     match result {
                         Ok(Ok(bytes_read)) => {
                             if bytes_read > 0 {
-                                match self.ingest(&buf[..bytes_read]).await {
+                                let bytes = if let Some(inflater) = &mut self.inflater {
+                                    match inflater.inflate(&buf[..bytes_read]) {
+                                        Ok(bytes) => Cow::Owned(bytes),
+                                        Err(err) => {
+                                            tracing::debug!(parent: &self.span, event = "error", reason = %err, "Failed to decompress IMAP stream.");
+                                            break;
+                                        }
+                                    }
+                                } else {
+                                    Cow::Borrowed(&buf[..bytes_read])
+                                };
+                                match self.ingest(&bytes).await {
                                     Ok(false) => (),
                                     Ok(true) => {
                                         return true;
@@ -96,6 +111,16 @@ impl<T: AsyncRead> Session<T> {
                         }
                     }
                 },
+                state_change = next_state_change(&mut self.notifier) => {
+                    if let Some(state_change) = state_change {
+                        if self.handle_state_change(state_change).await.is_err() {
+                            break;
+                        }
+                    } else {
+                        tracing::debug!(parent: &self.span, "NOTIFY channel closed.");
+                        self.notifier = None;
+                    }
+                },
                 _ = shutdown_rx.changed() => {
                     self.write_bytes(&b"* BYE Server shutting down.\r\n"[..]).await.ok();
                     tracing::debug!(parent: &self.span, event = "shutdown", "IMAP server shutting down.");
@@ -128,6 +153,7 @@ impl Session<TcpStream> {
             state: State::NotAuthenticated { auth_failures: 0 },
             writer: writer::spawn_writer(writer::Event::Stream(stream_tx), session.span.clone()),
             is_tls: false,
+            tls_end_point: None,
             is_condstore: false,
             is_qresync: false,
             imap: manager.imap,
@@ -137,6 +163,9 @@ impl Session<TcpStream> {
             in_flight: session.in_flight,
             remote_addr: RemoteAddress::IpAddress(session.remote_ip),
             stream_rx,
+            inflater: None,
+            notifier: None,
+            scram: None,
         })
     }
 
@@ -163,8 +192,9 @@ impl Session<TcpStream> {
         };
 
         // Upgrade to TLS
-        let (stream_rx, stream_tx) =
-            tokio::io::split(self.instance.tls_accept(stream, &self.span).await?);
+        let stream = self.instance.tls_accept(stream, &self.span).await?;
+        let tls_end_point = self.instance.tls_end_point(&stream);
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
         if let Err(err) = self.writer.send(writer::Event::StreamTls(stream_tx)).await {
             tracing::debug!("Failed to send stream: {}", err);
             return Err(());
@@ -178,6 +208,7 @@ impl Session<TcpStream> {
             version: self.version,
             state: self.state,
             is_tls: true,
+            tls_end_point,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
             writer: self.writer,
@@ -185,6 +216,9 @@ impl Session<TcpStream> {
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
             stream_rx,
+            inflater: self.inflater,
+            notifier: self.notifier,
+            scram: None,
         })
     }
 }
@@ -208,6 +242,7 @@ impl Session<TlsStream<TcpStream>> {
         }
 
         // Spit stream into read and write halves
+        let tls_end_point = session.instance.tls_end_point(&stream);
         let (stream_rx, stream_tx) = tokio::io::split(stream);
 
         Ok(Session {
@@ -216,6 +251,7 @@ impl Session<TlsStream<TcpStream>> {
             state: State::NotAuthenticated { auth_failures: 0 },
             writer: writer::spawn_writer(writer::Event::StreamTls(stream_tx), span.clone()),
             is_tls: true,
+            tls_end_point,
             is_condstore: false,
             is_qresync: false,
             imap: manager.imap,
@@ -225,6 +261,9 @@ impl Session<TlsStream<TcpStream>> {
             in_flight: session.in_flight,
             remote_addr: RemoteAddress::IpAddress(session.remote_ip),
             stream_rx,
+            inflater: None,
+            notifier: None,
+            scram: None,
         })
     }
 
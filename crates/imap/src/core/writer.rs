@@ -31,7 +31,7 @@ use tokio::{
 use tokio_rustls::server::TlsStream;
 use tracing::debug;
 
-use super::{Session, SessionData};
+use super::{compress::Deflater, Session, SessionData};
 
 const IPC_CHANNEL_BUFFER: usize = 128;
 
@@ -40,11 +40,14 @@ pub enum Event {
     StreamTls(WriteHalf<TlsStream<TcpStream>>),
     Bytes(Cow<'static, [u8]>),
     Upgrade(oneshot::Sender<WriteHalf<TcpStream>>),
+    Deflate,
 }
 
 pub fn spawn_writer(mut stream: Event, span: tracing::Span) -> mpsc::Sender<Event> {
     let (tx, mut rx) = mpsc::channel::<Event>(IPC_CHANNEL_BUFFER);
     tokio::spawn(async move {
+        let mut deflater = None;
+
         'outer: loop {
             match stream {
                 Event::Stream(mut stream_tx) => {
@@ -65,6 +68,13 @@ pub fn spawn_writer(mut stream: Event, span: tracing::Span) -> mpsc::Sender<Even
                                     )
                                 );*/
 
+                                let bytes = match deflate(&mut deflater, bytes) {
+                                    Ok(bytes) => bytes,
+                                    Err(err) => {
+                                        debug!("Failed to compress bytes: {}", err);
+                                        break 'outer;
+                                    }
+                                };
                                 if let Err(err) = stream_tx.write_all(bytes.as_ref()).await {
                                     debug!("Failed to write to stream: {}", err);
                                     break 'outer;
@@ -82,6 +92,9 @@ pub fn spawn_writer(mut stream: Event, span: tracing::Span) -> mpsc::Sender<Even
                                     break 'outer;
                                 }
                             }
+                            Event::Deflate => {
+                                deflater = Deflater::new().into();
+                            }
                             _ => {
                                 stream = event;
                                 continue 'outer;
@@ -94,11 +107,21 @@ pub fn spawn_writer(mut stream: Event, span: tracing::Span) -> mpsc::Sender<Even
                     while let Some(event) = rx.recv().await {
                         match event {
                             Event::Bytes(bytes) => {
+                                let bytes = match deflate(&mut deflater, bytes) {
+                                    Ok(bytes) => bytes,
+                                    Err(err) => {
+                                        debug!("Failed to compress bytes: {}", err);
+                                        break 'outer;
+                                    }
+                                };
                                 if let Err(err) = stream_tx.write_all(bytes.as_ref()).await {
                                     debug!("Failed to write to stream: {}", err);
                                     break 'outer;
                                 }
                             }
+                            Event::Deflate => {
+                                deflater = Deflater::new().into();
+                            }
                             _ => {
                                 stream = event;
                                 continue 'outer;
@@ -114,6 +137,17 @@ pub fn spawn_writer(mut stream: Event, span: tracing::Span) -> mpsc::Sender<Even
     tx
 }
 
+fn deflate(
+    deflater: &mut Option<Deflater>,
+    bytes: Cow<'static, [u8]>,
+) -> std::io::Result<Cow<'static, [u8]>> {
+    if let Some(deflater) = deflater {
+        deflater.deflate(bytes.as_ref()).map(Cow::Owned)
+    } else {
+        Ok(bytes)
+    }
+}
+
 impl<T: AsyncRead> Session<T> {
     pub async fn write_bytes(&self, bytes: impl Into<Cow<'static, [u8]>>) -> crate::OpResult {
         let bytes = bytes.into();
@@ -77,6 +77,8 @@ impl IMAP {
             rate_requests: config.property_or_static("imap.rate-limit.requests", "2000/1m")?,
             rate_concurrent: config.property("imap.rate-limit.concurrent")?.unwrap_or(4),
             allow_plain_auth: config.property_or_static("imap.auth.allow-plain-text", "false")?,
+            metadata_max_size: config.property_or_static("imap.metadata.max-size", "65536")?,
+            metadata_max_entries: config.property_or_static("imap.metadata.max-entries", "100")?,
         }))
     }
 }
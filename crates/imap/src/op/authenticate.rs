@@ -23,16 +23,22 @@
 
 use std::sync::Arc;
 
+use directory::{
+    scram::{ScramResult, ScramServer},
+    Protocol,
+};
 use imap_proto::{
     protocol::{authenticate::Mechanism, capability::Capability},
     receiver::{self, Request},
     Command, ResponseCode, StatusResponse,
 };
+use jmap::auth::AccessToken;
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use tokio::io::AsyncRead;
 
-use crate::core::{Session, SessionData, State};
+use crate::core::{ScramExchange, Session, SessionData, State};
 
 impl<T: AsyncRead> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -80,6 +86,10 @@ impl<T: AsyncRead> Session<T> {
                         self.write_bytes(b"+ \"\"\r\n".to_vec()).await
                     }
                 }
+                Mechanism::ScramSha256 | Mechanism::ScramSha256Plus => {
+                    self.handle_scram(args.tag, args.mechanism, args.params.pop())
+                        .await
+                }
                 _ => {
                     self.write_bytes(
                         StatusResponse::no("Authentication mechanism not supported.")
@@ -94,13 +104,127 @@ impl<T: AsyncRead> Session<T> {
         }
     }
 
-    pub async fn authenticate(
+    async fn handle_scram(
         &mut self,
-        credentials: Credentials<String>,
         tag: String,
-    ) -> crate::Result<()> {
-        // Throttle authentication requests
-        if self.jmap.is_auth_allowed(self.remote_addr.clone()).is_err() {
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> crate::OpResult {
+        let mut exchange = if let Some(exchange) = self.scram.take() {
+            exchange
+        } else if mechanism == Mechanism::ScramSha256Plus && self.tls_end_point.is_none() {
+            return self
+                .write_bytes(
+                    StatusResponse::no("Channel binding is only available over TLS.")
+                        .with_tag(tag)
+                        .with_code(ResponseCode::Cannot)
+                        .into_bytes(),
+                )
+                .await;
+        } else {
+            self.check_auth_throttle().await?;
+            Box::new(ScramExchange {
+                server: ScramServer::new(
+                    mechanism == Mechanism::ScramSha256Plus,
+                    self.tls_end_point.clone(),
+                ),
+                principal: None,
+            })
+        };
+
+        let response = match response {
+            Some(response) if response == "*" => {
+                return self
+                    .write_bytes(
+                        StatusResponse::bad("Authentication cancelled.")
+                            .with_tag(tag)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+            Some(response) => base64_decode(response.as_bytes()),
+            None => {
+                if let Some(principal) = exchange.principal.take() {
+                    // Client acknowledged the server-final message
+                    let access_token = self.jmap.access_token_from_principal(principal).await;
+                    return self.complete_authentication(access_token, tag).await;
+                } else if exchange.server.is_new() {
+                    return self.continue_scram(exchange, tag, mechanism, &[]).await;
+                } else {
+                    None
+                }
+            }
+        };
+
+        let result = match response {
+            Some(response) if exchange.principal.is_none() => {
+                exchange
+                    .server
+                    .next(self.jmap.directory.as_ref(), &response)
+                    .await
+            }
+            _ => Ok(ScramResult::Failure("Invalid SCRAM response.")),
+        };
+
+        match result {
+            Ok(ScramResult::Continue(challenge)) => {
+                self.continue_scram(exchange, tag, mechanism, &challenge)
+                    .await
+            }
+            Ok(ScramResult::Success {
+                principal,
+                server_final,
+            }) => {
+                exchange.principal = principal.into();
+                self.continue_scram(exchange, tag, mechanism, &server_final)
+                    .await
+            }
+            Ok(ScramResult::Failure(reason)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "authenticate",
+                    reason = reason,
+                    "SCRAM authentication failed."
+                );
+                self.complete_authentication(None, tag).await
+            }
+            Err(_) => {
+                self.write_bytes(
+                    StatusResponse::database_failure()
+                        .with_tag(tag)
+                        .into_bytes(),
+                )
+                .await
+            }
+        }
+    }
+
+    async fn continue_scram(
+        &mut self,
+        exchange: Box<ScramExchange>,
+        tag: String,
+        mechanism: Mechanism,
+        challenge: &[u8],
+    ) -> crate::OpResult {
+        self.scram = exchange.into();
+        self.receiver.request = receiver::Request {
+            tag,
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+
+        let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 8);
+        buf.extend_from_slice(b"+ ");
+        buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+        buf.extend_from_slice(b"\r\n");
+        self.write_bytes(buf).await
+    }
+
+    async fn check_auth_throttle(&mut self) -> crate::Result<()> {
+        if self.jmap.is_auth_allowed(self.remote_addr.clone()).is_ok() {
+            Ok(())
+        } else {
             self.write_bytes(
                 StatusResponse::bye("Too many authentication requests from this IP address.")
                     .into_bytes(),
@@ -110,34 +234,43 @@ impl<T: AsyncRead> Session<T> {
                 event = "disconnect",
                 "Too many authentication attempts, disconnecting.",
             );
-            return Err(());
+            Err(())
         }
+    }
+
+    pub async fn authenticate(
+        &mut self,
+        credentials: Credentials<String>,
+        tag: String,
+    ) -> crate::Result<()> {
+        // Throttle authentication requests
+        self.check_auth_throttle().await?;
 
         // Authenticate
         let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
-                self.jmap.authenticate_plain(&username, &secret).await
+            Credentials::Plain { username, secret } => {
+                self.jmap
+                    .authenticate_plain(&username, &secret, Protocol::Imap)
+                    .await
             }
-            Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+            Credentials::XOauth2 { username, secret } => {
+                self.jmap
+                    .authenticate_xoauth2(&username, &secret, Protocol::Imap)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
+            }
+            Credentials::OAuthBearer { token } => {
+                self.jmap.authenticate_bearer(&token, Protocol::Imap).await
             }
         };
 
+        self.complete_authentication(access_token, tag).await
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        tag: String,
+    ) -> crate::Result<()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = self
@@ -208,6 +341,7 @@ impl<T: AsyncRead> Session<T> {
 
     pub async fn handle_unauthenticate(&mut self, request: Request<Command>) -> crate::OpResult {
         self.state = State::NotAuthenticated { auth_failures: 0 };
+        self.notifier = None;
 
         self.write_bytes(
             StatusResponse::completed(Command::Unauthenticate)
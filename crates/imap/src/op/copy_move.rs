@@ -28,7 +28,7 @@ use imap_proto::{
     StatusResponse,
 };
 
-use jmap::email::set::TagManager;
+use jmap::{email::set::TagManager, mailbox::TRASH_ID};
 use jmap_proto::{
     error::{method::MethodError, set::SetErrorType},
     types::{
@@ -211,6 +211,29 @@ impl SessionData {
         if src_mailbox.id.account_id == dest_mailbox.account_id {
             // Mailboxes are in the same account
             let account_id = src_mailbox.id.account_id;
+
+            // Train the spam classifier when messages are moved to or from Junk
+            let junk_mailbox_id = if self.jmap.config.spam_enable {
+                self.jmap
+                    .mailbox_get_by_role(account_id, "junk")
+                    .await
+                    .map_err(|_| StatusResponse::database_failure().with_tag(&arguments.tag))?
+            } else {
+                None
+            };
+            let spam_train = junk_mailbox_id.and_then(|junk_id| {
+                if dest_mailbox_id == junk_id {
+                    Some(true)
+                } else if is_move
+                    && src_mailbox.id.mailbox_id == Some(junk_id)
+                    && dest_mailbox_id != TRASH_ID
+                {
+                    Some(false)
+                } else {
+                    None
+                }
+            });
+
             for (id, imap_id) in ids {
                 // Obtain mailbox tags
                 let (mut mailboxes, thread_id) = if let Some(result) = self
@@ -268,6 +291,9 @@ impl SessionData {
                             );
                             did_move = true;
                         }
+                        if let Some(is_spam) = spam_train {
+                            self.jmap.spam_train_email(account_id, id, is_spam).await;
+                        }
                         copied_ids.push((imap_id, id));
                     }
                     Err(MethodError::ServerUnavailable) => {
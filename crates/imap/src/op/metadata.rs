@@ -0,0 +1,393 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::{
+    protocol::{
+        list::Attribute,
+        metadata::{Arguments, Entry, MetadataResponse},
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+
+use jmap::{auth::acl::EffectiveAcl, mailbox::set::SCHEMA};
+use jmap_proto::{
+    error::method::MethodError,
+    object::{index::ObjectIndexBuilder, Object},
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
+};
+use store::write::{assert::HashedValue, BatchBuilder, F_VALUE};
+use tokio::io::AsyncRead;
+
+use crate::core::{Session, SessionData};
+
+const SPECIAL_USE: &str = "/private/specialuse";
+
+impl<T: AsyncRead> Session<T> {
+    pub async fn handle_get_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    data.write_bytes(match data.get_metadata(&arguments).await {
+                        Ok((entries, long_entries)) => {
+                            let mut response = StatusResponse::completed(Command::GetMetadata)
+                                .with_tag(arguments.tag);
+                            if long_entries > 0 {
+                                response = response.with_code(ResponseCode::MetadataLongEntries {
+                                    size: long_entries,
+                                });
+                            }
+                            response.serialize(if !entries.is_empty() {
+                                MetadataResponse {
+                                    mailbox_name: arguments.mailbox_name,
+                                    entries,
+                                }
+                                .into_bytes(is_rev2)
+                            } else {
+                                Vec::new()
+                            })
+                        }
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_metadata(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_metadata(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    data.write_bytes(match data.set_metadata(&arguments).await {
+                        Ok(_) => StatusResponse::completed(Command::SetMetadata)
+                            .with_tag(arguments.tag)
+                            .into_bytes(),
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl SessionData {
+    pub async fn get_metadata(
+        &self,
+        arguments: &Arguments,
+    ) -> crate::op::Result<(Vec<Entry>, usize)> {
+        // Obtain the entries visible to this user
+        let metadata = if arguments.mailbox_name.is_empty() {
+            let mut metadata = self
+                .get_server_metadata(self.account_id)
+                .await?
+                .map(|v| v.inner)
+                .unwrap_or_default();
+            if let Some(shared) = self.get_server_metadata(u32::MAX).await? {
+                for (name, value) in shared.inner.properties {
+                    metadata.properties.append(name, value);
+                }
+            }
+            metadata
+        } else {
+            let (account_id, _, mut values) =
+                self.get_metadata_mailbox(&arguments.mailbox_name).await?;
+            let mut metadata = match values.inner.properties.remove(&Property::Metadata) {
+                Some(Value::Object(metadata)) => metadata,
+                _ => Object::default(),
+            };
+
+            // Private entries are only supported on mailboxes owned by the user,
+            // shared entries require the read right.
+            let access_token = self.get_access_token().await?;
+            if access_token.is_member(account_id) {
+                // Special-use attributes are exposed from the mailbox role (RFC 6154)
+                if let Some(Ok(attribute)) = values
+                    .inner
+                    .properties
+                    .get(&Property::Role)
+                    .and_then(|role| role.as_string())
+                    .map(Attribute::try_from)
+                {
+                    let mut value = Vec::new();
+                    attribute.serialize(&mut value);
+                    metadata
+                        .properties
+                        .set(Property::_T(SPECIAL_USE.to_string()), Value::Blob(value));
+                }
+            } else {
+                let can_read = values
+                    .inner
+                    .effective_acl(&access_token)
+                    .contains(Acl::ReadItems);
+                metadata.properties = metadata
+                    .properties
+                    .into_iter()
+                    .filter(|(name, _)| {
+                        can_read
+                            && matches!(name, Property::_T(name) if name.starts_with("/shared/"))
+                    })
+                    .collect();
+            }
+            metadata
+        };
+
+        // Filter entries
+        let mut entries = Vec::new();
+        let mut long_entries = 0;
+        for (name, value) in metadata.properties {
+            if let (Property::_T(name), Value::Blob(value)) = (name, value) {
+                if arguments
+                    .entries
+                    .iter()
+                    .any(|entry| arguments.depth.matches(&entry.name, &name))
+                {
+                    if arguments
+                        .max_size
+                        .map_or(false, |max_size| value.len() > max_size)
+                    {
+                        long_entries = std::cmp::max(long_entries, value.len());
+                    } else {
+                        entries.push(Entry::new(name, Some(value)));
+                    }
+                }
+            }
+        }
+
+        Ok((entries, long_entries))
+    }
+
+    pub async fn set_metadata(&self, arguments: &Arguments) -> crate::op::Result<()> {
+        // Validate value sizes
+        if arguments.entries.iter().any(|entry| {
+            entry
+                .value
+                .as_ref()
+                .map_or(false, |value| value.len() > self.imap.metadata_max_size)
+        }) {
+            return Err(
+                StatusResponse::no("Metadata value is too large.").with_code(
+                    ResponseCode::MetadataMaxSize {
+                        size: self.imap.metadata_max_size,
+                    },
+                ),
+            );
+        }
+
+        if arguments.mailbox_name.is_empty() {
+            // Private server entries are stored in the user's account,
+            // shared server entries are stored server-wide.
+            let (private, shared): (Vec<_>, Vec<_>) = arguments
+                .entries
+                .iter()
+                .partition(|entry| entry.is_private());
+            if !shared.is_empty() && !self.get_access_token().await?.is_superuser {
+                return Err(StatusResponse::no(
+                    "Only administrators are allowed to modify shared server metadata.",
+                )
+                .with_code(ResponseCode::NoPerm));
+            }
+
+            for (account_id, entries) in [(self.account_id, private), (u32::MAX, shared)] {
+                if entries.is_empty() {
+                    continue;
+                }
+                let current = self.get_server_metadata(account_id).await?;
+                let mut metadata = current
+                    .as_ref()
+                    .map(|current| current.inner.clone())
+                    .unwrap_or_default();
+                self.apply_metadata(&mut metadata, entries)?;
+
+                let mut batch = BatchBuilder::new();
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::Mailbox)
+                    .update_document(u32::MAX);
+                if let Some(current) = &current {
+                    batch.assert_value(Property::Metadata, current);
+                } else {
+                    batch.assert_value(Property::Metadata, ());
+                }
+                batch.value(Property::Metadata, &metadata, F_VALUE);
+                self.write_metadata(batch).await?;
+            }
+        } else {
+            let (account_id, mailbox_id, values) =
+                self.get_metadata_mailbox(&arguments.mailbox_name).await?;
+
+            // Validate ACLs
+            let access_token = self.get_access_token().await?;
+            if arguments
+                .entries
+                .iter()
+                .any(|entry| entry.name == SPECIAL_USE)
+            {
+                return Err(StatusResponse::no(
+                    "Special-use attributes can only be set when creating a mailbox.",
+                )
+                .with_code(ResponseCode::Cannot));
+            } else if !access_token.is_member(account_id) {
+                if arguments.entries.iter().any(|entry| entry.is_private()) {
+                    return Err(StatusResponse::no(
+                        "Private metadata is not supported on shared mailboxes.",
+                    )
+                    .with_code(ResponseCode::MetadataNoPrivate));
+                } else if !values
+                    .inner
+                    .effective_acl(&access_token)
+                    .contains(Acl::ModifyItems)
+                {
+                    return Err(StatusResponse::no(
+                        "You do not have enough permissions to perform this operation.",
+                    )
+                    .with_code(ResponseCode::NoPerm));
+                }
+            }
+
+            let mut metadata = match values.inner.properties.get(&Property::Metadata) {
+                Some(Value::Object(metadata)) => metadata.clone(),
+                _ => Object::default(),
+            };
+            self.apply_metadata(&mut metadata, arguments.entries.iter())?;
+
+            let mut changes = Object::with_capacity(1);
+            changes.append(
+                Property::Metadata,
+                if !metadata.properties.is_empty() {
+                    Value::Object(metadata)
+                } else {
+                    Value::Null
+                },
+            );
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Mailbox)
+                .update_document(mailbox_id)
+                .custom(
+                    ObjectIndexBuilder::new(SCHEMA)
+                        .with_changes(changes)
+                        .with_current(values),
+                );
+            self.write_metadata(batch).await?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_metadata<'x>(
+        &self,
+        metadata: &mut Object<Value>,
+        entries: impl IntoIterator<Item = &'x Entry>,
+    ) -> crate::op::Result<()> {
+        for entry in entries {
+            let name = Property::_T(entry.name.clone());
+            if let Some(value) = &entry.value {
+                metadata.properties.set(name, Value::Blob(value.clone()));
+            } else {
+                metadata.properties.remove(&name);
+            }
+        }
+
+        if metadata.properties.len() <= self.imap.metadata_max_entries {
+            Ok(())
+        } else {
+            Err(StatusResponse::no("Too many metadata entries.")
+                .with_code(ResponseCode::MetadataTooMany))
+        }
+    }
+
+    async fn get_server_metadata(
+        &self,
+        account_id: u32,
+    ) -> crate::op::Result<Option<HashedValue<Object<Value>>>> {
+        self.jmap
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::Mailbox,
+                u32::MAX,
+                Property::Metadata,
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn get_metadata_mailbox(
+        &self,
+        mailbox_name: &str,
+    ) -> crate::op::Result<(u32, u32, HashedValue<Object<Value>>)> {
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        match self.get_mailbox_by_name(mailbox_name) {
+            Some(mailbox) => {
+                if let Some(mailbox_id) = mailbox.mailbox_id {
+                    if let Some(values) = self
+                        .jmap
+                        .get_property::<HashedValue<Object<Value>>>(
+                            mailbox.account_id,
+                            Collection::Mailbox,
+                            mailbox_id,
+                            Property::Value,
+                        )
+                        .await?
+                    {
+                        Ok((mailbox.account_id, mailbox_id, values))
+                    } else {
+                        Err(StatusResponse::no("Mailbox no longer exists.")
+                            .with_code(ResponseCode::NonExistent))
+                    }
+                } else {
+                    Err(StatusResponse::no(
+                        "Metadata operations are not permitted on this mailbox.",
+                    ))
+                }
+            }
+            None => {
+                Err(StatusResponse::no("Mailbox does not exist.")
+                    .with_code(ResponseCode::NonExistent))
+            }
+        }
+    }
+
+    async fn write_metadata(&self, batch: BatchBuilder) -> crate::op::Result<()> {
+        match self.jmap.write_batch(batch).await {
+            Ok(_) => Ok(()),
+            Err(MethodError::ServerUnavailable) => Err(StatusResponse::no(
+                "Another process is currently updating this mailbox.",
+            )),
+            Err(_) => Err(StatusResponse::database_failure()),
+        }
+    }
+}
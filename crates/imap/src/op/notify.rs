@@ -0,0 +1,414 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use imap_proto::{
+    protocol::{
+        fetch,
+        list::{Attribute, ListItem, Tag},
+        notify::{Arguments, Event, EventGroup, Filter},
+        status::Status,
+        Sequence,
+    },
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::{state::StateChange, type_state::TypeState};
+use tokio::io::AsyncRead;
+use utils::map::bitmap::Bitmap;
+
+use crate::core::{Notifier, SelectedMailbox, Session, SessionData, State};
+
+impl<T: AsyncRead> Session<T> {
+    pub async fn handle_notify(&mut self, request: Request<Command>) -> crate::OpResult {
+        let arguments = match request.parse_notify(self.version) {
+            Ok(arguments) => arguments,
+            Err(response) => return self.write_bytes(response.into_bytes()).await,
+        };
+
+        // NOTIFY NONE
+        if arguments.groups.is_empty() {
+            self.notifier = None;
+            return self
+                .write_bytes(
+                    StatusResponse::completed(Command::Notify)
+                        .with_tag(arguments.tag)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // Make sure all requested events are supported
+        if arguments
+            .groups
+            .iter()
+            .any(|group| group.events.iter().any(|event| !event.is_supported()))
+        {
+            return self
+                .write_bytes(
+                    StatusResponse::no("One or more events are not supported.")
+                        .with_tag(arguments.tag)
+                        .with_code(ResponseCode::BadEvent)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        // Register with state manager
+        let (data, mailbox) = match &self.state {
+            State::Authenticated { data } => (data.clone(), None),
+            State::Selected { data, mailbox } => (data.clone(), mailbox.clone().into()),
+            _ => unreachable!(),
+        };
+        let account_ids = data
+            .mailboxes
+            .lock()
+            .iter()
+            .map(|account| account.account_id)
+            .collect::<Vec<_>>();
+        let change_rx = if let Some(change_rx) = self
+            .jmap
+            .subscribe_state_manager_accounts(
+                data.account_id,
+                account_ids,
+                Bitmap::from_iter([
+                    TypeState::Email,
+                    TypeState::Mailbox,
+                    TypeState::EmailDelivery,
+                ]),
+            )
+            .await
+        {
+            change_rx
+        } else {
+            return self
+                .write_bytes(
+                    StatusResponse::no("It was not possible to start NOTIFY.")
+                        .with_tag(arguments.tag)
+                        .with_code(ResponseCode::ContactAdmin)
+                        .into_bytes(),
+                )
+                .await;
+        };
+
+        // Send the initial status of the monitored mailboxes
+        if arguments.status {
+            data.write_notify_status(&arguments, &mailbox, self.version.is_rev2())
+                .await;
+        }
+
+        tracing::debug!(parent: &self.span, event = "start", context = "notify", "Starting NOTIFY.");
+        self.notifier = Some(Box::new(Notifier {
+            change_rx,
+            groups: arguments.groups,
+        }));
+
+        self.write_bytes(
+            StatusResponse::completed(Command::Notify)
+                .with_tag(arguments.tag)
+                .into_bytes(),
+        )
+        .await
+    }
+
+    pub async fn handle_state_change(&mut self, state_change: StateChange) -> crate::OpResult {
+        let (data, mailbox) = match &self.state {
+            State::Authenticated { data } => (data.clone(), None),
+            State::Selected { data, mailbox } => (data.clone(), mailbox.clone().into()),
+            State::NotAuthenticated { .. } => return Ok(()),
+        };
+        let groups = if let Some(notifier) = &self.notifier {
+            notifier.groups.clone()
+        } else {
+            return Ok(());
+        };
+
+        let mut has_mailbox_changes = false;
+        let mut has_email_changes = false;
+        for (type_state, _) in state_change.types {
+            match type_state {
+                TypeState::Email | TypeState::EmailDelivery => {
+                    has_email_changes = true;
+                }
+                TypeState::Mailbox => {
+                    has_mailbox_changes = true;
+                }
+                _ => {}
+            }
+        }
+
+        if has_mailbox_changes || has_email_changes {
+            data.write_notifications(
+                &groups,
+                &mailbox,
+                has_mailbox_changes,
+                has_email_changes,
+                self.is_qresync,
+                self.version.is_rev2(),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn next_state_change(notifier: &mut Option<Box<Notifier>>) -> Option<StateChange> {
+    if let Some(notifier) = notifier {
+        notifier.change_rx.recv().await
+    } else {
+        std::future::pending().await
+    }
+}
+
+impl SessionData {
+    pub async fn write_notify_status(
+        &self,
+        arguments: &Arguments,
+        mailbox: &Option<Arc<SelectedMailbox>>,
+        is_rev2: bool,
+    ) {
+        let mailbox_names = self
+            .mailboxes
+            .lock()
+            .iter()
+            .flat_map(|account| account.mailbox_names.keys().cloned())
+            .collect::<Vec<_>>();
+
+        let mut buf = Vec::with_capacity(64);
+        for mailbox_name in mailbox_names {
+            if self.is_selected_mailbox(mailbox, &mailbox_name) {
+                continue;
+            }
+            if let Some(group) = self
+                .notify_group(&arguments.groups, &mailbox_name)
+                .filter(|group| group.has_message_events())
+            {
+                if let Ok(status) = self.status(mailbox_name, &notify_status_items(group)).await {
+                    status.serialize(&mut buf, is_rev2);
+                }
+            }
+        }
+
+        if !buf.is_empty() {
+            self.write_bytes(buf).await;
+        }
+    }
+
+    pub async fn write_notifications(
+        &self,
+        groups: &[EventGroup],
+        mailbox: &Option<Arc<SelectedMailbox>>,
+        check_mailboxes: bool,
+        check_emails: bool,
+        is_qresync: bool,
+        is_rev2: bool,
+    ) {
+        // Notify changes in mailboxes other than the selected one
+        if check_mailboxes {
+            match self.synchronize_mailboxes(true).await {
+                Ok(Some(changes)) => {
+                    let mut buf = Vec::with_capacity(64);
+
+                    // List deleted mailboxes
+                    for mailbox_name in changes.deleted {
+                        if self
+                            .notify_group(groups, &mailbox_name)
+                            .map_or(false, |group| group.has_event(&Event::MailboxName))
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: vec![Attribute::NonExistent],
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    // List added mailboxes
+                    for mailbox_name in changes.added {
+                        if self
+                            .notify_group(groups, &mailbox_name)
+                            .map_or(false, |group| group.has_event(&Event::MailboxName))
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: vec![],
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    // List renamed mailboxes
+                    for (old_name, mailbox_name) in changes.renamed {
+                        if [&old_name, &mailbox_name].into_iter().any(|name| {
+                            self.notify_group(groups, name)
+                                .map_or(false, |group| group.has_event(&Event::MailboxName))
+                        }) {
+                            ListItem {
+                                mailbox_name,
+                                attributes: vec![],
+                                tags: vec![Tag::OldName(old_name)],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    // List mailboxes with subscription changes
+                    for (mailbox_name, is_subscribed) in changes.subscriptions {
+                        if self
+                            .notify_group(groups, &mailbox_name)
+                            .map_or(false, |group| group.has_event(&Event::SubscriptionChange))
+                        {
+                            ListItem {
+                                mailbox_name,
+                                attributes: if is_subscribed {
+                                    vec![Attribute::Subscribed]
+                                } else {
+                                    vec![]
+                                },
+                                tags: vec![],
+                            }
+                            .serialize(&mut buf, is_rev2, false);
+                        }
+                    }
+
+                    // Obtain status of changed mailboxes, the selected mailbox
+                    // is reported using untagged EXISTS/EXPUNGE/FETCH responses
+                    for mailbox_name in changes.changed {
+                        if self.is_selected_mailbox(mailbox, &mailbox_name) {
+                            continue;
+                        }
+                        if let Some(group) = self
+                            .notify_group(groups, &mailbox_name)
+                            .filter(|group| group.has_message_events())
+                        {
+                            if let Ok(status) =
+                                self.status(mailbox_name, &notify_status_items(group)).await
+                            {
+                                status.serialize(&mut buf, is_rev2);
+                            }
+                        }
+                    }
+
+                    if !buf.is_empty() {
+                        self.write_bytes(buf).await;
+                    }
+                }
+                Err(_) => {
+                    tracing::debug!(parent: &self.span, "Failed to refresh mailboxes.");
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // Notify changes in the selected mailbox
+        if let (true, Some(mailbox), Some(group)) = (
+            check_emails,
+            mailbox,
+            groups
+                .iter()
+                .find(|group| group.filter == Filter::Selected && group.has_message_events()),
+        ) {
+            let uid_max = mailbox.state.lock().uid_max;
+            self.write_changes(&Some(mailbox.clone()), false, true, is_qresync, is_rev2)
+                .await;
+
+            // Fetch the attributes requested for new messages
+            let new_uid_max = mailbox.state.lock().uid_max;
+            if new_uid_max > uid_max {
+                if let Some(Event::MessageNew(attributes)) = group
+                    .events
+                    .iter()
+                    .find(|event| matches!(event, Event::MessageNew(_)))
+                {
+                    if !attributes.is_empty() {
+                        self.fetch(
+                            fetch::Arguments {
+                                tag: String::new(),
+                                sequence_set: Sequence::range(Some(uid_max + 1), Some(new_uid_max)),
+                                attributes: attributes.clone(),
+                                changed_since: None,
+                                include_vanished: false,
+                            },
+                            mailbox.clone(),
+                            true,
+                            is_qresync,
+                            false,
+                        )
+                        .await;
+                    }
+                }
+            }
+        }
+    }
+
+    fn notify_group<'x>(
+        &self,
+        groups: &'x [EventGroup],
+        mailbox_name: &str,
+    ) -> Option<&'x EventGroup> {
+        let is_personal = !mailbox_name.starts_with(&self.imap.name_shared);
+        let is_subscribed = self.mailboxes.lock().iter().any(|account| {
+            account
+                .mailbox_names
+                .get(mailbox_name)
+                .and_then(|mailbox_id| account.mailbox_state.get(mailbox_id))
+                .map_or(false, |mailbox| mailbox.is_subscribed)
+        });
+
+        groups.iter().find(|group| match &group.filter {
+            Filter::Selected | Filter::SelectedDelayed => false,
+            Filter::Inboxes => is_personal && mailbox_name.eq_ignore_ascii_case("INBOX"),
+            Filter::Personal => is_personal,
+            Filter::Subscribed => is_subscribed,
+            Filter::Subtree(names) => names.iter().any(|name| {
+                mailbox_name
+                    .strip_prefix(name.as_str())
+                    .map_or(false, |suffix| suffix.is_empty() || suffix.starts_with('/'))
+            }),
+            Filter::Mailboxes(names) => names.iter().any(|name| name == mailbox_name),
+        })
+    }
+
+    fn is_selected_mailbox(
+        &self,
+        mailbox: &Option<Arc<SelectedMailbox>>,
+        mailbox_name: &str,
+    ) -> bool {
+        mailbox.as_ref().map_or(false, |mailbox| {
+            self.get_mailbox_by_name(mailbox_name)
+                .map_or(false, |mailbox_id| mailbox_id == mailbox.id)
+        })
+    }
+}
+
+fn notify_status_items(group: &EventGroup) -> Vec<Status> {
+    let mut items = vec![Status::Messages, Status::UidNext, Status::UidValidity];
+    if group.has_event(&Event::FlagChange) {
+        items.push(Status::Unseen);
+    }
+    items
+}
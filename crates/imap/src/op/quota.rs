@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::{
+    protocol::quota::{QuotaItem, QuotaResource, QuotaResponse, QuotaRootResponse},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+use jmap_proto::types::collection::Collection;
+use tokio::io::AsyncRead;
+
+use crate::core::{Session, SessionData};
+
+impl<T: AsyncRead> Session<T> {
+    pub async fn handle_get_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+
+                tokio::spawn(async move {
+                    data.write_bytes(match data.get_quota_by_root(&arguments.name).await {
+                        Ok(quota) => StatusResponse::completed(Command::GetQuota)
+                            .with_tag(arguments.tag)
+                            .serialize(quota.into_bytes()),
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_get_quota_root(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                let data = self.state.session_data();
+                let is_rev2 = self.version.is_rev2();
+
+                tokio::spawn(async move {
+                    // Refresh mailboxes
+                    if let Err(err) = data.synchronize_mailboxes(false).await {
+                        data.write_bytes(err.with_tag(arguments.tag).into_bytes())
+                            .await;
+                        return;
+                    }
+
+                    data.write_bytes(match data.get_quota_root(arguments.name).await {
+                        Ok(response) => StatusResponse::completed(Command::GetQuotaRoot)
+                            .with_tag(arguments.tag)
+                            .serialize(response.into_bytes(is_rev2)),
+                        Err(response) => response.with_tag(arguments.tag).into_bytes(),
+                    })
+                    .await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+
+    pub async fn handle_set_quota(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_quota(self.version) {
+            Ok(arguments) => {
+                self.write_bytes(
+                    StatusResponse::no("Quota limits are managed by the directory.")
+                        .with_tag(arguments.tag)
+                        .with_code(ResponseCode::NoPerm)
+                        .into_bytes(),
+                )
+                .await
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl SessionData {
+    async fn get_quota_root(&self, mailbox_name: String) -> crate::op::Result<QuotaRootResponse> {
+        let account_id = self
+            .get_mailbox_by_name(&mailbox_name)
+            .ok_or_else(|| {
+                StatusResponse::no("Mailbox does not exist.").with_code(ResponseCode::NonExistent)
+            })?
+            .account_id;
+        let quota_root = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == account_id)
+            .and_then(|account| account.prefix.clone())
+            .unwrap_or_default();
+
+        Ok(QuotaRootResponse {
+            mailbox_name,
+            quotas: self
+                .get_quota(account_id, quota_root)
+                .await?
+                .map(|quota| vec![quota])
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn get_quota_by_root(&self, quota_root: &str) -> crate::op::Result<QuotaResponse> {
+        let account_id = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.prefix.as_deref().unwrap_or_default() == quota_root)
+            .map(|account| account.account_id)
+            .ok_or_else(|| {
+                StatusResponse::no("Quota root does not exist.")
+                    .with_code(ResponseCode::NonExistent)
+            })?;
+
+        self.get_quota(account_id, quota_root.to_string())
+            .await?
+            .ok_or_else(|| {
+                StatusResponse::no("No quota limits are set for this quota root.")
+                    .with_code(ResponseCode::NonExistent)
+            })
+    }
+
+    async fn get_quota(
+        &self,
+        account_id: u32,
+        quota_root: String,
+    ) -> crate::op::Result<Option<QuotaResponse>> {
+        // Obtain the quota of the account owning the quota root
+        let access_token = self.get_access_token().await?;
+        let limit = if access_token.is_primary_id(account_id) {
+            access_token.quota as u64
+        } else if let Some(account_name) = self.jmap.get_account_name(account_id).await? {
+            self.jmap
+                .directory
+                .principal(&account_name)
+                .await
+                .map_err(|err| {
+                    tracing::warn!(parent: &self.span,
+                                   event = "error",
+                                   reason = ?err,
+                                   "Failed to obtain principal quota");
+                    StatusResponse::database_failure()
+                })?
+                .map(|principal| principal.quota as u64)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut items = Vec::with_capacity(2);
+        if limit > 0 {
+            // Storage is reported in units of 1024 octets
+            let usage = std::cmp::max(self.jmap.get_used_quota(account_id).await?, 0) as u64;
+            items.push(QuotaItem {
+                resource: QuotaResource::Storage,
+                usage: (usage + 1023) / 1024,
+                limit: (limit + 1023) / 1024,
+            });
+        }
+        if self.jmap.config.mail_max_messages > 0 {
+            items.push(QuotaItem {
+                resource: QuotaResource::Message,
+                usage: self
+                    .jmap
+                    .get_document_ids(account_id, Collection::Email)
+                    .await?
+                    .map_or(0, |ids| ids.len()),
+                limit: self.jmap.config.mail_max_messages,
+            });
+        }
+
+        Ok(if !items.is_empty() {
+            Some(QuotaResponse { quota_root, items })
+        } else {
+            None
+        })
+    }
+}
@@ -142,6 +142,13 @@ impl SessionData {
                                 items_update.push_unique(*item);
                             }
                         }
+                        Status::DeletedStorage => {
+                            if let Some(value) = mailbox_state.total_deleted_storage {
+                                items_response.push((*item, StatusItemType::Number(value as u64)));
+                            } else {
+                                items_update.push_unique(*item);
+                            }
+                        }
                         Status::Size => {
                             if let Some(value) = mailbox_state.size {
                                 items_response.push((*item, StatusItemType::Number(value as u64)));
@@ -250,6 +257,35 @@ impl SessionData {
                                 0
                             }
                         }
+                        Status::DeletedStorage => {
+                            if let (Some(mailbox_message_ids), Some(mut deleted)) = (
+                                &mailbox_message_ids,
+                                self.jmap
+                                    .get_tag(
+                                        mailbox.account_id,
+                                        Collection::Email,
+                                        Property::Keywords,
+                                        Keyword::Deleted,
+                                    )
+                                    .await?,
+                            ) {
+                                deleted &= mailbox_message_ids.as_ref();
+                                if !deleted.is_empty() {
+                                    (self
+                                        .calculate_mailbox_size(
+                                            mailbox.account_id,
+                                            &Arc::new(deleted),
+                                        )
+                                        .await? as u64
+                                        + 1023)
+                                        / 1024
+                                } else {
+                                    0
+                                }
+                            } else {
+                                0
+                            }
+                        }
                         Status::Size => {
                             if let Some(mailbox_message_ids) = &mailbox_message_ids {
                                 self.calculate_mailbox_size(mailbox.account_id, mailbox_message_ids)
@@ -303,6 +339,30 @@ impl SessionData {
                             .await?
                             .map(|v| v.len())
                             .unwrap_or(0),
+                        Status::DeletedStorage => {
+                            match self
+                                .jmap
+                                .get_tag(
+                                    mailbox.account_id,
+                                    Collection::Email,
+                                    Property::Keywords,
+                                    Keyword::Deleted,
+                                )
+                                .await?
+                            {
+                                Some(deleted) if !deleted.is_empty() => {
+                                    (self
+                                        .calculate_mailbox_size(
+                                            mailbox.account_id,
+                                            &Arc::new(deleted),
+                                        )
+                                        .await? as u64
+                                        + 1023)
+                                        / 1024
+                                }
+                                _ => 0,
+                            }
+                        }
                         Status::Size => {
                             if !message_ids.is_empty() {
                                 self.calculate_mailbox_size(mailbox.account_id, &message_ids)
@@ -336,6 +396,9 @@ impl SessionData {
                             Status::UidValidity => mailbox_state.uid_validity = value.into(),
                             Status::Unseen => mailbox_state.total_unseen = value.into(),
                             Status::Deleted => mailbox_state.total_deleted = value.into(),
+                            Status::DeletedStorage => {
+                                mailbox_state.total_deleted_storage = value.into()
+                            }
                             Status::Size => mailbox_state.size = value.into(),
                             Status::HighestModSeq | Status::MailboxId | Status::Recent => {
                                 unreachable!()
@@ -43,6 +43,7 @@ pub enum MethodError {
     AccountNotFound,
     AccountNotSupportedByMethod,
     AccountReadOnly,
+    UnknownDataType(String),
     NotFound,
 }
 
@@ -68,6 +69,7 @@ impl Display for MethodError {
                 write!(f, "Account not supported by method")
             }
             MethodError::AccountReadOnly => write!(f, "Account read only"),
+            MethodError::UnknownDataType(err) => write!(f, "Unknown data type: {}", err),
             MethodError::NotFound => write!(f, "Not found"),
         }
     }
@@ -155,6 +157,7 @@ impl Serialize for MethodError {
                 "accountReadOnly",
                 "This method modifies state, but the account is read-only.",
             ),
+            MethodError::UnknownDataType(description) => ("unknownDataType", description.as_str()),
         };
 
         map.serialize_entry("type", error_type)?;
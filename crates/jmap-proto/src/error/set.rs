@@ -99,6 +99,12 @@ pub enum SetErrorType {
     InvalidScript,
     #[serde(rename = "scriptIsActive")]
     ScriptIsActive,
+    #[serde(rename = "addressBookHasContents")]
+    AddressBookHasContents,
+    #[serde(rename = "calendarHasEvent")]
+    CalendarHasEvent,
+    #[serde(rename = "mdnAlreadySent")]
+    MdnAlreadySent,
 }
 
 impl SetErrorType {
@@ -129,6 +135,9 @@ impl SetErrorType {
             SetErrorType::AlreadyExists => "alreadyExists",
             SetErrorType::InvalidScript => "invalidScript",
             SetErrorType::ScriptIsActive => "scriptIsActive",
+            SetErrorType::AddressBookHasContents => "addressBookHasContents",
+            SetErrorType::CalendarHasEvent => "calendarHasEvent",
+            SetErrorType::MdnAlreadySent => "mdnAlreadySent",
         }
     }
 }
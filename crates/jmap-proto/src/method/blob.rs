@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use mail_parser::decoders::base64::base64_decode;
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    error::set::SetError,
+    object::Object,
+    parser::{json::Parser, Ignore, JsonObjectParser, Token},
+    request::{
+        reference::{MaybeReference, ResultReference},
+        RequestProperty,
+    },
+    types::{blob::BlobId, id::Id, property::Property, type_state::TypeState, value::Value},
+};
+
+#[derive(Debug, Clone)]
+pub struct BlobUploadRequest {
+    pub account_id: Id,
+    pub create: VecMap<String, UploadObject>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadObject {
+    pub type_: Option<String>,
+    pub data: Vec<DataSourceObject>,
+}
+
+#[derive(Debug, Clone)]
+pub enum DataSourceObject {
+    Id {
+        id: MaybeReference<BlobId, String>,
+        length: Option<usize>,
+        offset: Option<usize>,
+    },
+    Value(Vec<u8>),
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobUploadResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "created")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub created: VecMap<String, BlobUploadResponseObject>,
+
+    #[serde(rename = "notCreated")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub not_created: VecMap<String, SetError>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobUploadResponseObject {
+    #[serde(rename = "id")]
+    pub id: BlobId,
+
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+
+    #[serde(rename = "size")]
+    pub size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlobGetRequest {
+    pub account_id: Id,
+    pub ids: Option<MaybeReference<Vec<BlobId>, ResultReference>>,
+    pub properties: Option<MaybeReference<Vec<Property>, ResultReference>>,
+    pub offset: Option<usize>,
+    pub length: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobGetResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    pub list: Vec<Object<Value>>,
+
+    #[serde(rename = "notFound")]
+    pub not_found: Vec<BlobId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlobLookupRequest {
+    pub account_id: Id,
+    pub type_names: Vec<TypeState>,
+    pub ids: MaybeReference<Vec<BlobId>, ResultReference>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobLookupResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    pub list: Vec<BlobInfo>,
+
+    #[serde(rename = "notFound")]
+    pub not_found: Vec<BlobId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobInfo {
+    pub id: BlobId,
+
+    #[serde(rename = "matchedIds")]
+    pub matched_ids: VecMap<TypeState, Vec<Id>>,
+}
+
+impl JsonObjectParser for BlobUploadRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = BlobUploadRequest {
+            account_id: Id::default(),
+            create: VecMap::new(),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x6574_6165_7263 => {
+                    request.create = <VecMap<String, UploadObject>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for UploadObject {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = UploadObject {
+            type_: None,
+            data: Vec::new(),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<u128>()? {
+            match key {
+                0x6570_7974 => {
+                    request.type_ = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("type")?;
+                }
+                0x6174_6164 => {
+                    parser.next_token::<Ignore>()?.assert(Token::ArrayStart)?;
+                    loop {
+                        match parser.next_token::<Ignore>()? {
+                            Token::DictStart => {
+                                request.data.push(parse_data_source(parser)?);
+                            }
+                            Token::Comma => (),
+                            Token::ArrayEnd => break,
+                            token => return Err(token.error("data", "object")),
+                        }
+                    }
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+fn parse_data_source(parser: &mut Parser<'_>) -> crate::parser::Result<DataSourceObject> {
+    let mut data = None;
+    let mut blob_id = None;
+    let mut offset = None;
+    let mut length = None;
+
+    while let Some(key) = parser.next_dict_key::<u128>()? {
+        match key {
+            0x0074_7865_5473_613a_6174_6164 => {
+                data = parser
+                    .next_token::<String>()?
+                    .unwrap_string("data:asText")?
+                    .into_bytes()
+                    .into();
+            }
+            0x0034_3665_7361_4273_613a_6174_6164 => {
+                let value = parser
+                    .next_token::<String>()?
+                    .unwrap_string("data:asBase64")?;
+                data = base64_decode(value.as_bytes())
+                    .ok_or_else(|| parser.error("Failed to decode data:asBase64"))?
+                    .into();
+            }
+            0x6449_626f_6c62 => {
+                blob_id = parser
+                    .next_token::<MaybeReference<BlobId, String>>()?
+                    .unwrap_string("blobId")?
+                    .into();
+            }
+            0x7465_7366_666f => {
+                offset = parser
+                    .next_token::<Ignore>()?
+                    .unwrap_usize_or_null("offset")?;
+            }
+            0x6874_676e_656c => {
+                length = parser
+                    .next_token::<Ignore>()?
+                    .unwrap_usize_or_null("length")?;
+            }
+            _ => {
+                parser.skip_token(parser.depth_array, parser.depth_dict)?;
+            }
+        }
+    }
+
+    match (data, blob_id) {
+        (Some(data), None) => Ok(DataSourceObject::Value(data)),
+        (None, Some(id)) => Ok(DataSourceObject::Id { id, length, offset }),
+        _ => Err(parser.error("Expected one of data:asText, data:asBase64 or blobId")),
+    }
+}
+
+impl JsonObjectParser for BlobGetRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = BlobGetRequest {
+            account_id: Id::default(),
+            ids: None,
+            properties: None,
+            offset: None,
+            length: None,
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_6469 => {
+                    request.ids = if !key.is_ref {
+                        <Option<Vec<BlobId>>>::parse(parser)?.map(MaybeReference::Value)
+                    } else {
+                        Some(MaybeReference::Reference(ResultReference::parse(parser)?))
+                    };
+                }
+                0x7365_6974_7265_706f_7270 => {
+                    request.properties = if !key.is_ref {
+                        <Option<Vec<Property>>>::parse(parser)?.map(MaybeReference::Value)
+                    } else {
+                        Some(MaybeReference::Reference(ResultReference::parse(parser)?))
+                    };
+                }
+                0x7465_7366_666f if !key.is_ref => {
+                    request.offset = parser
+                        .next_token::<Ignore>()?
+                        .unwrap_usize_or_null("offset")?;
+                }
+                0x6874_676e_656c if !key.is_ref => {
+                    request.length = parser
+                        .next_token::<Ignore>()?
+                        .unwrap_usize_or_null("length")?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for BlobLookupRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = BlobLookupRequest {
+            account_id: Id::default(),
+            type_names: Vec::new(),
+            ids: MaybeReference::Value(Vec::new()),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_656d_614e_6570_7974 if !key.is_ref => {
+                    request.type_names = <Vec<TypeState>>::parse(parser)?;
+                }
+                0x0073_6469 => {
+                    request.ids = if !key.is_ref {
+                        MaybeReference::Value(<Vec<BlobId>>::parse(parser)?)
+                    } else {
+                        MaybeReference::Reference(ResultReference::parse(parser)?)
+                    };
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl BlobGetRequest {
+    pub fn unwrap_properties(&mut self, default: &[Property]) -> Vec<Property> {
+        if let Some(mut properties) = self.properties.take().map(|p| p.unwrap()) {
+            if !properties.contains(&Property::Id) {
+                properties.push(Property::Id);
+            }
+            properties
+        } else {
+            default.to_vec()
+        }
+    }
+}
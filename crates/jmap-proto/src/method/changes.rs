@@ -68,6 +68,11 @@ pub enum RequestArguments {
     Thread,
     Identity,
     EmailSubmission,
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
+    Quota,
 }
 
 impl JsonObjectParser for ChangesRequest {
@@ -82,6 +87,11 @@ impl JsonObjectParser for ChangesRequest {
                 MethodObject::Thread => RequestArguments::Thread,
                 MethodObject::Identity => RequestArguments::Identity,
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
+                MethodObject::AddressBook => RequestArguments::AddressBook,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent,
+                MethodObject::Quota => RequestArguments::Quota,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/changes",
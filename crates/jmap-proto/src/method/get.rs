@@ -52,6 +52,11 @@ pub enum RequestArguments {
     SieveScript,
     VacationResponse,
     Principal,
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
+    Quota,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -85,6 +90,11 @@ impl JsonObjectParser for GetRequest<RequestArguments> {
                 MethodObject::SieveScript => RequestArguments::SieveScript,
                 MethodObject::VacationResponse => RequestArguments::VacationResponse,
                 MethodObject::Principal => RequestArguments::Principal,
+                MethodObject::AddressBook => RequestArguments::AddressBook,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent,
+                MethodObject::Quota => RequestArguments::Quota,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/get",
@@ -0,0 +1,485 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    error::set::SetError,
+    object::Object,
+    parser::{json::Parser, Ignore, JsonObjectParser, Token},
+    request::{reference::MaybeReference, RequestProperty},
+    types::{blob::BlobId, id::Id, value::SetValue},
+};
+
+#[derive(Debug, Clone)]
+pub struct MdnSendRequest {
+    pub account_id: Id,
+    pub identity_id: Id,
+    pub send: VecMap<String, Mdn>,
+    pub on_success_update_email: Option<VecMap<MaybeReference<Id, String>, Object<SetValue>>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MdnSendResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "sent")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub sent: VecMap<String, Mdn>,
+
+    #[serde(rename = "notSent")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub not_sent: VecMap<String, SetError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MdnParseRequest {
+    pub account_id: Id,
+    pub blob_ids: Vec<BlobId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MdnParseResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "parsed")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub parsed: VecMap<BlobId, Mdn>,
+
+    #[serde(rename = "notParsable")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_parsable: Vec<BlobId>,
+
+    #[serde(rename = "notFound")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_found: Vec<BlobId>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Mdn {
+    #[serde(rename = "forEmailId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub for_email_id: Option<Id>,
+
+    #[serde(rename = "subject")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+
+    #[serde(rename = "textBody")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_body: Option<String>,
+
+    #[serde(rename = "includeOriginalMessage")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_original_message: Option<bool>,
+
+    #[serde(rename = "reportingUA")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reporting_ua: Option<String>,
+
+    #[serde(rename = "disposition")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disposition: Option<Disposition>,
+
+    #[serde(rename = "mdnGateway")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdn_gateway: Option<String>,
+
+    #[serde(rename = "originalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_recipient: Option<String>,
+
+    #[serde(rename = "finalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_recipient: Option<String>,
+
+    #[serde(rename = "originalMessageId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_message_id: Option<String>,
+
+    #[serde(rename = "error")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Vec<String>>,
+
+    #[serde(rename = "extensionFields")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_fields: Option<VecMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Disposition {
+    #[serde(rename = "actionMode")]
+    pub action_mode: ActionMode,
+
+    #[serde(rename = "sendingMode")]
+    pub sending_mode: SendingMode,
+
+    #[serde(rename = "type")]
+    pub type_: DispositionType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum ActionMode {
+    #[serde(rename = "manual-action")]
+    Manual,
+    #[serde(rename = "automatic-action")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SendingMode {
+    #[serde(rename = "mdn-sent-manually")]
+    Manual,
+    #[serde(rename = "mdn-sent-automatically")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum DispositionType {
+    #[serde(rename = "deleted")]
+    Deleted,
+    #[serde(rename = "dispatched")]
+    Dispatched,
+    #[serde(rename = "displayed")]
+    Displayed,
+    #[serde(rename = "processed")]
+    Processed,
+}
+
+impl JsonObjectParser for MdnSendRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = MdnSendRequest {
+            account_id: Id::default(),
+            identity_id: Id::default(),
+            send: VecMap::new(),
+            on_success_update_email: None,
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match (&key.hash[0], &key.hash[1]) {
+                (0x0064_4974_6e75_6f63_6361, _) if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                (0x6449_7974_6974_6e65_6469, _) if !key.is_ref => {
+                    request.identity_id = parser.next_token::<Id>()?.unwrap_string("identityId")?;
+                }
+                (0x646e_6573, _) if !key.is_ref => {
+                    request.send = <VecMap<String, Mdn>>::parse(parser)?;
+                }
+                (0x4565_7461_6470_5573_7365_6363_7553_6e6f, 0x6c69_616d) if !key.is_ref => {
+                    request.on_success_update_email = <Option<
+                        VecMap<MaybeReference<Id, String>, Object<SetValue>>,
+                    >>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for MdnParseRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = MdnParseRequest {
+            account_id: Id::default(),
+            blob_ids: vec![],
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_6449_626f_6c62 if !key.is_ref => {
+                    request.blob_ids = <Vec<BlobId>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for Mdn {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut mdn = Mdn::default();
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match (&key.hash[0], &key.hash[1]) {
+                (0x6449_6c69_616d_4572_6f66, _) => {
+                    mdn.for_email_id = parser
+                        .next_token::<Id>()?
+                        .unwrap_string("forEmailId")?
+                        .into();
+                }
+                (0x0074_6365_6a62_7573, _) => {
+                    mdn.subject = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("subject")?;
+                }
+                (0x7964_6f42_7478_6574, _) => {
+                    mdn.text_body = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("textBody")?;
+                }
+                (0x4d6c_616e_6967_6972_4f65_6475_6c63_6e69, 0x6567_6173_7365) => {
+                    mdn.include_original_message = parser
+                        .next_token::<Ignore>()?
+                        .unwrap_bool_or_null("includeOriginalMessage")?;
+                }
+                (0x0041_5567_6e69_7472_6f70_6572, _) => {
+                    mdn.reporting_ua = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("reportingUA")?;
+                }
+                (0x006e_6f69_7469_736f_7073_6964, _) => {
+                    mdn.disposition = Disposition::parse(parser)?.into();
+                }
+                (0x7961_7765_7461_476e_646d, _) => {
+                    mdn.mdn_gateway = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("mdnGateway")?;
+                }
+                (0x6e65_6970_6963_6552_6c61_6e69_6769_726f, 0x0074) => {
+                    mdn.original_recipient = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("originalRecipient")?;
+                }
+                (0x746e_6569_7069_6365_526c_616e_6966, _) => {
+                    mdn.final_recipient = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("finalRecipient")?;
+                }
+                (0x4965_6761_7373_654d_6c61_6e69_6769_726f, 0x0064) => {
+                    mdn.original_message_id = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("originalMessageId")?;
+                }
+                (0x0072_6f72_7265, _) => {
+                    mdn.error = <Option<Vec<String>>>::parse(parser)?;
+                }
+                (0x0073_646c_6569_466e_6f69_736e_6574_7865, _) => {
+                    match parser.next_token::<Ignore>()? {
+                        Token::DictStart => {
+                            let mut fields = VecMap::new();
+                            while let Some(name) = parser.next_dict_key::<String>()? {
+                                fields.append(
+                                    name,
+                                    parser
+                                        .next_token::<String>()?
+                                        .unwrap_string("extensionFields")?,
+                                );
+                            }
+                            mdn.extension_fields = fields.into();
+                        }
+                        Token::Null => (),
+                        token => return Err(token.error("extensionFields", "object or null")),
+                    }
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(mdn)
+    }
+}
+
+impl JsonObjectParser for Disposition {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut action_mode = None;
+        let mut sending_mode = None;
+        let mut type_ = None;
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<u128>()? {
+            match key {
+                0x6564_6f4d_6e6f_6974_6361 => {
+                    let value = parser.next_token::<String>()?.unwrap_string("actionMode")?;
+                    action_mode = ActionMode::parse(&value)
+                        .ok_or_else(|| parser.error(&format!("Invalid actionMode {value:?}")))?
+                        .into();
+                }
+                0x0065_646f_4d67_6e69_646e_6573 => {
+                    let value = parser
+                        .next_token::<String>()?
+                        .unwrap_string("sendingMode")?;
+                    sending_mode = SendingMode::parse(&value)
+                        .ok_or_else(|| parser.error(&format!("Invalid sendingMode {value:?}")))?
+                        .into();
+                }
+                0x6570_7974 => {
+                    let value = parser.next_token::<String>()?.unwrap_string("type")?;
+                    type_ = DispositionType::parse(&value)
+                        .ok_or_else(|| parser.error(&format!("Invalid type {value:?}")))?
+                        .into();
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        match (action_mode, sending_mode, type_) {
+            (Some(action_mode), Some(sending_mode), Some(type_)) => Ok(Disposition {
+                action_mode,
+                sending_mode,
+                type_,
+            }),
+            _ => Err(parser.error("Disposition requires actionMode, sendingMode and type")),
+        }
+    }
+}
+
+impl Disposition {
+    /// Parses the value of a Disposition field, for example
+    /// "manual-action/MDN-sent-manually; displayed".
+    pub fn parse_field(value: &str) -> Option<Self> {
+        let (modes, type_) = value.split_once(';')?;
+        let (action_mode, sending_mode) = modes.split_once('/')?;
+        let type_ = type_.trim();
+
+        Some(Disposition {
+            action_mode: ActionMode::parse(action_mode.trim())?,
+            sending_mode: SendingMode::parse(sending_mode.trim())?,
+            type_: DispositionType::parse(
+                type_
+                    .split_once(|c: char| c == '/' || c.is_whitespace())
+                    .map_or(type_, |(type_, _)| type_),
+            )?,
+        })
+    }
+}
+
+impl Display for Disposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}; {}",
+            self.action_mode.as_str(),
+            self.sending_mode.as_str(),
+            self.type_.as_str()
+        )
+    }
+}
+
+impl ActionMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("manual-action") {
+            Some(ActionMode::Manual)
+        } else if value.eq_ignore_ascii_case("automatic-action") {
+            Some(ActionMode::Automatic)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionMode::Manual => "manual-action",
+            ActionMode::Automatic => "automatic-action",
+        }
+    }
+}
+
+impl SendingMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("mdn-sent-manually") {
+            Some(SendingMode::Manual)
+        } else if value.eq_ignore_ascii_case("mdn-sent-automatically") {
+            Some(SendingMode::Automatic)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SendingMode::Manual => "MDN-sent-manually",
+            SendingMode::Automatic => "MDN-sent-automatically",
+        }
+    }
+}
+
+impl DispositionType {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("deleted") {
+            Some(DispositionType::Deleted)
+        } else if value.eq_ignore_ascii_case("dispatched") {
+            Some(DispositionType::Dispatched)
+        } else if value.eq_ignore_ascii_case("displayed") {
+            Some(DispositionType::Displayed)
+        } else if value.eq_ignore_ascii_case("processed") {
+            Some(DispositionType::Processed)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DispositionType::Deleted => "deleted",
+            DispositionType::Dispatched => "dispatched",
+            DispositionType::Displayed => "displayed",
+            DispositionType::Processed => "processed",
+        }
+    }
+}
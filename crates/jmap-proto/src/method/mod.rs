@@ -23,10 +23,12 @@
 
 use ahash::AHashMap;
 
+pub mod blob;
 pub mod changes;
 pub mod copy;
 pub mod get;
 pub mod import;
+pub mod mdn;
 pub mod parse;
 pub mod query;
 pub mod query_changes;
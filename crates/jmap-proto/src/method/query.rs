@@ -25,7 +25,7 @@ use std::fmt::Display;
 
 use crate::{
     error::method::MethodError,
-    object::{email, mailbox},
+    object::{calendar_event, email, mailbox},
     parser::{json::Parser, Error, Ignore, JsonObjectParser, Token},
     request::{method::MethodObject, RequestProperty, RequestPropertyParser},
     types::{date::UTCDate, id::Id, keyword::Keyword, state::State},
@@ -113,6 +113,13 @@ pub enum Filter {
     HasAnyRole(bool),
     IsSubscribed(bool),
     IsActive(bool),
+    InAddressBook(Id),
+    Uid(String),
+    InCalendars(Vec<Id>),
+    Title(String),
+    Description(String),
+    Scope(String),
+    ResourceType(String),
     _T(String),
 
     And,
@@ -149,6 +156,8 @@ pub enum SortProperty {
     HasKeyword,
     AllInThreadHaveKeyword,
     SomeInThreadHaveKeyword,
+    Start,
+    Used,
     _T(String),
 }
 
@@ -159,6 +168,9 @@ pub enum RequestArguments {
     EmailSubmission,
     SieveScript,
     Principal,
+    ContactCard,
+    CalendarEvent(calendar_event::QueryArguments),
+    Quota,
 }
 
 impl JsonObjectParser for QueryRequest<RequestArguments> {
@@ -173,6 +185,9 @@ impl JsonObjectParser for QueryRequest<RequestArguments> {
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
                 MethodObject::SieveScript => RequestArguments::SieveScript,
                 MethodObject::Principal => RequestArguments::Principal,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
+                MethodObject::Quota => RequestArguments::Quota,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/query",
@@ -428,6 +443,31 @@ pub fn parse_filter(parser: &mut Parser) -> crate::parser::Result<Vec<Filter>> {
                         (0x6576_6974_6341_7369, _) => Filter::IsActive(
                             parser.next_token::<String>()?.unwrap_bool("isActive")?,
                         ),
+                        (0x006b_6f6f_4273_7365_7264_6441_6e69, _) => Filter::InAddressBook(
+                            parser.next_token::<Id>()?.unwrap_string("inAddressBook")?,
+                        ),
+                        (0x0064_6975, _) => {
+                            Filter::Uid(parser.next_token::<String>()?.unwrap_string("uid")?)
+                        }
+                        (0x0073_7261_646e_656c_6143_6e69, _) => {
+                            Filter::InCalendars(<Vec<Id>>::parse(parser)?)
+                        }
+                        (0x0065_6c74_6974, _) => {
+                            Filter::Title(parser.next_token::<String>()?.unwrap_string("title")?)
+                        }
+                        (0x006e_6f69_7470_6972_6373_6564, _) => Filter::Description(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_string("description")?,
+                        ),
+                        (0x0065_706f_6373, _) => {
+                            Filter::Scope(parser.next_token::<String>()?.unwrap_string("scope")?)
+                        }
+                        (0x6570_7954_6563_7275_6f73_6572, _) => Filter::ResourceType(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_string("resourceType")?,
+                        ),
                         _ => {
                             if parser.is_eof || parser.skip_string() {
                                 let filter = Filter::_T(
@@ -569,6 +609,8 @@ impl JsonObjectParser for SortProperty {
             0x6472_6f77_7965_4b73_6168 => Ok(SortProperty::HasKeyword),
             0x4b65_7661_4864_6165_7268_546e_496c_6c61 => Ok(SortProperty::AllInThreadHaveKeyword),
             0x6576_6148_6461_6572_6854_6e49_656d_6f73 => Ok(SortProperty::SomeInThreadHaveKeyword),
+            0x0074_7261_7473 => Ok(SortProperty::Start),
+            0x6465_7375 => Ok(SortProperty::Used),
             _ => {
                 if parser.is_eof || parser.skip_string() {
                     Ok(SortProperty::_T(
@@ -629,6 +671,13 @@ impl Display for Filter {
             Filter::HasAnyRole(_) => "hasAnyRole",
             Filter::IsSubscribed(_) => "isSubscribed",
             Filter::IsActive(_) => "isActive",
+            Filter::InAddressBook(_) => "inAddressBook",
+            Filter::Uid(_) => "uid",
+            Filter::InCalendars(_) => "inCalendars",
+            Filter::Title(_) => "title",
+            Filter::Description(_) => "description",
+            Filter::Scope(_) => "scope",
+            Filter::ResourceType(_) => "resourceType",
             Filter::_T(v) => v.as_str(),
             Filter::And => "and",
             Filter::Or => "or",
@@ -659,6 +708,8 @@ impl Display for SortProperty {
             SortProperty::HasKeyword => "hasKeyword",
             SortProperty::AllInThreadHaveKeyword => "allInThreadHaveKeyword",
             SortProperty::SomeInThreadHaveKeyword => "someInThreadHaveKeyword",
+            SortProperty::Start => "start",
+            SortProperty::Used => "used",
             SortProperty::_T(s) => s,
         })
     }
@@ -673,6 +724,7 @@ impl RequestPropertyParser for RequestArguments {
         match self {
             RequestArguments::Email(args) => args.parse(parser, property),
             RequestArguments::Mailbox(args) => args.parse(parser, property),
+            RequestArguments::CalendarEvent(args) => args.parse(parser, property),
             _ => Ok(false),
         }
     }
@@ -86,6 +86,8 @@ impl JsonObjectParser for QueryChangesRequest {
                 MethodObject::Email => RequestArguments::Email(Default::default()),
                 MethodObject::Mailbox => RequestArguments::Mailbox(Default::default()),
                 MethodObject::EmailSubmission => RequestArguments::EmailSubmission,
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/queryChanges",
@@ -29,7 +29,7 @@ use crate::{
         method::MethodError,
         set::{InvalidProperty, SetError},
     },
-    object::{email_submission, mailbox, sieve, Object},
+    object::{calendar, calendar_event, contact, email_submission, mailbox, sieve, Object},
     parser::{json::Parser, Error, JsonObjectParser, Token},
     request::{
         method::MethodObject,
@@ -70,6 +70,10 @@ pub enum RequestArguments {
     PushSubscription,
     SieveScript(sieve::SetArguments),
     VacationResponse,
+    AddressBook(contact::SetArguments),
+    ContactCard,
+    Calendar(calendar::SetArguments),
+    CalendarEvent(calendar_event::SetArguments),
 }
 
 #[derive(Debug, Clone, Default, serde::Serialize)]
@@ -130,6 +134,10 @@ impl JsonObjectParser for SetRequest<RequestArguments> {
                 MethodObject::PushSubscription => RequestArguments::PushSubscription,
                 MethodObject::VacationResponse => RequestArguments::VacationResponse,
                 MethodObject::SieveScript => RequestArguments::SieveScript(Default::default()),
+                MethodObject::AddressBook => RequestArguments::AddressBook(Default::default()),
+                MethodObject::ContactCard => RequestArguments::ContactCard,
+                MethodObject::Calendar => RequestArguments::Calendar(Default::default()),
+                MethodObject::CalendarEvent => RequestArguments::CalendarEvent(Default::default()),
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/set",
@@ -199,6 +207,23 @@ impl JsonObjectParser for Object<SetValue> {
         while let Some(mut key) = parser.next_dict_key::<SetProperty>()? {
             let value = if !key.is_ref {
                 match &key.property {
+                    Property::AddressBookIds | Property::CalendarIds => {
+                        if key.patch.is_empty() {
+                            SetValue::IdReferences(
+                                <SetValueMap<MaybeReference<Id, String>>>::parse(parser)?.values,
+                            )
+                        } else {
+                            key.patch.push(Value::Bool(bool::parse(parser)?));
+                            SetValue::Patch(key.patch)
+                        }
+                    }
+                    _ if matches!(
+                        parser.ctx,
+                        MethodObject::ContactCard | MethodObject::CalendarEvent
+                    ) =>
+                    {
+                        SetValue::Value(Value::parse_untyped(parser.next_token()?, parser)?)
+                    }
                     Property::Id | Property::ThreadId => parser
                         .next_token::<Id>()?
                         .unwrap_string_or_null("")?
@@ -258,6 +283,8 @@ impl JsonObjectParser for Object<SetValue> {
                     }
                     Property::HasAttachment
                     | Property::IsSubscribed
+                    | Property::IsDefault
+                    | Property::IsVisible
                     | Property::IsEnabled
                     | Property::IsActive => parser
                         .next_token::<String>()?
@@ -385,6 +412,9 @@ impl RequestPropertyParser for RequestArguments {
             RequestArguments::Mailbox(args) => args.parse(parser, property),
             RequestArguments::EmailSubmission(args) => args.parse(parser, property),
             RequestArguments::SieveScript(args) => args.parse(parser, property),
+            RequestArguments::AddressBook(args) => args.parse(parser, property),
+            RequestArguments::Calendar(args) => args.parse(parser, property),
+            RequestArguments::CalendarEvent(args) => args.parse(parser, property),
             _ => Ok(false),
         }
     }
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    parser::{json::Parser, Ignore},
+    request::{RequestProperty, RequestPropertyParser},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct SetArguments {
+    pub send_scheduling_messages: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QueryArguments {
+    pub expand_recurrences: Option<bool>,
+    pub time_zone: Option<String>,
+}
+
+impl RequestPropertyParser for SetArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        if property.hash[0] == 0x654d_676e_696c_7564_6568_6353_646e_6573
+            && property.hash[1] == 0x7365_6761_7373
+        {
+            self.send_scheduling_messages = parser
+                .next_token::<Ignore>()?
+                .unwrap_bool_or_null("sendSchedulingMessages")?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl RequestPropertyParser for QueryArguments {
+    fn parse(
+        &mut self,
+        parser: &mut Parser,
+        property: RequestProperty,
+    ) -> crate::parser::Result<bool> {
+        match (&property.hash[0], &property.hash[1]) {
+            (0x6563_6e65_7272_7563_6552_646e_6170_7865, 0x0073) => {
+                self.expand_recurrences = parser
+                    .next_token::<Ignore>()?
+                    .unwrap_bool_or_null("expandRecurrences")?;
+            }
+            (0x656e_6f5a_656d_6974, 0) => {
+                self.time_zone = parser
+                    .next_token::<String>()?
+                    .unwrap_string_or_null("timeZone")?;
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
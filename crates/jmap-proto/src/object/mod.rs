@@ -21,6 +21,9 @@
  * for more details.
 */
 
+pub mod calendar;
+pub mod calendar_event;
+pub mod contact;
 pub mod email;
 pub mod email_submission;
 pub mod index;
@@ -75,6 +78,36 @@ impl Object<Value> {
     pub fn get(&self, property: &Property) -> &Value {
         self.properties.get(property).unwrap_or(&Value::Null)
     }
+
+    /// Applies a JSON pointer patch, setting `value` at the given path.
+    /// Returns false if an intermediate object does not exist.
+    pub fn patch(&mut self, pointer: &str, value: Value) -> bool {
+        let mut path = pointer
+            .split('/')
+            .map(|item| item.replace("~1", "/").replace("~0", "~"));
+        let mut property = if let Some(item) = path.next() {
+            Property::parse(&item)
+        } else {
+            return false;
+        };
+        let mut object = self;
+
+        for item in path {
+            object = if let Some(Value::Object(object)) = object.properties.get_mut(&property) {
+                object
+            } else {
+                return false;
+            };
+            property = Property::_T(item);
+        }
+
+        if value != Value::Null {
+            object.set(property, value);
+        } else {
+            object.remove(&property);
+        }
+        true
+    }
 }
 
 impl ToBitmaps for Value {
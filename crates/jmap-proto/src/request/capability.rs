@@ -44,6 +44,12 @@ pub enum Capability {
     WebSocket = 1 << 6,
     #[serde(rename(serialize = "urn:ietf:params:jmap:sieve"))]
     Sieve = 1 << 7,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:quota"))]
+    Quota = 1 << 8,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:blob"))]
+    Blob = 1 << 9,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:mdn"))]
+    Mdn = 1 << 10,
 }
 
 impl JsonObjectParser for Capability {
@@ -71,6 +77,9 @@ impl JsonObjectParser for Capability {
                 0x0073_7261_646e_656c_6163 => Ok(Capability::Calendars),
                 0x0074_656b_636f_7362_6577 => Ok(Capability::WebSocket),
                 0x0065_7665_6973 => Ok(Capability::Sieve),
+                0x0061_746f_7571 => Ok(Capability::Quota),
+                0x626f_6c62 => Ok(Capability::Blob),
+                0x006e_646d => Ok(Capability::Mdn),
                 _ => Err(parser.error_capability()),
             },
             Err(Error::Method(_)) => Err(parser.error_capability()),
@@ -45,6 +45,12 @@ pub enum MethodObject {
     VacationResponse,
     SieveScript,
     Principal,
+    AddressBook,
+    ContactCard,
+    Calendar,
+    CalendarEvent,
+    Quota,
+    Mdn,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -59,6 +65,9 @@ pub enum MethodFunction {
     Parse,
     Validate,
     Echo,
+    Upload,
+    Lookup,
+    Send,
 }
 
 impl JsonObjectParser for MethodName {
@@ -109,6 +118,12 @@ impl JsonObjectParser for MethodName {
                 0x6e6f_6974_7069_7263_7362_7553_6873_7550 => MethodObject::PushSubscription,
                 0x0074_7069_7263_5365_7665_6953 => MethodObject::SieveScript,
                 0x006c_6170_6963_6e69_7250 => MethodObject::Principal,
+                0x006b_6f6f_4273_7365_7264_6441 => MethodObject::AddressBook,
+                0x0064_7261_4374_6361_746e_6f43 => MethodObject::ContactCard,
+                0x7261_646e_656c_6143 => MethodObject::Calendar,
+                0x0074_6e65_7645_7261_646e_656c_6143 => MethodObject::CalendarEvent,
+                0x0061_746f_7551 => MethodObject::Quota,
+                0x004e_444d => MethodObject::Mdn,
                 0x6572_6f43 => MethodObject::Core,
                 _ => return Err(parser.error_value()),
             },
@@ -123,6 +138,9 @@ impl JsonObjectParser for MethodName {
                 0x0065_7372_6170 => MethodFunction::Parse,
                 0x6574_6164_696c_6176 => MethodFunction::Validate,
                 0x6f68_6365 => MethodFunction::Echo,
+                0x6461_6f6c_7075 => MethodFunction::Upload,
+                0x7075_6b6f_6f6c => MethodFunction::Lookup,
+                0x646e_6573 => MethodFunction::Send,
                 _ => return Err(parser.error_value()),
             },
         })
@@ -151,6 +169,9 @@ impl MethodName {
         match (self.fnc, self.obj) {
             (MethodFunction::Echo, MethodObject::Core) => "Core/echo",
             (MethodFunction::Copy, MethodObject::Blob) => "Blob/copy",
+            (MethodFunction::Get, MethodObject::Blob) => "Blob/get",
+            (MethodFunction::Upload, MethodObject::Blob) => "Blob/upload",
+            (MethodFunction::Lookup, MethodObject::Blob) => "Blob/lookup",
             (MethodFunction::Get, MethodObject::PushSubscription) => "PushSubscription/get",
             (MethodFunction::Set, MethodObject::PushSubscription) => "PushSubscription/set",
             (MethodFunction::Get, MethodObject::Mailbox) => "Mailbox/get",
@@ -188,6 +209,29 @@ impl MethodName {
             (MethodFunction::Get, MethodObject::Principal) => "Principal/get",
             (MethodFunction::Set, MethodObject::Principal) => "Principal/set",
             (MethodFunction::Query, MethodObject::Principal) => "Principal/query",
+            (MethodFunction::Get, MethodObject::AddressBook) => "AddressBook/get",
+            (MethodFunction::Changes, MethodObject::AddressBook) => "AddressBook/changes",
+            (MethodFunction::Set, MethodObject::AddressBook) => "AddressBook/set",
+            (MethodFunction::Get, MethodObject::ContactCard) => "ContactCard/get",
+            (MethodFunction::Changes, MethodObject::ContactCard) => "ContactCard/changes",
+            (MethodFunction::Query, MethodObject::ContactCard) => "ContactCard/query",
+            (MethodFunction::QueryChanges, MethodObject::ContactCard) => "ContactCard/queryChanges",
+            (MethodFunction::Set, MethodObject::ContactCard) => "ContactCard/set",
+            (MethodFunction::Get, MethodObject::Calendar) => "Calendar/get",
+            (MethodFunction::Changes, MethodObject::Calendar) => "Calendar/changes",
+            (MethodFunction::Set, MethodObject::Calendar) => "Calendar/set",
+            (MethodFunction::Get, MethodObject::CalendarEvent) => "CalendarEvent/get",
+            (MethodFunction::Changes, MethodObject::CalendarEvent) => "CalendarEvent/changes",
+            (MethodFunction::Query, MethodObject::CalendarEvent) => "CalendarEvent/query",
+            (MethodFunction::QueryChanges, MethodObject::CalendarEvent) => {
+                "CalendarEvent/queryChanges"
+            }
+            (MethodFunction::Set, MethodObject::CalendarEvent) => "CalendarEvent/set",
+            (MethodFunction::Get, MethodObject::Quota) => "Quota/get",
+            (MethodFunction::Changes, MethodObject::Quota) => "Quota/changes",
+            (MethodFunction::Query, MethodObject::Quota) => "Quota/query",
+            (MethodFunction::Send, MethodObject::Mdn) => "MDN/send",
+            (MethodFunction::Parse, MethodObject::Mdn) => "MDN/parse",
             _ => "error",
         }
     }
@@ -208,6 +252,12 @@ impl Display for MethodObject {
             MethodObject::Mailbox => "Mailbox",
             MethodObject::Thread => "Thread",
             MethodObject::Email => "Email",
+            MethodObject::AddressBook => "AddressBook",
+            MethodObject::ContactCard => "ContactCard",
+            MethodObject::Calendar => "Calendar",
+            MethodObject::CalendarEvent => "CalendarEvent",
+            MethodObject::Quota => "Quota",
+            MethodObject::Mdn => "MDN",
         })
     }
 }
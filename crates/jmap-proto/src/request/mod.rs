@@ -36,10 +36,12 @@ use std::{
 use crate::{
     error::method::MethodError,
     method::{
+        blob::{BlobGetRequest, BlobLookupRequest, BlobUploadRequest},
         changes::ChangesRequest,
         copy::{self, CopyBlobRequest, CopyRequest},
         get::{self, GetRequest},
         import::ImportEmailRequest,
+        mdn::{MdnParseRequest, MdnSendRequest},
         parse::ParseEmailRequest,
         query::{self, QueryRequest},
         query_changes::QueryChangesRequest,
@@ -80,8 +82,13 @@ pub enum RequestMethod {
     Changes(ChangesRequest),
     Copy(CopyRequest<copy::RequestArguments>),
     CopyBlob(CopyBlobRequest),
+    GetBlob(BlobGetRequest),
+    UploadBlob(BlobUploadRequest),
+    LookupBlob(BlobLookupRequest),
     ImportEmail(ImportEmailRequest),
     ParseEmail(ParseEmailRequest),
+    SendMdn(MdnSendRequest),
+    ParseMdn(MdnParseRequest),
     QueryChanges(QueryChangesRequest),
     Query(QueryRequest<query::RequestArguments>),
     SearchSnippet(GetSearchSnippetRequest),
@@ -29,10 +29,12 @@ use crate::{
         request::{RequestError, RequestLimitError},
     },
     method::{
+        blob::{BlobGetRequest, BlobLookupRequest, BlobUploadRequest},
         changes::ChangesRequest,
         copy::{CopyBlobRequest, CopyRequest},
         get::GetRequest,
         import::ImportEmailRequest,
+        mdn::{MdnParseRequest, MdnSendRequest},
         parse::ParseEmailRequest,
         query::QueryRequest,
         query_changes::QueryChangesRequest,
@@ -129,14 +131,16 @@ impl Request {
                         let start_depth_dict = parser.depth_dict;
 
                         let method = match (&method_name.fnc, &method_name.obj) {
-                            (MethodFunction::Get, _) => {
-                                if method_name.obj != MethodObject::SearchSnippet {
-                                    GetRequest::parse(parser).map(RequestMethod::Get)
-                                } else {
+                            (MethodFunction::Get, _) => match method_name.obj {
+                                MethodObject::SearchSnippet => {
                                     GetSearchSnippetRequest::parse(parser)
                                         .map(RequestMethod::SearchSnippet)
                                 }
-                            }
+                                MethodObject::Blob => {
+                                    BlobGetRequest::parse(parser).map(RequestMethod::GetBlob)
+                                }
+                                _ => GetRequest::parse(parser).map(RequestMethod::Get),
+                            },
                             (MethodFunction::Query, _) => {
                                 QueryRequest::parse(parser).map(RequestMethod::Query)
                             }
@@ -155,12 +159,24 @@ impl Request {
                             (MethodFunction::Copy, MethodObject::Blob) => {
                                 CopyBlobRequest::parse(parser).map(RequestMethod::CopyBlob)
                             }
+                            (MethodFunction::Upload, MethodObject::Blob) => {
+                                BlobUploadRequest::parse(parser).map(RequestMethod::UploadBlob)
+                            }
+                            (MethodFunction::Lookup, MethodObject::Blob) => {
+                                BlobLookupRequest::parse(parser).map(RequestMethod::LookupBlob)
+                            }
                             (MethodFunction::Import, MethodObject::Email) => {
                                 ImportEmailRequest::parse(parser).map(RequestMethod::ImportEmail)
                             }
                             (MethodFunction::Parse, MethodObject::Email) => {
                                 ParseEmailRequest::parse(parser).map(RequestMethod::ParseEmail)
                             }
+                            (MethodFunction::Send, MethodObject::Mdn) => {
+                                MdnSendRequest::parse(parser).map(RequestMethod::SendMdn)
+                            }
+                            (MethodFunction::Parse, MethodObject::Mdn) => {
+                                MdnParseRequest::parse(parser).map(RequestMethod::ParseMdn)
+                            }
                             (MethodFunction::Validate, MethodObject::SieveScript) => {
                                 ValidateSieveScriptRequest::parse(parser)
                                     .map(RequestMethod::ValidateScript)
@@ -29,10 +29,12 @@ use std::collections::HashMap;
 use crate::{
     error::method::MethodError,
     method::{
+        blob::{BlobGetResponse, BlobLookupResponse, BlobUploadResponse},
         changes::ChangesResponse,
         copy::{CopyBlobResponse, CopyResponse},
         get::GetResponse,
         import::ImportEmailResponse,
+        mdn::{MdnParseResponse, MdnSendResponse},
         parse::ParseEmailResponse,
         query::QueryResponse,
         query_changes::QueryChangesResponse,
@@ -54,8 +56,13 @@ pub enum ResponseMethod {
     Changes(ChangesResponse),
     Copy(CopyResponse),
     CopyBlob(CopyBlobResponse),
+    GetBlob(BlobGetResponse),
+    UploadBlob(BlobUploadResponse),
+    LookupBlob(BlobLookupResponse),
     ImportEmail(ImportEmailResponse),
     ParseEmail(ParseEmailResponse),
+    SendMdn(MdnSendResponse),
+    ParseMdn(MdnParseResponse),
     QueryChanges(QueryChangesResponse),
     Query(QueryResponse),
     SearchSnippet(GetSearchSnippetResponse),
@@ -155,6 +162,24 @@ impl From<CopyBlobResponse> for ResponseMethod {
     }
 }
 
+impl From<BlobGetResponse> for ResponseMethod {
+    fn from(get_blob: BlobGetResponse) -> Self {
+        ResponseMethod::GetBlob(get_blob)
+    }
+}
+
+impl From<BlobUploadResponse> for ResponseMethod {
+    fn from(upload_blob: BlobUploadResponse) -> Self {
+        ResponseMethod::UploadBlob(upload_blob)
+    }
+}
+
+impl From<BlobLookupResponse> for ResponseMethod {
+    fn from(lookup_blob: BlobLookupResponse) -> Self {
+        ResponseMethod::LookupBlob(lookup_blob)
+    }
+}
+
 impl From<ImportEmailResponse> for ResponseMethod {
     fn from(import_email: ImportEmailResponse) -> Self {
         ResponseMethod::ImportEmail(import_email)
@@ -167,6 +192,18 @@ impl From<ParseEmailResponse> for ResponseMethod {
     }
 }
 
+impl From<MdnSendResponse> for ResponseMethod {
+    fn from(send_mdn: MdnSendResponse) -> Self {
+        ResponseMethod::SendMdn(send_mdn)
+    }
+}
+
+impl From<MdnParseResponse> for ResponseMethod {
+    fn from(parse_mdn: MdnParseResponse) -> Self {
+        ResponseMethod::ParseMdn(parse_mdn)
+    }
+}
+
 impl From<QueryChangesResponse> for ResponseMethod {
     fn from(query_changes: QueryChangesResponse) -> Self {
         ResponseMethod::QueryChanges(query_changes)
@@ -34,6 +34,7 @@ use crate::{
         RequestMethod,
     },
     types::{
+        blob::BlobId,
         id::Id,
         property::Property,
         value::{MaybePatchValue, SetValue, Value},
@@ -183,6 +184,32 @@ impl Response {
                     }
                 }
             }
+            RequestMethod::GetBlob(request) => {
+                // Resolve blob id references
+                if let Some(MaybeReference::Reference(reference)) = &request.ids {
+                    request.ids = Some(MaybeReference::Value(
+                        self.eval_result_references(reference)
+                            .unwrap_blob_ids(reference)?,
+                    ));
+                }
+
+                // Resolve properties references
+                if let Some(MaybeReference::Reference(reference)) = &request.properties {
+                    request.properties = Some(MaybeReference::Value(
+                        self.eval_result_references(reference)
+                            .unwrap_properties(reference)?,
+                    ));
+                }
+            }
+            RequestMethod::LookupBlob(request) => {
+                // Resolve blob id references
+                if let MaybeReference::Reference(reference) = &request.ids {
+                    request.ids = MaybeReference::Value(
+                        self.eval_result_references(reference)
+                            .unwrap_blob_ids(reference)?,
+                    );
+                }
+            }
             RequestMethod::SearchSnippet(request) => {
                 // Resolve emailIds references
                 if let MaybeReference::Reference(reference) = &request.email_ids {
@@ -420,6 +447,38 @@ impl EvalResult {
         }
     }
 
+    pub fn unwrap_blob_ids(self, rr: &ResultReference) -> Result<Vec<BlobId>, MethodError> {
+        if let EvalResult::Values(values) = self {
+            let mut blob_ids = Vec::with_capacity(values.len());
+            for value in values {
+                match value {
+                    Value::BlobId(blob_id) => blob_ids.push(blob_id),
+                    Value::List(list) => {
+                        for value in list {
+                            if let Value::BlobId(blob_id) = value {
+                                blob_ids.push(blob_id);
+                            } else {
+                                return Err(MethodError::InvalidResultReference(format!(
+                                    "Failed to evaluate {rr} result reference."
+                                )));
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(MethodError::InvalidResultReference(format!(
+                            "Failed to evaluate {rr} result reference."
+                        )))
+                    }
+                }
+            }
+            Ok(blob_ids)
+        } else {
+            Err(MethodError::InvalidResultReference(format!(
+                "Failed to evaluate {rr} result reference."
+            )))
+        }
+    }
+
     pub fn unwrap_properties(self, rr: &ResultReference) -> Result<Vec<Property>, MethodError> {
         if let EvalResult::Properties(properties) = self {
             Ok(properties)
@@ -33,7 +33,10 @@ use utils::codec::{
     leb128::{Leb128Iterator, Leb128Writer},
 };
 
-use crate::parser::{base32::JsonBase32Reader, json::Parser, JsonObjectParser};
+use crate::{
+    parser::{base32::JsonBase32Reader, json::Parser, JsonObjectParser},
+    request::reference::MaybeReference,
+};
 
 use super::collection::Collection;
 
@@ -111,6 +114,22 @@ impl JsonObjectParser for BlobId {
     }
 }
 
+impl JsonObjectParser for MaybeReference<BlobId, String> {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let value = String::parse(parser)?;
+        if let Some(reference) = value.strip_prefix('#') {
+            Ok(MaybeReference::Reference(reference.to_string()))
+        } else {
+            BlobId::from_base32(&value)
+                .map(MaybeReference::Value)
+                .ok_or_else(|| parser.error_value())
+        }
+    }
+}
+
 impl BlobId {
     pub fn new(kind: BlobKind) -> Self {
         BlobId {
@@ -27,7 +27,10 @@ use mail_parser::RfcHeader;
 use serde::Serialize;
 use store::write::{DeserializeFrom, SerializeInto};
 
-use crate::parser::{json::Parser, Error, JsonObjectParser};
+use crate::{
+    parser::{json::Parser, Error, JsonObjectParser},
+    request::method::MethodObject,
+};
 
 use super::{acl::Acl, id::Id, keyword::Keyword, value::Value};
 
@@ -130,6 +133,35 @@ pub enum Property {
     MayCreateChild,
     MayRename,
     MaySubmit,
+    Metadata,
+    AddressBookIds,
+    Uid,
+    IsDefault,
+    MayRead,
+    MayWrite,
+    MayShare,
+    FullName,
+    CalendarIds,
+    Color,
+    IsVisible,
+    Title,
+    MayReadFreeBusy,
+    MayWriteAll,
+    MayWriteOwn,
+    MayUpdatePrivate,
+    MayRSVP,
+    MayAdmin,
+    Used,
+    HardLimit,
+    WarnLimit,
+    SoftLimit,
+    Scope,
+    ResourceType,
+    Data,
+    DataAsText,
+    DataAsBase64,
+    DigestSha,
+    DigestSha256,
     _T(String),
 }
 
@@ -167,6 +199,11 @@ impl JsonObjectParser for Property {
                 }
             } else if ch == b':' && first_char == b'h' && hash == 0x0072_6564_6165 {
                 return parse_header_property(parser);
+            } else if ch == b':'
+                && first_char == b'd'
+                && matches!(hash, 0x0061_7461 | 0x0074_7365_6769)
+            {
+                return parse_blob_property(parser, hash);
             } else {
                 return parser.invalid_property();
             }
@@ -238,7 +275,18 @@ impl JsonObjectParser for SetProperty {
 
         if is_patch {
             match &property {
-                Property::MailboxIds | Property::Members => match Id::parse(parser) {
+                _ if matches!(
+                    parser.ctx,
+                    MethodObject::ContactCard | MethodObject::CalendarEvent
+                ) && !matches!(property, Property::AddressBookIds | Property::CalendarIds) =>
+                {
+                    // JSContact patches are resolved against the stored card
+                    property = parser.invalid_property()?;
+                }
+                Property::MailboxIds
+                | Property::Members
+                | Property::AddressBookIds
+                | Property::CalendarIds => match Id::parse(parser) {
                     Ok(id) => {
                         patch.push(Value::Id(id));
                     }
@@ -326,6 +374,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x6c63 => Property::Acl,
             0x7365_7361_696c => Property::Aliases,
             0x7374_6e65_6d68_6361_7474 => Property::Attachments,
+            0x0073_6449_6b6f_6f42_7373_6572_6464 => Property::AddressBookIds,
             _ => return None,
         },
         b'b' => match hash {
@@ -336,6 +385,8 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'c' => match hash {
+            0x7364_4972_6164_6e65_6c61 => Property::CalendarIds,
+            0x726f_6c6f => Property::Color,
             0x0073_6569_7469_6c69_6261_7061 => Property::Capabilities,
             0x63 => Property::Cc,
             0x7465_7372_6168 => Property::Charset,
@@ -348,6 +399,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0064_4974_6e65_696c_4365_6369_7665 => Property::DeviceClientId,
             0x6e6f_6974_6973_6f70_7369 => Property::Disposition,
             0x0073_6449_626f_6c42_6e73 => Property::DsnBlobIds,
+            0x0061_7461 => Property::Data,
             _ => return None,
         },
         b'e' => match hash {
@@ -364,6 +416,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'h' => match hash {
+            0x7469_6d69_4c64_7261 => Property::HardLimit,
             0x746e_656d_6863_6174_7441_7361 => Property::HasAttachment,
             0x7372_6564_6165 => Property::Headers,
             0x0079_646f_426c_6d74 => Property::HtmlBody,
@@ -371,12 +424,14 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             _ => return None,
         },
         b'i' => match hash {
+            0x656c_6269_7369_5673 => Property::IsVisible,
             0x64 => Property::Id,
             0x0064_4979_7469_746e_6564 => Property::IdentityId,
             0x6f54_796c_7065_526e => Property::InReplyTo,
             0x0065_7669_7463_4173 => Property::IsActive,
             0x6465_6c62_616e_4573 => Property::IsEnabled,
             0x0064_6562_6972_6373_6275_5373 => Property::IsSubscribed,
+            0x746c_7561_6665_4473 => Property::IsDefault,
             _ => return None,
         },
         b'k' => match hash {
@@ -415,23 +470,27 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
         },
         b'r' => match hash {
             0x0074_4164_6576_6965_6365 => Property::ReceivedAt,
+            0x0065_7079_5465_6372_756f_7365 => Property::ResourceType,
             0x0073_6563_6e65_7265_6665 => Property::References,
             0x6f54_796c_7065 => Property::ReplyTo,
             0x0065_6c6f => Property::Role,
             _ => return None,
         },
         b's' => match hash {
+            0x6570_6f63 => Property::Scope,
             0x0074_6572_6365 => Property::Secret,
             0x0074_4164_6e65 => Property::SendAt,
             0x0072_6564_6e65 => Property::Sender,
             0x0074_4174_6e65 => Property::SentAt,
             0x0065_7a69 => Property::Size,
+            0x7469_6d69_4c74_666f => Property::SoftLimit,
             0x7265_6472_4f74_726f => Property::SortOrder,
             0x7463_656a_6275 => Property::Subject,
             0x7374_7261_5062_7573 => Property::SubParts,
             _ => return None,
         },
         b't' => match hash {
+            0x656c_7469 => Property::Title,
             0x0079_646f_4274_7865 => Property::TextBody,
             0x6572_7574_616e_6769_5374_7865 => Property::TextSignature,
             0x0064_4964_6165_7268 => Property::ThreadId,
@@ -449,16 +508,44 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0073_6c69_616d_4564_6165_726e => Property::UnreadEmails,
             0x7364_6165_7268_5464_6165_726e => Property::UnreadThreads,
             0x6c72 => Property::Url,
+            0x6469 => Property::Uid,
+            0x0064_6573 => Property::Used,
             _ => return None,
         },
         b'v' => match hash {
             0x0065_646f_436e_6f69_7461_6369_6669_7265 => Property::VerificationCode,
             _ => return None,
         },
+        b'w' => match hash {
+            0x7469_6d69_4c6e_7261 => Property::WarnLimit,
+            _ => return None,
+        },
         _ => return None,
     })
 }
 
+fn parse_blob_property(parser: &mut Parser, prefix: u128) -> crate::parser::Result<Property> {
+    let mut hash = 0;
+    let mut shift = 0;
+
+    while let Some(ch) = parser.next_unescaped()? {
+        if shift < 128 {
+            hash |= (ch as u128) << shift;
+            shift += 8;
+        } else {
+            return parser.invalid_property();
+        }
+    }
+
+    match (prefix, hash) {
+        (0x0061_7461, 0x7478_6554_7361) => Ok(Property::DataAsText),
+        (0x0061_7461, 0x3436_6573_6142_7361) => Ok(Property::DataAsBase64),
+        (0x0074_7365_6769, 0x0061_6873) => Ok(Property::DigestSha),
+        (0x0074_7365_6769, 0x0036_3532_2d61_6873) => Ok(Property::DigestSha256),
+        _ => parser.invalid_property(),
+    }
+}
+
 fn parse_header_property(parser: &mut Parser) -> crate::parser::Result<Property> {
     let hdr_start_pos = parser.pos;
     let mut has_next = false;
@@ -614,6 +701,15 @@ impl JsonObjectParser for ObjectProperty {
                 0x656d_616e_6552_7961 => Property::MayRename,
                 0x6574_656c_6544_7961 => Property::MayDelete,
                 0x7469_6d62_7553_7961 => Property::MaySubmit,
+                0x6461_6552_7961 => Property::MayRead,
+                0x0065_7469_7257_7961 => Property::MayWrite,
+                0x7973_7542_6565_7246_6461_6552_7961 => Property::MayReadFreeBusy,
+                0x6c6c_4165_7469_7257_7961 => Property::MayWriteAll,
+                0x6e77_4f65_7469_7257_7961 => Property::MayWriteOwn,
+                0x0065_7461_7669_7250_6574_6164_7055_7961 => Property::MayUpdatePrivate,
+                0x5056_5352_7961 => Property::MayRSVP,
+                0x006e_696d_6441_7961 => Property::MayAdmin,
+                0x0065_7261_6853_7961 => Property::MayShare,
                 _ => parser.invalid_property()?,
             },
             b'n' => match hash {
@@ -810,6 +906,35 @@ impl Display for Property {
             Property::MayCreateChild => write!(f, "mayCreateChild"),
             Property::MayRename => write!(f, "mayRename"),
             Property::MaySubmit => write!(f, "maySubmit"),
+            Property::Metadata => write!(f, "metadata"),
+            Property::AddressBookIds => write!(f, "addressBookIds"),
+            Property::Uid => write!(f, "uid"),
+            Property::IsDefault => write!(f, "isDefault"),
+            Property::MayRead => write!(f, "mayRead"),
+            Property::MayWrite => write!(f, "mayWrite"),
+            Property::MayShare => write!(f, "mayShare"),
+            Property::FullName => write!(f, "fullName"),
+            Property::CalendarIds => write!(f, "calendarIds"),
+            Property::Color => write!(f, "color"),
+            Property::IsVisible => write!(f, "isVisible"),
+            Property::Title => write!(f, "title"),
+            Property::MayReadFreeBusy => write!(f, "mayReadFreeBusy"),
+            Property::MayWriteAll => write!(f, "mayWriteAll"),
+            Property::MayWriteOwn => write!(f, "mayWriteOwn"),
+            Property::MayUpdatePrivate => write!(f, "mayUpdatePrivate"),
+            Property::MayRSVP => write!(f, "mayRSVP"),
+            Property::MayAdmin => write!(f, "mayAdmin"),
+            Property::Used => write!(f, "used"),
+            Property::HardLimit => write!(f, "hardLimit"),
+            Property::WarnLimit => write!(f, "warnLimit"),
+            Property::SoftLimit => write!(f, "softLimit"),
+            Property::Scope => write!(f, "scope"),
+            Property::ResourceType => write!(f, "resourceType"),
+            Property::Data => write!(f, "data"),
+            Property::DataAsText => write!(f, "data:asText"),
+            Property::DataAsBase64 => write!(f, "data:asBase64"),
+            Property::DigestSha => write!(f, "digest:sha"),
+            Property::DigestSha256 => write!(f, "digest:sha-256"),
             Property::_T(s) => write!(f, "{s}"),
         }
     }
@@ -983,6 +1108,35 @@ impl From<&Property> for u8 {
             Property::Id => 94,
             Property::IdentityId => 95,
             Property::InReplyTo => 96,
+            Property::Metadata => 98,
+            Property::AddressBookIds => 99,
+            Property::Uid => 100,
+            Property::IsDefault => 101,
+            Property::MayRead => 102,
+            Property::MayWrite => 103,
+            Property::MayShare => 104,
+            Property::FullName => 105,
+            Property::CalendarIds => 106,
+            Property::Color => 107,
+            Property::IsVisible => 108,
+            Property::Title => 109,
+            Property::MayReadFreeBusy => 110,
+            Property::MayWriteAll => 111,
+            Property::MayWriteOwn => 112,
+            Property::MayUpdatePrivate => 113,
+            Property::MayRSVP => 114,
+            Property::MayAdmin => 115,
+            Property::Used => 116,
+            Property::HardLimit => 117,
+            Property::WarnLimit => 118,
+            Property::SoftLimit => 119,
+            Property::Scope => 120,
+            Property::ResourceType => 121,
+            Property::Data => 122,
+            Property::DataAsText => 123,
+            Property::DataAsBase64 => 124,
+            Property::DigestSha => 125,
+            Property::DigestSha256 => 126,
             Property::_T(_) => 97,
         }
     }
@@ -1114,6 +1268,35 @@ impl SerializeInto for Property {
             Property::Id => 94,
             Property::IdentityId => 95,
             Property::InReplyTo => 96,
+            Property::Metadata => 98,
+            Property::AddressBookIds => 99,
+            Property::Uid => 100,
+            Property::IsDefault => 101,
+            Property::MayRead => 102,
+            Property::MayWrite => 103,
+            Property::MayShare => 104,
+            Property::FullName => 105,
+            Property::CalendarIds => 106,
+            Property::Color => 107,
+            Property::IsVisible => 108,
+            Property::Title => 109,
+            Property::MayReadFreeBusy => 110,
+            Property::MayWriteAll => 111,
+            Property::MayWriteOwn => 112,
+            Property::MayUpdatePrivate => 113,
+            Property::MayRSVP => 114,
+            Property::MayAdmin => 115,
+            Property::Used => 116,
+            Property::HardLimit => 117,
+            Property::WarnLimit => 118,
+            Property::SoftLimit => 119,
+            Property::Scope => 120,
+            Property::ResourceType => 121,
+            Property::Data => 122,
+            Property::DataAsText => 123,
+            Property::DataAsBase64 => 124,
+            Property::DigestSha => 125,
+            Property::DigestSha256 => 126,
             Property::_T(value) => {
                 buf.push(97);
                 value.serialize_into(buf);
@@ -1228,6 +1411,35 @@ impl DeserializeFrom for Property {
             95 => Some(Property::IdentityId),
             96 => Some(Property::InReplyTo),
             97 => String::deserialize_from(bytes).map(Property::_T),
+            98 => Some(Property::Metadata),
+            99 => Some(Property::AddressBookIds),
+            100 => Some(Property::Uid),
+            101 => Some(Property::IsDefault),
+            102 => Some(Property::MayRead),
+            103 => Some(Property::MayWrite),
+            104 => Some(Property::MayShare),
+            105 => Some(Property::FullName),
+            106 => Some(Property::CalendarIds),
+            107 => Some(Property::Color),
+            108 => Some(Property::IsVisible),
+            109 => Some(Property::Title),
+            110 => Some(Property::MayReadFreeBusy),
+            111 => Some(Property::MayWriteAll),
+            112 => Some(Property::MayWriteOwn),
+            113 => Some(Property::MayUpdatePrivate),
+            114 => Some(Property::MayRSVP),
+            115 => Some(Property::MayAdmin),
+            116 => Some(Property::Used),
+            117 => Some(Property::HardLimit),
+            118 => Some(Property::WarnLimit),
+            119 => Some(Property::SoftLimit),
+            120 => Some(Property::Scope),
+            121 => Some(Property::ResourceType),
+            122 => Some(Property::Data),
+            123 => Some(Property::DataAsText),
+            124 => Some(Property::DataAsBase64),
+            125 => Some(Property::DigestSha),
+            126 => Some(Property::DigestSha256),
             _ => None,
         }
     }
@@ -44,7 +44,17 @@ pub enum TypeState {
     Thread = 4,
     #[serde(rename = "Identity")]
     Identity = 5,
-    None = 6,
+    #[serde(rename = "AddressBook")]
+    AddressBook = 6,
+    #[serde(rename = "ContactCard")]
+    ContactCard = 7,
+    #[serde(rename = "Calendar")]
+    Calendar = 8,
+    #[serde(rename = "CalendarEvent")]
+    CalendarEvent = 9,
+    #[serde(rename = "Quota")]
+    Quota = 10,
+    None = 11,
 }
 
 impl BitmapItem for TypeState {
@@ -66,6 +76,11 @@ impl From<u64> for TypeState {
             3 => TypeState::Mailbox,
             4 => TypeState::Thread,
             5 => TypeState::Identity,
+            6 => TypeState::AddressBook,
+            7 => TypeState::ContactCard,
+            8 => TypeState::Calendar,
+            9 => TypeState::CalendarEvent,
+            10 => TypeState::Quota,
             _ => {
                 debug_assert!(false, "Invalid type_state value: {}", value);
                 TypeState::None
@@ -104,6 +119,11 @@ impl JsonObjectParser for TypeState {
             0x0078_6f62_6c69_614d => Ok(TypeState::Mailbox),
             0x6461_6572_6854 => Ok(TypeState::Thread),
             0x7974_6974_6e65_6449 => Ok(TypeState::Identity),
+            0x006b_6f6f_4273_7365_7264_6441 => Ok(TypeState::AddressBook),
+            0x0064_7261_4374_6361_746e_6f43 => Ok(TypeState::ContactCard),
+            0x7261_646e_656c_6143 => Ok(TypeState::Calendar),
+            0x0074_6e65_7645_7261_646e_656c_6143 => Ok(TypeState::CalendarEvent),
+            0x0061_746f_7551 => Ok(TypeState::Quota),
             _ => Err(parser.error_value()),
         }
     }
@@ -132,6 +152,11 @@ impl TryFrom<&str> for TypeState {
             0x0078_6f62_6c69_614d => Ok(TypeState::Mailbox),
             0x6461_6572_6854 => Ok(TypeState::Thread),
             0x7974_6974_6e65_6449 => Ok(TypeState::Identity),
+            0x006b_6f6f_4273_7365_7264_6441 => Ok(TypeState::AddressBook),
+            0x0064_7261_4374_6361_746e_6f43 => Ok(TypeState::ContactCard),
+            0x7261_646e_656c_6143 => Ok(TypeState::Calendar),
+            0x0074_6e65_7645_7261_646e_656c_6143 => Ok(TypeState::CalendarEvent),
+            0x0061_746f_7551 => Ok(TypeState::Quota),
             _ => Err(()),
         }
     }
@@ -146,6 +171,11 @@ impl TypeState {
             TypeState::Mailbox => "Mailbox",
             TypeState::Thread => "Thread",
             TypeState::Identity => "Identity",
+            TypeState::AddressBook => "AddressBook",
+            TypeState::ContactCard => "ContactCard",
+            TypeState::Calendar => "Calendar",
+            TypeState::CalendarEvent => "CalendarEvent",
+            TypeState::Quota => "Quota",
             TypeState::None => "",
         }
     }
@@ -172,6 +202,11 @@ impl DeserializeFrom for TypeState {
             3 => Some(TypeState::Mailbox),
             4 => Some(TypeState::Thread),
             5 => Some(TypeState::Identity),
+            6 => Some(TypeState::AddressBook),
+            7 => Some(TypeState::ContactCard),
+            8 => Some(TypeState::Calendar),
+            9 => Some(TypeState::CalendarEvent),
+            10 => Some(TypeState::Quota),
             _ => None,
         }
     }
@@ -119,6 +119,36 @@ impl Value {
         })
     }
 
+    pub fn parse_untyped(
+        token: Token<String>,
+        parser: &mut Parser<'_>,
+    ) -> crate::parser::Result<Self> {
+        Ok(match token {
+            Token::DictStart => {
+                let mut properties = Object::with_capacity(4);
+                while let Some(key) = parser.next_dict_key::<String>()? {
+                    let value = Value::parse_untyped(parser.next_token()?, parser)?;
+                    properties.append(Property::_T(key), value);
+                }
+                Value::Object(properties)
+            }
+            Token::ArrayStart => {
+                let mut values = Vec::with_capacity(4);
+                loop {
+                    match parser.next_token::<String>()? {
+                        Token::Comma => (),
+                        Token::ArrayEnd => break,
+                        token => {
+                            values.push(Value::parse_untyped(token, parser)?);
+                        }
+                    }
+                }
+                Value::List(values)
+            }
+            token => Value::parse::<String, String>(token, parser)?,
+        })
+    }
+
     pub fn from_property(
         parser: &mut Parser<'_>,
         property: &Property,
@@ -182,7 +212,16 @@ impl Value {
             | Property::MayCreateChild
             | Property::MayRename
             | Property::MayDelete
-            | Property::MaySubmit => Ok(parser
+            | Property::MaySubmit
+            | Property::MayRead
+            | Property::MayWrite
+            | Property::MayShare
+            | Property::MayReadFreeBusy
+            | Property::MayWriteAll
+            | Property::MayWriteOwn
+            | Property::MayUpdatePrivate
+            | Property::MayRSVP
+            | Property::MayAdmin => Ok(parser
                 .next_token::<String>()?
                 .unwrap_bool_or_null("")?
                 .map(Value::Bool)
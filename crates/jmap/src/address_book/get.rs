@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
+};
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    JMAP,
+};
+
+impl JMAP {
+    pub async fn address_book_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.config.get_max_objects)?;
+        let properties = request.unwrap_properties(&[
+            Property::Id,
+            Property::Name,
+            Property::Description,
+            Property::SortOrder,
+            Property::IsDefault,
+            Property::IsSubscribed,
+            Property::MyRights,
+        ]);
+        let account_id = request.account_id.document_id();
+        let mut address_book_ids = self.address_book_get_or_create(account_id).await?;
+        if access_token.is_shared(account_id) {
+            address_book_ids &= self
+                .shared_documents(access_token, account_id, Collection::AddressBook, Acl::Read)
+                .await?;
+        }
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            address_book_ids
+                .iter()
+                .take(self.config.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::AddressBook)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the address book object
+            let document_id = id.document_id();
+            if !address_book_ids.contains(document_id) {
+                response.not_found.push(id);
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::AddressBook,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id);
+                continue;
+            };
+
+            let mut address_book = Object::with_capacity(properties.len());
+            for property in &properties {
+                let value = match property {
+                    Property::Id => Value::Id(id),
+                    Property::Name | Property::Description => values.remove(property),
+                    Property::SortOrder => values
+                        .properties
+                        .remove(property)
+                        .unwrap_or(Value::UnsignedInt(0)),
+                    Property::IsDefault => values
+                        .properties
+                        .remove(property)
+                        .unwrap_or(Value::Bool(false)),
+                    Property::IsSubscribed => values
+                        .properties
+                        .remove(property)
+                        .map(|subscribers| match subscribers {
+                            Value::List(subscribers)
+                                if subscribers
+                                    .contains(&Value::Id(access_token.primary_id().into())) =>
+                            {
+                                Value::Bool(true)
+                            }
+                            _ => Value::Bool(false),
+                        })
+                        .unwrap_or(Value::Bool(false)),
+                    Property::MyRights => {
+                        if access_token.is_shared(account_id) {
+                            let acl = values.effective_acl(access_token);
+                            Object::with_capacity(4)
+                                .with_property(Property::MayRead, acl.contains(Acl::ReadItems))
+                                .with_property(
+                                    Property::MayWrite,
+                                    acl.contains(Acl::AddItems)
+                                        && acl.contains(Acl::ModifyItems)
+                                        && acl.contains(Acl::RemoveItems),
+                                )
+                                .with_property(Property::MayShare, acl.contains(Acl::Administer))
+                                .with_property(Property::MayDelete, acl.contains(Acl::Delete))
+                                .into()
+                        } else {
+                            Object::with_capacity(4)
+                                .with_property(Property::MayRead, true)
+                                .with_property(Property::MayWrite, true)
+                                .with_property(Property::MayShare, true)
+                                .with_property(Property::MayDelete, true)
+                                .into()
+                        }
+                    }
+                    Property::Acl => {
+                        self.acl_get(
+                            values
+                                .properties
+                                .get(&Property::Acl)
+                                .and_then(|v| v.as_list())
+                                .map(|v| &v[..])
+                                .unwrap_or_else(|| &[]),
+                            access_token,
+                            account_id,
+                        )
+                        .await
+                    }
+                    _ => Value::Null,
+                };
+
+                address_book.append(property.clone(), value);
+            }
+
+            // Add result to response
+            response.list.push(address_book);
+        }
+        Ok(response)
+    }
+}
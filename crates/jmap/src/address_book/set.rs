@@ -0,0 +1,552 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        contact::SetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::TypeState,
+        value::{MaybePatchValue, SetValue, Value},
+    },
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+};
+
+use crate::{
+    auth::{acl::EffectiveAcl, AccessToken},
+    contact::set::SCHEMA as CONTACT_SCHEMA,
+    mailbox::set::MailboxSubscribe,
+    JMAP,
+};
+
+use super::DEFAULT_ADDRESS_BOOK_NAME;
+
+struct SetContext<'x> {
+    access_token: &'x AccessToken,
+    is_shared: bool,
+    response: SetResponse,
+}
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Name)
+        .index_as(IndexAs::Text {
+            tokenize: true,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::SortOrder).index_as(IndexAs::Integer),
+    IndexProperty::new(Property::IsSubscribed).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::Acl).index_as(IndexAs::Acl),
+];
+
+impl JMAP {
+    pub async fn address_book_set(
+        &self,
+        mut request: SetRequest<SetArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let on_destroy_remove_contents = request
+            .arguments
+            .on_destroy_remove_contents
+            .unwrap_or(false);
+        let mut ctx = SetContext {
+            is_shared: access_token.is_shared(account_id),
+            access_token,
+            response: self
+                .prepare_set_response(&request, Collection::AddressBook)
+                .await?,
+        };
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        for (id, object) in request.unwrap_create() {
+            if ctx.is_shared {
+                ctx.response.not_created.append(
+                    id,
+                    SetError::forbidden().with_description(
+                        "You are not allowed to create address books in shared accounts.",
+                    ),
+                );
+                continue;
+            }
+
+            match self.address_book_set_item(object, None, &ctx).await? {
+                Ok(builder) => {
+                    let mut batch = BatchBuilder::new();
+                    let document_id = self
+                        .assign_document_id(account_id, Collection::AddressBook)
+                        .await?;
+                    batch
+                        .with_account_id(account_id)
+                        .with_collection(Collection::AddressBook)
+                        .create_document(document_id)
+                        .custom(builder);
+                    changes.log_insert(Collection::AddressBook, document_id);
+                    self.write_batch(batch).await?;
+                    ctx.response.created(id, document_id);
+                }
+                Err(err) => {
+                    ctx.response.not_created.append(id, err);
+                }
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                ctx.response
+                    .not_updated
+                    .append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain address book
+            let document_id = id.document_id();
+            if let Some(address_book) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::AddressBook,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                // Validate ACL
+                if ctx.is_shared {
+                    let acl = address_book.inner.effective_acl(access_token);
+                    if !acl.contains(Acl::Modify) {
+                        ctx.response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to modify this address book.",
+                            ),
+                        );
+                        continue 'update;
+                    } else if object.properties.contains_key(&Property::Acl)
+                        && !acl.contains(Acl::Administer)
+                    {
+                        ctx.response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "You are not allowed to change the permissions of this address book.",
+                            ),
+                        );
+                        continue 'update;
+                    }
+                }
+
+                match self
+                    .address_book_set_item(object, address_book.into(), &ctx)
+                    .await?
+                {
+                    Ok(builder) => {
+                        let mut batch = BatchBuilder::new();
+                        batch
+                            .with_account_id(account_id)
+                            .with_collection(Collection::AddressBook)
+                            .update_document(document_id)
+                            .custom(builder);
+                        if !batch.is_empty() {
+                            match self.store.write(batch.build()).await {
+                                Ok(_) => {
+                                    changes.log_update(Collection::AddressBook, document_id);
+                                }
+                                Err(store::Error::AssertValueFailed) => {
+                                    ctx.response.not_updated.append(id, SetError::forbidden().with_description(
+                                        "Another process modified this address book, please try again.",
+                                    ));
+                                    continue 'update;
+                                }
+                                Err(err) => {
+                                    tracing::error!(
+                                        event = "error",
+                                        context = "address_book_set",
+                                        account_id = account_id,
+                                        error = ?err,
+                                        "Failed to update address book(s).");
+                                    return Err(MethodError::ServerPartialFail);
+                                }
+                            }
+                        }
+                        ctx.response.updated.append(id, None);
+                    }
+                    Err(err) => {
+                        ctx.response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                }
+            } else {
+                ctx.response.not_updated.append(id, SetError::not_found());
+            }
+        }
+
+        // Process deletions
+        let mut did_remove_contacts = false;
+        for id in will_destroy {
+            match self
+                .address_book_destroy(
+                    account_id,
+                    id.document_id(),
+                    &mut changes,
+                    ctx.access_token,
+                    on_destroy_remove_contents,
+                )
+                .await?
+            {
+                Ok(removed_contacts) => {
+                    did_remove_contacts |= removed_contacts;
+                    ctx.response.destroyed.push(id);
+                }
+                Err(err) => {
+                    ctx.response.not_destroyed.append(id, err);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            let state_change =
+                StateChange::new(account_id).with_change(TypeState::AddressBook, changes.change_id);
+            ctx.response.state_change = if did_remove_contacts {
+                state_change.with_change(TypeState::ContactCard, changes.change_id)
+            } else {
+                state_change
+            }
+            .into();
+            ctx.response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(ctx.response)
+    }
+
+    pub async fn address_book_destroy(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        changes: &mut ChangeLogBuilder,
+        access_token: &AccessToken,
+        remove_contents: bool,
+    ) -> Result<Result<bool, SetError>, MethodError> {
+        // Obtain address book
+        let address_book = if let Some(address_book) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::AddressBook,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            address_book
+        } else {
+            return Ok(Err(SetError::not_found()));
+        };
+
+        // The default address book cannot be deleted
+        if address_book.inner.get(&Property::IsDefault) == &Value::Bool(true)
+            && !access_token.is_super_user()
+        {
+            return Ok(Err(SetError::forbidden().with_description(
+                "You are not allowed to delete the default address book.",
+            )));
+        }
+
+        // Validate ACLs
+        if access_token.is_shared(account_id) {
+            let acl = address_book.inner.effective_acl(access_token);
+            if !acl.contains(Acl::Administer) {
+                if !acl.contains(Acl::Delete) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete this address book.",
+                    )));
+                } else if remove_contents && !acl.contains(Acl::RemoveItems) {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "You are not allowed to delete contacts from this address book.",
+                    )));
+                }
+            }
+        }
+
+        // Verify that the address book is empty
+        let contact_ids = self
+            .filter(
+                account_id,
+                Collection::ContactCard,
+                vec![Filter::eq(Property::AddressBookIds, document_id)],
+            )
+            .await?
+            .results;
+        let did_remove_contacts = !contact_ids.is_empty();
+        if did_remove_contacts {
+            if !remove_contents {
+                return Ok(Err(SetError::new(SetErrorType::AddressBookHasContents)
+                    .with_description("Address book is not empty.")));
+            }
+
+            // If the contact is in multiple address books, remove it from the current
+            // address book, otherwise delete it.
+            for contact_id in contact_ids {
+                let contact = if let Some(contact) = self
+                    .get_property::<HashedValue<Object<Value>>>(
+                        account_id,
+                        Collection::ContactCard,
+                        contact_id,
+                        Property::Value,
+                    )
+                    .await?
+                {
+                    contact
+                } else {
+                    continue;
+                };
+                let address_book_ids = contact
+                    .inner
+                    .get(&Property::AddressBookIds)
+                    .as_list()
+                    .map(|ids| {
+                        ids.iter()
+                            .filter(|id| id.try_cast_uint() != Some(document_id as u64))
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                let mut batch = BatchBuilder::new();
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::ContactCard);
+                if !address_book_ids.is_empty() {
+                    batch.update_document(contact_id).custom(
+                        ObjectIndexBuilder::new(CONTACT_SCHEMA)
+                            .with_current(contact)
+                            .with_changes(Object::with_capacity(1).with_property(
+                                Property::AddressBookIds,
+                                Value::List(address_book_ids),
+                            )),
+                    );
+                    changes.log_update(Collection::ContactCard, contact_id);
+                } else {
+                    batch
+                        .delete_document(contact_id)
+                        .custom(ObjectIndexBuilder::new(CONTACT_SCHEMA).with_current(contact));
+                    changes.log_delete(Collection::ContactCard, contact_id);
+                }
+
+                match self.store.write(batch.build()).await {
+                    Ok(_) => (),
+                    Err(store::Error::AssertValueFailed) => {
+                        return Ok(Err(SetError::forbidden().with_description(concat!(
+                            "Another process modified a contact in this address book ",
+                            "while deleting it, please try again."
+                        ))));
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            event = "error",
+                            context = "address_book_set",
+                            account_id = account_id,
+                            address_book_id = document_id,
+                            contact_id = contact_id,
+                            error = ?err,
+                            "Failed to update contact while deleting address book.");
+                        return Err(MethodError::ServerPartialFail);
+                    }
+                }
+            }
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .delete_document(document_id)
+            .custom(ObjectIndexBuilder::new(SCHEMA).with_current(address_book));
+
+        match self.store.write(batch.build()).await {
+            Ok(_) => {
+                changes.log_delete(Collection::AddressBook, document_id);
+                Ok(Ok(did_remove_contacts))
+            }
+            Err(store::Error::AssertValueFailed) => Ok(Err(SetError::forbidden()
+                .with_description(concat!(
+                    "Another process modified this address book ",
+                    "while deleting it, please try again."
+                )))),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "address_book_set",
+                    account_id = account_id,
+                    document_id = document_id,
+                    error = ?err,
+                    "Failed to delete address book.");
+                Err(MethodError::ServerPartialFail)
+            }
+        }
+    }
+
+    async fn address_book_set_item(
+        &self,
+        changes_: Object<SetValue>,
+        current: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext<'_>,
+    ) -> Result<Result<ObjectIndexBuilder, SetError>, MethodError> {
+        // Parse properties
+        let mut changes = Object::with_capacity(changes_.properties.len());
+        for (property, value) in changes_.properties {
+            let value = match ctx.response.eval_object_references(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return Ok(Err(err));
+                }
+            };
+            let value = match (&property, value) {
+                (Property::Name, MaybePatchValue::Value(Value::Text(value))) => {
+                    let value = value.trim();
+                    if !value.is_empty() && value.len() < self.config.contacts_max_name_len {
+                        Value::Text(value.to_string())
+                    } else {
+                        return Ok(Err(SetError::invalid_properties()
+                            .with_property(Property::Name)
+                            .with_description(if !value.is_empty() {
+                                "Address book name is too long."
+                            } else {
+                                "Address book name cannot be empty."
+                            })));
+                    }
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Text(value))) => {
+                    Value::Text(value)
+                }
+                (Property::Description, MaybePatchValue::Value(Value::Null)) => Value::Null,
+                (Property::SortOrder, MaybePatchValue::Value(Value::UnsignedInt(value))) => {
+                    Value::UnsignedInt(value)
+                }
+                (Property::IsSubscribed, MaybePatchValue::Value(Value::Bool(subscribe))) => {
+                    if let Some(current) = current.as_ref() {
+                        if let Some(value) = current
+                            .inner
+                            .mailbox_subscribe(ctx.access_token.primary_id(), subscribe)
+                        {
+                            value
+                        } else {
+                            continue;
+                        }
+                    } else if subscribe {
+                        Value::List(vec![Value::Id(ctx.access_token.primary_id().into())])
+                    } else {
+                        continue;
+                    }
+                }
+                (Property::Acl, value) => {
+                    match self.acl_set(&mut changes, current.as_ref(), value).await {
+                        Ok(_) => continue,
+                        Err(err) => {
+                            return Ok(Err(err));
+                        }
+                    }
+                }
+
+                _ => {
+                    return Ok(Err(SetError::invalid_properties()
+                        .with_property(property)
+                        .with_description("Invalid property or value.".to_string())))
+                }
+            };
+
+            changes.append(property, value);
+        }
+
+        // Refresh ACLs
+        if changes.properties.contains_key(&Property::Acl) {
+            self.refresh_acls(&changes, &current);
+        }
+
+        // Validate
+        Ok(ObjectIndexBuilder::new(SCHEMA)
+            .with_changes(changes)
+            .with_current_opt(current)
+            .validate())
+    }
+
+    pub async fn address_book_get_or_create(
+        &self,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut address_book_ids = self
+            .get_document_ids(account_id, Collection::AddressBook)
+            .await?
+            .unwrap_or_default();
+        if !address_book_ids.is_empty() {
+            return Ok(address_book_ids);
+        }
+
+        // Create the default address book
+        let document_id = self
+            .assign_document_id(account_id, Collection::AddressBook)
+            .await?;
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::AddressBook)
+            .create_document(document_id)
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA).with_changes(
+                    Object::with_capacity(2)
+                        .with_property(Property::Name, DEFAULT_ADDRESS_BOOK_NAME)
+                        .with_property(Property::IsDefault, true),
+                ),
+            );
+        self.store.write(batch.build()).await.map_err(|err| {
+            tracing::error!(
+                event = "error",
+                context = "address_book_get_or_create",
+                error = ?err,
+                "Failed to create address book.");
+            MethodError::ServerPartialFail
+        })?;
+        address_book_ids.insert(document_id);
+
+        Ok(address_book_ids)
+    }
+}
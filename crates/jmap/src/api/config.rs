@@ -64,6 +64,9 @@ impl crate::Config {
             upload_max_concurrent: settings
                 .property("jmap.protocol.upload.max-concurrent")?
                 .unwrap_or(4),
+            upload_max_data_sources: settings
+                .property("jmap.protocol.upload.max-data-sources")?
+                .unwrap_or(64),
             upload_tmp_quota_size: settings
                 .property("jmap.protocol.upload.quota.size")?
                 .unwrap_or(50000000),
@@ -83,9 +86,34 @@ impl crate::Config {
             mail_max_size: settings
                 .property("jmap.email.max-size")?
                 .unwrap_or(75000000),
+            mail_max_messages: settings.property("jmap.email.max-messages")?.unwrap_or(0),
             mail_parse_max_items: settings
                 .property("jmap.email.parse.max-items")?
                 .unwrap_or(10),
+            contacts_max_name_len: settings
+                .property("jmap.contacts.max-name-length")?
+                .unwrap_or(255),
+            contacts_max_size: settings
+                .property("jmap.contacts.max-size")?
+                .unwrap_or(102400),
+            contacts_max_address_books: settings
+                .property("jmap.contacts.max-address-books-per-card")?
+                .unwrap_or(10),
+            calendar_max_name_len: settings
+                .property("jmap.calendar.max-name-length")?
+                .unwrap_or(255),
+            calendar_max_size: settings
+                .property("jmap.calendar.max-size")?
+                .unwrap_or(512000),
+            calendar_max_calendars: settings
+                .property("jmap.calendar.max-calendars-per-event")?
+                .unwrap_or(10),
+            calendar_max_expansions: settings
+                .property("jmap.calendar.max-expansions")?
+                .unwrap_or(1000),
+            calendar_imip_enable: settings
+                .property("jmap.calendar.imip.enable")?
+                .unwrap_or(false),
             sieve_max_script_name: settings
                 .property("jmap.sieve.limits.name-length")?
                 .unwrap_or(512),
@@ -139,7 +167,20 @@ impl crate::Config {
             principal_allow_lookups: settings
                 .property("jmap.principal.allow-lookups")?
                 .unwrap_or(true),
+            spam_enable: settings.property("jmap.spam.enable")?.unwrap_or(false),
+            spam_threshold: settings.property_or_static("jmap.spam.threshold", "0.9")?,
+            spam_min_learns: settings.property_or_static("jmap.spam.min-learns", "20")?,
+            spam_max_tokens: settings.property_or_static("jmap.spam.max-tokens", "1000")?,
+            metrics_enable: settings.property("jmap.metrics.enable")?.unwrap_or(false),
+            metrics_require_auth: settings
+                .property("jmap.metrics.require-auth")?
+                .unwrap_or(true),
         };
+        if config.spam_min_learns < 1 {
+            return Err(
+                "Invalid value for \"jmap.spam.min-learns\", it must be at least 1.".to_string(),
+            );
+        }
         config.add_capabilites(settings);
         Ok(config)
     }
@@ -43,11 +43,15 @@ use tokio::{
     io::{AsyncRead, AsyncWrite},
     net::TcpStream,
 };
-use utils::listener::{ServerInstance, SessionData, SessionManager};
+use utils::{
+    listener::{ServerInstance, SessionData, SessionManager},
+    metrics::METRICS,
+};
 
 use crate::{
     auth::{oauth::OAuthMetadata, AccessToken},
     blob::{DownloadResponse, UploadResponse},
+    dav::DavResponse,
     services::state,
     websocket::upgrade::upgrade_websocket_connection,
     JMAP,
@@ -167,6 +171,16 @@ pub async fn parse_jmap_request(
                 _ => (),
             }
         }
+        "dav" => {
+            // Authenticate request
+            return match jmap.authenticate_headers(&req, remote_ip).await {
+                Ok(Some((_in_flight, access_token))) => {
+                    jmap.handle_dav_request(req, access_token, &instance).await
+                }
+                Ok(None) => DavResponse::unauthorized().into_http_response(),
+                Err(err) => err.into_http_response(),
+            };
+        }
         ".well-known" => match (path.next().unwrap_or(""), req.method()) {
             ("jmap", &Method::GET) => {
                 // Authenticate request
@@ -182,6 +196,9 @@ pub async fn parse_jmap_request(
                     Err(err) => err.into_http_response(),
                 };
             }
+            ("caldav" | "carddav", _) => {
+                return DavResponse::redirect("/dav/").into_http_response();
+            }
             ("oauth-authorization-server", &Method::GET) => {
                 let remote_addr = jmap.build_remote_addr(&req, remote_ip);
                 // Limit anonymous requests
@@ -240,11 +257,11 @@ pub async fn parse_jmap_request(
 
         "admin" => {
             // Make sure the user is a superuser
-            match jmap.authenticate_headers(&req, remote_ip).await {
-                Ok(Some((_, access_token))) if access_token.is_super_user() => (),
+            let access_token = match jmap.authenticate_headers(&req, remote_ip).await {
+                Ok(Some((_, access_token))) if access_token.is_super_user() => access_token,
                 Ok(_) => return RequestError::unauthorized().into_http_response(),
                 Err(err) => return err.into_http_response(),
-            }
+            };
 
             match (
                 path.next().unwrap_or(""),
@@ -340,6 +357,12 @@ pub async fn parse_jmap_request(
                         .into_http_response(),
                     };
                 }
+                ("principal", name, _) => {
+                    let name = name.to_string();
+                    return jmap
+                        .handle_manage_principal(&mut req, &name, &access_token)
+                        .await;
+                }
                 (path_1 @ ("queue" | "report"), path_2, &Method::GET) => {
                     return jmap
                         .smtp
@@ -349,6 +372,29 @@ pub async fn parse_jmap_request(
                 _ => (),
             }
         }
+        "metrics" if jmap.config.metrics_enable && req.method() == Method::GET => {
+            // Exporting metrics requires a superuser unless explicitly disabled
+            if jmap.config.metrics_require_auth {
+                match jmap.authenticate_headers(&req, remote_ip).await {
+                    Ok(Some((_, access_token))) if access_token.is_super_user() => (),
+                    Ok(_) => return RequestError::unauthorized().into_http_response(),
+                    Err(err) => return err.into_http_response(),
+                }
+            }
+
+            return hyper::Response::builder()
+                .status(StatusCode::OK)
+                .header(
+                    header::CONTENT_TYPE,
+                    "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                )
+                .body(
+                    Full::new(Bytes::from(METRICS.export()))
+                        .map_err(|never| match never {})
+                        .boxed(),
+                )
+                .unwrap();
+        }
         _ => (),
     }
     RequestError::not_found().into_http_response()
@@ -369,6 +415,7 @@ impl SessionManager for JmapSessionManager {
                                 stream,
                                 local_ip: session.local_ip,
                                 remote_ip: session.remote_ip,
+                                remote_port: session.remote_port,
                                 span,
                                 in_flight: session.in_flight,
                                 instance: session.instance,
@@ -0,0 +1,394 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    app_password::AppPassword, secret::hash_secret, totp::Totp, DirectoryError, Principal,
+    PrincipalUpdate, Protocol, Type,
+};
+use hyper::{Method, StatusCode};
+use jmap_proto::error::request::RequestError;
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{
+    http::{fetch_body, ToHttpResponse},
+    HttpRequest, HttpResponse, JsonResponse,
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrincipalObject {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quota: u32,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    pub member_of: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub app_passwords: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub totp_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", content = "value", rename_all = "camelCase")]
+pub enum PrincipalPatch {
+    SetSecrets(Vec<String>),
+    SetDescription(Option<String>),
+    SetType(String),
+    SetQuota(u32),
+    AddEmail(String),
+    RemoveEmail(String),
+    AddList(String),
+    RemoveList(String),
+    AddMemberOf(String),
+    RemoveMemberOf(String),
+    AddAppPassword(AppPasswordObject),
+    RemoveAppPassword(String),
+    EnableTotp,
+    DisableTotp,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPasswordObject {
+    pub name: String,
+    #[serde(default)]
+    pub protocols: Vec<String>,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ManageResponse<T: Serialize> {
+    data: T,
+}
+
+impl JMAP {
+    pub async fn handle_manage_principal(
+        &self,
+        req: &mut HttpRequest,
+        name: &str,
+        access_token: &AccessToken,
+    ) -> HttpResponse {
+        let directory = match self.directory.writer() {
+            Some(directory) => directory,
+            None => {
+                return RequestError::blank(
+                    StatusCode::BAD_REQUEST.as_u16(),
+                    "Unsupported",
+                    "The configured directory does not support principal management.",
+                )
+                .into_http_response()
+            }
+        };
+
+        let result = match (name, req.method().clone()) {
+            ("", Method::GET) => directory.list_principals().await.map(|names| {
+                JsonResponse::new(ManageResponse { data: names }).into_http_response()
+            }),
+            (name, Method::GET) => match self.directory.principal(name).await {
+                Ok(Some(principal)) => self.directory.emails_by_name(name).await.map(|emails| {
+                    JsonResponse::new(ManageResponse {
+                        data: PrincipalObject {
+                            name: principal.name,
+                            typ: match principal.typ {
+                                Type::Superuser => "superuser",
+                                typ => typ.to_jmap(),
+                            }
+                            .to_string(),
+                            secrets: vec![],
+                            app_passwords: principal
+                                .app_passwords()
+                                .map(|app_password| app_password.name)
+                                .collect(),
+                            totp_enabled: principal.totp().is_some(),
+                            description: principal.description,
+                            quota: principal.quota,
+                            emails,
+                            member_of: principal.member_of,
+                        },
+                    })
+                    .into_http_response()
+                }),
+                Ok(None) => Err(DirectoryError::not_found(name)),
+                Err(err) => Err(err),
+            },
+            ("", Method::POST) => {
+                let principal = match fetch_body(req, self.config.request_max_size, access_token)
+                    .await
+                    .and_then(|bytes| serde_json::from_slice::<PrincipalObject>(&bytes).ok())
+                {
+                    Some(principal) if !principal.name.is_empty() => principal,
+                    _ => return RequestError::invalid_parameters().into_http_response(),
+                };
+                let typ = match Type::parse(&principal.typ) {
+                    Some(typ) => typ,
+                    None => return RequestError::invalid_parameters().into_http_response(),
+                };
+
+                directory
+                    .create_principal(
+                        Principal {
+                            name: principal.name,
+                            secrets: principal.secrets,
+                            typ,
+                            description: principal.description,
+                            quota: principal.quota,
+                            member_of: principal.member_of,
+                        },
+                        principal.emails,
+                    )
+                    .await
+                    .map(|_| success())
+            }
+            (name, Method::PATCH) if !name.is_empty() => {
+                let patches = match fetch_body(req, self.config.request_max_size, access_token)
+                    .await
+                    .and_then(|bytes| serde_json::from_slice::<Vec<PrincipalPatch>>(&bytes).ok())
+                {
+                    Some(patches) => patches,
+                    None => return RequestError::invalid_parameters().into_http_response(),
+                };
+
+                match self.principal_changes(name, patches).await {
+                    Ok(Some((changes, response))) => directory
+                        .update_principal(name, changes)
+                        .await
+                        .map(|_| response),
+                    Ok(None) => return RequestError::invalid_parameters().into_http_response(),
+                    Err(err) => Err(err),
+                }
+            }
+            (name, Method::DELETE) if !name.is_empty() => {
+                match directory.delete_principal(name).await {
+                    Ok(_) => {
+                        // Remove any data stored for the account
+                        match self.try_get_account_id(name).await {
+                            Ok(Some(account_id)) => {
+                                match self.delete_account(name, account_id).await {
+                                    Ok(_) => Ok(success()),
+                                    Err(err) => {
+                                        return RequestError::blank(
+                                            StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                                            "Account deletion failed",
+                                            err.to_string(),
+                                        )
+                                        .into_http_response()
+                                    }
+                                }
+                            }
+                            Ok(None) => Ok(success()),
+                            Err(_) => {
+                                return RequestError::internal_server_error().into_http_response()
+                            }
+                        }
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(DirectoryError::NotFound(name)) => RequestError::blank(
+                StatusCode::NOT_FOUND.as_u16(),
+                "Not found",
+                format!("Principal or group {name:?} does not exist."),
+            )
+            .into_http_response(),
+            Err(DirectoryError::AlreadyExists(name)) => RequestError::blank(
+                StatusCode::CONFLICT.as_u16(),
+                "Already exists",
+                format!("{name:?} is already in use."),
+            )
+            .into_http_response(),
+            Err(DirectoryError::Unsupported) => RequestError::blank(
+                StatusCode::BAD_REQUEST.as_u16(),
+                "Unsupported",
+                "The configured directory does not support this operation.",
+            )
+            .into_http_response(),
+            Err(_) => RequestError::internal_server_error().into_http_response(),
+        }
+    }
+}
+
+impl JMAP {
+    // Converts the requested patches into directory updates, returns None when
+    // any of the patches is invalid.
+    async fn principal_changes(
+        &self,
+        name: &str,
+        patches: Vec<PrincipalPatch>,
+    ) -> directory::Result<Option<(Vec<PrincipalUpdate>, HttpResponse)>> {
+        // Credential changes are applied on top of the current secrets
+        let mut secrets = if patches.iter().any(|patch| patch.is_credential_change()) {
+            Some(
+                self.directory
+                    .principal(name)
+                    .await?
+                    .ok_or_else(|| DirectoryError::not_found(name))?
+                    .secrets,
+            )
+        } else {
+            None
+        };
+        let mut changes = Vec::with_capacity(patches.len());
+        let mut response = None;
+
+        for patch in patches {
+            changes.push(match patch {
+                PrincipalPatch::SetDescription(description) => {
+                    PrincipalUpdate::Description(description)
+                }
+                PrincipalPatch::SetType(typ) => match Type::parse(&typ) {
+                    Some(typ) => PrincipalUpdate::Type(typ),
+                    None => return Ok(None),
+                },
+                PrincipalPatch::SetQuota(quota) => PrincipalUpdate::Quota(quota),
+                PrincipalPatch::AddEmail(email) => PrincipalUpdate::AddEmail(email),
+                PrincipalPatch::RemoveEmail(email) => PrincipalUpdate::RemoveEmail(email),
+                PrincipalPatch::AddList(email) => PrincipalUpdate::AddList(email),
+                PrincipalPatch::RemoveList(email) => PrincipalUpdate::RemoveList(email),
+                PrincipalPatch::AddMemberOf(group) => PrincipalUpdate::AddMemberOf(group),
+                PrincipalPatch::RemoveMemberOf(group) => PrincipalUpdate::RemoveMemberOf(group),
+                PrincipalPatch::SetSecrets(new_secrets) => {
+                    // Changing the password keeps the second factor and app passwords
+                    let secrets = secrets.as_mut().unwrap();
+                    secrets.retain(|secret| {
+                        AppPassword::is_app_password(secret) || Totp::is_totp_secret(secret)
+                    });
+                    secrets.extend(new_secrets);
+                    continue;
+                }
+                PrincipalPatch::AddAppPassword(app_password) => {
+                    let protocols = match app_password
+                        .protocols
+                        .iter()
+                        .map(|protocol| Protocol::parse(protocol))
+                        .collect::<Option<Vec<_>>>()
+                    {
+                        Some(protocols)
+                            if AppPassword::is_valid_name(&app_password.name)
+                                && !app_password.secret.is_empty()
+                                && !protocols.iter().any(|p| p.is_interactive()) =>
+                        {
+                            protocols
+                        }
+                        _ => return Ok(None),
+                    };
+                    let secrets = secrets.as_mut().unwrap();
+                    if secrets
+                        .iter()
+                        .filter_map(|secret| AppPassword::parse(secret))
+                        .any(|existing| existing.name == app_password.name)
+                    {
+                        return Err(DirectoryError::AlreadyExists(app_password.name));
+                    }
+                    secrets.push(
+                        AppPassword::new(
+                            app_password.name,
+                            protocols,
+                            hash_secret(app_password.secret).await?,
+                        )
+                        .to_string(),
+                    );
+                    continue;
+                }
+                PrincipalPatch::RemoveAppPassword(app_name) => {
+                    let secrets = secrets.as_mut().unwrap();
+                    let num_secrets = secrets.len();
+                    secrets.retain(|secret| {
+                        AppPassword::parse(secret)
+                            .map_or(true, |existing| existing.name != app_name)
+                    });
+                    if secrets.len() == num_secrets {
+                        return Err(DirectoryError::NotFound(app_name));
+                    }
+                    continue;
+                }
+                PrincipalPatch::EnableTotp => {
+                    let secrets = secrets.as_mut().unwrap();
+                    let totp = Totp::generate(
+                        &format!(
+                            "Stalwart:{}",
+                            form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>()
+                        ),
+                        "Stalwart".into(),
+                    )
+                    .to_string();
+                    secrets.retain(|secret| !Totp::is_totp_secret(secret));
+                    secrets.push(totp.clone());
+                    response = totp.into();
+                    continue;
+                }
+                PrincipalPatch::DisableTotp => {
+                    secrets
+                        .as_mut()
+                        .unwrap()
+                        .retain(|secret| !Totp::is_totp_secret(secret));
+                    continue;
+                }
+            });
+        }
+
+        if let Some(secrets) = secrets {
+            changes.push(PrincipalUpdate::Secrets(secrets));
+        }
+
+        Ok(Some((
+            changes,
+            if let Some(response) = response {
+                JsonResponse::new(ManageResponse { data: response }).into_http_response()
+            } else {
+                success()
+            },
+        )))
+    }
+}
+
+impl PrincipalPatch {
+    fn is_credential_change(&self) -> bool {
+        matches!(
+            self,
+            PrincipalPatch::SetSecrets(_)
+                | PrincipalPatch::AddAppPassword(_)
+                | PrincipalPatch::RemoveAppPassword(_)
+                | PrincipalPatch::EnableTotp
+                | PrincipalPatch::DisableTotp
+        )
+    }
+}
+
+fn success() -> HttpResponse {
+    JsonResponse::new(ManageResponse { data: "success" }).into_http_response()
+}
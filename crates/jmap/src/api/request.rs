@@ -177,6 +177,31 @@ impl JMAP {
                         ));
                     }
                 }
+                get::RequestArguments::AddressBook => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::Calendar => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::CalendarEvent => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_get(req, access_token).await?.into()
+                }
+                get::RequestArguments::Quota => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.quota_get(req, access_token).await?.into()
+                }
             },
             RequestMethod::Query(mut req) => match req.take_arguments() {
                 query::RequestArguments::Email(arguments) => {
@@ -212,6 +237,23 @@ impl JMAP {
                         ));
                     }
                 }
+                query::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_query(req, access_token).await?.into()
+                }
+                query::RequestArguments::CalendarEvent(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_query(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                query::RequestArguments::Quota => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.quota_query(req, access_token).await?.into()
+                }
             },
             RequestMethod::Set(mut req) => match req.take_arguments() {
                 set::RequestArguments::Email => {
@@ -253,6 +295,32 @@ impl JMAP {
 
                     self.vacation_response_set(req).await?.into()
                 }
+                set::RequestArguments::AddressBook(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::AddressBook)?;
+
+                    self.address_book_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::ContactCard => {
+                    access_token.assert_has_access(req.account_id, Collection::ContactCard)?;
+
+                    self.contact_card_set(req, access_token).await?.into()
+                }
+                set::RequestArguments::Calendar(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::Calendar)?;
+
+                    self.calendar_set(req.with_arguments(arguments), access_token)
+                        .await?
+                        .into()
+                }
+                set::RequestArguments::CalendarEvent(arguments) => {
+                    access_token.assert_has_access(req.account_id, Collection::CalendarEvent)?;
+
+                    self.calendar_event_set(req.with_arguments(arguments), instance, access_token)
+                        .await?
+                        .into()
+                }
             },
             RequestMethod::Changes(req) => self.changes(req, access_token).await?.into(),
             RequestMethod::Copy(req) => {
@@ -263,6 +331,17 @@ impl JMAP {
                 self.email_copy(req, access_token, next_call).await?.into()
             }
             RequestMethod::CopyBlob(req) => self.blob_copy(req, access_token).await?.into(),
+            RequestMethod::GetBlob(req) => self.blob_get(req, access_token).await?.into(),
+            RequestMethod::UploadBlob(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.blob_upload_many(req, access_token).await?.into()
+            }
+            RequestMethod::LookupBlob(req) => {
+                access_token.assert_has_access(req.account_id, Collection::Email)?;
+
+                self.blob_lookup(req, access_token).await?.into()
+            }
             RequestMethod::ImportEmail(req) => {
                 access_token.assert_has_access(req.account_id, Collection::Email)?;
 
@@ -273,6 +352,16 @@ impl JMAP {
 
                 self.email_parse(req, access_token).await?.into()
             }
+            RequestMethod::SendMdn(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.mdn_send(req, instance, next_call).await?.into()
+            }
+            RequestMethod::ParseMdn(req) => {
+                access_token.assert_has_access(req.account_id, Collection::Email)?;
+
+                self.mdn_parse(req, access_token).await?.into()
+            }
             RequestMethod::QueryChanges(req) => self.query_changes(req, access_token).await?.into(),
             RequestMethod::SearchSnippet(req) => {
                 access_token.assert_has_access(req.account_id, Collection::Email)?;
@@ -81,6 +81,11 @@ pub enum Capabilities {
     VacationResponse(VacationResponseCapabilities),
     WebSocket(WebSocketCapabilities),
     Sieve(SieveCapabilities),
+    Contacts(ContactsCapabilities),
+    Calendars(CalendarsCapabilities),
+    Quota(QuotaCapabilities),
+    Blob(BlobCapabilities),
+    Mdn(MdnCapabilities),
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -158,6 +163,40 @@ pub struct SubmissionCapabilities {
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct VacationResponseCapabilities {}
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContactsCapabilities {
+    #[serde(rename(serialize = "maxAddressBooksPerCard"))]
+    max_address_books_per_card: usize,
+    #[serde(rename(serialize = "mayCreateAddressBook"))]
+    may_create_address_book: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CalendarsCapabilities {
+    #[serde(rename(serialize = "maxCalendarsPerEvent"))]
+    max_calendars_per_event: usize,
+    #[serde(rename(serialize = "mayCreateCalendar"))]
+    may_create_calendar: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QuotaCapabilities {}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlobCapabilities {
+    #[serde(rename(serialize = "maxSizeBlobSet"))]
+    max_size_blob_set: usize,
+    #[serde(rename(serialize = "maxDataSources"))]
+    max_data_sources: usize,
+    #[serde(rename(serialize = "supportedTypeNames"))]
+    supported_type_names: Vec<String>,
+    #[serde(rename(serialize = "supportedDigestAlgorithms"))]
+    supported_digest_algorithms: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MdnCapabilities {}
+
 #[derive(Default)]
 pub struct BaseCapabilities {
     pub capabilities: VecMap<Capability, Capabilities>,
@@ -198,7 +237,26 @@ impl JMAP {
                     .unwrap_or_else(|| Id::from(*id).to_string()),
                 is_personal,
                 is_readonly,
-                Some(&[Capability::Core, Capability::Mail, Capability::WebSocket]),
+                Some(if is_personal {
+                    &[
+                        Capability::Core,
+                        Capability::Mail,
+                        Capability::Contacts,
+                        Capability::Calendars,
+                        Capability::WebSocket,
+                        Capability::Blob,
+                    ]
+                } else {
+                    &[
+                        Capability::Core,
+                        Capability::Mail,
+                        Capability::Contacts,
+                        Capability::Calendars,
+                        Capability::WebSocket,
+                        Capability::Quota,
+                        Capability::Blob,
+                    ]
+                }),
             );
         }
 
@@ -233,6 +291,39 @@ impl crate::Config {
             Capability::Sieve,
             Capabilities::Sieve(SieveCapabilities::new(self, settings)),
         );
+        self.capabilities.capabilities.append(
+            Capability::Contacts,
+            Capabilities::Contacts(ContactsCapabilities {
+                max_address_books_per_card: self.contacts_max_address_books,
+                may_create_address_book: true,
+            }),
+        );
+        self.capabilities.capabilities.append(
+            Capability::Calendars,
+            Capabilities::Calendars(CalendarsCapabilities {
+                max_calendars_per_event: self.calendar_max_calendars,
+                may_create_calendar: true,
+            }),
+        );
+        self.capabilities
+            .capabilities
+            .append(Capability::Quota, Capabilities::Quota(QuotaCapabilities {}));
+        self.capabilities.capabilities.append(
+            Capability::Blob,
+            Capabilities::Blob(BlobCapabilities {
+                max_size_blob_set: self.upload_max_size,
+                max_data_sources: self.upload_max_data_sources,
+                supported_type_names: vec![
+                    "Email".to_string(),
+                    "Mailbox".to_string(),
+                    "Thread".to_string(),
+                ],
+                supported_digest_algorithms: vec!["sha".to_string(), "sha-256".to_string()],
+            }),
+        );
+        self.capabilities
+            .capabilities
+            .append(Capability::Mdn, Capabilities::Mdn(MdnCapabilities {}));
     }
 }
 
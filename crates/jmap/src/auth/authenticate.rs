@@ -27,6 +27,7 @@ use std::{
     time::Instant,
 };
 
+use directory::{Principal, Protocol};
 use hyper::header;
 use jmap_proto::{
     error::{method::MethodError, request::RequestError},
@@ -73,7 +74,8 @@ impl JMAP {
                             })
                         })
                     {
-                        self.authenticate_plain(&account, &secret).await
+                        self.authenticate_plain(&account, &secret, Protocol::Jmap)
+                            .await
                     } else {
                         tracing::debug!(
                             context = "authenticate_headers",
@@ -86,17 +88,7 @@ impl JMAP {
                     // Enforce anonymous rate limit for bearer auth requests
                     self.is_anonymous_allowed(addr)?;
 
-                    match self.validate_access_token("access_token", &token).await {
-                        Ok((account_id, _, _)) => self.get_access_token(account_id).await,
-                        Err(err) => {
-                            tracing::debug!(
-                                context = "authenticate_headers",
-                                err = err,
-                                "Failed to validate access token."
-                            );
-                            None
-                        }
-                    }
+                    self.authenticate_bearer(&token, Protocol::Jmap).await
                 } else {
                     // Enforce anonymous rate limit
                     self.is_anonymous_allowed(addr)?;
@@ -266,38 +258,137 @@ impl JMAP {
         }
     }
 
-    pub async fn authenticate_plain(&self, username: &str, secret: &str) -> Option<AccessToken> {
-        let mut principal = self
-            .directory
-            .authenticate(&Credentials::Plain {
+    pub async fn authenticate_plain(
+        &self,
+        username: &str,
+        secret: &str,
+        protocol: Protocol,
+    ) -> Option<AccessToken> {
+        self.authenticate_credentials(
+            &Credentials::Plain {
                 username: username.to_string(),
                 secret: secret.to_string(),
-            })
+            },
+            protocol,
+        )
+        .await
+    }
+
+    pub async fn authenticate_bearer(
+        &self,
+        token: &str,
+        protocol: Protocol,
+    ) -> Option<AccessToken> {
+        match self.validate_access_token("access_token", token).await {
+            Ok((account_id, _, _)) => self.get_access_token(account_id).await,
+            Err(err) => {
+                // Tokens issued by an external OpenID Connect provider are
+                // validated by the directory
+                let access_token = self
+                    .authenticate_credentials(
+                        &Credentials::OAuthBearer {
+                            token: token.to_string(),
+                        },
+                        protocol,
+                    )
+                    .await;
+                if access_token.is_none() {
+                    tracing::debug!(
+                        context = "authenticate",
+                        err = err,
+                        "Failed to validate access token."
+                    );
+                }
+                access_token
+            }
+        }
+    }
+
+    pub async fn authenticate_xoauth2(
+        &self,
+        username: &str,
+        token: &str,
+        protocol: Protocol,
+    ) -> Option<AccessToken> {
+        match self.validate_access_token("access_token", token).await {
+            Ok((account_id, _, _)) => {
+                // XOAUTH2 includes the username, make sure it matches the token
+                let access_token = self.get_access_token(account_id).await?;
+                if username.eq_ignore_ascii_case(&access_token.name)
+                    || self
+                        .directory
+                        .emails_by_name(&access_token.name)
+                        .await
+                        .ok()?
+                        .iter()
+                        .any(|email| email.eq_ignore_ascii_case(username))
+                {
+                    Some(access_token)
+                } else {
+                    tracing::debug!(
+                        context = "authenticate",
+                        username = username,
+                        subject = access_token.name.as_str(),
+                        "Username does not match the access token."
+                    );
+                    None
+                }
+            }
+            Err(err) => {
+                // Tokens issued by an external OpenID Connect provider are
+                // validated by the directory
+                let access_token = self
+                    .authenticate_credentials(
+                        &Credentials::XOauth2 {
+                            username: username.to_string(),
+                            secret: token.to_string(),
+                        },
+                        protocol,
+                    )
+                    .await;
+                if access_token.is_none() {
+                    tracing::debug!(
+                        context = "authenticate",
+                        err = err,
+                        "Failed to validate access token."
+                    );
+                }
+                access_token
+            }
+        }
+    }
+
+    pub async fn authenticate_credentials(
+        &self,
+        credentials: &Credentials<String>,
+        protocol: Protocol,
+    ) -> Option<AccessToken> {
+        let mut principal = self
+            .directory
+            .authenticate(credentials, protocol)
             .await
             .ok()??;
         if !principal.has_name() {
-            principal.name = username.to_string();
-        }
-        // Obtain groups
-        if let (Ok(account_id), Ok(member_of)) = (
-            self.get_account_id(&principal.name).await,
-            self.map_member_of(std::mem::take(&mut principal.member_of))
-                .await,
-        ) {
-            // Create access token
-            self.update_access_token(
-                AccessToken::new(principal, account_id).with_member_of(member_of),
-            )
-            .await
-        } else {
-            None
+            match credentials {
+                Credentials::Plain { username, .. } | Credentials::XOauth2 { username, .. } => {
+                    principal.name = username.to_string();
+                }
+                Credentials::OAuthBearer { .. } => return None,
+            }
         }
+        self.access_token_from_principal(principal).await
     }
 
     pub async fn get_access_token(&self, account_id: u32) -> Option<AccessToken> {
         let name = self.get_account_name(account_id).await.ok()??;
-        let mut principal = self.directory.principal(&name).await.ok()??;
+        let principal = self.directory.principal(&name).await.ok()??;
+        self.access_token_from_principal(principal).await
+    }
 
+    pub async fn access_token_from_principal(
+        &self,
+        mut principal: Principal,
+    ) -> Option<AccessToken> {
         // Obtain groups
         if let (Ok(account_id), Ok(member_of)) = (
             self.get_account_id(&principal.name).await,
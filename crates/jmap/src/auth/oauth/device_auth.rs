@@ -26,6 +26,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+use directory::Protocol;
 use hyper::StatusCode;
 use store::rand::{
     distributions::{Alphanumeric, Standard},
@@ -43,9 +44,10 @@ use crate::{
 };
 
 use super::{
-    parse_form_data, DeviceAuthResponse, OAuthCode, CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN,
-    OAUTH_HTML_FOOTER, OAUTH_HTML_HEADER, OAUTH_HTML_LOGIN_CODE, OAUTH_HTML_LOGIN_FORM,
-    OAUTH_HTML_LOGIN_HEADER_DEVICE, STATUS_PENDING, USER_CODE_ALPHABET, USER_CODE_LEN,
+    parse_form_data, parse_login_fields, DeviceAuthResponse, OAuthCode, CLIENT_ID_MAX_LEN,
+    DEVICE_CODE_LEN, OAUTH_HTML_FOOTER, OAUTH_HTML_HEADER, OAUTH_HTML_LOGIN_CODE,
+    OAUTH_HTML_LOGIN_FORM, OAUTH_HTML_LOGIN_HEADER_DEVICE, STATUS_PENDING, USER_CODE_ALPHABET,
+    USER_CODE_LEN,
 };
 
 // Device authorization endpoint
@@ -168,9 +170,11 @@ impl JMAP {
             if (STATUS_PENDING..STATUS_PENDING + self.config.oauth_max_auth_attempts)
                 .contains(&oauth.status.load(atomic::Ordering::Relaxed))
             {
-                if let (Some(email), Some(password)) = (fields.get("email"), fields.get("password"))
-                {
-                    if let Some(id) = self.authenticate_plain(email, password).await {
+                if let Some((email, password)) = parse_login_fields(&fields) {
+                    if let Some(id) = self
+                        .authenticate_plain(email, &password, Protocol::OAuth)
+                        .await
+                    {
                         oauth
                             .account_id
                             .store(id.primary_id(), atomic::Ordering::Relaxed);
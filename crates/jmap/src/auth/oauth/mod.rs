@@ -244,6 +244,20 @@ pub async fn parse_form_data(
     }
 }
 
+// Returns the login credentials submitted in an authorization form, the TOTP code
+// (if provided) is appended to the password as expected by the directory.
+pub fn parse_login_fields(fields: &HashMap<String, String>) -> Option<(&str, String)> {
+    let email = fields.get("email")?;
+    let password = fields.get("password")?;
+    Some((
+        email.as_str(),
+        match fields.get("otp").map(|code| code.trim()) {
+            Some(code) if !code.is_empty() => format!("{password}${code}"),
+            _ => password.to_string(),
+        },
+    ))
+}
+
 pub async fn fetch_body(req: &mut HttpRequest) -> Option<Vec<u8>> {
     let mut bytes = Vec::with_capacity(1024);
     while let Some(Ok(frame)) = req.frame().await {
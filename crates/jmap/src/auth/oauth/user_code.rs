@@ -27,6 +27,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+use directory::Protocol;
 use http_body_util::{BodyExt, Full};
 use hyper::{body::Bytes, header, StatusCode};
 use mail_builder::encoders::base64::base64_encode;
@@ -41,8 +42,8 @@ use crate::{
 };
 
 use super::{
-    parse_form_data, OAuthCode, CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN, OAUTH_HTML_FOOTER,
-    OAUTH_HTML_HEADER, OAUTH_HTML_LOGIN_CODE_HIDDEN, OAUTH_HTML_LOGIN_FORM,
+    parse_form_data, parse_login_fields, OAuthCode, CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN,
+    OAUTH_HTML_FOOTER, OAUTH_HTML_HEADER, OAUTH_HTML_LOGIN_CODE_HIDDEN, OAUTH_HTML_LOGIN_FORM,
     OAUTH_HTML_LOGIN_HEADER_CLIENT, OAUTH_HTML_LOGIN_HEADER_FAILED, STATUS_AUTHORIZED,
 };
 
@@ -131,8 +132,11 @@ impl JMAP {
         };
 
         // Authenticate user
-        if let (Some(email), Some(password)) = (params.get("email"), params.get("password")) {
-            if let Some(access_token) = self.authenticate_plain(email, password).await {
+        if let Some((email, password)) = parse_login_fields(&params) {
+            if let Some(access_token) = self
+                .authenticate_plain(email, &password, Protocol::OAuth)
+                .await
+            {
                 // Generate client code
                 let client_code = thread_rng()
                     .sample_iter(Alphanumeric)
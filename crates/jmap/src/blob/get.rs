@@ -0,0 +1,263 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::blob::{
+        BlobGetRequest, BlobGetResponse, BlobInfo, BlobLookupRequest, BlobLookupResponse,
+    },
+    object::Object,
+    types::{
+        acl::Acl, collection::Collection, id::Id, property::Property, type_state::TypeState,
+        value::Value,
+    },
+};
+use mail_builder::encoders::base64::base64_encode;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use store::BlobKind;
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    pub async fn blob_get(
+        &self,
+        mut request: BlobGetRequest,
+        access_token: &AccessToken,
+    ) -> Result<BlobGetResponse, MethodError> {
+        let ids = request
+            .ids
+            .take()
+            .map(|ids| ids.unwrap())
+            .unwrap_or_default();
+        if ids.len() > self.config.get_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let properties = request.unwrap_properties(&[Property::Id, Property::Data, Property::Size]);
+        let offset = request.offset.unwrap_or(0);
+        let mut response = BlobGetResponse {
+            account_id: request.account_id,
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for blob_id in ids {
+            let bytes = if let Some(bytes) = self.blob_download(&blob_id, access_token).await? {
+                bytes
+            } else {
+                response.not_found.push(blob_id);
+                continue;
+            };
+
+            // Obtain requested range
+            let start = std::cmp::min(offset, bytes.len());
+            let end = request.length.map_or(bytes.len(), |length| {
+                std::cmp::min(start.saturating_add(length), bytes.len())
+            });
+            let is_truncated = offset > bytes.len()
+                || request
+                    .length
+                    .map_or(false, |length| offset.saturating_add(length) > bytes.len());
+            let range = &bytes[start..end];
+
+            let mut blob = Object::with_capacity(properties.len());
+            let mut has_data = false;
+            let mut is_encoding_problem = false;
+            for property in &properties {
+                match property {
+                    Property::Id => {
+                        blob.append(Property::Id, Value::BlobId(blob_id.clone()));
+                    }
+                    Property::Size => {
+                        blob.append(Property::Size, Value::UnsignedInt(bytes.len() as u64));
+                    }
+                    Property::Data => {
+                        has_data = true;
+                        if let Ok(text) = std::str::from_utf8(range) {
+                            blob.append(Property::DataAsText, Value::Text(text.to_string()));
+                        } else {
+                            blob.append(Property::DataAsBase64, Value::Text(encode_base64(range)));
+                        }
+                    }
+                    Property::DataAsText => {
+                        has_data = true;
+                        if let Ok(text) = std::str::from_utf8(range) {
+                            blob.append(Property::DataAsText, Value::Text(text.to_string()));
+                        } else {
+                            is_encoding_problem = true;
+                            blob.append(Property::DataAsText, Value::Null);
+                        }
+                    }
+                    Property::DataAsBase64 => {
+                        has_data = true;
+                        blob.append(Property::DataAsBase64, Value::Text(encode_base64(range)));
+                    }
+                    Property::DigestSha => {
+                        blob.append(
+                            Property::DigestSha,
+                            Value::Text(encode_base64(&Sha1::digest(range))),
+                        );
+                    }
+                    Property::DigestSha256 => {
+                        blob.append(
+                            Property::DigestSha256,
+                            Value::Text(encode_base64(&Sha256::digest(range))),
+                        );
+                    }
+                    property => {
+                        blob.append(property.clone(), Value::Null);
+                    }
+                }
+            }
+            if is_encoding_problem {
+                blob.append(Property::IsEncodingProblem, Value::Bool(true));
+            }
+            if has_data && is_truncated {
+                blob.append(Property::IsTruncated, Value::Bool(true));
+            }
+            response.list.push(blob);
+        }
+
+        Ok(response)
+    }
+
+    pub async fn blob_lookup(
+        &self,
+        request: BlobLookupRequest,
+        access_token: &AccessToken,
+    ) -> Result<BlobLookupResponse, MethodError> {
+        for type_name in &request.type_names {
+            if !matches!(
+                type_name,
+                TypeState::Email | TypeState::Mailbox | TypeState::Thread
+            ) {
+                return Err(MethodError::UnknownDataType(format!(
+                    "Type {type_name:?} is not supported."
+                )));
+            }
+        }
+
+        let ids = request.ids.unwrap();
+        if ids.len() > self.config.get_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = BlobLookupResponse {
+            account_id: request.account_id,
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+        let email_ids = self
+            .get_document_ids(account_id, Collection::Email)
+            .await?
+            .unwrap_or_default();
+        let shared_mailboxes = if !access_token.is_member(account_id) {
+            self.shared_documents(access_token, account_id, Collection::Mailbox, Acl::Read)
+                .await?
+                .into()
+        } else {
+            None
+        };
+
+        for blob_id in ids {
+            if blob_id.account_id() != account_id
+                || !self.has_access_blob(&blob_id, access_token).await?
+            {
+                response.not_found.push(blob_id);
+                continue;
+            }
+
+            // Only message blobs are referenced by other objects
+            let document_id = match &blob_id.kind {
+                BlobKind::Linked {
+                    collection,
+                    document_id,
+                    ..
+                } if *collection == u8::from(Collection::Email) => Some(*document_id),
+                BlobKind::LinkedMaildir { document_id, .. } => Some(*document_id),
+                _ => None,
+            }
+            .filter(|document_id| email_ids.contains(*document_id));
+
+            let mut matched_ids = VecMap::with_capacity(request.type_names.len());
+            for type_name in &request.type_names {
+                let ids = if let Some(document_id) = document_id {
+                    match type_name {
+                        TypeState::Email => self
+                            .get_property::<u32>(
+                                account_id,
+                                Collection::Email,
+                                document_id,
+                                Property::ThreadId,
+                            )
+                            .await?
+                            .map(|thread_id| vec![Id::from_parts(thread_id, document_id)])
+                            .unwrap_or_default(),
+                        TypeState::Thread => self
+                            .get_property::<u32>(
+                                account_id,
+                                Collection::Email,
+                                document_id,
+                                Property::ThreadId,
+                            )
+                            .await?
+                            .map(|thread_id| vec![Id::from(thread_id)])
+                            .unwrap_or_default(),
+                        _ => self
+                            .get_property::<Vec<u32>>(
+                                account_id,
+                                Collection::Email,
+                                document_id,
+                                Property::MailboxIds,
+                            )
+                            .await?
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter(|mailbox_id| {
+                                shared_mailboxes
+                                    .as_ref()
+                                    .map_or(true, |shared| shared.contains(*mailbox_id))
+                            })
+                            .map(Id::from)
+                            .collect(),
+                    }
+                } else {
+                    vec![]
+                };
+                matched_ids.append(*type_name, ids);
+            }
+
+            response.list.push(BlobInfo {
+                id: blob_id,
+                matched_ids,
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
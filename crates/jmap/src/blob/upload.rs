@@ -24,10 +24,19 @@
 use std::sync::Arc;
 
 use jmap_proto::{
-    error::{method::MethodError, request::RequestError},
+    error::{
+        method::MethodError,
+        request::RequestError,
+        set::{SetError, SetErrorType},
+    },
+    method::blob::{
+        BlobUploadRequest, BlobUploadResponse, BlobUploadResponseObject, DataSourceObject,
+    },
+    request::reference::MaybeReference,
     types::{blob::BlobId, id::Id},
 };
 use store::BlobKind;
+use utils::map::vec_map::VecMap;
 
 use crate::{auth::AccessToken, JMAP};
 
@@ -57,37 +66,15 @@ impl JMAP {
         }
 
         // Enforce quota
-        let (total_files, total_bytes) = self
-            .store
-            .get_tmp_blob_usage(account_id.document_id(), self.config.upload_tmp_ttl)
+        if self
+            .is_tmp_blob_quota_exceeded(account_id.document_id(), data.len(), &access_token)
             .await
-            .map_err(|err| {
-                tracing::error!(event = "error",
-                    context = "blob_store",
-                    account_id = account_id.document_id(),
-                    error = ?err,
-                    "Failed to obtain blob quota");
-                RequestError::internal_server_error()
-            })?;
-
-        if ((self.config.upload_tmp_quota_size > 0
-            && total_bytes + data.len() > self.config.upload_tmp_quota_size)
-            || (self.config.upload_tmp_quota_amount > 0
-                && total_files + 1 > self.config.upload_tmp_quota_amount))
-            && !access_token.is_super_user()
+            .map_err(|_| RequestError::internal_server_error())?
         {
-            let err = Err(RequestError::over_blob_quota(
+            return Err(RequestError::over_blob_quota(
                 self.config.upload_tmp_quota_amount,
                 self.config.upload_tmp_quota_size,
             ));
-
-            #[cfg(feature = "test_mode")]
-            if !DISABLE_UPLOAD_QUOTA.load(std::sync::atomic::Ordering::Relaxed) {
-                return err;
-            }
-
-            #[cfg(not(feature = "test_mode"))]
-            return err;
         }
 
         let blob_id = BlobId::temporary(account_id.document_id());
@@ -112,6 +99,159 @@ impl JMAP {
         }
     }
 
+    pub async fn blob_upload_many(
+        &self,
+        request: BlobUploadRequest,
+        access_token: &AccessToken,
+    ) -> Result<BlobUploadResponse, MethodError> {
+        if request.create.len() > self.config.set_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = BlobUploadResponse {
+            account_id: request.account_id,
+            created: VecMap::with_capacity(request.create.len()),
+            not_created: VecMap::new(),
+        };
+
+        'outer: for (create_id, upload_object) in request.create {
+            if upload_object.data.len() > self.config.upload_max_data_sources {
+                response.not_created.append(
+                    create_id,
+                    SetError::new(SetErrorType::TooLarge).with_description(format!(
+                        "Too many data sources, maximum is {}.",
+                        self.config.upload_max_data_sources
+                    )),
+                );
+                continue;
+            }
+
+            // Concatenate data sources
+            let mut data = Vec::new();
+            for data_source in upload_object.data {
+                match data_source {
+                    DataSourceObject::Value(bytes) => {
+                        data.extend(bytes);
+                    }
+                    DataSourceObject::Id { id, length, offset } => {
+                        let blob_id = match id {
+                            MaybeReference::Value(blob_id) => blob_id,
+                            MaybeReference::Reference(reference) => {
+                                if let Some(obj) = response.created.get(&reference) {
+                                    obj.id.clone()
+                                } else {
+                                    response.not_created.append(
+                                        create_id,
+                                        SetError::not_found().with_description(format!(
+                                            "Id reference {reference:?} not found."
+                                        )),
+                                    );
+                                    continue 'outer;
+                                }
+                            }
+                        };
+                        let bytes = if let Some(bytes) =
+                            self.blob_download(&blob_id, access_token).await?
+                        {
+                            bytes
+                        } else {
+                            response.not_created.append(
+                                create_id,
+                                SetError::new(SetErrorType::BlobNotFound)
+                                    .with_description(format!("blobId {blob_id} does not exist.")),
+                            );
+                            continue 'outer;
+                        };
+                        let offset = offset.unwrap_or(0);
+                        let end =
+                            length.map_or(bytes.len(), |length| offset.saturating_add(length));
+                        if let Some(bytes) = bytes.get(offset..end) {
+                            data.extend_from_slice(bytes);
+                        } else {
+                            response.not_created.append(
+                                create_id,
+                                SetError::invalid_properties().with_description(format!(
+                                    "Range {offset}..{end} is out of bounds for blobId {blob_id}."
+                                )),
+                            );
+                            continue 'outer;
+                        }
+                    }
+                }
+
+                if data.len() > self.config.upload_max_size {
+                    response.not_created.append(
+                        create_id,
+                        SetError::new(SetErrorType::TooLarge).with_description(format!(
+                            "Blob size exceeds maximum of {} bytes.",
+                            self.config.upload_max_size
+                        )),
+                    );
+                    continue 'outer;
+                }
+            }
+
+            // Enforce quota
+            if self
+                .is_tmp_blob_quota_exceeded(account_id, data.len(), access_token)
+                .await?
+            {
+                response.not_created.append(
+                    create_id,
+                    SetError::new(SetErrorType::OverQuota)
+                        .with_description("Temporary blob quota exceeded."),
+                );
+                continue;
+            }
+
+            let blob_id = BlobId::temporary(account_id);
+            self.put_blob(&blob_id.kind, &data).await?;
+            response.created.append(
+                create_id,
+                BlobUploadResponseObject {
+                    id: blob_id,
+                    type_: upload_object.type_,
+                    size: data.len(),
+                },
+            );
+        }
+
+        Ok(response)
+    }
+
+    pub async fn is_tmp_blob_quota_exceeded(
+        &self,
+        account_id: u32,
+        size: usize,
+        access_token: &AccessToken,
+    ) -> Result<bool, MethodError> {
+        let (total_files, total_bytes) = self
+            .store
+            .get_tmp_blob_usage(account_id, self.config.upload_tmp_ttl)
+            .await
+            .map_err(|err| {
+                tracing::error!(event = "error",
+                    context = "blob_store",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to obtain blob quota");
+                MethodError::ServerPartialFail
+            })?;
+
+        let is_exceeded = ((self.config.upload_tmp_quota_size > 0
+            && total_bytes + size > self.config.upload_tmp_quota_size)
+            || (self.config.upload_tmp_quota_amount > 0
+                && total_files + 1 > self.config.upload_tmp_quota_amount))
+            && !access_token.is_super_user();
+
+        #[cfg(feature = "test_mode")]
+        if is_exceeded && DISABLE_UPLOAD_QUOTA.load(std::sync::atomic::Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        Ok(is_exceeded)
+    }
+
     pub async fn put_blob(&self, kind: &BlobKind, data: &[u8]) -> Result<(), MethodError> {
         self.store.put_blob(kind, data).await.map_err(|err| {
             tracing::error!(
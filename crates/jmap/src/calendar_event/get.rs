@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::recurrence::JSCalendar;
+
+impl JMAP {
+    pub async fn calendar_event_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.config.get_max_objects)?;
+        let properties = request.properties.take().map(|p| p.unwrap());
+        let account_id = request.account_id.document_id();
+        let event_ids = self
+            .owned_or_shared_events(access_token, account_id, Acl::ReadItems)
+            .await?;
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            event_ids
+                .iter()
+                .take(self.config.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::CalendarEvent)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the event object
+            let document_id = id.document_id();
+            if !event_ids.contains(document_id) {
+                response.not_found.push(id);
+                continue;
+            }
+            let mut values = if let Some(values) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                values
+            } else {
+                response.not_found.push(id);
+                continue;
+            };
+
+            // Ids with a prefix refer to an occurrence of a recurring event
+            if id.prefix_id() != 0 {
+                if let Some(occurrence) =
+                    values.occurrence(id.prefix_id(), self.config.calendar_max_expansions)
+                {
+                    values = values.instance(&occurrence);
+                } else {
+                    response.not_found.push(id);
+                    continue;
+                }
+            }
+
+            // Remove internal properties
+            values.properties.remove(&Property::FromDate);
+            values.properties.remove(&Property::ToDate);
+
+            let mut event = Object::with_capacity(values.properties.len() + 1);
+            event.append(Property::Id, Value::Id(id));
+            if let Some(properties) = &properties {
+                for property in properties {
+                    if property != &Property::Id {
+                        let value = values.remove(property);
+                        event.append(property.clone(), value);
+                    }
+                }
+            } else {
+                for (property, value) in values.properties {
+                    event.append(property, value);
+                }
+            }
+
+            // Calendar ids are returned as a map
+            if let Some(value) = event.properties.get_mut(&Property::CalendarIds) {
+                let mut obj = Object::with_capacity(1);
+                for calendar_id in std::mem::take(value).try_unwrap_list().unwrap_or_default() {
+                    if let Value::Id(calendar_id) = calendar_id {
+                        obj.append(Property::_T(calendar_id.to_string()), true);
+                    }
+                }
+                *value = Value::Object(obj);
+            }
+
+            // Add result to response
+            response.list.push(event);
+        }
+        Ok(response)
+    }
+}
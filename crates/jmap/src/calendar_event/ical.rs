@@ -0,0 +1,943 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Write;
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use chrono::NaiveDateTime;
+
+use super::recurrence::{format_local_date_time, parse_local_date_time, JSCalendar, Occurrence};
+
+pub const PRODID: &str = "-//Stalwart Labs Ltd.//Stalwart Mail Server//EN";
+
+#[derive(Debug, Default)]
+pub struct ICalendar {
+    pub method: Option<String>,
+    pub events: Vec<ICalendarEvent>,
+}
+
+#[derive(Debug)]
+pub struct ICalendarEvent {
+    pub uid: String,
+    pub sequence: u64,
+    pub recurrence_id: Option<String>,
+    pub event: Object<Value>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ContentLine<'x> {
+    pub name: String,
+    pub params: Vec<(String, &'x str)>,
+    pub value: &'x str,
+}
+
+pub trait Participants {
+    fn organizer(&self) -> Option<String>;
+    fn attendees(&self) -> Vec<(String, Option<String>)>;
+    fn set_participation_status(&mut self, email: &str, status: &str) -> bool;
+}
+
+impl ICalendar {
+    pub fn parse(text: &str) -> Option<Self> {
+        let unfolded = unfold(text);
+        let mut calendar = ICalendar::default();
+        let mut in_calendar = false;
+        let mut event: Option<Object<Value>> = None;
+        let mut nested_level = 0;
+
+        for line in unfolded.lines().filter_map(ContentLine::parse) {
+            match (line.name.as_str(), &mut event) {
+                ("BEGIN", None) if line.value.eq_ignore_ascii_case("VCALENDAR") => {
+                    in_calendar = true;
+                }
+                ("END", None) if line.value.eq_ignore_ascii_case("VCALENDAR") => {
+                    break;
+                }
+                ("METHOD", None) if in_calendar => {
+                    calendar.method = line.value.trim().to_ascii_uppercase().into();
+                }
+                ("BEGIN", None) if in_calendar && line.value.eq_ignore_ascii_case("VEVENT") => {
+                    event = Object::with_capacity(8)
+                        .with_property(Property::_T("@type".to_string()), "Event")
+                        .into();
+                }
+                ("BEGIN", Some(_)) => {
+                    // Skip nested components such as VALARM
+                    nested_level += 1;
+                }
+                ("END", Some(_)) if nested_level > 0 => {
+                    nested_level -= 1;
+                }
+                ("END", Some(_)) => {
+                    let obj = event.take().unwrap();
+                    if let Some(uid) = obj.get(&Property::Uid).as_string() {
+                        calendar.events.push(ICalendarEvent {
+                            uid: uid.to_string(),
+                            sequence: obj.get(&Property::parse("sequence")).as_uint().unwrap_or(0),
+                            recurrence_id: obj
+                                .get(&Property::parse("recurrenceId"))
+                                .as_string()
+                                .map(|id| id.to_string()),
+                            event: obj,
+                        });
+                    }
+                }
+                (_, Some(obj)) if nested_level == 0 => {
+                    line.apply(obj);
+                }
+                _ => (),
+            }
+        }
+
+        if in_calendar {
+            Some(calendar)
+        } else {
+            None
+        }
+    }
+
+    /// Merges the components of a calendar object resource into a single event,
+    /// adding modified instances as recurrence overrides of the master event.
+    pub fn into_event(self) -> Option<Object<Value>> {
+        let mut events = self.events;
+        let mut event = events.remove(
+            events
+                .iter()
+                .position(|event| event.recurrence_id.is_none())?,
+        );
+        for instance in events {
+            if instance.uid != event.uid {
+                return None;
+            }
+            let patch = override_patch(&event.event, &instance.event);
+            if let Value::Object(overrides) = event
+                .event
+                .properties
+                .get_mut_or_insert_with(Property::parse("recurrenceOverrides"), || {
+                    Value::Object(Object::with_capacity(1))
+                })
+            {
+                overrides.set(Property::_T(instance.recurrence_id?), patch);
+            }
+        }
+        Some(event.event)
+    }
+
+    pub fn build(method: Option<&str>, event: &Object<Value>, dt_stamp: i64) -> String {
+        let mut ical = String::with_capacity(512);
+        let _ = write!(
+            ical,
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:{PRODID}\r\n"
+        );
+        if let Some(method) = method {
+            let _ = write!(ical, "METHOD:{method}\r\n");
+        }
+        write_event(&mut ical, method, event, dt_stamp);
+
+        // Modified occurrences are written as separate components
+        if let Some(overrides) = event.get(&Property::parse("recurrenceOverrides")).as_obj() {
+            for (recurrence_id, patch) in &overrides.properties {
+                if let Some(recurrence_id) = parse_local_date_time(&recurrence_id.to_string())
+                    .filter(|_| {
+                        patch.as_obj().map_or(false, |patch| {
+                            patch.get(&Property::_T("excluded".to_string())) != &Value::Bool(true)
+                        })
+                    })
+                {
+                    let instance = event.instance(&Occurrence {
+                        recurrence_id,
+                        start: recurrence_id,
+                        end: recurrence_id,
+                    });
+                    write_event(&mut ical, method, &instance, dt_stamp);
+                }
+            }
+        }
+
+        ical.push_str("END:VCALENDAR\r\n");
+        ical
+    }
+}
+
+fn write_event(ical: &mut String, method: Option<&str>, event: &Object<Value>, dt_stamp: i64) {
+    ical.push_str("BEGIN:VEVENT\r\n");
+    if let Some(uid) = event.get(&Property::Uid).as_string() {
+        write_line(ical, "UID", &[], &escape(uid));
+    }
+    if let Some(recurrence_id) = event
+        .get(&Property::parse("recurrenceId"))
+        .as_string()
+        .and_then(parse_local_date_time)
+    {
+        write_date(ical, "RECURRENCE-ID", event, &recurrence_id);
+    }
+    write_line(
+        ical,
+        "DTSTAMP",
+        &[],
+        &NaiveDateTime::from_timestamp_opt(dt_stamp, 0)
+            .unwrap_or_default()
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string(),
+    );
+    write_line(
+        ical,
+        "SEQUENCE",
+        &[],
+        &event
+            .get(&Property::parse("sequence"))
+            .as_uint()
+            .unwrap_or(0)
+            .to_string(),
+    );
+    if let Some(start) = event.start() {
+        write_date(ical, "DTSTART", event, &start);
+    }
+    if let Some(duration) = event.get(&Property::parse("duration")).as_string() {
+        write_line(ical, "DURATION", &[], duration);
+    }
+    if let Some(title) = event.get(&Property::Title).as_string() {
+        write_line(ical, "SUMMARY", &[], &escape(title));
+    }
+    if let Some(description) = event.get(&Property::Description).as_string() {
+        write_line(ical, "DESCRIPTION", &[], &escape(description));
+    }
+    if let Some(locations) = event.get(&Property::parse("locations")).as_obj() {
+        for location in locations.properties.values() {
+            if let Some(name) = location
+                .as_obj()
+                .and_then(|l| l.get(&Property::_T("name".to_string())).as_string())
+            {
+                write_line(ical, "LOCATION", &[], &escape(name));
+                break;
+            }
+        }
+    }
+    if method == Some("CANCEL") {
+        write_line(ical, "STATUS", &[], "CANCELLED");
+    } else if let Some(status) = event.get(&Property::parse("status")).as_string() {
+        write_line(ical, "STATUS", &[], &status.to_ascii_uppercase());
+    }
+    if let Some(rules) = event.get(&Property::parse("recurrenceRules")).as_list() {
+        for rule in rules.iter().filter_map(|rule| rule.as_obj()) {
+            if let Some(rrule) = build_rrule(rule) {
+                write_line(ical, "RRULE", &[], &rrule);
+            }
+        }
+    }
+    if let Some(overrides) = event.get(&Property::parse("recurrenceOverrides")).as_obj() {
+        for (recurrence_id, patch) in &overrides.properties {
+            if patch.as_obj().map_or(false, |patch| {
+                patch.get(&Property::_T("excluded".to_string())) == &Value::Bool(true)
+            }) {
+                if let Some(date) = parse_local_date_time(&recurrence_id.to_string()) {
+                    write_line(
+                        ical,
+                        "EXDATE",
+                        &[],
+                        &date.format("%Y%m%dT%H%M%S").to_string(),
+                    );
+                }
+            }
+        }
+    }
+    if let Some(participants) = event.get(&Property::parse("participants")).as_obj() {
+        for participant in participants.properties.values().filter_map(|p| p.as_obj()) {
+            let email = if let Some(email) = participant_email(participant) {
+                email
+            } else {
+                continue;
+            };
+            let mut params = Vec::new();
+            if let Some(name) = participant
+                .get(&Property::_T("name".to_string()))
+                .as_string()
+            {
+                params.push(("CN", name));
+            }
+            let roles = participant.get(&Property::_T("roles".to_string())).as_obj();
+            let is_owner = roles.map_or(false, |roles| {
+                roles.get(&Property::_T("owner".to_string())) == &Value::Bool(true)
+            });
+            let value = format!("mailto:{email}");
+            if is_owner {
+                write_line(ical, "ORGANIZER", &params, &value);
+            }
+            if !is_owner
+                || roles.map_or(false, |roles| {
+                    roles.get(&Property::_T("attendee".to_string())) == &Value::Bool(true)
+                })
+            {
+                let status = participant
+                    .get(&Property::_T("participationStatus".to_string()))
+                    .as_string()
+                    .unwrap_or("needs-action")
+                    .to_ascii_uppercase();
+                params.push(("PARTSTAT", status.as_str()));
+                if participant.get(&Property::_T("expectReply".to_string())) == &Value::Bool(true) {
+                    params.push(("RSVP", "TRUE"));
+                }
+                write_line(ical, "ATTENDEE", &params, &value);
+            }
+        }
+    }
+    ical.push_str("END:VEVENT\r\n");
+}
+
+fn write_date(ical: &mut String, name: &str, event: &Object<Value>, date: &NaiveDateTime) {
+    if event.get(&Property::parse("showWithoutTime")) == &Value::Bool(true) {
+        write_line(
+            ical,
+            name,
+            &[("VALUE", "DATE")],
+            &date.format("%Y%m%d").to_string(),
+        );
+    } else {
+        match event.get(&Property::parse("timeZone")).as_string() {
+            Some("Etc/UTC" | "UTC") => {
+                write_line(ical, name, &[], &date.format("%Y%m%dT%H%M%SZ").to_string());
+            }
+            Some(tz) => {
+                write_line(
+                    ical,
+                    name,
+                    &[("TZID", tz)],
+                    &date.format("%Y%m%dT%H%M%S").to_string(),
+                );
+            }
+            None => {
+                write_line(ical, name, &[], &date.format("%Y%m%dT%H%M%S").to_string());
+            }
+        }
+    }
+}
+
+impl Participants for Object<Value> {
+    fn organizer(&self) -> Option<String> {
+        if let Some(participants) = self.get(&Property::parse("participants")).as_obj() {
+            for participant in participants.properties.values().filter_map(|p| p.as_obj()) {
+                if participant
+                    .get(&Property::_T("roles".to_string()))
+                    .as_obj()
+                    .map_or(false, |roles| {
+                        roles.get(&Property::_T("owner".to_string())) == &Value::Bool(true)
+                    })
+                {
+                    if let Some(email) = participant_email(participant) {
+                        return Some(email);
+                    }
+                }
+            }
+        }
+
+        self.get(&Property::parse("replyTo"))
+            .as_obj()
+            .and_then(|reply_to| reply_to.get(&Property::_T("imip".to_string())).as_string())
+            .and_then(parse_mailto)
+    }
+
+    fn attendees(&self) -> Vec<(String, Option<String>)> {
+        let mut attendees = Vec::new();
+        if let Some(participants) = self.get(&Property::parse("participants")).as_obj() {
+            for participant in participants.properties.values().filter_map(|p| p.as_obj()) {
+                if participant
+                    .get(&Property::_T("roles".to_string()))
+                    .as_obj()
+                    .map_or(true, |roles| {
+                        roles.get(&Property::_T("attendee".to_string())) == &Value::Bool(true)
+                            || roles.get(&Property::_T("owner".to_string())) != &Value::Bool(true)
+                    })
+                {
+                    if let Some(email) = participant_email(participant) {
+                        attendees.push((
+                            email,
+                            participant
+                                .get(&Property::_T("participationStatus".to_string()))
+                                .as_string()
+                                .map(|status| status.to_string()),
+                        ));
+                    }
+                }
+            }
+        }
+        attendees
+    }
+
+    fn set_participation_status(&mut self, email: &str, status: &str) -> bool {
+        if let Some(participants) = self
+            .properties
+            .get_mut(&Property::parse("participants"))
+            .and_then(|p| p.as_obj_mut())
+        {
+            for participant in participants
+                .properties
+                .values_mut()
+                .filter_map(|p| p.as_obj_mut())
+            {
+                if participant_email(participant).map_or(false, |p| p == email) {
+                    participant.set(
+                        Property::_T("participationStatus".to_string()),
+                        status.to_string(),
+                    );
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl<'x> ContentLine<'x> {
+    pub fn parse(line: &'x str) -> Option<Self> {
+        // Split the name and parameters from the value, ignoring colons in quoted strings
+        let mut in_quote = false;
+        let mut value_start = None;
+        for (pos, ch) in line.char_indices() {
+            match ch {
+                '"' => in_quote = !in_quote,
+                ':' if !in_quote => {
+                    value_start = pos.into();
+                    break;
+                }
+                _ => (),
+            }
+        }
+        let value_start = value_start?;
+        let mut parts = line[..value_start].split(';');
+        let name = parts.next()?.trim().to_ascii_uppercase();
+        let params = parts
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((
+                    key.trim().to_ascii_uppercase(),
+                    value.trim().trim_matches('"'),
+                ))
+            })
+            .collect();
+
+        Some(ContentLine {
+            name,
+            params,
+            value: &line[value_start + 1..],
+        })
+    }
+
+    pub fn param(&self, name: &str) -> Option<&'x str> {
+        self.params
+            .iter()
+            .find_map(|(key, value)| if key == name { Some(*value) } else { None })
+    }
+
+    fn apply(&self, event: &mut Object<Value>) {
+        match self.name.as_str() {
+            "UID" => {
+                event.set(Property::Uid, unescape(self.value));
+            }
+            "SEQUENCE" => {
+                if let Ok(sequence) = self.value.trim().parse::<u64>() {
+                    event.set(Property::parse("sequence"), Value::UnsignedInt(sequence));
+                }
+            }
+            "SUMMARY" => {
+                event.set(Property::Title, unescape(self.value));
+            }
+            "DESCRIPTION" => {
+                event.set(Property::Description, unescape(self.value));
+            }
+            "LOCATION" => {
+                event.set(
+                    Property::parse("locations"),
+                    Object::with_capacity(1).with_property(
+                        Property::_T("1".to_string()),
+                        Object::with_capacity(2)
+                            .with_property(Property::_T("@type".to_string()), "Location")
+                            .with_property(Property::_T("name".to_string()), unescape(self.value)),
+                    ),
+                );
+            }
+            "STATUS" => {
+                let status = self.value.trim().to_ascii_lowercase();
+                if matches!(status.as_str(), "confirmed" | "tentative" | "cancelled") {
+                    event.set(Property::parse("status"), status);
+                }
+            }
+            "DTSTART" | "RECURRENCE-ID" => {
+                if let Some((date, is_utc, is_date)) = parse_ical_date(self.value) {
+                    if self.name == "DTSTART" {
+                        event.set(Property::parse("start"), format_local_date_time(&date));
+                        if is_date {
+                            event.set(Property::parse("showWithoutTime"), true);
+                        } else if is_utc {
+                            event.set(Property::parse("timeZone"), "Etc/UTC");
+                        } else if let Some(tz) = self.param("TZID") {
+                            event.set(Property::parse("timeZone"), tz.to_string());
+                        }
+                    } else {
+                        event.set(
+                            Property::parse("recurrenceId"),
+                            format_local_date_time(&date),
+                        );
+                    }
+                }
+            }
+            "DTEND" => {
+                if let (Some((end, _, _)), Some(start)) =
+                    (parse_ical_date(self.value), event.start())
+                {
+                    let seconds = (end - start).num_seconds();
+                    if seconds >= 0 {
+                        event.set(Property::parse("duration"), format_duration(seconds));
+                    }
+                }
+            }
+            "DURATION" => {
+                event.set(Property::parse("duration"), self.value.trim().to_string());
+            }
+            "RRULE" => {
+                if let Some(rule) = parse_rrule(self.value) {
+                    if let Value::List(rules) = event
+                        .properties
+                        .get_mut_or_insert_with(Property::parse("recurrenceRules"), || {
+                            Value::List(vec![])
+                        })
+                    {
+                        rules.push(Value::Object(rule));
+                    }
+                }
+            }
+            "EXDATE" => {
+                for date in self.value.split(',') {
+                    if let Some((date, _, _)) = parse_ical_date(date) {
+                        if let Value::Object(overrides) = event
+                            .properties
+                            .get_mut_or_insert_with(Property::parse("recurrenceOverrides"), || {
+                                Value::Object(Object::with_capacity(1))
+                            })
+                        {
+                            overrides.set(
+                                Property::_T(format_local_date_time(&date)),
+                                Object::with_capacity(1)
+                                    .with_property(Property::_T("excluded".to_string()), true),
+                            );
+                        }
+                    }
+                }
+            }
+            "ORGANIZER" | "ATTENDEE" => {
+                let email = if let Some(email) = parse_mailto(self.value) {
+                    email
+                } else {
+                    return;
+                };
+                let is_organizer = self.name == "ORGANIZER";
+
+                // Merge with an existing participant with the same address
+                let participant_list = event
+                    .properties
+                    .get_mut_or_insert_with(Property::parse("participants"), || {
+                        Value::Object(Object::with_capacity(2))
+                    });
+                let participant_list = if let Value::Object(participant_list) = participant_list {
+                    participant_list
+                } else {
+                    return;
+                };
+                let participant_id = if let Some(participant_id) =
+                    participant_list.properties.iter().find_map(|(id, p)| {
+                        p.as_obj()
+                            .and_then(participant_email)
+                            .filter(|p| p == &email)
+                            .map(|_| id.clone())
+                    }) {
+                    participant_id
+                } else {
+                    let participant_id =
+                        Property::_T((participant_list.properties.len() + 1).to_string());
+                    participant_list.append(
+                        participant_id.clone(),
+                        Object::with_capacity(6)
+                            .with_property(Property::_T("@type".to_string()), "Participant")
+                            .with_property(
+                                Property::_T("sendTo".to_string()),
+                                Object::with_capacity(1).with_property(
+                                    Property::_T("imip".to_string()),
+                                    format!("mailto:{email}"),
+                                ),
+                            )
+                            .with_property(Property::_T("email".to_string()), email.clone())
+                            .with_property(
+                                Property::_T("roles".to_string()),
+                                Object::with_capacity(2),
+                            ),
+                    );
+                    participant_id
+                };
+                let participant = if let Some(Value::Object(participant)) =
+                    participant_list.properties.get_mut(&participant_id)
+                {
+                    participant
+                } else {
+                    return;
+                };
+                if let Some(name) = self.param("CN") {
+                    participant.set(Property::_T("name".to_string()), name.to_string());
+                }
+                if let Some(roles) = participant
+                    .properties
+                    .get_mut(&Property::_T("roles".to_string()))
+                    .and_then(|r| r.as_obj_mut())
+                {
+                    roles.set(
+                        Property::_T(if is_organizer { "owner" } else { "attendee" }.to_string()),
+                        true,
+                    );
+                }
+                if !is_organizer {
+                    if let Some(status) = self.param("PARTSTAT") {
+                        participant.set(
+                            Property::_T("participationStatus".to_string()),
+                            status.to_ascii_lowercase(),
+                        );
+                    }
+                    if self
+                        .param("RSVP")
+                        .map_or(false, |rsvp| rsvp.eq_ignore_ascii_case("TRUE"))
+                    {
+                        participant.set(Property::_T("expectReply".to_string()), true);
+                    }
+                } else {
+                    event.set(
+                        Property::parse("replyTo"),
+                        Object::with_capacity(1).with_property(
+                            Property::_T("imip".to_string()),
+                            format!("mailto:{email}"),
+                        ),
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Builds a recurrence override patch from the properties of an occurrence
+/// that differ from the main event.
+pub fn override_patch(event: &Object<Value>, occurrence: &Object<Value>) -> Object<Value> {
+    let mut patch = Object::with_capacity(4);
+    for name in ["start", "duration", "title", "description", "status"] {
+        let property = Property::parse(name);
+        let value = occurrence.get(&property);
+        if value != &Value::Null && value != event.get(&property) {
+            patch.append(Property::_T(name.to_string()), value.clone());
+        }
+    }
+    patch
+}
+
+pub fn participant_email(participant: &Object<Value>) -> Option<String> {
+    participant
+        .get(&Property::_T("email".to_string()))
+        .as_string()
+        .map(|email| email.trim().to_lowercase())
+        .or_else(|| {
+            participant
+                .get(&Property::_T("sendTo".to_string()))
+                .as_obj()
+                .and_then(|send_to| send_to.get(&Property::_T("imip".to_string())).as_string())
+                .and_then(parse_mailto)
+        })
+        .filter(|email| !email.is_empty())
+}
+
+pub fn parse_mailto(value: &str) -> Option<String> {
+    let value = value.trim();
+    let email = if value.len() > 7 && value[..7].eq_ignore_ascii_case("mailto:") {
+        &value[7..]
+    } else {
+        value
+    };
+    if email.contains('@') {
+        Some(email.trim().to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn parse_ical_date(value: &str) -> Option<(chrono::NaiveDateTime, bool, bool)> {
+    let value = value.trim();
+    let (value, is_utc) = if let Some(value) = value.strip_suffix('Z') {
+        (value, true)
+    } else {
+        (value, false)
+    };
+    if value.len() == 8 {
+        chrono::NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| (date, is_utc, true))
+    } else {
+        chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|date| (date, is_utc, false))
+    }
+}
+
+fn parse_rrule(value: &str) -> Option<Object<Value>> {
+    let mut rule =
+        Object::with_capacity(4).with_property(Property::_T("@type".to_string()), "RecurrenceRule");
+    for part in value.trim().split(';') {
+        let (key, value) = part.split_once('=')?;
+        let value = value.trim();
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                rule.set(
+                    Property::_T("frequency".to_string()),
+                    value.to_ascii_lowercase(),
+                );
+            }
+            "INTERVAL" | "COUNT" => {
+                rule.set(
+                    Property::_T(key.trim().to_ascii_lowercase()),
+                    Value::UnsignedInt(value.parse().ok()?),
+                );
+            }
+            "UNTIL" => {
+                let (date, _, _) = parse_ical_date(value)?;
+                rule.set(
+                    Property::_T("until".to_string()),
+                    format_local_date_time(&date),
+                );
+            }
+            "BYDAY" => {
+                let mut days = Vec::new();
+                for day in value.split(',') {
+                    let day = day.trim();
+                    if day.len() < 2 || !day.is_char_boundary(day.len() - 2) {
+                        return None;
+                    }
+                    let (nth, weekday) = day.split_at(day.len() - 2);
+                    let mut by_day = Object::with_capacity(3)
+                        .with_property(Property::_T("@type".to_string()), "NDay")
+                        .with_property(
+                            Property::_T("day".to_string()),
+                            weekday.to_ascii_lowercase(),
+                        );
+                    let nth = nth.trim_start_matches('+');
+                    if !nth.is_empty() {
+                        // Negative ordinals cannot be represented as unsigned values
+                        by_day.set(
+                            Property::_T("nthOfPeriod".to_string()),
+                            Value::UnsignedInt(nth.parse().ok()?),
+                        );
+                    }
+                    days.push(Value::Object(by_day));
+                }
+                rule.set(Property::_T("byDay".to_string()), Value::List(days));
+            }
+            "BYMONTHDAY" => {
+                let mut days = Vec::new();
+                for day in value.split(',') {
+                    days.push(Value::UnsignedInt(day.trim().parse().ok()?));
+                }
+                rule.set(Property::_T("byMonthDay".to_string()), Value::List(days));
+            }
+            "BYMONTH" => {
+                rule.set(
+                    Property::_T("byMonth".to_string()),
+                    Value::List(
+                        value
+                            .split(',')
+                            .map(|month| Value::Text(month.trim().to_string()))
+                            .collect(),
+                    ),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    if rule.get(&Property::_T("frequency".to_string())) != &Value::Null {
+        Some(rule)
+    } else {
+        None
+    }
+}
+
+fn build_rrule(rule: &Object<Value>) -> Option<String> {
+    let get = |name: &str| rule.get(&Property::_T(name.to_string()));
+    let mut rrule = format!(
+        "FREQ={}",
+        get("frequency").as_string()?.to_ascii_uppercase()
+    );
+    if let Some(interval) = get("interval").as_uint() {
+        let _ = write!(rrule, ";INTERVAL={interval}");
+    }
+    if let Some(count) = get("count").as_uint() {
+        let _ = write!(rrule, ";COUNT={count}");
+    }
+    if let Some(until) = get("until").as_string().and_then(parse_local_date_time) {
+        let _ = write!(rrule, ";UNTIL={}", until.format("%Y%m%dT%H%M%S"));
+    }
+    if let Some(days) = get("byDay").as_list() {
+        let days = days
+            .iter()
+            .filter_map(|day| {
+                let day = day.as_obj()?;
+                let weekday = day
+                    .get(&Property::_T("day".to_string()))
+                    .as_string()?
+                    .to_ascii_uppercase();
+                Some(
+                    if let Some(nth) = day.get(&Property::_T("nthOfPeriod".to_string())).as_uint() {
+                        format!("{nth}{weekday}")
+                    } else {
+                        weekday
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        if !days.is_empty() {
+            let _ = write!(rrule, ";BYDAY={}", days.join(","));
+        }
+    }
+    for (name, key) in [("byMonthDay", "BYMONTHDAY"), ("byMonth", "BYMONTH")] {
+        if let Some(values) = get(name).as_list() {
+            let values = values
+                .iter()
+                .filter_map(|value| match value {
+                    Value::UnsignedInt(value) => Some(value.to_string()),
+                    Value::Text(value) => Some(value.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            if !values.is_empty() {
+                let _ = write!(rrule, ";{key}={}", values.join(","));
+            }
+        }
+    }
+
+    Some(rrule)
+}
+
+fn format_duration(seconds: i64) -> String {
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+    let mut duration = String::from("P");
+    if days > 0 {
+        let _ = write!(duration, "{days}D");
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        duration.push('T');
+        if hours > 0 {
+            let _ = write!(duration, "{hours}H");
+        }
+        if minutes > 0 {
+            let _ = write!(duration, "{minutes}M");
+        }
+        if seconds > 0 || (hours == 0 && minutes == 0) {
+            let _ = write!(duration, "{seconds}S");
+        }
+    }
+    duration
+}
+
+pub(crate) fn unfold(text: &str) -> String {
+    let mut unfolded = String::with_capacity(text.len());
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+            unfolded.push_str(continuation);
+        } else {
+            if !unfolded.is_empty() {
+                unfolded.push('\n');
+            }
+            unfolded.push_str(line);
+        }
+    }
+    unfolded
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => result.push('\n'),
+                Some(ch) => result.push(ch),
+                None => (),
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                result.push('\\');
+                result.push(ch);
+            }
+            '\n' => result.push_str("\\n"),
+            '\r' => (),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+pub(crate) fn write_line(ical: &mut String, name: &str, params: &[(&str, &str)], value: &str) {
+    let mut line = String::with_capacity(name.len() + value.len() + 2);
+    line.push_str(name);
+    for (key, param) in params {
+        let _ = if param.contains([':', ';', ',']) {
+            write!(line, ";{key}=\"{}\"", param.replace('"', ""))
+        } else {
+            write!(line, ";{key}={param}")
+        };
+    }
+    line.push(':');
+    line.push_str(value);
+
+    // Fold lines longer than 75 octets
+    let mut line_len = 0;
+    for ch in line.chars() {
+        if line_len + ch.len_utf8() > 75 {
+            ical.push_str("\r\n ");
+            line_len = 1;
+        }
+        ical.push(ch);
+        line_len += ch.len_utf8();
+    }
+    ical.push_str("\r\n");
+}
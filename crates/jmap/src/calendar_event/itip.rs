@@ -0,0 +1,515 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap_proto::{
+    error::method::MethodError,
+    object::Object,
+    types::{collection::Collection, property::Property, value::Value},
+};
+use mail_builder::{
+    headers::content_type::ContentType,
+    mime::{BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::{Message, PartType};
+use smtp_proto::{MailFrom, RcptTo};
+use store::{
+    query::Filter,
+    write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder},
+};
+use utils::listener::ServerInstance;
+
+use crate::{auth::AccessToken, submission::LocalSubmissionError, JMAP};
+
+use super::{
+    ical::{override_patch, participant_email, ICalendar, ICalendarEvent, Participants},
+    set::SetContext,
+};
+
+// Properties that belong to the recipient's copy of the event and are
+// kept when the organizer sends an update.
+const LOCAL_PROPERTIES: &[&str] = &["alerts", "color", "keywords", "useDefaultAlerts"];
+
+pub type SchedulingChange = (Option<Object<Value>>, Option<Object<Value>>);
+
+impl JMAP {
+    pub async fn calendar_event_schedule(
+        &self,
+        instance: &Arc<ServerInstance>,
+        account_id: u32,
+        access_token: &AccessToken,
+        changes: Vec<SchedulingChange>,
+    ) -> Result<(), MethodError> {
+        // Obtain the account's addresses
+        let account_name = if access_token.primary_id == account_id {
+            access_token.name.clone()
+        } else {
+            self.get_account_name(account_id).await?.unwrap_or_default()
+        };
+        let account_emails = self
+            .directory
+            .emails_by_name(&account_name)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|email| email.to_lowercase())
+            .collect::<Vec<_>>();
+
+        for (current, event) in changes {
+            let organizer = if let Some(organizer) = event
+                .as_ref()
+                .or(current.as_ref())
+                .and_then(|event| event.organizer())
+            {
+                organizer
+            } else {
+                continue;
+            };
+
+            if account_emails.contains(&organizer) {
+                // Send invitations or cancellations to attendees
+                let mut attendees = Vec::new();
+                if let Some(event) = &event {
+                    attendees = event
+                        .attendees()
+                        .into_iter()
+                        .map(|(email, _)| email)
+                        .filter(|email| email != &organizer)
+                        .collect::<Vec<_>>();
+                    let method =
+                        if event.get(&Property::parse("status")).as_string() == Some("cancelled") {
+                            "CANCEL"
+                        } else {
+                            "REQUEST"
+                        };
+                    self.send_itip_message(instance, &organizer, attendees.clone(), method, event)
+                        .await;
+                }
+                if let Some(current) = &current {
+                    let removed = current
+                        .attendees()
+                        .into_iter()
+                        .map(|(email, _)| email)
+                        .filter(|email| email != &organizer && !attendees.contains(email))
+                        .collect::<Vec<_>>();
+                    self.send_itip_message(instance, &organizer, removed, "CANCEL", current)
+                        .await;
+                }
+            } else if let (Some(current), Some(event)) = (&current, &event) {
+                // Reply to the organizer when the participation status changes
+                let previous = current.attendees();
+                for (email, status) in event.attendees() {
+                    if status.is_some()
+                        && account_emails.contains(&email)
+                        && previous
+                            .iter()
+                            .find(|(previous_email, _)| previous_email == &email)
+                            .map_or(true, |(_, previous_status)| previous_status != &status)
+                    {
+                        let mut reply = event.clone();
+                        if let Some(Value::Object(participants)) =
+                            reply.properties.get_mut(&Property::parse("participants"))
+                        {
+                            participants.properties = std::mem::take(&mut participants.properties)
+                                .into_iter()
+                                .filter(|(_, participant)| {
+                                    participant.as_obj().and_then(participant_email).map_or(
+                                        false,
+                                        |participant| {
+                                            participant == email || participant == organizer
+                                        },
+                                    )
+                                })
+                                .collect();
+                        }
+                        self.send_itip_message(
+                            instance,
+                            &email,
+                            vec![organizer.clone()],
+                            "REPLY",
+                            &reply,
+                        )
+                        .await;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send_itip_message(
+        &self,
+        instance: &Arc<ServerInstance>,
+        from: &str,
+        recipients: Vec<String>,
+        method: &str,
+        event: &Object<Value>,
+    ) {
+        if recipients.is_empty() {
+            return;
+        }
+
+        // Build message
+        let title = event
+            .get(&Property::Title)
+            .as_string()
+            .unwrap_or("Untitled event");
+        let (subject, text) = match method {
+            "REQUEST" => (
+                format!("Invitation: {title}"),
+                format!("You have been invited to \"{title}\" by {from}."),
+            ),
+            "CANCEL" => (
+                format!("Cancelled: {title}"),
+                format!("The event \"{title}\" has been cancelled by {from}."),
+            ),
+            _ => (
+                format!("Reply: {title}"),
+                format!("{from} has replied to the invitation \"{title}\"."),
+            ),
+        };
+        let message = MessageBuilder::new()
+            .from(from)
+            .to(recipients
+                .iter()
+                .map(|rcpt| rcpt.as_str())
+                .collect::<Vec<_>>())
+            .subject(subject)
+            .body(MimePart::new(
+                ContentType::new("multipart/alternative"),
+                BodyPart::Multipart(vec![
+                    MimePart::new(
+                        ContentType::new("text/plain").attribute("charset", "utf-8"),
+                        BodyPart::Text(text.into()),
+                    ),
+                    MimePart::new(
+                        ContentType::new("text/calendar")
+                            .attribute("charset", "utf-8")
+                            .attribute("method", method),
+                        BodyPart::Text(ICalendar::build(Some(method), event, now() as i64).into()),
+                    ),
+                ]),
+            ))
+            .write_to_vec()
+            .unwrap_or_default();
+
+        // Submit message
+        match self
+            .submit_message(
+                instance,
+                MailFrom {
+                    address: from.to_string(),
+                    ..Default::default()
+                },
+                recipients
+                    .into_iter()
+                    .map(|address| RcptTo {
+                        address,
+                        ..Default::default()
+                    })
+                    .collect(),
+                message,
+            )
+            .await
+        {
+            Ok(submission) => {
+                for (rcpt, response) in submission.responses {
+                    if let Some(response) = response {
+                        tracing::debug!(
+                            context = "calendar_event_schedule",
+                            event = "rejected",
+                            from = from,
+                            rcpt = rcpt,
+                            reason = response.trim(),
+                            "Scheduling message rejected for recipient."
+                        );
+                    }
+                }
+            }
+            Err(LocalSubmissionError::MailFrom(reason) | LocalSubmissionError::Data(reason)) => {
+                tracing::debug!(
+                    context = "calendar_event_schedule",
+                    event = "rejected",
+                    from = from,
+                    reason = reason,
+                    "Failed to send scheduling message."
+                );
+            }
+        }
+    }
+
+    pub async fn calendar_event_ingest_imip(
+        &self,
+        account_id: u32,
+        sender: &str,
+        is_sender_authenticated: bool,
+        raw_message: &[u8],
+    ) -> Result<Option<u64>, MethodError> {
+        // Find iCalendar parts
+        let message = if let Some(message) = Message::parse(raw_message) {
+            message
+        } else {
+            return Ok(None);
+        };
+        let sender = sender.trim().to_lowercase();
+        let mut changes = ChangeLogBuilder::new();
+        let mut ctx = None;
+
+        for part in &message.parts {
+            if !part.content_type().map_or(false, |ct| {
+                ct.ctype().eq_ignore_ascii_case("text")
+                    && ct
+                        .subtype()
+                        .map_or(false, |st| st.eq_ignore_ascii_case("calendar"))
+            }) {
+                continue;
+            }
+            let calendar = match &part.body {
+                PartType::Text(text) | PartType::Html(text) => ICalendar::parse(text.as_ref()),
+                PartType::Binary(bytes) | PartType::InlineBinary(bytes) => {
+                    std::str::from_utf8(bytes.as_ref())
+                        .ok()
+                        .and_then(ICalendar::parse)
+                }
+                _ => None,
+            };
+            let (method, events) = match calendar {
+                Some(ICalendar {
+                    method: Some(method),
+                    events,
+                }) => (method, events),
+                _ => continue,
+            };
+
+            for itip in events {
+                // Obtain the calendar context on first use
+                if ctx.is_none() {
+                    ctx = SetContext {
+                        calendar_ids: self.calendar_get_or_create(account_id).await?,
+                        can_add_calendar_ids: None,
+                        can_remove_calendar_ids: None,
+                    }
+                    .into();
+                }
+                let ctx = ctx.as_ref().unwrap();
+
+                // Obtain the existing event, if any
+                let current = if let Some(document_id) = self
+                    .filter(
+                        account_id,
+                        Collection::CalendarEvent,
+                        vec![Filter::eq(Property::Uid, itip.uid.as_str())],
+                    )
+                    .await?
+                    .results
+                    .min()
+                {
+                    self.get_property::<HashedValue<Object<Value>>>(
+                        account_id,
+                        Collection::CalendarEvent,
+                        document_id,
+                        Property::Value,
+                    )
+                    .await?
+                    .map(|current| (document_id, current))
+                } else {
+                    None
+                };
+
+                let event = match self.itip_apply(
+                    &method,
+                    &sender,
+                    is_sender_authenticated,
+                    itip,
+                    current.as_ref(),
+                    ctx,
+                ) {
+                    Some(event) => event,
+                    None => continue,
+                };
+
+                // Validate and write changes
+                let mut batch = BatchBuilder::new();
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::CalendarEvent);
+                if let Some((document_id, current)) = current {
+                    match self.calendar_event_validate(event, current.into(), ctx) {
+                        Ok(builder) => {
+                            batch.update_document(document_id).custom(builder);
+                            changes.log_update(Collection::CalendarEvent, document_id);
+                        }
+                        Err(err) => {
+                            tracing::debug!(
+                                context = "calendar_event_ingest_imip",
+                                event = "invalid",
+                                account_id = account_id,
+                                reason = ?err,
+                                "Failed to update event from iMIP message.");
+                            continue;
+                        }
+                    }
+                } else {
+                    match self.calendar_event_validate(event, None, ctx) {
+                        Ok(builder) => {
+                            let document_id = self
+                                .assign_document_id(account_id, Collection::CalendarEvent)
+                                .await?;
+                            batch.create_document(document_id).custom(builder);
+                            changes.log_insert(Collection::CalendarEvent, document_id);
+                        }
+                        Err(err) => {
+                            tracing::debug!(
+                                context = "calendar_event_ingest_imip",
+                                event = "invalid",
+                                account_id = account_id,
+                                reason = ?err,
+                                "Failed to create event from iMIP message.");
+                            continue;
+                        }
+                    }
+                }
+                match self.store.write(batch.build()).await {
+                    Ok(_) | Err(store::Error::AssertValueFailed) => (),
+                    Err(err) => {
+                        tracing::error!(
+                            event = "error",
+                            context = "calendar_event_ingest_imip",
+                            account_id = account_id,
+                            error = ?err,
+                            "Failed to write calendar event.");
+                        return Err(MethodError::ServerPartialFail);
+                    }
+                }
+            }
+        }
+
+        if !changes.is_empty() {
+            Ok(Some(self.commit_changes(account_id, changes).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn itip_apply(
+        &self,
+        method: &str,
+        sender: &str,
+        is_sender_authenticated: bool,
+        itip: ICalendarEvent,
+        current: Option<&(u32, HashedValue<Object<Value>>)>,
+        ctx: &SetContext,
+    ) -> Option<Object<Value>> {
+        let current = current.map(|(_, current)| &current.inner);
+        let current_sequence = current
+            .and_then(|current| current.get(&Property::parse("sequence")).as_uint())
+            .unwrap_or(0);
+
+        match method {
+            "REQUEST" | "CANCEL" => {
+                // Only the organizer can send requests and cancellations, and
+                // the sender address has to be authenticated to tell
+                if !is_sender_authenticated
+                    || itip.event.organizer().as_deref() != Some(sender)
+                    || current.map_or(false, |current| {
+                        current.organizer().as_deref() != Some(sender)
+                            || current_sequence > itip.sequence
+                    })
+                {
+                    return None;
+                }
+
+                if let Some(recurrence_id) = itip.recurrence_id {
+                    // Update a single occurrence of an existing event
+                    let mut event = current?.clone();
+                    let patch = if method == "CANCEL" {
+                        Object::with_capacity(1)
+                            .with_property(Property::_T("excluded".to_string()), true)
+                    } else {
+                        override_patch(&event, &itip.event)
+                    };
+                    if let Value::Object(overrides) = event
+                        .properties
+                        .get_mut_or_insert_with(Property::parse("recurrenceOverrides"), || {
+                            Value::Object(Object::with_capacity(1))
+                        })
+                    {
+                        overrides.set(Property::_T(recurrence_id), patch);
+                    }
+                    Some(event)
+                } else if method == "CANCEL" {
+                    let mut event = current?.clone();
+                    event.set(Property::parse("status"), "cancelled");
+                    event.set(
+                        Property::parse("sequence"),
+                        Value::UnsignedInt(itip.sequence),
+                    );
+                    Some(event)
+                } else {
+                    let mut event = itip.event;
+                    if let Some(current) = current {
+                        event.set(
+                            Property::CalendarIds,
+                            current.get(&Property::CalendarIds).clone(),
+                        );
+                        for name in LOCAL_PROPERTIES {
+                            let property = Property::parse(name);
+                            let value = current.get(&property);
+                            if value != &Value::Null {
+                                event.set(property, value.clone());
+                            }
+                        }
+                    } else {
+                        event.set(
+                            Property::CalendarIds,
+                            Value::List(vec![Value::Id(ctx.calendar_ids.min()?.into())]),
+                        );
+                    }
+                    Some(event)
+                }
+            }
+            "REPLY" => {
+                // Only attendees can reply, and only for themselves
+                if !is_sender_authenticated {
+                    return None;
+                }
+                let mut event = current?.clone();
+                let status = itip
+                    .event
+                    .attendees()
+                    .into_iter()
+                    .find(|(email, _)| email == sender)?
+                    .1?;
+                if event.set_participation_status(sender, &status) {
+                    Some(event)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
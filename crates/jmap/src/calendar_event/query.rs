@@ -0,0 +1,243 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::NaiveDateTime;
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{Comparator, Filter, QueryRequest, QueryResponse, SortProperty},
+    object::{calendar_event::QueryArguments, Object},
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+use store::{fts::Language, query};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::recurrence::JSCalendar;
+
+impl JMAP {
+    pub async fn calendar_event_query(
+        &self,
+        mut request: QueryRequest<QueryArguments>,
+        access_token: &AccessToken,
+    ) -> Result<QueryResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut filters = Vec::with_capacity(request.filter.len());
+        let mut after = None;
+        let mut before = None;
+
+        for cond in std::mem::take(&mut request.filter) {
+            match cond {
+                Filter::InCalendars(calendar_ids) => {
+                    filters.push(query::Filter::Or);
+                    for calendar_id in calendar_ids {
+                        filters.push(query::Filter::eq(
+                            Property::CalendarIds,
+                            calendar_id.document_id(),
+                        ));
+                    }
+                    filters.push(query::Filter::End);
+                }
+                Filter::After(date) => {
+                    after = date.timestamp().into();
+                    filters.push(query::Filter::gt(
+                        Property::ToDate,
+                        std::cmp::max(date.timestamp(), 0) as u64,
+                    ));
+                }
+                Filter::Before(date) => {
+                    before = date.timestamp().into();
+                    filters.push(query::Filter::lt(
+                        Property::FromDate,
+                        std::cmp::max(date.timestamp(), 0) as u64,
+                    ));
+                }
+                Filter::Uid(uid) => filters.push(query::Filter::eq(Property::Uid, uid)),
+                Filter::Title(title) => filters.push(query::Filter::has_text(
+                    Property::Title,
+                    &title,
+                    Language::None,
+                )),
+                Filter::Description(description) => filters.push(query::Filter::has_text(
+                    Property::Description,
+                    &description,
+                    Language::None,
+                )),
+                Filter::Text(text) => {
+                    filters.push(query::Filter::Or);
+                    filters.push(query::Filter::has_text(
+                        Property::Title,
+                        &text,
+                        Language::None,
+                    ));
+                    filters.push(query::Filter::has_text(
+                        Property::Description,
+                        &text,
+                        Language::None,
+                    ));
+                    filters.push(query::Filter::End);
+                }
+                Filter::And | Filter::Or | Filter::Not | Filter::Close => {
+                    filters.push(cond.into());
+                }
+                other => return Err(MethodError::UnsupportedFilter(other.to_string())),
+            }
+        }
+
+        let mut result_set = self
+            .filter(account_id, Collection::CalendarEvent, filters)
+            .await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(
+                self.shared_events(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            );
+        }
+
+        // Parse sort criteria
+        let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
+        for comparator in request
+            .sort
+            .take()
+            .and_then(|s| if !s.is_empty() { s.into() } else { None })
+            .unwrap_or_else(|| vec![Comparator::ascending(SortProperty::Start)])
+        {
+            comparators.push(match comparator.property {
+                SortProperty::Start => {
+                    query::Comparator::field(Property::FromDate, comparator.is_ascending)
+                }
+                other => return Err(MethodError::UnsupportedSort(other.to_string())),
+            });
+        }
+
+        if !request.arguments.expand_recurrences.unwrap_or(false) {
+            let (response, paginate) = self.build_query_response(&result_set, &request).await?;
+            return if let Some(paginate) = paginate {
+                self.sort(result_set, comparators, paginate, response).await
+            } else {
+                Ok(response)
+            };
+        }
+
+        // Expanding recurrences requires a bounded time range
+        let (after, before) = match (after, before) {
+            (Some(after), Some(before)) => (after, before),
+            _ => {
+                return Err(MethodError::InvalidArguments(
+                    "Both 'after' and 'before' filters are required to expand recurrences."
+                        .to_string(),
+                ))
+            }
+        };
+        let mut occurrences = Vec::new();
+        for document_id in &result_set.results {
+            let event = if let Some(event) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    &Property::Value,
+                )
+                .await?
+            {
+                event
+            } else {
+                continue;
+            };
+
+            if event.is_recurrent() {
+                // Occurrences starting before 'after' can still overlap the range
+                let from = after.saturating_sub(event.duration().num_seconds());
+                for occurrence in event
+                    .expand_range(
+                        NaiveDateTime::from_timestamp_opt(from, 0).unwrap_or(NaiveDateTime::MIN),
+                        NaiveDateTime::from_timestamp_opt(before, 0).unwrap_or(NaiveDateTime::MAX),
+                        self.config.calendar_max_expansions,
+                    )
+                    .occurrences
+                {
+                    let start = occurrence.start.timestamp();
+                    let end = occurrence.end.timestamp();
+                    if (end > after || start >= after) && start < before {
+                        if let Some(occurrence_id) = event.occurrence_id(&occurrence) {
+                            occurrences.push((start, Id::from_parts(occurrence_id, document_id)));
+                        }
+                    }
+                }
+            } else if let Some(start) = event.start() {
+                occurrences.push((start.timestamp(), Id::from(document_id)));
+            }
+        }
+        let is_ascending = request
+            .sort
+            .as_ref()
+            .and_then(|sort| sort.first())
+            .map_or(true, |comparator| comparator.is_ascending);
+        occurrences.sort_unstable_by(|a, b| if is_ascending { a.cmp(b) } else { b.cmp(a) });
+
+        // Paginate results
+        let total = occurrences.len();
+        let limit = std::cmp::min(
+            request
+                .limit
+                .filter(|limit| *limit > 0)
+                .unwrap_or(usize::MAX),
+            self.config.query_max_results,
+        );
+        let position = if let Some(anchor) = request.anchor {
+            let anchor_position = occurrences
+                .iter()
+                .position(|(_, id)| id == &anchor)
+                .ok_or(MethodError::AnchorNotFound)? as i64;
+            std::cmp::max(
+                anchor_position + request.anchor_offset.unwrap_or(0) as i64,
+                0,
+            ) as usize
+        } else {
+            match request.position.unwrap_or(0) {
+                position if position >= 0 => position as usize,
+                position => total.saturating_sub(position.unsigned_abs() as usize),
+            }
+        };
+
+        Ok(QueryResponse {
+            account_id: request.account_id,
+            query_state: self
+                .get_state(account_id, Collection::CalendarEvent)
+                .await?,
+            can_calculate_changes: false,
+            position: std::cmp::min(position, total) as i32,
+            ids: occurrences
+                .into_iter()
+                .skip(position)
+                .take(limit)
+                .map(|(_, id)| id)
+                .collect(),
+            total: if request.calculate_total.unwrap_or(false) {
+                Some(total)
+            } else {
+                None
+            },
+            limit: if total > limit { Some(limit) } else { None },
+        })
+    }
+}
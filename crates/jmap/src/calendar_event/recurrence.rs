@@ -0,0 +1,615 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+// Guards against rules that never produce an occurrence (e.g. February 30th)
+const MAX_EMPTY_PERIODS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub recurrence_id: NaiveDateTime,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct Expansion {
+    pub occurrences: Vec<Occurrence>,
+    pub is_truncated: bool,
+}
+
+pub trait JSCalendar {
+    fn start(&self) -> Option<NaiveDateTime>;
+    fn duration(&self) -> Duration;
+    fn is_recurrent(&self) -> bool;
+    fn expand(&self, max_expansions: usize) -> Expansion;
+    fn expand_range(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        max_expansions: usize,
+    ) -> Expansion;
+    fn occurrence(&self, occurrence_id: u32, max_expansions: usize) -> Option<Occurrence>;
+    fn occurrence_id(&self, occurrence: &Occurrence) -> Option<u32>;
+    fn instance(&self, occurrence: &Occurrence) -> Object<Value>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+    Secondly,
+}
+
+impl Frequency {
+    // Upper bound of the length of a period, in seconds
+    fn max_seconds(&self) -> i64 {
+        match self {
+            Frequency::Yearly => 366 * 86400,
+            Frequency::Monthly => 31 * 86400,
+            Frequency::Weekly => 7 * 86400,
+            Frequency::Daily => 86400,
+            Frequency::Hourly => 3600,
+            Frequency::Minutely => 60,
+            Frequency::Secondly => 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RecurrenceRule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<NaiveDateTime>,
+    by_day: Vec<(Weekday, Option<i64>)>,
+    by_month_day: Vec<u32>,
+    by_month: Vec<u32>,
+}
+
+impl JSCalendar for Object<Value> {
+    fn start(&self) -> Option<NaiveDateTime> {
+        self.get(&Property::parse("start"))
+            .as_string()
+            .and_then(parse_local_date_time)
+    }
+
+    fn duration(&self) -> Duration {
+        self.get(&Property::parse("duration"))
+            .as_string()
+            .and_then(parse_duration)
+            .unwrap_or_else(Duration::zero)
+    }
+
+    fn is_recurrent(&self) -> bool {
+        self.get(&Property::parse("recurrenceRules"))
+            .as_list()
+            .map_or(false, |rules| !rules.is_empty())
+            || self
+                .get(&Property::parse("recurrenceOverrides"))
+                .as_obj()
+                .map_or(false, |overrides| !overrides.properties.is_empty())
+    }
+
+    fn expand(&self, max_expansions: usize) -> Expansion {
+        self.expand_range(NaiveDateTime::MIN, NaiveDateTime::MAX, max_expansions)
+    }
+
+    // Expands the occurrences with a recurrence id within `from..=to`
+    fn expand_range(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        max_expansions: usize,
+    ) -> Expansion {
+        let mut expansion = Expansion::default();
+        let start = if let Some(start) = self.start() {
+            start
+        } else {
+            return expansion;
+        };
+        let duration = self.duration();
+
+        // Expand recurrence rules, the start date is always the first occurrence
+        let mut recurrence_ids = Vec::new();
+        if (from..=to).contains(&start) {
+            recurrence_ids.push(start);
+        }
+        for rule in RecurrenceRule::parse_list(self.get(&Property::parse("recurrenceRules"))) {
+            let (dates, is_truncated) = rule.expand(start, from, to, max_expansions);
+            recurrence_ids.extend(dates);
+            expansion.is_truncated |= is_truncated;
+        }
+        for rule in
+            RecurrenceRule::parse_list(self.get(&Property::parse("excludedRecurrenceRules")))
+        {
+            let (dates, _) = rule.expand(start, from, to, max_expansions);
+            recurrence_ids.retain(|date| !dates.contains(date));
+        }
+
+        // Apply overrides
+        let overrides = self.get(&Property::parse("recurrenceOverrides")).as_obj();
+        if let Some(overrides) = overrides {
+            for (recurrence_id, patch) in &overrides.properties {
+                if let Some(recurrence_id) = parse_local_date_time(&recurrence_id.to_string()) {
+                    if is_excluded(patch) {
+                        recurrence_ids.retain(|date| date != &recurrence_id);
+                    } else if (from..=to).contains(&recurrence_id)
+                        && !recurrence_ids.contains(&recurrence_id)
+                    {
+                        recurrence_ids.push(recurrence_id);
+                    }
+                }
+            }
+        }
+        recurrence_ids.sort_unstable();
+        recurrence_ids.dedup();
+        if recurrence_ids.len() > max_expansions {
+            recurrence_ids.truncate(max_expansions);
+            expansion.is_truncated = true;
+        }
+
+        expansion.occurrences = recurrence_ids
+            .into_iter()
+            .map(|recurrence_id| {
+                let patch = overrides.and_then(|overrides| {
+                    overrides
+                        .get(&Property::_T(format_local_date_time(&recurrence_id)))
+                        .as_obj()
+                });
+                let start = patch
+                    .and_then(|patch| patch.get(&Property::_T("start".to_string())).as_string())
+                    .and_then(parse_local_date_time)
+                    .unwrap_or(recurrence_id);
+                let duration = patch
+                    .and_then(|patch| patch.get(&Property::_T("duration".to_string())).as_string())
+                    .and_then(parse_duration)
+                    .unwrap_or(duration);
+                Occurrence {
+                    recurrence_id,
+                    start,
+                    end: start + duration,
+                }
+            })
+            .collect();
+
+        expansion
+    }
+
+    fn occurrence(&self, occurrence_id: u32, max_expansions: usize) -> Option<Occurrence> {
+        let recurrence_id = self
+            .start()?
+            .checked_add_signed(Duration::seconds(occurrence_id.checked_sub(1)? as i64))?;
+        self.expand_range(recurrence_id, recurrence_id, max_expansions)
+            .occurrences
+            .into_iter()
+            .find(|occurrence| occurrence.recurrence_id == recurrence_id)
+    }
+
+    // Occurrence ids are the number of seconds between the recurrence id and the
+    // start of the event plus one, so they do not change as occurrences are added or removed
+    fn occurrence_id(&self, occurrence: &Occurrence) -> Option<u32> {
+        u32::try_from((occurrence.recurrence_id - self.start()?).num_seconds())
+            .ok()?
+            .checked_add(1)
+    }
+
+    fn instance(&self, occurrence: &Occurrence) -> Object<Value> {
+        let recurrence_id = format_local_date_time(&occurrence.recurrence_id);
+        let mut instance = Object::with_capacity(self.properties.len() + 1);
+        for (property, value) in &self.properties {
+            match property {
+                Property::_T(name)
+                    if matches!(
+                        name.as_str(),
+                        "recurrenceRules" | "excludedRecurrenceRules" | "recurrenceOverrides"
+                    ) => {}
+                _ => {
+                    instance.append(property.clone(), value.clone());
+                }
+            }
+        }
+        instance.set(Property::parse("recurrenceId"), recurrence_id.clone());
+        instance.set(Property::parse("start"), recurrence_id.clone());
+
+        // Apply the override patch for this occurrence, if any
+        if let Some(patch) = self
+            .get(&Property::parse("recurrenceOverrides"))
+            .as_obj()
+            .and_then(|overrides| overrides.get(&Property::_T(recurrence_id)).as_obj())
+        {
+            for (property, value) in &patch.properties {
+                let pointer = property.to_string();
+                if pointer.contains('/') {
+                    instance.patch(&pointer, value.clone());
+                } else if value != &Value::Null {
+                    instance.set(Property::parse(&pointer), value.clone());
+                } else {
+                    instance.remove(&Property::parse(&pointer));
+                }
+            }
+        }
+
+        instance
+    }
+}
+
+impl RecurrenceRule {
+    fn parse_list(value: &Value) -> Vec<Self> {
+        value
+            .as_list()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .filter_map(|rule| rule.as_obj().and_then(RecurrenceRule::parse))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn parse(rule: &Object<Value>) -> Option<Self> {
+        let get = |name: &str| rule.get(&Property::_T(name.to_string()));
+
+        Some(RecurrenceRule {
+            frequency: match get("frequency").as_string()? {
+                "yearly" => Frequency::Yearly,
+                "monthly" => Frequency::Monthly,
+                "weekly" => Frequency::Weekly,
+                "daily" => Frequency::Daily,
+                "hourly" => Frequency::Hourly,
+                "minutely" => Frequency::Minutely,
+                "secondly" => Frequency::Secondly,
+                _ => return None,
+            },
+            interval: get("interval")
+                .as_uint()
+                .filter(|interval| *interval > 0)
+                .unwrap_or(1) as u32,
+            count: get("count").as_uint().map(|count| count as usize),
+            until: get("until").as_string().and_then(parse_local_date_time),
+            by_day: get("byDay")
+                .as_list()
+                .map(|days| {
+                    days.iter()
+                        .filter_map(|day| {
+                            let day = day.as_obj()?;
+                            let weekday = parse_weekday(
+                                day.get(&Property::_T("day".to_string())).as_string()?,
+                            )?;
+                            let nth = day
+                                .get(&Property::_T("nthOfPeriod".to_string()))
+                                .as_uint()
+                                .filter(|nth| *nth > 0)
+                                .map(|nth| nth as i64);
+                            Some((weekday, nth))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            by_month_day: get("byMonthDay")
+                .as_list()
+                .map(|days| {
+                    days.iter()
+                        .filter_map(|day| day.as_uint())
+                        .filter(|day| (1..=31).contains(day))
+                        .map(|day| day as u32)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            by_month: get("byMonth")
+                .as_list()
+                .map(|months| {
+                    months
+                        .iter()
+                        .filter_map(|month| match month {
+                            Value::Text(month) => month.parse::<u32>().ok(),
+                            Value::UnsignedInt(month) => Some(*month as u32),
+                            _ => None,
+                        })
+                        .filter(|month| (1..=12).contains(month))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    fn expand(
+        &self,
+        start: NaiveDateTime,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        max_expansions: usize,
+    ) -> (Vec<NaiveDateTime>, bool) {
+        let mut dates = Vec::new();
+        let mut count = 0;
+        let mut empty_periods = 0;
+
+        // Without a count there is no need to walk the periods before the range
+        let first_period = if self.count.is_none() && from > start {
+            ((from - start).num_seconds() / (self.frequency.max_seconds() * self.interval as i64))
+                .saturating_sub(1)
+                .clamp(0, u32::MAX as i64) as u32
+        } else {
+            0
+        };
+
+        for period in first_period.. {
+            let candidates = if let Some(candidates) = self.candidates(start, period) {
+                candidates
+            } else {
+                break;
+            };
+            if candidates.is_empty() {
+                empty_periods += 1;
+                if empty_periods > MAX_EMPTY_PERIODS {
+                    break;
+                }
+                continue;
+            }
+            empty_periods = 0;
+
+            for date in candidates {
+                if date < start {
+                    continue;
+                } else if date > to || self.until.map_or(false, |until| date > until) {
+                    return (dates, false);
+                }
+                count += 1;
+                if date != start && date >= from {
+                    if dates.len() == max_expansions {
+                        return (dates, true);
+                    }
+                    dates.push(date);
+                }
+                if self.count.map_or(false, |max_count| count >= max_count) {
+                    return (dates, false);
+                }
+            }
+        }
+
+        (dates, false)
+    }
+
+    fn candidates(&self, start: NaiveDateTime, period: u32) -> Option<Vec<NaiveDateTime>> {
+        let offset = period.checked_mul(self.interval)?;
+        let time = start.time();
+        let mut dates = match self.frequency {
+            Frequency::Yearly => {
+                let year = start.year().checked_add(offset as i32)?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                let mut dates = Vec::new();
+                if !self.by_month.is_empty() {
+                    for &month in &self.by_month {
+                        dates.extend(self.month_days(year, month, start.day()));
+                    }
+                } else if !self.by_month_day.is_empty() {
+                    for month in 1..=12 {
+                        dates.extend(self.month_days(year, month, start.day()));
+                    }
+                } else if !self.by_day.is_empty() {
+                    let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                    let last = NaiveDate::from_ymd_opt(year, 12, 31)?;
+                    dates.extend(
+                        first
+                            .iter_days()
+                            .take_while(|date| date <= &last)
+                            .filter(|date| self.matches_day(date, &first)),
+                    );
+                } else if let Some(date) = NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                {
+                    dates.push(date);
+                }
+                dates
+            }
+            Frequency::Monthly => {
+                let months = start.month0() + offset;
+                let year = start.year().checked_add((months / 12) as i32)?;
+                let month = (months % 12) + 1;
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                self.month_days(year, month, start.day())
+            }
+            Frequency::Weekly => {
+                let week_start = start.date()
+                    - Duration::days(start.weekday().num_days_from_monday() as i64)
+                    + checked_days(offset as i64 * 7)?;
+                if self.by_day.is_empty() {
+                    vec![week_start + Duration::days(start.weekday().num_days_from_monday() as i64)]
+                } else {
+                    week_start
+                        .iter_days()
+                        .take(7)
+                        .filter(|date| self.by_day.iter().any(|(day, _)| day == &date.weekday()))
+                        .collect()
+                }
+            }
+            Frequency::Daily => {
+                vec![start
+                    .date()
+                    .checked_add_signed(checked_days(offset as i64)?)?]
+            }
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let seconds = offset as i64
+                    * match self.frequency {
+                        Frequency::Hourly => 3600,
+                        Frequency::Minutely => 60,
+                        _ => 1,
+                    };
+                let date = start.checked_add_signed(checked_days(seconds / 86400)?)?
+                    + Duration::seconds(seconds % 86400);
+                return Some(if self.matches_filters(&date.date()) {
+                    vec![date]
+                } else {
+                    vec![]
+                });
+            }
+        }
+        .into_iter()
+        .filter(|date| self.matches_filters(date))
+        .map(|date| date.and_time(time))
+        .collect::<Vec<_>>();
+        dates.sort_unstable();
+        dates.dedup();
+
+        Some(dates)
+    }
+
+    fn month_days(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let (first, last) = if let Some(range) = month_range(year, month) {
+            range
+        } else {
+            return vec![];
+        };
+        if self.by_month_day.is_empty() && self.by_day.is_empty() {
+            NaiveDate::from_ymd_opt(year, month, default_day)
+                .into_iter()
+                .collect()
+        } else {
+            first
+                .iter_days()
+                .take_while(|date| date <= &last)
+                .filter(|date| {
+                    (self.by_month_day.is_empty() || self.by_month_day.contains(&date.day()))
+                        && (self.by_day.is_empty() || self.matches_day(date, &first))
+                })
+                .collect()
+        }
+    }
+
+    fn matches_day(&self, date: &NaiveDate, first: &NaiveDate) -> bool {
+        self.by_day.iter().any(|(day, nth)| {
+            day == &date.weekday()
+                && nth.map_or(true, |nth| (*date - *first).num_days() / 7 + 1 == nth)
+        })
+    }
+
+    fn matches_filters(&self, date: &NaiveDate) -> bool {
+        // Day filters are applied while generating candidates for
+        // weekly, monthly and yearly rules
+        let is_daily = matches!(
+            self.frequency,
+            Frequency::Daily | Frequency::Hourly | Frequency::Minutely | Frequency::Secondly
+        );
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (!is_daily
+                || self.by_month_day.is_empty()
+                || self.by_month_day.contains(&date.day()))
+            && (!is_daily
+                || self.by_day.iter().any(|(day, _)| day == &date.weekday())
+                || self.by_day.is_empty())
+    }
+}
+
+fn month_range(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let last = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    }
+    .pred_opt()?;
+    Some((first, last))
+}
+
+fn is_excluded(patch: &Value) -> bool {
+    patch.as_obj().map_or(false, |patch| {
+        patch.get(&Property::_T("excluded".to_string())) == &Value::Bool(true)
+    })
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "mo" => Some(Weekday::Mon),
+        "tu" => Some(Weekday::Tue),
+        "we" => Some(Weekday::Wed),
+        "th" => Some(Weekday::Thu),
+        "fr" => Some(Weekday::Fri),
+        "sa" => Some(Weekday::Sat),
+        "su" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+pub fn parse_local_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim().trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+}
+
+pub fn format_local_date_time(value: &NaiveDateTime) -> String {
+    value.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut number: Option<i64> = None;
+    let mut in_time = false;
+
+    for ch in value.chars() {
+        let multiplier = match ch {
+            '0'..='9' => {
+                number = (number.unwrap_or(0))
+                    .checked_mul(10)?
+                    .checked_add(ch as i64 - '0' as i64)?
+                    .into();
+                continue;
+            }
+            'T' if !in_time && number.is_none() => {
+                in_time = true;
+                continue;
+            }
+            'W' if !in_time => 7 * 86400,
+            'D' if !in_time => 86400,
+            'H' if in_time => 3600,
+            'M' if in_time => 60,
+            'S' if in_time => 1,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.take()?.checked_mul(multiplier)?)?;
+    }
+
+    if number.is_none() {
+        Some(checked_days(seconds / 86400)? + Duration::seconds(seconds % 86400))
+    } else {
+        None
+    }
+}
+
+fn checked_days(days: i64) -> Option<Duration> {
+    // Keep well within the range supported by chrono
+    if days.abs() < 100_000_000 {
+        Some(Duration::days(days))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,634 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{SetRequest, SetResponse},
+    object::{
+        calendar_event::SetArguments,
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::TypeState,
+        value::{MaybePatchValue, Value},
+    },
+};
+use store::{
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+    Serialize,
+};
+use utils::listener::ServerInstance;
+
+use crate::{auth::AccessToken, contact::set::generate_uid, JMAP};
+
+use super::recurrence::JSCalendar;
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Uid)
+        .index_as(IndexAs::Text {
+            tokenize: false,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::Title).index_as(IndexAs::Text {
+        tokenize: true,
+        index: true,
+    }),
+    IndexProperty::new(Property::Description).index_as(IndexAs::Text {
+        tokenize: true,
+        index: false,
+    }),
+    IndexProperty::new(Property::CalendarIds).index_as(IndexAs::IntegerList),
+    IndexProperty::new(Property::FromDate).index_as(IndexAs::LongInteger),
+    IndexProperty::new(Property::ToDate).index_as(IndexAs::LongInteger),
+];
+
+pub(crate) struct SetContext {
+    pub calendar_ids: RoaringBitmap,
+    pub can_add_calendar_ids: Option<RoaringBitmap>,
+    pub can_remove_calendar_ids: Option<RoaringBitmap>,
+}
+
+impl JMAP {
+    pub async fn calendar_event_set(
+        &self,
+        mut request: SetRequest<SetArguments>,
+        instance: &Arc<ServerInstance>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let send_scheduling_messages = request.arguments.send_scheduling_messages.unwrap_or(false);
+        let mut response = self
+            .prepare_set_response(&request, Collection::CalendarEvent)
+            .await?;
+
+        // Obtain calendar ids and permissions
+        let is_shared = access_token.is_shared(account_id);
+        let ctx = SetContext {
+            calendar_ids: self.calendar_get_or_create(account_id).await?,
+            can_add_calendar_ids: if is_shared {
+                self.shared_documents(
+                    access_token,
+                    account_id,
+                    Collection::Calendar,
+                    Acl::AddItems,
+                )
+                .await?
+                .into()
+            } else {
+                None
+            },
+            can_remove_calendar_ids: if is_shared {
+                self.shared_documents(
+                    access_token,
+                    account_id,
+                    Collection::Calendar,
+                    Acl::RemoveItems,
+                )
+                .await?
+                .into()
+            } else {
+                None
+            },
+        };
+        let (can_modify_event_ids, can_destroy_event_ids) = if is_shared {
+            (
+                self.shared_events(access_token, account_id, Acl::ModifyItems)
+                    .await?
+                    .into(),
+                self.shared_events(access_token, account_id, Acl::RemoveItems)
+                    .await?
+                    .into(),
+            )
+        } else {
+            (None, None)
+        };
+        let will_destroy = request.unwrap_destroy();
+        let mut scheduling = Vec::new();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        'create: for (id, object) in request.unwrap_create() {
+            let mut event = Object::with_capacity(object.properties.len() + 4);
+
+            for (property, value) in object.properties {
+                match (property, response.eval_object_references(value)) {
+                    (Property::CalendarIds, Ok(MaybePatchValue::Value(Value::List(ids)))) => {
+                        event.set(Property::CalendarIds, Value::List(ids));
+                    }
+                    (
+                        property @ (Property::Id
+                        | Property::FromDate
+                        | Property::ToDate
+                        | Property::CalendarIds),
+                        _,
+                    ) => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(property)
+                                .with_description("Invalid property or value."),
+                        );
+                        continue 'create;
+                    }
+                    (Property::_T(key), _) if key.contains('/') => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(Property::_T(key))
+                                .with_description(
+                                    "Patches are not allowed when creating an event.",
+                                ),
+                        );
+                        continue 'create;
+                    }
+                    (property, Ok(MaybePatchValue::Value(value))) => {
+                        if value != Value::Null {
+                            event.set(property, value);
+                        }
+                    }
+                    (property, Ok(_)) => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(property)
+                                .with_description("Invalid property or value."),
+                        );
+                        continue 'create;
+                    }
+                    (_, Err(err)) => {
+                        response.not_created.append(id, err);
+                        continue 'create;
+                    }
+                }
+            }
+
+            // Add defaults
+            let type_ = Property::_T("@type".to_string());
+            if event.get(&type_) == &Value::Null {
+                event.append(type_, "Event");
+            }
+            if event.get(&Property::Uid) == &Value::Null {
+                event.append(Property::Uid, generate_uid());
+            }
+
+            // Validate event
+            let builder = match self.calendar_event_validate(event.clone(), None, &ctx) {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+
+            // Insert record
+            let document_id = self
+                .assign_document_id(account_id, Collection::CalendarEvent)
+                .await?;
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .create_document(document_id)
+                .custom(builder);
+            self.write_batch(batch).await?;
+            changes.log_insert(Collection::CalendarEvent, document_id);
+            response.created(id, document_id);
+            if send_scheduling_messages {
+                scheduling.push((None, Some(event)));
+            }
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            } else if id.prefix_id() != 0 {
+                response.not_updated.append(
+                    id,
+                    SetError::forbidden().with_description(concat!(
+                        "Occurrences of a recurring event cannot be modified directly, ",
+                        "update recurrenceOverrides on the main event instead."
+                    )),
+                );
+                continue 'update;
+            }
+
+            // Obtain calendar event
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            let mut event = current.inner.clone();
+            let mut has_event_changes = false;
+            let mut has_sequence = false;
+            for (property, value) in object.properties {
+                let value = match response.eval_object_references(value) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                };
+                match (property, value) {
+                    (Property::CalendarIds, MaybePatchValue::Value(Value::List(ids))) => {
+                        event.set(Property::CalendarIds, Value::List(ids));
+                    }
+                    (Property::CalendarIds, MaybePatchValue::Patch(patch)) => {
+                        let mut patch = patch.into_iter();
+                        let calendar_id = patch.next().unwrap();
+                        let add = patch.next().unwrap().unwrap_bool();
+                        let mut calendar_ids = event
+                            .remove(&Property::CalendarIds)
+                            .try_unwrap_list()
+                            .unwrap_or_default();
+                        if add {
+                            if !calendar_ids.contains(&calendar_id) {
+                                calendar_ids.push(calendar_id);
+                            }
+                        } else {
+                            calendar_ids.retain(|id| id != &calendar_id);
+                        }
+                        event.set(Property::CalendarIds, Value::List(calendar_ids));
+                    }
+                    (Property::Uid, MaybePatchValue::Value(value))
+                        if event.get(&Property::Uid) == &value => {}
+                    (Property::_T(key), MaybePatchValue::Value(value)) if key.contains('/') => {
+                        if !event.patch(&key, value) {
+                            response.not_updated.append(
+                                id,
+                                SetError::new(SetErrorType::InvalidPatch).with_description(
+                                    format!("Path {key:?} does not exist in this event."),
+                                ),
+                            );
+                            continue 'update;
+                        }
+                        has_event_changes = true;
+                    }
+                    (
+                        property @ (Property::Id
+                        | Property::Uid
+                        | Property::FromDate
+                        | Property::ToDate
+                        | Property::CalendarIds),
+                        _,
+                    ) => {
+                        response.invalid_property_update(id, property);
+                        continue 'update;
+                    }
+                    (property, MaybePatchValue::Value(value)) => {
+                        has_sequence |= property == Property::parse("sequence");
+                        if value != Value::Null {
+                            event.set(property, value);
+                        } else {
+                            event.remove(&property);
+                        }
+                        has_event_changes = true;
+                    }
+                    (property, _) => {
+                        response.invalid_property_update(id, property);
+                        continue 'update;
+                    }
+                }
+            }
+
+            // Verify permissions on shared accounts
+            if has_event_changes
+                && can_modify_event_ids
+                    .as_ref()
+                    .map_or(false, |ids: &RoaringBitmap| !ids.contains(document_id))
+            {
+                response.not_updated.append(
+                    id,
+                    SetError::forbidden()
+                        .with_description("You are not allowed to modify this event."),
+                );
+                continue 'update;
+            }
+
+            // Scheduling messages require a new sequence number
+            if send_scheduling_messages && has_event_changes && !has_sequence {
+                let sequence = event
+                    .get(&Property::parse("sequence"))
+                    .as_uint()
+                    .unwrap_or(0);
+                event.set(
+                    Property::parse("sequence"),
+                    Value::UnsignedInt(sequence + 1),
+                );
+            }
+
+            // Validate event
+            let previous = current.inner.clone();
+            let builder = match self.calendar_event_validate(event.clone(), current.into(), &ctx) {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+
+            // Write changes
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .update_document(document_id)
+                .custom(builder);
+            if !batch.is_empty() {
+                match self.store.write(batch.build()).await {
+                    Ok(_) => {
+                        changes.log_update(Collection::CalendarEvent, document_id);
+                    }
+                    Err(store::Error::AssertValueFailed) => {
+                        response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "Another process modified this event, please try again.",
+                            ),
+                        );
+                        continue 'update;
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            event = "error",
+                            context = "calendar_event_set",
+                            account_id = account_id,
+                            error = ?err,
+                            "Failed to update calendar event.");
+                        return Err(MethodError::ServerPartialFail);
+                    }
+                }
+            }
+            response.updated.append(id, None);
+            if send_scheduling_messages && has_event_changes {
+                scheduling.push((Some(previous), Some(event)));
+            }
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+
+            // Verify permissions on shared accounts
+            if id.prefix_id() != 0
+                || can_destroy_event_ids
+                    .as_ref()
+                    .map_or(false, |ids: &RoaringBitmap| !ids.contains(document_id))
+            {
+                response.not_destroyed.append(
+                    id,
+                    SetError::forbidden()
+                        .with_description("You are not allowed to delete this event."),
+                );
+                continue;
+            }
+
+            // Obtain calendar event
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::CalendarEvent,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+            let previous = if send_scheduling_messages {
+                Some(current.inner.clone())
+            } else {
+                None
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::CalendarEvent)
+                .delete_document(document_id)
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+            match self.store.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_delete(Collection::CalendarEvent, document_id);
+                    response.destroyed.push(id);
+                    if let Some(previous) = previous {
+                        scheduling.push((Some(previous), None));
+                    }
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this event, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "calendar_event_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to delete calendar event.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.state_change = StateChange::new(account_id)
+                .with_change(TypeState::CalendarEvent, changes.change_id)
+                .into();
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        // Send scheduling messages to participants
+        if !scheduling.is_empty() {
+            self.calendar_event_schedule(instance, account_id, access_token, scheduling)
+                .await?;
+        }
+
+        Ok(response)
+    }
+
+    pub(crate) fn calendar_event_validate(
+        &self,
+        mut event: Object<Value>,
+        current: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext,
+    ) -> Result<ObjectIndexBuilder, SetError> {
+        // Validate calendar ids
+        let calendar_ids = event
+            .get(&Property::CalendarIds)
+            .as_list()
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default();
+        let current_calendar_ids = current
+            .as_ref()
+            .and_then(|c| c.inner.get(&Property::CalendarIds).as_list())
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default();
+        if calendar_ids.is_empty() {
+            return Err(SetError::invalid_properties()
+                .with_property(Property::CalendarIds)
+                .with_description("Event has to belong to at least one calendar."));
+        } else if calendar_ids.len() > self.config.calendar_max_calendars {
+            return Err(SetError::invalid_properties()
+                .with_property(Property::CalendarIds)
+                .with_description(format!(
+                    "Event cannot belong to more than {} calendars.",
+                    self.config.calendar_max_calendars
+                )));
+        }
+        for calendar_id in calendar_ids {
+            if current_calendar_ids.contains(calendar_id) {
+                continue;
+            }
+            let calendar_id = calendar_id.try_cast_uint().unwrap_or(u64::MAX) as u32;
+            if !ctx.calendar_ids.contains(calendar_id) {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::CalendarIds)
+                    .with_description(format!("calendarId {calendar_id} does not exist.")));
+            } else if ctx
+                .can_add_calendar_ids
+                .as_ref()
+                .map_or(false, |ids| !ids.contains(calendar_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to add events to calendar {calendar_id}."
+                )));
+            }
+        }
+        for calendar_id in current_calendar_ids {
+            if calendar_ids.contains(calendar_id) {
+                continue;
+            }
+            let calendar_id = calendar_id.try_cast_uint().unwrap_or(u64::MAX) as u32;
+            if ctx
+                .can_remove_calendar_ids
+                .as_ref()
+                .map_or(false, |ids| !ids.contains(calendar_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to remove events from calendar {calendar_id}."
+                )));
+            }
+        }
+
+        // Update the time range covered by all occurrences
+        if event.start().is_none() {
+            return Err(SetError::invalid_properties()
+                .with_property(Property::parse("start"))
+                .with_description("Event start is missing or invalid."));
+        }
+        let expansion = event.expand(self.config.calendar_max_expansions);
+        let from_date = expansion
+            .occurrences
+            .iter()
+            .map(|occurrence| occurrence.start.timestamp())
+            .min()
+            .unwrap_or(0);
+        let to_date = if !expansion.is_truncated {
+            expansion
+                .occurrences
+                .iter()
+                .map(|occurrence| occurrence.end.timestamp())
+                .max()
+                .unwrap_or(0)
+        } else {
+            i64::MAX
+        };
+        event.set(
+            Property::FromDate,
+            Value::UnsignedInt(std::cmp::max(from_date, 0) as u64),
+        );
+        event.set(
+            Property::ToDate,
+            Value::UnsignedInt(std::cmp::max(to_date, 0) as u64),
+        );
+
+        // Validate size
+        if (&event).serialize().len() > self.config.calendar_max_size {
+            return Err(
+                SetError::new(SetErrorType::TooLarge).with_description(format!(
+                    "Event cannot be larger than {} bytes.",
+                    self.config.calendar_max_size
+                )),
+            );
+        }
+
+        let builder = if let Some(current) = current {
+            // Only include properties that changed
+            let mut changes = Object::with_capacity(event.properties.len());
+            for property in current.inner.properties.keys() {
+                if !event.properties.contains_key(property) {
+                    changes.append(property.clone(), Value::Null);
+                }
+            }
+            for (property, value) in event.properties {
+                if current.inner.get(&property) != &value {
+                    changes.append(property, value);
+                }
+            }
+            ObjectIndexBuilder::new(SCHEMA)
+                .with_current(current)
+                .with_changes(changes)
+        } else {
+            ObjectIndexBuilder::new(SCHEMA).with_changes(event)
+        };
+
+        builder.validate()
+    }
+}
@@ -51,6 +51,12 @@ impl JMAP {
                         query::RequestArguments::EmailSubmission => {
                             changes::RequestArguments::EmailSubmission
                         }
+                        query::RequestArguments::ContactCard => {
+                            changes::RequestArguments::ContactCard
+                        }
+                        query::RequestArguments::CalendarEvent(_) => {
+                            changes::RequestArguments::CalendarEvent
+                        }
                         _ => return Err(MethodError::UnknownMethod("Unknown method".to_string())),
                     },
                 },
@@ -80,7 +86,10 @@ impl JMAP {
                 calculate_total: request.calculate_total,
                 arguments: query::RequestArguments::EmailSubmission,
             };
-            let is_mutable = query.filter.iter().any(|f| !f.is_immutable())
+            let is_mutable = matches!(
+                request.arguments,
+                query::RequestArguments::ContactCard | query::RequestArguments::CalendarEvent(_)
+            ) || query.filter.iter().any(|f| !f.is_immutable())
                 || query
                     .sort
                     .as_ref()
@@ -97,6 +106,13 @@ impl JMAP {
                 query::RequestArguments::EmailSubmission => {
                     self.email_submission_query(query).await?
                 }
+                query::RequestArguments::ContactCard => {
+                    self.contact_card_query(query, access_token).await?
+                }
+                query::RequestArguments::CalendarEvent(arguments) => {
+                    self.calendar_event_query(query.with_arguments(arguments), access_token)
+                        .await?
+                }
                 _ => unreachable!(),
             };
 
@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+pub mod get;
+pub mod query;
+pub mod set;
+pub mod vcard;
+
+pub trait JSContact {
+    fn full_name(&self) -> Option<String>;
+    fn email_addresses(&self) -> Vec<Value>;
+}
+
+impl JSContact for Object<Value> {
+    fn full_name(&self) -> Option<String> {
+        let name = self.get(&Property::Name).as_obj()?;
+        if let Some(full) = name
+            .get(&Property::_T("full".to_string()))
+            .as_string()
+            .map(|full| full.trim())
+            .filter(|full| !full.is_empty())
+        {
+            return Some(full.to_string());
+        }
+
+        let mut full_name = String::new();
+        for component in name
+            .get(&Property::_T("components".to_string()))
+            .as_list()?
+        {
+            if let Some(value) = component
+                .as_obj()
+                .and_then(|c| c.get(&Property::_T("value".to_string())).as_string())
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+            {
+                if !full_name.is_empty() {
+                    full_name.push(' ');
+                }
+                full_name.push_str(value);
+            }
+        }
+
+        if !full_name.is_empty() {
+            Some(full_name)
+        } else {
+            None
+        }
+    }
+
+    fn email_addresses(&self) -> Vec<Value> {
+        let mut addresses = Vec::new();
+        if let Some(emails) = self.get(&Property::parse("emails")).as_obj() {
+            for email in emails.properties.values() {
+                if let Some(address) = email
+                    .as_obj()
+                    .and_then(|e| e.get(&Property::_T("address".to_string())).as_string())
+                    .map(|address| address.trim().to_lowercase())
+                    .filter(|address| !address.is_empty())
+                {
+                    let address = Value::Text(address);
+                    if !addresses.contains(&address) {
+                        addresses.push(address);
+                    }
+                }
+            }
+        }
+        addresses
+    }
+}
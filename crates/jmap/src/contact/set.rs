@@ -0,0 +1,578 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::{
+        index::{IndexAs, IndexProperty, ObjectIndexBuilder},
+        Object,
+    },
+    response::references::EvalObjectReferences,
+    types::{
+        acl::Acl,
+        collection::Collection,
+        property::Property,
+        state::StateChange,
+        type_state::TypeState,
+        value::{MaybePatchValue, Value},
+    },
+};
+use store::{
+    rand::{thread_rng, Rng},
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+    Serialize,
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::JSContact;
+
+pub static SCHEMA: &[IndexProperty] = &[
+    IndexProperty::new(Property::Uid)
+        .index_as(IndexAs::Text {
+            tokenize: false,
+            index: true,
+        })
+        .required(),
+    IndexProperty::new(Property::FullName).index_as(IndexAs::Text {
+        tokenize: true,
+        index: true,
+    }),
+    IndexProperty::new(Property::Email).index_as(IndexAs::TextList {
+        tokenize: true,
+        index: true,
+    }),
+    IndexProperty::new(Property::AddressBookIds).index_as(IndexAs::IntegerList),
+];
+
+struct SetContext {
+    address_book_ids: RoaringBitmap,
+    can_add_address_book_ids: Option<RoaringBitmap>,
+    can_remove_address_book_ids: Option<RoaringBitmap>,
+}
+
+impl JMAP {
+    pub async fn contact_card_set(
+        &self,
+        mut request: SetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<SetResponse, MethodError> {
+        // Prepare response
+        let account_id = request.account_id.document_id();
+        let mut response = self
+            .prepare_set_response(&request, Collection::ContactCard)
+            .await?;
+
+        // Obtain address book ids and permissions
+        let is_shared = access_token.is_shared(account_id);
+        let ctx = SetContext {
+            address_book_ids: self.address_book_get_or_create(account_id).await?,
+            can_add_address_book_ids: if is_shared {
+                self.shared_documents(
+                    access_token,
+                    account_id,
+                    Collection::AddressBook,
+                    Acl::AddItems,
+                )
+                .await?
+                .into()
+            } else {
+                None
+            },
+            can_remove_address_book_ids: if is_shared {
+                self.shared_documents(
+                    access_token,
+                    account_id,
+                    Collection::AddressBook,
+                    Acl::RemoveItems,
+                )
+                .await?
+                .into()
+            } else {
+                None
+            },
+        };
+        let (can_modify_contact_ids, can_destroy_contact_ids) = if is_shared {
+            (
+                self.shared_contacts(access_token, account_id, Acl::ModifyItems)
+                    .await?
+                    .into(),
+                self.shared_contacts(access_token, account_id, Acl::RemoveItems)
+                    .await?
+                    .into(),
+            )
+        } else {
+            (None, None)
+        };
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        'create: for (id, object) in request.unwrap_create() {
+            let mut card = Object::with_capacity(object.properties.len() + 4);
+
+            for (property, value) in object.properties {
+                match (property, response.eval_object_references(value)) {
+                    (Property::AddressBookIds, Ok(MaybePatchValue::Value(Value::List(ids)))) => {
+                        card.set(Property::AddressBookIds, Value::List(ids));
+                    }
+                    (
+                        property @ (Property::Id
+                        | Property::Email
+                        | Property::FullName
+                        | Property::AddressBookIds),
+                        _,
+                    ) => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(property)
+                                .with_description("Invalid property or value."),
+                        );
+                        continue 'create;
+                    }
+                    (Property::_T(key), _) if key.contains('/') => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(Property::_T(key))
+                                .with_description("Patches are not allowed when creating a card."),
+                        );
+                        continue 'create;
+                    }
+                    (property, Ok(MaybePatchValue::Value(value))) => {
+                        if value != Value::Null {
+                            card.set(property, value);
+                        }
+                    }
+                    (property, Ok(_)) => {
+                        response.not_created.append(
+                            id,
+                            SetError::invalid_properties()
+                                .with_property(property)
+                                .with_description("Invalid property or value."),
+                        );
+                        continue 'create;
+                    }
+                    (_, Err(err)) => {
+                        response.not_created.append(id, err);
+                        continue 'create;
+                    }
+                }
+            }
+
+            // Add defaults
+            let type_ = Property::_T("@type".to_string());
+            if card.get(&type_) == &Value::Null {
+                card.append(type_, "Card");
+            }
+            let version = Property::_T("version".to_string());
+            if card.get(&version) == &Value::Null {
+                card.append(version, "1.0");
+            }
+            if card.get(&Property::Uid) == &Value::Null {
+                card.append(Property::Uid, generate_uid());
+            }
+
+            // Validate card
+            let builder = match self.contact_card_validate(card, None, &ctx) {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_created.append(id, err);
+                    continue 'create;
+                }
+            };
+
+            // Insert record
+            let document_id = self
+                .assign_document_id(account_id, Collection::ContactCard)
+                .await?;
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .create_document(document_id)
+                .custom(builder);
+            self.write_batch(batch).await?;
+            changes.log_insert(Collection::ContactCard, document_id);
+            response.created(id, document_id);
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain contact card
+            let document_id = id.document_id();
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            let mut card = current.inner.clone();
+            let mut has_card_changes = false;
+            for (property, value) in object.properties {
+                let value = match response.eval_object_references(value) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                };
+                match (property, value) {
+                    (Property::AddressBookIds, MaybePatchValue::Value(Value::List(ids))) => {
+                        card.set(Property::AddressBookIds, Value::List(ids));
+                    }
+                    (Property::AddressBookIds, MaybePatchValue::Patch(patch)) => {
+                        let mut patch = patch.into_iter();
+                        let address_book_id = patch.next().unwrap();
+                        let add = patch.next().unwrap().unwrap_bool();
+                        let mut address_book_ids = card
+                            .remove(&Property::AddressBookIds)
+                            .try_unwrap_list()
+                            .unwrap_or_default();
+                        if add {
+                            if !address_book_ids.contains(&address_book_id) {
+                                address_book_ids.push(address_book_id);
+                            }
+                        } else {
+                            address_book_ids.retain(|id| id != &address_book_id);
+                        }
+                        card.set(Property::AddressBookIds, Value::List(address_book_ids));
+                    }
+                    (Property::Uid, MaybePatchValue::Value(value))
+                        if card.get(&Property::Uid) == &value => {}
+                    (Property::_T(key), MaybePatchValue::Value(value)) if key.contains('/') => {
+                        if !card.patch(&key, value) {
+                            response.not_updated.append(
+                                id,
+                                SetError::new(SetErrorType::InvalidPatch).with_description(
+                                    format!("Path {key:?} does not exist in this card."),
+                                ),
+                            );
+                            continue 'update;
+                        }
+                        has_card_changes = true;
+                    }
+                    (
+                        property @ (Property::Id
+                        | Property::Uid
+                        | Property::Email
+                        | Property::FullName
+                        | Property::AddressBookIds),
+                        _,
+                    ) => {
+                        response.invalid_property_update(id, property);
+                        continue 'update;
+                    }
+                    (property, MaybePatchValue::Value(value)) => {
+                        if value != Value::Null {
+                            card.set(property, value);
+                        } else {
+                            card.remove(&property);
+                        }
+                        has_card_changes = true;
+                    }
+                    (property, _) => {
+                        response.invalid_property_update(id, property);
+                        continue 'update;
+                    }
+                }
+            }
+
+            // Verify permissions on shared accounts
+            if has_card_changes
+                && matches!(&can_modify_contact_ids, Some(ids) if !ids.contains(document_id))
+            {
+                response.not_updated.append(
+                    id,
+                    SetError::forbidden()
+                        .with_description("You are not allowed to modify this card."),
+                );
+                continue 'update;
+            }
+
+            // Validate card
+            let builder = match self.contact_card_validate(card, current.into(), &ctx) {
+                Ok(builder) => builder,
+                Err(err) => {
+                    response.not_updated.append(id, err);
+                    continue 'update;
+                }
+            };
+
+            // Write changes
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .update_document(document_id)
+                .custom(builder);
+            if !batch.is_empty() {
+                match self.store.write(batch.build()).await {
+                    Ok(_) => {
+                        changes.log_update(Collection::ContactCard, document_id);
+                    }
+                    Err(store::Error::AssertValueFailed) => {
+                        response.not_updated.append(
+                            id,
+                            SetError::forbidden().with_description(
+                                "Another process modified this card, please try again.",
+                            ),
+                        );
+                        continue 'update;
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            event = "error",
+                            context = "contact_card_set",
+                            account_id = account_id,
+                            error = ?err,
+                            "Failed to update contact card.");
+                        return Err(MethodError::ServerPartialFail);
+                    }
+                }
+            }
+            response.updated.append(id, None);
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+
+            // Verify permissions on shared accounts
+            if matches!(&can_destroy_contact_ids, Some(ids) if !ids.contains(document_id)) {
+                response.not_destroyed.append(
+                    id,
+                    SetError::forbidden()
+                        .with_description("You are not allowed to delete this card."),
+                );
+                continue;
+            }
+
+            // Obtain contact card
+            let current = if let Some(current) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    Collection::ContactCard,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                current
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+                continue;
+            };
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::ContactCard)
+                .delete_document(document_id)
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_current(current));
+            match self.store.write(batch.build()).await {
+                Ok(_) => {
+                    changes.log_delete(Collection::ContactCard, document_id);
+                    response.destroyed.push(id);
+                }
+                Err(store::Error::AssertValueFailed) => {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden().with_description(
+                            "Another process modified this card, please try again.",
+                        ),
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "contact_card_set",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to delete contact card.");
+                    return Err(MethodError::ServerPartialFail);
+                }
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.state_change = StateChange::new(account_id)
+                .with_change(TypeState::ContactCard, changes.change_id)
+                .into();
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(response)
+    }
+
+    fn contact_card_validate(
+        &self,
+        mut card: Object<Value>,
+        current: Option<HashedValue<Object<Value>>>,
+        ctx: &SetContext,
+    ) -> Result<ObjectIndexBuilder, SetError> {
+        // Validate address book ids
+        let address_book_ids = card
+            .get(&Property::AddressBookIds)
+            .as_list()
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default();
+        let current_address_book_ids = current
+            .as_ref()
+            .and_then(|c| c.inner.get(&Property::AddressBookIds).as_list())
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default();
+        if address_book_ids.is_empty() {
+            return Err(SetError::invalid_properties()
+                .with_property(Property::AddressBookIds)
+                .with_description("Card has to belong to at least one address book."));
+        } else if address_book_ids.len() > self.config.contacts_max_address_books {
+            return Err(SetError::invalid_properties()
+                .with_property(Property::AddressBookIds)
+                .with_description(format!(
+                    "Card cannot belong to more than {} address books.",
+                    self.config.contacts_max_address_books
+                )));
+        }
+        for address_book_id in address_book_ids {
+            if current_address_book_ids.contains(address_book_id) {
+                continue;
+            }
+            let address_book_id = address_book_id.try_cast_uint().unwrap_or(u64::MAX) as u32;
+            if !ctx.address_book_ids.contains(address_book_id) {
+                return Err(SetError::invalid_properties()
+                    .with_property(Property::AddressBookIds)
+                    .with_description(format!("addressBookId {address_book_id} does not exist.")));
+            } else if ctx
+                .can_add_address_book_ids
+                .as_ref()
+                .map_or(false, |ids| !ids.contains(address_book_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to add cards to address book {address_book_id}."
+                )));
+            }
+        }
+        for address_book_id in current_address_book_ids {
+            if address_book_ids.contains(address_book_id) {
+                continue;
+            }
+            let address_book_id = address_book_id.try_cast_uint().unwrap_or(u64::MAX) as u32;
+            if ctx
+                .can_remove_address_book_ids
+                .as_ref()
+                .map_or(false, |ids| !ids.contains(address_book_id))
+            {
+                return Err(SetError::forbidden().with_description(format!(
+                    "You are not allowed to remove cards from address book {address_book_id}."
+                )));
+            }
+        }
+
+        // Update derived index properties
+        if let Some(full_name) = card.full_name() {
+            card.set(Property::FullName, full_name);
+        } else {
+            card.remove(&Property::FullName);
+        }
+        let emails = card.email_addresses();
+        if !emails.is_empty() {
+            card.set(Property::Email, Value::List(emails));
+        } else {
+            card.remove(&Property::Email);
+        }
+
+        // Validate size
+        if (&card).serialize().len() > self.config.contacts_max_size {
+            return Err(
+                SetError::new(SetErrorType::TooLarge).with_description(format!(
+                    "Card cannot be larger than {} bytes.",
+                    self.config.contacts_max_size
+                )),
+            );
+        }
+
+        let builder = if let Some(current) = current {
+            // Only include properties that changed
+            let mut changes = Object::with_capacity(card.properties.len());
+            for property in current.inner.properties.keys() {
+                if !card.properties.contains_key(property) {
+                    changes.append(property.clone(), Value::Null);
+                }
+            }
+            for (property, value) in card.properties {
+                if current.inner.get(&property) != &value {
+                    changes.append(property, value);
+                }
+            }
+            ObjectIndexBuilder::new(SCHEMA)
+                .with_current(current)
+                .with_changes(changes)
+        } else {
+            ObjectIndexBuilder::new(SCHEMA).with_changes(card)
+        };
+
+        builder.validate()
+    }
+}
+
+pub(crate) fn generate_uid() -> String {
+    let mut bytes = thread_rng().gen::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
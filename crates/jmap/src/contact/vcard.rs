@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    object::Object,
+    types::{property::Property, value::Value},
+};
+
+use crate::calendar_event::ical::{escape, unescape, unfold, write_line, ContentLine, PRODID};
+
+use super::JSContact;
+
+const NAME_COMPONENTS: [&str; 5] = ["surname", "given", "additional", "prefix", "suffix"];
+
+pub fn parse_vcard(text: &str) -> Option<Object<Value>> {
+    let unfolded = unfold(text);
+    let mut card: Option<Object<Value>> = None;
+
+    for line in unfolded.lines().filter_map(ContentLine::parse) {
+        // Property names may be prefixed by a group name (e.g. "item1.EMAIL")
+        let name = line.name.rsplit('.').next().unwrap_or_default();
+        let card = match (name, &mut card) {
+            ("BEGIN", None) if line.value.trim().eq_ignore_ascii_case("VCARD") => {
+                card = Object::with_capacity(8)
+                    .with_property(Property::_T("@type".to_string()), "Card")
+                    .with_property(Property::parse("version"), "1.0")
+                    .into();
+                continue;
+            }
+            ("END", Some(_)) if line.value.trim().eq_ignore_ascii_case("VCARD") => {
+                break;
+            }
+            (_, Some(card)) => card,
+            _ => continue,
+        };
+        let value = unescape(line.value);
+
+        match name {
+            "UID" => {
+                card.set(Property::Uid, value);
+            }
+            "KIND" => {
+                card.set(Property::parse("kind"), value.trim().to_ascii_lowercase());
+            }
+            "FN" => {
+                name_object(card).set(Property::_T("full".to_string()), value);
+            }
+            "N" => {
+                let mut components = Vec::new();
+                for (kind, values) in NAME_COMPONENTS.iter().zip(split_unescaped(line.value, ';')) {
+                    for value in split_unescaped(values, ',') {
+                        let value = unescape(value);
+                        if !value.trim().is_empty() {
+                            components.push(Value::Object(
+                                Object::with_capacity(3)
+                                    .with_property(
+                                        Property::_T("@type".to_string()),
+                                        "NameComponent",
+                                    )
+                                    .with_property(Property::_T("kind".to_string()), *kind)
+                                    .with_property(Property::_T("value".to_string()), value),
+                            ));
+                        }
+                    }
+                }
+                if !components.is_empty() {
+                    name_object(card).set(
+                        Property::_T("components".to_string()),
+                        Value::List(components),
+                    );
+                }
+            }
+            "NICKNAME" => {
+                for nickname in split_unescaped(line.value, ',') {
+                    add_entry(
+                        card,
+                        "nicknames",
+                        Object::with_capacity(1)
+                            .with_property(Property::_T("name".to_string()), unescape(nickname)),
+                    );
+                }
+            }
+            "EMAIL" => {
+                let mut email = Object::with_capacity(3)
+                    .with_property(Property::_T("address".to_string()), value.trim());
+                add_contexts(&line, &mut email);
+                add_entry(card, "emails", email);
+            }
+            "TEL" => {
+                let number = value.trim();
+                let number = number.strip_prefix("tel:").unwrap_or(number);
+                let mut phone = Object::with_capacity(4)
+                    .with_property(Property::_T("number".to_string()), number);
+                let features = types(&line)
+                    .into_iter()
+                    .filter_map(|kind| match kind.as_str() {
+                        "cell" => Some("mobile".to_string()),
+                        "voice" | "fax" | "pager" | "text" | "video" | "textphone" => Some(kind),
+                        _ => None,
+                    })
+                    .fold(Object::with_capacity(1), |features, feature| {
+                        features.with_property(Property::_T(feature), true)
+                    });
+                if !features.properties.is_empty() {
+                    phone.set(
+                        Property::_T("features".to_string()),
+                        Value::Object(features),
+                    );
+                }
+                add_contexts(&line, &mut phone);
+                add_entry(card, "phones", phone);
+            }
+            "ORG" => {
+                let mut units = split_unescaped(line.value, ';').map(unescape);
+                let mut organization = Object::with_capacity(2).with_property(
+                    Property::_T("name".to_string()),
+                    units.next().unwrap_or_default(),
+                );
+                let units = units
+                    .filter(|unit| !unit.trim().is_empty())
+                    .map(|unit| {
+                        Value::Object(
+                            Object::with_capacity(1)
+                                .with_property(Property::_T("name".to_string()), unit),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if !units.is_empty() {
+                    organization.set(Property::_T("units".to_string()), Value::List(units));
+                }
+                add_entry(card, "organizations", organization);
+            }
+            "TITLE" => {
+                add_entry(
+                    card,
+                    "titles",
+                    Object::with_capacity(1).with_property(Property::_T("name".to_string()), value),
+                );
+            }
+            "NOTE" => {
+                add_entry(
+                    card,
+                    "notes",
+                    Object::with_capacity(1).with_property(Property::_T("note".to_string()), value),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    card
+}
+
+pub fn build_vcard(card: &Object<Value>) -> String {
+    let mut vcard = String::with_capacity(256);
+    vcard.push_str("BEGIN:VCARD\r\nVERSION:4.0\r\n");
+    write_line(&mut vcard, "PRODID", &[], PRODID);
+    if let Some(uid) = card.get(&Property::Uid).as_string() {
+        write_line(&mut vcard, "UID", &[], &escape(uid));
+    }
+    if let Some(kind) = card.get(&Property::parse("kind")).as_string() {
+        write_line(&mut vcard, "KIND", &[], &escape(kind));
+    }
+    write_line(
+        &mut vcard,
+        "FN",
+        &[],
+        &escape(&card.full_name().unwrap_or_default()),
+    );
+    if let Some(components) = card
+        .get(&Property::Name)
+        .as_obj()
+        .and_then(|name| name.get(&Property::_T("components".to_string())).as_list())
+    {
+        let mut parts = vec![Vec::new(); NAME_COMPONENTS.len()];
+        for component in components.iter().filter_map(|c| c.as_obj()) {
+            if let (Some(pos), Some(value)) = (
+                component
+                    .get(&Property::_T("kind".to_string()))
+                    .as_string()
+                    .and_then(|kind| NAME_COMPONENTS.iter().position(|k| *k == kind)),
+                component
+                    .get(&Property::_T("value".to_string()))
+                    .as_string(),
+            ) {
+                parts[pos].push(escape(value));
+            }
+        }
+        write_line(
+            &mut vcard,
+            "N",
+            &[],
+            &parts
+                .into_iter()
+                .map(|values| values.join(","))
+                .collect::<Vec<_>>()
+                .join(";"),
+        );
+    }
+    for nickname in entries(card, "nicknames", "name") {
+        write_line(&mut vcard, "NICKNAME", &[], &escape(nickname));
+    }
+    if let Some(emails) = card.get(&Property::parse("emails")).as_obj() {
+        for email in emails.properties.values().filter_map(|e| e.as_obj()) {
+            if let Some(address) = email.get(&Property::_T("address".to_string())).as_string() {
+                let types = context_types(email, &[]);
+                write_line(&mut vcard, "EMAIL", &type_params(&types), &escape(address));
+            }
+        }
+    }
+    if let Some(phones) = card.get(&Property::parse("phones")).as_obj() {
+        for phone in phones.properties.values().filter_map(|p| p.as_obj()) {
+            if let Some(number) = phone.get(&Property::_T("number".to_string())).as_string() {
+                let features = phone
+                    .get(&Property::_T("features".to_string()))
+                    .as_obj()
+                    .map(|features| {
+                        features
+                            .properties
+                            .keys()
+                            .map(|feature| match feature.to_string().as_str() {
+                                "mobile" => "cell".to_string(),
+                                feature => feature.to_string(),
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let types = context_types(phone, &features);
+                write_line(&mut vcard, "TEL", &type_params(&types), &escape(number));
+            }
+        }
+    }
+    if let Some(organizations) = card.get(&Property::parse("organizations")).as_obj() {
+        for organization in organizations.properties.values().filter_map(|o| o.as_obj()) {
+            let mut value = escape(
+                organization
+                    .get(&Property::_T("name".to_string()))
+                    .as_string()
+                    .unwrap_or_default(),
+            );
+            for unit in organization
+                .get(&Property::_T("units".to_string()))
+                .as_list()
+                .map(|units| units.as_slice())
+                .unwrap_or_default()
+            {
+                if let Some(name) = unit
+                    .as_obj()
+                    .and_then(|unit| unit.get(&Property::_T("name".to_string())).as_string())
+                {
+                    value.push(';');
+                    value.push_str(&escape(name));
+                }
+            }
+            write_line(&mut vcard, "ORG", &[], &value);
+        }
+    }
+    for title in entries(card, "titles", "name") {
+        write_line(&mut vcard, "TITLE", &[], &escape(title));
+    }
+    for note in entries(card, "notes", "note") {
+        write_line(&mut vcard, "NOTE", &[], &escape(note));
+    }
+    vcard.push_str("END:VCARD\r\n");
+    vcard
+}
+
+fn name_object(card: &mut Object<Value>) -> &mut Object<Value> {
+    let name = card
+        .properties
+        .get_mut_or_insert_with(Property::Name, || Value::Object(Object::with_capacity(2)));
+    if !matches!(name, Value::Object(_)) {
+        *name = Value::Object(Object::with_capacity(2));
+    }
+    name.as_obj_mut().unwrap()
+}
+
+fn add_entry(card: &mut Object<Value>, property: &str, entry: Object<Value>) {
+    if let Value::Object(entries) = card
+        .properties
+        .get_mut_or_insert_with(Property::parse(property), || {
+            Value::Object(Object::with_capacity(1))
+        })
+    {
+        let id = Property::_T((entries.properties.len() + 1).to_string());
+        entries.append(id, entry);
+    }
+}
+
+fn entries<'x>(
+    card: &'x Object<Value>,
+    property: &str,
+    field: &str,
+) -> impl Iterator<Item = &'x str> {
+    let field = Property::_T(field.to_string());
+    card.get(&Property::parse(property))
+        .as_obj()
+        .into_iter()
+        .flat_map(|entries| entries.properties.values())
+        .filter_map(move |entry| entry.as_obj()?.get(&field).as_string())
+}
+
+fn types(line: &ContentLine<'_>) -> Vec<String> {
+    line.params
+        .iter()
+        .filter(|(key, _)| key == "TYPE")
+        .flat_map(|(_, value)| value.split(','))
+        .map(|value| value.trim().to_ascii_lowercase())
+        .collect()
+}
+
+fn add_contexts(line: &ContentLine<'_>, entry: &mut Object<Value>) {
+    let mut contexts = Object::with_capacity(1);
+    let mut is_pref = line.param("PREF").is_some();
+    for kind in types(line) {
+        match kind.as_str() {
+            "work" => contexts.append(Property::_T("work".to_string()), true),
+            "home" => contexts.append(Property::_T("private".to_string()), true),
+            "pref" => is_pref = true,
+            _ => (),
+        }
+    }
+    if !contexts.properties.is_empty() {
+        entry.set(
+            Property::_T("contexts".to_string()),
+            Value::Object(contexts),
+        );
+    }
+    if is_pref {
+        entry.set(Property::_T("pref".to_string()), Value::UnsignedInt(1));
+    }
+}
+
+fn context_types(entry: &Object<Value>, extra: &[String]) -> Vec<String> {
+    let mut types = extra.to_vec();
+    if let Some(contexts) = entry.get(&Property::_T("contexts".to_string())).as_obj() {
+        for context in contexts.properties.keys() {
+            match context.to_string().as_str() {
+                "work" => types.push("work".to_string()),
+                "private" => types.push("home".to_string()),
+                _ => (),
+            }
+        }
+    }
+    if entry.get(&Property::_T("pref".to_string())).as_uint() == Some(1) {
+        types.push("pref".to_string());
+    }
+    types
+}
+
+fn type_params(types: &[String]) -> Vec<(&str, &str)> {
+    types.iter().map(|kind| ("TYPE", kind.as_str())).collect()
+}
+
+fn split_unescaped(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    let mut is_escaped = false;
+    let mut parts = Vec::new();
+    for (pos, ch) in value.char_indices() {
+        if is_escaped {
+            is_escaped = false;
+        } else if ch == '\\' {
+            is_escaped = true;
+        } else if ch == separator {
+            parts.push(&value[start..pos]);
+            start = pos + 1;
+        }
+    }
+    parts.push(&value[start..]);
+    parts.into_iter()
+}
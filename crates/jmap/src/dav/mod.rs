@@ -0,0 +1,493 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{fmt::Write, sync::Arc};
+
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, header, StatusCode};
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    types::{collection::Collection, id::Id, property::Property},
+};
+use utils::listener::ServerInstance;
+
+use crate::{
+    api::{http::fetch_body, http::ToHttpResponse, HttpRequest, HttpResponse},
+    auth::AccessToken,
+    JMAP,
+};
+
+use self::xml::{DavRequest, MultiStatus};
+
+pub mod object;
+pub mod propfind;
+pub mod xml;
+
+pub const DAV_PREFIX: &str = "/dav";
+const DAV_CAPABILITIES: &str = "1, 3, calendar-access, addressbook";
+const DAV_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DavCollection {
+    Calendar,
+    AddressBook,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavResource {
+    Root,
+    Principal {
+        account_id: u32,
+    },
+    Home {
+        account_id: u32,
+        collection: DavCollection,
+    },
+    Collection {
+        account_id: u32,
+        collection: DavCollection,
+        document_id: u32,
+    },
+    Item {
+        account_id: u32,
+        collection: DavCollection,
+        parent_id: u32,
+        uid: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Zero,
+    One,
+}
+
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+}
+
+pub struct DavResponse {
+    status: StatusCode,
+    headers: Vec<(&'static str, String)>,
+    body: Option<(&'static str, String)>,
+}
+
+impl JMAP {
+    pub async fn handle_dav_request(
+        &self,
+        mut req: HttpRequest,
+        access_token: Arc<AccessToken>,
+        instance: &Arc<ServerInstance>,
+    ) -> HttpResponse {
+        let resource = match self
+            .parse_dav_resource(req.uri().path(), &access_token)
+            .await
+        {
+            Ok(Some(resource)) => resource,
+            Ok(None) => return DavResponse::new(StatusCode::NOT_FOUND).into_http_response(),
+            Err(err) => return DavResponse::from(err).into_http_response(),
+        };
+        let depth = match req
+            .headers()
+            .get("Depth")
+            .and_then(|depth| depth.to_str().ok())
+        {
+            Some("0") => Depth::Zero,
+            _ => Depth::One,
+        };
+        let preconditions = Preconditions {
+            if_match: req
+                .headers()
+                .get(header::IF_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+            if_none_match: req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+        };
+
+        let method = req.method().as_str().to_string();
+        let result =
+            match method.as_str() {
+                "OPTIONS" => Ok(DavResponse::new(StatusCode::OK)
+                    .with_header("DAV", DAV_CAPABILITIES)
+                    .with_header("Allow", DAV_METHODS)),
+                "PROPFIND" | "REPORT" => {
+                    let is_report = method == "REPORT";
+                    match fetch_body(&mut req, self.config.request_max_size, &access_token)
+                        .await
+                        .map(|bytes| DavRequest::parse(&bytes, is_report))
+                    {
+                        Some(Some(request)) if is_report => {
+                            self.dav_report(&access_token, resource, request, depth)
+                                .await
+                        }
+                        Some(Some(request)) => {
+                            self.dav_propfind(&access_token, resource, request, depth)
+                                .await
+                        }
+                        Some(None) => Ok(DavResponse::new(StatusCode::BAD_REQUEST)),
+                        None => Ok(DavResponse::new(StatusCode::PAYLOAD_TOO_LARGE)),
+                    }
+                }
+                "GET" | "HEAD" => self.dav_get(&access_token, resource).await,
+                "PUT" => {
+                    match fetch_body(&mut req, self.config.request_max_size, &access_token).await {
+                        Some(bytes) => {
+                            self.dav_put(&access_token, instance, resource, bytes, preconditions)
+                                .await
+                        }
+                        None => Ok(DavResponse::new(StatusCode::PAYLOAD_TOO_LARGE)),
+                    }
+                }
+                "DELETE" => {
+                    self.dav_delete(&access_token, instance, resource, preconditions)
+                        .await
+                }
+                _ => Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header("Allow", DAV_METHODS)),
+            };
+
+        match result {
+            Ok(response) => response,
+            Err(err) => DavResponse::from(err),
+        }
+        .into_http_response()
+    }
+
+    async fn parse_dav_resource(
+        &self,
+        path: &str,
+        access_token: &AccessToken,
+    ) -> Result<Option<DavResource>, MethodError> {
+        let mut path = path
+            .strip_prefix(DAV_PREFIX)
+            .unwrap_or_default()
+            .split('/')
+            .filter(|part| !part.is_empty());
+        let collection = match path.next() {
+            Some("principals") => None,
+            Some("calendars") => Some(DavCollection::Calendar),
+            Some("addressbooks") => Some(DavCollection::AddressBook),
+            Some(_) => return Ok(None),
+            None => return Ok(Some(DavResource::Root)),
+        };
+
+        // Obtain account id and make sure the account is accessible
+        let account_name = if let Some(account_name) = path.next().and_then(decode_path) {
+            account_name
+        } else {
+            return Ok(None);
+        };
+        let account_id = if account_name == access_token.name {
+            access_token.primary_id()
+        } else if let Some(account_id) = self.try_get_account_id(&account_name).await? {
+            account_id
+        } else {
+            return Ok(None);
+        };
+        if !access_token.is_member(account_id)
+            && !match collection {
+                Some(collection) => access_token.has_access(account_id, collection.container()),
+                None => {
+                    access_token.has_access(account_id, Collection::Calendar)
+                        || access_token.has_access(account_id, Collection::AddressBook)
+                }
+            }
+        {
+            return Err(MethodError::Forbidden(format!(
+                "You do not have access to account {account_name}."
+            )));
+        }
+
+        let collection = if let Some(collection) = collection {
+            collection
+        } else {
+            return Ok(if path.next().is_none() {
+                Some(DavResource::Principal { account_id })
+            } else {
+                None
+            });
+        };
+        let document_id = if let Some(document_id) = path.next() {
+            if let Some(id) = Id::from_bytes(document_id.as_bytes()) {
+                id.document_id()
+            } else {
+                return Ok(None);
+            }
+        } else {
+            return Ok(Some(DavResource::Home {
+                account_id,
+                collection,
+            }));
+        };
+        let uid = if let Some(name) = path.next() {
+            if let Some(uid) = name
+                .strip_suffix(collection.extension())
+                .and_then(decode_path)
+            {
+                uid
+            } else {
+                return Ok(None);
+            }
+        } else {
+            return Ok(Some(DavResource::Collection {
+                account_id,
+                collection,
+                document_id,
+            }));
+        };
+
+        Ok(if path.next().is_none() {
+            Some(DavResource::Item {
+                account_id,
+                collection,
+                parent_id: document_id,
+                uid,
+            })
+        } else {
+            None
+        })
+    }
+}
+
+impl DavCollection {
+    pub fn path(&self) -> &'static str {
+        match self {
+            DavCollection::Calendar => "calendars",
+            DavCollection::AddressBook => "addressbooks",
+        }
+    }
+
+    pub fn container(&self) -> Collection {
+        match self {
+            DavCollection::Calendar => Collection::Calendar,
+            DavCollection::AddressBook => Collection::AddressBook,
+        }
+    }
+
+    pub fn item(&self) -> Collection {
+        match self {
+            DavCollection::Calendar => Collection::CalendarEvent,
+            DavCollection::AddressBook => Collection::ContactCard,
+        }
+    }
+
+    pub fn parent_property(&self) -> Property {
+        match self {
+            DavCollection::Calendar => Property::CalendarIds,
+            DavCollection::AddressBook => Property::AddressBookIds,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DavCollection::Calendar => ".ics",
+            DavCollection::AddressBook => ".vcf",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DavCollection::Calendar => "text/calendar; charset=utf-8",
+            DavCollection::AddressBook => "text/vcard; charset=utf-8",
+        }
+    }
+}
+
+impl Preconditions {
+    /// Evaluates the If-Match and If-None-Match headers against the
+    /// ETag of the current resource, if any.
+    pub fn matches(&self, etag: Option<&str>) -> bool {
+        let contains = |header: &str| {
+            header.split(',').any(|value| {
+                let value = value.trim();
+                value == "*" || Some(value.trim_start_matches("W/")) == etag
+            })
+        };
+        self.if_match
+            .as_deref()
+            .map_or(true, |header| etag.is_some() && contains(header))
+            && self
+                .if_none_match
+                .as_deref()
+                .map_or(true, |header| etag.is_none() || !contains(header))
+    }
+}
+
+impl DavResponse {
+    pub fn new(status: StatusCode) -> Self {
+        DavResponse {
+            status,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        DavResponse::new(StatusCode::UNAUTHORIZED)
+            .with_header("WWW-Authenticate", "Basic realm=\"Stalwart Server\"")
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        DavResponse::new(StatusCode::MOVED_PERMANENTLY).with_header("Location", location)
+    }
+
+    pub fn multistatus(multistatus: MultiStatus) -> Self {
+        DavResponse::new(StatusCode::MULTI_STATUS)
+            .with_body("application/xml; charset=utf-8", multistatus.to_xml())
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &'static str, body: String) -> Self {
+        self.body = Some((content_type, body));
+        self
+    }
+}
+
+impl ToHttpResponse for DavResponse {
+    fn into_http_response(self) -> HttpResponse {
+        let mut response = hyper::Response::builder().status(self.status);
+        for (name, value) in self.headers {
+            response = response.header(name, value);
+        }
+        let body = if let Some((content_type, body)) = self.body {
+            response = response.header(header::CONTENT_TYPE, content_type);
+            body
+        } else {
+            String::new()
+        };
+        response
+            .body(
+                Full::new(Bytes::from(body))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+}
+
+impl From<MethodError> for DavResponse {
+    fn from(err: MethodError) -> Self {
+        match err {
+            MethodError::Forbidden(description) => DavResponse::new(StatusCode::FORBIDDEN)
+                .with_body("text/plain; charset=utf-8", description),
+            MethodError::NotFound | MethodError::AccountNotFound => {
+                DavResponse::new(StatusCode::NOT_FOUND)
+            }
+            err => DavResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .with_body("text/plain; charset=utf-8", err.to_string()),
+        }
+    }
+}
+
+impl From<SetError> for DavResponse {
+    fn from(err: SetError) -> Self {
+        let status = match err.type_ {
+            SetErrorType::Forbidden
+            | SetErrorType::AddressBookHasContents
+            | SetErrorType::CalendarHasEvent => StatusCode::FORBIDDEN,
+            SetErrorType::NotFound => StatusCode::NOT_FOUND,
+            SetErrorType::OverQuota => StatusCode::INSUFFICIENT_STORAGE,
+            SetErrorType::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        if let Some(description) = err.description {
+            DavResponse::new(status)
+                .with_body("text/plain; charset=utf-8", description.into_owned())
+        } else {
+            DavResponse::new(status)
+        }
+    }
+}
+
+pub fn principal_href(account_name: &str) -> String {
+    format!("{DAV_PREFIX}/principals/{}/", encode_path(account_name))
+}
+
+pub fn home_href(collection: DavCollection, account_name: &str) -> String {
+    format!(
+        "{DAV_PREFIX}/{}/{}/",
+        collection.path(),
+        encode_path(account_name)
+    )
+}
+
+pub fn collection_href(collection: DavCollection, account_name: &str, document_id: u32) -> String {
+    format!(
+        "{}{}/",
+        home_href(collection, account_name),
+        Id::from(document_id)
+    )
+}
+
+pub fn item_href(
+    collection: DavCollection,
+    account_name: &str,
+    parent_id: u32,
+    uid: &str,
+) -> String {
+    format!(
+        "{}{}{}",
+        collection_href(collection, account_name, parent_id),
+        encode_path(uid),
+        collection.extension()
+    )
+}
+
+pub fn encode_path(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~@".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+pub fn decode_path(value: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
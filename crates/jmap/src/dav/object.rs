@@ -0,0 +1,426 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use hyper::StatusCode;
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::set::{RequestArguments, SetRequest},
+    object::{calendar, calendar_event, contact, Object},
+    request::reference::MaybeReference,
+    types::{
+        id::Id,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use utils::{listener::ServerInstance, map::vec_map::VecMap};
+
+use crate::{
+    auth::AccessToken,
+    calendar_event::ical::ICalendar,
+    contact::vcard::{build_vcard, parse_vcard},
+    JMAP,
+};
+
+use super::{
+    propfind::{build_icalendar, etag},
+    DavCollection, DavResource, DavResponse, Preconditions, DAV_METHODS,
+};
+
+enum DavOperation {
+    Create(Object<SetValue>),
+    Update(u32, Object<SetValue>),
+    Destroy(u32),
+}
+
+/// Properties managed by the server that are never replaced by a PUT.
+static PROTECTED_PROPERTIES: &[Property] = &[
+    Property::Id,
+    Property::Uid,
+    Property::CalendarIds,
+    Property::AddressBookIds,
+    Property::FromDate,
+    Property::ToDate,
+    Property::FullName,
+    Property::Email,
+];
+
+impl JMAP {
+    pub(crate) async fn dav_get(
+        &self,
+        access_token: &AccessToken,
+        resource: DavResource,
+    ) -> Result<DavResponse, MethodError> {
+        if let DavResource::Item {
+            account_id,
+            collection,
+            parent_id,
+            uid,
+        } = resource
+        {
+            Ok(
+                if let Some((_, object)) = self
+                    .dav_item(access_token, account_id, collection, parent_id, &uid)
+                    .await?
+                {
+                    let body = match collection {
+                        DavCollection::Calendar => build_icalendar(&object.inner),
+                        DavCollection::AddressBook => build_vcard(&object.inner),
+                    };
+                    DavResponse::new(StatusCode::OK)
+                        .with_header("ETag", etag(&object))
+                        .with_body(collection.content_type(), body)
+                } else {
+                    DavResponse::new(StatusCode::NOT_FOUND)
+                },
+            )
+        } else {
+            Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED).with_header("Allow", DAV_METHODS))
+        }
+    }
+
+    pub(crate) async fn dav_put(
+        &self,
+        access_token: &AccessToken,
+        instance: &Arc<ServerInstance>,
+        resource: DavResource,
+        bytes: Vec<u8>,
+        preconditions: Preconditions,
+    ) -> Result<DavResponse, MethodError> {
+        let (account_id, collection, parent_id, uid) = if let DavResource::Item {
+            account_id,
+            collection,
+            parent_id,
+            uid,
+        } = resource
+        {
+            (account_id, collection, parent_id, uid)
+        } else {
+            return Ok(
+                DavResponse::new(StatusCode::METHOD_NOT_ALLOWED).with_header("Allow", DAV_METHODS)
+            );
+        };
+
+        // Parse object
+        let mut object = if let Some(object) =
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|text| match collection {
+                    DavCollection::Calendar => ICalendar::parse(text)?.into_event(),
+                    DavCollection::AddressBook => parse_vcard(text),
+                }) {
+            object
+        } else {
+            return Ok(DavResponse::new(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        };
+        match object.get(&Property::Uid).as_string() {
+            Some(object_uid) if object_uid == uid => (),
+            Some(_) => {
+                return Ok(DavResponse::new(StatusCode::CONFLICT).with_body(
+                    "text/plain; charset=utf-8",
+                    "The UID of the object does not match the resource name.".to_string(),
+                ));
+            }
+            None => {
+                object.set(Property::Uid, uid.clone());
+            }
+        }
+
+        // Make sure the preconditions are met
+        let current = self
+            .dav_item(access_token, account_id, collection, parent_id, &uid)
+            .await?;
+        if !preconditions.matches(
+            current
+                .as_ref()
+                .map(|(_, current)| etag(current))
+                .as_deref(),
+        ) {
+            return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+        }
+
+        let (operation, status) = if let Some((document_id, current)) = current {
+            // Replace all properties, removing the ones missing from the new version
+            let mut update = Object {
+                properties: VecMap::with_capacity(object.properties.len()),
+            };
+            for property in current.inner.properties.keys() {
+                if !PROTECTED_PROPERTIES.contains(property)
+                    && !object.properties.contains_key(property)
+                {
+                    update
+                        .properties
+                        .append(property.clone(), SetValue::Value(Value::Null));
+                }
+            }
+            for (property, value) in object.properties {
+                if !PROTECTED_PROPERTIES.contains(&property) {
+                    update.properties.append(property, SetValue::Value(value));
+                }
+            }
+            (
+                DavOperation::Update(document_id, update),
+                StatusCode::NO_CONTENT,
+            )
+        } else {
+            object.set(
+                collection.parent_property(),
+                Value::List(vec![Value::Id(parent_id.into())]),
+            );
+            (
+                DavOperation::Create(Object {
+                    properties: object
+                        .properties
+                        .into_iter()
+                        .map(|(property, value)| (property, SetValue::Value(value)))
+                        .collect(),
+                }),
+                StatusCode::CREATED,
+            )
+        };
+
+        if let Err(err) = self
+            .dav_write(access_token, instance, account_id, collection, operation)
+            .await?
+        {
+            return Ok(err.into());
+        }
+
+        let mut response = DavResponse::new(status);
+        if let Some((_, object)) = self
+            .dav_item(access_token, account_id, collection, parent_id, &uid)
+            .await?
+        {
+            response = response.with_header("ETag", etag(&object));
+        }
+        Ok(response)
+    }
+
+    pub(crate) async fn dav_delete(
+        &self,
+        access_token: &AccessToken,
+        instance: &Arc<ServerInstance>,
+        resource: DavResource,
+        preconditions: Preconditions,
+    ) -> Result<DavResponse, MethodError> {
+        match resource {
+            DavResource::Item {
+                account_id,
+                collection,
+                parent_id,
+                uid,
+            } => {
+                let (document_id, current) = if let Some(current) = self
+                    .dav_item(access_token, account_id, collection, parent_id, &uid)
+                    .await?
+                {
+                    current
+                } else {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                };
+                if !preconditions.matches(Some(&etag(&current))) {
+                    return Ok(DavResponse::new(StatusCode::PRECONDITION_FAILED));
+                }
+
+                // Objects that belong to other collections are only removed from this one
+                let parent_ids = current
+                    .inner
+                    .get(&collection.parent_property())
+                    .as_list()
+                    .map(|ids| {
+                        ids.iter()
+                            .filter(|id| {
+                                id.as_id().map_or(true, |id| id.document_id() != parent_id)
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let operation = if !parent_ids.is_empty() {
+                    DavOperation::Update(
+                        document_id,
+                        Object {
+                            properties: VecMap::from_iter([(
+                                collection.parent_property(),
+                                SetValue::Value(Value::List(parent_ids)),
+                            )]),
+                        },
+                    )
+                } else {
+                    DavOperation::Destroy(document_id)
+                };
+
+                Ok(
+                    match self
+                        .dav_write(access_token, instance, account_id, collection, operation)
+                        .await?
+                    {
+                        Ok(()) => DavResponse::new(StatusCode::NO_CONTENT),
+                        Err(err) => err.into(),
+                    },
+                )
+            }
+            DavResource::Collection {
+                account_id,
+                collection,
+                document_id,
+            } => {
+                let destroy = Some(MaybeReference::Value(vec![Id::from(document_id)]));
+                let mut response = match collection {
+                    DavCollection::Calendar => {
+                        self.calendar_set(
+                            SetRequest {
+                                account_id: Id::from(account_id),
+                                if_in_state: None,
+                                create: None,
+                                update: None,
+                                destroy,
+                                arguments: calendar::SetArguments {
+                                    on_destroy_remove_events: Some(true),
+                                },
+                            },
+                            access_token,
+                        )
+                        .await?
+                    }
+                    DavCollection::AddressBook => {
+                        self.address_book_set(
+                            SetRequest {
+                                account_id: Id::from(account_id),
+                                if_in_state: None,
+                                create: None,
+                                update: None,
+                                destroy,
+                                arguments: contact::SetArguments {
+                                    on_destroy_remove_contents: Some(true),
+                                },
+                            },
+                            access_token,
+                        )
+                        .await?
+                    }
+                };
+                if let Some(state_change) = response.state_change.take() {
+                    self.broadcast_state_change(state_change).await;
+                }
+
+                Ok(
+                    if let Some(err) = response
+                        .not_destroyed
+                        .into_iter()
+                        .map(|(_, err)| err)
+                        .next()
+                    {
+                        err.into()
+                    } else {
+                        DavResponse::new(StatusCode::NO_CONTENT)
+                    },
+                )
+            }
+            _ => {
+                Ok(DavResponse::new(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header("Allow", DAV_METHODS))
+            }
+        }
+    }
+
+    async fn dav_write(
+        &self,
+        access_token: &AccessToken,
+        instance: &Arc<ServerInstance>,
+        account_id: u32,
+        collection: DavCollection,
+        operation: DavOperation,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        let mut create = None;
+        let mut update = None;
+        let mut destroy = None;
+        match operation {
+            DavOperation::Create(object) => {
+                create = VecMap::from_iter([("dav".to_string(), object)]).into();
+            }
+            DavOperation::Update(document_id, object) => {
+                update = VecMap::from_iter([(Id::from(document_id), object)]).into();
+            }
+            DavOperation::Destroy(document_id) => {
+                destroy = MaybeReference::Value(vec![Id::from(document_id)]).into();
+            }
+        }
+
+        // Changes are applied through the JMAP methods so that ACLs, quotas,
+        // validations and scheduling messages are handled in a single place.
+        let mut response = match collection {
+            DavCollection::Calendar => {
+                self.calendar_event_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy,
+                        arguments: calendar_event::SetArguments {
+                            send_scheduling_messages: Some(true),
+                        },
+                    },
+                    instance,
+                    access_token,
+                )
+                .await?
+            }
+            DavCollection::AddressBook => {
+                self.contact_card_set(
+                    SetRequest {
+                        account_id: Id::from(account_id),
+                        if_in_state: None,
+                        create,
+                        update,
+                        destroy,
+                        arguments: RequestArguments::ContactCard,
+                    },
+                    access_token,
+                )
+                .await?
+            }
+        };
+        if let Some(state_change) = response.state_change.take() {
+            self.broadcast_state_change(state_change).await;
+        }
+
+        Ok(
+            if let Some(err) = response
+                .not_created
+                .into_iter()
+                .map(|(_, err)| err)
+                .chain(response.not_updated.into_iter().map(|(_, err)| err))
+                .chain(response.not_destroyed.into_iter().map(|(_, err)| err))
+                .next()
+            {
+                Err(err)
+            } else {
+                Ok(())
+            },
+        )
+    }
+}
@@ -0,0 +1,866 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use hyper::StatusCode;
+use jmap_proto::{
+    error::method::MethodError,
+    object::Object,
+    types::{acl::Acl, property::Property, state::State, value::Value},
+};
+use store::{
+    query::{
+        self,
+        log::{Change, Query},
+    },
+    roaring::RoaringBitmap,
+    write::assert::HashedValue,
+};
+
+use crate::{
+    auth::AccessToken, calendar_event::ical::ICalendar, contact::vcard::build_vcard, JMAP,
+};
+
+use super::{
+    collection_href, home_href, item_href, principal_href,
+    xml::{
+        escape_xml, href, DavProperties, DavProperty, DavRequest, DavRequestType, DavResponseItem,
+        MultiStatus, NS_DAV,
+    },
+    DavCollection, DavResource, DavResponse, Depth, DAV_PREFIX,
+};
+
+const SYNC_TOKEN_PREFIX: &str = "data:,sync-";
+
+static ALL_PROPERTIES: &[DavProperty] = &[
+    DavProperty::ResourceType,
+    DavProperty::DisplayName,
+    DavProperty::GetETag,
+    DavProperty::GetContentType,
+    DavProperty::GetCTag,
+    DavProperty::SyncToken,
+    DavProperty::CurrentUserPrincipal,
+    DavProperty::Owner,
+];
+
+pub(crate) enum DavEntry {
+    Root,
+    Principal {
+        name: String,
+        emails: Vec<String>,
+        calendar_homes: Vec<String>,
+        addressbook_homes: Vec<String>,
+    },
+    Home {
+        account_name: String,
+    },
+    Collection {
+        account_name: String,
+        collection: DavCollection,
+        object: Object<Value>,
+        is_writable: bool,
+        sync_token: String,
+    },
+    Item {
+        collection: DavCollection,
+        object: HashedValue<Object<Value>>,
+    },
+}
+
+impl JMAP {
+    pub(crate) async fn dav_propfind(
+        &self,
+        access_token: &AccessToken,
+        resource: DavResource,
+        request: DavRequest,
+        depth: Depth,
+    ) -> Result<DavResponse, MethodError> {
+        let mut entries = Vec::new();
+        match resource {
+            DavResource::Root => {
+                entries.push((format!("{DAV_PREFIX}/"), DavEntry::Root));
+            }
+            DavResource::Principal { account_id } => {
+                let name = self.dav_account_name(access_token, account_id).await?;
+                entries.push((
+                    principal_href(&name),
+                    self.dav_principal(access_token, name).await?,
+                ));
+            }
+            DavResource::Home {
+                account_id,
+                collection,
+            } => {
+                let account_name = self.dav_account_name(access_token, account_id).await?;
+                entries.push((
+                    home_href(collection, &account_name),
+                    DavEntry::Home {
+                        account_name: account_name.clone(),
+                    },
+                ));
+                if depth == Depth::One {
+                    for document_id in self
+                        .dav_collection_ids(access_token, account_id, collection)
+                        .await?
+                    {
+                        if let Some(entry) = self
+                            .dav_collection(
+                                access_token,
+                                account_id,
+                                &account_name,
+                                collection,
+                                document_id,
+                            )
+                            .await?
+                        {
+                            entries.push((
+                                collection_href(collection, &account_name, document_id),
+                                entry,
+                            ));
+                        }
+                    }
+                }
+            }
+            DavResource::Collection {
+                account_id,
+                collection,
+                document_id,
+            } => {
+                let account_name = self.dav_account_name(access_token, account_id).await?;
+                let entry = if self
+                    .dav_collection_ids(access_token, account_id, collection)
+                    .await?
+                    .contains(document_id)
+                {
+                    self.dav_collection(
+                        access_token,
+                        account_id,
+                        &account_name,
+                        collection,
+                        document_id,
+                    )
+                    .await?
+                } else {
+                    None
+                };
+                if let Some(entry) = entry {
+                    entries.push((
+                        collection_href(collection, &account_name, document_id),
+                        entry,
+                    ));
+                } else {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                }
+                if depth == Depth::One {
+                    let item_ids = self
+                        .dav_item_ids(access_token, account_id, collection, document_id, None)
+                        .await?;
+                    self.dav_items(
+                        account_id,
+                        &account_name,
+                        collection,
+                        document_id,
+                        item_ids,
+                        &mut entries,
+                    )
+                    .await?;
+                }
+            }
+            DavResource::Item {
+                account_id,
+                collection,
+                parent_id,
+                uid,
+            } => {
+                if let Some((_, object)) = self
+                    .dav_item(access_token, account_id, collection, parent_id, &uid)
+                    .await?
+                {
+                    let account_name = self.dav_account_name(access_token, account_id).await?;
+                    entries.push((
+                        item_href(collection, &account_name, parent_id, &uid),
+                        DavEntry::Item { collection, object },
+                    ));
+                } else {
+                    return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+                }
+            }
+        }
+
+        let current_principal = principal_href(&access_token.name);
+        Ok(DavResponse::multistatus(MultiStatus {
+            responses: entries
+                .into_iter()
+                .map(|(href, entry)| {
+                    entry.build_response(href, &request.properties, &current_principal)
+                })
+                .collect(),
+            sync_token: None,
+        }))
+    }
+
+    pub(crate) async fn dav_report(
+        &self,
+        access_token: &AccessToken,
+        resource: DavResource,
+        request: DavRequest,
+        depth: Depth,
+    ) -> Result<DavResponse, MethodError> {
+        let (account_id, collection, parent_id) = match resource {
+            DavResource::Collection {
+                account_id,
+                collection,
+                document_id,
+            } => (account_id, collection, document_id),
+            _ => return Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+        };
+        match (request.request_type, collection) {
+            (
+                DavRequestType::CalendarQuery | DavRequestType::CalendarMultiget,
+                DavCollection::Calendar,
+            )
+            | (
+                DavRequestType::AddressbookQuery | DavRequestType::AddressbookMultiget,
+                DavCollection::AddressBook,
+            )
+            | (DavRequestType::SyncCollection, _) => (),
+            _ => return Ok(DavResponse::new(StatusCode::FORBIDDEN)),
+        }
+        if !self
+            .dav_collection_ids(access_token, account_id, collection)
+            .await?
+            .contains(parent_id)
+        {
+            return Ok(DavResponse::new(StatusCode::NOT_FOUND));
+        }
+
+        let account_name = self.dav_account_name(access_token, account_id).await?;
+        let mut entries = Vec::new();
+        let mut missing = Vec::new();
+        let mut sync_token = None;
+        match request.request_type {
+            DavRequestType::CalendarQuery | DavRequestType::AddressbookQuery => {
+                let item_ids = if depth == Depth::One {
+                    self.dav_item_ids(
+                        access_token,
+                        account_id,
+                        collection,
+                        parent_id,
+                        request.time_range,
+                    )
+                    .await?
+                } else {
+                    RoaringBitmap::new()
+                };
+                self.dav_items(
+                    account_id,
+                    &account_name,
+                    collection,
+                    parent_id,
+                    item_ids,
+                    &mut entries,
+                )
+                .await?;
+            }
+            DavRequestType::CalendarMultiget | DavRequestType::AddressbookMultiget => {
+                for href in request.hrefs {
+                    let uid = match self
+                        .parse_dav_resource(href_path(&href), access_token)
+                        .await
+                    {
+                        Ok(Some(DavResource::Item {
+                            account_id: item_account_id,
+                            collection: item_collection,
+                            parent_id: item_parent_id,
+                            uid,
+                        })) if item_account_id == account_id
+                            && item_collection == collection
+                            && item_parent_id == parent_id =>
+                        {
+                            uid
+                        }
+                        _ => {
+                            missing.push(href);
+                            continue;
+                        }
+                    };
+                    if let Some((_, object)) = self
+                        .dav_item(access_token, account_id, collection, parent_id, &uid)
+                        .await?
+                    {
+                        entries.push((
+                            item_href(collection, &account_name, parent_id, &uid),
+                            DavEntry::Item { collection, object },
+                        ));
+                    } else {
+                        missing.push(href);
+                    }
+                }
+            }
+            DavRequestType::SyncCollection => {
+                let state = self.get_state(account_id, collection.item()).await?;
+                let since = match request
+                    .sync_token
+                    .as_deref()
+                    .filter(|token| !token.is_empty())
+                {
+                    Some(token) => match parse_sync_token(token) {
+                        Some(since) => since,
+                        None => return Ok(invalid_sync_token()),
+                    },
+                    None => None,
+                };
+                let item_ids = self
+                    .dav_item_ids(access_token, account_id, collection, parent_id, None)
+                    .await?;
+
+                if let Some(since) = since {
+                    let changelog = self
+                        .changes_(account_id, collection.item(), Query::Since(since))
+                        .await?;
+                    let mut changed_ids = RoaringBitmap::new();
+                    for change in changelog.changes.iter() {
+                        match change {
+                            Change::Insert(id) | Change::Update(id) | Change::ChildUpdate(id) => {
+                                changed_ids.insert(*id as u32);
+                            }
+                            Change::Delete(_) => {
+                                // Resources are addressed by their uid, which is no longer
+                                // available once deleted. Ask the client to resynchronize.
+                                return Ok(invalid_sync_token());
+                            }
+                        }
+                    }
+                    sync_token = Some(format_sync_token(&State::Exact(
+                        if !changelog.changes.is_empty() {
+                            changelog.to_change_id
+                        } else {
+                            since
+                        },
+                    )));
+
+                    // Changed resources that are no longer part of this collection
+                    let removed_ids = &changed_ids - &item_ids;
+                    for object in self
+                        .get_properties::<Object<Value>>(
+                            account_id,
+                            collection.item(),
+                            removed_ids.iter(),
+                            Property::Value,
+                        )
+                        .await?
+                        .into_iter()
+                        .flatten()
+                    {
+                        if let Some(uid) = object.get(&Property::Uid).as_string() {
+                            missing.push(item_href(collection, &account_name, parent_id, uid));
+                        }
+                    }
+
+                    self.dav_items(
+                        account_id,
+                        &account_name,
+                        collection,
+                        parent_id,
+                        changed_ids & item_ids,
+                        &mut entries,
+                    )
+                    .await?;
+                } else {
+                    sync_token = Some(format_sync_token(&state));
+                    self.dav_items(
+                        account_id,
+                        &account_name,
+                        collection,
+                        parent_id,
+                        item_ids,
+                        &mut entries,
+                    )
+                    .await?;
+                }
+            }
+            DavRequestType::PropFind => return Ok(DavResponse::new(StatusCode::BAD_REQUEST)),
+        }
+
+        let current_principal = principal_href(&access_token.name);
+        let mut responses = entries
+            .into_iter()
+            .map(|(href, entry)| {
+                entry.build_response(href, &request.properties, &current_principal)
+            })
+            .collect::<Vec<_>>();
+        responses.extend(missing.into_iter().map(|href| DavResponseItem {
+            href,
+            status: Some(404),
+            found: vec![],
+            not_found: vec![],
+        }));
+
+        Ok(DavResponse::multistatus(MultiStatus {
+            responses,
+            sync_token,
+        }))
+    }
+
+    pub(crate) async fn dav_account_name(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+    ) -> Result<String, MethodError> {
+        if access_token.primary_id() == account_id {
+            Ok(access_token.name.clone())
+        } else {
+            self.get_account_name(account_id)
+                .await?
+                .ok_or(MethodError::AccountNotFound)
+        }
+    }
+
+    async fn dav_principal(
+        &self,
+        access_token: &AccessToken,
+        name: String,
+    ) -> Result<DavEntry, MethodError> {
+        let emails = self
+            .directory
+            .emails_by_name(&name)
+            .await
+            .unwrap_or_default();
+        let mut calendar_homes = vec![home_href(DavCollection::Calendar, &name)];
+        let mut addressbook_homes = vec![home_href(DavCollection::AddressBook, &name)];
+
+        // Include the homes of accounts shared with the current user
+        if name == access_token.name {
+            for (collection, homes) in [
+                (DavCollection::Calendar, &mut calendar_homes),
+                (DavCollection::AddressBook, &mut addressbook_homes),
+            ] {
+                for account_id in access_token.shared_accounts(collection.container()) {
+                    if let Some(account_name) = self.get_account_name(*account_id).await? {
+                        homes.push(home_href(collection, &account_name));
+                    }
+                }
+            }
+        }
+
+        Ok(DavEntry::Principal {
+            name,
+            emails,
+            calendar_homes,
+            addressbook_homes,
+        })
+    }
+
+    pub(crate) async fn dav_collection_ids(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        collection: DavCollection,
+    ) -> Result<RoaringBitmap, MethodError> {
+        if access_token.is_member(account_id) {
+            match collection {
+                DavCollection::Calendar => self.calendar_get_or_create(account_id).await,
+                DavCollection::AddressBook => self.address_book_get_or_create(account_id).await,
+            }
+        } else {
+            self.shared_documents(access_token, account_id, collection.container(), Acl::Read)
+                .await
+        }
+    }
+
+    async fn dav_collection(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        account_name: &str,
+        collection: DavCollection,
+        document_id: u32,
+    ) -> Result<Option<DavEntry>, MethodError> {
+        let object = if let Some(object) = self
+            .get_property::<Object<Value>>(
+                account_id,
+                collection.container(),
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            object
+        } else {
+            return Ok(None);
+        };
+        let is_writable = access_token.is_member(account_id)
+            || self
+                .shared_documents(
+                    access_token,
+                    account_id,
+                    collection.container(),
+                    Acl::AddItems,
+                )
+                .await?
+                .contains(document_id);
+
+        Ok(Some(DavEntry::Collection {
+            account_name: account_name.to_string(),
+            collection,
+            object,
+            is_writable,
+            sync_token: format_sync_token(&self.get_state(account_id, collection.item()).await?),
+        }))
+    }
+
+    pub(crate) async fn dav_item_ids(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        collection: DavCollection,
+        parent_id: u32,
+        time_range: Option<(Option<i64>, Option<i64>)>,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut filters = vec![query::Filter::eq(collection.parent_property(), parent_id)];
+        if let (Some((start, end)), DavCollection::Calendar) = (time_range, collection) {
+            if let Some(start) = start {
+                filters.push(query::Filter::gt(
+                    Property::ToDate,
+                    std::cmp::max(start, 0) as u64,
+                ));
+            }
+            if let Some(end) = end {
+                filters.push(query::Filter::lt(
+                    Property::FromDate,
+                    std::cmp::max(end, 0) as u64,
+                ));
+            }
+        }
+        let mut item_ids = self
+            .filter(account_id, collection.item(), filters)
+            .await?
+            .results;
+        if access_token.is_shared(account_id) {
+            item_ids &= match collection {
+                DavCollection::Calendar => {
+                    self.shared_events(access_token, account_id, Acl::ReadItems)
+                        .await?
+                }
+                DavCollection::AddressBook => {
+                    self.shared_contacts(access_token, account_id, Acl::ReadItems)
+                        .await?
+                }
+            };
+        }
+        Ok(item_ids)
+    }
+
+    pub(crate) async fn dav_item(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+        collection: DavCollection,
+        parent_id: u32,
+        uid: &str,
+    ) -> Result<Option<(u32, HashedValue<Object<Value>>)>, MethodError> {
+        let mut item_ids = self
+            .filter(
+                account_id,
+                collection.item(),
+                vec![query::Filter::eq(Property::Uid, uid.to_string())],
+            )
+            .await?
+            .results;
+        if !item_ids.is_empty() {
+            item_ids &= self
+                .dav_item_ids(access_token, account_id, collection, parent_id, None)
+                .await?;
+        }
+        for document_id in item_ids {
+            if let Some(object) = self
+                .get_property::<HashedValue<Object<Value>>>(
+                    account_id,
+                    collection.item(),
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                return Ok(Some((document_id, object)));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn dav_items(
+        &self,
+        account_id: u32,
+        account_name: &str,
+        collection: DavCollection,
+        parent_id: u32,
+        item_ids: RoaringBitmap,
+        entries: &mut Vec<(String, DavEntry)>,
+    ) -> Result<(), MethodError> {
+        for object in self
+            .get_properties::<HashedValue<Object<Value>>>(
+                account_id,
+                collection.item(),
+                item_ids.iter(),
+                Property::Value,
+            )
+            .await?
+            .into_iter()
+            .flatten()
+        {
+            if let Some(uid) = object.inner.get(&Property::Uid).as_string() {
+                entries.push((
+                    item_href(collection, account_name, parent_id, uid),
+                    DavEntry::Item { collection, object },
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DavEntry {
+    fn build_response(
+        &self,
+        href: String,
+        properties: &DavProperties,
+        current_principal: &str,
+    ) -> DavResponseItem {
+        let mut response = DavResponseItem {
+            href,
+            status: None,
+            found: vec![],
+            not_found: vec![],
+        };
+        match properties {
+            DavProperties::All | DavProperties::Names => {
+                for property in ALL_PROPERTIES {
+                    if let Some(value) = self.value(property, current_principal) {
+                        response.found.push((
+                            property.clone(),
+                            if properties == &DavProperties::All {
+                                value
+                            } else {
+                                String::new()
+                            },
+                        ));
+                    }
+                }
+            }
+            DavProperties::Some(properties) => {
+                for property in properties {
+                    if let Some(value) = self.value(property, current_principal) {
+                        response.found.push((property.clone(), value));
+                    } else {
+                        response.not_found.push(property.clone());
+                    }
+                }
+            }
+        }
+        response
+    }
+
+    fn value(&self, property: &DavProperty, current_principal: &str) -> Option<String> {
+        match (property, self) {
+            (DavProperty::ResourceType, entry) => Some(
+                match entry {
+                    DavEntry::Root | DavEntry::Home { .. } => "<D:collection/>",
+                    DavEntry::Principal { .. } => "<D:principal/>",
+                    DavEntry::Collection {
+                        collection: DavCollection::Calendar,
+                        ..
+                    } => "<D:collection/><C:calendar/>",
+                    DavEntry::Collection {
+                        collection: DavCollection::AddressBook,
+                        ..
+                    } => "<D:collection/><CR:addressbook/>",
+                    DavEntry::Item { .. } => "",
+                }
+                .to_string(),
+            ),
+            (DavProperty::DisplayName, DavEntry::Principal { name, .. }) => Some(escape_xml(name)),
+            (DavProperty::DisplayName, DavEntry::Collection { object, .. }) => {
+                object.get(&Property::Name).as_string().map(escape_xml)
+            }
+            (
+                DavProperty::CalendarDescription,
+                DavEntry::Collection {
+                    collection: DavCollection::Calendar,
+                    object,
+                    ..
+                },
+            )
+            | (
+                DavProperty::AddressbookDescription,
+                DavEntry::Collection {
+                    collection: DavCollection::AddressBook,
+                    object,
+                    ..
+                },
+            ) => object
+                .get(&Property::Description)
+                .as_string()
+                .map(escape_xml),
+            (
+                DavProperty::CalendarColor,
+                DavEntry::Collection {
+                    collection: DavCollection::Calendar,
+                    object,
+                    ..
+                },
+            ) => object.get(&Property::Color).as_string().map(escape_xml),
+            (
+                DavProperty::SupportedCalendarComponentSet,
+                DavEntry::Collection {
+                    collection: DavCollection::Calendar,
+                    ..
+                },
+            ) => Some("<C:comp name=\"VEVENT\"/>".to_string()),
+            (
+                DavProperty::GetCTag | DavProperty::SyncToken,
+                DavEntry::Collection { sync_token, .. },
+            ) => Some(escape_xml(sync_token)),
+            (DavProperty::SupportedReportSet, DavEntry::Collection { collection, .. }) => Some(
+                match collection {
+                    DavCollection::Calendar => ["C:calendar-query", "C:calendar-multiget"],
+                    DavCollection::AddressBook => {
+                        ["CR:addressbook-query", "CR:addressbook-multiget"]
+                    }
+                }
+                .iter()
+                .chain(["D:sync-collection"].iter())
+                .map(|report| {
+                    format!(
+                        "<D:supported-report><D:report><{report}/></D:report></D:supported-report>"
+                    )
+                })
+                .collect(),
+            ),
+            (DavProperty::CurrentUserPrivilegeSet, DavEntry::Collection { is_writable, .. }) => {
+                Some(
+                    if *is_writable {
+                        &["read", "write", "write-content", "bind", "unbind"][..]
+                    } else {
+                        &["read"][..]
+                    }
+                    .iter()
+                    .map(|privilege| format!("<D:privilege><D:{privilege}/></D:privilege>"))
+                    .collect(),
+                )
+            }
+            (
+                DavProperty::Owner,
+                DavEntry::Home { account_name } | DavEntry::Collection { account_name, .. },
+            ) => Some(href(&principal_href(account_name))),
+            (DavProperty::CurrentUserPrincipal, _) => Some(href(current_principal)),
+            (DavProperty::PrincipalUrl, DavEntry::Principal { name, .. }) => {
+                Some(href(&principal_href(name)))
+            }
+            (DavProperty::CalendarHomeSet, DavEntry::Principal { calendar_homes, .. }) => {
+                Some(calendar_homes.iter().map(|home| href(home)).collect())
+            }
+            (
+                DavProperty::AddressbookHomeSet,
+                DavEntry::Principal {
+                    addressbook_homes, ..
+                },
+            ) => Some(addressbook_homes.iter().map(|home| href(home)).collect()),
+            (DavProperty::CalendarUserAddressSet, DavEntry::Principal { emails, .. }) => Some(
+                emails
+                    .iter()
+                    .map(|email| href(&format!("mailto:{email}")))
+                    .collect(),
+            ),
+            (DavProperty::GetETag, DavEntry::Item { object, .. }) => {
+                Some(escape_xml(&etag(object)))
+            }
+            (DavProperty::GetContentType, DavEntry::Item { collection, .. }) => {
+                Some(collection.content_type().to_string())
+            }
+            (
+                DavProperty::CalendarData,
+                DavEntry::Item {
+                    collection: DavCollection::Calendar,
+                    object,
+                },
+            ) => Some(escape_xml(&build_icalendar(&object.inner))),
+            (
+                DavProperty::AddressData,
+                DavEntry::Item {
+                    collection: DavCollection::AddressBook,
+                    object,
+                },
+            ) => Some(escape_xml(&build_vcard(&object.inner))),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn etag(object: &HashedValue<Object<Value>>) -> String {
+    format!("\"{:x}\"", object.hash)
+}
+
+pub(crate) fn build_icalendar(event: &Object<Value>) -> String {
+    // Use a stable timestamp so that the same version always produces the same output
+    let dt_stamp = event
+        .get(&Property::parse("updated"))
+        .as_string()
+        .and_then(|updated| chrono::DateTime::parse_from_rfc3339(updated).ok())
+        .map_or(0, |updated| updated.timestamp());
+    ICalendar::build(None, event, dt_stamp)
+}
+
+fn format_sync_token(state: &State) -> String {
+    match state {
+        State::Exact(change_id) => format!("{SYNC_TOKEN_PREFIX}{change_id}"),
+        _ => format!("{SYNC_TOKEN_PREFIX}initial"),
+    }
+}
+
+fn parse_sync_token(token: &str) -> Option<Option<u64>> {
+    match token.strip_prefix(SYNC_TOKEN_PREFIX)? {
+        "initial" => Some(None),
+        change_id => change_id.parse().ok().map(Some),
+    }
+}
+
+fn invalid_sync_token() -> DavResponse {
+    DavResponse::new(StatusCode::FORBIDDEN).with_body(
+        "application/xml; charset=utf-8",
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+                "<D:error xmlns:D=\"{}\"><D:valid-sync-token/></D:error>\n"
+            ),
+            NS_DAV
+        ),
+    )
+}
+
+fn href_path(href: &str) -> &str {
+    // Hrefs may be absolute URLs
+    if let Some((_, url)) = href.split_once("://") {
+        url.find('/').map_or("/", |pos| &url[pos..])
+    } else {
+        href
+    }
+}
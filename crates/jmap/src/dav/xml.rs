@@ -0,0 +1,400 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Write;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    name::ResolveResult,
+    NsReader,
+};
+
+pub const NS_DAV: &str = "DAV:";
+pub const NS_CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+pub const NS_CARDDAV: &str = "urn:ietf:params:xml:ns:carddav";
+pub const NS_CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+pub const NS_APPLE: &str = "http://apple.com/ns/ical/";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavProperty {
+    ResourceType,
+    DisplayName,
+    GetETag,
+    GetContentType,
+    GetCTag,
+    SyncToken,
+    CurrentUserPrincipal,
+    PrincipalUrl,
+    Owner,
+    SupportedReportSet,
+    CurrentUserPrivilegeSet,
+    CalendarHomeSet,
+    CalendarUserAddressSet,
+    CalendarDescription,
+    SupportedCalendarComponentSet,
+    CalendarData,
+    CalendarColor,
+    AddressbookHomeSet,
+    AddressbookDescription,
+    AddressData,
+    Other { namespace: String, name: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DavRequestType {
+    PropFind,
+    CalendarQuery,
+    CalendarMultiget,
+    AddressbookQuery,
+    AddressbookMultiget,
+    SyncCollection,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavProperties {
+    All,
+    Names,
+    Some(Vec<DavProperty>),
+}
+
+#[derive(Debug)]
+pub struct DavRequest {
+    pub request_type: DavRequestType,
+    pub properties: DavProperties,
+    pub hrefs: Vec<String>,
+    pub time_range: Option<(Option<i64>, Option<i64>)>,
+    pub sync_token: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct MultiStatus {
+    pub responses: Vec<DavResponseItem>,
+    pub sync_token: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct DavResponseItem {
+    pub href: String,
+    pub status: Option<u16>,
+    pub found: Vec<(DavProperty, String)>,
+    pub not_found: Vec<DavProperty>,
+}
+
+impl DavProperty {
+    pub fn parse(namespace: &str, name: &str) -> Self {
+        match (namespace, name) {
+            (NS_DAV, "resourcetype") => DavProperty::ResourceType,
+            (NS_DAV, "displayname") => DavProperty::DisplayName,
+            (NS_DAV, "getetag") => DavProperty::GetETag,
+            (NS_DAV, "getcontenttype") => DavProperty::GetContentType,
+            (NS_DAV, "sync-token") => DavProperty::SyncToken,
+            (NS_DAV, "current-user-principal") => DavProperty::CurrentUserPrincipal,
+            (NS_DAV, "principal-URL") => DavProperty::PrincipalUrl,
+            (NS_DAV, "owner") => DavProperty::Owner,
+            (NS_DAV, "supported-report-set") => DavProperty::SupportedReportSet,
+            (NS_DAV, "current-user-privilege-set") => DavProperty::CurrentUserPrivilegeSet,
+            (NS_CALENDARSERVER, "getctag") => DavProperty::GetCTag,
+            (NS_CALDAV, "calendar-home-set") => DavProperty::CalendarHomeSet,
+            (NS_CALDAV, "calendar-user-address-set") => DavProperty::CalendarUserAddressSet,
+            (NS_CALDAV, "calendar-description") => DavProperty::CalendarDescription,
+            (NS_CALDAV, "supported-calendar-component-set") => {
+                DavProperty::SupportedCalendarComponentSet
+            }
+            (NS_CALDAV, "calendar-data") => DavProperty::CalendarData,
+            (NS_APPLE, "calendar-color") => DavProperty::CalendarColor,
+            (NS_CARDDAV, "addressbook-home-set") => DavProperty::AddressbookHomeSet,
+            (NS_CARDDAV, "addressbook-description") => DavProperty::AddressbookDescription,
+            (NS_CARDDAV, "address-data") => DavProperty::AddressData,
+            _ => DavProperty::Other {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            },
+        }
+    }
+
+    fn tag(&self) -> &str {
+        match self {
+            DavProperty::ResourceType => "D:resourcetype",
+            DavProperty::DisplayName => "D:displayname",
+            DavProperty::GetETag => "D:getetag",
+            DavProperty::GetContentType => "D:getcontenttype",
+            DavProperty::GetCTag => "CS:getctag",
+            DavProperty::SyncToken => "D:sync-token",
+            DavProperty::CurrentUserPrincipal => "D:current-user-principal",
+            DavProperty::PrincipalUrl => "D:principal-URL",
+            DavProperty::Owner => "D:owner",
+            DavProperty::SupportedReportSet => "D:supported-report-set",
+            DavProperty::CurrentUserPrivilegeSet => "D:current-user-privilege-set",
+            DavProperty::CalendarHomeSet => "C:calendar-home-set",
+            DavProperty::CalendarUserAddressSet => "C:calendar-user-address-set",
+            DavProperty::CalendarDescription => "C:calendar-description",
+            DavProperty::SupportedCalendarComponentSet => "C:supported-calendar-component-set",
+            DavProperty::CalendarData => "C:calendar-data",
+            DavProperty::CalendarColor => "A:calendar-color",
+            DavProperty::AddressbookHomeSet => "CR:addressbook-home-set",
+            DavProperty::AddressbookDescription => "CR:addressbook-description",
+            DavProperty::AddressData => "CR:address-data",
+            DavProperty::Other { name, .. } => name,
+        }
+    }
+
+    fn write(&self, xml: &mut String, value: Option<&str>) {
+        let tag = self.tag();
+        let _ = if let DavProperty::Other { namespace, .. } = self {
+            write!(xml, "<X:{tag} xmlns:X=\"{}\"", escape_xml(namespace))
+        } else {
+            write!(xml, "<{tag}")
+        };
+        match value {
+            Some(value) if !value.is_empty() => {
+                let _ = if matches!(self, DavProperty::Other { .. }) {
+                    write!(xml, ">{value}</X:{tag}>")
+                } else {
+                    write!(xml, ">{value}</{tag}>")
+                };
+            }
+            _ => xml.push_str("/>"),
+        }
+    }
+}
+
+impl DavRequest {
+    pub fn parse(bytes: &[u8], is_report: bool) -> Option<Self> {
+        let mut request = DavRequest {
+            request_type: DavRequestType::PropFind,
+            properties: DavProperties::All,
+            hrefs: Vec::new(),
+            time_range: None,
+            sync_token: None,
+        };
+
+        // An empty PROPFIND body is equivalent to an allprop request
+        if bytes.iter().all(|ch| ch.is_ascii_whitespace()) {
+            return if !is_report { Some(request) } else { None };
+        }
+
+        let mut reader = NsReader::from_reader(bytes);
+        let mut path: Vec<(String, String)> = Vec::new();
+        let mut properties = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            let (namespace, event) = reader.read_resolved_event().ok()?;
+            let namespace = match namespace {
+                ResolveResult::Bound(namespace) => {
+                    String::from_utf8_lossy(namespace.as_ref()).into_owned()
+                }
+                _ => String::new(),
+            };
+            match event {
+                Event::Start(element) => {
+                    let name = local_name(&element);
+                    request.start_element(&path, &namespace, &name, &element, &mut properties)?;
+                    path.push((namespace, name));
+                    text.clear();
+                }
+                Event::Empty(element) => {
+                    let name = local_name(&element);
+                    request.start_element(&path, &namespace, &name, &element, &mut properties)?;
+                }
+                Event::Text(value) => {
+                    text.push_str(value.unescape().ok()?.as_ref());
+                }
+                Event::End(_) => {
+                    if let Some((namespace, name)) = path.pop() {
+                        match (namespace.as_str(), name.as_str()) {
+                            (NS_DAV, "href") => {
+                                request.hrefs.push(text.trim().to_string());
+                            }
+                            (NS_DAV, "sync-token") if path.len() == 1 => {
+                                request.sync_token = Some(text.trim().to_string());
+                            }
+                            _ => (),
+                        }
+                    }
+                    text.clear();
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        if !properties.is_empty() {
+            request.properties = DavProperties::Some(properties);
+        }
+        if is_report == (request.request_type != DavRequestType::PropFind) {
+            Some(request)
+        } else {
+            None
+        }
+    }
+
+    fn start_element(
+        &mut self,
+        path: &[(String, String)],
+        namespace: &str,
+        name: &str,
+        element: &BytesStart<'_>,
+        properties: &mut Vec<DavProperty>,
+    ) -> Option<()> {
+        match path.len() {
+            0 => {
+                self.request_type = match (namespace, name) {
+                    (NS_DAV, "propfind") => DavRequestType::PropFind,
+                    (NS_DAV, "sync-collection") => DavRequestType::SyncCollection,
+                    (NS_CALDAV, "calendar-query") => DavRequestType::CalendarQuery,
+                    (NS_CALDAV, "calendar-multiget") => DavRequestType::CalendarMultiget,
+                    (NS_CARDDAV, "addressbook-query") => DavRequestType::AddressbookQuery,
+                    (NS_CARDDAV, "addressbook-multiget") => DavRequestType::AddressbookMultiget,
+                    _ => return None,
+                };
+            }
+            1 => match (namespace, name) {
+                (NS_DAV, "allprop") => {
+                    self.properties = DavProperties::All;
+                }
+                (NS_DAV, "propname") => {
+                    self.properties = DavProperties::Names;
+                }
+                _ => (),
+            },
+            2 if path[1].0 == NS_DAV && path[1].1 == "prop" => {
+                properties.push(DavProperty::parse(namespace, name));
+            }
+            _ => {
+                if namespace == NS_CALDAV && name == "time-range" {
+                    let attribute = |name: &str| {
+                        element
+                            .try_get_attribute(name)
+                            .ok()
+                            .flatten()
+                            .and_then(|value| value.unescape_value().ok().map(|v| v.into_owned()))
+                            .and_then(|value| {
+                                chrono::NaiveDateTime::parse_from_str(&value, "%Y%m%dT%H%M%SZ").ok()
+                            })
+                            .map(|date| date.timestamp())
+                    };
+                    self.time_range = Some((attribute("start"), attribute("end")));
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+impl MultiStatus {
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::with_capacity(1024);
+        let _ = write!(
+            xml,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+                "<D:multistatus xmlns:D=\"{}\" xmlns:C=\"{}\" xmlns:CR=\"{}\" ",
+                "xmlns:CS=\"{}\" xmlns:A=\"{}\">"
+            ),
+            NS_DAV, NS_CALDAV, NS_CARDDAV, NS_CALENDARSERVER, NS_APPLE
+        );
+        for response in &self.responses {
+            let _ = write!(
+                xml,
+                "<D:response><D:href>{}</D:href>",
+                escape_xml(&response.href)
+            );
+            if let Some(status) = response.status {
+                write_status(&mut xml, status);
+            }
+            for (properties, status) in [
+                (
+                    response
+                        .found
+                        .iter()
+                        .map(|(property, value)| (property, Some(value.as_str())))
+                        .collect::<Vec<_>>(),
+                    200,
+                ),
+                (
+                    response
+                        .not_found
+                        .iter()
+                        .map(|property| (property, None))
+                        .collect::<Vec<_>>(),
+                    404,
+                ),
+            ] {
+                if !properties.is_empty() {
+                    xml.push_str("<D:propstat><D:prop>");
+                    for (property, value) in properties {
+                        property.write(&mut xml, value);
+                    }
+                    xml.push_str("</D:prop>");
+                    write_status(&mut xml, status);
+                    xml.push_str("</D:propstat>");
+                }
+            }
+            xml.push_str("</D:response>");
+        }
+        if let Some(sync_token) = &self.sync_token {
+            let _ = write!(
+                xml,
+                "<D:sync-token>{}</D:sync-token>",
+                escape_xml(sync_token)
+            );
+        }
+        xml.push_str("</D:multistatus>\n");
+        xml
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+pub fn href(value: &str) -> String {
+    format!("<D:href>{}</D:href>", escape_xml(value))
+}
+
+fn write_status(xml: &mut String, status: u16) {
+    let _ = write!(
+        xml,
+        "<D:status>HTTP/1.1 {status} {}</D:status>",
+        hyper::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or_default()
+    );
+}
+
+fn local_name(element: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
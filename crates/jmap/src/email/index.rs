@@ -40,6 +40,7 @@ use mail_parser::{
 use store::{
     fts::{
         builder::{FtsIndexBuilder, MAX_TOKEN_LENGTH},
+        extract::{extract_text, DocumentType},
         Language,
     },
     write::{BatchBuilder, IntoOperations, F_BITMAP, F_CLEAR, F_INDEX, F_VALUE},
@@ -52,6 +53,8 @@ pub const MAX_ID_LENGTH: usize = 100;
 pub const MAX_SORT_FIELD_LENGTH: usize = 255;
 pub const MAX_STORED_FIELD_LENGTH: usize = 512;
 pub const PREVIEW_LENGTH: usize = 256;
+// Decompression budget for attachments when their size is not limited
+pub const MAX_EXTRACTED_SIZE: usize = 50_000_000;
 
 pub struct SortedAddressBuilder {
     last_is_space: bool,
@@ -66,6 +69,7 @@ pub(super) trait IndexMessage {
         mailbox_ids: Vec<u32>,
         received_at: u64,
         default_language: Language,
+        max_attachment_size: usize,
     ) -> store::Result<&mut Self>;
 }
 
@@ -77,6 +81,7 @@ impl IndexMessage for BatchBuilder {
         mailbox_ids: Vec<u32>,
         received_at: u64,
         default_language: Language,
+        max_attachment_size: usize,
     ) -> store::Result<&mut Self> {
         let mut metadata = Object::with_capacity(15);
 
@@ -304,6 +309,33 @@ impl IndexMessage for BatchBuilder {
                 self.value(Property::Subject, "!", F_INDEX);
             }
 
+            // Extract the text of attached documents and archives
+            if let PartType::Binary(bytes) | PartType::InlineBinary(bytes) = &part.body {
+                if max_attachment_size == 0 || bytes.len() <= max_attachment_size {
+                    let content_type = part.content_type().map(|ct| {
+                        ct.subtype()
+                            .map(|st| format!("{}/{}", ct.ctype(), st))
+                            .unwrap_or_else(|| ct.ctype().to_string())
+                    });
+                    if let Some(text) =
+                        DocumentType::detect(content_type.as_deref(), part.attachment_name())
+                            .and_then(|typ| {
+                                extract_text(
+                                    bytes,
+                                    typ,
+                                    if max_attachment_size > 0 {
+                                        max_attachment_size
+                                    } else {
+                                        MAX_EXTRACTED_SIZE
+                                    },
+                                )
+                            })
+                    {
+                        fts.index(Property::Attachments, text, part_language);
+                    }
+                }
+            }
+
             match part.body {
                 PartType::Text(text) => {
                     if part_id == preview_part_id {
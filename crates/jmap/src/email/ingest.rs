@@ -83,6 +83,16 @@ impl JMAP {
         {
             return Err(IngestError::OverQuota);
         }
+        if self.config.mail_max_messages > 0
+            && self
+                .get_document_ids(params.account_id, Collection::Email)
+                .await
+                .map_err(|_| IngestError::Temporary)?
+                .map_or(0, |ids| ids.len())
+                >= self.config.mail_max_messages
+        {
+            return Err(IngestError::OverQuota);
+        }
 
         // Parse message
         let raw_message = params.raw_message;
@@ -252,6 +262,7 @@ impl JMAP {
                 params.mailbox_ids,
                 params.received_at.unwrap_or_else(now),
                 self.config.default_language,
+                self.config.mail_attachments_max_size,
             )
             .map_err(|err| {
                 tracing::error!(
@@ -32,3 +32,4 @@ pub mod parse;
 pub mod query;
 pub mod set;
 pub mod snippet;
+pub mod spam;
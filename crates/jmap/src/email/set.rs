@@ -61,7 +61,7 @@ use store::{
     BlobKind, Serialize, ValueKey,
 };
 
-use crate::{auth::AccessToken, IngestError, JMAP};
+use crate::{auth::AccessToken, mailbox::TRASH_ID, IngestError, JMAP};
 
 use super::{
     headers::{BuildHeader, ValueToHeader},
@@ -756,6 +756,11 @@ impl JMAP {
 
         // Process updates
         let mut changes = ChangeLogBuilder::new();
+        let junk_mailbox_id = if self.config.spam_enable {
+            self.mailbox_get_by_role(account_id, "junk").await?
+        } else {
+            None
+        };
         'update: for (id, object) in request.unwrap_update() {
             // Make sure id won't be destroyed
             if will_destroy.contains(&id) {
@@ -850,6 +855,7 @@ impl JMAP {
             // Log change
             batch.update_document(document_id);
             let mut changed_mailboxes = AHashSet::new();
+            let mut spam_train = None;
             changes.log_update(Collection::Email, id);
 
             // Process keywords
@@ -942,6 +948,17 @@ impl JMAP {
                     }
                 }
 
+                // Train the spam classifier when messages are moved to or from Junk
+                if let Some(junk_id) = junk_mailbox_id {
+                    if mailboxes.added().contains(&junk_id) {
+                        spam_train = Some(true);
+                    } else if mailboxes.removed().contains(&junk_id)
+                        && !mailboxes.current().contains(&TRASH_ID)
+                    {
+                        spam_train = Some(false);
+                    }
+                }
+
                 // Update mailboxIds property
                 mailboxes.update_batch(&mut batch, Property::MailboxIds);
             }
@@ -957,6 +974,11 @@ impl JMAP {
                     Ok(_) => {
                         // Add to updated list
                         response.updated.append(id, None);
+
+                        if let Some(is_spam) = spam_train {
+                            self.spam_train_email(account_id, document_id, is_spam)
+                                .await;
+                        }
                     }
                     Err(store::Error::AssertValueFailed) => {
                         response.not_updated.append(
@@ -1060,6 +1082,10 @@ impl JMAP {
         // Remove last changeId
         batch.value(Property::Cid, (), F_VALUE | F_CLEAR);
 
+        // Reset the spam training state, document ids are reused
+        self.spam_clear_trained(&mut batch, account_id, document_id)
+            .await?;
+
         // Remove mailboxes
         let mailboxes = if let Some(mailboxes) = self
             .get_property::<HashedValue<Vec<u32>>>(
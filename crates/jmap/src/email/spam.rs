@@ -0,0 +1,402 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{error::method::MethodError, types::collection::Collection};
+use mail_parser::{
+    decoders::html::html_to_text, HeaderName, HeaderValue, Message, PartType, RfcHeader,
+};
+use store::{
+    ahash::AHashSet,
+    fts::{lang::LanguageDetector, tokenizers::Tokenizer, Language},
+    write::BatchBuilder,
+    BlobKind, CounterKey,
+};
+use utils::ipc::SpamClassification;
+
+use crate::JMAP;
+
+// Token counts shared by all accounts are stored under this account id
+pub const SPAM_GLOBAL_ACCOUNT_ID: u32 = u32::MAX;
+
+const TOKEN_SPAM: u8 = 0;
+const TOKEN_HAM: u8 = 1;
+const TOTAL_SPAM: u8 = 2;
+const TOTAL_HAM: u8 = 3;
+const TRAINED: u8 = 4;
+
+// Per-message training state
+const TRAINED_SPAM: i64 = 1;
+const TRAINED_HAM: i64 = -1;
+
+const MAX_TOKEN_LENGTH: usize = 40;
+const MAX_DISCRIMINATORS: usize = 150;
+const MIN_PROBABILITY: f64 = 0.01;
+const MAX_PROBABILITY: f64 = 0.99;
+
+impl JMAP {
+    pub async fn spam_classify(
+        &self,
+        account_id: Option<u32>,
+        raw_message: &[u8],
+    ) -> Result<Option<SpamClassification>, MethodError> {
+        if !self.config.spam_enable {
+            return Ok(None);
+        }
+
+        // Use the account's token counts when it has been trained enough,
+        // otherwise fall back to the global counts.
+        let mut totals = None;
+        for account_id in account_id
+            .into_iter()
+            .chain(std::iter::once(SPAM_GLOBAL_ACCOUNT_ID))
+        {
+            let counts = self
+                .spam_get_counters(
+                    account_id,
+                    vec![spam_key(TOTAL_SPAM, ""), spam_key(TOTAL_HAM, "")],
+                )
+                .await?;
+            if counts
+                .iter()
+                .all(|&count| count >= self.config.spam_min_learns)
+            {
+                totals = Some((account_id, counts[0] as f64, counts[1] as f64));
+                break;
+            }
+        }
+        let (account_id, total_spam, total_ham) = if let Some(totals) = totals {
+            totals
+        } else {
+            return Ok(None);
+        };
+
+        // Obtain token counts
+        let tokens = if let Some(message) = Message::parse(raw_message) {
+            self.spam_tokenize(&message)
+        } else {
+            return Ok(None);
+        };
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let counts = self
+            .spam_get_counters(
+                account_id,
+                tokens
+                    .iter()
+                    .flat_map(|token| [spam_key(TOKEN_SPAM, token), spam_key(TOKEN_HAM, token)])
+                    .collect(),
+            )
+            .await?;
+
+        // Calculate the Robinson probability of each token
+        let mut probabilities = counts
+            .chunks_exact(2)
+            .filter_map(|counts| {
+                let (spam, ham) = (counts[0].max(0) as f64, counts[1].max(0) as f64);
+                let total = spam + ham;
+                if total > 0.0 {
+                    let spam_ratio = spam / total_spam;
+                    let ham_ratio = ham / total_ham;
+                    let p = spam_ratio / (spam_ratio + ham_ratio);
+                    Some(
+                        ((0.5 + total * p) / (1.0 + total)).clamp(MIN_PROBABILITY, MAX_PROBABILITY),
+                    )
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if probabilities.is_empty() {
+            return Ok(None);
+        }
+
+        // Keep only the most significant tokens
+        probabilities.sort_unstable_by(|a, b| {
+            (b - 0.5)
+                .abs()
+                .partial_cmp(&(a - 0.5).abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        probabilities.truncate(MAX_DISCRIMINATORS);
+
+        // Combine probabilities using Fisher's method
+        let n = probabilities.len() * 2;
+        let spamminess = 1.0
+            - chi2q(
+                -2.0 * probabilities.iter().map(|p| (1.0 - p).ln()).sum::<f64>(),
+                n,
+            );
+        let hamminess = 1.0 - chi2q(-2.0 * probabilities.iter().map(|p| p.ln()).sum::<f64>(), n);
+        let score = (spamminess - hamminess + 1.0) / 2.0;
+
+        Ok(Some(SpamClassification {
+            score,
+            is_spam: score >= self.config.spam_threshold,
+        }))
+    }
+
+    pub async fn spam_classify_delivery(
+        &self,
+        raw_message: &[u8],
+        recipients: &[String],
+    ) -> Option<SpamClassification> {
+        // Per-account counts are only used when there is a single local recipient
+        let mut account_id = None;
+        if let [rcpt] = recipients {
+            if let [name] = self
+                .directory
+                .names_by_email(rcpt)
+                .await
+                .unwrap_or_default()
+                .as_slice()
+            {
+                account_id = self.get_account_id(name).await.ok();
+            }
+        }
+
+        self.spam_classify(account_id, raw_message).await.ok()?
+    }
+
+    pub async fn spam_train(&self, account_id: u32, raw_message: &[u8], is_spam: bool) {
+        if !self.config.spam_enable {
+            return;
+        }
+        let tokens = Message::parse(raw_message)
+            .map(|message| self.spam_tokenize(&message))
+            .unwrap_or_default();
+        if tokens.is_empty() {
+            return;
+        }
+
+        let mut batch = BatchBuilder::new();
+        spam_update_counts(&mut batch, account_id, &tokens, is_spam, 1);
+        self.spam_write(account_id, batch).await;
+    }
+
+    pub async fn spam_train_email(&self, account_id: u32, document_id: u32, is_spam: bool) {
+        if !self.config.spam_enable {
+            return;
+        }
+
+        // Skip messages already trained as the requested class
+        let trained_state = if let Ok(counts) = self
+            .spam_get_counters(account_id, vec![spam_trained_key(document_id)])
+            .await
+        {
+            counts[0]
+        } else {
+            return;
+        };
+        let new_state = if is_spam { TRAINED_SPAM } else { TRAINED_HAM };
+        if trained_state == new_state {
+            return;
+        }
+
+        let tokens = if let Ok(Some(raw_message)) = self
+            .get_blob(
+                &BlobKind::LinkedMaildir {
+                    account_id,
+                    document_id,
+                },
+                0..u32::MAX,
+            )
+            .await
+        {
+            Message::parse(&raw_message)
+                .map(|message| self.spam_tokenize(&message))
+                .unwrap_or_default()
+        } else {
+            return;
+        };
+        if tokens.is_empty() {
+            return;
+        }
+
+        // Untrain the previous class before training the new one
+        let mut batch = BatchBuilder::new();
+        if trained_state != 0 {
+            spam_update_counts(&mut batch, account_id, &tokens, !is_spam, -1);
+        }
+        spam_update_counts(&mut batch, account_id, &tokens, is_spam, 1);
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .counter(spam_trained_key(document_id), new_state - trained_state);
+        self.spam_write(account_id, batch).await;
+    }
+
+    pub async fn spam_clear_trained(
+        &self,
+        batch: &mut BatchBuilder,
+        account_id: u32,
+        document_id: u32,
+    ) -> Result<(), MethodError> {
+        let trained_state = self
+            .spam_get_counters(account_id, vec![spam_trained_key(document_id)])
+            .await?[0];
+        if trained_state != 0 {
+            batch.counter(spam_trained_key(document_id), -trained_state);
+        }
+        Ok(())
+    }
+
+    async fn spam_write(&self, account_id: u32, batch: BatchBuilder) {
+        if let Err(err) = self.store.write(batch.build()).await {
+            tracing::error!(
+                event = "error",
+                context = "spam_train",
+                account_id = account_id,
+                error = ?err,
+                "Failed to write spam token counts.");
+        }
+    }
+
+    pub fn spam_tokenize(&self, message: &Message) -> AHashSet<String> {
+        let mut tokens = AHashSet::new();
+        let mut add_tokens = |text: &str| {
+            let language = LanguageDetector::detect_single(text)
+                .map_or(self.config.default_language, |(language, _)| language);
+            for token in Tokenizer::new(text, language, MAX_TOKEN_LENGTH) {
+                if tokens.len() >= self.config.spam_max_tokens {
+                    break;
+                }
+                tokens.insert(token.word.into_owned());
+            }
+        };
+
+        if let Some(part) = message.parts.first() {
+            for header in &part.headers {
+                if let (HeaderName::Rfc(RfcHeader::Subject), HeaderValue::Text(subject)) =
+                    (&header.name, &header.value)
+                {
+                    add_tokens(subject.as_ref());
+                }
+            }
+        }
+
+        for part in &message.parts {
+            match &part.body {
+                PartType::Text(text) => add_tokens(text.as_ref()),
+                PartType::Html(html) => add_tokens(&html_to_text(html.as_ref())),
+                _ => (),
+            }
+        }
+
+        tokens
+    }
+
+    async fn spam_get_counters(
+        &self,
+        account_id: u32,
+        keys: Vec<Vec<u8>>,
+    ) -> Result<Vec<i64>, MethodError> {
+        self.store
+            .get_counters(
+                keys.into_iter()
+                    .map(|key| CounterKey {
+                        account_id,
+                        collection: Collection::Email.into(),
+                        key,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "spam_classify",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to retrieve spam token counts.");
+                MethodError::ServerPartialFail
+            })
+    }
+}
+
+pub fn is_spam_message(message: &Message, raw_message: &[u8]) -> bool {
+    message.parts.first().map_or(false, |part| {
+        part.headers.iter().any(|header| {
+            header.name.as_str().eq_ignore_ascii_case("X-Spam-Status")
+                && raw_message
+                    .get(header.offset_start..header.offset_end)
+                    .and_then(|value| std::str::from_utf8(value).ok())
+                    .map_or(false, |value| {
+                        value
+                            .trim_start()
+                            .get(..3)
+                            .map_or(false, |value| value.eq_ignore_ascii_case("yes"))
+                    })
+        })
+    })
+}
+
+fn spam_update_counts(
+    batch: &mut BatchBuilder,
+    account_id: u32,
+    tokens: &AHashSet<String>,
+    is_spam: bool,
+    by: i64,
+) {
+    let (token_type, total_type) = if is_spam {
+        (TOKEN_SPAM, TOTAL_SPAM)
+    } else {
+        (TOKEN_HAM, TOTAL_HAM)
+    };
+
+    for account_id in [account_id, SPAM_GLOBAL_ACCOUNT_ID] {
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email)
+            .counter(spam_key(total_type, ""), by);
+        for token in tokens {
+            batch.counter(spam_key(token_type, token), by);
+        }
+    }
+}
+
+fn spam_trained_key(document_id: u32) -> Vec<u8> {
+    let mut key = Vec::with_capacity(std::mem::size_of::<u32>() + 1);
+    key.push(TRAINED);
+    key.extend_from_slice(&document_id.to_be_bytes());
+    key
+}
+
+fn spam_key(key_type: u8, token: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(token.len() + 1);
+    key.push(key_type);
+    key.extend_from_slice(token.as_bytes());
+    key
+}
+
+// Inverse chi-square probability for an even number of degrees of freedom
+fn chi2q(x2: f64, v: usize) -> f64 {
+    let m = x2 / 2.0;
+    let mut term = (-m).exp();
+    let mut sum = term;
+    for i in 1..(v / 2) {
+        term *= m / i as f64;
+        sum += term;
+    }
+    sum.min(1.0)
+}
@@ -62,15 +62,22 @@ use utils::{
     UnwrapFailure,
 };
 
+pub mod address_book;
 pub mod api;
 pub mod auth;
 pub mod blob;
+pub mod calendar;
+pub mod calendar_event;
 pub mod changes;
+pub mod contact;
+pub mod dav;
 pub mod email;
 pub mod identity;
 pub mod mailbox;
+pub mod mdn;
 pub mod principal;
 pub mod push;
+pub mod quota;
 pub mod services;
 pub mod sieve;
 pub mod submission;
@@ -81,7 +88,7 @@ pub mod websocket;
 pub const LONG_SLUMBER: Duration = Duration::from_secs(60 * 60 * 24);
 
 pub struct JMAP {
-    pub store: Store,
+    pub store: Arc<Store>,
     pub config: Config,
     pub directory: Arc<dyn Directory>,
 
@@ -115,6 +122,7 @@ pub struct Config {
 
     pub upload_max_size: usize,
     pub upload_max_concurrent: usize,
+    pub upload_max_data_sources: usize,
 
     pub upload_tmp_quota_size: usize,
     pub upload_tmp_quota_amount: usize,
@@ -125,6 +133,17 @@ pub struct Config {
     pub mail_attachments_max_size: usize,
     pub mail_parse_max_items: usize,
     pub mail_max_size: usize,
+    pub mail_max_messages: u64,
+
+    pub contacts_max_name_len: usize,
+    pub contacts_max_size: usize,
+    pub contacts_max_address_books: usize,
+
+    pub calendar_max_name_len: usize,
+    pub calendar_max_size: usize,
+    pub calendar_max_calendars: usize,
+    pub calendar_max_expansions: usize,
+    pub calendar_imip_enable: bool,
 
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
@@ -152,6 +171,14 @@ pub struct Config {
 
     pub principal_allow_lookups: bool,
 
+    pub spam_enable: bool,
+    pub spam_threshold: f64,
+    pub spam_min_learns: i64,
+    pub spam_max_tokens: usize,
+
+    pub metrics_enable: bool,
+    pub metrics_require_auth: bool,
+
     pub capabilities: BaseCapabilities,
 }
 
@@ -169,6 +196,7 @@ impl JMAP {
     pub async fn init(
         config: &utils::config::Config,
         directory_config: &DirectoryConfig,
+        store: Arc<Store>,
         delivery_rx: mpsc::Receiver<DeliveryEvent>,
         smtp: Arc<SMTP>,
     ) -> Result<Arc<Self>, String> {
@@ -189,7 +217,7 @@ impl JMAP {
                     config.value_require("jmap.directory")?
                 ))
                 .clone(),
-            store: Store::open(config).await.failed("Unable to open database"),
+            store,
             config: Config::new(config).failed("Invalid configuration file"),
             sessions: TtlDashMap::with_capacity(
                 config.property("jmap.session.cache.size")?.unwrap_or(100),
@@ -557,9 +585,9 @@ impl JMAP {
     ) -> Result<i64, MethodError> {
         Ok(if access_token.primary_id == account_id {
             access_token.quota as i64
-        } else {
+        } else if let Some(name) = self.get_account_name(account_id).await? {
             self.directory
-                .principal(&access_token.name)
+                .principal(&name)
                 .await
                 .map_err(|err| {
                     tracing::error!(
@@ -572,6 +600,8 @@ impl JMAP {
                 })?
                 .map(|p| p.quota as i64)
                 .unwrap_or_default()
+        } else {
+            0
         })
     }
 
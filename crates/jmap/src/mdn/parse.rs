@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::mdn::{Disposition, Mdn, MdnParseRequest, MdnParseResponse},
+    object::Object,
+    types::{acl::Acl, collection::Collection, id::Id, property::Property, value::Value},
+};
+use mail_parser::{Message, MimeHeaders, PartType};
+use store::query::Filter;
+use utils::map::vec_map::VecMap;
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    pub async fn mdn_parse(
+        &self,
+        request: MdnParseRequest,
+        access_token: &AccessToken,
+    ) -> Result<MdnParseResponse, MethodError> {
+        if request.blob_ids.len() > self.config.mail_parse_max_items {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = MdnParseResponse {
+            account_id: request.account_id,
+            parsed: VecMap::with_capacity(request.blob_ids.len()),
+            not_parsable: vec![],
+            not_found: vec![],
+        };
+
+        for blob_id in request.blob_ids {
+            // Fetch raw message to parse
+            let raw_message = match self.blob_download(&blob_id, access_token).await? {
+                Some(raw_message) => raw_message,
+                None => {
+                    response.not_found.push(blob_id);
+                    continue;
+                }
+            };
+            let mut mdn =
+                if let Some(mdn) = Message::parse(&raw_message).as_ref().and_then(parse_mdn) {
+                    mdn
+                } else {
+                    response.not_parsable.push(blob_id);
+                    continue;
+                };
+
+            // Link the MDN to the original message
+            if let Some(message_id) = &mdn.original_message_id {
+                mdn.for_email_id = self
+                    .find_email_by_message_id(
+                        account_id,
+                        message_id
+                            .trim()
+                            .trim_start_matches('<')
+                            .trim_end_matches('>'),
+                        access_token,
+                    )
+                    .await?;
+            }
+
+            response.parsed.append(blob_id, mdn);
+        }
+
+        Ok(response)
+    }
+
+    async fn find_email_by_message_id(
+        &self,
+        account_id: u32,
+        message_id: &str,
+        access_token: &AccessToken,
+    ) -> Result<Option<Id>, MethodError> {
+        if message_id.is_empty() {
+            return Ok(None);
+        }
+
+        // The index also contains In-Reply-To and References, so make sure
+        // the candidates have a matching Message-ID header.
+        let mut document_ids = self
+            .filter(
+                account_id,
+                Collection::Email,
+                vec![Filter::eq(Property::MessageId, message_id)],
+            )
+            .await?
+            .results;
+        if !access_token.is_member(account_id) {
+            document_ids &= self
+                .shared_messages(access_token, account_id, Acl::ReadItems)
+                .await?;
+        }
+
+        for document_id in document_ids {
+            let has_message_id = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::BodyStructure,
+                )
+                .await?
+                .map_or(false, |metadata| match metadata.get(&Property::MessageId) {
+                    Value::List(ids) => ids.iter().any(|id| id.as_string() == Some(message_id)),
+                    Value::Text(id) => id == message_id,
+                    _ => false,
+                });
+            if has_message_id {
+                if let Some(thread_id) = self
+                    .get_property::<u32>(
+                        account_id,
+                        Collection::Email,
+                        document_id,
+                        Property::ThreadId,
+                    )
+                    .await?
+                {
+                    return Ok(Some(Id::from_parts(thread_id, document_id)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn parse_mdn(message: &Message) -> Option<Mdn> {
+    let mut mdn = None;
+    let mut text_body = None;
+    let mut include_original_message = false;
+
+    for part in &message.parts {
+        if part.is_content_type("message", "disposition-notification") {
+            let fields = match &part.body {
+                PartType::Text(text) => text.as_bytes(),
+                PartType::Binary(bytes) | PartType::InlineBinary(bytes) => bytes.as_ref(),
+                _ => continue,
+            };
+            mdn = parse_disposition_fields(&String::from_utf8_lossy(fields));
+        } else if matches!(part.body, PartType::Message(_))
+            || part.is_content_type("text", "rfc822-headers")
+        {
+            include_original_message = true;
+        } else if text_body.is_none() && part.is_content_type("text", "plain") {
+            if let PartType::Text(text) = &part.body {
+                text_body = text.to_string().into();
+            }
+        }
+    }
+
+    mdn.map(|mut mdn| {
+        mdn.subject = message.subject().map(|subject| subject.to_string());
+        mdn.text_body = text_body;
+        mdn.include_original_message = include_original_message.into();
+        mdn
+    })
+}
+
+fn parse_disposition_fields(fields: &str) -> Option<Mdn> {
+    // Unfold fields
+    let mut values: Vec<(&str, String)> = Vec::new();
+    for line in fields.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = values.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            values.push((name.trim(), value.trim().to_string()));
+        }
+    }
+
+    let mut mdn = Mdn::default();
+    let mut extension_fields = VecMap::new();
+    for (name, value) in values {
+        match name.to_ascii_lowercase().as_str() {
+            "reporting-ua" => mdn.reporting_ua = value.into(),
+            "mdn-gateway" => mdn.mdn_gateway = value.into(),
+            "original-recipient" => mdn.original_recipient = value.into(),
+            "final-recipient" => mdn.final_recipient = value.into(),
+            "original-message-id" => mdn.original_message_id = value.into(),
+            "disposition" => mdn.disposition = Disposition::parse_field(&value),
+            "error" => mdn.error.get_or_insert_with(Vec::new).push(value),
+            _ => extension_fields.append(name.to_string(), value),
+        }
+    }
+    if !extension_fields.is_empty() {
+        mdn.extension_fields = extension_fields.into();
+    }
+
+    // Disposition is the only mandatory field
+    mdn.disposition.as_ref()?;
+
+    Some(mdn)
+}
@@ -0,0 +1,457 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::{
+        mdn::{DispositionType, Mdn, MdnSendRequest, MdnSendResponse, SendingMode},
+        set::{self, SetRequest},
+    },
+    object::Object,
+    request::{
+        method::{MethodFunction, MethodName, MethodObject},
+        reference::MaybeReference,
+        Call, RequestMethod,
+    },
+    types::{
+        collection::Collection,
+        id::Id,
+        keyword::Keyword,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::{parsers::MessageStream, HeaderValue, Message};
+use smtp_proto::{MailFrom, RcptTo};
+use store::BlobKind;
+use utils::{listener::ServerInstance, map::vec_map::VecMap};
+
+use crate::{identity::set::sanitize_email, submission::LocalSubmissionError, JMAP};
+
+impl JMAP {
+    pub async fn mdn_send(
+        &self,
+        request: MdnSendRequest,
+        instance: &Arc<ServerInstance>,
+        next_call: &mut Option<Call<RequestMethod>>,
+    ) -> Result<MdnSendResponse, MethodError> {
+        if request.send.len() > self.config.set_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = MdnSendResponse {
+            account_id: request.account_id,
+            sent: VecMap::with_capacity(request.send.len()),
+            not_sent: VecMap::new(),
+        };
+
+        // Obtain identity
+        let identity = self
+            .get_property::<Object<Value>>(
+                account_id,
+                Collection::Identity,
+                request.identity_id.document_id(),
+                Property::Value,
+            )
+            .await?
+            .and_then(|mut identity| {
+                let email = identity.remove(&Property::Email).try_unwrap_string()?;
+                let name = identity
+                    .remove(&Property::Name)
+                    .try_unwrap_string()
+                    .unwrap_or_default();
+                Some((name, email))
+            });
+
+        let mut sent_email_ids = HashMap::new();
+        for (id, mdn) in request.send {
+            let result = if let Some((from_name, from_email)) = &identity {
+                self.send_mdn(account_id, instance, from_name, from_email, mdn)
+                    .await?
+            } else {
+                Err(SetError::invalid_properties()
+                    .with_property(Property::IdentityId)
+                    .with_description("Identity not found."))
+            };
+
+            match result {
+                Ok((email_id, mdn)) => {
+                    sent_email_ids.insert(id.clone(), email_id);
+                    response.sent.append(id, mdn);
+                }
+                Err(err) => {
+                    response.not_sent.append(id, err);
+                }
+            }
+        }
+
+        // Set the $mdnsent keyword and apply any onSuccessUpdateEmail patches
+        if !sent_email_ids.is_empty() {
+            let mut update: VecMap<Id, Object<SetValue>> = VecMap::new();
+            for (id, patch) in request.on_success_update_email.unwrap_or_default() {
+                let id = match id {
+                    MaybeReference::Value(id) => id,
+                    MaybeReference::Reference(id_ref) => match sent_email_ids.get(&id_ref) {
+                        Some(id) => *id,
+                        None => continue,
+                    },
+                };
+                let properties = &mut update
+                    .get_mut_or_insert_with(id, || Object {
+                        properties: VecMap::new(),
+                    })
+                    .properties;
+                for (property, value) in patch.properties {
+                    properties.append(property, value);
+                }
+            }
+            for id in sent_email_ids.into_values() {
+                update
+                    .get_mut_or_insert_with(id, || Object {
+                        properties: VecMap::new(),
+                    })
+                    .properties
+                    .append(
+                        Property::Keywords,
+                        SetValue::Patch(vec![Value::Keyword(Keyword::MdnSent), Value::Bool(true)]),
+                    );
+            }
+
+            *next_call = Call {
+                id: String::new(),
+                name: MethodName::new(MethodObject::Email, MethodFunction::Set),
+                method: RequestMethod::Set(SetRequest {
+                    account_id: request.account_id,
+                    if_in_state: None,
+                    create: None,
+                    update: update.into(),
+                    destroy: None,
+                    arguments: set::RequestArguments::Email,
+                }),
+            }
+            .into();
+        }
+
+        Ok(response)
+    }
+
+    async fn send_mdn(
+        &self,
+        account_id: u32,
+        instance: &Arc<ServerInstance>,
+        from_name: &str,
+        from_email: &str,
+        mdn: Mdn,
+    ) -> Result<Result<(Id, Mdn), SetError>, MethodError> {
+        // Validate request
+        let (email_id, disposition) = match (mdn.for_email_id, &mdn.disposition) {
+            (Some(email_id), Some(disposition)) => (email_id, disposition),
+            _ => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_properties([
+                        Property::_T("forEmailId".to_string()),
+                        Property::Disposition,
+                    ])
+                    .with_description(
+                        "forEmailId and disposition properties are required.",
+                    )));
+            }
+        };
+        let mut extension_fields = String::new();
+        for (name, value) in mdn.extension_fields.iter().flatten() {
+            if name.is_empty()
+                || !name
+                    .bytes()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == b'-')
+            {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::_T("extensionFields".to_string()))
+                    .with_description(format!(
+                        "Invalid extension field name {name:?}."
+                    ))));
+            }
+            let _ = write!(extension_fields, "{name}: {}\r\n", field_value(value));
+        }
+
+        // Make sure an MDN has not been sent already
+        let document_id = email_id.document_id();
+        if let Some(keywords) = self
+            .get_property::<Vec<Keyword>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::Keywords,
+            )
+            .await?
+        {
+            if keywords.contains(&Keyword::MdnSent) {
+                return Ok(Err(SetError::new(SetErrorType::MdnAlreadySent)
+                    .with_description(
+                        "An MDN has already been sent for this message.",
+                    )));
+            }
+        } else {
+            return Ok(Err(SetError::not_found()
+                .with_property(Property::_T("forEmailId".to_string()))
+                .with_description("Email not found.")));
+        }
+
+        // Obtain original message
+        let raw_message = if let Some(raw_message) = self
+            .get_blob(
+                &BlobKind::LinkedMaildir {
+                    account_id,
+                    document_id,
+                },
+                0..u32::MAX,
+            )
+            .await?
+        {
+            raw_message
+        } else {
+            return Ok(Err(SetError::not_found()
+                .with_property(Property::_T("forEmailId".to_string()))
+                .with_description("Blob for email not found.")));
+        };
+        let message = if let Some(message) = Message::parse(&raw_message) {
+            message
+        } else {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::_T("forEmailId".to_string()))
+                .with_description("Failed to parse email.")));
+        };
+
+        // Obtain the recipient of the MDN and the original recipient
+        let mut rcpt_to = None;
+        let mut original_recipient = None;
+        for header in message.root_part().headers().iter().rev() {
+            let value = raw_message
+                .get(header.offset_start..header.offset_end)
+                .unwrap_or_default();
+            if header
+                .name
+                .as_str()
+                .eq_ignore_ascii_case("Disposition-Notification-To")
+            {
+                rcpt_to = match MessageStream::new(value).parse_address() {
+                    HeaderValue::Address(addr) => addr.address,
+                    HeaderValue::AddressList(addrs) => {
+                        addrs.into_iter().next().and_then(|addr| addr.address)
+                    }
+                    _ => None,
+                }
+                .and_then(|addr| sanitize_email(addr.as_ref()));
+            } else if header
+                .name
+                .as_str()
+                .eq_ignore_ascii_case("Original-Recipient")
+            {
+                original_recipient = std::str::from_utf8(value)
+                    .ok()
+                    .map(|value| field_value(value.trim()));
+            }
+        }
+        let rcpt_to = if let Some(rcpt_to) = rcpt_to {
+            rcpt_to
+        } else {
+            return Ok(Err(SetError::invalid_properties()
+                .with_property(Property::_T("forEmailId".to_string()))
+                .with_description(
+                    "Email does not request a disposition notification.",
+                )));
+        };
+
+        // Build MDN
+        let original_subject = message.subject().unwrap_or_default();
+        let original_message_id = message.message_id().map(|id| format!("<{id}>"));
+        let mut sent = Mdn::default();
+        let subject = mdn.subject.clone().unwrap_or_else(|| {
+            let subject = format!(
+                "Return Receipt ({}): {original_subject}",
+                disposition.type_.as_str()
+            );
+            sent.subject = subject.clone().into();
+            subject
+        });
+        let text_body = mdn.text_body.clone().unwrap_or_else(|| {
+            let text_body = format!(
+                concat!(
+                    "This is a Return Receipt for the mail that you sent to {} ",
+                    "with subject \"{}\".\r\n\r\n",
+                    "{}"
+                ),
+                from_email,
+                original_subject,
+                match disposition.type_ {
+                    DispositionType::Displayed => concat!(
+                        "The message has been displayed. There is no guarantee that ",
+                        "the content has been read or understood."
+                    ),
+                    DispositionType::Deleted => "The message has been deleted unread.",
+                    DispositionType::Dispatched => {
+                        "The message has been dispatched without being displayed."
+                    }
+                    DispositionType::Processed => {
+                        "The message has been processed without being displayed."
+                    }
+                }
+            );
+            sent.text_body = text_body.clone().into();
+            text_body
+        });
+        let reporting_ua = mdn.reporting_ua.clone().unwrap_or_else(|| {
+            let reporting_ua = format!("{}; Stalwart JMAP", instance.hostname);
+            sent.reporting_ua = reporting_ua.clone().into();
+            reporting_ua
+        });
+        let final_recipient = mdn.final_recipient.clone().unwrap_or_else(|| {
+            let final_recipient = format!("rfc822; {from_email}");
+            sent.final_recipient = final_recipient.clone().into();
+            final_recipient
+        });
+
+        let mut fields = format!("Reporting-UA: {}\r\n", field_value(&reporting_ua));
+        if let Some(original_recipient) = original_recipient {
+            let _ = write!(fields, "Original-Recipient: {original_recipient}\r\n");
+            sent.original_recipient = original_recipient.into();
+        }
+        let _ = write!(
+            fields,
+            "Final-Recipient: {}\r\n",
+            field_value(&final_recipient)
+        );
+        if let Some(original_message_id) = &original_message_id {
+            let _ = write!(fields, "Original-Message-ID: {original_message_id}\r\n");
+            sent.original_message_id = original_message_id.clone().into();
+        }
+        let _ = write!(fields, "Disposition: {disposition}\r\n");
+        for error in mdn.error.iter().flatten() {
+            let _ = write!(fields, "Error: {}\r\n", field_value(error));
+        }
+        fields.push_str(&extension_fields);
+
+        let mut parts = vec![
+            MimePart::new(
+                ContentType::new("text/plain").attribute("charset", "utf-8"),
+                BodyPart::Text(text_body.into()),
+            ),
+            MimePart::new(
+                ContentType::new("message/disposition-notification"),
+                BodyPart::Text(fields.into()),
+            ),
+        ];
+        if mdn.include_original_message.unwrap_or(false) {
+            parts.push(MimePart::new(
+                ContentType::new("message/rfc822"),
+                BodyPart::Text(String::from_utf8_lossy(&raw_message).into_owned().into()),
+            ));
+        }
+        let mut builder = if !from_name.is_empty() {
+            MessageBuilder::new().from((from_name, from_email))
+        } else {
+            MessageBuilder::new().from(from_email)
+        }
+        .to(rcpt_to.as_str())
+        .subject(subject);
+        if disposition.sending_mode == SendingMode::Automatic {
+            builder = builder.header("Auto-Submitted", HeaderType::Text("auto-replied".into()));
+        }
+        let mdn_message = builder
+            .body(MimePart::new(
+                ContentType::new("multipart/report")
+                    .attribute("report-type", "disposition-notification"),
+                BodyPart::Multipart(parts),
+            ))
+            .write_to_vec()
+            .unwrap_or_default();
+        if mdn_message.len() > self.config.mail_max_size {
+            return Ok(Err(SetError::new(SetErrorType::TooLarge).with_description(
+                format!(
+                    "MDN exceeds maximum size of {} bytes.",
+                    self.config.mail_max_size
+                ),
+            )));
+        }
+
+        // Submit message
+        match self
+            .submit_message(
+                instance,
+                MailFrom {
+                    address: from_email.to_string(),
+                    ..Default::default()
+                },
+                vec![RcptTo {
+                    address: rcpt_to,
+                    ..Default::default()
+                }],
+                mdn_message,
+            )
+            .await
+        {
+            Ok(submitted) if submitted.queue_id.is_some() => Ok(Ok((email_id, sent))),
+            Ok(submitted) => Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                .with_description(format!(
+                    "Server rejected recipient: {}",
+                    submitted
+                        .responses
+                        .into_iter()
+                        .find_map(|(_, response)| response)
+                        .unwrap_or_default()
+                )))),
+            Err(LocalSubmissionError::MailFrom(error)) => {
+                Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
+                    .with_description(format!(
+                        "Server rejected MAIL-FROM: {}",
+                        error
+                    ))))
+            }
+            Err(LocalSubmissionError::Data(error)) => {
+                Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                    .with_description(format!(
+                        "Server rejected DATA: {}",
+                        error
+                    ))))
+            }
+        }
+    }
+}
+
+// Removes line breaks from values written to the MDN fields.
+fn field_value(value: &str) -> String {
+    value
+        .split(['\r', '\n'])
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{property::Property, state::State, value::Value},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::QUOTA_ID;
+
+impl JMAP {
+    pub async fn quota_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.config.get_max_objects)?;
+        let properties = request.unwrap_properties(&[
+            Property::Id,
+            Property::ResourceType,
+            Property::Used,
+            Property::HardLimit,
+            Property::Scope,
+            Property::Name,
+            Property::Description,
+            Property::Types,
+        ]);
+        let account_id = request.account_id.document_id();
+        let quota = self.account_quota(access_token, account_id).await?;
+        let ids = if let Some(ids) = ids {
+            ids
+        } else if quota.is_some() {
+            vec![QUOTA_ID.into()]
+        } else {
+            vec![]
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: quota
+                .as_ref()
+                .map(|quota| State::Exact(quota.used as u64))
+                .unwrap_or(State::Initial)
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            let quota = match &quota {
+                Some(quota) if id.document_id() == QUOTA_ID => quota,
+                _ => {
+                    response.not_found.push(id);
+                    continue;
+                }
+            };
+
+            let mut result = Object::with_capacity(properties.len());
+            for property in &properties {
+                let value = match property {
+                    Property::Id => Value::Id(id),
+                    Property::ResourceType => Value::Text("octets".to_string()),
+                    Property::Used => Value::UnsignedInt(quota.used as u64),
+                    Property::HardLimit => Value::UnsignedInt(quota.hard_limit as u64),
+                    Property::Scope => Value::Text("account".to_string()),
+                    Property::Name => Value::Text(quota.name.clone()),
+                    Property::Description => quota
+                        .description
+                        .clone()
+                        .map(Value::Text)
+                        .unwrap_or(Value::Null),
+                    Property::Types => Value::List(vec![
+                        Value::Text("Email".to_string()),
+                        Value::Text("SieveScript".to_string()),
+                    ]),
+                    _ => Value::Null,
+                };
+
+                result.append(property.clone(), value);
+            }
+            response.list.push(result);
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+pub mod get;
+pub mod query;
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::changes::{ChangesRequest, ChangesResponse},
+    types::{id::Id, state::State},
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+pub const QUOTA_ID: u32 = 0;
+
+pub struct AccountQuota {
+    pub name: String,
+    pub description: Option<String>,
+    pub hard_limit: i64,
+    pub used: i64,
+}
+
+impl JMAP {
+    pub async fn account_quota(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+    ) -> Result<Option<AccountQuota>, MethodError> {
+        let hard_limit = self.get_quota(access_token, account_id).await?;
+        if hard_limit <= 0 {
+            return Ok(None);
+        }
+
+        let (name, description) = if access_token.primary_id == account_id {
+            (access_token.name.clone(), access_token.description.clone())
+        } else {
+            (
+                self.get_account_name(account_id)
+                    .await?
+                    .unwrap_or_else(|| Id::from(account_id).to_string()),
+                None,
+            )
+        };
+
+        Ok(Some(AccountQuota {
+            name,
+            description,
+            hard_limit,
+            used: self.get_used_quota(account_id).await?,
+        }))
+    }
+
+    pub async fn quota_changes(
+        &self,
+        request: ChangesRequest,
+        access_token: &AccessToken,
+    ) -> Result<ChangesResponse, MethodError> {
+        access_token.assert_is_member(request.account_id)?;
+
+        let mut response = ChangesResponse {
+            account_id: request.account_id,
+            old_state: request.since_state.clone(),
+            new_state: State::Initial,
+            has_more_changes: false,
+            created: vec![],
+            updated: vec![],
+            destroyed: vec![],
+            updated_properties: None,
+        };
+
+        // Quotas are never created or destroyed by the client, the state
+        // is derived from the used octets so any difference is an update.
+        if let Some(quota) = self
+            .account_quota(access_token, request.account_id.document_id())
+            .await?
+        {
+            response.new_state = State::Exact(quota.used as u64);
+            match &request.since_state {
+                State::Initial => {
+                    response.created.push(QUOTA_ID.into());
+                }
+                since_state if since_state != &response.new_state => {
+                    response.updated.push(QUOTA_ID.into());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(response)
+    }
+}
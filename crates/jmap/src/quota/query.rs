@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{Filter, QueryRequest, QueryResponse, RequestArguments, SortProperty},
+    types::state::State,
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::{AccountQuota, QUOTA_ID};
+
+enum Operator {
+    And,
+    Or,
+    Not,
+}
+
+impl JMAP {
+    pub async fn quota_query(
+        &self,
+        mut request: QueryRequest<RequestArguments>,
+        access_token: &AccessToken,
+    ) -> Result<QueryResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let quota = self.account_quota(access_token, account_id).await?;
+
+        // Validate sort properties
+        for comparator in request.sort.as_deref().unwrap_or_default() {
+            match &comparator.property {
+                SortProperty::Name | SortProperty::Used => (),
+                other => return Err(MethodError::UnsupportedSort(other.to_string())),
+            }
+        }
+
+        // Evaluate filters against the account's quota
+        let mut stack = Vec::new();
+        let mut operator = Operator::And;
+        let mut results = Vec::new();
+        for cond in std::mem::take(&mut request.filter) {
+            match cond {
+                Filter::And | Filter::Or | Filter::Not => {
+                    stack.push((operator, std::mem::take(&mut results)));
+                    operator = match cond {
+                        Filter::And => Operator::And,
+                        Filter::Or => Operator::Or,
+                        _ => Operator::Not,
+                    };
+                }
+                Filter::Close => {
+                    let result = operator.eval(&results);
+                    (operator, results) = stack.pop().ok_or_else(|| {
+                        MethodError::InvalidArguments("Invalid filter.".to_string())
+                    })?;
+                    results.push(result);
+                }
+                cond => results.push(matches_filter(quota.as_ref(), cond)?),
+            }
+        }
+        let is_match = quota.is_some() && operator.eval(&results);
+
+        Ok(QueryResponse {
+            account_id: request.account_id,
+            query_state: quota
+                .as_ref()
+                .map(|quota| State::Exact(quota.used as u64))
+                .unwrap_or(State::Initial),
+            can_calculate_changes: false,
+            position: 0,
+            ids: if is_match && request.position.unwrap_or(0) <= 0 && request.limit != Some(0) {
+                vec![QUOTA_ID.into()]
+            } else {
+                vec![]
+            },
+            total: if request.calculate_total.unwrap_or(false) {
+                Some(usize::from(is_match))
+            } else {
+                None
+            },
+            limit: None,
+        })
+    }
+}
+
+impl Operator {
+    fn eval(&self, results: &[bool]) -> bool {
+        match self {
+            Operator::And => results.iter().all(|r| *r),
+            Operator::Or => results.iter().any(|r| *r),
+            Operator::Not => !results.iter().any(|r| *r),
+        }
+    }
+}
+
+fn matches_filter(quota: Option<&AccountQuota>, cond: Filter) -> Result<bool, MethodError> {
+    Ok(match cond {
+        Filter::Name(name) => quota.map_or(false, |quota| {
+            quota.name.to_lowercase().contains(&name.to_lowercase())
+        }),
+        Filter::Scope(scope) => scope == "account",
+        Filter::ResourceType(resource_type) => resource_type == "octets",
+        Filter::Type(typ) => matches!(typ.as_str(), "Email" | "SieveScript"),
+        other => return Err(MethodError::UnsupportedFilter(other.to_string())),
+    })
+}
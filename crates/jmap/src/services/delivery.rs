@@ -35,6 +35,18 @@ pub fn spawn_delivery_manager(core: Arc<JMAP>, mut delivery_rx: mpsc::Receiver<D
                 DeliveryEvent::Ingest { message, result_tx } => {
                     result_tx.send(core.deliver_message(message).await).ok();
                 }
+                DeliveryEvent::SpamClassify {
+                    message,
+                    recipients,
+                    result_tx,
+                } => {
+                    let core = core.clone();
+                    tokio::spawn(async move {
+                        result_tx
+                            .send(core.spam_classify_delivery(&message, &recipients).await)
+                            .ok();
+                    });
+                }
                 DeliveryEvent::Stop => break,
             }
         }
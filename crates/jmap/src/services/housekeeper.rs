@@ -28,7 +28,7 @@ use std::{
 
 use chrono::{Datelike, TimeZone, Timelike};
 use tokio::sync::mpsc;
-use utils::{config::Config, failed, map::ttl_dashmap::TtlMap, UnwrapFailure};
+use utils::{config::Config, failed, map::ttl_dashmap::TtlMap, metrics::METRICS, UnwrapFailure};
 
 use crate::JMAP;
 
@@ -111,6 +111,7 @@ pub fn spawn_housekeeper(core: Arc<JMAP>, settings: &Config, mut rx: mpsc::Recei
                 let core = core.clone();
 
                 tokio::spawn(async move {
+                    let start = Instant::now();
                     match task_id {
                         TASK_PURGE_DB => {
                             tracing::info!("Purging database.");
@@ -138,6 +139,7 @@ pub fn spawn_housekeeper(core: Arc<JMAP>, settings: &Config, mut rx: mpsc::Recei
                         }
                         _ => unreachable!(),
                     }
+                    METRICS.housekeeper[task_id].observe(start.elapsed());
                 });
             }
         }
@@ -26,7 +26,11 @@ use mail_parser::Message;
 use store::ahash::AHashMap;
 use utils::ipc::{DeliveryResult, IngestMessage};
 
-use crate::{email::ingest::IngestEmail, mailbox::INBOX_ID, IngestError, JMAP};
+use crate::{
+    email::{ingest::IngestEmail, spam::is_spam_message},
+    mailbox::INBOX_ID,
+    IngestError, JMAP,
+};
 
 impl JMAP {
     pub async fn deliver_message(&self, message: IngestMessage) -> Vec<DeliveryResult> {
@@ -95,12 +99,25 @@ impl JMAP {
                         }
                     };
 
+                    // File messages classified as spam into the Junk folder
+                    let parsed_message = Message::parse(&raw_message);
+                    let mut mailbox_id = INBOX_ID;
+                    if self.config.spam_enable
+                        && parsed_message
+                            .as_ref()
+                            .map_or(false, |message| is_spam_message(message, &raw_message))
+                    {
+                        if let Ok(Some(junk_id)) = self.mailbox_get_by_role(uid, "junk").await {
+                            mailbox_id = junk_id;
+                        }
+                    }
+
                     self.email_ingest(IngestEmail {
                         raw_message: &raw_message,
-                        message: Message::parse(&raw_message),
+                        message: parsed_message,
                         account_id: uid,
                         account_quota,
-                        mailbox_ids: vec![INBOX_ID],
+                        mailbox_ids: vec![mailbox_id],
                         keywords: vec![],
                         received_at: None,
                         skip_duplicates: true,
@@ -128,6 +145,40 @@ impl JMAP {
                         )
                         .await;
                     }
+
+                    // Process calendar invitations and replies
+                    if self.config.calendar_imip_enable
+                        && ingested_message.change_id != u64::MAX
+                        && has_calendar_part(&raw_message)
+                    {
+                        match self
+                            .calendar_event_ingest_imip(
+                                uid,
+                                &message.sender_address,
+                                message.sender_authenticated,
+                                &raw_message,
+                            )
+                            .await
+                        {
+                            Ok(Some(change_id)) => {
+                                self.broadcast_state_change(
+                                    StateChange::new(uid)
+                                        .with_change(TypeState::CalendarEvent, change_id),
+                                )
+                                .await;
+                            }
+                            Ok(None) => (),
+                            Err(err) => {
+                                tracing::debug!(
+                                    context = "deliver_message",
+                                    event = "error",
+                                    account_id = uid,
+                                    error = ?err,
+                                    "Failed to process iMIP message."
+                                );
+                            }
+                        }
+                    }
                 }
                 Err(err) => match err {
                     IngestError::OverQuota => {
@@ -195,3 +246,9 @@ impl JMAP {
             .collect()
     }
 }
+
+fn has_calendar_part(raw_message: &[u8]) -> bool {
+    raw_message
+        .windows(13)
+        .any(|window| window.eq_ignore_ascii_case(b"text/calendar"))
+}
@@ -392,32 +392,55 @@ impl JMAP {
         id: u32,
         account_id: u32,
         types: Bitmap<TypeState>,
+    ) -> Option<mpsc::Receiver<StateChange>> {
+        self.subscribe_state_manager_accounts(id, [account_id], types)
+            .await
+    }
+
+    pub async fn subscribe_state_manager_accounts(
+        &self,
+        id: u32,
+        account_ids: impl IntoIterator<Item = u32>,
+        types: Bitmap<TypeState>,
     ) -> Option<mpsc::Receiver<StateChange>> {
         let (change_tx, change_rx) = mpsc::channel::<StateChange>(IPC_CHANNEL_BUFFER);
         let state_tx = self.state_tx.clone();
 
-        for event in [
-            Event::UpdateSharedAccounts { account_id },
-            Event::Subscribe {
-                id,
-                account_id,
-                types,
-                tx: change_tx,
-            },
-        ] {
-            if let Err(err) = state_tx.send(event).await {
-                tracing::error!(
-                    "Channel failure while subscribing to state manager: {}",
-                    err
-                );
-                return None;
+        for account_id in account_ids {
+            for event in [
+                Event::UpdateSharedAccounts { account_id },
+                Event::Subscribe {
+                    id,
+                    account_id,
+                    types,
+                    tx: change_tx.clone(),
+                },
+            ] {
+                if let Err(err) = state_tx.send(event).await {
+                    tracing::error!(
+                        "Channel failure while subscribing to state manager: {}",
+                        err
+                    );
+                    return None;
+                }
             }
         }
 
         change_rx.into()
     }
 
-    pub async fn broadcast_state_change(&self, state_change: StateChange) -> bool {
+    pub async fn broadcast_state_change(&self, mut state_change: StateChange) -> bool {
+        // Email changes may have moved the account's used quota
+        if state_change
+            .types
+            .iter()
+            .any(|(type_state, _)| matches!(type_state, TypeState::Email))
+        {
+            if let Ok(used) = self.get_used_quota(state_change.account_id).await {
+                state_change = state_change.with_change(TypeState::Quota, used as u64);
+            }
+        }
+
         match self
             .state_tx
             .clone()
@@ -39,6 +39,8 @@ use jmap_proto::{
         collection::Collection,
         id::Id,
         property::Property,
+        state::StateChange,
+        type_state::TypeState,
         value::{MaybePatchValue, SetValue, Value},
     },
 };
@@ -285,6 +287,19 @@ impl JMAP {
         // Write changes
         if !changes.is_empty() {
             ctx.response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+
+            // Script sizes count towards the account's quota
+            if !ctx.response.created.is_empty()
+                || !ctx.response.updated.is_empty()
+                || !ctx.response.destroyed.is_empty()
+            {
+                ctx.response.state_change = StateChange::new(account_id)
+                    .with_change(
+                        TypeState::Quota,
+                        self.get_used_quota(account_id).await? as u64,
+                    )
+                    .into();
+            }
         }
 
         Ok(ctx.response)
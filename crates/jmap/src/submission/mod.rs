@@ -24,3 +24,81 @@
 pub mod get;
 pub mod query;
 pub mod set;
+
+use std::sync::Arc;
+
+use smtp::{
+    core::{NullIo, Session, SessionData, State},
+    queue::QueueId,
+};
+use smtp_proto::{MailFrom, RcptTo};
+use utils::listener::ServerInstance;
+
+use crate::JMAP;
+
+pub struct LocalSubmission {
+    pub queue_id: Option<QueueId>,
+    pub responses: Vec<(String, Option<String>)>,
+}
+
+pub enum LocalSubmissionError {
+    MailFrom(String),
+    Data(String),
+}
+
+impl JMAP {
+    // Submits a message through a local SMTP session, returning the queue id
+    // and the server response for each recipient.
+    pub async fn submit_message(
+        &self,
+        instance: &Arc<ServerInstance>,
+        mail_from: MailFrom<String>,
+        rcpt_to: Vec<RcptTo<String>>,
+        message: Vec<u8>,
+    ) -> Result<LocalSubmission, LocalSubmissionError> {
+        // Begin local SMTP session
+        let mut session =
+            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
+
+        // MAIL FROM
+        let _ = session.handle_mail_from(mail_from).await;
+        if let Some(error) = session.has_failed() {
+            return Err(LocalSubmissionError::MailFrom(error.trim().to_string()));
+        }
+
+        // RCPT TO
+        let mut responses = Vec::new();
+        let mut has_success = false;
+        for rcpt in rcpt_to {
+            let addr = rcpt.address.clone();
+            let _ = session.handle_rcpt_to(rcpt).await;
+            let response = session.has_failed();
+            if response.is_none() {
+                has_success = true;
+            }
+            responses.push((addr, response));
+        }
+
+        // DATA
+        let mut queue_id = None;
+        if has_success {
+            session.data.message = message;
+            let response = session.queue_message().await;
+            if let State::Accepted(id) = session.state {
+                queue_id = id.into();
+            } else {
+                return Err(LocalSubmissionError::Data(
+                    std::str::from_utf8(&response)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                ));
+            }
+        }
+
+        Ok(LocalSubmission {
+            queue_id,
+            responses,
+        })
+    }
+}
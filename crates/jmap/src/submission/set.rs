@@ -47,10 +47,7 @@ use jmap_proto::{
         value::{MaybePatchValue, SetValue, Value},
     },
 };
-use smtp::{
-    core::{management::QueueRequest, NullIo, Session, SessionData, State},
-    queue,
-};
+use smtp::{core::management::QueueRequest, queue};
 use smtp_proto::{request::parser::Rfc5321Parser, MailFrom, RcptTo};
 use store::{
     write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder},
@@ -61,6 +58,8 @@ use utils::{listener::ServerInstance, map::vec_map::VecMap};
 
 use crate::{identity::set::sanitize_email, JMAP};
 
+use super::LocalSubmissionError;
+
 pub static SCHEMA: &[IndexProperty] = &[
     IndexProperty::new(Property::UndoStatus).index_as(IndexAs::Text {
         tokenize: false,
@@ -596,47 +595,31 @@ impl JMAP {
                 .with_description("Blob for email not found.")));
         };
 
-        // Begin local SMTP session
-        let mut session =
-            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
-
-        // MAIL FROM
-        let _ = session.handle_mail_from(mail_from).await;
-        if let Some(error) = session.has_failed() {
-            return Ok(Err(SetError::new(SetErrorType::ForbiddenMailFrom)
-                .with_description(format!(
-                    "Server rejected MAIL-FROM: {}",
-                    error.trim()
-                ))));
-        }
-
-        // RCPT TO
-        let mut responses = Vec::new();
-        let mut has_success = false;
-        for rcpt in rcpt_to {
-            let addr = rcpt.address.clone();
-            let _ = session.handle_rcpt_to(rcpt).await;
-            let response = session.has_failed();
-            if response.is_none() {
-                has_success = true;
+        // Submit message
+        let has_success;
+        let responses = match self
+            .submit_message(instance, mail_from, rcpt_to, message)
+            .await
+        {
+            Ok(submitted) => {
+                has_success = submitted.queue_id.is_some();
+                if let Some(queue_id) = submitted.queue_id {
+                    submission.append(Property::MessageId, queue_id);
+                }
+                submitted.responses
             }
-            responses.push((addr, response));
-        }
-
-        // DATA
-        if has_success {
-            session.data.message = message;
-            let response = session.queue_message().await;
-            if let State::Accepted(queue_id) = session.state {
-                submission.append(Property::MessageId, queue_id);
-            } else {
-                return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+            Err(LocalSubmissionError::MailFrom(error)) => {
+                return Ok(Err(SetError::new(SetErrorType::ForbiddenMailFrom)
                     .with_description(format!(
-                        "Server rejected DATA: {}",
-                        std::str::from_utf8(&response).unwrap().trim()
+                        "Server rejected MAIL-FROM: {}",
+                        error
                     ))));
             }
-        }
+            Err(LocalSubmissionError::Data(error)) => {
+                return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                    .with_description(format!("Server rejected DATA: {}", error))));
+            }
+        };
 
         // Set responses
         submission.append(
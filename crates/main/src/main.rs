@@ -21,13 +21,14 @@
  * for more details.
 */
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use directory::config::ConfigDirectory;
 use imap::core::{ImapSessionManager, IMAP};
 use jmap::{api::JmapSessionManager, services::IPC_CHANNEL_BUFFER, JMAP};
 use managesieve::core::ManageSieveSessionManager;
 use smtp::core::{SmtpSessionManager, SMTP};
+use store::Store;
 use tokio::sync::mpsc;
 use utils::{
     config::{Config, ServerProtocol},
@@ -45,7 +46,6 @@ static GLOBAL: Jemalloc = Jemalloc;
 async fn main() -> std::io::Result<()> {
     let config = Config::init();
     let servers = config.parse_servers().failed("Invalid configuration");
-    let directory = config.parse_directory().failed("Invalid configuration");
 
     // Bind ports and drop privileges
     servers.bind(&config);
@@ -60,12 +60,18 @@ async fn main() -> std::io::Result<()> {
     )
     .failed("Failed to enable tracing");
 
+    // Open the database and parse directories
+    let store = Arc::new(Store::open(&config).await.failed("Unable to open database"));
+    let directory = config
+        .parse_directory_with_store(Some(&store))
+        .failed("Invalid configuration");
+
     // Init servers
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &directory, delivery_tx)
+    let smtp = SMTP::init(&config, &servers, &directory, store.clone(), delivery_tx)
         .await
         .failed("Invalid configuration file");
-    let jmap = JMAP::init(&config, &directory, delivery_rx, smtp.clone())
+    let jmap = JMAP::init(&config, &directory, store, delivery_rx, smtp.clone())
         .await
         .failed("Invalid configuration file");
     let imap = IMAP::init(&config)
@@ -26,6 +26,7 @@ pub mod session;
 
 use std::{borrow::Cow, sync::Arc};
 
+use directory::scram::ScramServer;
 use imap::core::IMAP;
 use imap_proto::receiver::{CommandParser, Receiver};
 use jmap::{
@@ -49,6 +50,7 @@ pub struct Session<T: AsyncRead + AsyncWrite> {
     pub stream: T,
     pub span: tracing::Span,
     pub in_flight: InFlight,
+    pub scram: Option<Box<ScramServer>>,
 }
 
 pub enum State {
@@ -103,6 +105,9 @@ pub enum Command {
 
 pub trait IsTls {
     fn is_tls(&self) -> bool;
+    fn tls_end_point(&self, _instance: &ServerInstance) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl IsTls for TcpStream {
@@ -115,6 +120,10 @@ impl IsTls for TlsStream<TcpStream> {
     fn is_tls(&self) -> bool {
         true
     }
+
+    fn tls_end_point(&self, instance: &ServerInstance) -> Option<Vec<u8>> {
+        instance.tls_end_point(self)
+    }
 }
 
 impl CommandParser for Command {
@@ -165,7 +174,7 @@ pub enum ResponseCode {
     QuotaMaxScripts,
     QuotaMaxSize,
     Referral,
-    Sasl,
+    Sasl(String),
     TransitionNeeded,
     TryLater,
     Active,
@@ -184,7 +193,12 @@ impl ResponseCode {
             ResponseCode::QuotaMaxScripts => b"QUOTA/MAXSCRIPTS",
             ResponseCode::QuotaMaxSize => b"QUOTA/MAXSIZE",
             ResponseCode::Referral => b"REFERRAL",
-            ResponseCode::Sasl => b"SASL",
+            ResponseCode::Sasl(data) => {
+                buf.extend_from_slice(b"SASL \"");
+                buf.extend_from_slice(data.as_bytes());
+                buf.push(b'\"');
+                return;
+            }
             ResponseCode::TransitionNeeded => b"TRANSITION-NEEDED",
             ResponseCode::TryLater => b"TRYLATER",
             ResponseCode::Active => b"ACTIVE",
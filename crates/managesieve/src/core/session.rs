@@ -48,6 +48,7 @@ impl SessionManager for ManageSieveSessionManager {
             remote_addr: RemoteAddress::IpAddress(session.remote_ip),
             receiver: Receiver::with_max_request_size(self.imap.max_request_size)
                 .with_start_state(receiver::State::Command { is_uid: false }),
+            scram: None,
         };
 
         tokio::spawn(async move {
@@ -160,6 +161,7 @@ impl Session<TcpStream> {
             imap: self.imap,
             receiver: self.receiver,
             remote_addr: self.remote_addr,
+            scram: None,
         })
     }
 
@@ -23,16 +23,22 @@
 
 use std::sync::Arc;
 
+use directory::{
+    scram::{ScramResult, ScramServer},
+    Protocol,
+};
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
 use imap_proto::{
     protocol::authenticate::Mechanism,
     receiver::{self, Request},
 };
+use jmap::auth::AccessToken;
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::core::{Command, IsTls, Session, State, StatusResponse};
+use crate::core::{Command, IsTls, ResponseCode, Session, State, StatusResponse};
 
 impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::op::OpResult {
@@ -68,6 +74,9 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
                     return Ok(b"{0}\r\n".to_vec());
                 }
             }
+            Mechanism::ScramSha256 | Mechanism::ScramSha256Plus => {
+                return self.handle_scram(mechanism, params.pop()).await;
+            }
             _ => {
                 return Err(StatusResponse::no(
                     "Authentication mechanism not supported.",
@@ -76,41 +85,129 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
         };
 
         // Throttle authentication requests
-        if self.jmap.is_auth_allowed(self.remote_addr.clone()).is_err() {
-            tracing::debug!(parent: &self.span,
-                event = "disconnect",
-                "Too many authentication attempts, disconnecting.",
-            );
-            return Err(StatusResponse::bye(
-                "Too many authentication requests from this IP address.",
-            ));
-        }
+        self.check_auth_throttle()?;
 
         // Authenticate
         let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
-                self.jmap.authenticate_plain(&username, &secret).await
+            Credentials::Plain { username, secret } => {
+                self.jmap
+                    .authenticate_plain(&username, &secret, Protocol::ManageSieve)
+                    .await
+            }
+            Credentials::XOauth2 { username, secret } => {
+                self.jmap
+                    .authenticate_xoauth2(&username, &secret, Protocol::ManageSieve)
+                    .await
             }
             Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+                self.jmap
+                    .authenticate_bearer(&token, Protocol::ManageSieve)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
             }
         };
 
+        self.complete_authentication(access_token, None).await
+    }
+
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> crate::op::OpResult {
+        let mut server = if let Some(server) = self.scram.take() {
+            server
+        } else {
+            let tls_end_point = self.stream.tls_end_point(&self.instance);
+            if mechanism == Mechanism::ScramSha256Plus && tls_end_point.is_none() {
+                return Err(StatusResponse::no(
+                    "Channel binding is not available for this connection.",
+                ));
+            }
+            self.check_auth_throttle()?;
+            Box::new(ScramServer::new(
+                mechanism == Mechanism::ScramSha256Plus,
+                tls_end_point,
+            ))
+        };
+
+        let response = match response {
+            Some(response) if response == "*" => {
+                return Err(StatusResponse::no("Authentication cancelled."));
+            }
+            Some(response) => base64_decode(response.as_bytes())
+                .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?,
+            None if server.is_new() => {
+                return Ok(self.continue_scram(server, mechanism, &[]));
+            }
+            None => return Err(StatusResponse::no("Invalid SCRAM response.")),
+        };
+
+        match server.next(self.jmap.directory.as_ref(), &response).await {
+            Ok(ScramResult::Continue(challenge)) => {
+                Ok(self.continue_scram(server, mechanism, &challenge))
+            }
+            Ok(ScramResult::Success {
+                principal,
+                server_final,
+            }) => {
+                let access_token = self.jmap.access_token_from_principal(principal).await;
+                self.complete_authentication(
+                    access_token,
+                    ResponseCode::Sasl(encode(&server_final)).into(),
+                )
+                .await
+            }
+            Ok(ScramResult::Failure(reason)) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "authenticate",
+                    reason = reason,
+                    "SCRAM authentication failed."
+                );
+                self.complete_authentication(None, None).await
+            }
+            Err(_) => {
+                Err(StatusResponse::no("Temporary server failure.")
+                    .with_code(ResponseCode::TryLater))
+            }
+        }
+    }
+
+    fn continue_scram(
+        &mut self,
+        server: Box<ScramServer>,
+        mechanism: Mechanism,
+        challenge: &[u8],
+    ) -> Vec<u8> {
+        self.scram = server.into();
+        self.receiver.request = receiver::Request {
+            tag: String::new(),
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        format!("\"{}\"\r\n", encode(challenge)).into_bytes()
+    }
+
+    fn check_auth_throttle(&self) -> Result<(), StatusResponse> {
+        if self.jmap.is_auth_allowed(self.remote_addr.clone()).is_ok() {
+            Ok(())
+        } else {
+            tracing::debug!(parent: &self.span,
+                event = "disconnect",
+                "Too many authentication attempts, disconnecting.",
+            );
+            Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            ))
+        }
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        code: Option<ResponseCode>,
+    ) -> crate::op::OpResult {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = self
@@ -130,7 +227,9 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
                     in_flight,
                 };
 
-                self.handle_capability("Authentication successful").await
+                let mut response = StatusResponse::ok("Authentication successful");
+                response.code = code;
+                Ok(response.serialize(self.capabilities()))
             } else {
                 tracing::debug!(parent: &self.span,
                     event = "disconnect",
@@ -166,3 +265,7 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
         Ok(StatusResponse::ok("Unauthenticate successful.").into_bytes())
     }
 }
+
+fn encode(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
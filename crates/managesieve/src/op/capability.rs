@@ -28,6 +28,10 @@ use crate::core::{IsTls, Session, StatusResponse};
 
 impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
     pub async fn handle_capability(&self, message: &'static str) -> super::OpResult {
+        Ok(StatusResponse::ok(message).serialize(self.capabilities()))
+    }
+
+    pub fn capabilities(&self) -> Vec<u8> {
         let mut response = Vec::with_capacity(128);
         response.extend_from_slice(b"\"IMPLEMENTATION\" \"Stalwart ManageSieve v");
         response.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
@@ -37,7 +41,9 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
             response.extend_from_slice(b"\"SASL\" \"\"\r\n");
             response.extend_from_slice(b"\"STARTTLS\"\r\n");
         } else {
-            response.extend_from_slice(b"\"SASL\" \"PLAIN OAUTHBEARER\"\r\n");
+            response.extend_from_slice(
+                b"\"SASL\" \"PLAIN OAUTHBEARER SCRAM-SHA-256 SCRAM-SHA-256-PLUS\"\r\n",
+            );
         };
         if let Some(sieve) =
             self.jmap
@@ -70,6 +76,6 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
             response.extend_from_slice(b"\"SIEVE\" \"\"\r\n");
         }
 
-        Ok(StatusResponse::ok(message).serialize(response))
+        response
     }
 }
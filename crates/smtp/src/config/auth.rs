@@ -72,6 +72,9 @@ impl ConfigAuth for Config {
                     .parse_if_block::<Vec<String>>("auth.dkim.sign", ctx, &envelope_sender_keys)?
                     .unwrap_or_default()
                     .map_if_block(&ctx.signers, "auth.dkim.sign", "signature")?,
+                sign_managed: self
+                    .parse_if_block("auth.dkim.managed.sign", ctx, &envelope_sender_keys)?
+                    .unwrap_or_default(),
             },
             arc: ArcAuthConfig {
                 verify: self
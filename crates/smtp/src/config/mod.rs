@@ -59,6 +59,7 @@ pub struct Host {
     pub protocol: ServerProtocol,
     pub concurrency: usize,
     pub timeout: Duration,
+    pub weight: u32,
     pub tls_implicit: bool,
     pub tls_allow_invalid_certs: bool,
     pub username: Option<String>,
@@ -247,6 +248,8 @@ pub struct Rcpt {
 pub struct Data {
     pub script: IfBlock<Option<Arc<Sieve>>>,
     pub pipe_commands: Vec<Pipe>,
+    pub milters: Vec<Milter>,
+    pub quarantine_path: Option<PathBuf>,
 
     // Limits
     pub max_messages: IfBlock<usize>,
@@ -260,6 +263,7 @@ pub struct Data {
     pub add_auth_results: IfBlock<bool>,
     pub add_message_id: IfBlock<bool>,
     pub add_date: IfBlock<bool>,
+    pub add_spam: IfBlock<bool>,
 }
 
 pub struct Pipe {
@@ -268,6 +272,22 @@ pub struct Pipe {
     pub timeout: IfBlock<Duration>,
 }
 
+pub struct Milter {
+    pub enable: IfBlock<bool>,
+    pub hostname: String,
+    pub port: u16,
+    pub timeout_connect: Duration,
+    pub timeout_command: Duration,
+    pub timeout_data: Duration,
+    pub tls: bool,
+    pub tls_allow_invalid_certs: bool,
+    pub tempfail_on_error: bool,
+    pub max_frame_len: usize,
+    pub protocol_version: u32,
+    pub flags_actions: Option<u32>,
+    pub flags_protocol: Option<u32>,
+}
+
 pub struct SessionConfig {
     pub timeout: IfBlock<Duration>,
     pub duration: IfBlock<Duration>,
@@ -290,12 +310,14 @@ pub struct SessionThrottle {
 }
 
 pub struct RelayHost {
+    pub id: String,
     pub address: String,
     pub port: u16,
     pub protocol: ServerProtocol,
     pub auth: Option<Credentials<String>>,
     pub tls_implicit: bool,
     pub tls_allow_invalid_certs: bool,
+    pub weight: u32,
 }
 
 pub struct QueueConfig {
@@ -309,7 +331,8 @@ pub struct QueueConfig {
 
     // Outbound
     pub hostname: IfBlock<String>,
-    pub next_hop: IfBlock<Option<RelayHost>>,
+    pub next_hop: IfBlock<Vec<RelayHost>>,
+    pub relay: QueueOutboundRelay,
     pub max_mx: IfBlock<usize>,
     pub max_multihomed: IfBlock<usize>,
     pub ip_strategy: IfBlock<IpLookupStrategy>,
@@ -320,6 +343,12 @@ pub struct QueueConfig {
     // Timeouts
     pub timeout: QueueOutboundTimeout,
 
+    // Connection reuse
+    pub reuse: QueueOutboundReuse,
+
+    // Adaptive throttling
+    pub adaptive: QueueOutboundAdaptive,
+
     // Throttle and Quotas
     pub throttle: QueueThrottle,
     pub quota: QueueQuotas,
@@ -361,6 +390,7 @@ pub struct Dsn {
     pub name: IfBlock<String>,
     pub address: IfBlock<String>,
     pub sign: IfBlock<Vec<Arc<DkimSigner>>>,
+    pub sign_managed: IfBlock<bool>,
 }
 
 pub struct AggregateReport {
@@ -398,6 +428,29 @@ pub struct QueueOutboundTimeout {
     pub mta_sts: IfBlock<Duration>,
 }
 
+pub struct QueueOutboundReuse {
+    pub idle_timeout: IfBlock<Duration>,
+    pub max_messages: IfBlock<usize>,
+}
+
+pub struct QueueOutboundRelay {
+    pub strategy: IfBlock<RelayStrategy>,
+    pub failure_backoff: IfBlock<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RelayStrategy {
+    #[default]
+    Failover,
+    RoundRobin,
+}
+
+pub struct QueueOutboundAdaptive {
+    pub enable: IfBlock<bool>,
+    pub initial_delay: IfBlock<Duration>,
+    pub max_delay: IfBlock<Duration>,
+}
+
 #[derive(Debug)]
 pub struct QueueThrottle {
     pub sender: Vec<Throttle>,
@@ -427,14 +480,14 @@ pub enum AggregateFrequency {
     Never,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct TlsStrategy {
     pub dane: RequireOptional,
     pub mta_sts: RequireOptional,
     pub tls: RequireOptional,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum RequireOptional {
     #[default]
     Optional,
@@ -531,11 +584,13 @@ impl<'x> ConfigContext<'x> {
 impl std::fmt::Debug for RelayHost {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RelayHost")
+            .field("id", &self.id)
             .field("address", &self.address)
             .field("port", &self.port)
             .field("protocol", &self.protocol)
             .field("tls_implicit", &self.tls_implicit)
             .field("tls_allow_invalid_certs", &self.tls_allow_invalid_certs)
+            .field("weight", &self.weight)
             .finish()
     }
 }
@@ -79,8 +79,12 @@ impl ConfigQueue for Config {
         ];
 
         let next_hop = self
-            .parse_if_block::<Option<String>>("queue.outbound.next-hop", ctx, &rcpt_envelope_keys)?
-            .unwrap_or_else(|| IfBlock::new(None));
+            .parse_if_block::<Vec<Option<String>>>(
+                "queue.outbound.next-hop",
+                ctx,
+                &rcpt_envelope_keys,
+            )?
+            .unwrap_or_default();
 
         let default_hostname = self.value_require("server.hostname")?;
 
@@ -138,6 +142,18 @@ impl ConfigQueue for Config {
                     .unwrap_or_else(|| IfBlock::new(Vec::new())),
             },
             next_hop: next_hop.into_relay_host(ctx)?,
+            relay: QueueOutboundRelay {
+                strategy: self
+                    .parse_if_block("queue.outbound.relay.strategy", ctx, &rcpt_envelope_keys)?
+                    .unwrap_or_default(),
+                failure_backoff: self
+                    .parse_if_block(
+                        "queue.outbound.relay.failure-backoff",
+                        ctx,
+                        &rcpt_envelope_keys,
+                    )?
+                    .unwrap_or_else(|| IfBlock::new(Duration::from_secs(60))),
+            },
             tls: QueueOutboundTls {
                 dane: self
                     .parse_if_block("queue.outbound.tls.dane", ctx, &mx_envelope_keys)?
@@ -181,6 +197,29 @@ impl ConfigQueue for Config {
                     .parse_if_block("queue.outbound.timeouts.mta-sts", ctx, &rcpt_envelope_keys)?
                     .unwrap_or_else(|| IfBlock::new(Duration::from_secs(10 * 60))),
             },
+            reuse: QueueOutboundReuse {
+                idle_timeout: self
+                    .parse_if_block("queue.outbound.reuse.idle-timeout", ctx, &mx_envelope_keys)?
+                    .unwrap_or_else(|| IfBlock::new(Duration::ZERO)),
+                max_messages: self
+                    .parse_if_block("queue.outbound.reuse.max-messages", ctx, &mx_envelope_keys)?
+                    .unwrap_or_else(|| IfBlock::new(100)),
+            },
+            adaptive: QueueOutboundAdaptive {
+                enable: self
+                    .parse_if_block("queue.outbound.adaptive.enable", ctx, &mx_envelope_keys)?
+                    .unwrap_or_else(|| IfBlock::new(false)),
+                initial_delay: self
+                    .parse_if_block(
+                        "queue.outbound.adaptive.initial-delay",
+                        ctx,
+                        &mx_envelope_keys,
+                    )?
+                    .unwrap_or_else(|| IfBlock::new(Duration::from_secs(1))),
+                max_delay: self
+                    .parse_if_block("queue.outbound.adaptive.max-delay", ctx, &mx_envelope_keys)?
+                    .unwrap_or_else(|| IfBlock::new(Duration::from_secs(15 * 60))),
+            },
             dsn: Dsn {
                 name: self
                     .parse_if_block("report.dsn.from-name", ctx, &sender_envelope_keys)?
@@ -380,55 +419,49 @@ impl ConfigQueue for Config {
     }
 }
 
-impl IfBlock<Option<String>> {
-    pub fn into_relay_host(self, ctx: &ConfigContext) -> super::Result<IfBlock<Option<RelayHost>>> {
-        Ok(IfBlock {
-            if_then: {
-                let mut if_then = Vec::with_capacity(self.if_then.len());
-
-                for i in self.if_then {
-                    if_then.push(IfThen {
-                        conditions: i.conditions,
-                        then: if let Some(then) = i.then {
-                            Some(
-                                ctx.hosts
-                                    .get(&then)
-                                    .ok_or_else(|| {
-                                        format!(
-                                            "Host {then:?} not found for property \"queue.next-hop\".",
-                                        )
-                                    })?
-                                    .into(),
-                            )
-                        } else {
-                            None
-                        },
-                    });
-                }
+impl IfBlock<Vec<Option<String>>> {
+    pub fn into_relay_host(self, ctx: &ConfigContext) -> super::Result<IfBlock<Vec<RelayHost>>> {
+        let mut if_then = Vec::with_capacity(self.if_then.len());
+        for if_clause in self.if_then {
+            if_then.push(IfThen {
+                conditions: if_clause.conditions,
+                then: Self::map_relay_hosts(ctx, if_clause.then)?,
+            });
+        }
 
-                if_then
-            },
-            default: if let Some(default) = self.default {
-                Some(
-                    ctx.hosts
-                        .get(&default)
-                        .ok_or_else(|| {
-                            format!(
-                                "Relay host {default:?} not found for property \"queue.next-hop\".",
-                            )
-                        })?
-                        .into(),
-                )
-            } else {
-                None
-            },
+        Ok(IfBlock {
+            if_then,
+            default: Self::map_relay_hosts(ctx, self.default)?,
         })
     }
+
+    fn map_relay_hosts(
+        ctx: &ConfigContext,
+        ids: Vec<Option<String>>,
+    ) -> super::Result<Vec<RelayHost>> {
+        let mut hosts: Vec<RelayHost> = Vec::with_capacity(ids.len());
+        for id in ids.into_iter().flatten() {
+            let host = ctx.hosts.get(&id).ok_or_else(|| {
+                format!("Relay host {id:?} not found for property \"queue.next-hop\".")
+            })?;
+            if hosts
+                .first()
+                .map_or(false, |first| first.protocol != host.protocol)
+            {
+                return Err(format!(
+                    "Relay host {id:?} uses a different protocol than other hosts in \"queue.next-hop\"."
+                ));
+            }
+            hosts.push((&id, host).into());
+        }
+        Ok(hosts)
+    }
 }
 
-impl From<&Host> for RelayHost {
-    fn from(host: &Host) -> Self {
+impl From<(&String, &Host)> for RelayHost {
+    fn from((id, host): (&String, &Host)) -> Self {
         RelayHost {
+            id: id.to_string(),
             address: host.address.to_string(),
             port: host.port,
             protocol: host.protocol,
@@ -439,6 +472,21 @@ impl From<&Host> for RelayHost {
             },
             tls_implicit: host.tls_implicit,
             tls_allow_invalid_certs: host.tls_allow_invalid_certs,
+            weight: host.weight,
+        }
+    }
+}
+
+impl ParseValue for RelayStrategy {
+    fn parse_value(key: impl AsKey, value: &str) -> super::Result<Self> {
+        match value {
+            "failover" => Ok(RelayStrategy::Failover),
+            "round-robin" => Ok(RelayStrategy::RoundRobin),
+            _ => Err(format!(
+                "Invalid relay strategy value {:?} for key {:?}.",
+                value,
+                key.as_key()
+            )),
         }
     }
 }
@@ -58,6 +58,10 @@ impl ConfigHost for Config {
             timeout: self
                 .property(("remote", id, "timeout"))?
                 .unwrap_or(Duration::from_secs(60)),
+            weight: self
+                .property::<u32>(("remote", id, "weight"))?
+                .unwrap_or(1)
+                .max(1),
         })
     }
 }
@@ -46,6 +46,11 @@ pub trait ConfigSession {
         ctx: &ConfigContext,
         available_keys: &[EnvelopeKey],
     ) -> super::Result<Vec<Pipe>>;
+    fn parse_milters(
+        &self,
+        ctx: &ConfigContext,
+        available_keys: &[EnvelopeKey],
+    ) -> super::Result<Vec<Milter>>;
 }
 
 impl ConfigSession for Config {
@@ -383,7 +388,12 @@ impl ConfigSession for Config {
             add_date: self
                 .parse_if_block("session.data.add-headers.date", ctx, &available_keys)?
                 .unwrap_or_else(|| IfBlock::new(true)),
+            add_spam: self
+                .parse_if_block("session.data.add-headers.spam", ctx, &available_keys)?
+                .unwrap_or_else(|| IfBlock::new(false)),
             pipe_commands: self.parse_pipes(ctx, &available_keys)?,
+            milters: self.parse_milters(ctx, &available_keys)?,
+            quarantine_path: self.property("session.data.quarantine.path")?,
         })
     }
 
@@ -408,6 +418,69 @@ impl ConfigSession for Config {
         }
         Ok(pipes)
     }
+
+    fn parse_milters(
+        &self,
+        ctx: &ConfigContext,
+        available_keys: &[EnvelopeKey],
+    ) -> super::Result<Vec<Milter>> {
+        let mut milters = Vec::new();
+        for id in self.sub_keys("session.data.milter") {
+            let hostname = self
+                .value_require(("session.data.milter", id, "hostname"))?
+                .to_string();
+            let port = self.property_require(("session.data.milter", id, "port"))?;
+            let protocol_version = self
+                .property(("session.data.milter", id, "options.version"))?
+                .unwrap_or(6);
+            if !matches!(protocol_version, 2..=6) {
+                return Err(format!(
+                    "Unsupported milter protocol version {protocol_version} for milter {id:?}."
+                ));
+            }
+
+            milters.push(Milter {
+                enable: self
+                    .parse_if_block(("session.data.milter", id, "enable"), ctx, available_keys)?
+                    .unwrap_or_else(|| IfBlock::new(true)),
+                hostname,
+                port,
+                timeout_connect: self
+                    .property(("session.data.milter", id, "timeout.connect"))?
+                    .unwrap_or(Duration::from_secs(30)),
+                timeout_command: self
+                    .property(("session.data.milter", id, "timeout.command"))?
+                    .unwrap_or(Duration::from_secs(30)),
+                timeout_data: self
+                    .property(("session.data.milter", id, "timeout.data"))?
+                    .unwrap_or(Duration::from_secs(60)),
+                tls: self
+                    .property(("session.data.milter", id, "tls"))?
+                    .unwrap_or(false),
+                tls_allow_invalid_certs: self
+                    .property(("session.data.milter", id, "allow-invalid-certs"))?
+                    .unwrap_or(false),
+                tempfail_on_error: self
+                    .property(("session.data.milter", id, "options.tempfail-on-error"))?
+                    .unwrap_or(true),
+                max_frame_len: self
+                    .property(("session.data.milter", id, "options.max-response-size"))?
+                    .unwrap_or(52428800),
+                protocol_version,
+                flags_actions: self.property((
+                    "session.data.milter",
+                    id,
+                    "options.flags.actions",
+                ))?,
+                flags_protocol: self.property((
+                    "session.data.milter",
+                    id,
+                    "options.flags.protocol",
+                ))?,
+            });
+        }
+        Ok(milters)
+    }
 }
 
 struct Mechanism {
@@ -422,9 +495,9 @@ impl ParseValue for Mechanism {
                 "PLAIN" => AUTH_PLAIN,
                 "XOAUTH2" => AUTH_XOAUTH2,
                 "OAUTHBEARER" => AUTH_OAUTHBEARER,
-                /*"SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
+                "SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
                 "SCRAM-SHA-256" => AUTH_SCRAM_SHA_256,
-                "SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
+                /*"SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
                 "SCRAM-SHA-1" => AUTH_SCRAM_SHA_1,
                 "XOAUTH" => AUTH_XOAUTH,
                 "9798-M-DSA-SHA1" => AUTH_9798_M_DSA_SHA1,
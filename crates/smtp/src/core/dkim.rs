@@ -0,0 +1,626 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use ahash::AHashMap;
+use mail_auth::common::crypto::{Ed25519Key, RsaKey, Sha256};
+use mail_builder::encoders::base64::base64_encode;
+use mail_parser::{decoders::base64::base64_decode, DateTime};
+use parking_lot::RwLock;
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use rsa::{
+    pkcs1::{EncodeRsaPrivateKey, LineEnding},
+    pkcs8::EncodePublicKey,
+    RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use store::{
+    write::{key::KeySerializer, now, BatchBuilder, Operation, ValueClass},
+    CustomValueKey, Store,
+};
+use tokio::sync::Notify;
+use utils::config::{
+    utils::{AsKey, ParseValue},
+    Config,
+};
+
+use crate::config::DkimSigner;
+
+use super::SMTP;
+
+pub const DKIM_COLLECTION: u8 = u8::MAX - 4;
+
+const DKIM_KEY: u8 = 12;
+
+const RSA_KEY_BITS: usize = 2048;
+const MAX_REFRESH_INTERVAL: u64 = 60 * 60;
+
+pub struct DkimKeyStore {
+    pub store: Arc<Store>,
+    pub algorithm: DkimKeyAlgorithm,
+    pub selector: String,
+    pub rotate_after: Duration,
+    pub overlap: Duration,
+    signers: RwLock<AHashMap<String, Arc<DkimSigner>>>,
+    refresh: Notify,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DkimKey {
+    pub id: u32,
+    pub domain: String,
+    pub selector: String,
+    pub algorithm: DkimKeyAlgorithm,
+    pub private_key: String,
+    pub public_key: String,
+    pub created: u64,
+    pub active_from: u64,
+    pub retire_at: Option<u64>,
+    pub remove_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DkimKeyAlgorithm {
+    #[serde(rename = "rsa")]
+    Rsa,
+    #[serde(rename = "ed25519")]
+    Ed25519,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DkimKeyStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "active")]
+    Active,
+    #[serde(rename = "retired")]
+    Retired,
+}
+
+impl DkimKeyStore {
+    pub fn init(config: &Config, store: Arc<Store>) -> Result<Option<Self>, String> {
+        if !config
+            .property::<bool>("auth.dkim.managed.enable")?
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(DkimKeyStore {
+            store,
+            algorithm: config
+                .property("auth.dkim.managed.algorithm")?
+                .unwrap_or(DkimKeyAlgorithm::Rsa),
+            selector: config
+                .value("auth.dkim.managed.selector")
+                .unwrap_or("stalwart")
+                .to_string(),
+            rotate_after: config
+                .property("auth.dkim.managed.rotate-after")?
+                .unwrap_or(Duration::from_secs(90 * 24 * 60 * 60)),
+            overlap: config
+                .property("auth.dkim.managed.overlap")?
+                .unwrap_or(Duration::from_secs(7 * 24 * 60 * 60)),
+            signers: RwLock::new(AHashMap::new()),
+            refresh: Notify::new(),
+        }))
+    }
+
+    /// Returns the signer of the active key for a domain, if any.
+    pub fn signer(&self, domain: &str) -> Option<Arc<DkimSigner>> {
+        self.signers.read().get(domain).cloned()
+    }
+
+    pub async fn list(&self, domain: Option<&str>) -> store::Result<Vec<DkimKey>> {
+        let mut keys = self
+            .store
+            .iterate(
+                Vec::new(),
+                CustomValueKey {
+                    value: record_key(0),
+                },
+                CustomValueKey {
+                    value: record_key(u32::MAX),
+                },
+                false,
+                true,
+                |keys, _, value| {
+                    keys.push(<DkimKey as store::Deserialize>::deserialize(value)?);
+                    Ok(true)
+                },
+            )
+            .await?;
+        if let Some(domain) = domain {
+            keys.retain(|key| key.domain == domain);
+        }
+        keys.sort_unstable_by(|a, b| (&a.domain, a.active_from).cmp(&(&b.domain, b.active_from)));
+
+        Ok(keys)
+    }
+
+    /// Generates a new key for a domain. Domains without keys start signing
+    /// right away, otherwise the new key is published alongside the current
+    /// one and takes over once the overlap period has elapsed.
+    pub async fn generate(
+        &self,
+        domain: &str,
+        algorithm: Option<DkimKeyAlgorithm>,
+        selector: Option<String>,
+    ) -> store::Result<Result<DkimKey, String>> {
+        let now = now();
+        let domain = domain.to_lowercase();
+        let keys = self.list(domain.as_str().into()).await?;
+        if keys.iter().any(|key| key.active_from > now) {
+            return Ok(Err(format!(
+                "A key rotation is already scheduled for domain {domain:?}."
+            )));
+        }
+        if let Some(selector) = &selector {
+            if keys.iter().any(|key| &key.selector == selector) {
+                return Ok(Err(format!(
+                    "Selector {selector:?} is already in use for domain {domain:?}."
+                )));
+            }
+        }
+
+        let key = match keys
+            .iter()
+            .rev()
+            .find(|key| key.status(now) == DkimKeyStatus::Active)
+        {
+            Some(current) => {
+                self.rotate(
+                    current,
+                    now + self.overlap.as_secs(),
+                    algorithm,
+                    selector,
+                    &keys,
+                )
+                .await?
+            }
+            None => {
+                self.create(&domain, now, algorithm, selector, &keys)
+                    .await?
+            }
+        };
+        self.refresh.notify_one();
+
+        Ok(Ok(key))
+    }
+
+    pub async fn delete(&self, id: u32) -> store::Result<bool> {
+        let key = self
+            .store
+            .get_value::<DkimKey>(CustomValueKey {
+                value: record_key(id),
+            })
+            .await?;
+        if key.is_some() {
+            self.remove(id).await?;
+            self.refresh.notify_one();
+        }
+
+        Ok(key.is_some())
+    }
+
+    /// Removes expired keys, schedules upcoming rotations and reloads the
+    /// signers of the active keys. Returns the time until the next key
+    /// changes state.
+    pub async fn maintain(&self) -> store::Result<Duration> {
+        let now = now();
+        let mut next_event = now + MAX_REFRESH_INTERVAL;
+        let mut keys = self.list(None).await?;
+
+        // Remove keys that no longer need to be published
+        for key in &keys {
+            if key.remove_at.map_or(false, |remove_at| remove_at <= now) {
+                tracing::info!(
+                    context = "dkim",
+                    event = "key-removed",
+                    domain = key.domain,
+                    selector = key.selector,
+                    "DKIM key removed after rotation."
+                );
+                self.remove(key.id).await?;
+            }
+        }
+        keys.retain(|key| key.remove_at.map_or(true, |remove_at| remove_at > now));
+
+        // Schedule the next key ahead of time so that it can be published
+        // before it is used for signing
+        let mut has_changes = false;
+        let mut domains = keys
+            .iter()
+            .map(|key| key.domain.clone())
+            .collect::<Vec<_>>();
+        domains.sort_unstable();
+        domains.dedup();
+        for domain in domains {
+            let domain_keys = keys
+                .iter()
+                .filter(|key| key.domain == domain)
+                .cloned()
+                .collect::<Vec<_>>();
+            if domain_keys.iter().any(|key| key.active_from > now) {
+                continue;
+            }
+            if let Some(current) = domain_keys
+                .iter()
+                .rev()
+                .find(|key| key.status(now) == DkimKeyStatus::Active && key.retire_at.is_none())
+            {
+                let rotate_at = current.active_from + self.rotate_after.as_secs();
+                let schedule_at = rotate_at.saturating_sub(self.overlap.as_secs());
+                if schedule_at <= now {
+                    self.rotate(
+                        current,
+                        std::cmp::max(rotate_at, now + self.overlap.as_secs()),
+                        None,
+                        None,
+                        &domain_keys,
+                    )
+                    .await?;
+                    has_changes = true;
+                } else {
+                    next_event = std::cmp::min(next_event, schedule_at);
+                }
+            }
+        }
+
+        if has_changes {
+            keys = self.list(None).await?;
+        }
+
+        // Reload signers
+        let mut signers = AHashMap::new();
+        keys.sort_unstable_by_key(|key| key.active_from);
+        for key in &keys {
+            for time in [
+                key.active_from,
+                key.retire_at.unwrap_or(0),
+                key.remove_at.unwrap_or(0),
+            ] {
+                if time > now {
+                    next_event = std::cmp::min(next_event, time);
+                }
+            }
+            if key.status(now) == DkimKeyStatus::Active {
+                match key.signer() {
+                    Ok(signer) => {
+                        signers.insert(key.domain.clone(), Arc::new(signer));
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            context = "dkim",
+                            event = "error",
+                            domain = key.domain,
+                            selector = key.selector,
+                            "Failed to load DKIM key: {}",
+                            err
+                        );
+                    }
+                }
+            }
+        }
+        *self.signers.write() = signers;
+
+        Ok(Duration::from_secs(next_event.saturating_sub(now)))
+    }
+
+    pub fn spawn(core: Arc<SMTP>) {
+        tokio::spawn(async move {
+            loop {
+                let keys = if let Some(keys) = &core.dkim_keys {
+                    keys
+                } else {
+                    break;
+                };
+                let next_refresh = match keys.maintain().await {
+                    Ok(next_refresh) => next_refresh,
+                    Err(err) => {
+                        tracing::error!(
+                            context = "dkim",
+                            event = "error",
+                            "Failed to maintain DKIM keys: {}",
+                            err
+                        );
+                        Duration::from_secs(60)
+                    }
+                };
+
+                tokio::select! {
+                    _ = tokio::time::sleep(next_refresh) => {},
+                    _ = keys.refresh.notified() => {},
+                }
+            }
+        });
+    }
+
+    async fn create(
+        &self,
+        domain: &str,
+        active_from: u64,
+        algorithm: Option<DkimKeyAlgorithm>,
+        selector: Option<String>,
+        keys: &[DkimKey],
+    ) -> store::Result<DkimKey> {
+        let algorithm = algorithm.unwrap_or(self.algorithm);
+        let (private_key, public_key) = tokio::task::spawn_blocking(move || algorithm.generate())
+            .await
+            .map_err(|err| store::Error::InternalError(format!("Key generation failed: {err}")))?
+            .map_err(store::Error::InternalError)?;
+        let key = DkimKey {
+            id: self
+                .store
+                .assign_document_id(u32::MAX, DKIM_COLLECTION)
+                .await?,
+            domain: domain.to_string(),
+            selector: selector.unwrap_or_else(|| self.new_selector(active_from, keys)),
+            algorithm,
+            private_key,
+            public_key,
+            created: now(),
+            active_from,
+            retire_at: None,
+            remove_at: None,
+        };
+        key.signer().map_err(store::Error::InternalError)?;
+
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(DKIM_COLLECTION)
+            .create_document(key.id)
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: record_key(key.id),
+                },
+                set: Some(key.serialize()?),
+            });
+        self.store.write(batch.build()).await?;
+
+        tracing::info!(
+            context = "dkim",
+            event = "key-created",
+            domain = key.domain,
+            selector = key.selector,
+            active_from = key.active_from,
+            "New DKIM key created, publish record {:?} as {:?}.",
+            key.dns_record(),
+            key.dns_name()
+        );
+
+        Ok(key)
+    }
+
+    async fn rotate(
+        &self,
+        current: &DkimKey,
+        active_from: u64,
+        algorithm: Option<DkimKeyAlgorithm>,
+        selector: Option<String>,
+        keys: &[DkimKey],
+    ) -> store::Result<DkimKey> {
+        let next_key = self
+            .create(
+                &current.domain,
+                active_from,
+                algorithm.or(Some(current.algorithm)),
+                selector,
+                keys,
+            )
+            .await?;
+
+        // Keep the current key published until signatures made with it
+        // are unlikely to be verified anymore
+        let mut current = current.clone();
+        current.retire_at = active_from.into();
+        current.remove_at = (active_from + self.overlap.as_secs()).into();
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(DKIM_COLLECTION)
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: record_key(current.id),
+                },
+                set: Some(current.serialize()?),
+            });
+        self.store.write(batch.build()).await?;
+
+        Ok(next_key)
+    }
+
+    async fn remove(&self, id: u32) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(DKIM_COLLECTION)
+            .delete_document(id)
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: record_key(id),
+                },
+                set: None,
+            });
+        self.store.write(batch.build()).await
+    }
+
+    fn new_selector(&self, active_from: u64, keys: &[DkimKey]) -> String {
+        let date = DateTime::from_timestamp(active_from as i64);
+        let selector = format!(
+            "{}-{:04}{:02}{:02}",
+            self.selector, date.year, date.month, date.day
+        );
+        if !keys.iter().any(|key| key.selector == selector) {
+            selector
+        } else {
+            (2..)
+                .map(|num| format!("{selector}-{num}"))
+                .find(|selector| !keys.iter().any(|key| &key.selector == selector))
+                .unwrap()
+        }
+    }
+}
+
+impl DkimKey {
+    pub fn status(&self, now: u64) -> DkimKeyStatus {
+        if self.active_from > now {
+            DkimKeyStatus::Pending
+        } else if self.retire_at.map_or(true, |retire_at| retire_at > now) {
+            DkimKeyStatus::Active
+        } else {
+            DkimKeyStatus::Retired
+        }
+    }
+
+    pub fn dns_name(&self) -> String {
+        format!("{}._domainkey.{}", self.selector, self.domain)
+    }
+
+    pub fn dns_record(&self) -> String {
+        format!(
+            "v=DKIM1; k={}; p={}",
+            match self.algorithm {
+                DkimKeyAlgorithm::Rsa => "rsa",
+                DkimKeyAlgorithm::Ed25519 => "ed25519",
+            },
+            self.public_key
+        )
+    }
+
+    fn signer(&self) -> Result<DkimSigner, String> {
+        let headers = ["From", "To", "Date", "Subject", "Message-ID"]
+            .iter()
+            .map(|header| header.to_string())
+            .collect::<Vec<_>>();
+
+        match self.algorithm {
+            DkimKeyAlgorithm::Rsa => {
+                let key = RsaKey::<Sha256>::from_rsa_pem(&self.private_key)
+                    .map_err(|err| format!("Failed to build RSA key: {err}"))?;
+                Ok(DkimSigner::RsaSha256(
+                    mail_auth::dkim::DkimSigner::from_key(key)
+                        .domain(&self.domain)
+                        .selector(&self.selector)
+                        .headers(headers),
+                ))
+            }
+            DkimKeyAlgorithm::Ed25519 => {
+                let (Some(private_key), Some(public_key)) = (
+                    base64_decode(self.private_key.as_bytes()),
+                    base64_decode(self.public_key.as_bytes()),
+                ) else {
+                    return Err("Failed to base64 decode ED25519 key.".to_string());
+                };
+                let key = Ed25519Key::from_seed_and_public_key(&private_key, &public_key)
+                    .map_err(|err| format!("Failed to build ED25519 key: {err}"))?;
+                Ok(DkimSigner::Ed25519Sha256(
+                    mail_auth::dkim::DkimSigner::from_key(key)
+                        .domain(&self.domain)
+                        .selector(&self.selector)
+                        .headers(headers),
+                ))
+            }
+        }
+    }
+
+    fn serialize(&self) -> store::Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|err| {
+            store::Error::InternalError(format!("Failed to serialize DKIM key: {err}"))
+        })
+    }
+}
+
+impl DkimKeyAlgorithm {
+    /// Returns a new private key and the base64 encoded public key.
+    fn generate(self) -> Result<(String, String), String> {
+        match self {
+            DkimKeyAlgorithm::Rsa => {
+                let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), RSA_KEY_BITS)
+                    .map_err(|err| format!("Failed to generate RSA key: {err}"))?;
+                let public_key = RsaPublicKey::from(&private_key)
+                    .to_public_key_der()
+                    .map_err(|err| format!("Failed to encode RSA public key: {err}"))?;
+                let private_key = private_key
+                    .to_pkcs1_pem(LineEnding::LF)
+                    .map_err(|err| format!("Failed to encode RSA private key: {err}"))?;
+
+                Ok((
+                    private_key.to_string(),
+                    encode_base64(public_key.as_bytes()),
+                ))
+            }
+            DkimKeyAlgorithm::Ed25519 => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                    .map_err(|_| "Failed to generate ED25519 key.".to_string())?;
+                let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                    .map_err(|err| format!("Failed to parse ED25519 key: {err}"))?;
+
+                // The 32-byte seed follows the 16-byte PKCS#8 v2 header
+                Ok((
+                    encode_base64(&pkcs8.as_ref()[16..48]),
+                    encode_base64(key_pair.public_key().as_ref()),
+                ))
+            }
+        }
+    }
+}
+
+impl ParseValue for DkimKeyAlgorithm {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        match value {
+            "rsa" | "rsa-sha256" | "rsa-sha-256" => Ok(DkimKeyAlgorithm::Rsa),
+            "ed25519" | "ed25519-sha256" | "ed25519-sha-256" => Ok(DkimKeyAlgorithm::Ed25519),
+            _ => Err(format!(
+                "Invalid algorithm {:?} for key {:?}.",
+                value,
+                key.as_key()
+            )),
+        }
+    }
+}
+
+impl store::Deserialize for DkimKey {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        serde_json::from_slice(bytes).map_err(|err| {
+            store::Error::InternalError(format!("Failed to deserialize DKIM key: {err}"))
+        })
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
+
+fn record_key(id: u32) -> Vec<u8> {
+    KeySerializer::new((std::mem::size_of::<u32>() * 2) + 1)
+        .write(u32::MAX)
+        .write(DKIM_KEY)
+        .write(id)
+        .finalize()
+}
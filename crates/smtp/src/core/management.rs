@@ -23,7 +23,7 @@
 
 use std::{borrow::Cow, fmt::Display, net::IpAddr, sync::Arc, time::Instant};
 
-use directory::Type;
+use directory::{Protocol, Type};
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
 use hyper::{
     body::{self, Bytes},
@@ -41,17 +41,27 @@ use tokio::{
     sync::oneshot,
 };
 
-use utils::listener::{limiter::InFlight, SessionManager};
+use utils::{
+    config::utils::ParseValue,
+    listener::{limiter::InFlight, SessionManager},
+};
 
 use crate::{
-    queue::{self, instant_to_timestamp, InstantFromTimestamp, QueueId, Status},
+    queue::{
+        self, adaptive::AdaptiveLimiter, instant_to_timestamp, InstantFromTimestamp, QueueId,
+        Status,
+    },
     reporting::{
         self,
         scheduler::{ReportKey, ReportPolicy, ReportType, ReportValue},
+        store::IncomingReportQuery,
     },
 };
 
-use super::{SmtpAdminSessionManager, SMTP};
+use super::{
+    dkim::{self, DkimKeyAlgorithm, DkimKeyStatus},
+    SmtpAdminSessionManager, SMTP,
+};
 
 #[derive(Debug)]
 pub enum QueueRequest {
@@ -142,6 +152,50 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Throttle {
+    pub mx: String,
+    pub delay: u64,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    pub next_attempt: Option<DateTime>,
+    pub deferrals: u64,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub last_deferral: DateTime,
+    pub last_response: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DkimKey {
+    pub id: u32,
+    pub domain: String,
+    pub selector: String,
+    pub algorithm: DkimKeyAlgorithm,
+    pub status: DkimKeyStatus,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub active_from: DateTime,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    pub retire_at: Option<DateTime>,
+    #[serde(deserialize_with = "deserialize_maybe_datetime")]
+    #[serde(serialize_with = "serialize_maybe_datetime")]
+    pub remove_at: Option<DateTime>,
+    pub dns_record: DkimDnsRecord,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DkimDnsRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub value: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Report {
     pub domain: String,
@@ -255,7 +309,7 @@ impl SMTP {
                         .queue
                         .config
                         .management_lookup
-                        .authenticate(&Credentials::Plain { username, secret })
+                        .authenticate(&Credentials::Plain { username, secret }, Protocol::Http)
                         .await
                     {
                         Ok(Some(principal)) if principal.typ == Type::Superuser => {
@@ -530,6 +584,44 @@ impl SMTP {
                     Some(error) => error.into_bad_request(),
                 }
             }
+            (&Method::GET, "queue", "throttle") => {
+                let mut mx = None;
+                let mut error = None;
+
+                if let Some(query) = uri.query() {
+                    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                        match key.as_ref() {
+                            "mx" => {
+                                mx = value.to_lowercase().into();
+                            }
+                            _ => {
+                                error = format!("Invalid parameter {key:?}.").into();
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match error {
+                    None => {
+                        let now = Instant::now();
+                        let mut result = self
+                            .queue
+                            .adaptive
+                            .iter()
+                            .filter(|entry| mx.as_ref().map_or(true, |mx| entry.key() == mx))
+                            .map(|entry| Throttle::from((entry.key(), entry.value(), now)))
+                            .collect::<Vec<_>>();
+                        result.sort_unstable_by(|a, b| a.mx.cmp(&b.mx));
+
+                        (
+                            StatusCode::OK,
+                            serde_json::to_string(&Response { data: result }).unwrap_or_default(),
+                        )
+                    }
+                    Some(error) => error.into_bad_request(),
+                }
+            }
             (&Method::GET, "report", "list") => {
                 let mut domain = None;
                 let mut type_ = None;
@@ -655,6 +747,182 @@ impl SMTP {
                     Some(error) => error.into_bad_request(),
                 }
             }
+            (&Method::GET, "incoming", "list") => match uri.parse_incoming_query() {
+                Ok(query) => match &self.report.store {
+                    Some(store) => store.query(&query).await.into_response(),
+                    None => incoming_not_enabled(),
+                },
+                Err(error) => error.into_bad_request(),
+            },
+            (&Method::GET, "incoming", "get") => {
+                let mut report_ids = Vec::new();
+                let mut error = None;
+
+                if let Some(query) = uri.query() {
+                    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                        match key.as_ref() {
+                            "id" | "ids" => match value.parse_incoming_ids() {
+                                Ok(ids) => {
+                                    report_ids = ids;
+                                }
+                                Err(reason) => {
+                                    error = reason.into();
+                                    break;
+                                }
+                            },
+                            _ => {
+                                error = format!("Invalid parameter {key:?}.").into();
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match (error, &self.report.store) {
+                    (None, Some(store)) => {
+                        let mut reports = Vec::with_capacity(report_ids.len());
+                        let mut result = Ok(());
+                        for id in report_ids {
+                            match store.read(id).await {
+                                Ok(report) => reports.push(report),
+                                Err(err) => {
+                                    result = Err(err);
+                                    break;
+                                }
+                            }
+                        }
+                        result.map(|_| reports).into_response()
+                    }
+                    (None, None) => incoming_not_enabled(),
+                    (Some(error), _) => error.into_bad_request(),
+                }
+            }
+            (&Method::GET, "incoming", "aggregate") => match uri.parse_incoming_query() {
+                Ok(query) => match &self.report.store {
+                    Some(store) => store.aggregate(&query).await.into_response(),
+                    None => incoming_not_enabled(),
+                },
+                Err(error) => error.into_bad_request(),
+            },
+            (&Method::GET, "dkim", "list" | "dns") => {
+                let mut domain = None;
+                let mut error = None;
+
+                if let Some(query) = uri.query() {
+                    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                        match key.as_ref() {
+                            "domain" => {
+                                domain = value.to_lowercase().into();
+                            }
+                            _ => {
+                                error = format!("Invalid parameter {key:?}.").into();
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match (error, &self.dkim_keys) {
+                    (None, Some(keys)) => {
+                        let now = store::write::now();
+                        let keys = keys.list(domain.as_deref()).await;
+                        if path_2 == "dns" {
+                            keys.map(|keys| {
+                                keys.iter().map(DkimDnsRecord::from).collect::<Vec<_>>()
+                            })
+                            .into_response()
+                        } else {
+                            keys.map(|keys| {
+                                keys.iter()
+                                    .map(|key| DkimKey::from((key, now)))
+                                    .collect::<Vec<_>>()
+                            })
+                            .into_response()
+                        }
+                    }
+                    (None, None) => dkim_not_enabled(),
+                    (Some(error), _) => error.into_bad_request(),
+                }
+            }
+            (&Method::GET, "dkim", "generate") => {
+                let mut domain = None;
+                let mut algorithm = None;
+                let mut selector = None;
+                let mut error = None;
+
+                if let Some(query) = uri.query() {
+                    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                        match key.as_ref() {
+                            "domain" if !value.is_empty() => {
+                                domain = value.to_lowercase().into();
+                            }
+                            "algorithm" => match DkimKeyAlgorithm::parse_value("algorithm", &value)
+                            {
+                                Ok(value) => {
+                                    algorithm = value.into();
+                                }
+                                Err(reason) => {
+                                    error = reason.into();
+                                    break;
+                                }
+                            },
+                            "selector" if !value.is_empty() => {
+                                selector = value.into_owned().into();
+                            }
+                            _ => {
+                                error = format!("Invalid parameter {key:?}.").into();
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match (error, domain, &self.dkim_keys) {
+                    (None, Some(domain), Some(keys)) => {
+                        match keys.generate(&domain, algorithm, selector).await {
+                            Ok(Ok(key)) => {
+                                Ok(DkimKey::from((&key, store::write::now()))).into_response()
+                            }
+                            Ok(Err(reason)) => reason.into_bad_request(),
+                            Err(err) => Err::<(), _>(err).into_response(),
+                        }
+                    }
+                    (None, _, None) => dkim_not_enabled(),
+                    (None, None, _) => "Missing domain parameter.".to_string().into_bad_request(),
+                    (Some(error), _, _) => error.into_bad_request(),
+                }
+            }
+            (&Method::GET, "dkim", "delete") => {
+                let mut id = None;
+                let mut error = None;
+
+                if let Some(query) = uri.query() {
+                    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                        match key.as_ref() {
+                            "id" => match value.parse::<u32>() {
+                                Ok(value) => {
+                                    id = value.into();
+                                }
+                                Err(_) => {
+                                    error = format!("Invalid key id {value:?}.").into();
+                                    break;
+                                }
+                            },
+                            _ => {
+                                error = format!("Invalid parameter {key:?}.").into();
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match (error, id, &self.dkim_keys) {
+                    (None, Some(id), Some(keys)) => keys.delete(id).await.into_response(),
+                    (None, _, None) => dkim_not_enabled(),
+                    (None, None, _) => "Missing id parameter.".to_string().into_bad_request(),
+                    (Some(error), _, _) => error.into_bad_request(),
+                }
+            }
             _ => (
                 StatusCode::NOT_FOUND,
                 format!(
@@ -825,6 +1093,55 @@ impl From<&queue::Message> for Message {
     }
 }
 
+impl From<(&String, &AdaptiveLimiter, Instant)> for Throttle {
+    fn from((mx, limiter, now): (&String, &AdaptiveLimiter, Instant)) -> Self {
+        Throttle {
+            mx: mx.clone(),
+            delay: limiter.delay.as_secs(),
+            next_attempt: if limiter.next_attempt > now {
+                DateTime::from_timestamp(instant_to_timestamp(now, limiter.next_attempt) as i64)
+                    .into()
+            } else {
+                None
+            },
+            deferrals: limiter.deferrals,
+            last_deferral: DateTime::from_timestamp(limiter.last_deferral as i64),
+            last_response: limiter.last_response.clone(),
+        }
+    }
+}
+
+impl From<(&dkim::DkimKey, u64)> for DkimKey {
+    fn from((key, now): (&dkim::DkimKey, u64)) -> Self {
+        DkimKey {
+            id: key.id,
+            domain: key.domain.clone(),
+            selector: key.selector.clone(),
+            algorithm: key.algorithm,
+            status: key.status(now),
+            created: DateTime::from_timestamp(key.created as i64),
+            active_from: DateTime::from_timestamp(key.active_from as i64),
+            retire_at: key
+                .retire_at
+                .map(|retire_at| DateTime::from_timestamp(retire_at as i64)),
+            remove_at: key
+                .remove_at
+                .map(|remove_at| DateTime::from_timestamp(remove_at as i64)),
+            dns_record: DkimDnsRecord::from(key),
+        }
+    }
+}
+
+impl From<&dkim::DkimKey> for DkimDnsRecord {
+    fn from(key: &dkim::DkimKey) -> Self {
+        DkimDnsRecord {
+            name: key.dns_name(),
+            type_: "TXT".to_string(),
+            value: key.dns_record(),
+        }
+    }
+}
+
 impl From<(&ReportKey, &ReportValue)> for Report {
     fn from((key, value): (&ReportKey, &ReportValue)) -> Self {
         match (key, value) {
@@ -862,8 +1179,10 @@ impl Display for ReportKey {
 
 trait ParseValues {
     fn parse_timestamp(&self) -> Result<Instant, String>;
+    fn parse_date(&self) -> Result<u64, String>;
     fn parse_queue_ids(&self) -> Result<Vec<QueueId>, String>;
     fn parse_report_ids(&self) -> Result<Vec<ReportKey>, String>;
+    fn parse_incoming_ids(&self) -> Result<Vec<u32>, String>;
 }
 
 impl ParseValues for Cow<'_, str> {
@@ -878,6 +1197,13 @@ impl ParseValues for Cow<'_, str> {
         Err(format!("Invalid timestamp {self:?}."))
     }
 
+    fn parse_date(&self) -> Result<u64, String> {
+        DateTime::parse_rfc3339(self.as_ref())
+            .filter(|dt| dt.to_timestamp() >= 0)
+            .map(|dt| dt.to_timestamp() as u64)
+            .ok_or_else(|| format!("Invalid timestamp {self:?}."))
+    }
+
     fn parse_queue_ids(&self) -> Result<Vec<QueueId>, String> {
         let mut ids = Vec::new();
         for id in self.split(',') {
@@ -922,6 +1248,117 @@ impl ParseValues for Cow<'_, str> {
         }
         Ok(ids)
     }
+
+    fn parse_incoming_ids(&self) -> Result<Vec<u32>, String> {
+        let mut ids = Vec::new();
+        for id in self.split(',') {
+            if !id.is_empty() {
+                match id.parse() {
+                    Ok(id) => {
+                        ids.push(id);
+                    }
+                    Err(_) => {
+                        return Err(format!("Failed to parse id {id:?}."));
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+}
+
+trait ParseIncomingQuery {
+    fn parse_incoming_query(&self) -> Result<IncomingReportQuery, String>;
+}
+
+impl ParseIncomingQuery for Uri {
+    fn parse_incoming_query(&self) -> Result<IncomingReportQuery, String> {
+        let mut query = IncomingReportQuery::default();
+
+        if let Some(params) = self.query() {
+            for (key, value) in form_urlencoded::parse(params.as_bytes()) {
+                match key.as_ref() {
+                    "type" => match value.as_ref() {
+                        "dmarc" => {
+                            query.type_ = ReportType::Dmarc(()).into();
+                        }
+                        "tls" => {
+                            query.type_ = ReportType::Tls(()).into();
+                        }
+                        _ => {
+                            return Err(format!("Invalid report type {value:?}."));
+                        }
+                    },
+                    "domain" => {
+                        query.domain = value.into_owned().into();
+                    }
+                    "ip" => match value.parse() {
+                        Ok(ip) => {
+                            query.source_ip = Some(ip);
+                        }
+                        Err(_) => {
+                            return Err(format!("Invalid IP address {value:?}."));
+                        }
+                    },
+                    "after" => {
+                        query.after = value.parse_date()?.into();
+                    }
+                    "before" => {
+                        query.before = value.parse_date()?.into();
+                    }
+                    _ => {
+                        return Err(format!("Invalid parameter {key:?}."));
+                    }
+                }
+            }
+        }
+
+        Ok(query)
+    }
+}
+
+trait IntoResponse {
+    fn into_response(self) -> (StatusCode, String);
+}
+
+impl<T: Serialize> IntoResponse for store::Result<T> {
+    fn into_response(self) -> (StatusCode, String) {
+        match self {
+            Ok(result) => (
+                StatusCode::OK,
+                serde_json::to_string(&Response { data: result }).unwrap_or_default(),
+            ),
+            Err(err) => {
+                tracing::error!(
+                    context = "management",
+                    event = "error",
+                    "Failed to query report store: {}",
+                    err
+                );
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "{\"error\": \"internal-error\", \"details\": \"Resource unavailable, try again later.\"}"
+                        .to_string(),
+                )
+            }
+        }
+    }
+}
+
+fn incoming_not_enabled() -> (StatusCode, String) {
+    (
+        StatusCode::BAD_REQUEST,
+        "{\"error\": \"not-enabled\", \"details\": \"Incoming report storage is not enabled.\"}"
+            .to_string(),
+    )
+}
+
+fn dkim_not_enabled() -> (StatusCode, String) {
+    (
+        StatusCode::BAD_REQUEST,
+        "{\"error\": \"not-enabled\", \"details\": \"Managed DKIM keys are not enabled.\"}"
+            .to_string(),
+    )
 }
 
 trait BadRequest {
@@ -971,14 +1408,14 @@ where
     }
 }
 
-fn serialize_datetime<S>(value: &DateTime, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_datetime<S>(value: &DateTime, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     serializer.serialize_str(&value.to_rfc3339())
 }
 
-fn deserialize_datetime<'de, D>(deserializer: D) -> Result<DateTime, D::Error>
+pub(crate) fn deserialize_datetime<'de, D>(deserializer: D) -> Result<DateTime, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -57,13 +57,21 @@ use crate::{
     outbound::{
         dane::{DnssecResolver, Tlsa},
         mta_sts,
+        pool::ConnectionPool,
+        relay::RelayState,
     },
-    queue::{self, DomainPart, QueueId, QuotaLimiter},
-    reporting,
+    queue::{
+        self, adaptive::AdaptiveLimiter, store::QueueStore, DomainPart, QueueId, QuotaLimiter,
+    },
+    reporting::{self, store::ReportStore},
 };
 
-use self::throttle::{Limiter, ThrottleKey, ThrottleKeyHasherBuilder};
+use self::{
+    dkim::DkimKeyStore,
+    throttle::{Limiter, ThrottleKey, ThrottleKeyHasherBuilder},
+};
 
+pub mod dkim;
 pub mod if_block;
 pub mod management;
 pub mod params;
@@ -101,6 +109,7 @@ pub struct SMTP {
     pub mail_auth: MailAuthConfig,
     pub report: ReportCore,
     pub sieve: SieveCore,
+    pub dkim_keys: Option<DkimKeyStore>,
     #[cfg(feature = "local_delivery")]
     pub delivery_tx: mpsc::Sender<DeliveryEvent>,
 }
@@ -140,14 +149,19 @@ pub struct QueueCore {
     pub config: QueueConfig,
     pub throttle: DashMap<ThrottleKey, Limiter, ThrottleKeyHasherBuilder>,
     pub quota: DashMap<ThrottleKey, Arc<QuotaLimiter>, ThrottleKeyHasherBuilder>,
+    pub adaptive: DashMap<String, AdaptiveLimiter>,
     pub tx: mpsc::Sender<queue::Event>,
     pub id_seq: AtomicU32,
     pub connectors: TlsConnectors,
+    pub connections: ConnectionPool,
+    pub relays: RelayState,
+    pub store: Option<QueueStore>,
 }
 
 pub struct ReportCore {
     pub config: ReportConfig,
     pub tx: mpsc::Sender<reporting::Event>,
+    pub store: Option<ReportStore>,
 }
 
 pub struct TlsConnectors {
@@ -180,6 +194,7 @@ pub struct Session<T: AsyncWrite + AsyncRead> {
 pub struct SessionData {
     pub local_ip: IpAddr,
     pub remote_ip: IpAddr,
+    pub remote_port: u16,
     pub helo_domain: String,
 
     pub mail_from: Option<SessionAddress>,
@@ -248,10 +263,11 @@ pub struct SessionParameters {
 }
 
 impl SessionData {
-    pub fn new(local_ip: IpAddr, remote_ip: IpAddr) -> Self {
+    pub fn new(local_ip: IpAddr, remote_ip: IpAddr, remote_port: u16) -> Self {
         SessionData {
             local_ip,
             remote_ip,
+            remote_port,
             helo_domain: String::new(),
             mail_from: None,
             rcpt_to: Vec::new(),
@@ -415,6 +431,7 @@ static ref SIEVE: Arc<ServerInstance> = Arc::new(utils::listener::ServerInstance
     hostname: "localhost".to_string(),
     data: "localhost".to_string(),
     tls_acceptor: None,
+    tls_end_points: Default::default(),
     is_tls_implicit: true,
     limiter: utils::listener::limiter::ConcurrencyLimiter::new(0),
     shutdown_rx: tokio::sync::watch::channel(false).1,
@@ -514,6 +531,7 @@ impl SessionData {
         SessionData {
             local_ip: IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
             remote_ip: IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
+            remote_port: 0,
             helo_domain: "localhost".into(),
             mail_from,
             rcpt_to,
@@ -21,9 +21,17 @@
  * for more details.
 */
 
+use directory::{
+    scram::{ScramResult, ScramServer},
+    Protocol,
+};
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
+use smtp_proto::{
+    IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_SCRAM_SHA_256,
+    AUTH_SCRAM_SHA_256_PLUS, AUTH_XOAUTH2,
+};
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::core::Session;
@@ -31,37 +39,66 @@ use crate::core::Session;
 pub struct SaslToken {
     mechanism: u64,
     credentials: Credentials<String>,
+    scram: Option<Box<ScramServer>>,
+    authenticated_as: Option<String>,
 }
 
 impl SaslToken {
-    pub fn from_mechanism(mechanism: u64) -> Option<SaslToken> {
+    pub fn from_mechanism(mechanism: u64, tls_end_point: Option<Vec<u8>>) -> Option<SaslToken> {
         match mechanism {
-            AUTH_PLAIN | AUTH_LOGIN => SaslToken {
+            AUTH_PLAIN | AUTH_LOGIN => SaslToken::new(
                 mechanism,
-                credentials: Credentials::Plain {
+                Credentials::Plain {
                     username: String::new(),
                     secret: String::new(),
                 },
-            }
+            )
             .into(),
-            AUTH_OAUTHBEARER => SaslToken {
+            AUTH_OAUTHBEARER => SaslToken::new(
                 mechanism,
-                credentials: Credentials::OAuthBearer {
+                Credentials::OAuthBearer {
                     token: String::new(),
                 },
-            }
+            )
             .into(),
-            AUTH_XOAUTH2 => SaslToken {
+            AUTH_XOAUTH2 => SaslToken::new(
                 mechanism,
-                credentials: Credentials::XOauth2 {
+                Credentials::XOauth2 {
                     username: String::new(),
                     secret: String::new(),
                 },
-            }
+            )
             .into(),
+            AUTH_SCRAM_SHA_256 | AUTH_SCRAM_SHA_256_PLUS
+                if mechanism == AUTH_SCRAM_SHA_256 || tls_end_point.is_some() =>
+            {
+                SaslToken {
+                    scram: Box::new(ScramServer::new(
+                        mechanism == AUTH_SCRAM_SHA_256_PLUS,
+                        tls_end_point,
+                    ))
+                    .into(),
+                    ..SaslToken::new(
+                        mechanism,
+                        Credentials::OAuthBearer {
+                            token: String::new(),
+                        },
+                    )
+                }
+                .into()
+            }
             _ => None,
         }
     }
+
+    fn new(mechanism: u64, credentials: Credentials<String>) -> Self {
+        SaslToken {
+            mechanism,
+            credentials,
+            scram: None,
+            authenticated_as: None,
+        }
+    }
 }
 
 impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
@@ -70,6 +107,10 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         token: &mut SaslToken,
         response: &[u8],
     ) -> Result<bool, ()> {
+        if token.scram.is_some() {
+            return self.handle_scram_response(token, response).await;
+        }
+
         if response.is_empty() {
             match (token.mechanism, &token.credentials) {
                 (AUTH_PLAIN | AUTH_XOAUTH2 | AUTH_OAUTHBEARER, _) => {
@@ -173,6 +214,70 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
+    async fn handle_scram_response(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        let scram = token.scram.as_mut().unwrap();
+        if response.is_empty() {
+            if let Some(authenticated_as) = token.authenticated_as.take() {
+                return self.auth_success(authenticated_as).await;
+            } else if scram.is_new() {
+                self.write(b"334 \r\n").await?;
+                return Ok(true);
+            }
+        } else if let Some(response) = base64_decode(response) {
+            let directory = if let Some(directory) = &self.params.auth_directory {
+                directory.clone()
+            } else {
+                return self.authenticate_unavailable().await;
+            };
+
+            match scram.next(directory.as_ref(), &response).await {
+                Ok(ScramResult::Continue(challenge)) => {
+                    self.write_challenge(&challenge).await?;
+                    return Ok(true);
+                }
+                Ok(ScramResult::Success {
+                    principal,
+                    server_final,
+                }) => {
+                    token.authenticated_as = principal.name.into();
+                    self.write_challenge(&server_final).await?;
+                    return Ok(true);
+                }
+                Ok(ScramResult::Failure(reason)) => {
+                    tracing::debug!(
+                        parent: &self.span,
+                        context = "auth",
+                        event = "authenticate",
+                        result = "failed",
+                        reason = reason
+                    );
+                    return self
+                        .auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
+                        .await;
+                }
+                Err(_) => {
+                    self.write(b"454 4.7.0 Temporary authentication failure\r\n")
+                        .await?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
+    }
+
+    async fn write_challenge(&mut self, challenge: &[u8]) -> Result<(), ()> {
+        let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 8);
+        buf.extend_from_slice(b"334 ");
+        buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+        buf.extend_from_slice(b"\r\n");
+        self.write(&buf).await
+    }
+
     pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
         if let Some(lookup) = &self.params.auth_directory {
             let authenticated_as = match &credentials {
@@ -180,40 +285,60 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
                 | Credentials::XOauth2 { username, .. }
                 | Credentials::OAuthBearer { token: username } => username.to_string(),
             };
-            if let Ok(is_authenticated) =
-                lookup.authenticate(&credentials).await.map(|r| r.is_some())
+            if let Ok(is_authenticated) = lookup
+                .authenticate(&credentials, Protocol::Smtp)
+                .await
+                .map(|r| r.is_some())
             {
-                tracing::debug!(
-                    parent: &self.span,
-                    context = "auth",
-                    event = "authenticate",
-                    result = if is_authenticated {"success"} else {"failed"}
-                );
                 return if is_authenticated {
-                    self.data.authenticated_as = authenticated_as;
-                    self.eval_post_auth_params().await;
-                    self.write(b"235 2.7.0 Authentication succeeded.\r\n")
-                        .await?;
-                    Ok(false)
+                    self.auth_success(authenticated_as).await
                 } else {
+                    tracing::debug!(
+                        parent: &self.span,
+                        context = "auth",
+                        event = "authenticate",
+                        result = "failed"
+                    );
                     self.auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
                         .await
                 };
             }
+            self.write(b"454 4.7.0 Temporary authentication failure\r\n")
+                .await?;
+
+            Ok(false)
         } else {
-            tracing::warn!(
-                parent: &self.span,
-                context = "auth",
-                event = "error",
-                "No lookup list configured for authentication."
-            );
+            self.authenticate_unavailable().await
         }
+    }
+
+    async fn authenticate_unavailable(&mut self) -> Result<bool, ()> {
+        tracing::warn!(
+            parent: &self.span,
+            context = "auth",
+            event = "error",
+            "No lookup list configured for authentication."
+        );
         self.write(b"454 4.7.0 Temporary authentication failure\r\n")
             .await?;
 
         Ok(false)
     }
 
+    async fn auth_success(&mut self, authenticated_as: String) -> Result<bool, ()> {
+        tracing::debug!(
+            parent: &self.span,
+            context = "auth",
+            event = "authenticate",
+            result = "success"
+        );
+        self.data.authenticated_as = authenticated_as;
+        self.eval_post_auth_params().await;
+        self.write(b"235 2.7.0 Authentication succeeded.\r\n")
+            .await?;
+        Ok(false)
+    }
+
     pub async fn auth_error(&mut self, response: &[u8]) -> Result<bool, ()> {
         tokio::time::sleep(self.params.auth_errors_wait).await;
         self.data.auth_errors += 1;
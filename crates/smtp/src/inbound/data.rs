@@ -31,21 +31,25 @@ use std::{
 
 use mail_auth::{
     common::headers::HeaderWriter, dmarc, AuthenticatedMessage, AuthenticationResults, DkimResult,
-    DmarcResult, ReceivedSpf,
+    DmarcResult, ReceivedSpf, SpfResult,
 };
 use mail_builder::headers::{date::Date, message_id::generate_message_id_header};
 use smtp_proto::{
     MAIL_BY_RETURN, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
 };
+#[cfg(feature = "local_delivery")]
+use tokio::sync::oneshot;
 use tokio::{
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
     process::Command,
 };
+#[cfg(feature = "local_delivery")]
+use utils::ipc::{DeliveryEvent, SpamClassification};
 
 use crate::{
     config::DNSBL_FROM,
     core::{scripts::ScriptResult, Session, SessionAddress, State},
-    queue::{self, DomainPart, Message, SimpleEnvelope},
+    queue::{self, DomainPart, Message, SimpleEnvelope, MAIL_SENDER_AUTHENTICATED},
     reporting::analysis::AnalyzeReport,
 };
 
@@ -284,8 +288,23 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
             }
         }
 
+        // Remove any spam headers not added by this server
+        let stripped_message = if *dc.add_spam.eval(self).await {
+            strip_spam_headers(&raw_message).map(Arc::new)
+        } else {
+            None
+        };
+
+        // Run milters
+        let mut edited_message = match self
+            .run_milters(stripped_message.as_ref().unwrap_or(&raw_message))
+            .await
+        {
+            Ok(modified_message) => modified_message.map(Arc::new).or(stripped_message),
+            Err(response) => return response,
+        };
+
         // Pipe message
-        let mut edited_message = None;
         for pipe in &dc.pipe_commands {
             if let Some(command_) = pipe.command.eval(self).await {
                 let piped_message = edited_message.as_ref().unwrap_or(&raw_message).clone();
@@ -399,10 +418,35 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
             }
         }
 
+        // Spam classification
+        #[cfg(feature = "local_delivery")]
+        let spam_classification = if *dc.add_spam.eval(self).await {
+            self.classify_spam(edited_message.as_ref().unwrap_or(&raw_message).clone())
+                .await
+        } else {
+            None
+        };
+
         // Build message
         let mail_from = self.data.mail_from.clone().unwrap();
         let rcpt_to = std::mem::take(&mut self.data.rcpt_to);
+        let is_sender_authenticated = !self.data.authenticated_as.is_empty()
+            || self
+                .data
+                .spf_mail_from
+                .as_ref()
+                .map_or(false, |spf| spf.result() == SpfResult::Pass)
+            || (!mail_from.domain.is_empty()
+                && dkim_output.iter().any(|output| {
+                    matches!(output.result(), DkimResult::Pass)
+                        && output.signature().map_or(false, |signature| {
+                            signature.domain().eq_ignore_ascii_case(&mail_from.domain)
+                        })
+                }));
         let mut message = self.build_message(mail_from, rcpt_to).await;
+        if is_sender_authenticated {
+            message.flags |= MAIL_SENDER_AUTHENTICATED;
+        }
 
         // Add Received header
         let mut headers = Vec::with_capacity(64);
@@ -460,6 +504,19 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
             headers.extend_from_slice(b"\r\n");
         }
 
+        // Add spam classification headers
+        #[cfg(feature = "local_delivery")]
+        if let Some(spam) = spam_classification {
+            headers.extend_from_slice(b"X-Spam-Status: ");
+            headers.extend_from_slice(if spam.is_spam {
+                &b"Yes"[..]
+            } else {
+                &b"No"[..]
+            });
+            headers.extend_from_slice(format!(", score={:.2}\r\n", spam.score).as_bytes());
+            headers.extend_from_slice(format!("X-Spam-Score: {:.2}\r\n", spam.score).as_bytes());
+        }
+
         // Add Return-Path
         if *dc.add_return_path.eval(self).await {
             headers.extend_from_slice(b"Return-Path: <");
@@ -469,7 +526,22 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
 
         // DKIM sign
         let raw_message = edited_message.unwrap_or(raw_message);
-        for signer in ac.dkim.sign.eval(self).await.iter() {
+        let managed_signer = if *ac.dkim.sign_managed.eval(self).await {
+            self.core
+                .dkim_keys
+                .as_ref()
+                .and_then(|keys| keys.signer(&auth_message.from().domain_part().to_lowercase()))
+        } else {
+            None
+        };
+        for signer in ac
+            .dkim
+            .sign
+            .eval(self)
+            .await
+            .iter()
+            .chain(managed_signer.iter())
+        {
             match signer.sign_chained(&[headers.as_ref(), &raw_message]) {
                 Ok(signature) => {
                     signature.write_header(&mut headers);
@@ -648,6 +720,44 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
         }
     }
 
+    #[cfg(feature = "local_delivery")]
+    async fn classify_spam(&self, message: Arc<Vec<u8>>) -> Option<SpamClassification> {
+        let (result_tx, result_rx) = oneshot::channel();
+        if self
+            .core
+            .delivery_tx
+            .send(DeliveryEvent::SpamClassify {
+                message,
+                recipients: self
+                    .data
+                    .rcpt_to
+                    .iter()
+                    .map(|rcpt| rcpt.address_lcase.clone())
+                    .collect(),
+                result_tx,
+            })
+            .await
+            .is_ok()
+        {
+            match result_rx.await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!(parent: &self.span,
+                        context = "spam",
+                        event = "error",
+                        reason = "result channel closed");
+                    None
+                }
+            }
+        } else {
+            tracing::warn!(parent: &self.span,
+                context = "spam",
+                event = "error",
+                reason = "tx channel closed");
+            None
+        }
+    }
+
     fn write_received(&self, headers: &mut Vec<u8>, id: u64) {
         headers.extend_from_slice(b"Received: from ");
         headers.extend_from_slice(self.data.helo_domain.as_bytes());
@@ -683,3 +793,44 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
         headers.extend_from_slice(b"\r\n");
     }
 }
+
+/// Removes all X-Spam-* headers from a message, returning `None` when
+/// there were none.
+fn strip_spam_headers(message: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = Vec::with_capacity(message.len());
+    let mut is_spam_header = false;
+    let mut has_changes = false;
+    let mut pos = 0;
+
+    while pos < message.len() {
+        let line_end = message[pos..]
+            .iter()
+            .position(|&ch| ch == b'\n')
+            .map_or(message.len(), |p| pos + p + 1);
+        let line = &message[pos..line_end];
+
+        if line == b"\r\n" || line == b"\n" {
+            // End of headers
+            stripped.extend_from_slice(&message[pos..]);
+            break;
+        } else if !matches!(line.first(), Some(b' ' | b'\t')) {
+            // Folded lines belong to the previous header
+            is_spam_header = line
+                .get(..7)
+                .map_or(false, |name| name.eq_ignore_ascii_case(b"X-Spam-"));
+        }
+
+        if is_spam_header {
+            has_changes = true;
+        } else {
+            stripped.extend_from_slice(line);
+        }
+        pos = line_end;
+    }
+
+    if has_changes {
+        Some(stripped)
+    } else {
+        None
+    }
+}
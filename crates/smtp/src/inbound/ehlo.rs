@@ -167,7 +167,8 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
             response.auth_mechanisms = *ac.mechanisms.eval(self).await;
             if response.auth_mechanisms != 0 {
                 if !self.stream.is_tls() {
-                    response.auth_mechanisms &= !(AUTH_PLAIN | AUTH_LOGIN);
+                    response.auth_mechanisms &=
+                        !(AUTH_PLAIN | AUTH_LOGIN | AUTH_SCRAM_SHA_256_PLUS);
                 }
                 if response.auth_mechanisms != 0 {
                     response.capabilities |= EXT_AUTH;
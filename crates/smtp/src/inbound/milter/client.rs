@@ -0,0 +1,353 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{net::IpAddr, time::Duration};
+
+use rustls::ServerName;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+use crate::config::Milter;
+
+use super::{
+    Action, Command, Error, MilterClient, Modification, Options, Response, MILTER_CHUNK_SIZE,
+    SMFIP_HDR_LEADSPC, SMFIP_NOBODY, SMFIP_NOCONNECT, SMFIP_NODATA, SMFIP_NOEOH, SMFIP_NOHDRS,
+    SMFIP_NOHELO, SMFIP_NOMAIL, SMFIP_NORCPT, SMFIP_NR_BODY, SMFIP_NR_CONN, SMFIP_NR_DATA,
+    SMFIP_NR_EOH, SMFIP_NR_HDR, SMFIP_NR_HELO, SMFIP_NR_MAIL, SMFIP_NR_RCPT,
+};
+
+impl MilterClient<TcpStream> {
+    pub async fn connect(config: &Milter, span: tracing::Span) -> super::Result<Self> {
+        let addrs = tokio::time::timeout(
+            config.timeout_connect,
+            lookup_host((config.hostname.as_str(), config.port)),
+        )
+        .await
+        .map_err(|_| Error::Timeout)??;
+
+        let mut last_err = Error::Disconnected;
+        for addr in addrs {
+            match tokio::time::timeout(config.timeout_connect, TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => {
+                    return Ok(MilterClient {
+                        stream,
+                        timeout_cmd: config.timeout_command,
+                        timeout_data: config.timeout_data,
+                        max_frame_len: config.max_frame_len,
+                        options: config.options(),
+                        span,
+                    });
+                }
+                Ok(Err(err)) => {
+                    last_err = Error::Io(err);
+                }
+                Err(_) => {
+                    last_err = Error::Timeout;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    pub async fn into_tls(
+        self,
+        tls_connector: &TlsConnector,
+        tls_hostname: &str,
+    ) -> super::Result<MilterClient<TlsStream<TcpStream>>> {
+        let server_name = ServerName::try_from(tls_hostname).map_err(|_| Error::TLSInvalidName)?;
+        Ok(MilterClient {
+            stream: tokio::time::timeout(
+                self.timeout_cmd,
+                tls_connector.connect(server_name, self.stream),
+            )
+            .await
+            .map_err(|_| Error::Timeout)??,
+            timeout_cmd: self.timeout_cmd,
+            timeout_data: self.timeout_data,
+            max_frame_len: self.max_frame_len,
+            options: self.options,
+            span: self.span,
+        })
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> MilterClient<T> {
+    pub async fn init(&mut self) -> super::Result<Options> {
+        self.write(Command::OptionNegotiation(self.options)).await?;
+        match self.read().await? {
+            Response::OptionNegotiation(options) => {
+                // Only keep the actions and protocol steps both sides support
+                self.options = Options {
+                    version: options.version.min(self.options.version),
+                    actions: options.actions & self.options.actions,
+                    protocol: options.protocol & self.options.protocol,
+                };
+                Ok(self.options)
+            }
+            response => Err(Error::Unexpected(response)),
+        }
+    }
+
+    pub async fn connection(
+        &mut self,
+        hostname: impl AsRef<[u8]>,
+        remote_ip: IpAddr,
+        remote_port: u16,
+        macros: Vec<(&[u8], &[u8])>,
+    ) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NOCONNECT) {
+            self.write_macros(b'C', macros).await?;
+            self.cmd(
+                Command::Connect {
+                    hostname: hostname.as_ref(),
+                    port: remote_port,
+                    addr: remote_ip,
+                },
+                SMFIP_NR_CONN,
+            )
+            .await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn helo(
+        &mut self,
+        hostname: impl AsRef<[u8]>,
+        macros: Vec<(&[u8], &[u8])>,
+    ) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NOHELO) {
+            self.write_macros(b'H', macros).await?;
+            self.cmd(
+                Command::Helo {
+                    hostname: hostname.as_ref(),
+                },
+                SMFIP_NR_HELO,
+            )
+            .await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn mail_from(
+        &mut self,
+        sender: impl AsRef<[u8]>,
+        macros: Vec<(&[u8], &[u8])>,
+    ) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NOMAIL) {
+            self.write_macros(b'M', macros).await?;
+            self.cmd(
+                Command::MailFrom {
+                    sender: sender.as_ref(),
+                },
+                SMFIP_NR_MAIL,
+            )
+            .await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn rcpt_to(
+        &mut self,
+        recipient: impl AsRef<[u8]>,
+        macros: Vec<(&[u8], &[u8])>,
+    ) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NORCPT) {
+            self.write_macros(b'R', macros).await?;
+            self.cmd(
+                Command::Rcpt {
+                    recipient: recipient.as_ref(),
+                },
+                SMFIP_NR_RCPT,
+            )
+            .await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn data(&mut self) -> super::Result<Action> {
+        if self.options.version >= 4 && !self.has_option(SMFIP_NODATA) {
+            self.cmd(Command::Data, SMFIP_NR_DATA).await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn headers<'x>(
+        &mut self,
+        headers: impl Iterator<Item = (&'x [u8], &'x [u8])>,
+    ) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NOHDRS) {
+            let strip_space = !self.has_option(SMFIP_HDR_LEADSPC);
+            for (name, value) in headers {
+                let value = match value {
+                    [b' ', value @ ..] if strip_space => value,
+                    _ => value,
+                };
+                let action = self
+                    .cmd(Command::Header { name, value }, SMFIP_NR_HDR)
+                    .await?;
+                if action != Action::Continue {
+                    return Ok(action);
+                }
+            }
+        }
+
+        if !self.has_option(SMFIP_NOEOH) {
+            self.cmd(Command::EndOfHeader, SMFIP_NR_EOH).await
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    pub async fn body(&mut self, body: &[u8]) -> super::Result<Action> {
+        if !self.has_option(SMFIP_NOBODY) {
+            for value in body.chunks(MILTER_CHUNK_SIZE) {
+                match self.cmd(Command::Body { value }, SMFIP_NR_BODY).await? {
+                    Action::Continue => (),
+                    Action::Skip => break,
+                    action => return Ok(action),
+                }
+            }
+        }
+
+        Ok(Action::Continue)
+    }
+
+    pub async fn eom(&mut self) -> super::Result<(Action, Vec<Modification>)> {
+        self.write(Command::EndOfBody).await?;
+        let mut modifications = Vec::new();
+        loop {
+            match self.read().await? {
+                Response::Action(action) => return Ok((action, modifications)),
+                Response::Modification(modification) => modifications.push(modification),
+                response => return Err(Error::Unexpected(response)),
+            }
+        }
+    }
+
+    pub async fn quit(&mut self) -> super::Result<()> {
+        self.write(Command::Quit).await?;
+        self.stream.shutdown().await.map_err(Error::Io)
+    }
+
+    pub fn has_option(&self, option: u32) -> bool {
+        self.options.protocol & option == option
+    }
+
+    async fn cmd(&mut self, command: Command<'_>, no_reply: u32) -> super::Result<Action> {
+        self.write(command).await?;
+        if !self.has_option(no_reply) {
+            match self.read().await? {
+                Response::Action(action) => Ok(action),
+                response => Err(Error::Unexpected(response)),
+            }
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    async fn write_macros(
+        &mut self,
+        cmdcode: u8,
+        macros: Vec<(&[u8], &[u8])>,
+    ) -> super::Result<()> {
+        if !macros.is_empty() {
+            self.write(Command::Macro { cmdcode, macros }).await
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn write(&mut self, command: Command<'_>) -> super::Result<()> {
+        tracing::trace!(parent: &self.span,
+            context = "milter",
+            event = "write",
+            command = ?command);
+
+        let timeout = if matches!(command, Command::Body { .. }) {
+            self.timeout_data
+        } else {
+            self.timeout_cmd
+        };
+        let bytes = command.serialize();
+        match tokio::time::timeout(timeout, async {
+            self.stream.write_all(&bytes).await?;
+            self.stream.flush().await
+        })
+        .await
+        {
+            Ok(result) => result.map_err(Error::Io),
+            Err(_) => Err(Error::Timeout),
+        }
+    }
+
+    async fn read(&mut self) -> super::Result<Response> {
+        loop {
+            let frame = self.read_frame(self.timeout_cmd).await?;
+            let response = Response::parse(&frame)?;
+
+            tracing::trace!(parent: &self.span,
+                context = "milter",
+                event = "read",
+                response = ?response);
+
+            // Progress responses reset the timeout
+            if response != Response::Progress {
+                return Ok(response);
+            }
+        }
+    }
+
+    async fn read_frame(&mut self, timeout: Duration) -> super::Result<Vec<u8>> {
+        match tokio::time::timeout(timeout, async {
+            let len = match self.stream.read_u32().await {
+                Ok(len) => len as usize,
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Err(Error::Disconnected)
+                }
+                Err(err) => return Err(Error::Io(err)),
+            };
+            if len == 0 {
+                return Err(Error::FrameInvalid(vec![]));
+            } else if len > self.max_frame_len {
+                return Err(Error::FrameTooLarge(len));
+            }
+            let mut frame = vec![0u8; len];
+            self.stream.read_exact(&mut frame).await?;
+            Ok(frame)
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(Error::Timeout),
+        }
+    }
+}
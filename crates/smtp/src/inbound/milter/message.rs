@@ -0,0 +1,454 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::borrow::Cow;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{config::Milter, core::Session, inbound::IsTls};
+
+use super::{Action, MilterClient, Modification, SMFIP_HDR_LEADSPC};
+
+impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
+    /// Runs the message through all enabled milters, returning the modified message
+    /// (if any) or the response to send to the client when processing should stop.
+    pub async fn run_milters(&self, message: &[u8]) -> Result<Option<Vec<u8>>, Cow<'static, [u8]>> {
+        let mut modified_message: Option<Vec<u8>> = None;
+        let mut quarantine_reason: Option<String> = None;
+
+        for milter in &self.core.session.config.data.milters {
+            if !*milter.enable.eval(self).await {
+                continue;
+            }
+
+            let current_message = modified_message.as_deref().unwrap_or(message);
+            match self.connect_milter(milter, current_message).await {
+                Ok((Action::Accept | Action::Continue | Action::Skip, mut modifications)) => {
+                    // Quarantine requests hold the message rather than modifying it
+                    modifications.retain(|modification| {
+                        if let Modification::Quarantine { reason } = modification {
+                            quarantine_reason = Some(reason.trim().to_string());
+                            false
+                        } else {
+                            true
+                        }
+                    });
+
+                    if !modifications.is_empty() {
+                        tracing::debug!(parent: &self.span,
+                            context = "milter",
+                            event = "accept",
+                            hostname = &milter.hostname,
+                            port = milter.port,
+                            modifications = modifications.len());
+
+                        if let Some(new_message) =
+                            self.apply_milter_modifications(current_message, modifications)
+                        {
+                            modified_message = Some(new_message);
+                        }
+                    }
+                }
+                Ok((Action::Discard, _)) => {
+                    tracing::info!(parent: &self.span,
+                        context = "milter",
+                        event = "discard",
+                        hostname = &milter.hostname,
+                        port = milter.port);
+
+                    return Err((b"250 2.0.0 Message queued for delivery.\r\n"[..]).into());
+                }
+                Ok((action, _)) => {
+                    tracing::info!(parent: &self.span,
+                        context = "milter",
+                        event = "reject",
+                        hostname = &milter.hostname,
+                        port = milter.port,
+                        action = ?action);
+
+                    return Err(action.into_response().unwrap_or_else(|| {
+                        (&b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into()
+                    }));
+                }
+                Err(err) => {
+                    tracing::warn!(parent: &self.span,
+                        context = "milter",
+                        event = "error",
+                        hostname = &milter.hostname,
+                        port = milter.port,
+                        reason = %err);
+
+                    if milter.tempfail_on_error {
+                        return Err(
+                            (&b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into(),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(reason) = quarantine_reason {
+            return Err(self
+                .quarantine_message(modified_message.as_deref().unwrap_or(message), &reason)
+                .await);
+        }
+
+        Ok(modified_message)
+    }
+
+    async fn quarantine_message(&self, message: &[u8], reason: &str) -> Cow<'static, [u8]> {
+        let path = if let Some(path) = &self.core.session.config.data.quarantine_path {
+            let mut path = path.clone();
+            path.push(format!("{:x}.eml", self.core.queue.queue_id()));
+            path
+        } else {
+            tracing::warn!(parent: &self.span,
+                context = "milter",
+                event = "quarantine",
+                reason = reason,
+                "Milter requested quarantine but no quarantine path is configured.");
+
+            return (&b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into();
+        };
+
+        let mut contents = Vec::with_capacity(message.len() + reason.len() + 16);
+        contents.extend_from_slice(b"X-Quarantine: ");
+        contents.extend_from_slice(reason.as_bytes());
+        contents.extend_from_slice(b"\r\n");
+        contents.extend_from_slice(message);
+
+        match tokio::fs::write(&path, contents).await {
+            Ok(_) => {
+                tracing::info!(parent: &self.span,
+                    context = "milter",
+                    event = "quarantine",
+                    reason = reason,
+                    path = %path.display());
+
+                (b"250 2.0.0 Message queued for delivery.\r\n"[..]).into()
+            }
+            Err(err) => {
+                tracing::error!(parent: &self.span,
+                    context = "milter",
+                    event = "error",
+                    path = %path.display(),
+                    reason = %err,
+                    "Failed to write quarantined message.");
+
+                (&b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into()
+            }
+        }
+    }
+
+    async fn connect_milter(
+        &self,
+        milter: &Milter,
+        message: &[u8],
+    ) -> super::Result<(Action, Vec<Modification>)> {
+        let client = MilterClient::connect(milter, self.span.clone()).await?;
+        if !milter.tls {
+            self.run_milter(client, message).await
+        } else {
+            let client = client
+                .into_tls(
+                    if !milter.tls_allow_invalid_certs {
+                        &self.core.queue.connectors.pki_verify
+                    } else {
+                        &self.core.queue.connectors.dummy_verify
+                    },
+                    &milter.hostname,
+                )
+                .await?;
+            self.run_milter(client, message).await
+        }
+    }
+
+    async fn run_milter<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut client: MilterClient<S>,
+        message: &[u8],
+    ) -> super::Result<(Action, Vec<Modification>)> {
+        let result = self.run_milter_steps(&mut client, message).await;
+        client.quit().await.ok();
+        let (action, mut modifications) = result?;
+
+        // Header values are stored with their leading whitespace
+        if !client.has_option(SMFIP_HDR_LEADSPC) {
+            for modification in &mut modifications {
+                if let Modification::AddHeader { value, .. }
+                | Modification::InsertHeader { value, .. }
+                | Modification::ChangeHeader { value, .. } = modification
+                {
+                    if !value.is_empty() {
+                        value.insert(0, ' ');
+                    }
+                }
+            }
+        }
+
+        Ok((action, modifications))
+    }
+
+    async fn run_milter_steps<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        client: &mut MilterClient<S>,
+        message: &[u8],
+    ) -> super::Result<(Action, Vec<Modification>)> {
+        client.init().await?;
+
+        // Connect
+        let remote_ip = self.data.remote_ip.to_string();
+        let local_ip = self.data.local_ip.to_string();
+        let action = client
+            .connection(
+                format!("[{remote_ip}]"),
+                self.data.remote_ip,
+                self.data.remote_port,
+                vec![
+                    (b"j".as_slice(), self.instance.hostname.as_bytes()),
+                    (b"{client_addr}".as_slice(), remote_ip.as_bytes()),
+                    (b"{daemon_addr}".as_slice(), local_ip.as_bytes()),
+                ],
+            )
+            .await?;
+        if action != Action::Continue {
+            return Ok((action, vec![]));
+        }
+
+        // EHLO/HELO
+        let action = client.helo(&self.data.helo_domain, vec![]).await?;
+        if action != Action::Continue {
+            return Ok((action, vec![]));
+        }
+
+        // MAIL FROM
+        if let Some(mail_from) = &self.data.mail_from {
+            let mut macros = vec![(b"{mail_addr}".as_slice(), mail_from.address.as_bytes())];
+            if !self.data.authenticated_as.is_empty() {
+                macros.push((
+                    b"{auth_authen}".as_slice(),
+                    self.data.authenticated_as.as_bytes(),
+                ));
+            }
+            let action = client.mail_from(&mail_from.address, macros).await?;
+            if action != Action::Continue {
+                return Ok((action, vec![]));
+            }
+        }
+
+        // RCPT TO
+        for rcpt in &self.data.rcpt_to {
+            let action = client
+                .rcpt_to(
+                    &rcpt.address,
+                    vec![(b"{rcpt_addr}".as_slice(), rcpt.address.as_bytes())],
+                )
+                .await?;
+            if action != Action::Continue {
+                return Ok((action, vec![]));
+            }
+        }
+
+        // DATA
+        let action = client.data().await?;
+        if action != Action::Continue {
+            return Ok((action, vec![]));
+        }
+
+        // Headers
+        let (headers, body) = split_message(message);
+        let action = client
+            .headers(headers.iter().map(|(name, value)| (*name, *value)))
+            .await?;
+        if action != Action::Continue {
+            return Ok((action, vec![]));
+        }
+
+        // Body
+        let action = client.body(body).await?;
+        if action != Action::Continue {
+            return Ok((action, vec![]));
+        }
+
+        client.eom().await
+    }
+
+    fn apply_milter_modifications(
+        &self,
+        message: &[u8],
+        modifications: Vec<Modification>,
+    ) -> Option<Vec<u8>> {
+        let (headers, body) = split_message(message);
+        let mut headers = headers
+            .into_iter()
+            .map(|(name, value)| (Cow::Borrowed(name), Cow::Borrowed(value)))
+            .collect::<Vec<_>>();
+        let mut new_body: Option<Vec<u8>> = None;
+        let mut has_changes = false;
+
+        for modification in modifications {
+            match modification {
+                Modification::ChangeFrom { .. }
+                | Modification::AddRcpt { .. }
+                | Modification::DeleteRcpt { .. } => {
+                    tracing::debug!(parent: &self.span,
+                        context = "milter",
+                        event = "unsupported",
+                        modification = ?modification);
+                }
+                Modification::ReplaceBody { value } => {
+                    new_body.get_or_insert_with(Vec::new).extend(value);
+                }
+                Modification::AddHeader { name, value } => {
+                    headers.push((
+                        Cow::Owned(name.into_bytes()),
+                        Cow::Owned(value.into_bytes()),
+                    ));
+                    has_changes = true;
+                }
+                Modification::InsertHeader { index, name, value } => {
+                    headers.insert(
+                        std::cmp::min(index as usize, headers.len()),
+                        (
+                            Cow::Owned(name.into_bytes()),
+                            Cow::Owned(value.into_bytes()),
+                        ),
+                    );
+                    has_changes = true;
+                }
+                Modification::ChangeHeader { index, name, value } => {
+                    // Header indexes are 1-based and count only headers with the same name
+                    let pos = headers
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, (header_name, _))| {
+                            std::str::from_utf8(header_name)
+                                .map_or(false, |n| n.trim().eq_ignore_ascii_case(&name))
+                        })
+                        .nth((index as usize).saturating_sub(1))
+                        .map(|(pos, _)| pos);
+
+                    match pos {
+                        Some(pos) if value.is_empty() => {
+                            headers.remove(pos);
+                        }
+                        Some(pos) => {
+                            headers[pos].1 = Cow::Owned(value.into_bytes());
+                        }
+                        None if !value.is_empty() => {
+                            headers.push((
+                                Cow::Owned(name.into_bytes()),
+                                Cow::Owned(value.into_bytes()),
+                            ));
+                        }
+                        None => {
+                            continue;
+                        }
+                    }
+                    has_changes = true;
+                }
+                Modification::Quarantine { .. } => {}
+            }
+        }
+
+        if has_changes || new_body.is_some() {
+            let body = new_body.as_deref().unwrap_or(body);
+            let mut message = Vec::with_capacity(message.len() + 64);
+            for (name, value) in headers {
+                message.extend_from_slice(&name);
+                message.push(b':');
+                message.extend_from_slice(&value);
+                message.extend_from_slice(b"\r\n");
+            }
+            message.extend_from_slice(b"\r\n");
+            message.extend_from_slice(body);
+            Some(message)
+        } else {
+            None
+        }
+    }
+}
+
+/// Splits a raw message into its headers (name and unparsed value, including folding)
+/// and body.
+fn split_message(message: &[u8]) -> (Vec<(&[u8], &[u8])>, &[u8]) {
+    let mut headers: Vec<(&[u8], &[u8])> = Vec::new();
+    let mut header_start: Option<(usize, usize)> = None;
+    let mut pos = 0;
+
+    while pos < message.len() {
+        let line_end = message[pos..]
+            .iter()
+            .position(|&ch| ch == b'\n')
+            .map_or(message.len(), |p| pos + p + 1);
+        let line = &message[pos..line_end];
+        let line_trimmed = line
+            .strip_suffix(b"\n")
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .unwrap_or(line);
+
+        if matches!(line.first(), Some(b' ' | b'\t')) && header_start.is_some() {
+            // Folded header
+            pos = line_end;
+            continue;
+        }
+
+        // Add previous header
+        if let Some((name_start, value_start)) = header_start.take() {
+            let value_end = trim_line_end(message, pos);
+            headers.push((
+                &message[name_start..value_start - 1],
+                &message[value_start..value_end],
+            ));
+        }
+
+        if line_trimmed.is_empty() {
+            return (headers, &message[line_end..]);
+        } else if let Some(colon) = line_trimmed.iter().position(|&ch| ch == b':') {
+            header_start = Some((pos, pos + colon + 1));
+            pos = line_end;
+        } else {
+            // Not a header, treat the rest of the message as the body
+            return (headers, &message[pos..]);
+        }
+    }
+
+    if let Some((name_start, value_start)) = header_start {
+        headers.push((
+            &message[name_start..value_start - 1],
+            &message[value_start..trim_line_end(message, message.len())],
+        ));
+    }
+
+    (headers, &[])
+}
+
+fn trim_line_end(message: &[u8], mut end: usize) -> usize {
+    if end > 0 && message[end - 1] == b'\n' {
+        end -= 1;
+        if end > 0 && message[end - 1] == b'\r' {
+            end -= 1;
+        }
+    }
+    end
+}
@@ -0,0 +1,263 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{borrow::Cow, fmt::Display, net::IpAddr, time::Duration};
+
+use crate::config::Milter;
+
+pub mod client;
+pub mod message;
+pub mod protocol;
+
+pub struct MilterClient<T> {
+    stream: T,
+    timeout_cmd: Duration,
+    timeout_data: Duration,
+    max_frame_len: usize,
+    options: Options,
+    span: tracing::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command<'x> {
+    Abort,
+    Body {
+        value: &'x [u8],
+    },
+    Connect {
+        hostname: &'x [u8],
+        port: u16,
+        addr: IpAddr,
+    },
+    Macro {
+        cmdcode: u8,
+        macros: Vec<(&'x [u8], &'x [u8])>,
+    },
+    EndOfBody,
+    Helo {
+        hostname: &'x [u8],
+    },
+    Header {
+        name: &'x [u8],
+        value: &'x [u8],
+    },
+    MailFrom {
+        sender: &'x [u8],
+    },
+    EndOfHeader,
+    OptionNegotiation(Options),
+    Quit,
+    Rcpt {
+        recipient: &'x [u8],
+    },
+    Data,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options {
+    pub version: u32,
+    pub actions: u32,
+    pub protocol: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Action(Action),
+    Modification(Modification),
+    Progress,
+    OptionNegotiation(Options),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Accept,
+    Continue,
+    Discard,
+    Reject,
+    TempFail,
+    Skip,
+    ReplyCode { code: [u8; 3], text: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Modification {
+    ChangeFrom {
+        sender: String,
+        args: String,
+    },
+    AddRcpt {
+        recipient: String,
+        args: String,
+    },
+    DeleteRcpt {
+        recipient: String,
+    },
+    ReplaceBody {
+        value: Vec<u8>,
+    },
+    AddHeader {
+        name: String,
+        value: String,
+    },
+    InsertHeader {
+        index: u32,
+        name: String,
+        value: String,
+    },
+    ChangeHeader {
+        index: u32,
+        name: String,
+        value: String,
+    },
+    Quarantine {
+        reason: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    FrameTooLarge(usize),
+    FrameInvalid(Vec<u8>),
+    Unexpected(Response),
+    Timeout,
+    TLSInvalidName,
+    Disconnected,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Actions the milter may request
+pub const SMFIF_ADDHDRS: u32 = 0x00000001;
+pub const SMFIF_CHGBODY: u32 = 0x00000002;
+pub const SMFIF_ADDRCPT: u32 = 0x00000004;
+pub const SMFIF_DELRCPT: u32 = 0x00000008;
+pub const SMFIF_CHGHDRS: u32 = 0x00000010;
+pub const SMFIF_QUARANTINE: u32 = 0x00000020;
+pub const SMFIF_CHGFROM: u32 = 0x00000040;
+pub const SMFIF_ADDRCPT_PAR: u32 = 0x00000080;
+
+// Protocol steps the milter may skip or not reply to
+pub const SMFIP_NOCONNECT: u32 = 0x00000001;
+pub const SMFIP_NOHELO: u32 = 0x00000002;
+pub const SMFIP_NOMAIL: u32 = 0x00000004;
+pub const SMFIP_NORCPT: u32 = 0x00000008;
+pub const SMFIP_NOBODY: u32 = 0x00000010;
+pub const SMFIP_NOHDRS: u32 = 0x00000020;
+pub const SMFIP_NOEOH: u32 = 0x00000040;
+pub const SMFIP_NR_HDR: u32 = 0x00000080;
+pub const SMFIP_NOUNKNOWN: u32 = 0x00000100;
+pub const SMFIP_NODATA: u32 = 0x00000200;
+pub const SMFIP_SKIP: u32 = 0x00000400;
+pub const SMFIP_NR_CONN: u32 = 0x00001000;
+pub const SMFIP_NR_HELO: u32 = 0x00002000;
+pub const SMFIP_NR_MAIL: u32 = 0x00004000;
+pub const SMFIP_NR_RCPT: u32 = 0x00008000;
+pub const SMFIP_NR_DATA: u32 = 0x00010000;
+pub const SMFIP_NR_EOH: u32 = 0x00040000;
+pub const SMFIP_NR_BODY: u32 = 0x00080000;
+pub const SMFIP_HDR_LEADSPC: u32 = 0x00100000;
+
+pub const MILTER_CHUNK_SIZE: usize = 65535;
+
+impl Milter {
+    pub fn options(&self) -> Options {
+        Options {
+            version: self.protocol_version,
+            actions: self
+                .flags_actions
+                .unwrap_or(SMFIF_ADDHDRS | SMFIF_CHGBODY | SMFIF_CHGHDRS | SMFIF_QUARANTINE),
+            protocol: self
+                .flags_protocol
+                .unwrap_or(if self.protocol_version >= 6 {
+                    SMFIP_NOCONNECT
+                        | SMFIP_NOHELO
+                        | SMFIP_NOMAIL
+                        | SMFIP_NORCPT
+                        | SMFIP_NOBODY
+                        | SMFIP_NOHDRS
+                        | SMFIP_NOEOH
+                        | SMFIP_NR_HDR
+                        | SMFIP_NOUNKNOWN
+                        | SMFIP_NODATA
+                        | SMFIP_SKIP
+                        | SMFIP_NR_CONN
+                        | SMFIP_NR_HELO
+                        | SMFIP_NR_MAIL
+                        | SMFIP_NR_RCPT
+                        | SMFIP_NR_DATA
+                        | SMFIP_NR_EOH
+                        | SMFIP_NR_BODY
+                        | SMFIP_HDR_LEADSPC
+                } else {
+                    SMFIP_NOCONNECT
+                        | SMFIP_NOHELO
+                        | SMFIP_NOMAIL
+                        | SMFIP_NORCPT
+                        | SMFIP_NOBODY
+                        | SMFIP_NOHDRS
+                        | SMFIP_NOEOH
+                }),
+        }
+    }
+}
+
+impl Action {
+    pub fn into_response(self) -> Option<Cow<'static, [u8]>> {
+        match self {
+            Action::Reject => Some((&b"550 5.7.1 Message rejected.\r\n"[..]).into()),
+            Action::TempFail => {
+                Some((&b"451 4.3.5 Unable to accept message at this time.\r\n"[..]).into())
+            }
+            Action::ReplyCode { code, text } => {
+                let mut response = Vec::with_capacity(text.len() + 6);
+                response.extend_from_slice(&code);
+                response.push(b' ');
+                response.extend_from_slice(text.trim().as_bytes());
+                response.extend_from_slice(b"\r\n");
+                Some(response.into())
+            }
+            Action::Accept | Action::Continue | Action::Discard | Action::Skip => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {e}"),
+            Error::FrameTooLarge(size) => write!(f, "Frame too large ({size} bytes)"),
+            Error::FrameInvalid(bytes) => write!(f, "Invalid frame {bytes:?}"),
+            Error::Unexpected(response) => write!(f, "Unexpected response {response:?}"),
+            Error::Timeout => write!(f, "Connection timed out"),
+            Error::TLSInvalidName => write!(f, "Invalid TLS name"),
+            Error::Disconnected => write!(f, "Connection closed by milter"),
+        }
+    }
+}
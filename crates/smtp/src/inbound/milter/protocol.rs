@@ -0,0 +1,228 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use super::{Action, Command, Error, Modification, Options, Response};
+
+impl Command<'_> {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&[0, 0, 0, 0]);
+
+        match self {
+            Command::Abort => {
+                buf.push(b'A');
+            }
+            Command::Body { value } => {
+                buf.push(b'B');
+                buf.extend_from_slice(value);
+            }
+            Command::Connect {
+                hostname,
+                port,
+                addr,
+            } => {
+                buf.push(b'C');
+                buf.extend_from_slice(hostname);
+                buf.push(0);
+                match addr {
+                    IpAddr::V4(addr) => {
+                        buf.push(b'4');
+                        buf.extend_from_slice(&port.to_be_bytes());
+                        buf.extend_from_slice(addr.to_string().as_bytes());
+                    }
+                    IpAddr::V6(addr) => {
+                        buf.push(b'6');
+                        buf.extend_from_slice(&port.to_be_bytes());
+                        buf.extend_from_slice(addr.to_string().as_bytes());
+                    }
+                }
+                buf.push(0);
+            }
+            Command::Macro { cmdcode, macros } => {
+                buf.push(b'D');
+                buf.push(*cmdcode);
+                for (name, value) in macros {
+                    buf.extend_from_slice(name);
+                    buf.push(0);
+                    buf.extend_from_slice(value);
+                    buf.push(0);
+                }
+            }
+            Command::EndOfBody => {
+                buf.push(b'E');
+            }
+            Command::Helo { hostname } => {
+                buf.push(b'H');
+                buf.extend_from_slice(hostname);
+                buf.push(0);
+            }
+            Command::Header { name, value } => {
+                buf.push(b'L');
+                buf.extend_from_slice(name);
+                buf.push(0);
+                buf.extend_from_slice(value);
+                buf.push(0);
+            }
+            Command::MailFrom { sender } => {
+                buf.push(b'M');
+                buf.push(b'<');
+                buf.extend_from_slice(sender);
+                buf.push(b'>');
+                buf.push(0);
+            }
+            Command::EndOfHeader => {
+                buf.push(b'N');
+            }
+            Command::OptionNegotiation(options) => {
+                buf.push(b'O');
+                buf.extend_from_slice(&options.version.to_be_bytes());
+                buf.extend_from_slice(&options.actions.to_be_bytes());
+                buf.extend_from_slice(&options.protocol.to_be_bytes());
+            }
+            Command::Quit => {
+                buf.push(b'Q');
+            }
+            Command::Rcpt { recipient } => {
+                buf.push(b'R');
+                buf.push(b'<');
+                buf.extend_from_slice(recipient);
+                buf.push(b'>');
+                buf.push(0);
+            }
+            Command::Data => {
+                buf.push(b'T');
+            }
+        }
+
+        let len = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+        buf
+    }
+}
+
+impl Response {
+    pub fn parse(frame: &[u8]) -> super::Result<Response> {
+        let (command, payload) = frame
+            .split_first()
+            .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?;
+        let mut payload = PayloadReader { bytes: payload };
+
+        Ok(match command {
+            b'a' => Response::Action(Action::Accept),
+            b'c' => Response::Action(Action::Continue),
+            b'd' => Response::Action(Action::Discard),
+            b'r' => Response::Action(Action::Reject),
+            b't' => Response::Action(Action::TempFail),
+            b's' => Response::Action(Action::Skip),
+            b'p' => Response::Progress,
+            b'y' => {
+                let reply = payload.string();
+                let code = reply
+                    .as_bytes()
+                    .get(0..3)
+                    .filter(|code| {
+                        code.iter().all(|ch| ch.is_ascii_digit()) && matches!(code[0], b'4' | b'5')
+                    })
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?;
+                Response::Action(Action::ReplyCode {
+                    code: [code[0], code[1], code[2]],
+                    text: reply.get(3..).unwrap_or_default().trim().to_string(),
+                })
+            }
+            b'O' => Response::OptionNegotiation(Options {
+                version: payload
+                    .u32()
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?,
+                actions: payload
+                    .u32()
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?,
+                protocol: payload
+                    .u32()
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?,
+            }),
+            b'+' => Response::Modification(Modification::AddRcpt {
+                recipient: payload.string(),
+                args: String::new(),
+            }),
+            b'2' => Response::Modification(Modification::AddRcpt {
+                recipient: payload.string(),
+                args: payload.string(),
+            }),
+            b'-' => Response::Modification(Modification::DeleteRcpt {
+                recipient: payload.string(),
+            }),
+            b'e' => Response::Modification(Modification::ChangeFrom {
+                sender: payload.string(),
+                args: payload.string(),
+            }),
+            b'b' => Response::Modification(Modification::ReplaceBody {
+                value: payload.bytes.to_vec(),
+            }),
+            b'h' => Response::Modification(Modification::AddHeader {
+                name: payload.string(),
+                value: payload.string(),
+            }),
+            b'i' => Response::Modification(Modification::InsertHeader {
+                index: payload
+                    .u32()
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?,
+                name: payload.string(),
+                value: payload.string(),
+            }),
+            b'm' => Response::Modification(Modification::ChangeHeader {
+                index: payload
+                    .u32()
+                    .ok_or_else(|| Error::FrameInvalid(frame.to_vec()))?,
+                name: payload.string(),
+                value: payload.string(),
+            }),
+            b'q' => Response::Modification(Modification::Quarantine {
+                reason: payload.string(),
+            }),
+            _ => return Err(Error::FrameInvalid(frame.to_vec())),
+        })
+    }
+}
+
+struct PayloadReader<'x> {
+    bytes: &'x [u8],
+}
+
+impl PayloadReader<'_> {
+    fn u32(&mut self) -> Option<u32> {
+        let value = u32::from_be_bytes(self.bytes.get(..4)?.try_into().ok()?);
+        self.bytes = &self.bytes[4..];
+        Some(value)
+    }
+
+    fn string(&mut self) -> String {
+        let (value, bytes) = match self.bytes.iter().position(|&ch| ch == 0) {
+            Some(pos) => (&self.bytes[..pos], &self.bytes[pos + 1..]),
+            None => (self.bytes, &b""[..]),
+        };
+        self.bytes = bytes;
+        String::from_utf8_lossy(value).into_owned()
+    }
+}
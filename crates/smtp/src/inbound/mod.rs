@@ -26,6 +26,7 @@ use mail_auth::{
 };
 use tokio::net::TcpStream;
 use tokio_rustls::server::TlsStream;
+use utils::listener::ServerInstance;
 
 use crate::config::{ArcSealer, DkimSigner};
 
@@ -33,6 +34,7 @@ pub mod auth;
 pub mod data;
 pub mod ehlo;
 pub mod mail;
+pub mod milter;
 pub mod rcpt;
 pub mod session;
 pub mod spawn;
@@ -41,6 +43,9 @@ pub mod vrfy;
 pub trait IsTls {
     fn is_tls(&self) -> bool;
     fn write_tls_header(&self, headers: &mut Vec<u8>);
+    fn tls_end_point(&self, _instance: &ServerInstance) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl IsTls for TcpStream {
@@ -55,6 +60,10 @@ impl IsTls for TlsStream<TcpStream> {
         true
     }
 
+    fn tls_end_point(&self, instance: &ServerInstance) -> Option<Vec<u8>> {
+        instance.tls_end_point(self)
+    }
+
     fn write_tls_header(&self, headers: &mut Vec<u8>) {
         let (_, conn) = self.get_ref();
         headers.extend_from_slice(b"(using ");
@@ -102,9 +102,10 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
                                     && !self.stream.is_tls()
                                 {
                                     self.write(b"503 5.5.1 Clear text authentication without TLS is forbidden.\r\n").await?;
-                                } else if let Some(mut token) =
-                                    SaslToken::from_mechanism(mechanism & auth)
-                                {
+                                } else if let Some(mut token) = SaslToken::from_mechanism(
+                                    mechanism & auth,
+                                    self.stream.tls_end_point(&self.instance),
+                                ) {
                                     if self
                                         .handle_sasl_response(
                                             &mut token,
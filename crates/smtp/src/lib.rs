@@ -22,7 +22,8 @@
 */
 
 use crate::core::{
-    throttle::ThrottleKeyHasherBuilder, QueueCore, ReportCore, SessionCore, TlsConnectors, SMTP,
+    dkim::DkimKeyStore, throttle::ThrottleKeyHasherBuilder, QueueCore, ReportCore, SessionCore,
+    TlsConnectors, SMTP,
 };
 use std::sync::Arc;
 
@@ -33,8 +34,10 @@ use config::{
 use dashmap::DashMap;
 use directory::DirectoryConfig;
 use mail_send::smtp::tls::build_tls_connector;
-use queue::manager::SpawnQueue;
-use reporting::scheduler::SpawnReport;
+use outbound::{pool::ConnectionPool, relay::RelayState};
+use queue::{manager::SpawnQueue, store::QueueStore};
+use reporting::{scheduler::SpawnReport, store::ReportStore};
+use store::Store;
 use tokio::sync::mpsc;
 use utils::{
     config::{Config, ServerProtocol, Servers},
@@ -55,6 +58,7 @@ impl SMTP {
         config: &Config,
         servers: &Servers,
         directory: &DirectoryConfig,
+        store: Arc<Store>,
         #[cfg(feature = "local_delivery")] delivery_tx: mpsc::Sender<utils::ipc::DeliveryEvent>,
     ) -> Result<Arc<Self>, String> {
         // Read configuration parameters
@@ -71,6 +75,7 @@ impl SMTP {
                     protocol: ServerProtocol::Jmap,
                     concurrency: Default::default(),
                     timeout: Default::default(),
+                    weight: 1,
                     tls_implicit: Default::default(),
                     tls_allow_invalid_certs: Default::default(),
                     username: Default::default(),
@@ -86,6 +91,9 @@ impl SMTP {
         let queue_config = config.parse_queue(&config_ctx)?;
         let mail_auth_config = config.parse_mail_auth(&config_ctx)?;
         let report_config = config.parse_reports(&config_ctx)?;
+        let queue_store = QueueStore::init(config, store.clone())?;
+        let report_store = ReportStore::init(config, store.clone())?;
+        let dkim_keys = DkimKeyStore::init(config, store)?;
 
         // Build core
         let (queue_tx, queue_rx) = mpsc::channel(1024);
@@ -122,7 +130,12 @@ impl SMTP {
                         .unwrap_or(32)
                         .next_power_of_two() as usize,
                 ),
-                id_seq: 0.into(),
+                id_seq: if queue_store.is_some() {
+                    // Reduce the chance of queue id collisions between nodes
+                    rand::random::<u32>().into()
+                } else {
+                    0.into()
+                },
                 quota: DashMap::with_capacity_and_hasher_and_shard_amount(
                     config.property("global.shared-map.capacity")?.unwrap_or(2),
                     ThrottleKeyHasherBuilder::default(),
@@ -131,18 +144,24 @@ impl SMTP {
                         .unwrap_or(32)
                         .next_power_of_two() as usize,
                 ),
+                adaptive: DashMap::default(),
                 tx: queue_tx,
                 connectors: TlsConnectors {
                     pki_verify: build_tls_connector(false),
                     dummy_verify: build_tls_connector(true),
                 },
+                connections: ConnectionPool::default(),
+                relays: RelayState::default(),
+                store: queue_store,
             },
             report: ReportCore {
                 tx: report_tx,
                 config: report_config,
+                store: report_store,
             },
             mail_auth: mail_auth_config,
             sieve: sieve_config,
+            dkim_keys,
             #[cfg(feature = "local_delivery")]
             delivery_tx,
         });
@@ -153,6 +172,11 @@ impl SMTP {
         // Spawn report manager
         report_rx.spawn(core.clone(), core.report.read_reports().await);
 
+        // Spawn DKIM key manager
+        if core.dkim_keys.is_some() {
+            DkimKeyStore::spawn(core.clone());
+        }
+
         Ok(core)
     }
 }
@@ -33,7 +33,7 @@ use mail_auth::{
 };
 use mail_send::SmtpClient;
 use smtp_proto::MAIL_REQUIRETLS;
-use utils::config::ServerProtocol;
+use utils::{config::ServerProtocol, metrics::METRICS};
 
 use crate::{
     config::{AggregateFrequency, TlsStrategy},
@@ -45,16 +45,23 @@ use crate::{
 use super::{
     lookup::ToNextHop,
     mta_sts,
+    pool::{Connection, ConnectionKey, ConnectionReuse, SmtpConnection},
     session::{read_greeting, say_helo, try_start_tls, SessionParams, StartTlsResult},
     NextHop,
 };
 use crate::queue::{
-    manager::Queue, throttle, DeliveryAttempt, Domain, Error, Event, OnHold, QueueEnvelope,
-    Schedule, Status, WorkerResult,
+    adaptive::AdaptiveBackoff, manager::Queue, throttle, DeliveryAttempt, Domain, Error, Event,
+    OnHold, QueueEnvelope, Schedule, Status, WorkerResult, RCPT_STATUS_CHANGED,
 };
 
 impl DeliveryAttempt {
     pub async fn try_deliver(mut self, core: Arc<SMTP>, queue: &mut Queue) {
+        // Make sure that no other node took over this message
+        if !core.queue.lock_message(&self.message).await {
+            core.queue.release_message(&self.message).await;
+            return;
+        }
+
         // Check that the message still has recipients to be delivered
         let has_pending_delivery = self.has_pending_delivery();
 
@@ -66,7 +73,7 @@ impl DeliveryAttempt {
             let due = self.message.next_delivery_event();
             if due > Instant::now() {
                 // Save changes to disk
-                self.message.save_changes().await;
+                core.queue.save_changes(&mut self.message).await;
 
                 queue.schedule(Schedule {
                     due,
@@ -76,7 +83,7 @@ impl DeliveryAttempt {
             }
         } else {
             // All message recipients expired, do not re-queue. (DSN has been already sent)
-            self.message.remove().await;
+            core.queue.remove_message(&self.message).await;
             return;
         }
 
@@ -93,7 +100,7 @@ impl DeliveryAttempt {
                 .await
             {
                 // Save changes to disk
-                self.message.save_changes().await;
+                core.queue.save_changes(&mut self.message).await;
 
                 match err {
                     throttle::Error::Concurrency { limiter } => {
@@ -160,8 +167,8 @@ impl DeliveryAttempt {
                 }
 
                 // Obtain next hop
-                let (mut remote_hosts, is_smtp) = match queue_config.next_hop.eval(&envelope).await
-                {
+                let relay_hosts = queue_config.next_hop.eval(&envelope).await;
+                let (mut remote_hosts, is_smtp) = match relay_hosts.first() {
                     #[cfg(feature = "local_delivery")]
                     Some(next_hop) if next_hop.protocol == ServerProtocol::Jmap => {
                         // Deliver message locally
@@ -180,7 +187,10 @@ impl DeliveryAttempt {
                         continue 'next_domain;
                     }
                     Some(next_hop) => (
-                        vec![NextHop::Relay(next_hop)],
+                        core.queue.relays.next_hops(
+                            relay_hosts,
+                            *queue_config.relay.strategy.eval(&envelope).await,
+                        ),
                         next_hop.protocol == ServerProtocol::Smtp,
                     ),
                     None => (Vec::with_capacity(0), true),
@@ -353,7 +363,19 @@ impl DeliveryAttempt {
                 // Try delivering message
                 let max_multihomed = *queue_config.max_multihomed.eval(&envelope).await;
                 let mut last_status = Status::Scheduled;
-                'next_host: for remote_host in &remote_hosts {
+                'next_host: for (host_idx, remote_host) in remote_hosts.iter().enumerate() {
+                    // Try other relays first after a failed attempt
+                    if let Some(NextHop::Relay(relay)) = host_idx
+                        .checked_sub(1)
+                        .and_then(|prev_idx| remote_hosts.get(prev_idx))
+                    {
+                        core.queue.relays.set_unavailable(
+                            relay,
+                            *queue_config.relay.failure_backoff.eval(&envelope).await,
+                            &span,
+                        );
+                    }
+
                     // Validate MTA-STS
                     envelope.mx = remote_host.hostname();
                     if let Some(mta_sts_policy) = &mta_sts_policy {
@@ -390,6 +412,25 @@ impl DeliveryAttempt {
                         }
                     }
 
+                    // Slow down delivery to hosts that deferred recent messages
+                    let adaptive = if *queue_config.adaptive.enable.eval(&envelope).await {
+                        if let Err(err) = core.queue.is_adaptive_allowed(envelope.mx, &span) {
+                            domain.set_throttle_error(err, &mut on_hold);
+                            continue 'next_domain;
+                        }
+                        AdaptiveBackoff {
+                            initial_delay: *queue_config
+                                .adaptive
+                                .initial_delay
+                                .eval(&envelope)
+                                .await,
+                            max_delay: *queue_config.adaptive.max_delay.eval(&envelope).await,
+                        }
+                        .into()
+                    } else {
+                        None
+                    };
+
                     // Obtain source and remote IPs
                     let (source_ip, remote_ips) = match core
                         .resolve_host(remote_host, &envelope, max_multihomed)
@@ -554,164 +595,98 @@ impl DeliveryAttempt {
                         None
                     };
 
-                    // Try each IP address
-                    envelope.local_ip = source_ip.unwrap_or(no_ip);
-                    'next_ip: for remote_ip in remote_ips {
-                        // Throttle remote host
-                        let mut in_flight_host = Vec::new();
-                        envelope.remote_ip = remote_ip;
-                        for throttle in &queue_config.throttle.host {
-                            if let Err(err) = core
-                                .queue
-                                .is_allowed(throttle, &envelope, &mut in_flight_host, &span)
-                                .await
-                            {
-                                domain.set_throttle_error(err, &mut on_hold);
-                                continue 'next_domain;
-                            }
-                        }
-
-                        // Connect
-                        let mut smtp_client = match if let Some(ip_addr) = source_ip {
-                            SmtpClient::connect_using(
-                                ip_addr,
-                                SocketAddr::new(remote_ip, remote_host.port()),
-                                *queue_config.timeout.connect.eval(&envelope).await,
-                            )
-                            .await
-                        } else {
-                            SmtpClient::connect(
-                                SocketAddr::new(remote_ip, remote_host.port()),
-                                *queue_config.timeout.connect.eval(&envelope).await,
-                            )
-                            .await
-                        } {
-                            Ok(smtp_client) => {
-                                tracing::debug!(
-                                    parent: &span,
-                                    context = "connect",
-                                    event = "success",
-                                    mx = envelope.mx,
-                                    source_ip = %source_ip.unwrap_or(no_ip),
-                                    remote_ip = %remote_ip,
-                                    remote_port = remote_host.port(),
-                                );
-
-                                smtp_client
-                            }
-                            Err(err) => {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "connect",
-                                    event = "failed",
-                                    mx = envelope.mx,
-                                    reason = %err,
-                                );
-                                last_status = Status::from_smtp_error(envelope.mx, "", err);
-                                continue 'next_ip;
+                    // Deliver using an idle session or by connecting to each IP address,
+                    // a failed greeting moves on to the next host
+                    let result = 'attempt: {
+                        // Try reusing an idle session to this host
+                        envelope.local_ip = source_ip.unwrap_or(no_ip);
+                        let idle_timeout = *queue_config.reuse.idle_timeout.eval(&envelope).await;
+                        let reuse = if !idle_timeout.is_zero() {
+                            ConnectionReuse {
+                                key: ConnectionKey {
+                                    hostname: envelope.mx.to_string(),
+                                    port: remote_host.port(),
+                                    relay_id: remote_host.relay_id().map(|id| id.to_string()),
+                                    source_ip,
+                                    local_hostname: queue_config
+                                        .hostname
+                                        .eval(&envelope)
+                                        .await
+                                        .to_string(),
+                                    tls_strategy,
+                                },
+                                idle_timeout,
+                                max_messages: *queue_config
+                                    .reuse
+                                    .max_messages
+                                    .eval(&envelope)
+                                    .await,
                             }
-                        };
-
-                        // Obtail session parameters
-                        let params = SessionParams {
-                            span: &span,
-                            credentials: remote_host.credentials(),
-                            is_smtp: remote_host.is_smtp(),
-                            hostname: envelope.mx,
-                            local_hostname: queue_config.hostname.eval(&envelope).await,
-                            timeout_ehlo: *queue_config.timeout.ehlo.eval(&envelope).await,
-                            timeout_mail: *queue_config.timeout.mail.eval(&envelope).await,
-                            timeout_rcpt: *queue_config.timeout.rcpt.eval(&envelope).await,
-                            timeout_data: *queue_config.timeout.data.eval(&envelope).await,
-                        };
-
-                        // Prepare TLS connector
-                        let tls_connector = if !remote_host.allow_invalid_certs() {
-                            &core.queue.connectors.pki_verify
+                            .into()
                         } else {
-                            &core.queue.connectors.dummy_verify
+                            None
                         };
-
-                        let delivery_result = if !remote_host.implicit_tls() {
-                            // Read greeting
-                            smtp_client.timeout =
-                                *queue_config.timeout.greeting.eval(&envelope).await;
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
+                        if let Some(key) = reuse.as_ref().map(|reuse| &reuse.key) {
+                            let allow_plain = !(tls_strategy.is_tls_required()
+                                || (self.message.flags & MAIL_REQUIRETLS) != 0
+                                || mta_sts_policy.is_some()
+                                || dane_policy.is_some());
+                            if let Some(conn) = core
+                                .queue
+                                .connections
+                                .acquire(
+                                    key,
+                                    allow_plain,
+                                    *queue_config.timeout.ehlo.eval(&envelope).await,
+                                )
+                                .await
                             {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "greeting",
-                                    event = "invalid",
-                                    mx = envelope.mx,
-                                    status = %status,
-                                );
+                                // Throttle remote host
+                                let mut in_flight_host = Vec::new();
+                                envelope.remote_ip = conn.remote_ip;
+                                for throttle in &queue_config.throttle.host {
+                                    if let Err(err) = core
+                                        .queue
+                                        .is_allowed(throttle, &envelope, &mut in_flight_host, &span)
+                                        .await
+                                    {
+                                        core.queue.connections.release(
+                                            key.clone(),
+                                            conn,
+                                            idle_timeout,
+                                        );
+                                        domain.set_throttle_error(err, &mut on_hold);
+                                        continue 'next_domain;
+                                    }
+                                }
 
-                                last_status = status;
-                                continue 'next_host;
-                            }
+                                // Verify DANE
+                                let is_verified = match (&conn.client, &dane_policy) {
+                                    (SmtpConnection::Tls(smtp_client), Some(dane_policy)) => {
+                                        dane_policy
+                                            .verify(
+                                                &span,
+                                                envelope.mx,
+                                                smtp_client.tls_connection().peer_certificates(),
+                                            )
+                                            .is_ok()
+                                    }
+                                    _ => true,
+                                };
 
-                            // Say EHLO
-                            let capabilties = match say_helo(&mut smtp_client, &params).await {
-                                Ok(capabilities) => capabilities,
-                                Err(status) => {
-                                    tracing::info!(
+                                if is_verified {
+                                    tracing::debug!(
                                         parent: &span,
-                                        context = "ehlo",
-                                        event = "rejected",
+                                        context = "connection",
+                                        event = "reuse",
                                         mx = envelope.mx,
-                                        status = %status,
+                                        remote_ip = %conn.remote_ip,
                                     );
 
-                                    last_status = status;
-                                    continue 'next_host;
-                                }
-                            };
-
-                            // Try starting TLS
-                            smtp_client.timeout = *queue_config.timeout.tls.eval(&envelope).await;
-                            match try_start_tls(
-                                smtp_client,
-                                tls_connector,
-                                envelope.mx,
-                                &capabilties,
-                            )
-                            .await
-                            {
-                                StartTlsResult::Success { smtp_client } => {
-                                    // Verify DANE
-                                    if let Some(dane_policy) = &dane_policy {
-                                        if let Err(status) = dane_policy.verify(
-                                            &span,
-                                            envelope.mx,
-                                            smtp_client.tls_connection().peer_certificates(),
-                                        ) {
-                                            // Report DANE verification failure
-                                            if let Some(tls_report) = &tls_report {
-                                                core.schedule_report(TlsEvent {
-                                                    policy: dane_policy.into(),
-                                                    domain: envelope.domain.to_string(),
-                                                    failure: FailureDetails::new(
-                                                        ResultType::ValidationFailure,
-                                                    )
-                                                    .with_receiving_mx_hostname(envelope.mx)
-                                                    .with_receiving_ip(remote_ip)
-                                                    .with_failure_reason_code(
-                                                        "No matching certificates found.",
-                                                    )
-                                                    .into(),
-                                                    tls_record: tls_report.record.clone(),
-                                                    interval: tls_report.interval,
-                                                })
-                                                .await;
-                                            }
-
-                                            last_status = status;
-                                            continue 'next_host;
-                                        }
-                                    }
-
                                     // Report TLS success
-                                    if let Some(tls_report) = &tls_report {
+                                    if let (Some(tls_report), true) =
+                                        (&tls_report, conn.client.is_tls())
+                                    {
                                         core.schedule_report(TlsEvent {
                                             policy: (&mta_sts_policy, &dane_policy).into(),
                                             domain: envelope.domain.to_string(),
@@ -722,112 +697,305 @@ impl DeliveryAttempt {
                                         .await;
                                     }
 
-                                    // Deliver message over TLS
-                                    self.message
+                                    // Deliver message over the idle session
+                                    let params = SessionParams {
+                                        span: &span,
+                                        credentials: remote_host.credentials(),
+                                        is_smtp: remote_host.is_smtp(),
+                                        hostname: envelope.mx,
+                                        local_hostname: queue_config.hostname.eval(&envelope).await,
+                                        timeout_ehlo: *queue_config
+                                            .timeout
+                                            .ehlo
+                                            .eval(&envelope)
+                                            .await,
+                                        timeout_mail: *queue_config
+                                            .timeout
+                                            .mail
+                                            .eval(&envelope)
+                                            .await,
+                                        timeout_rcpt: *queue_config
+                                            .timeout
+                                            .rcpt
+                                            .eval(&envelope)
+                                            .await,
+                                        timeout_data: *queue_config
+                                            .timeout
+                                            .data
+                                            .eval(&envelope)
+                                            .await,
+                                    };
+                                    let delivery_result = core
+                                        .queue
+                                        .connections
                                         .deliver(
-                                            smtp_client,
+                                            conn,
+                                            &self.message,
                                             recipients
                                                 .iter_mut()
                                                 .filter(|r| r.domain_idx == domain_idx),
                                             params,
+                                            reuse.clone(),
                                         )
-                                        .await
+                                        .await;
+
+                                    break 'attempt Ok(delivery_result);
+                                } else {
+                                    conn.client.quit().await;
                                 }
-                                StartTlsResult::Unavailable {
-                                    response,
-                                    smtp_client,
-                                } => {
-                                    // Report unavailable STARTTLS
-                                    let reason =
-                                        response.as_ref().map(|r| r.to_string()).unwrap_or_else(
-                                            || "STARTTLS was not advertised by host".to_string(),
-                                        );
+                            }
+                        }
+
+                        // Try each IP address
+                        'next_ip: for remote_ip in remote_ips {
+                            // Throttle remote host
+                            let mut in_flight_host = Vec::new();
+                            envelope.remote_ip = remote_ip;
+                            for throttle in &queue_config.throttle.host {
+                                if let Err(err) = core
+                                    .queue
+                                    .is_allowed(throttle, &envelope, &mut in_flight_host, &span)
+                                    .await
+                                {
+                                    domain.set_throttle_error(err, &mut on_hold);
+                                    continue 'next_domain;
+                                }
+                            }
+
+                            // Connect
+                            let mut smtp_client = match if let Some(ip_addr) = source_ip {
+                                SmtpClient::connect_using(
+                                    ip_addr,
+                                    SocketAddr::new(remote_ip, remote_host.port()),
+                                    *queue_config.timeout.connect.eval(&envelope).await,
+                                )
+                                .await
+                            } else {
+                                SmtpClient::connect(
+                                    SocketAddr::new(remote_ip, remote_host.port()),
+                                    *queue_config.timeout.connect.eval(&envelope).await,
+                                )
+                                .await
+                            } {
+                                Ok(smtp_client) => {
+                                    tracing::debug!(
+                                        parent: &span,
+                                        context = "connect",
+                                        event = "success",
+                                        mx = envelope.mx,
+                                        source_ip = %source_ip.unwrap_or(no_ip),
+                                        remote_ip = %remote_ip,
+                                        remote_port = remote_host.port(),
+                                    );
 
+                                    smtp_client
+                                }
+                                Err(err) => {
                                     tracing::info!(
                                         parent: &span,
-                                        context = "tls",
-                                        event = "unavailable",
+                                        context = "connect",
+                                        event = "failed",
                                         mx = envelope.mx,
-                                        reason = reason,
+                                        reason = %err,
                                     );
+                                    last_status = Status::from_smtp_error(envelope.mx, "", err);
+                                    continue 'next_ip;
+                                }
+                            };
 
-                                    if let Some(tls_report) = &tls_report {
-                                        core.schedule_report(TlsEvent {
-                                            policy: (&mta_sts_policy, &dane_policy).into(),
-                                            domain: envelope.domain.to_string(),
-                                            failure: FailureDetails::new(
-                                                ResultType::StartTlsNotSupported,
-                                            )
-                                            .with_receiving_mx_hostname(envelope.mx)
-                                            .with_receiving_ip(remote_ip)
-                                            .with_failure_reason_code(reason)
-                                            .into(),
-                                            tls_record: tls_report.record.clone(),
-                                            interval: tls_report.interval,
-                                        })
-                                        .await;
-                                    }
+                            // Obtail session parameters
+                            let params = SessionParams {
+                                span: &span,
+                                credentials: remote_host.credentials(),
+                                is_smtp: remote_host.is_smtp(),
+                                hostname: envelope.mx,
+                                local_hostname: queue_config.hostname.eval(&envelope).await,
+                                timeout_ehlo: *queue_config.timeout.ehlo.eval(&envelope).await,
+                                timeout_mail: *queue_config.timeout.mail.eval(&envelope).await,
+                                timeout_rcpt: *queue_config.timeout.rcpt.eval(&envelope).await,
+                                timeout_data: *queue_config.timeout.data.eval(&envelope).await,
+                            };
 
-                                    if tls_strategy.is_tls_required()
-                                        || (self.message.flags & MAIL_REQUIRETLS) != 0
-                                        || mta_sts_policy.is_some()
-                                        || dane_policy.is_some()
-                                    {
-                                        last_status =
-                                            Status::from_starttls_error(envelope.mx, response);
+                            // Prepare TLS connector
+                            let tls_connector = if !remote_host.allow_invalid_certs() {
+                                &core.queue.connectors.pki_verify
+                            } else {
+                                &core.queue.connectors.dummy_verify
+                            };
+
+                            let delivery_result = if !remote_host.implicit_tls() {
+                                // Read greeting
+                                smtp_client.timeout =
+                                    *queue_config.timeout.greeting.eval(&envelope).await;
+                                if let Err(status) =
+                                    read_greeting(&mut smtp_client, envelope.mx).await
+                                {
+                                    tracing::info!(
+                                        parent: &span,
+                                        context = "greeting",
+                                        event = "invalid",
+                                        mx = envelope.mx,
+                                        status = %status,
+                                    );
+
+                                    break 'attempt Err(status);
+                                }
+
+                                // Say EHLO
+                                let capabilties = match say_helo(&mut smtp_client, &params).await {
+                                    Ok(capabilities) => capabilities,
+                                    Err(status) => {
+                                        tracing::info!(
+                                            parent: &span,
+                                            context = "ehlo",
+                                            event = "rejected",
+                                            mx = envelope.mx,
+                                            status = %status,
+                                        );
+
+                                        last_status = status;
                                         continue 'next_host;
-                                    } else {
-                                        // TLS is not required, proceed in plain-text
-                                        self.message
+                                    }
+                                };
+
+                                // Try starting TLS
+                                smtp_client.timeout =
+                                    *queue_config.timeout.tls.eval(&envelope).await;
+                                match try_start_tls(
+                                    smtp_client,
+                                    tls_connector,
+                                    envelope.mx,
+                                    &capabilties,
+                                )
+                                .await
+                                {
+                                    StartTlsResult::Success { smtp_client } => {
+                                        // Verify DANE
+                                        if let Some(dane_policy) = &dane_policy {
+                                            if let Err(status) = dane_policy.verify(
+                                                &span,
+                                                envelope.mx,
+                                                smtp_client.tls_connection().peer_certificates(),
+                                            ) {
+                                                // Report DANE verification failure
+                                                if let Some(tls_report) = &tls_report {
+                                                    core.schedule_report(TlsEvent {
+                                                        policy: dane_policy.into(),
+                                                        domain: envelope.domain.to_string(),
+                                                        failure: FailureDetails::new(
+                                                            ResultType::ValidationFailure,
+                                                        )
+                                                        .with_receiving_mx_hostname(envelope.mx)
+                                                        .with_receiving_ip(remote_ip)
+                                                        .with_failure_reason_code(
+                                                            "No matching certificates found.",
+                                                        )
+                                                        .into(),
+                                                        tls_record: tls_report.record.clone(),
+                                                        interval: tls_report.interval,
+                                                    })
+                                                    .await;
+                                                }
+
+                                                last_status = status;
+                                                continue 'next_host;
+                                            }
+                                        }
+
+                                        // Report TLS success
+                                        if let Some(tls_report) = &tls_report {
+                                            core.schedule_report(TlsEvent {
+                                                policy: (&mta_sts_policy, &dane_policy).into(),
+                                                domain: envelope.domain.to_string(),
+                                                failure: None,
+                                                tls_record: tls_report.record.clone(),
+                                                interval: tls_report.interval,
+                                            })
+                                            .await;
+                                        }
+
+                                        // Deliver message over TLS
+                                        core.queue
+                                            .connections
                                             .deliver(
-                                                smtp_client,
+                                                Connection::new(
+                                                    SmtpConnection::Tls(smtp_client),
+                                                    remote_ip,
+                                                ),
+                                                &self.message,
                                                 recipients
                                                     .iter_mut()
                                                     .filter(|r| r.domain_idx == domain_idx),
                                                 params,
+                                                reuse.clone(),
                                             )
                                             .await
                                     }
-                                }
-                                StartTlsResult::Error { error } => {
-                                    tracing::info!(
-                                        parent: &span,
-                                        context = "tls",
-                                        event = "failed",
-                                        mx = envelope.mx,
-                                        error = %error,
-                                    );
+                                    StartTlsResult::Unavailable {
+                                        response,
+                                        smtp_client,
+                                    } => {
+                                        // Report unavailable STARTTLS
+                                        let reason = response
+                                            .as_ref()
+                                            .map(|r| r.to_string())
+                                            .unwrap_or_else(|| {
+                                                "STARTTLS was not advertised by host".to_string()
+                                            });
 
-                                    // Report TLS failure
-                                    if let (Some(tls_report), mail_send::Error::Tls(error)) =
-                                        (&tls_report, &error)
-                                    {
-                                        core.schedule_report(TlsEvent {
-                                            policy: (&mta_sts_policy, &dane_policy).into(),
-                                            domain: envelope.domain.to_string(),
-                                            failure: FailureDetails::new(
-                                                ResultType::CertificateNotTrusted,
-                                            )
-                                            .with_receiving_mx_hostname(envelope.mx)
-                                            .with_receiving_ip(remote_ip)
-                                            .with_failure_reason_code(error.to_string())
-                                            .into(),
-                                            tls_record: tls_report.record.clone(),
-                                            interval: tls_report.interval,
-                                        })
-                                        .await;
+                                        tracing::info!(
+                                            parent: &span,
+                                            context = "tls",
+                                            event = "unavailable",
+                                            mx = envelope.mx,
+                                            reason = reason,
+                                        );
+
+                                        if let Some(tls_report) = &tls_report {
+                                            core.schedule_report(TlsEvent {
+                                                policy: (&mta_sts_policy, &dane_policy).into(),
+                                                domain: envelope.domain.to_string(),
+                                                failure: FailureDetails::new(
+                                                    ResultType::StartTlsNotSupported,
+                                                )
+                                                .with_receiving_mx_hostname(envelope.mx)
+                                                .with_receiving_ip(remote_ip)
+                                                .with_failure_reason_code(reason)
+                                                .into(),
+                                                tls_record: tls_report.record.clone(),
+                                                interval: tls_report.interval,
+                                            })
+                                            .await;
+                                        }
+
+                                        if tls_strategy.is_tls_required()
+                                            || (self.message.flags & MAIL_REQUIRETLS) != 0
+                                            || mta_sts_policy.is_some()
+                                            || dane_policy.is_some()
+                                        {
+                                            last_status =
+                                                Status::from_starttls_error(envelope.mx, response);
+                                            continue 'next_host;
+                                        } else {
+                                            // TLS is not required, proceed in plain-text
+                                            core.queue
+                                                .connections
+                                                .deliver(
+                                                    Connection::new(
+                                                        SmtpConnection::Plain(smtp_client),
+                                                        remote_ip,
+                                                    ),
+                                                    &self.message,
+                                                    recipients
+                                                        .iter_mut()
+                                                        .filter(|r| r.domain_idx == domain_idx),
+                                                    params,
+                                                    reuse.clone(),
+                                                )
+                                                .await
+                                        }
                                     }
-                                    last_status = Status::from_tls_error(envelope.mx, error);
-                                    continue 'next_host;
-                                }
-                            }
-                        } else {
-                            // Start TLS
-                            smtp_client.timeout = *queue_config.timeout.tls.eval(&envelope).await;
-                            let mut smtp_client =
-                                match smtp_client.into_tls(tls_connector, envelope.mx).await {
-                                    Ok(smtp_client) => smtp_client,
-                                    Err(error) => {
+                                    StartTlsResult::Error { error } => {
                                         tracing::info!(
                                             parent: &span,
                                             context = "tls",
@@ -836,51 +1004,154 @@ impl DeliveryAttempt {
                                             error = %error,
                                         );
 
+                                        // Report TLS failure
+                                        if let (Some(tls_report), mail_send::Error::Tls(error)) =
+                                            (&tls_report, &error)
+                                        {
+                                            core.schedule_report(TlsEvent {
+                                                policy: (&mta_sts_policy, &dane_policy).into(),
+                                                domain: envelope.domain.to_string(),
+                                                failure: FailureDetails::new(
+                                                    ResultType::CertificateNotTrusted,
+                                                )
+                                                .with_receiving_mx_hostname(envelope.mx)
+                                                .with_receiving_ip(remote_ip)
+                                                .with_failure_reason_code(error.to_string())
+                                                .into(),
+                                                tls_record: tls_report.record.clone(),
+                                                interval: tls_report.interval,
+                                            })
+                                            .await;
+                                        }
                                         last_status = Status::from_tls_error(envelope.mx, error);
                                         continue 'next_host;
                                     }
-                                };
+                                }
+                            } else {
+                                // Start TLS
+                                smtp_client.timeout =
+                                    *queue_config.timeout.tls.eval(&envelope).await;
+                                let mut smtp_client =
+                                    match smtp_client.into_tls(tls_connector, envelope.mx).await {
+                                        Ok(smtp_client) => smtp_client,
+                                        Err(error) => {
+                                            tracing::info!(
+                                                parent: &span,
+                                                context = "tls",
+                                                event = "failed",
+                                                mx = envelope.mx,
+                                                error = %error,
+                                            );
+
+                                            last_status =
+                                                Status::from_tls_error(envelope.mx, error);
+                                            continue 'next_host;
+                                        }
+                                    };
+
+                                // Read greeting
+                                smtp_client.timeout =
+                                    *queue_config.timeout.greeting.eval(&envelope).await;
+                                if let Err(status) =
+                                    read_greeting(&mut smtp_client, envelope.mx).await
+                                {
+                                    tracing::info!(
+                                        parent: &span,
+                                        context = "greeting",
+                                        event = "invalid",
+                                        mx = envelope.mx,
+                                        status = %status,
+                                    );
 
-                            // Read greeting
-                            smtp_client.timeout =
-                                *queue_config.timeout.greeting.eval(&envelope).await;
-                            if let Err(status) = read_greeting(&mut smtp_client, envelope.mx).await
-                            {
-                                tracing::info!(
-                                    parent: &span,
-                                    context = "greeting",
-                                    event = "invalid",
-                                    mx = envelope.mx,
-                                    status = %status,
-                                );
+                                    break 'attempt Err(status);
+                                }
 
-                                last_status = status;
-                                continue 'next_host;
-                            }
+                                // Deliver message
+                                core.queue
+                                    .connections
+                                    .deliver(
+                                        Connection::new(
+                                            SmtpConnection::Tls(smtp_client),
+                                            remote_ip,
+                                        ),
+                                        &self.message,
+                                        recipients
+                                            .iter_mut()
+                                            .filter(|r| r.domain_idx == domain_idx),
+                                        params,
+                                        reuse.clone(),
+                                    )
+                                    .await
+                            };
 
-                            // Deliver message
-                            self.message
-                                .deliver(
-                                    smtp_client,
-                                    recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
-                                    params,
-                                )
-                                .await
-                        };
+                            break 'attempt Ok(delivery_result);
+                        }
 
-                        // Update status for the current domain and continue with the next one
-                        domain
-                            .set_status(delivery_result, queue_config.retry.eval(&envelope).await);
-                        continue 'next_domain;
+                        continue 'next_host;
+                    };
+
+                    // Learn from deferrals
+                    if let Some(adaptive) = adaptive {
+                        core.queue.adaptive_update(
+                            envelope.mx,
+                            adaptive,
+                            match &result {
+                                Ok(status) | Err(status) => status,
+                            },
+                            recipients
+                                .iter()
+                                .filter(|r| result.is_ok() && r.domain_idx == domain_idx),
+                            &span,
+                        );
+                    }
+
+                    match result {
+                        Ok(delivery_result) => {
+                            // Relay host is reachable
+                            if let NextHop::Relay(relay) = remote_host {
+                                core.queue.relays.set_available(relay);
+                            }
+
+                            // Update status for the current domain and continue with the next one
+                            domain.set_status(
+                                delivery_result,
+                                queue_config.retry.eval(&envelope).await,
+                            );
+                            continue 'next_domain;
+                        }
+                        Err(status) => {
+                            last_status = status;
+                        }
                     }
                 }
 
+                // All hosts failed
+                if let Some(NextHop::Relay(relay)) = remote_hosts.last() {
+                    core.queue.relays.set_unavailable(
+                        relay,
+                        *queue_config.relay.failure_backoff.eval(&envelope).await,
+                        &span,
+                    );
+                }
+
                 // Update status
                 domain.set_status(last_status, queue_config.retry.eval(&envelope).await);
             }
             self.message.domains = domains;
             self.message.recipients = recipients;
 
+            // Update delivery metrics
+            for rcpt in &self.message.recipients {
+                if rcpt.has_flag(RCPT_STATUS_CHANGED) {
+                    match &rcpt.status {
+                        Status::Completed(_) => METRICS.delivery_completed.inc(),
+                        Status::TemporaryFailure(_) => METRICS.delivery_temp_fail.inc(),
+                        Status::PermanentFailure(_) => METRICS.delivery_perm_fail.inc(),
+                        Status::Scheduled => (),
+                    }
+                }
+            }
+
             // Send Delivery Status Notifications
             core.queue.send_dsn(&mut self).await;
 
@@ -891,7 +1162,7 @@ impl DeliveryAttempt {
                 self.message.release_quota();
 
                 // Save changes to disk
-                self.message.save_changes().await;
+                core.queue.save_changes(&mut self.message).await;
 
                 tracing::info!(
                     parent: &span,
@@ -911,7 +1182,7 @@ impl DeliveryAttempt {
                 self.message.release_quota();
 
                 // Save changes to disk
-                self.message.save_changes().await;
+                core.queue.save_changes(&mut self.message).await;
 
                 tracing::info!(
                     parent: &span,
@@ -927,7 +1198,7 @@ impl DeliveryAttempt {
                 })
             } else {
                 // Delete message from queue
-                self.message.remove().await;
+                core.queue.remove_message(&self.message).await;
 
                 tracing::info!(
                     parent: &span,
@@ -26,7 +26,8 @@ use tokio::sync::{mpsc, oneshot};
 use utils::ipc::{DeliveryEvent, DeliveryResult, IngestMessage};
 
 use crate::queue::{
-    Error, ErrorDetails, HostResponse, Message, Recipient, Status, RCPT_STATUS_CHANGED,
+    Error, ErrorDetails, HostResponse, Message, Recipient, Status, MAIL_SENDER_AUTHENTICATED,
+    RCPT_STATUS_CHANGED,
 };
 
 impl Message {
@@ -62,6 +63,7 @@ impl Message {
             .send(DeliveryEvent::Ingest {
                 message: IngestMessage {
                     sender_address: self.return_path_lcase.clone(),
+                    sender_authenticated: (self.flags & MAIL_SENDER_AUTHENTICATED) != 0,
                     recipients: recipient_addresses,
                     message_path: self.path.clone(),
                     message_size: self.size,
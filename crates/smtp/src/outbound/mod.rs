@@ -38,6 +38,8 @@ pub mod delivery;
 pub mod local;
 pub mod lookup;
 pub mod mta_sts;
+pub mod pool;
+pub mod relay;
 pub mod session;
 
 impl Status<(), Error> {
@@ -277,6 +279,14 @@ impl<'x> NextHop<'x> {
         }
     }
 
+    #[inline(always)]
+    fn relay_id(&self) -> Option<&str> {
+        match self {
+            NextHop::MX(_) => None,
+            NextHop::Relay(host) => host.id.as_str().into(),
+        }
+    }
+
     #[inline(always)]
     fn credentials(&self) -> Option<&Credentials<String>> {
         match self {
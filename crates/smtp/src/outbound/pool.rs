@@ -0,0 +1,274 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use mail_send::{smtp::AssertReply, SmtpClient};
+use parking_lot::Mutex;
+use smtp_proto::EhloResponse;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::{
+    config::TlsStrategy,
+    queue::{Error, Message, Recipient, Status},
+};
+
+use super::session::{quit, start_session, SessionParams};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
+    pub hostname: String,
+    pub port: u16,
+    // Relays sharing an address may authenticate with different credentials
+    pub relay_id: Option<String>,
+    pub source_ip: Option<IpAddr>,
+    pub local_hostname: String,
+    pub tls_strategy: TlsStrategy,
+}
+
+pub enum SmtpConnection {
+    Plain(SmtpClient<TcpStream>),
+    Tls(SmtpClient<TlsStream<TcpStream>>),
+}
+
+pub struct Connection {
+    pub client: SmtpConnection,
+    pub remote_ip: IpAddr,
+    capabilities: Option<EhloResponse<String>>,
+    num_messages: usize,
+    id: u64,
+}
+
+#[derive(Clone)]
+pub struct ConnectionReuse {
+    pub key: ConnectionKey,
+    pub idle_timeout: Duration,
+    pub max_messages: usize,
+}
+
+#[derive(Default)]
+pub struct ConnectionPool {
+    idle: Arc<Mutex<AHashMap<ConnectionKey, Vec<Connection>>>>,
+    id_seq: AtomicU64,
+}
+
+impl ConnectionPool {
+    /// Obtains an idle session for the given key, discarding any sessions
+    /// that were closed by the remote host while idle.
+    pub async fn acquire(
+        &self,
+        key: &ConnectionKey,
+        allow_plain: bool,
+        timeout: Duration,
+    ) -> Option<Connection> {
+        loop {
+            let mut conn = {
+                let mut idle = self.idle.lock();
+                let conns = idle.get_mut(key)?;
+                let pos = conns
+                    .iter()
+                    .rposition(|conn| allow_plain || conn.client.is_tls())?;
+                let conn = conns.swap_remove(pos);
+                if conns.is_empty() {
+                    idle.remove(key);
+                }
+                conn
+            };
+
+            if conn.client.noop(timeout).await {
+                return Some(conn);
+            }
+            conn.client.quit().await;
+        }
+    }
+
+    /// Returns a session to the pool and schedules its removal once it has
+    /// been idle for longer than `idle_timeout`.
+    pub fn release(&self, key: ConnectionKey, mut conn: Connection, idle_timeout: Duration) {
+        let id = self.id_seq.fetch_add(1, Ordering::Relaxed);
+        conn.id = id;
+        self.idle.lock().entry(key.clone()).or_default().push(conn);
+
+        let idle = self.idle.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(idle_timeout).await;
+            let conn = {
+                let mut idle = idle.lock();
+                if let Some(conns) = idle.get_mut(&key) {
+                    let conn = conns
+                        .iter()
+                        .position(|conn| conn.id == id)
+                        .map(|pos| conns.swap_remove(pos));
+                    if conns.is_empty() {
+                        idle.remove(&key);
+                    }
+                    conn
+                } else {
+                    None
+                }
+            };
+            if let Some(conn) = conn {
+                conn.client.quit().await;
+            }
+        });
+    }
+
+    /// Returns the number of idle sessions currently held by the pool.
+    pub fn num_idle(&self) -> usize {
+        self.idle.lock().values().map(|conns| conns.len()).sum()
+    }
+
+    /// Delivers a message over a new or pooled session. The session is
+    /// returned to the pool afterwards if reuse is enabled and the
+    /// transaction left it in a clean state, otherwise it is closed.
+    pub async fn deliver(
+        &self,
+        mut conn: Connection,
+        message: &Message,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: SessionParams<'_>,
+        reuse: Option<ConnectionReuse>,
+    ) -> Status<(), Error> {
+        let capabilities = match conn.capabilities.take() {
+            Some(capabilities) => capabilities,
+            None => match conn.client.start_session(&params).await {
+                Ok(capabilities) => capabilities,
+                Err(status) => {
+                    conn.client.quit().await;
+                    return status;
+                }
+            },
+        };
+
+        match conn
+            .client
+            .send_transaction(message, &capabilities, recipients, &params)
+            .await
+        {
+            Ok(status) => {
+                conn.num_messages += 1;
+                match reuse {
+                    Some(reuse) if conn.num_messages < reuse.max_messages => {
+                        tracing::debug!(
+                            parent: params.span,
+                            context = "connection",
+                            event = "idle",
+                            mx = &params.hostname,
+                            messages = conn.num_messages,
+                        );
+                        conn.capabilities = capabilities.into();
+                        self.release(reuse.key, conn, reuse.idle_timeout);
+                    }
+                    _ => {
+                        conn.client.quit().await;
+                    }
+                }
+                status
+            }
+            Err(status) => {
+                conn.client.quit().await;
+                status
+            }
+        }
+    }
+}
+
+impl Connection {
+    pub fn new(client: SmtpConnection, remote_ip: IpAddr) -> Self {
+        Connection {
+            client,
+            remote_ip,
+            capabilities: None,
+            num_messages: 0,
+            id: 0,
+        }
+    }
+}
+
+impl SmtpConnection {
+    pub fn is_tls(&self) -> bool {
+        matches!(self, SmtpConnection::Tls(_))
+    }
+
+    async fn start_session(
+        &mut self,
+        params: &SessionParams<'_>,
+    ) -> Result<EhloResponse<String>, Status<(), Error>> {
+        match self {
+            SmtpConnection::Plain(client) => start_session(client, params).await,
+            SmtpConnection::Tls(client) => start_session(client, params).await,
+        }
+    }
+
+    async fn send_transaction(
+        &mut self,
+        message: &Message,
+        capabilities: &EhloResponse<String>,
+        recipients: impl Iterator<Item = &mut Recipient>,
+        params: &SessionParams<'_>,
+    ) -> Result<Status<(), Error>, Status<(), Error>> {
+        match self {
+            SmtpConnection::Plain(client) => {
+                message
+                    .send_transaction(client, capabilities, recipients, params)
+                    .await
+            }
+            SmtpConnection::Tls(client) => {
+                message
+                    .send_transaction(client, capabilities, recipients, params)
+                    .await
+            }
+        }
+    }
+
+    async fn noop(&mut self, timeout: Duration) -> bool {
+        match self {
+            SmtpConnection::Plain(client) => {
+                client.timeout = timeout;
+                client.cmd(b"NOOP\r\n").await
+            }
+            SmtpConnection::Tls(client) => {
+                client.timeout = timeout;
+                client.cmd(b"NOOP\r\n").await
+            }
+        }
+        .and_then(|r| r.assert_positive_completion())
+        .is_ok()
+    }
+
+    pub async fn quit(self) {
+        match self {
+            SmtpConnection::Plain(client) => quit(client).await,
+            SmtpConnection::Tls(client) => quit(client).await,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+use crate::config::{RelayHost, RelayStrategy};
+
+use super::NextHop;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub struct RelayState {
+    unavailable: Arc<DashMap<String, Instant>>,
+    seq: AtomicU64,
+}
+
+impl RelayState {
+    /// Returns the order in which relay hosts should be tried. Hosts that
+    /// recently failed are moved to the end of the list and only used as a
+    /// last resort.
+    pub fn next_hops<'x>(
+        &self,
+        hosts: &'x [RelayHost],
+        strategy: RelayStrategy,
+    ) -> Vec<NextHop<'x>> {
+        let mut next_hops = hosts.iter().collect::<Vec<_>>();
+
+        if strategy == RelayStrategy::RoundRobin && next_hops.len() > 1 {
+            // Pick the first host proportionally to its weight
+            let total_weight = hosts.iter().map(|host| host.weight as u64).sum::<u64>();
+            let mut slot = self.seq.fetch_add(1, Ordering::Relaxed) % total_weight.max(1);
+            let first = hosts
+                .iter()
+                .position(|host| {
+                    if slot < host.weight as u64 {
+                        true
+                    } else {
+                        slot -= host.weight as u64;
+                        false
+                    }
+                })
+                .unwrap_or(0);
+            next_hops.rotate_left(first);
+        }
+
+        if !self.unavailable.is_empty() {
+            let now = Instant::now();
+            next_hops.sort_by_key(|host| !self.is_available(host, now));
+        }
+
+        next_hops.into_iter().map(NextHop::Relay).collect()
+    }
+
+    /// Marks a relay host as unavailable and starts probing it in the
+    /// background. The host stays at the end of the list until it accepts a
+    /// TCP connection or delivers a message successfully.
+    pub fn set_unavailable(&self, host: &RelayHost, backoff: Duration, span: &tracing::Span) {
+        if !backoff.is_zero() {
+            tracing::info!(
+                parent: span,
+                context = "relay",
+                event = "unavailable",
+                relay = host.id,
+                backoff = backoff.as_secs(),
+                "Relay host failed, trying other relays first."
+            );
+
+            if self
+                .unavailable
+                .insert(host.id.clone(), Instant::now() + backoff)
+                .is_none()
+            {
+                self.spawn_probe(host, backoff, span.clone());
+            }
+        }
+    }
+
+    fn spawn_probe(&self, host: &RelayHost, backoff: Duration, span: tracing::Span) {
+        let unavailable = self.unavailable.clone();
+        let id = host.id.clone();
+        let address = host.address.clone();
+        let port = host.port;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(backoff).await;
+
+                // Keep the host demoted while the probe is in flight, stop
+                // probing if it was marked as available in the meantime
+                match unavailable.get_mut(&id) {
+                    Some(mut until) => {
+                        *until = Instant::now() + backoff;
+                    }
+                    None => break,
+                }
+
+                match tokio::time::timeout(
+                    PROBE_TIMEOUT,
+                    tokio::net::TcpStream::connect((address.as_str(), port)),
+                )
+                .await
+                {
+                    Ok(Ok(_)) => {
+                        tracing::info!(
+                            parent: &span,
+                            context = "relay",
+                            event = "available",
+                            relay = id,
+                            "Relay host is reachable again."
+                        );
+                        unavailable.remove(&id);
+                        break;
+                    }
+                    Ok(Err(err)) => {
+                        tracing::debug!(
+                            parent: &span,
+                            context = "relay",
+                            event = "probe-failed",
+                            relay = id,
+                            reason = %err,
+                        );
+                    }
+                    Err(_) => {
+                        tracing::debug!(
+                            parent: &span,
+                            context = "relay",
+                            event = "probe-failed",
+                            relay = id,
+                            reason = "Connection timed out",
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn set_available(&self, host: &RelayHost) {
+        if !self.unavailable.is_empty() {
+            self.unavailable.remove(&host.id);
+        }
+    }
+
+    fn is_available(&self, host: &RelayHost, now: Instant) -> bool {
+        self.unavailable
+            .get(&host.id)
+            .map_or(true, |until| *until <= now)
+    }
+}
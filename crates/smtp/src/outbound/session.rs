@@ -56,62 +56,18 @@ pub struct SessionParams<'x> {
 }
 
 impl Message {
-    pub async fn deliver<T: AsyncRead + AsyncWrite + Unpin>(
+    /// Sends a single mail transaction over an established session. Returns `Ok` when
+    /// the session is left in a clean state and can be reused for another transaction.
+    pub async fn send_transaction<T: AsyncRead + AsyncWrite + Unpin>(
         &self,
-        mut smtp_client: SmtpClient<T>,
+        smtp_client: &mut SmtpClient<T>,
+        capabilities: &EhloResponse<String>,
         recipients: impl Iterator<Item = &mut Recipient>,
-        params: SessionParams<'_>,
-    ) -> Status<(), Error> {
-        // Obtain capabilities
-        let mut capabilities = match say_helo(&mut smtp_client, &params).await {
-            Ok(capabilities) => capabilities,
-            Err(status) => {
-                tracing::info!(
-                    parent: params.span,
-                    context = "ehlo",
-                    event = "rejected",
-                    mx = &params.hostname,
-                    reason = %status,
-                );
-                quit(smtp_client).await;
-                return status;
-            }
-        };
-
-        // Authenticate
-        if let Some(credentials) = params.credentials {
-            if let Err(err) = smtp_client.authenticate(credentials, &capabilities).await {
-                tracing::info!(
-                    parent: params.span,
-                    context = "auth",
-                    event = "failed",
-                    mx = &params.hostname,
-                    reason = %err,
-                );
-                quit(smtp_client).await;
-                return Status::from_smtp_error(params.hostname, "AUTH ...", err);
-            }
-
-            // Refresh capabilities
-            capabilities = match say_helo(&mut smtp_client, &params).await {
-                Ok(capabilities) => capabilities,
-                Err(status) => {
-                    tracing::info!(
-                        parent: params.span,
-                        context = "ehlo",
-                        event = "rejected",
-                        mx = &params.hostname,
-                        reason = %status,
-                    );
-                    quit(smtp_client).await;
-                    return status;
-                }
-            };
-        }
-
+        params: &SessionParams<'_>,
+    ) -> Result<Status<(), Error>, Status<(), Error>> {
         // MAIL FROM
         smtp_client.timeout = params.timeout_mail;
-        let cmd = self.build_mail_from(&capabilities);
+        let cmd = self.build_mail_from(capabilities);
         if let Err(err) = smtp_client
             .cmd(cmd.as_bytes())
             .await
@@ -124,8 +80,7 @@ impl Message {
                 mx = &params.hostname,
                 reason = %err,
             );
-            quit(smtp_client).await;
-            return Status::from_smtp_error(params.hostname, &cmd, err);
+            return Err(Status::from_smtp_error(params.hostname, &cmd, err));
         }
 
         // RCPT TO
@@ -143,7 +98,7 @@ impl Message {
                 continue;
             }
 
-            let cmd = self.build_rcpt_to(rcpt, &capabilities);
+            let cmd = self.build_rcpt_to(rcpt, capabilities);
             match smtp_client.cmd(cmd.as_bytes()).await {
                 Ok(response) => match response.severity() {
                     Severity::PositiveCompletion => {
@@ -192,21 +147,21 @@ impl Message {
                     );
 
                     // Something went wrong, abort.
-                    quit(smtp_client).await;
-                    return Status::from_smtp_error(params.hostname, "", err);
+                    return Err(Status::from_smtp_error(params.hostname, "", err));
                 }
             }
         }
 
         // Send message
-        if !accepted_rcpts.is_empty() {
+        let accepted_rcpts_empty = accepted_rcpts.is_empty();
+        if !accepted_rcpts_empty {
             let bdat_cmd = if capabilities.has_capability(EXT_CHUNKING) {
                 format!("BDAT {} LAST\r\n", self.size).into()
             } else {
                 None
             };
 
-            if let Err(status) = send_message(&mut smtp_client, self, &bdat_cmd, &params).await {
+            if let Err(status) = send_message(smtp_client, self, &bdat_cmd, params).await {
                 tracing::info!(
                     parent: params.span,
                     context = "message",
@@ -215,13 +170,12 @@ impl Message {
                     reason = %status,
                 );
 
-                quit(smtp_client).await;
-                return status;
+                return Err(status);
             }
 
             if params.is_smtp {
                 // Handle SMTP response
-                match read_smtp_data_respone(&mut smtp_client, params.hostname, &bdat_cmd).await {
+                match read_smtp_data_respone(smtp_client, params.hostname, &bdat_cmd).await {
                     Ok(response) => {
                         // Mark recipients as delivered
                         if response.code() == 250 {
@@ -248,12 +202,11 @@ impl Message {
                                 reason = %response,
                             );
 
-                            quit(smtp_client).await;
-                            return Status::from_smtp_error(
+                            return Err(Status::from_smtp_error(
                                 params.hostname,
                                 bdat_cmd.as_deref().unwrap_or("DATA"),
                                 mail_send::Error::UnexpectedReply(response),
-                            );
+                            ));
                         }
                     }
                     Err(status) => {
@@ -265,18 +218,13 @@ impl Message {
                             reason = %status,
                         );
 
-                        quit(smtp_client).await;
-                        return status;
+                        return Err(status);
                     }
                 }
             } else {
                 // Handle LMTP responses
-                match read_lmtp_data_respone(
-                    &mut smtp_client,
-                    params.hostname,
-                    accepted_rcpts.len(),
-                )
-                .await
+                match read_lmtp_data_respone(smtp_client, params.hostname, accepted_rcpts.len())
+                    .await
                 {
                     Ok(responses) => {
                         for ((rcpt, _), response) in accepted_rcpts.into_iter().zip(responses) {
@@ -337,19 +285,32 @@ impl Message {
                             reason = %status,
                         );
 
-                        quit(smtp_client).await;
-                        return status;
+                        return Err(status);
                     }
                 }
             }
         }
 
-        quit(smtp_client).await;
-        if total_completed == total_rcpt {
+        let status = if total_completed == total_rcpt {
             Status::Completed(())
         } else {
             Status::Scheduled
+        };
+
+        // Abort the transaction if no recipients were accepted
+        if accepted_rcpts_empty {
+            smtp_client.timeout = params.timeout_mail;
+            if smtp_client
+                .cmd(b"RSET\r\n")
+                .await
+                .and_then(|r| r.assert_positive_completion())
+                .is_err()
+            {
+                return Err(status);
+            }
         }
+
+        Ok(status)
     }
 
     fn build_mail_from(&self, capabilities: &EhloResponse<String>) -> String {
@@ -570,6 +531,57 @@ pub async fn send_message<T: AsyncRead + AsyncWrite + Unpin>(
     })
 }
 
+pub async fn start_session<T: AsyncRead + AsyncWrite + Unpin>(
+    smtp_client: &mut SmtpClient<T>,
+    params: &SessionParams<'_>,
+) -> Result<EhloResponse<String>, Status<(), Error>> {
+    // Obtain capabilities
+    let capabilities = match say_helo(smtp_client, params).await {
+        Ok(capabilities) => capabilities,
+        Err(status) => {
+            tracing::info!(
+                parent: params.span,
+                context = "ehlo",
+                event = "rejected",
+                mx = &params.hostname,
+                reason = %status,
+            );
+            return Err(status);
+        }
+    };
+
+    // Authenticate
+    if let Some(credentials) = params.credentials {
+        if let Err(err) = smtp_client.authenticate(credentials, &capabilities).await {
+            tracing::info!(
+                parent: params.span,
+                context = "auth",
+                event = "failed",
+                mx = &params.hostname,
+                reason = %err,
+            );
+            return Err(Status::from_smtp_error(params.hostname, "AUTH ...", err));
+        }
+
+        // Refresh capabilities
+        return match say_helo(smtp_client, params).await {
+            Ok(capabilities) => Ok(capabilities),
+            Err(status) => {
+                tracing::info!(
+                    parent: params.span,
+                    context = "ehlo",
+                    event = "rejected",
+                    mx = &params.hostname,
+                    reason = %status,
+                );
+                Err(status)
+            }
+        };
+    }
+
+    Ok(capabilities)
+}
+
 pub async fn say_helo<T: AsyncRead + AsyncWrite + Unpin>(
     smtp_client: &mut SmtpClient<T>,
     params: &SessionParams<'_>,
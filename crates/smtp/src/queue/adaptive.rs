@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{Duration, Instant, SystemTime};
+
+use smtp_proto::Response;
+
+use crate::core::QueueCore;
+
+use super::{throttle, Error, HostResponse, Recipient, Status};
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimiter {
+    pub delay: Duration,
+    pub next_attempt: Instant,
+    pub deferrals: u64,
+    pub last_deferral: u64,
+    pub last_response: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveBackoff {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl QueueCore {
+    pub fn is_adaptive_allowed(
+        &self,
+        mx: &str,
+        span: &tracing::Span,
+    ) -> Result<(), throttle::Error> {
+        if let Some(mut limiter) = self.adaptive.get_mut(mx) {
+            let now = Instant::now();
+            if limiter.next_attempt > now {
+                tracing::info!(
+                    parent: span,
+                    context = "throttle",
+                    event = "adaptive-backoff",
+                    mx = mx,
+                    delay = limiter.delay.as_secs(),
+                    "Delivery to host is being slowed down after deferrals."
+                );
+                return Err(throttle::Error::Rate {
+                    retry_at: limiter.next_attempt,
+                });
+            }
+
+            // Space out deliveries to this host while it is recovering
+            limiter.next_attempt = now + limiter.delay;
+        }
+
+        Ok(())
+    }
+
+    pub fn adaptive_update<'x>(
+        &self,
+        mx: &str,
+        backoff: AdaptiveBackoff,
+        status: &Status<(), Error>,
+        mut recipients: impl Iterator<Item = &'x Recipient>,
+        span: &tracing::Span,
+    ) {
+        let deferral = match status {
+            Status::TemporaryFailure(Error::UnexpectedResponse(HostResponse {
+                response, ..
+            })) if response.is_deferral() => Some(response),
+            Status::Completed(_) => recipients.find_map(|rcpt| match &rcpt.status {
+                Status::TemporaryFailure(HostResponse { response, .. })
+                    if response.is_deferral() =>
+                {
+                    Some(response)
+                }
+                _ => None,
+            }),
+            _ => None,
+        };
+
+        if let Some(response) = deferral {
+            let mut limiter =
+                self.adaptive
+                    .entry(mx.to_string())
+                    .or_insert_with(|| AdaptiveLimiter {
+                        delay: Duration::ZERO,
+                        next_attempt: Instant::now(),
+                        deferrals: 0,
+                        last_deferral: 0,
+                        last_response: String::new(),
+                    });
+            limiter.delay = std::cmp::min(
+                std::cmp::max(limiter.delay * 2, backoff.initial_delay),
+                backoff.max_delay,
+            );
+            limiter.next_attempt = Instant::now() + limiter.delay;
+            limiter.deferrals += 1;
+            limiter.last_deferral = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            limiter.last_response = response.to_string();
+
+            tracing::info!(
+                parent: span,
+                context = "throttle",
+                event = "adaptive-slowdown",
+                mx = mx,
+                delay = limiter.delay.as_secs(),
+                response = %response,
+                "Host deferred delivery, slowing down."
+            );
+        } else if matches!(status, Status::Completed(_)) {
+            let is_recovered = self.adaptive.get_mut(mx).map_or(false, |mut limiter| {
+                limiter.delay /= 2;
+                limiter.delay < backoff.initial_delay
+            });
+            if is_recovered {
+                self.adaptive.remove(mx);
+
+                tracing::debug!(
+                    parent: span,
+                    context = "throttle",
+                    event = "adaptive-recovered",
+                    mx = mx,
+                );
+            }
+        }
+    }
+}
+
+pub trait IsDeferral {
+    fn is_deferral(&self) -> bool;
+}
+
+impl IsDeferral for Response<String> {
+    fn is_deferral(&self) -> bool {
+        // 421 service not available or 4.7.x policy deferrals
+        self.code == 421 || (self.code / 100 == 4 && self.esc[0] == 4 && self.esc[1] == 7)
+    }
+}
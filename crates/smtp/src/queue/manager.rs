@@ -30,6 +30,7 @@ use std::{
 use ahash::AHashMap;
 use smtp_proto::Response;
 use tokio::sync::mpsc;
+use utils::metrics::METRICS;
 
 use crate::core::{
     management::{self},
@@ -48,6 +49,7 @@ pub struct Queue {
     pub scheduled: BinaryHeap<Schedule<QueueId>>,
     pub on_hold: Vec<OnHold<QueueId>>,
     pub messages: AHashMap<QueueId, Box<Message>>,
+    pub next_poll: Option<Instant>,
 }
 
 impl SpawnQueue for mpsc::Receiver<Event> {
@@ -56,6 +58,11 @@ impl SpawnQueue for mpsc::Receiver<Event> {
             loop {
                 let result = tokio::time::timeout(queue.wake_up_time(), self.recv()).await;
 
+                // Fetch due messages from the queue store
+                if queue.is_poll_due() {
+                    core.queue.poll_store(&mut queue).await;
+                }
+
                 // Deliver scheduled messages
                 while let Some(message) = queue.next_due() {
                     DeliveryAttempt::from(message)
@@ -228,15 +235,15 @@ impl SpawnQueue for mpsc::Receiver<Event> {
                                                             | Status::Scheduled
                                                     )
                                                 }) {
-                                                    message.save_changes().await;
+                                                    core.queue.save_changes(message).await;
                                                 } else {
-                                                    message.remove().await;
+                                                    core.queue.remove_message(message).await;
                                                     queue.messages.remove(queue_id);
                                                 }
                                             }
                                         }
                                     } else if let Some(message) = queue.messages.remove(queue_id) {
-                                        message.remove().await;
+                                        core.queue.remove_message(&message).await;
                                         found = true;
                                     }
                                     result.push(found);
@@ -272,7 +279,7 @@ impl SpawnQueue for mpsc::Receiver<Event> {
 
                                         if found {
                                             queue.on_hold.retain(|oh| &oh.message != queue_id);
-                                            message.save_changes().await;
+                                            core.queue.save_changes(message).await;
                                             if let Some(next_event) = message.next_event() {
                                                 queue.scheduled.push(Schedule {
                                                     due: next_event,
@@ -339,14 +346,35 @@ impl Queue {
     }
 
     pub fn wake_up_time(&self) -> Duration {
-        self.scheduled
+        let wake_up = self
+            .scheduled
             .peek()
             .map(|item| {
                 item.due
                     .checked_duration_since(Instant::now())
                     .unwrap_or(self.short_wait)
             })
-            .unwrap_or(self.long_wait)
+            .unwrap_or(self.long_wait);
+
+        if let Some(next_poll) = self.next_poll {
+            std::cmp::min(
+                wake_up,
+                next_poll
+                    .checked_duration_since(Instant::now())
+                    .unwrap_or(self.short_wait),
+            )
+        } else {
+            wake_up
+        }
+    }
+
+    pub fn is_poll_due(&self) -> bool {
+        self.next_poll
+            .map_or(false, |next_poll| next_poll <= Instant::now())
+    }
+
+    pub fn schedule_poll(&mut self, interval: Duration) {
+        self.next_poll = Some(Instant::now() + interval);
     }
 }
 
@@ -436,6 +464,12 @@ impl QueueCore {
         let mut queue = Queue::default();
         let mut messages = Vec::new();
 
+        // Due messages are fetched from the queue store on the first poll
+        if self.store.is_some() {
+            queue.next_poll = Some(Instant::now());
+            return queue;
+        }
+
         for path in self
             .config
             .path
@@ -513,6 +547,7 @@ impl QueueCore {
                     self.has_quota(&mut message).await;
 
                     // Schedule message
+                    METRICS.queue_messages.inc();
                     queue.schedule(Schedule {
                         due: message.next_event().unwrap_or_else(|| {
                             tracing::warn!(
@@ -552,6 +587,7 @@ impl Default for Queue {
             scheduled: BinaryHeap::with_capacity(128),
             on_hold: Vec::with_capacity(128),
             messages: AHashMap::with_capacity(128),
+            next_poll: None,
         }
     }
 }
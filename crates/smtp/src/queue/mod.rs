@@ -35,11 +35,13 @@ use utils::listener::limiter::{ConcurrencyLimiter, InFlight};
 
 use crate::core::{management, Envelope};
 
+pub mod adaptive;
 pub mod dsn;
 pub mod manager;
 pub mod quota;
 pub mod serialize;
 pub mod spool;
+pub mod store;
 pub mod throttle;
 
 pub type QueueId = u64;
@@ -115,6 +117,9 @@ pub struct Recipient {
 pub const RCPT_DSN_SENT: u64 = 1 << 32;
 pub const RCPT_STATUS_CHANGED: u64 = 2 << 32;
 
+// Set when SMTP AUTH, SPF or an aligned DKIM signature vouch for the return path
+pub const MAIL_SENDER_AUTHENTICATED: u64 = 1 << 32;
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status<T, E> {
     #[serde(rename = "scheduled")]
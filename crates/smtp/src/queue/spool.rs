@@ -33,6 +33,7 @@ use tokio::{fs, io::AsyncWriteExt};
 
 use crate::config::QueueConfig;
 use crate::core::QueueCore;
+use utils::metrics::METRICS;
 
 use super::{Domain, Event, Message, Recipient, Schedule, SimpleEnvelope, Status};
 
@@ -53,23 +54,14 @@ impl QueueCore {
         }
 
         // Build path
-        message.path = self.config.path.eval(message.as_ref()).await.clone();
-        let hash = *self.config.hash.eval(message.as_ref()).await;
-        if hash > 0 {
-            message.path.push((message.id % hash).to_string());
-        }
-        let _ = fs::create_dir(&message.path).await;
-
-        // Encode file name
-        let mut encoder = Base32Writer::with_capacity(20);
-        encoder.write(&message.id.to_le_bytes()[..]);
-        encoder.write(&(message.size as u32).to_le_bytes()[..]);
-        let mut file = encoder.finalize();
-        file.push_str(".msg");
-        message.path.push(file);
+        message.path = self.build_path(message.as_ref()).await;
 
-        // Serialize metadata
-        let metadata = message.serialize();
+        // Serialize metadata, store-backed queues keep it in the store instead
+        let metadata = if self.store.is_none() {
+            message.serialize()
+        } else {
+            Vec::new()
+        };
 
         // Save message
         let mut file = match fs::File::create(&message.path).await {
@@ -120,6 +112,23 @@ impl QueueCore {
             return false;
         }
 
+        // Persist message in the queue store
+        if let Some(store) = &self.store {
+            if let Err(err) = store.write(&message, raw_headers, raw_message).await {
+                tracing::error!(
+                    parent: span,
+                    context = "queue",
+                    event = "error",
+                    "Failed to write message to queue store: {}",
+                    err
+                );
+                message.remove().await;
+                return false;
+            }
+        }
+
+        METRICS.queue_messages.inc();
+
         tracing::info!(
             parent: span,
             context = "queue",
@@ -156,6 +165,24 @@ impl QueueCore {
         true
     }
 
+    pub async fn build_path(&self, message: &Message) -> PathBuf {
+        let mut path = self.config.path.eval(message).await.clone();
+        let hash = *self.config.hash.eval(message).await;
+        if hash > 0 {
+            path.push((message.id % hash).to_string());
+        }
+        let _ = fs::create_dir(&path).await;
+
+        // Encode file name
+        let mut encoder = Base32Writer::with_capacity(20);
+        encoder.write(&message.id.to_le_bytes()[..]);
+        encoder.write(&(message.size as u32).to_le_bytes()[..]);
+        let mut file = encoder.finalize();
+        file.push_str(".msg");
+        path.push(file);
+        path
+    }
+
     pub fn queue_id(&self) -> u64 {
         (SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -0,0 +1,662 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use store::{
+    write::{
+        assert::HashedValue,
+        key::{DeserializeBigEndian, KeySerializer},
+        now, BatchBuilder, Operation, ValueClass,
+    },
+    BlobKind, CustomValueKey, Deserialize, Store,
+};
+use utils::{config::Config, metrics::METRICS};
+
+use crate::core::QueueCore;
+
+use super::{instant_to_timestamp, manager::Queue, Message, QueueId, Schedule};
+
+pub const QUEUE_COLLECTION: u8 = u8::MAX - 1;
+
+const QUEUE_MESSAGE: u8 = 2;
+const QUEUE_LEASE: u8 = 3;
+const QUEUE_DUE: u8 = 4;
+
+pub struct QueueStore {
+    pub store: Arc<Store>,
+    pub node_id: u64,
+    pub lease_time: Duration,
+    pub poll_interval: Duration,
+    pub poll_batch: usize,
+}
+
+struct QueueRecord {
+    document_id: u32,
+    size: u64,
+    due: u64,
+    metadata: Vec<u8>,
+}
+
+struct QueueLease {
+    node_id: u64,
+    expires: u64,
+}
+
+impl QueueStore {
+    pub fn init(config: &Config, store: Arc<Store>) -> Result<Option<Self>, String> {
+        match config.value("queue.backend").unwrap_or("fs") {
+            "fs" => Ok(None),
+            "store" => Ok(Some(QueueStore {
+                store,
+                node_id: rand::random(),
+                lease_time: config.property_or_static("queue.store.lease-time", "10m")?,
+                poll_interval: config.property_or_static("queue.store.poll-interval", "15s")?,
+                poll_batch: config.property_or_static("queue.store.poll-batch", "100")?,
+            })),
+            backend => Err(format!(
+                "Invalid value {backend:?} for property \"queue.backend\"."
+            )),
+        }
+    }
+
+    pub async fn write(
+        &self,
+        message: &Message,
+        raw_headers: Option<&[u8]>,
+        raw_message: &[u8],
+    ) -> store::Result<()> {
+        // Store message contents
+        let document_id = self
+            .store
+            .assign_document_id(u32::MAX, QUEUE_COLLECTION)
+            .await?;
+        let blob_kind = blob_kind(document_id);
+        if let Some(raw_headers) = raw_headers {
+            let mut raw = Vec::with_capacity(raw_headers.len() + raw_message.len());
+            raw.extend_from_slice(raw_headers);
+            raw.extend_from_slice(raw_message);
+            self.store.put_blob(&blob_kind, &raw).await?;
+        } else {
+            self.store.put_blob(&blob_kind, raw_message).await?;
+        }
+
+        // Store metadata, due time and lease
+        let record = QueueRecord {
+            document_id,
+            size: message.size as u64,
+            due: message.due_timestamp(),
+            metadata: message.serialize(),
+        };
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(QUEUE_COLLECTION)
+            .create_document(document_id)
+            .assert_value(
+                ValueClass::Custom {
+                    bytes: message_key(message.id),
+                },
+                (),
+            )
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: due_key(record.due, message.id),
+                },
+                set: Some(vec![]),
+            })
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: lease_key(message.id),
+                },
+                set: Some(self.new_lease()),
+            })
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: message_key(message.id),
+                },
+                set: Some(record.serialize()),
+            });
+
+        match self.store.write(batch.build()).await {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                let _ = self.store.delete_blob(&blob_kind).await;
+                Err(err)
+            }
+        }
+    }
+
+    pub async fn update(&self, message: &Message) -> store::Result<bool> {
+        let record = if let Some(record) = self
+            .store
+            .get_value::<HashedValue<QueueRecord>>(CustomValueKey {
+                value: message_key(message.id),
+            })
+            .await?
+        {
+            record
+        } else {
+            return Ok(false);
+        };
+
+        let due = message.due_timestamp();
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(QUEUE_COLLECTION)
+            .assert_value(
+                ValueClass::Custom {
+                    bytes: message_key(message.id),
+                },
+                &record,
+            );
+        if record.inner.due != due {
+            batch
+                .op(Operation::Value {
+                    class: ValueClass::Custom {
+                        bytes: due_key(record.inner.due, message.id),
+                    },
+                    set: None,
+                })
+                .op(Operation::Value {
+                    class: ValueClass::Custom {
+                        bytes: due_key(due, message.id),
+                    },
+                    set: Some(vec![]),
+                });
+        }
+        batch.op(Operation::Value {
+            class: ValueClass::Custom {
+                bytes: message_key(message.id),
+            },
+            set: Some(
+                QueueRecord {
+                    document_id: record.inner.document_id,
+                    size: record.inner.size,
+                    due,
+                    metadata: message.serialize(),
+                }
+                .serialize(),
+            ),
+        });
+
+        match self.store.write(batch.build()).await {
+            Ok(_) => Ok(true),
+            Err(store::Error::AssertValueFailed) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn read(&self, id: QueueId) -> store::Result<Option<(Message, Vec<u8>)>> {
+        let record = if let Some(record) = self
+            .store
+            .get_value::<QueueRecord>(CustomValueKey {
+                value: message_key(id),
+            })
+            .await?
+        {
+            record
+        } else {
+            return Ok(None);
+        };
+        let raw_message = self
+            .store
+            .get_blob(&blob_kind(record.document_id), 0..u32::MAX)
+            .await?
+            .ok_or_else(|| {
+                store::Error::InternalError(format!("Contents of queued message {id} not found."))
+            })?;
+        let mut message = Message::deserialize(&record.metadata).ok_or_else(|| {
+            store::Error::InternalError(format!("Failed to deserialize queued message {id}."))
+        })?;
+        message.id = id;
+        message.size = record.size as usize;
+
+        Ok(Some((message, raw_message)))
+    }
+
+    // Removes a message from the queue store, fails if another node holds its lease
+    pub async fn delete(&self, id: QueueId) -> store::Result<bool> {
+        let lease = self
+            .store
+            .get_value::<HashedValue<QueueLease>>(CustomValueKey {
+                value: lease_key(id),
+            })
+            .await?;
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(QUEUE_COLLECTION);
+        match &lease {
+            Some(lease) if lease.inner.node_id != self.node_id => return Ok(false),
+            Some(lease) => {
+                batch.assert_value(
+                    ValueClass::Custom {
+                        bytes: lease_key(id),
+                    },
+                    lease,
+                );
+            }
+            None => {
+                batch.assert_value(
+                    ValueClass::Custom {
+                        bytes: lease_key(id),
+                    },
+                    (),
+                );
+            }
+        }
+        let record = self
+            .store
+            .get_value::<QueueRecord>(CustomValueKey {
+                value: message_key(id),
+            })
+            .await?;
+        if let Some(record) = &record {
+            batch
+                .delete_document(record.document_id)
+                .op(Operation::Value {
+                    class: ValueClass::Custom {
+                        bytes: message_key(id),
+                    },
+                    set: None,
+                })
+                .op(Operation::Value {
+                    class: ValueClass::Custom {
+                        bytes: due_key(record.due, id),
+                    },
+                    set: None,
+                });
+        }
+        batch.op(Operation::Value {
+            class: ValueClass::Custom {
+                bytes: lease_key(id),
+            },
+            set: None,
+        });
+        match self.store.write(batch.build()).await {
+            Ok(_) => (),
+            Err(store::Error::AssertValueFailed) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+
+        if let Some(record) = record {
+            self.store
+                .delete_blob(&blob_kind(record.document_id))
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    pub async fn due_messages(&self) -> store::Result<Vec<QueueId>> {
+        let from_key = due_key(0, 0);
+        let to_key = due_key(now(), u64::MAX);
+        let max_results = self.poll_batch;
+
+        self.store
+            .iterate(
+                Vec::new(),
+                CustomValueKey { value: from_key },
+                CustomValueKey { value: to_key },
+                false,
+                true,
+                move |ids, key, _| {
+                    ids.push(key.deserialize_be_u64(key.len() - std::mem::size_of::<u64>())?);
+                    Ok(ids.len() < max_results)
+                },
+            )
+            .await
+    }
+
+    // Obtains a lease on a message that is not currently held by any node, including
+    // expired leases of this node on messages that were released
+    pub async fn acquire_lease(&self, id: QueueId) -> store::Result<bool> {
+        let lease = self
+            .store
+            .get_value::<HashedValue<QueueLease>>(CustomValueKey {
+                value: lease_key(id),
+            })
+            .await?;
+        match &lease {
+            Some(lease) if lease.inner.expires > now() => Ok(false),
+            Some(lease) => self.write_lease(id, lease).await,
+            None => self.write_lease(id, ()).await,
+        }
+    }
+
+    // Extends a lease held by this node, fails if another node took over the message
+    pub async fn renew_lease(&self, id: QueueId) -> store::Result<bool> {
+        match self
+            .store
+            .get_value::<HashedValue<QueueLease>>(CustomValueKey {
+                value: lease_key(id),
+            })
+            .await?
+        {
+            Some(lease) if lease.inner.node_id == self.node_id => {
+                self.write_lease(id, &lease).await
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn write_lease(
+        &self,
+        id: QueueId,
+        current: impl store::write::assert::ToAssertValue,
+    ) -> store::Result<bool> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(QUEUE_COLLECTION)
+            .assert_value(
+                ValueClass::Custom {
+                    bytes: lease_key(id),
+                },
+                current,
+            )
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: lease_key(id),
+                },
+                set: Some(self.new_lease()),
+            });
+
+        match self.store.write(batch.build()).await {
+            Ok(_) => Ok(true),
+            Err(store::Error::AssertValueFailed) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn new_lease(&self) -> Vec<u8> {
+        KeySerializer::new(std::mem::size_of::<u64>() * 2)
+            .write(self.node_id)
+            .write(now() + self.lease_time.as_secs())
+            .finalize()
+    }
+}
+
+impl QueueCore {
+    pub async fn save_changes(&self, message: &mut Message) {
+        if let Some(store) = &self.store {
+            if !message.serialize_changes().is_empty() {
+                match store.update(message).await {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        tracing::warn!(
+                            context = "queue",
+                            event = "error",
+                            id = message.id,
+                            "Queued message was modified or removed by another node."
+                        );
+                    }
+                    Err(err) => {
+                        tracing::error!(
+                            context = "queue",
+                            event = "error",
+                            id = message.id,
+                            "Failed to update queued message: {}",
+                            err
+                        );
+                    }
+                }
+            }
+        } else {
+            message.save_changes().await;
+        }
+    }
+
+    pub async fn remove_message(&self, message: &Message) {
+        if let Some(store) = &self.store {
+            match store.delete(message.id).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    tracing::debug!(
+                        context = "queue",
+                        event = "skipped",
+                        id = message.id,
+                        "Queued message is owned by another node, releasing local copy."
+                    );
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "queue",
+                        event = "error",
+                        id = message.id,
+                        "Failed to delete queued message: {}",
+                        err
+                    );
+                }
+            }
+        }
+        self.release_message(message).await;
+    }
+
+    // Drops the local copy of a message, leaving it in the queue store
+    pub async fn release_message(&self, message: &Message) {
+        message.remove().await;
+        METRICS.queue_messages.dec();
+    }
+
+    pub async fn lock_message(&self, message: &Message) -> bool {
+        if let Some(store) = &self.store {
+            match store.renew_lease(message.id).await {
+                Ok(true) => true,
+                Ok(false) => {
+                    tracing::info!(
+                        context = "queue",
+                        event = "skipped",
+                        id = message.id,
+                        "Message is being handled by another node."
+                    );
+                    false
+                }
+                Err(err) => {
+                    // The message is picked up again once the lease expires
+                    tracing::error!(
+                        context = "queue",
+                        event = "error",
+                        id = message.id,
+                        "Failed to renew queue lease: {}",
+                        err
+                    );
+                    false
+                }
+            }
+        } else {
+            true
+        }
+    }
+
+    pub async fn poll_store(&self, queue: &mut Queue) {
+        let store = if let Some(store) = &self.store {
+            store
+        } else {
+            return;
+        };
+
+        let ids = match store.due_messages().await {
+            Ok(ids) => ids,
+            Err(err) => {
+                tracing::error!(
+                    context = "queue",
+                    event = "error",
+                    "Failed to obtain due messages from queue store: {}",
+                    err
+                );
+                queue.schedule_poll(store.poll_interval);
+                return;
+            }
+        };
+
+        for id in ids {
+            if queue.messages.contains_key(&id) {
+                continue;
+            }
+            match store.acquire_lease(id).await {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(err) => {
+                    tracing::error!(
+                        context = "queue",
+                        event = "error",
+                        id = id,
+                        "Failed to acquire queue lease: {}",
+                        err
+                    );
+                    continue;
+                }
+            }
+
+            let (mut message, raw_message) = match store.read(id).await {
+                Ok(Some(result)) => result,
+                Ok(None) => {
+                    // Message was removed by another node, release lease
+                    let _ = store.delete(id).await;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "queue",
+                        event = "error",
+                        id = id,
+                        "Failed to read message from queue store: {}",
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            // Write local copy of the message for delivery
+            message.path = self.build_path(&message).await;
+            if let Err(err) = tokio::fs::write(&message.path, &raw_message).await {
+                tracing::error!(
+                    context = "queue",
+                    event = "error",
+                    "Failed to write file {}: {}",
+                    message.path.display(),
+                    err
+                );
+                continue;
+            }
+
+            // Reserve quota
+            self.has_quota(&mut message).await;
+
+            tracing::debug!(
+                context = "queue",
+                event = "acquired",
+                id = id,
+                "Message acquired from queue store."
+            );
+
+            METRICS.queue_messages.inc();
+            queue.schedule(Schedule {
+                due: message.next_event().unwrap_or_else(std::time::Instant::now),
+                inner: Box::new(message),
+            });
+        }
+
+        queue.schedule_poll(store.poll_interval);
+    }
+}
+
+impl Message {
+    fn due_timestamp(&self) -> u64 {
+        let now = std::time::Instant::now();
+        self.next_event()
+            .map(|due| instant_to_timestamp(now, due))
+            .unwrap_or_else(now)
+    }
+}
+
+impl QueueRecord {
+    fn serialize(&self) -> Vec<u8> {
+        KeySerializer::new(std::mem::size_of::<u64>() * 3 + self.metadata.len())
+            .write(self.document_id)
+            .write(self.size)
+            .write(self.due)
+            .write(self.metadata.as_slice())
+            .finalize()
+    }
+}
+
+impl Deserialize for QueueRecord {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        Ok(QueueRecord {
+            document_id: bytes.deserialize_be_u32(0)?,
+            size: bytes.deserialize_be_u64(std::mem::size_of::<u32>())?,
+            due: bytes
+                .deserialize_be_u64(std::mem::size_of::<u32>() + std::mem::size_of::<u64>())?,
+            metadata: bytes
+                .get(std::mem::size_of::<u32>() + (std::mem::size_of::<u64>() * 2)..)
+                .unwrap_or_default()
+                .to_vec(),
+        })
+    }
+}
+
+impl Deserialize for QueueLease {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        Ok(QueueLease {
+            node_id: bytes.deserialize_be_u64(0)?,
+            expires: bytes.deserialize_be_u64(std::mem::size_of::<u64>())?,
+        })
+    }
+}
+
+fn blob_kind(document_id: u32) -> BlobKind {
+    BlobKind::Linked {
+        account_id: u32::MAX,
+        collection: QUEUE_COLLECTION,
+        document_id,
+    }
+}
+
+fn message_key(id: QueueId) -> Vec<u8> {
+    KeySerializer::new(std::mem::size_of::<u32>() + std::mem::size_of::<u64>() + 1)
+        .write(u32::MAX)
+        .write(QUEUE_MESSAGE)
+        .write(id)
+        .finalize()
+}
+
+fn lease_key(id: QueueId) -> Vec<u8> {
+    KeySerializer::new(std::mem::size_of::<u32>() + std::mem::size_of::<u64>() + 1)
+        .write(u32::MAX)
+        .write(QUEUE_LEASE)
+        .write(id)
+        .finalize()
+}
+
+fn due_key(due: u64, id: QueueId) -> Vec<u8> {
+    KeySerializer::new(std::mem::size_of::<u32>() + (std::mem::size_of::<u64>() * 2) + 1)
+        .write(u32::MAX)
+        .write(QUEUE_DUE)
+        .write(due)
+        .write(id)
+        .finalize()
+}
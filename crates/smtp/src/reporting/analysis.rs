@@ -36,9 +36,13 @@ use mail_auth::{
     zip,
 };
 use mail_parser::{DateTime, HeaderValue, Message, MimeHeaders, PartType};
+use tokio::runtime::Handle;
+use utils::metrics::{METRICS, REPORT_ARF, REPORT_DMARC, REPORT_TLS};
 
 use crate::core::SMTP;
 
+use super::store::IncomingReport;
+
 enum Compression {
     None,
     Gzip,
@@ -64,6 +68,7 @@ pub trait AnalyzeReport {
 impl AnalyzeReport for Arc<SMTP> {
     fn analyze_report(&self, message: Arc<Vec<u8>>) {
         let core = self.clone();
+        let handle = Handle::current();
         self.worker_pool.spawn(move || {
             let message = if let Some(message) = Message::parse(&message) {
                 message
@@ -220,7 +225,12 @@ impl AnalyzeReport for Arc<SMTP> {
                 match report.format {
                     Format::Dmarc => match Report::parse_xml(&data) {
                         Ok(report) => {
+                            METRICS.reports_received[REPORT_DMARC].inc();
                             report.log();
+                            handle.block_on(
+                                core.report
+                                    .save_incoming(IncomingReport::dmarc(from, report)),
+                            );
                         }
                         Err(err) => {
                             tracing::debug!(
@@ -234,7 +244,11 @@ impl AnalyzeReport for Arc<SMTP> {
                     },
                     Format::Tls => match TlsReport::parse_json(&data) {
                         Ok(report) => {
+                            METRICS.reports_received[REPORT_TLS].inc();
                             report.log();
+                            handle.block_on(
+                                core.report.save_incoming(IncomingReport::tls(from, report)),
+                            );
                         }
                         Err(err) => {
                             tracing::debug!(
@@ -248,6 +262,7 @@ impl AnalyzeReport for Arc<SMTP> {
                     },
                     Format::Arf => match Feedback::parse_arf(&data) {
                         Some(report) => {
+                            METRICS.reports_received[REPORT_ARF].inc();
                             report.log();
                         }
                         None => {
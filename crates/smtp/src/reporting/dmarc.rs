@@ -36,6 +36,7 @@ use tokio::{
     io::{AsyncRead, AsyncWrite},
     runtime::Handle,
 };
+use utils::metrics::{METRICS, REPORT_ARF, REPORT_DMARC};
 
 use crate::{
     config::AggregateFrequency,
@@ -222,6 +223,7 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
                 );
 
                 // Send report
+                METRICS.reports_sent[REPORT_ARF].inc();
                 self.core
                     .send_report(
                         from_addr,
@@ -403,6 +405,7 @@ impl GenerateDmarcReport for Arc<SMTP> {
             );
 
             // Send report
+            METRICS.reports_sent[REPORT_DMARC].inc();
             handle.block_on(core.send_report(
                 from_addr,
                 rua.iter(),
@@ -50,6 +50,7 @@ pub mod dkim;
 pub mod dmarc;
 pub mod scheduler;
 pub mod spf;
+pub mod store;
 pub mod tls;
 
 #[derive(Debug)]
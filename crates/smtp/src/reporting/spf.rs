@@ -23,7 +23,10 @@
 
 use mail_auth::{report::AuthFailureType, AuthenticationResults, SpfOutput};
 use tokio::io::{AsyncRead, AsyncWrite};
-use utils::config::Rate;
+use utils::{
+    config::Rate,
+    metrics::{METRICS, REPORT_ARF},
+};
 
 use crate::core::Session;
 
@@ -88,6 +91,7 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         );
 
         // Send report
+        METRICS.reports_sent[REPORT_ARF].inc();
         self.core
             .send_report(
                 from_addr,
@@ -0,0 +1,544 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{net::IpAddr, sync::Arc};
+
+use ahash::{AHashMap, AHashSet};
+use mail_auth::report::{
+    tlsrpt::{ResultType, TlsReport},
+    ActionDisposition, DmarcResult, Report,
+};
+use mail_parser::DateTime;
+use serde::{Deserialize, Serialize};
+use store::{
+    write::{
+        key::{DeserializeBigEndian, KeySerializer},
+        now, BatchBuilder, Operation, ValueClass,
+    },
+    CustomValueKey, Store,
+};
+use utils::config::Config;
+
+use crate::core::{
+    management::{deserialize_datetime, serialize_datetime},
+    ReportCore,
+};
+
+use super::scheduler::ReportType;
+
+pub const REPORT_COLLECTION: u8 = u8::MAX - 3;
+
+const REPORT_RECORD: u8 = 8;
+const REPORT_DATE: u8 = 9;
+const REPORT_DOMAIN: u8 = 10;
+const REPORT_IP: u8 = 11;
+
+const FORMAT_DMARC: u8 = 0;
+const FORMAT_TLS: u8 = 1;
+
+pub struct ReportStore {
+    pub store: Arc<Store>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IncomingReport {
+    pub from: String,
+    pub organization: String,
+    pub domains: Vec<String>,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub received: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub range_from: DateTime,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(serialize_with = "serialize_datetime")]
+    pub range_to: DateTime,
+    #[serde(flatten)]
+    pub report: IncomingReportData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "report")]
+pub enum IncomingReportData {
+    #[serde(rename = "dmarc")]
+    Dmarc(Report),
+    #[serde(rename = "tls")]
+    Tls(TlsReport),
+}
+
+#[derive(Debug, Default)]
+pub struct IncomingReportQuery {
+    pub type_: Option<ReportType<(), ()>>,
+    pub domain: Option<String>,
+    pub source_ip: Option<IpAddr>,
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReportAggregate {
+    pub reports: usize,
+    pub dmarc: DmarcAggregate,
+    pub tls: TlsAggregate,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DmarcAggregate {
+    pub messages: u64,
+    pub dmarc_pass: u64,
+    pub dmarc_quarantine: u64,
+    pub dmarc_reject: u64,
+    pub dmarc_none: u64,
+    pub dkim_pass: u64,
+    pub dkim_fail: u64,
+    pub spf_pass: u64,
+    pub spf_fail: u64,
+    pub sources: Vec<DmarcSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DmarcSource {
+    pub ip: IpAddr,
+    pub messages: u64,
+    pub dkim_fail: u64,
+    pub spf_fail: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TlsAggregate {
+    pub sessions_success: u64,
+    pub sessions_failure: u64,
+    pub reporters: Vec<TlsReporter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TlsReporter {
+    pub organization: String,
+    pub sessions_success: u64,
+    pub sessions_failure: u64,
+    pub failures: Vec<TlsFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TlsFailure {
+    pub result_type: ResultType,
+    pub sessions: u64,
+}
+
+impl ReportStore {
+    pub fn init(config: &Config, store: Arc<Store>) -> Result<Option<Self>, String> {
+        Ok(
+            if config
+                .property::<bool>("report.analysis.persist")?
+                .unwrap_or(false)
+            {
+                Some(ReportStore { store })
+            } else {
+                None
+            },
+        )
+    }
+
+    pub async fn write(&self, report: &IncomingReport) -> store::Result<u32> {
+        let document_id = self
+            .store
+            .assign_document_id(u32::MAX, REPORT_COLLECTION)
+            .await?;
+        let format = report.format();
+        let range_from = report.range_from.to_timestamp() as u64;
+        let value = serde_json::to_vec(report).map_err(|err| {
+            store::Error::InternalError(format!("Failed to serialize incoming report: {err}"))
+        })?;
+
+        // Store the report along with its date, domain and source IP indexes
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(u32::MAX)
+            .with_collection(REPORT_COLLECTION)
+            .create_document(document_id)
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: record_key(document_id),
+                },
+                set: Some(value),
+            })
+            .op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: index_key(REPORT_DATE, &[], range_from, document_id, format),
+                },
+                set: Some(vec![]),
+            });
+        for domain in &report.domains {
+            batch.op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: index_key(
+                        REPORT_DOMAIN,
+                        &domain_value(domain),
+                        range_from,
+                        document_id,
+                        format,
+                    ),
+                },
+                set: Some(vec![]),
+            });
+        }
+        for ip in report.source_ips() {
+            batch.op(Operation::Value {
+                class: ValueClass::Custom {
+                    bytes: index_key(REPORT_IP, &ip_value(&ip), range_from, document_id, format),
+                },
+                set: Some(vec![]),
+            });
+        }
+        self.store.write(batch.build()).await?;
+
+        Ok(document_id)
+    }
+
+    pub async fn read(&self, id: u32) -> store::Result<Option<IncomingReport>> {
+        self.store
+            .get_value::<IncomingReport>(CustomValueKey {
+                value: record_key(id),
+            })
+            .await
+    }
+
+    // Returns the ids of the reports matching a query, sorted by date
+    pub async fn query(&self, query: &IncomingReportQuery) -> store::Result<Vec<u32>> {
+        let mut ids = if let Some(domain) = &query.domain {
+            self.query_index(REPORT_DOMAIN, domain_value(domain), query)
+                .await?
+        } else if let Some(ip) = &query.source_ip {
+            self.query_index(REPORT_IP, ip_value(ip), query).await?
+        } else {
+            self.query_index(REPORT_DATE, vec![], query).await?
+        };
+
+        if let (Some(_), Some(ip)) = (&query.domain, &query.source_ip) {
+            let ip_ids = self
+                .query_index(REPORT_IP, ip_value(ip), query)
+                .await?
+                .into_iter()
+                .collect::<AHashSet<_>>();
+            ids.retain(|id| ip_ids.contains(id));
+        }
+
+        Ok(ids)
+    }
+
+    pub async fn aggregate(&self, query: &IncomingReportQuery) -> store::Result<ReportAggregate> {
+        let mut aggregate = ReportAggregate::default();
+        let mut sources = AHashMap::new();
+        let mut reporters = AHashMap::new();
+
+        for id in self.query(query).await? {
+            if let Some(report) = self.read(id).await? {
+                aggregate.reports += 1;
+                match &report.report {
+                    IncomingReportData::Dmarc(dmarc) => {
+                        aggregate.dmarc.add(dmarc, &mut sources);
+                    }
+                    IncomingReportData::Tls(tls) => {
+                        aggregate.tls.add(&report.organization, tls, &mut reporters);
+                    }
+                }
+            }
+        }
+
+        // List the sources and receivers with the most failures first
+        aggregate.dmarc.sources = sources.into_values().collect();
+        aggregate.dmarc.sources.sort_unstable_by(|a, b| {
+            (b.dkim_fail + b.spf_fail, b.messages).cmp(&(a.dkim_fail + a.spf_fail, a.messages))
+        });
+        aggregate.tls.reporters = reporters
+            .into_iter()
+            .map(
+                |(organization, (sessions_success, sessions_failure, failures))| {
+                    let mut failures = failures
+                        .into_iter()
+                        .map(|(result_type, sessions)| TlsFailure {
+                            result_type,
+                            sessions,
+                        })
+                        .collect::<Vec<_>>();
+                    failures.sort_unstable_by(|a, b| b.sessions.cmp(&a.sessions));
+                    TlsReporter {
+                        organization,
+                        sessions_success,
+                        sessions_failure,
+                        failures,
+                    }
+                },
+            )
+            .collect();
+        aggregate
+            .tls
+            .reporters
+            .sort_unstable_by(|a, b| b.sessions_failure.cmp(&a.sessions_failure));
+
+        Ok(aggregate)
+    }
+
+    async fn query_index(
+        &self,
+        typ: u8,
+        value: Vec<u8>,
+        query: &IncomingReportQuery,
+    ) -> store::Result<Vec<u32>> {
+        let from_key = index_key(typ, &value, query.after.unwrap_or(0), 0, 0);
+        let to_key = index_key(
+            typ,
+            &value,
+            query
+                .before
+                .map_or(u64::MAX, |before| before.saturating_sub(1)),
+            u32::MAX,
+            u8::MAX,
+        );
+        let format = query.type_.as_ref().map(|type_| match type_ {
+            ReportType::Dmarc(_) => FORMAT_DMARC,
+            ReportType::Tls(_) => FORMAT_TLS,
+        });
+
+        self.store
+            .iterate(
+                Vec::new(),
+                CustomValueKey { value: from_key },
+                CustomValueKey { value: to_key },
+                false,
+                true,
+                move |ids, key, _| {
+                    if format.map_or(true, |format| key.last() == Some(&format)) {
+                        ids.push(
+                            key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>() - 1)?,
+                        );
+                    }
+                    Ok(true)
+                },
+            )
+            .await
+    }
+}
+
+impl ReportCore {
+    pub async fn save_incoming(&self, report: IncomingReport) {
+        if let Some(store) = &self.store {
+            if let Err(err) = store.write(&report).await {
+                tracing::error!(
+                    context = "report",
+                    event = "error",
+                    from = report.from,
+                    "Failed to store incoming report: {}",
+                    err
+                );
+            }
+        }
+    }
+}
+
+impl IncomingReport {
+    pub fn dmarc(from: impl Into<String>, report: Report) -> Self {
+        IncomingReport {
+            from: from.into(),
+            organization: report.org_name().to_string(),
+            domains: vec![report.domain().to_lowercase()],
+            received: DateTime::from_timestamp(now() as i64),
+            range_from: DateTime::from_timestamp(report.date_range_begin() as i64),
+            range_to: DateTime::from_timestamp(report.date_range_end() as i64),
+            report: IncomingReportData::Dmarc(report),
+        }
+    }
+
+    pub fn tls(from: impl Into<String>, report: TlsReport) -> Self {
+        let mut domains = report
+            .policies
+            .iter()
+            .map(|policy| policy.policy.policy_domain.to_lowercase())
+            .collect::<Vec<_>>();
+        domains.sort_unstable();
+        domains.dedup();
+
+        IncomingReport {
+            from: from.into(),
+            organization: report
+                .organization_name
+                .as_ref()
+                .or(report.contact_info.as_ref())
+                .cloned()
+                .unwrap_or_default(),
+            domains,
+            received: DateTime::from_timestamp(now() as i64),
+            range_from: report.date_range.start_datetime.clone(),
+            range_to: report.date_range.end_datetime.clone(),
+            report: IncomingReportData::Tls(report),
+        }
+    }
+
+    fn format(&self) -> u8 {
+        match &self.report {
+            IncomingReportData::Dmarc(_) => FORMAT_DMARC,
+            IncomingReportData::Tls(_) => FORMAT_TLS,
+        }
+    }
+
+    fn source_ips(&self) -> Vec<IpAddr> {
+        let mut ips = match &self.report {
+            IncomingReportData::Dmarc(report) => report
+                .records()
+                .iter()
+                .filter_map(|record| record.source_ip())
+                .collect::<Vec<_>>(),
+            IncomingReportData::Tls(report) => report
+                .policies
+                .iter()
+                .flat_map(|policy| policy.failure_details.iter())
+                .filter_map(|failure| failure.sending_mta_ip)
+                .collect::<Vec<_>>(),
+        };
+        ips.sort_unstable();
+        ips.dedup();
+        ips
+    }
+}
+
+impl DmarcAggregate {
+    fn add(&mut self, report: &Report, sources: &mut AHashMap<IpAddr, DmarcSource>) {
+        for record in report.records() {
+            let count = std::cmp::max(record.count(), 1) as u64;
+            let dkim_fail = matches!(record.dmarc_dkim_result(), DmarcResult::Fail);
+            let spf_fail = matches!(record.dmarc_spf_result(), DmarcResult::Fail);
+
+            self.messages += count;
+            match record.action_disposition() {
+                ActionDisposition::Pass => self.dmarc_pass += count,
+                ActionDisposition::Quarantine => self.dmarc_quarantine += count,
+                ActionDisposition::Reject => self.dmarc_reject += count,
+                ActionDisposition::None | ActionDisposition::Unspecified => {
+                    self.dmarc_none += count
+                }
+            }
+            if dkim_fail {
+                self.dkim_fail += count;
+            } else if matches!(record.dmarc_dkim_result(), DmarcResult::Pass) {
+                self.dkim_pass += count;
+            }
+            if spf_fail {
+                self.spf_fail += count;
+            } else if matches!(record.dmarc_spf_result(), DmarcResult::Pass) {
+                self.spf_pass += count;
+            }
+
+            if let Some(ip) = record.source_ip() {
+                let source = sources.entry(ip).or_insert_with(|| DmarcSource {
+                    ip,
+                    messages: 0,
+                    dkim_fail: 0,
+                    spf_fail: 0,
+                });
+                source.messages += count;
+                if dkim_fail {
+                    source.dkim_fail += count;
+                }
+                if spf_fail {
+                    source.spf_fail += count;
+                }
+            }
+        }
+    }
+}
+
+type TlsReporterTotals = (u64, u64, AHashMap<ResultType, u64>);
+
+impl TlsAggregate {
+    fn add(
+        &mut self,
+        organization: &str,
+        report: &TlsReport,
+        reporters: &mut AHashMap<String, TlsReporterTotals>,
+    ) {
+        let reporter = reporters
+            .entry(organization.to_string())
+            .or_insert_with(|| (0, 0, AHashMap::new()));
+
+        for policy in &report.policies {
+            let success = policy.summary.total_success as u64;
+            let failure = policy.summary.total_failure as u64;
+            self.sessions_success += success;
+            self.sessions_failure += failure;
+            reporter.0 += success;
+            reporter.1 += failure;
+
+            for failure in &policy.failure_details {
+                *reporter.2.entry(failure.result_type).or_insert(0) +=
+                    failure.failed_session_count as u64;
+            }
+        }
+    }
+}
+
+impl store::Deserialize for IncomingReport {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        serde_json::from_slice(bytes).map_err(|err| {
+            store::Error::InternalError(format!("Failed to deserialize incoming report: {err}"))
+        })
+    }
+}
+
+fn record_key(id: u32) -> Vec<u8> {
+    KeySerializer::new((std::mem::size_of::<u32>() * 2) + 1)
+        .write(u32::MAX)
+        .write(REPORT_RECORD)
+        .write(id)
+        .finalize()
+}
+
+// Index keys end with the report start date, id and format so that each
+// index can be scanned by date range and filtered by type without reading
+// the report itself.
+fn index_key(typ: u8, value: &[u8], range_from: u64, id: u32, format: u8) -> Vec<u8> {
+    KeySerializer::new(
+        (std::mem::size_of::<u32>() * 2) + std::mem::size_of::<u64>() + value.len() + 2,
+    )
+    .write(u32::MAX)
+    .write(typ)
+    .write(value)
+    .write(range_from)
+    .write(id)
+    .write(format)
+    .finalize()
+}
+
+// Domains are zero terminated to avoid matching on other domains sharing the same prefix
+fn domain_value(domain: &str) -> Vec<u8> {
+    let mut value = domain.to_lowercase().into_bytes();
+    value.push(0);
+    value
+}
+
+fn ip_value(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
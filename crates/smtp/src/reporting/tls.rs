@@ -37,6 +37,7 @@ use reqwest::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 use tokio::runtime::Handle;
+use utils::metrics::{METRICS, REPORT_TLS};
 
 use crate::{
     config::AggregateFrequency,
@@ -256,6 +257,7 @@ impl GenerateTlsReport for Arc<SMTP> {
                 );
 
                 // Send report
+                METRICS.reports_sent[REPORT_TLS].inc();
                 handle.block_on(core.send_report(
                     from_addr,
                     rcpts.iter(),
@@ -376,6 +376,16 @@ impl ReadTransaction<'_> {
         }
     }
 
+    pub async fn get_counter(&self, key: impl Key) -> crate::Result<i64> {
+        if let Some(bytes) = self.trx.get(&key.serialize(), true).await? {
+            Ok(i64::from_le_bytes(bytes[..].try_into().map_err(|_| {
+                crate::Error::InternalError("Invalid counter value".to_string())
+            })?))
+        } else {
+            Ok(0)
+        }
+    }
+
     pub async fn refresh_if_old(&mut self) -> crate::Result<()> {
         if self.trx_age.elapsed() > Duration::from_millis(2000) {
             self.trx = self.db.create_trx()?;
@@ -396,7 +406,7 @@ impl Store {
 
     #[cfg(feature = "test_mode")]
     pub async fn assert_is_empty(&self) {
-        use crate::{SUBSPACE_BITMAPS, SUBSPACE_LOGS, SUBSPACE_VALUES};
+        use crate::{SUBSPACE_BITMAPS, SUBSPACE_COUNTERS, SUBSPACE_LOGS, SUBSPACE_VALUES};
 
         // Purge bitmaps
         self.purge_bitmaps().await.unwrap();
@@ -474,6 +484,9 @@ impl Store {
                     SUBSPACE_LOGS => {
                         delete_keys.push(key.to_vec());
                     }
+                    SUBSPACE_COUNTERS => {
+                        delete_keys.push(key_.to_vec());
+                    }
 
                     _ => panic!("Invalid key found in database: {key:?} for subspace {subspace}"),
                 }
@@ -30,14 +30,15 @@ use foundationdb::{
 };
 use futures::StreamExt;
 use rand::Rng;
+use utils::metrics::METRICS;
 
 use crate::{
     write::{
         key::{DeserializeBigEndian, KeySerializer},
         now, Batch, Operation, ValueClass,
     },
-    AclKey, BitmapKey, Deserialize, IndexKey, LogKey, Serialize, Store, ValueKey, SUBSPACE_QUOTAS,
-    SUBSPACE_VALUES,
+    AclKey, BitmapKey, CounterKey, Deserialize, IndexKey, LogKey, Serialize, Store, ValueKey,
+    SUBSPACE_QUOTAS, SUBSPACE_VALUES,
 };
 
 use super::bitmap::{next_available_index, DenseBitmap, BITS_PER_BLOCK};
@@ -232,6 +233,18 @@ impl Store {
                             MutationType::Add,
                         );
                     }
+                    Operation::UpdateCounter { key, by } => {
+                        trx.atomic_op(
+                            &CounterKey {
+                                account_id,
+                                collection,
+                                key,
+                            }
+                            .serialize(),
+                            &by.to_le_bytes()[..],
+                            MutationType::Add,
+                        );
+                    }
                 }
             }
 
@@ -245,6 +258,8 @@ impl Store {
 
             match trx.commit().await {
                 Ok(_) => {
+                    METRICS.store_write.observe(start.elapsed());
+
                     #[cfg(feature = "test_mode")]
                     {
                         for op in &batch.ops {
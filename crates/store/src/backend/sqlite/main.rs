@@ -30,7 +30,8 @@ use tokio::sync::oneshot;
 use utils::{config::Config, UnwrapFailure};
 
 use crate::{
-    blob::BlobStore, Store, SUBSPACE_BITMAPS, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_VALUES,
+    blob::BlobStore, Store, SUBSPACE_BITMAPS, SUBSPACE_COUNTERS, SUBSPACE_INDEXES, SUBSPACE_LOGS,
+    SUBSPACE_VALUES,
 };
 
 use super::pool::SqliteConnectionManager;
@@ -109,6 +110,17 @@ impl Store {
             [],
         )?;
 
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    k BLOB PRIMARY KEY,
+                    v INTEGER NOT NULL DEFAULT 0
+                )",
+                char::from(SUBSPACE_COUNTERS)
+            ),
+            [],
+        )?;
+
         conn.execute(
             &format!(
                 "CREATE TABLE IF NOT EXISTS {} (
@@ -347,6 +347,19 @@ impl ReadTransaction<'_> {
         }
     }
 
+    #[maybe_async::maybe_async]
+    pub(crate) async fn get_counter(&self, key: impl Key) -> crate::Result<i64> {
+        match self
+            .conn
+            .prepare_cached("SELECT v FROM c WHERE k = ?")?
+            .query_row([&key.serialize()], |row| row.get::<_, i64>(0))
+        {
+            Ok(value) => Ok(value),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     #[maybe_async::maybe_async]
     pub async fn refresh_if_old(&mut self) -> crate::Result<()> {
         Ok(())
@@ -440,8 +453,9 @@ impl Store {
             }
         }
 
-        // Delete logs
+        // Delete logs and counters
         conn.conn.execute("DELETE FROM l", []).unwrap();
+        conn.conn.execute("DELETE FROM c", []).unwrap();
 
         if has_errors {
             panic!("Database is not empty");
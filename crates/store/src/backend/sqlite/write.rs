@@ -21,11 +21,14 @@
  * for more details.
 */
 
+use std::time::Instant;
+
 use rusqlite::{params, OptionalExtension, TransactionBehavior};
+use utils::metrics::METRICS;
 
 use crate::{
     write::{Batch, Operation, ValueClass},
-    AclKey, BitmapKey, IndexKey, Key, LogKey, Serialize, Store, ValueKey,
+    AclKey, BitmapKey, CounterKey, IndexKey, Key, LogKey, Serialize, Store, ValueKey,
 };
 
 use super::{BITS_MASK, BITS_PER_BLOCK};
@@ -87,6 +90,13 @@ const CLEAR_QUERIES: &[&str] = &[
 
 impl Store {
     pub async fn write(&self, batch: Batch) -> crate::Result<()> {
+        let start = Instant::now();
+        let result = self.write_batch(batch).await;
+        METRICS.store_write.observe(start.elapsed());
+        result
+    }
+
+    async fn write_batch(&self, batch: Batch) -> crate::Result<()> {
         let mut conn = self.conn_pool.get()?;
         self.spawn_worker(move || {
             let mut account_id = u32::MAX;
@@ -257,6 +267,21 @@ impl Store {
                                 .execute(params![*bytes, account_id])?;
                         }
                     }
+                    Operation::UpdateCounter { key, by } => {
+                        trx.prepare_cached(concat!(
+                            "INSERT INTO c (k, v) VALUES (?, ?) ",
+                            "ON CONFLICT(k) DO UPDATE SET v = v + excluded.v"
+                        ))?
+                        .execute(params![
+                            CounterKey {
+                                account_id,
+                                collection,
+                                key,
+                            }
+                            .serialize(),
+                            *by
+                        ])?;
+                    }
                 }
             }
 
@@ -0,0 +1,115 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::io::{Cursor, Read};
+
+use zip::ZipArchive;
+
+use super::extract::{extract_text_into, DocumentType};
+
+pub(crate) fn extract_zip(bytes: &[u8], budget: &mut usize, depth: usize, text: &mut String) {
+    let mut archive = if let Ok(archive) = ZipArchive::new(Cursor::new(bytes)) {
+        archive
+    } else {
+        return;
+    };
+
+    for index in 0..archive.len() {
+        if *budget == 0 {
+            break;
+        }
+
+        // Encrypted or unsupported entries are skipped
+        let entry = if let Ok(entry) = archive.by_index(index) {
+            entry
+        } else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let size = entry.size();
+        extract_entry(&name, entry, size, budget, depth, text);
+    }
+}
+
+pub(crate) fn extract_tar(reader: impl Read, budget: &mut usize, depth: usize, text: &mut String) {
+    let mut archive = tar::Archive::new(reader);
+    let entries = if let Ok(entries) = archive.entries() {
+        entries
+    } else {
+        return;
+    };
+
+    for entry in entries {
+        if *budget == 0 {
+            break;
+        }
+
+        let entry = if let Ok(entry) = entry {
+            entry
+        } else {
+            break;
+        };
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = if let Ok(path) = entry.path() {
+            path.to_string_lossy().into_owned()
+        } else {
+            continue;
+        };
+        let size = entry.size();
+        extract_entry(&name, entry, size, budget, depth, text);
+    }
+}
+
+fn extract_entry(
+    name: &str,
+    reader: impl Read,
+    size: u64,
+    budget: &mut usize,
+    depth: usize,
+    text: &mut String,
+) {
+    // Index file names so archives can be searched by their contents' names
+    let file_name = name.rsplit_once('/').map_or(name, |(_, name)| name);
+    if !file_name.is_empty() {
+        text.push_str(file_name);
+        text.push('\n');
+    }
+
+    if let Some(typ) = DocumentType::from_file_name(file_name) {
+        if let Some(bytes) = read_entry(reader, size, budget) {
+            extract_text_into(&bytes, typ, budget, depth + 1, text);
+        }
+    }
+}
+
+pub(crate) fn read_entry(reader: impl Read, size: u64, budget: &mut usize) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(std::cmp::min(size, *budget as u64) as usize);
+    reader.take(*budget as u64).read_to_end(&mut bytes).ok()?;
+    *budget -= bytes.len();
+    Some(bytes)
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use super::{archive, office};
+
+// Maximum nesting level of archives and documents within archives
+const MAX_DEPTH: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    Text,
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl DocumentType {
+    // Detects the document type from the attachment's content type or, when it is too
+    // generic, from its file name
+    pub fn detect(content_type: Option<&str>, file_name: Option<&str>) -> Option<Self> {
+        match content_type.map(|ct| ct.to_ascii_lowercase()).as_deref() {
+            Some(
+                "application/zip"
+                | "application/x-zip"
+                | "application/x-zip-compressed"
+                | "application/vnd.ms-word.document.macroenabled.12"
+                | "application/vnd.ms-excel.sheet.macroenabled.12"
+                | "application/vnd.ms-powerpoint.presentation.macroenabled.12",
+            ) => Some(DocumentType::Zip),
+            Some(ct)
+                if ct.starts_with("application/vnd.openxmlformats-officedocument.")
+                    || ct.starts_with("application/vnd.oasis.opendocument.") =>
+            {
+                Some(DocumentType::Zip)
+            }
+            Some("application/x-tar" | "application/tar") => Some(DocumentType::Tar),
+            Some("application/x-gtar" | "application/x-compressed-tar") => {
+                Some(DocumentType::TarGz)
+            }
+            _ => file_name.and_then(Self::from_file_name),
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let file_name = file_name.to_ascii_lowercase();
+        if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            return Some(DocumentType::TarGz);
+        }
+
+        match file_name.rsplit_once('.')?.1 {
+            "zip" | "docx" | "docm" | "dotx" | "xlsx" | "xlsm" | "xltx" | "pptx" | "pptm"
+            | "potx" | "odt" | "ott" | "ods" | "ots" | "odp" | "otp" => Some(DocumentType::Zip),
+            "tar" => Some(DocumentType::Tar),
+            "txt" | "text" | "csv" | "tsv" | "md" | "log" | "json" => Some(DocumentType::Text),
+            _ => None,
+        }
+    }
+
+    fn is_container(&self) -> bool {
+        !matches!(self, DocumentType::Text)
+    }
+}
+
+// Extracts the text contained in a document, decompressing at most `max_size` bytes
+pub fn extract_text(bytes: &[u8], typ: DocumentType, max_size: usize) -> Option<String> {
+    let mut text = String::new();
+    let mut budget = max_size;
+    extract_text_into(bytes, typ, &mut budget, 0, &mut text);
+    if !text.trim().is_empty() {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn extract_text_into(
+    bytes: &[u8],
+    typ: DocumentType,
+    budget: &mut usize,
+    depth: usize,
+    text: &mut String,
+) {
+    if typ.is_container() && depth > MAX_DEPTH {
+        return;
+    }
+
+    match typ {
+        DocumentType::Text => {
+            text.push_str(&String::from_utf8_lossy(bytes));
+            text.push('\n');
+        }
+        DocumentType::Zip => {
+            if !office::extract_office(bytes, budget, text) {
+                archive::extract_zip(bytes, budget, depth, text);
+            }
+        }
+        DocumentType::Tar => {
+            archive::extract_tar(bytes, budget, depth, text);
+        }
+        DocumentType::TarGz => {
+            archive::extract_tar(flate2::read::GzDecoder::new(bytes), budget, depth, text);
+        }
+    }
+}
@@ -30,9 +30,12 @@ use self::{bloom::hash_token, builder::MAX_TOKEN_MASK, lang::LanguageDetector};
 
 pub mod lang;
 //pub mod pdf;
+pub mod archive;
 pub mod bloom;
 pub mod builder;
+pub mod extract;
 pub mod ngram;
+pub mod office;
 pub mod query;
 pub mod search_snippet;
 pub mod stemmer;
@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::io::Cursor;
+
+use quick_xml::{events::Event, Reader};
+use zip::ZipArchive;
+
+use super::archive::read_entry;
+
+// Extracts the text of OOXML (docx, xlsx, pptx) and ODF (odt, ods, odp) documents,
+// returns false if the archive is not an office document.
+pub(crate) fn extract_office(bytes: &[u8], budget: &mut usize, text: &mut String) -> bool {
+    let mut archive = if let Ok(archive) = ZipArchive::new(Cursor::new(bytes)) {
+        archive
+    } else {
+        return false;
+    };
+
+    // ODF documents start with an uncompressed "mimetype" entry
+    if archive.by_name("mimetype").ok().map_or(false, |entry| {
+        read_entry(entry, 64, &mut 64).map_or(false, |mime| {
+            mime.starts_with(b"application/vnd.oasis.opendocument")
+        })
+    }) {
+        if let Some(xml) = archive.by_name("content.xml").ok().and_then(|entry| {
+            let size = entry.size();
+            read_entry(entry, size, budget)
+        }) {
+            xml_to_text(&xml, None, text);
+        }
+        return true;
+    } else if archive.by_name("[Content_Types].xml").is_err() {
+        return false;
+    }
+
+    // Sort parts so that, for example, slide2.xml comes before slide10.xml
+    let mut parts = archive
+        .file_names()
+        .filter(|name| is_ooxml_text_part(name))
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    parts.sort_unstable_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    for part in parts {
+        if *budget == 0 {
+            break;
+        }
+        if let Some(xml) = archive.by_name(&part).ok().and_then(|entry| {
+            let size = entry.size();
+            read_entry(entry, size, budget)
+        }) {
+            // Text runs are contained in <w:t>, <a:t> and <t> elements
+            xml_to_text(&xml, Some(b"t".as_slice()), text);
+        }
+    }
+
+    true
+}
+
+fn is_ooxml_text_part(name: &str) -> bool {
+    name.ends_with(".xml")
+        && (name == "word/document.xml"
+            || name == "word/footnotes.xml"
+            || name == "word/endnotes.xml"
+            || name.starts_with("word/header")
+            || name.starts_with("word/footer")
+            || name == "xl/sharedStrings.xml"
+            || name.starts_with("xl/worksheets/sheet")
+            || name.starts_with("ppt/slides/slide")
+            || name.starts_with("ppt/notesSlides/notesSlide"))
+}
+
+fn xml_to_text(xml: &[u8], text_element: Option<&[u8]>, text: &mut String) {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut in_text = 0;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element)) => {
+                if text_element == Some(element.local_name().as_ref()) {
+                    in_text += 1;
+                }
+            }
+            Ok(Event::End(element)) => match element.local_name().as_ref() {
+                name if text_element == Some(name) => {
+                    in_text -= 1;
+                }
+                b"p" | b"h" | b"si" | b"row" | b"table-row" => {
+                    push_separator(text, '\n');
+                }
+                b"c" | b"table-cell" => {
+                    push_separator(text, ' ');
+                }
+                _ => (),
+            },
+            Ok(Event::Empty(element)) => match element.local_name().as_ref() {
+                b"tab" | b"s" => {
+                    push_separator(text, ' ');
+                }
+                b"br" | b"line-break" => {
+                    push_separator(text, '\n');
+                }
+                _ => (),
+            },
+            Ok(Event::Text(value)) if text_element.is_none() || in_text > 0 => {
+                if let Ok(value) = value.unescape() {
+                    text.push_str(&value);
+                }
+            }
+            Ok(Event::CData(value)) if text_element.is_none() || in_text > 0 => {
+                text.push_str(&String::from_utf8_lossy(&value));
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    push_separator(text, '\n');
+}
+
+fn push_separator(text: &mut String, ch: char) {
+    match text.chars().last() {
+        Some(' ') if ch == '\n' => {
+            text.pop();
+            text.push(ch);
+        }
+        Some(last) if !last.is_whitespace() => {
+            text.push(ch);
+        }
+        _ => (),
+    }
+}
@@ -149,6 +149,13 @@ pub struct AclKey {
     pub to_document_id: u32,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CounterKey<T: AsRef<[u8]>> {
+    pub account_id: u32,
+    pub collection: u8,
+    pub key: T,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LogKey {
     pub account_id: u32,
@@ -233,6 +240,7 @@ pub const SUBSPACE_VALUES: u8 = b'v';
 pub const SUBSPACE_LOGS: u8 = b'l';
 pub const SUBSPACE_INDEXES: u8 = b'i';
 pub const SUBSPACE_QUOTAS: u8 = b'q';
+pub const SUBSPACE_COUNTERS: u8 = b'c';
 
 #[cfg(not(feature = "backend"))]
 impl Store {
@@ -355,6 +363,10 @@ impl ReadTransaction<'_> {
         unimplemented!("No backend selected")
     }
 
+    pub(crate) async fn get_counter(&self, _key: impl Key) -> crate::Result<i64> {
+        unimplemented!("No backend selected")
+    }
+
     pub async fn refresh_if_old(&mut self) -> crate::Result<()> {
         unimplemented!("No backend selected")
     }
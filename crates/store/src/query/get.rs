@@ -21,7 +21,10 @@
  * for more details.
 */
 
+use std::time::Instant;
+
 use roaring::RoaringBitmap;
+use utils::metrics::METRICS;
 
 use crate::{BitmapKey, Deserialize, Key, Store};
 
@@ -30,16 +33,22 @@ impl Store {
     where
         U: Deserialize + 'static,
     {
-        #[cfg(not(feature = "is_sync"))]
-        {
-            self.read_transaction().await?.get_value(key).await
-        }
+        let start = Instant::now();
+        let result = {
+            #[cfg(not(feature = "is_sync"))]
+            {
+                self.read_transaction().await?.get_value(key).await
+            }
 
-        #[cfg(feature = "is_sync")]
-        {
-            let trx = self.read_transaction()?;
-            self.spawn_worker(move || trx.get_value(key)).await
-        }
+            #[cfg(feature = "is_sync")]
+            {
+                let trx = self.read_transaction()?;
+                self.spawn_worker(move || trx.get_value(key)).await
+            }
+        };
+
+        METRICS.store_read.observe(start.elapsed());
+        result
     }
 
     pub async fn get_values<U>(&self, key: Vec<impl Key>) -> crate::Result<Vec<Option<U>>>
@@ -110,6 +119,44 @@ impl Store {
         }
     }
 
+    pub async fn get_counter(&self, key: impl Key) -> crate::Result<i64> {
+        #[cfg(not(feature = "is_sync"))]
+        {
+            self.read_transaction().await?.get_counter(key).await
+        }
+
+        #[cfg(feature = "is_sync")]
+        {
+            let trx = self.read_transaction()?;
+            self.spawn_worker(move || trx.get_counter(key)).await
+        }
+    }
+
+    pub async fn get_counters(&self, keys: Vec<impl Key>) -> crate::Result<Vec<i64>> {
+        #[cfg(not(feature = "is_sync"))]
+        {
+            let trx = self.read_transaction().await?;
+            let mut results = Vec::with_capacity(keys.len());
+            for key in keys {
+                results.push(trx.get_counter(key).await?);
+            }
+            Ok(results)
+        }
+
+        #[cfg(feature = "is_sync")]
+        {
+            let trx = self.read_transaction()?;
+            self.spawn_worker(move || {
+                let mut results = Vec::with_capacity(keys.len());
+                for key in keys {
+                    results.push(trx.get_counter(key)?);
+                }
+                Ok(results)
+            })
+            .await
+        }
+    }
+
     pub async fn get_bitmap<T: AsRef<[u8]> + Send + Sync + 'static>(
         &self,
         key: BitmapKey<T>,
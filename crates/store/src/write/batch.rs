@@ -146,6 +146,14 @@ impl BatchBuilder {
         self
     }
 
+    pub fn counter(&mut self, key: impl Into<Vec<u8>>, by: i64) -> &mut Self {
+        self.ops.push(Operation::UpdateCounter {
+            key: key.into(),
+            by,
+        });
+        self
+    }
+
     pub fn op(&mut self, op: Operation) -> &mut Self {
         self.ops.push(op);
         self
@@ -25,8 +25,9 @@ use std::convert::TryInto;
 use utils::codec::leb128::Leb128_;
 
 use crate::{
-    AclKey, BitmapKey, CustomValueKey, Deserialize, Error, IndexKey, IndexKeyPrefix, Key, LogKey,
-    Serialize, ValueKey, SUBSPACE_BITMAPS, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_VALUES,
+    AclKey, BitmapKey, CounterKey, CustomValueKey, Deserialize, Error, IndexKey, IndexKeyPrefix,
+    Key, LogKey, Serialize, ValueKey, SUBSPACE_BITMAPS, SUBSPACE_COUNTERS, SUBSPACE_INDEXES,
+    SUBSPACE_LOGS, SUBSPACE_VALUES,
 };
 
 pub struct KeySerializer {
@@ -342,6 +343,39 @@ impl Serialize for &LogKey {
     }
 }
 
+impl<T: AsRef<[u8]>> Serialize for &CounterKey<T> {
+    fn serialize(self) -> Vec<u8> {
+        let key = self.key.as_ref();
+        {
+            #[cfg(feature = "key_subspace")]
+            {
+                KeySerializer::new(std::mem::size_of::<u32>() + key.len() + 2)
+                    .write(crate::SUBSPACE_COUNTERS)
+            }
+            #[cfg(not(feature = "key_subspace"))]
+            {
+                KeySerializer::new(std::mem::size_of::<u32>() + key.len() + 1)
+            }
+        }
+        .write(self.account_id)
+        .write(self.collection)
+        .write(key)
+        .finalize()
+    }
+}
+
+impl<T: AsRef<[u8]>> Serialize for CounterKey<T> {
+    fn serialize(self) -> Vec<u8> {
+        (&self).serialize()
+    }
+}
+
+impl<T: AsRef<[u8]> + Sync + Send + 'static> Key for CounterKey<T> {
+    fn subspace(&self) -> u8 {
+        SUBSPACE_COUNTERS
+    }
+}
+
 impl Serialize for LogKey {
     fn serialize(self) -> Vec<u8> {
         (&self).serialize()
@@ -84,6 +84,10 @@ pub enum Operation {
     UpdateQuota {
         bytes: i64,
     },
+    UpdateCounter {
+        key: Vec<u8>,
+        by: i64,
+    },
     Log {
         change_id: u64,
         collection: u8,
@@ -23,6 +23,7 @@
 
 use std::{io::Cursor, sync::Arc};
 
+use ahash::AHashMap;
 use rustls::{
     server::{ClientHello, ResolvesServerCert, ResolvesServerCertUsingSni},
     sign::CertifiedKey,
@@ -30,6 +31,7 @@ use rustls::{
     Certificate, PrivateKey, SupportedProtocolVersion,
 };
 use rustls_pemfile::{certs, read_one, Item};
+use sha2::{Digest, Sha256};
 
 use super::Config;
 
@@ -41,6 +43,28 @@ pub struct CertificateResolver {
     pub default_cert: Option<Arc<CertifiedKey>>,
 }
 
+// Channel binding data (RFC 5929) for the certificates served by a listener
+#[derive(Debug, Default, Clone)]
+pub struct TlsEndPoints {
+    pub default: Option<Vec<u8>>,
+    pub sni: AHashMap<String, Vec<u8>>,
+}
+
+impl TlsEndPoints {
+    pub fn get(&self, server_name: Option<&str>) -> Option<Vec<u8>> {
+        server_name
+            .and_then(|name| self.sni.get(&name.to_lowercase()))
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+// The end-point hash uses SHA-256, which is what RFC 5929 mandates for
+// certificates signed with MD5, SHA-1 or SHA-256.
+pub fn tls_server_end_point(certs: &[Certificate]) -> Option<Vec<u8>> {
+    certs.first().map(|cert| Sha256::digest(&cert.0).to_vec())
+}
+
 impl ResolvesServerCert for CertificateResolver {
     fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
         self.resolver
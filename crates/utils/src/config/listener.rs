@@ -39,7 +39,9 @@ use tokio::net::TcpSocket;
 use crate::UnwrapFailure;
 
 use super::{
-    certificate::{CertificateResolver, TLS12_VERSION, TLS13_VERSION},
+    certificate::{
+        tls_server_end_point, CertificateResolver, TlsEndPoints, TLS12_VERSION, TLS13_VERSION,
+    },
     utils::{AsKey, ParseKey, ParseValue},
     Config, Listener, Server, ServerProtocol, Servers,
 };
@@ -66,6 +68,7 @@ impl Config {
 
     fn parse_server(&self, id: &str) -> super::Result<Server> {
         // Build TLS config
+        let mut tls_end_points = TlsEndPoints::default();
         let (tls, tls_implicit) = if self
             .property_or_default(("server.listener", id, "tls.enable"), "server.tls.enable")?
             .unwrap_or(false)
@@ -105,6 +108,7 @@ impl Config {
                 .ok_or_else(|| format!("Undefined certificate id for listener {id:?}."))?;
             let cert = self.rustls_certificate(cert_id)?;
             let pki = self.rustls_private_key(cert_id)?;
+            tls_end_points.default = tls_server_end_point(&cert);
 
             // Add SNI certificates
             let mut resolver = ResolvesServerCertUsingSni::new();
@@ -114,6 +118,11 @@ impl Config {
             {
                 if let Some(prefix) = key.strip_suffix(".subject") {
                     has_sni = true;
+                    if let Some(end_point) = tls_server_end_point(&self.rustls_certificate(
+                        self.value((prefix, "certificate")).unwrap_or(cert_id),
+                    )?) {
+                        tls_end_points.sni.insert(value.to_lowercase(), end_point);
+                    }
                     resolver
                         .add(
                             value,
@@ -300,6 +309,7 @@ impl Config {
             listeners,
             tls,
             tls_implicit,
+            tls_end_points,
         })
     }
 }
@@ -33,6 +33,8 @@ use tokio::net::TcpSocket;
 
 use crate::{failed, UnwrapFailure};
 
+use self::certificate::TlsEndPoints;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Config {
     pub keys: BTreeMap<String, String>,
@@ -48,6 +50,7 @@ pub struct Server {
     pub listeners: Vec<Listener>,
     pub tls: Option<ServerConfig>,
     pub tls_implicit: bool,
+    pub tls_end_points: TlsEndPoints,
     pub max_connections: u64,
 }
 
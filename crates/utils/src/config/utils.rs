@@ -281,6 +281,30 @@ impl ParseValue for u64 {
     }
 }
 
+impl ParseValue for i64 {
+    fn parse_value(key: impl AsKey, value: &str) -> super::Result<Self> {
+        value.parse().map_err(|_| {
+            format!(
+                "Invalid integer value {:?} for property {:?}.",
+                value,
+                key.as_key()
+            )
+        })
+    }
+}
+
+impl ParseValue for f64 {
+    fn parse_value(key: impl AsKey, value: &str) -> super::Result<Self> {
+        value.parse().map_err(|_| {
+            format!(
+                "Invalid floating point value {:?} for property {:?}.",
+                value,
+                key.as_key()
+            )
+        })
+    }
+}
+
 impl ParseValue for u16 {
     fn parse_value(key: impl AsKey, value: &str) -> super::Result<Self> {
         value.parse().map_err(|_| {
@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use std::{borrow::Cow, path::PathBuf};
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
 
 use tokio::{fs, io::AsyncReadExt, sync::oneshot};
 
@@ -31,12 +31,24 @@ pub enum DeliveryEvent {
         message: IngestMessage,
         result_tx: oneshot::Sender<Vec<DeliveryResult>>,
     },
+    SpamClassify {
+        message: Arc<Vec<u8>>,
+        recipients: Vec<String>,
+        result_tx: oneshot::Sender<Option<SpamClassification>>,
+    },
     Stop,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct SpamClassification {
+    pub score: f64,
+    pub is_spam: bool,
+}
+
 #[derive(Debug)]
 pub struct IngestMessage {
     pub sender_address: String,
+    pub sender_authenticated: bool,
     pub recipients: Vec<String>,
     pub message_path: PathBuf,
     pub message_size: usize,
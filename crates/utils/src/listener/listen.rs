@@ -34,6 +34,7 @@ use crate::{
     config::{Config, Listener, Server, ServerProtocol, Servers},
     failed,
     listener::SessionData,
+    metrics::{protocol_idx, METRICS},
     UnwrapFailure,
 };
 
@@ -53,10 +54,13 @@ impl Server {
             protocol: self.protocol,
             hostname: self.hostname,
             tls_acceptor: self.tls.map(|config| TlsAcceptor::from(Arc::new(config))),
+            tls_end_points: self.tls_end_points,
             is_tls_implicit: self.tls_implicit,
             limiter: ConcurrencyLimiter::new(self.max_connections),
             shutdown_rx,
         });
+        METRICS.register_listener(&instance.id, instance.protocol, instance.limiter.clone());
+        let protocol_idx = protocol_idx(&instance.protocol);
 
         // Spawn listeners
         for listener in self.listeners {
@@ -85,6 +89,7 @@ impl Server {
                                 Ok((stream, remote_addr)) => {
                                     // Enforce concurrency
                                     if let Some(in_flight) = instance.limiter.is_allowed() {
+                                        METRICS.connections[protocol_idx].inc();
                                         let span = tracing::info_span!(
                                             "session",
                                             instance = instance.id,
@@ -98,11 +103,13 @@ impl Server {
                                             stream,
                                             local_ip,
                                             remote_ip: remote_addr.ip(),
+                                            remote_port: remote_addr.port(),
                                             span,
                                             in_flight,
                                             instance: instance.clone(),
                                         });
                                     } else {
+                                        METRICS.connections_rejected[protocol_idx].inc();
                                         tracing::info!(
                                             context = "throttle",
                                             event = "too-many-requests",
@@ -220,4 +227,8 @@ impl ServerInstance {
             }
         }
     }
+
+    pub fn tls_end_point(&self, stream: &TlsStream<TcpStream>) -> Option<Vec<u8>> {
+        self.tls_end_points.get(stream.get_ref().1.server_name())
+    }
 }
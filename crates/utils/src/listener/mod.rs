@@ -30,7 +30,7 @@ use tokio::{
 };
 use tokio_rustls::TlsAcceptor;
 
-use crate::config::ServerProtocol;
+use crate::config::{certificate::TlsEndPoints, ServerProtocol};
 
 use self::limiter::{ConcurrencyLimiter, InFlight};
 
@@ -44,6 +44,7 @@ pub struct ServerInstance {
     pub hostname: String,
     pub data: String,
     pub tls_acceptor: Option<TlsAcceptor>,
+    pub tls_end_points: TlsEndPoints,
     pub is_tls_implicit: bool,
     pub limiter: ConcurrencyLimiter,
     pub shutdown_rx: watch::Receiver<bool>,
@@ -53,6 +54,7 @@ pub struct SessionData<T: AsyncRead + AsyncWrite + Unpin + 'static> {
     pub stream: T,
     pub local_ip: IpAddr,
     pub remote_ip: IpAddr,
+    pub remote_port: u16,
     pub span: tracing::Span,
     pub in_flight: InFlight,
     pub instance: Arc<ServerInstance>,
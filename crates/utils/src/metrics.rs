@@ -0,0 +1,402 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{config::ServerProtocol, listener::limiter::ConcurrencyLimiter};
+
+pub static METRICS: Metrics = Metrics::new();
+
+pub const REPORT_DMARC: usize = 0;
+pub const REPORT_TLS: usize = 1;
+pub const REPORT_ARF: usize = 2;
+
+pub const HOUSEKEEPER_PURGE_DB: usize = 0;
+pub const HOUSEKEEPER_PURGE_BLOBS: usize = 1;
+pub const HOUSEKEEPER_PURGE_SESSIONS: usize = 2;
+
+const PROTOCOLS: [&str; 6] = ["smtp", "lmtp", "jmap", "imap", "http", "managesieve"];
+const REPORTS: [&str; 3] = ["dmarc", "tls", "arf"];
+const HOUSEKEEPER_TASKS: [&str; 3] = ["purge_db", "purge_blobs", "purge_sessions"];
+
+pub struct Metrics {
+    pub connections: [Counter; 6],
+    pub connections_rejected: [Counter; 6],
+    pub queue_messages: Gauge,
+    pub delivery_completed: Counter,
+    pub delivery_temp_fail: Counter,
+    pub delivery_perm_fail: Counter,
+    pub reports_received: [Counter; 3],
+    pub reports_sent: [Counter; 3],
+    pub store_read: Timer,
+    pub store_write: Timer,
+    pub housekeeper: [Timer; 3],
+    listeners: Mutex<Vec<ListenerMetric>>,
+}
+
+pub struct Counter {
+    value: AtomicU64,
+}
+
+pub struct Gauge {
+    value: AtomicI64,
+}
+
+pub struct Timer {
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+struct ListenerMetric {
+    id: String,
+    protocol: ServerProtocol,
+    limiter: ConcurrencyLimiter,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            connections: [
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+            ],
+            connections_rejected: [
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+                Counter::new(),
+            ],
+            queue_messages: Gauge::new(),
+            delivery_completed: Counter::new(),
+            delivery_temp_fail: Counter::new(),
+            delivery_perm_fail: Counter::new(),
+            reports_received: [Counter::new(), Counter::new(), Counter::new()],
+            reports_sent: [Counter::new(), Counter::new(), Counter::new()],
+            store_read: Timer::new(),
+            store_write: Timer::new(),
+            housekeeper: [Timer::new(), Timer::new(), Timer::new()],
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn register_listener(
+        &self,
+        id: &str,
+        protocol: ServerProtocol,
+        limiter: ConcurrencyLimiter,
+    ) {
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.push(ListenerMetric {
+                id: id.to_string(),
+                protocol,
+                limiter,
+            });
+        }
+    }
+
+    pub fn export(&self) -> String {
+        let mut buf = String::with_capacity(4096);
+
+        // Listeners
+        write_header(
+            &mut buf,
+            "stalwart_connections",
+            "counter",
+            "Accepted connections.",
+        );
+        for (protocol, counter) in PROTOCOLS.iter().zip(self.connections.iter()) {
+            let _ = writeln!(
+                buf,
+                "stalwart_connections_total{{protocol=\"{protocol}\"}} {}",
+                counter.get()
+            );
+        }
+        write_header(
+            &mut buf,
+            "stalwart_connections_rejected",
+            "counter",
+            "Connections rejected due to concurrency limits.",
+        );
+        for (protocol, counter) in PROTOCOLS.iter().zip(self.connections_rejected.iter()) {
+            let _ = writeln!(
+                buf,
+                "stalwart_connections_rejected_total{{protocol=\"{protocol}\"}} {}",
+                counter.get()
+            );
+        }
+        write_header(
+            &mut buf,
+            "stalwart_connections_active",
+            "gauge",
+            "Active connections per listener.",
+        );
+        if let Ok(listeners) = self.listeners.lock() {
+            for listener in listeners.iter() {
+                let _ = writeln!(
+                    buf,
+                    "stalwart_connections_active{{listener=\"{}\",protocol=\"{}\"}} {}",
+                    listener.id.replace(['\\', '"'], "_"),
+                    PROTOCOLS[protocol_idx(&listener.protocol)],
+                    listener.limiter.concurrent.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        // SMTP queue
+        write_header(
+            &mut buf,
+            "stalwart_smtp_queue_messages",
+            "gauge",
+            "Messages in the SMTP queue.",
+        );
+        let _ = writeln!(
+            buf,
+            "stalwart_smtp_queue_messages {}",
+            self.queue_messages.get()
+        );
+        write_header(
+            &mut buf,
+            "stalwart_smtp_delivery",
+            "counter",
+            "Outbound delivery results per recipient.",
+        );
+        for (result, counter) in [
+            ("completed", &self.delivery_completed),
+            ("temp_fail", &self.delivery_temp_fail),
+            ("perm_fail", &self.delivery_perm_fail),
+        ] {
+            let _ = writeln!(
+                buf,
+                "stalwart_smtp_delivery_total{{result=\"{result}\"}} {}",
+                counter.get()
+            );
+        }
+
+        // Reports
+        write_header(
+            &mut buf,
+            "stalwart_reports_received",
+            "counter",
+            "Incoming DMARC, TLS and ARF reports.",
+        );
+        for (report, counter) in REPORTS.iter().zip(self.reports_received.iter()) {
+            let _ = writeln!(
+                buf,
+                "stalwart_reports_received_total{{type=\"{report}\"}} {}",
+                counter.get()
+            );
+        }
+        write_header(
+            &mut buf,
+            "stalwart_reports_sent",
+            "counter",
+            "Outgoing DMARC, TLS and ARF reports.",
+        );
+        for (report, counter) in REPORTS.iter().zip(self.reports_sent.iter()) {
+            let _ = writeln!(
+                buf,
+                "stalwart_reports_sent_total{{type=\"{report}\"}} {}",
+                counter.get()
+            );
+        }
+
+        // Store
+        write_header(
+            &mut buf,
+            "stalwart_store_duration_seconds",
+            "summary",
+            "Store operation latency.",
+        );
+        for (op, timer) in [("read", &self.store_read), ("write", &self.store_write)] {
+            timer.export(&mut buf, "stalwart_store_duration_seconds", "op", op);
+        }
+
+        // Housekeeper
+        write_header(
+            &mut buf,
+            "stalwart_housekeeper_duration_seconds",
+            "summary",
+            "Housekeeper task run time.",
+        );
+        for (task, timer) in HOUSEKEEPER_TASKS.iter().zip(self.housekeeper.iter()) {
+            timer.export(
+                &mut buf,
+                "stalwart_housekeeper_duration_seconds",
+                "task",
+                task,
+            );
+        }
+
+        buf.push_str("# EOF\n");
+        buf
+    }
+}
+
+impl Counter {
+    pub const fn new() -> Self {
+        Counter {
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_by(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Gauge {
+    pub const fn new() -> Self {
+        Gauge {
+            value: AtomicI64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Timer {
+    pub const fn new() -> Self {
+        Timer {
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, elapsed: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn export(&self, buf: &mut String, name: &str, label: &str, value: &str) {
+        let _ = writeln!(
+            buf,
+            "{name}_count{{{label}=\"{value}\"}} {}",
+            self.count.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            buf,
+            "{name}_sum{{{label}=\"{value}\"}} {}",
+            self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Gauge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn protocol_idx(protocol: &ServerProtocol) -> usize {
+    match protocol {
+        ServerProtocol::Smtp => 0,
+        ServerProtocol::Lmtp => 1,
+        ServerProtocol::Jmap => 2,
+        ServerProtocol::Imap => 3,
+        ServerProtocol::Http => 4,
+        ServerProtocol::ManageSieve => 5,
+    }
+}
+
+fn write_header(buf: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(buf, "# TYPE {name} {kind}");
+    let _ = writeln!(buf, "# HELP {name} {help}");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Metrics;
+
+    #[test]
+    fn export_metrics() {
+        let metrics = Metrics::new();
+        metrics.connections[super::protocol_idx(&super::ServerProtocol::Imap)].inc();
+        metrics.queue_messages.inc();
+        metrics.queue_messages.inc();
+        metrics.queue_messages.dec();
+        metrics.delivery_perm_fail.inc_by(3);
+        metrics.store_read.observe(Duration::from_millis(1500));
+
+        let export = metrics.export();
+        for expected in [
+            "stalwart_connections_total{protocol=\"imap\"} 1\n",
+            "stalwart_connections_total{protocol=\"smtp\"} 0\n",
+            "stalwart_smtp_queue_messages 1\n",
+            "stalwart_smtp_delivery_total{result=\"perm_fail\"} 3\n",
+            "stalwart_store_duration_seconds_count{op=\"read\"} 1\n",
+            "stalwart_store_duration_seconds_sum{op=\"read\"} 1.5\n",
+        ] {
+            assert!(
+                export.contains(expected),
+                "{expected:?} not found in {export}"
+            );
+        }
+        assert!(export.ends_with("# EOF\n"));
+    }
+}
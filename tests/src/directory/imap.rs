@@ -23,6 +23,7 @@
 
 use std::sync::Arc;
 
+use directory::Protocol;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use tokio::{
@@ -76,7 +77,7 @@ async fn imap_directory() {
         assert_eq!(
             &LookupResult::from(
                 handle
-                    .authenticate(item.as_credentials())
+                    .authenticate(item.as_credentials(), Protocol::Imap)
                     .await
                     .unwrap()
                     .is_some()
@@ -96,7 +97,7 @@ async fn imap_directory() {
             tokio::spawn(async move {
                 LookupResult::from(
                     handle
-                        .authenticate(item.as_credentials())
+                        .authenticate(item.as_credentials(), Protocol::Imap)
                         .await
                         .unwrap()
                         .is_some(),
@@ -0,0 +1,264 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use directory::{
+    config::ConfigDirectory, DirectoryError, Principal, PrincipalUpdate, Protocol, Type,
+};
+use mail_send::Credentials;
+use store::Store;
+use utils::config::Config;
+
+use crate::store::TempDir;
+
+const CONFIG: &str = r#"
+store.blob.type = "local"
+store.blob.local.path = "{TMP}"
+store.db.path = "{TMP}/sqlite.db"
+
+[directory."internal"]
+type = "internal"
+
+[directory."internal".options]
+catch-all = true
+subaddressing = true
+
+[directory."internal".lookup]
+domains = "domains"
+recipients = "recipients"
+"#;
+
+#[tokio::test]
+async fn internal_directory() {
+    let temp_dir = TempDir::new("internal_directory_tests", true);
+    let config =
+        Config::parse(&CONFIG.replace("{TMP}", &temp_dir.path.display().to_string())).unwrap();
+    let store = Arc::new(Store::open(&config).await.unwrap());
+    let config = config.parse_directory_with_store(Some(&store)).unwrap();
+    let handle = config.directories.get("internal").unwrap();
+    let writer = handle
+        .writer()
+        .expect("internal directory should be writable");
+
+    // Create principals
+    writer
+        .create_principal(
+            Principal {
+                name: "sales".to_string(),
+                typ: Type::Group,
+                description: "Sales Team".to_string().into(),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+        .unwrap();
+    writer
+        .create_principal(
+            Principal {
+                name: "john".to_string(),
+                secrets: vec!["12345".to_string()],
+                typ: Type::Individual,
+                description: "John Doe".to_string().into(),
+                quota: 1024,
+                member_of: vec!["sales".to_string()],
+            },
+            vec![
+                "john@example.org".to_string(),
+                "jdoe@example.org".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    writer
+        .create_principal(
+            Principal {
+                name: "jane".to_string(),
+                secrets: vec!["abcde".to_string()],
+                typ: Type::Individual,
+                ..Default::default()
+            },
+            vec!["jane@example.org".to_string()],
+        )
+        .await
+        .unwrap();
+    for name in ["john", "jane"] {
+        writer
+            .update_principal(
+                name,
+                vec![PrincipalUpdate::AddList("info@example.org".to_string())],
+            )
+            .await
+            .unwrap();
+    }
+
+    // Duplicate names, taken addresses and missing groups are rejected
+    assert!(matches!(
+        writer
+            .create_principal(
+                Principal {
+                    name: "john".to_string(),
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await,
+        Err(DirectoryError::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        writer
+            .create_principal(
+                Principal {
+                    name: "bill".to_string(),
+                    ..Default::default()
+                },
+                vec!["jdoe@example.org".to_string()],
+            )
+            .await,
+        Err(DirectoryError::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        writer
+            .update_principal(
+                "jane",
+                vec![PrincipalUpdate::AddMemberOf("support".to_string())]
+            )
+            .await,
+        Err(DirectoryError::NotFound(_))
+    ));
+
+    // Secrets are stored hashed
+    let principal = handle.principal("john").await.unwrap().unwrap();
+    assert_eq!(principal.name, "john");
+    assert_eq!(principal.quota, 1024);
+    assert_eq!(principal.member_of, vec!["sales".to_string()]);
+    assert_ne!(principal.secrets, vec!["12345".to_string()]);
+    assert!(handle
+        .authenticate(
+            &Credentials::Plain {
+                username: "john".to_string(),
+                secret: "12345".to_string(),
+            },
+            Protocol::Imap,
+        )
+        .await
+        .unwrap()
+        .is_some());
+    assert!(handle
+        .authenticate(
+            &Credentials::Plain {
+                username: "john".to_string(),
+                secret: "54321".to_string(),
+            },
+            Protocol::Imap,
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+    // Address lookups
+    assert_eq!(
+        handle.emails_by_name("john").await.unwrap(),
+        vec![
+            "john@example.org".to_string(),
+            "jdoe@example.org".to_string()
+        ]
+    );
+    assert_eq!(
+        handle
+            .names_by_email("jdoe+alias@example.org")
+            .await
+            .unwrap(),
+        vec!["john".to_string()]
+    );
+    let mut names = handle.names_by_email("info@example.org").await.unwrap();
+    names.sort_unstable();
+    assert_eq!(names, vec!["jane".to_string(), "john".to_string()]);
+    assert!(handle.rcpt("jane@example.org").await.unwrap());
+    assert!(!handle.rcpt("unknown@example.org").await.unwrap());
+    assert_eq!(
+        handle.vrfy("jo").await.unwrap(),
+        vec!["john@example.org".to_string()]
+    );
+    let mut expn = handle.expn("info@example.org").await.unwrap();
+    expn.sort_unstable();
+    assert_eq!(
+        expn,
+        vec![
+            "jane@example.org".to_string(),
+            "john@example.org".to_string()
+        ]
+    );
+    assert!(handle.is_local_domain("example.org").await.unwrap());
+    assert!(!handle.is_local_domain("other.org").await.unwrap());
+
+    // Directory lookups
+    let lookup = config.lookups.get("internal/domains").unwrap();
+    assert_eq!(lookup.contains("example.org").await, Some(true));
+    assert_eq!(lookup.contains("other.org").await, Some(false));
+    let lookup = config.lookups.get("internal/recipients").unwrap();
+    assert_eq!(lookup.contains("jane@example.org").await, Some(true));
+    assert_eq!(lookup.contains("bill@example.org").await, Some(false));
+
+    // Removing the primary address promotes the first alias
+    writer
+        .update_principal(
+            "john",
+            vec![PrincipalUpdate::RemoveEmail("john@example.org".to_string())],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        handle.emails_by_name("john").await.unwrap(),
+        vec!["jdoe@example.org".to_string()]
+    );
+    assert!(!handle.rcpt("john@example.org").await.unwrap());
+    assert_eq!(
+        handle.vrfy("jdoe").await.unwrap(),
+        vec!["jdoe@example.org".to_string()]
+    );
+
+    // Deleting a group removes its memberships
+    writer.delete_principal("sales").await.unwrap();
+    assert!(handle
+        .principal("john")
+        .await
+        .unwrap()
+        .unwrap()
+        .member_of
+        .is_empty());
+    assert_eq!(
+        writer.list_principals().await.unwrap(),
+        vec!["jane".to_string(), "john".to_string()]
+    );
+
+    // Deleting all principals releases the domain
+    writer.delete_principal("john").await.unwrap();
+    writer.delete_principal("jane").await.unwrap();
+    assert!(!handle.rcpt("jane@example.org").await.unwrap());
+    assert!(!handle.is_local_domain("example.org").await.unwrap());
+    assert!(writer.list_principals().await.unwrap().is_empty());
+
+    temp_dir.delete();
+}
@@ -23,7 +23,7 @@
 
 use std::fmt::Debug;
 
-use directory::{Principal, Type};
+use directory::{Principal, Protocol, Type};
 use mail_send::Credentials;
 
 use crate::directory::parse_config;
@@ -44,10 +44,13 @@ async fn ldap_directory() {
     // Test authentication
     assert_eq!(
         handle
-            .authenticate(&Credentials::Plain {
-                username: "john".to_string(),
-                secret: "12345".to_string()
-            })
+            .authenticate(
+                &Credentials::Plain {
+                    username: "john".to_string(),
+                    secret: "12345".to_string()
+                },
+                Protocol::Imap,
+            )
             .await
             .unwrap()
             .unwrap(),
@@ -62,10 +65,13 @@ async fn ldap_directory() {
     );
     assert_eq!(
         handle
-            .authenticate(&Credentials::Plain {
-                username: "bill".to_string(),
-                secret: "password".to_string()
-            })
+            .authenticate(
+                &Credentials::Plain {
+                    username: "bill".to_string(),
+                    secret: "password".to_string()
+                },
+                Protocol::Imap,
+            )
             .await
             .unwrap()
             .unwrap(),
@@ -81,10 +87,13 @@ async fn ldap_directory() {
         }
     );
     assert!(handle
-        .authenticate(&Credentials::Plain {
-            username: "bill".to_string(),
-            secret: "invalid".to_string()
-        })
+        .authenticate(
+            &Credentials::Plain {
+                username: "bill".to_string(),
+                secret: "invalid".to_string()
+            },
+            Protocol::Imap,
+        )
         .await
         .unwrap()
         .is_none());
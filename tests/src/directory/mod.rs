@@ -22,9 +22,14 @@
 */
 
 pub mod imap;
+pub mod internal;
 pub mod ldap;
+pub mod oidc;
+pub mod scram;
 pub mod smtp;
 pub mod sql;
+pub mod two_factor;
+pub mod write;
 
 use directory::{config::ConfigDirectory, DirectoryConfig};
 use mail_send::Credentials;
@@ -62,6 +67,19 @@ email = "address"
 quota = "quota"
 type = "type"
 
+[directory."sql".write]
+list = "SELECT name FROM accounts ORDER BY name"
+insert-principal = "INSERT INTO accounts (name, type, secret, description, quota) VALUES (?, ?, ?, ?, ?)"
+update-principal = "UPDATE accounts SET type = ?, secret = ?, description = ?, quota = ? WHERE name = ?"
+delete-principal = "DELETE FROM accounts WHERE name = ?"
+insert-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+delete-email = "DELETE FROM emails WHERE name = ? AND address = ?"
+delete-emails = "DELETE FROM emails WHERE name = ?"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES (?, ?)"
+delete-member = "DELETE FROM group_members WHERE name = ? AND member_of = ?"
+delete-members = "DELETE FROM group_members WHERE name = ?"
+delete-group-members = "DELETE FROM group_members WHERE member_of = ?"
+
 [directory."sql".lookup]
 domains = "SELECT name FROM domains WHERE name = ?"
 
@@ -0,0 +1,403 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use directory::{config::ConfigDirectory, Directory, Protocol, Type};
+use hyper::{body, server::conn::http1, service::service_fn, StatusCode};
+use hyper_util::rt::TokioIo;
+use jmap::{
+    api::{
+        http::{fetch_body, ToHttpResponse},
+        HtmlResponse,
+    },
+    auth::AccessToken,
+};
+use mail_send::Credentials;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+const CONFIG: &str = r#"
+[directory."local"]
+type = "memory"
+
+[[directory."local".users]]
+name = "john"
+description = "John Doe"
+secret = "12345"
+email = "john@example.org"
+
+[directory."idp-jwks"]
+type = "oidc"
+issuer = "http://127.0.0.1:9088"
+audience = ["stalwart"]
+
+[directory."idp-jwks".jwks]
+url = "http://127.0.0.1:9088/jwks"
+
+[directory."idp-jwks".lookup]
+domains = ["example.org"]
+
+[directory."idp-introspect"]
+type = "oidc"
+issuer = "http://127.0.0.1:9088"
+backend = "local"
+
+[directory."idp-introspect".introspect]
+url = "http://127.0.0.1:9088/introspect"
+client-id = "stalwart"
+client-secret = "secret"
+"#;
+
+const SIGNING_KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+const OTHER_KEY: &[u8] = b"fedcba9876543210fedcba9876543210";
+
+#[tokio::test]
+async fn oidc_directory() {
+    // Start the identity provider
+    let signing_key = SigningKey::from_slice(SIGNING_KEY).unwrap();
+    spawn_identity_provider(&signing_key).await;
+
+    let mut config = utils::config::Config::parse(CONFIG)
+        .unwrap()
+        .parse_directory()
+        .unwrap();
+    let jwks = config.directories.remove("idp-jwks").unwrap();
+    let introspect = config.directories.remove("idp-introspect").unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let claims = json!({
+        "iss": "http://127.0.0.1:9088",
+        "aud": ["account", "stalwart"],
+        "exp": now + 300,
+        "iat": now,
+        "preferred_username": "jane.smith",
+        "email": "Jane.Smith@example.org",
+        "email_verified": true,
+        "name": "Jane Smith",
+        "groups": ["/sales", "superusers"],
+    });
+
+    // Valid token
+    let token = sign_token(&signing_key, "key1", &claims);
+    let principal = jwks
+        .authenticate(
+            &Credentials::OAuthBearer {
+                token: token.clone(),
+            },
+            Protocol::Imap,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(principal.name, "jane.smith");
+    assert_eq!(principal.description.as_deref(), Some("Jane Smith"));
+    assert_eq!(principal.typ, Type::Superuser);
+    assert_eq!(principal.member_of, vec!["sales".to_string()]);
+
+    // Principals are available for lookups once authenticated
+    assert_eq!(
+        jwks.principal("jane.smith").await.unwrap(),
+        Some(principal.clone())
+    );
+    assert_eq!(
+        jwks.emails_by_name("jane.smith").await.unwrap(),
+        vec!["jane.smith@example.org".to_string()]
+    );
+    assert_eq!(
+        jwks.names_by_email("jane.smith+tag@example.org")
+            .await
+            .unwrap(),
+        vec!["jane.smith".to_string()]
+    );
+    assert!(jwks.rcpt("jane.smith@example.org").await.unwrap());
+    assert!(!jwks.rcpt("john@example.org").await.unwrap());
+    assert!(jwks.is_local_domain("example.org").await.unwrap());
+    assert_eq!(jwks.principal("john").await.unwrap(), None);
+
+    // SASL OAUTHBEARER client response
+    assert_eq!(
+        jwks.authenticate(
+            &Credentials::OAuthBearer {
+                token: format!("n,a=jane.smith,\x01auth=Bearer {token}\x01\x01"),
+            },
+            Protocol::Smtp,
+        )
+        .await
+        .unwrap(),
+        Some(principal.clone())
+    );
+
+    // XOAUTH2 usernames have to match the token
+    for (username, expect) in [
+        ("jane.smith", true),
+        ("jane.smith@example.org", true),
+        ("john", false),
+    ] {
+        assert_eq!(
+            jwks.authenticate(
+                &Credentials::XOauth2 {
+                    username: username.to_string(),
+                    secret: token.clone(),
+                },
+                Protocol::Imap,
+            )
+            .await
+            .unwrap()
+            .is_some(),
+            expect,
+            "{username}"
+        );
+    }
+
+    // Unverified addresses and addresses on foreign domains are ignored
+    for (claim, value) in [
+        ("email_verified", json!(false)),
+        ("email", json!("jane.smith@foreign.org")),
+    ] {
+        let mut claims = claims.clone();
+        claims[claim] = value;
+        assert!(jwks
+            .authenticate(
+                &Credentials::OAuthBearer {
+                    token: sign_token(&signing_key, "key1", &claims),
+                },
+                Protocol::Imap,
+            )
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            jwks.emails_by_name("jane.smith").await.unwrap(),
+            Vec::<String>::new(),
+            "{claim}"
+        );
+        assert!(!jwks.rcpt("jane.smith@example.org").await.unwrap());
+        assert!(!jwks.rcpt("jane.smith@foreign.org").await.unwrap());
+        assert!(!jwks.is_local_domain("foreign.org").await.unwrap());
+    }
+
+    // Invalid tokens
+    let mut tampered = claims.clone();
+    tampered["preferred_username"] = "john".into();
+    let tampered = format!(
+        "{}.{}",
+        sign_token(&signing_key, "key1", &tampered)
+            .rsplit_once('.')
+            .unwrap()
+            .0,
+        token.rsplit_once('.').unwrap().1
+    );
+    let mut invalid_tokens = vec![
+        tampered,
+        sign_token(&SigningKey::from_slice(OTHER_KEY).unwrap(), "key2", &claims),
+        sign_token(&SigningKey::from_slice(OTHER_KEY).unwrap(), "key1", &claims),
+        "not-a-jwt".to_string(),
+    ];
+    for (claim, value) in [
+        ("iss", json!("https://evil.example.org")),
+        ("aud", json!("other")),
+        ("exp", json!(now - 3600)),
+        ("nbf", json!(now + 3600)),
+        ("preferred_username", json!(null)),
+    ] {
+        let mut claims = claims.clone();
+        claims[claim] = value;
+        invalid_tokens.push(sign_token(&signing_key, "key1", &claims));
+    }
+    for token in invalid_tokens {
+        assert_eq!(
+            jwks.authenticate(
+                &Credentials::OAuthBearer {
+                    token: token.clone()
+                },
+                Protocol::Imap
+            )
+            .await
+            .unwrap(),
+            None,
+            "{token}"
+        );
+    }
+
+    // Passwords are not accepted without a backend
+    assert_eq!(
+        jwks.authenticate(
+            &Credentials::Plain {
+                username: "jane.smith".to_string(),
+                secret: "secret".to_string(),
+            },
+            Protocol::Imap,
+        )
+        .await
+        .unwrap(),
+        None
+    );
+
+    // Token introspection, principals are obtained from the backend
+    let principal = introspect
+        .authenticate(
+            &Credentials::OAuthBearer {
+                token: "opaque-john".to_string(),
+            },
+            Protocol::Jmap,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(principal.name, "john");
+    assert_eq!(principal.description.as_deref(), Some("John Doe"));
+    assert_eq!(
+        introspect
+            .authenticate(
+                &Credentials::OAuthBearer {
+                    token: "opaque-revoked".to_string(),
+                },
+                Protocol::Jmap,
+            )
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(introspect
+        .authenticate(
+            &Credentials::Plain {
+                username: "john".to_string(),
+                secret: "12345".to_string(),
+            },
+            Protocol::Imap,
+        )
+        .await
+        .unwrap()
+        .is_some());
+}
+
+fn sign_token(key: &SigningKey, kid: &str, claims: &serde_json::Value) -> String {
+    let header = URL_SAFE_NO_PAD.encode(
+        json!({
+            "alg": "ES256",
+            "typ": "JWT",
+            "kid": kid,
+        })
+        .to_string(),
+    );
+    let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signature: Signature = key.sign(format!("{header}.{claims}").as_bytes());
+    format!(
+        "{header}.{claims}.{}",
+        URL_SAFE_NO_PAD.encode(signature.to_bytes())
+    )
+}
+
+async fn spawn_identity_provider(key: &SigningKey) {
+    let point = key.verifying_key().to_encoded_point(false);
+    let jwks = json!({
+        "keys": [{
+            "kty": "EC",
+            "kid": "key1",
+            "use": "sig",
+            "alg": "ES256",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        }]
+    })
+    .to_string();
+    let listener = TcpListener::bind("127.0.0.1:9088").await.unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let jwks = jwks.clone();
+            tokio::spawn(async move {
+                let _ = http1::Builder::new()
+                    .keep_alive(false)
+                    .serve_connection(
+                        TokioIo::new(stream),
+                        service_fn(|mut req: hyper::Request<body::Incoming>| {
+                            let jwks = jwks.clone();
+
+                            async move {
+                                let response = match req.uri().path() {
+                                    "/jwks" => jwks,
+                                    "/introspect" => {
+                                        let is_authorized = req
+                                            .headers()
+                                            .get(hyper::header::AUTHORIZATION)
+                                            .and_then(|h| h.to_str().ok())
+                                            .map_or(false, |h| {
+                                                h == format!(
+                                                    "Basic {}",
+                                                    STANDARD.encode("stalwart:secret")
+                                                )
+                                            });
+                                        let body = String::from_utf8(
+                                            fetch_body(&mut req, 1024, &AccessToken::default())
+                                                .await
+                                                .unwrap(),
+                                        )
+                                        .unwrap();
+                                        if !is_authorized {
+                                            return Ok::<_, hyper::Error>(
+                                                HtmlResponse::with_status(
+                                                    StatusCode::UNAUTHORIZED,
+                                                    "unauthorized".to_string(),
+                                                )
+                                                .into_http_response(),
+                                            );
+                                        } else if body.split('&').any(|v| v == "token=opaque-john")
+                                        {
+                                            json!({
+                                                "active": true,
+                                                "iss": "http://127.0.0.1:9088",
+                                                "preferred_username": "john",
+                                            })
+                                            .to_string()
+                                        } else {
+                                            json!({"active": false}).to_string()
+                                        }
+                                    }
+                                    _ => {
+                                        return Ok(HtmlResponse::with_status(
+                                            StatusCode::NOT_FOUND,
+                                            "not found".to_string(),
+                                        )
+                                        .into_http_response());
+                                    }
+                                };
+
+                                Ok(HtmlResponse::new(response).into_http_response())
+                            }
+                        }),
+                    )
+                    .await;
+            });
+        }
+    });
+}
@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use base64::{engine::general_purpose, Engine};
+use directory::{
+    scram::{ScramResult, ScramSecret, ScramServer},
+    Directory,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::directory::parse_config;
+
+#[tokio::test]
+async fn scram_authentication() {
+    let handle = parse_config().directories.remove("local").unwrap();
+
+    // Successful authentication without channel binding
+    let mut client = ScramClient::new("john", "12345", "n,,", None);
+    assert!(client.authenticate(handle.as_ref(), false, None).await);
+
+    // Wrong password and unknown account
+    let mut client = ScramClient::new("john", "54321", "n,,", None);
+    assert!(!client.authenticate(handle.as_ref(), false, None).await);
+    let mut client = ScramClient::new("robert", "12345", "n,,", None);
+    assert!(!client.authenticate(handle.as_ref(), false, None).await);
+
+    // Hashed secrets cannot be used for SCRAM
+    let mut client = ScramClient::new("bill", "password", "n,,", None);
+    assert!(!client.authenticate(handle.as_ref(), false, None).await);
+
+    // Channel binding
+    let binding = b"certificate hash".to_vec();
+    let mut client = ScramClient::new(
+        "jane",
+        "abcde",
+        "p=tls-server-end-point,,",
+        binding.clone().into(),
+    );
+    assert!(
+        client
+            .authenticate(handle.as_ref(), true, binding.clone().into())
+            .await
+    );
+    let mut client = ScramClient::new(
+        "jane",
+        "abcde",
+        "p=tls-server-end-point,,",
+        b"other certificate".to_vec().into(),
+    );
+    assert!(
+        !client
+            .authenticate(handle.as_ref(), true, binding.clone().into())
+            .await
+    );
+
+    // Downgrade attempts must fail when the server supports channel binding
+    let mut client = ScramClient::new("jane", "abcde", "y,,", None);
+    assert!(
+        !client
+            .authenticate(handle.as_ref(), false, binding.into())
+            .await
+    );
+
+    // Stored SCRAM secrets
+    let secret = ScramSecret::generate("secret");
+    let parsed = ScramSecret::parse(&secret.to_string()).unwrap();
+    assert_eq!(parsed, secret);
+    assert!(parsed.verify("secret"));
+    assert!(!parsed.verify("wrong secret"));
+}
+
+struct ScramClient {
+    username: String,
+    password: String,
+    gs2_header: String,
+    binding: Option<Vec<u8>>,
+    nonce: String,
+}
+
+impl ScramClient {
+    fn new(username: &str, password: &str, gs2_header: &str, binding: Option<Vec<u8>>) -> Self {
+        ScramClient {
+            username: username.to_string(),
+            password: password.to_string(),
+            gs2_header: gs2_header.to_string(),
+            binding,
+            nonce: "rOprNGfwEbeRWgbNEkqO".to_string(),
+        }
+    }
+
+    async fn authenticate(
+        &mut self,
+        directory: &dyn Directory,
+        is_plus: bool,
+        channel_binding: Option<Vec<u8>>,
+    ) -> bool {
+        let mut server = ScramServer::new(is_plus, channel_binding);
+        let client_first_bare = format!("n={},r={}", self.username, self.nonce);
+        let server_first = match server
+            .next(
+                directory,
+                format!("{}{}", self.gs2_header, client_first_bare).as_bytes(),
+            )
+            .await
+            .unwrap()
+        {
+            ScramResult::Continue(server_first) => String::from_utf8(server_first).unwrap(),
+            ScramResult::Failure(_) => return false,
+            ScramResult::Success { .. } => panic!("Unexpected SCRAM success."),
+        };
+
+        // Parse server-first message
+        let mut nonce = "";
+        let mut salt = vec![];
+        let mut iterations = 0;
+        for attribute in server_first.split(',') {
+            match attribute.split_once('=').unwrap() {
+                ("r", value) => nonce = value,
+                ("s", value) => salt = general_purpose::STANDARD.decode(value).unwrap(),
+                ("i", value) => iterations = value.parse().unwrap(),
+                _ => (),
+            }
+        }
+        assert!(nonce.starts_with(&self.nonce));
+
+        // Build client-final message
+        let mut binding = self.gs2_header.as_bytes().to_vec();
+        if let Some(cb) = &self.binding {
+            binding.extend_from_slice(cb);
+        }
+        let client_final_without_proof = format!(
+            "c={},r={}",
+            general_purpose::STANDARD.encode(&binding),
+            nonce
+        );
+        let auth_message =
+            format!("{client_first_bare},{server_first},{client_final_without_proof}");
+        let mut salted_password = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(
+            self.password.as_bytes(),
+            &salt,
+            iterations,
+            &mut salted_password,
+        );
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+        let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+        let proof = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+
+        match server
+            .next(
+                directory,
+                format!(
+                    "{},p={}",
+                    client_final_without_proof,
+                    general_purpose::STANDARD.encode(proof)
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap()
+        {
+            ScramResult::Success {
+                principal,
+                server_final,
+            } => {
+                assert_eq!(principal.name, self.username);
+                assert!(server.is_done());
+
+                // Verify server signature
+                let server_key = hmac_sha256(&salted_password, b"Server Key");
+                assert_eq!(
+                    String::from_utf8(server_final).unwrap(),
+                    format!(
+                        "v={}",
+                        general_purpose::STANDARD
+                            .encode(hmac_sha256(&server_key, auth_message.as_bytes()))
+                    )
+                );
+                true
+            }
+            ScramResult::Failure(_) => false,
+            ScramResult::Continue(_) => panic!("Unexpected SCRAM continuation."),
+        }
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
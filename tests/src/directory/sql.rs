@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use directory::{Directory, Principal, Type};
+use directory::{Directory, Principal, Protocol, Type};
 use mail_send::Credentials;
 
 use crate::directory::parse_config;
@@ -83,10 +83,13 @@ async fn sql_directory() {
     // Test authentication
     assert_eq!(
         handle
-            .authenticate(&Credentials::Plain {
-                username: "john".to_string(),
-                secret: "12345".to_string()
-            })
+            .authenticate(
+                &Credentials::Plain {
+                    username: "john".to_string(),
+                    secret: "12345".to_string()
+                },
+                Protocol::Imap,
+            )
             .await
             .unwrap()
             .unwrap(),
@@ -101,10 +104,13 @@ async fn sql_directory() {
     );
     assert_eq!(
         handle
-            .authenticate(&Credentials::Plain {
-                username: "bill".to_string(),
-                secret: "password".to_string()
-            })
+            .authenticate(
+                &Credentials::Plain {
+                    username: "bill".to_string(),
+                    secret: "password".to_string()
+                },
+                Protocol::Imap,
+            )
             .await
             .unwrap()
             .unwrap(),
@@ -120,10 +126,13 @@ async fn sql_directory() {
         }
     );
     assert!(handle
-        .authenticate(&Credentials::Plain {
-            username: "bill".to_string(),
-            secret: "invalid".to_string()
-        })
+        .authenticate(
+            &Credentials::Plain {
+                username: "bill".to_string(),
+                secret: "invalid".to_string()
+            },
+            Protocol::Imap,
+        )
         .await
         .unwrap()
         .is_none());
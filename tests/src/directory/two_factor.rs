@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    app_password::AppPassword,
+    secret::hash_secret,
+    totp::{Algorithm, Totp},
+    Principal, Protocol, Type,
+};
+
+#[tokio::test]
+async fn app_passwords_and_totp() {
+    // RFC 6238 test vectors
+    for (algorithm, secret, expected) in [
+        (
+            Algorithm::Sha1,
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+            ["94287082", "07081804", "14050471"],
+        ),
+        (
+            Algorithm::Sha256,
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA",
+            ["46119246", "68084774", "67062674"],
+        ),
+    ] {
+        let totp = Totp::parse(&format!(
+            "otpauth://totp/Test?secret={secret}&digits=8&algorithm={}",
+            if algorithm == Algorithm::Sha1 {
+                "SHA1"
+            } else {
+                "SHA256"
+            }
+        ))
+        .unwrap();
+        assert_eq!(totp.algorithm, algorithm);
+        for (timestamp, expected) in [59, 1111111109, 1111111111].into_iter().zip(expected) {
+            assert_eq!(totp.code_at(timestamp), expected);
+        }
+        assert_eq!(Totp::parse(&totp.to_string()).unwrap(), totp);
+    }
+
+    // Application passwords
+    let app_password = AppPassword::new(
+        "phone",
+        vec![Protocol::Imap, Protocol::Smtp],
+        hash_secret("app-secret".to_string()).await.unwrap(),
+    );
+    assert_eq!(
+        AppPassword::parse(&app_password.to_string()).unwrap(),
+        app_password
+    );
+    assert!(AppPassword::parse("$app$$imap$hash").is_none());
+    assert!(AppPassword::parse("$app$phone$pop4$hash").is_none());
+    let mut principal = Principal {
+        name: "john".to_string(),
+        secrets: vec!["12345".to_string(), app_password.to_string()],
+        typ: Type::Individual,
+        ..Default::default()
+    };
+
+    // Without 2FA, both the primary and application passwords are accepted
+    for protocol in [Protocol::Imap, Protocol::Jmap, Protocol::OAuth] {
+        assert!(principal.verify_secret("12345", protocol).await);
+    }
+    assert!(principal.verify_secret("app-secret", Protocol::Imap).await);
+    assert!(principal.verify_secret("app-secret", Protocol::Smtp).await);
+    assert!(!principal.verify_secret("app-secret", Protocol::Jmap).await);
+    assert!(!principal.verify_secret("app-secret", Protocol::OAuth).await);
+    assert!(principal.scram_secret().is_some());
+
+    // With 2FA enabled, interactive logins require a code and
+    // other protocols can only use application passwords
+    let totp = Totp::generate("Stalwart:john", "Stalwart".into());
+    principal.secrets.push(totp.to_string());
+    let code = totp.current_code();
+    let wrong_code = (0..4)
+        .map(|n| format!("{n:06}"))
+        .find(|candidate| !totp.verify(candidate))
+        .unwrap();
+    assert!(
+        principal
+            .verify_secret(&format!("12345${code}"), Protocol::OAuth)
+            .await
+    );
+    assert!(
+        !principal
+            .verify_secret(&format!("12345${wrong_code}"), Protocol::OAuth)
+            .await
+    );
+    assert!(
+        !principal
+            .verify_secret(&format!("12345${code}"), Protocol::OAuth)
+            .await,
+        "codes cannot be reused"
+    );
+    assert!(!principal.verify_secret("12345", Protocol::OAuth).await);
+    assert!(!principal.verify_secret("12345", Protocol::Imap).await);
+    assert!(
+        !principal
+            .verify_secret(&format!("12345${code}"), Protocol::Imap)
+            .await
+    );
+    assert!(principal.verify_secret("app-secret", Protocol::Imap).await);
+    assert!(!principal.verify_secret("app-secret", Protocol::OAuth).await);
+    assert!(principal.scram_secret().is_none());
+}
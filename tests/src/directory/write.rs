@@ -0,0 +1,256 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{Directory, DirectoryError, Principal, PrincipalUpdate, Protocol, Type};
+use mail_send::Credentials;
+
+use crate::directory::{parse_config, sql::create_test_directory};
+
+#[tokio::test]
+async fn directory_write() {
+    let mut config = parse_config();
+
+    for directory_id in ["local", "sql"] {
+        println!("Testing writes on {directory_id} directory...");
+        let handle = config.directories.remove(directory_id).unwrap();
+        if directory_id == "sql" {
+            create_test_directory(handle.as_ref()).await;
+        }
+        let writer = handle.writer().expect("directory should be writable");
+
+        // Create group and account
+        writer
+            .create_principal(
+                Principal {
+                    name: "staff".to_string(),
+                    typ: Type::Group,
+                    description: "Staff".to_string().into(),
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+        writer
+            .create_principal(
+                Principal {
+                    name: "alice".to_string(),
+                    secrets: vec!["s3cr3t".to_string()],
+                    typ: Type::Individual,
+                    description: "Alice Doe".to_string().into(),
+                    quota: 1024,
+                    member_of: vec!["staff".to_string()],
+                },
+                vec!["alice@example.org".to_string()],
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            writer
+                .create_principal(
+                    Principal {
+                        name: "alice".to_string(),
+                        ..Default::default()
+                    },
+                    vec![],
+                )
+                .await,
+            Err(DirectoryError::AlreadyExists(_))
+        ));
+        let mut principal = handle.principal("alice").await.unwrap().unwrap();
+        assert!(principal.secrets.len() > 1);
+        assert!(principal.secrets.iter().all(|secret| secret != "s3cr3t"));
+        assert!(principal.secrets[0].starts_with("$argon2"));
+        principal.secrets.clear();
+        assert_eq!(
+            principal,
+            Principal {
+                name: "alice".to_string(),
+                secrets: vec![],
+                typ: Type::Individual,
+                description: "Alice Doe".to_string().into(),
+                quota: 1024,
+                member_of: vec!["staff".to_string()],
+            }
+        );
+        assert!(handle
+            .authenticate(
+                &Credentials::Plain {
+                    username: "alice".to_string(),
+                    secret: "s3cr3t".to_string(),
+                },
+                Protocol::Imap,
+            )
+            .await
+            .unwrap()
+            .is_some());
+
+        // Addresses must be unique and groups must exist
+        assert!(matches!(
+            writer
+                .create_principal(
+                    Principal {
+                        name: "mallory".to_string(),
+                        typ: Type::Individual,
+                        ..Default::default()
+                    },
+                    vec!["Alice@example.org".to_string()],
+                )
+                .await,
+            Err(DirectoryError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            writer
+                .create_principal(
+                    Principal {
+                        name: "mallory".to_string(),
+                        typ: Type::Individual,
+                        member_of: vec!["admins".to_string()],
+                        ..Default::default()
+                    },
+                    vec![],
+                )
+                .await,
+            Err(DirectoryError::NotFound(_))
+        ));
+        assert_eq!(handle.principal("mallory").await.unwrap(), None);
+        assert!(handle.rcpt("alice@example.org").await.unwrap());
+        let names = writer.list_principals().await.unwrap();
+        assert!(names.contains(&"alice".to_string()) && names.contains(&"staff".to_string()));
+
+        // Invalid updates are rejected as a whole
+        assert!(matches!(
+            writer
+                .update_principal(
+                    "alice",
+                    vec![
+                        PrincipalUpdate::Quota(1),
+                        PrincipalUpdate::AddEmail("alice.doe@example.org".to_string()),
+                        PrincipalUpdate::AddMemberOf("admins".to_string()),
+                    ],
+                )
+                .await,
+            Err(DirectoryError::NotFound(_))
+        ));
+        assert_eq!(
+            handle.principal("alice").await.unwrap().unwrap().quota,
+            1024
+        );
+        assert!(!handle.rcpt("alice.doe@example.org").await.unwrap());
+
+        // Update account
+        writer
+            .update_principal(
+                "alice",
+                vec![
+                    PrincipalUpdate::Secrets(vec!["n3w_s3cr3t".to_string()]),
+                    PrincipalUpdate::Quota(2048),
+                    PrincipalUpdate::AddEmail("alice.doe@example.org".to_string()),
+                    PrincipalUpdate::RemoveMemberOf("staff".to_string()),
+                ],
+            )
+            .await
+            .unwrap();
+        let principal = handle
+            .authenticate(
+                &Credentials::Plain {
+                    username: "alice".to_string(),
+                    secret: "n3w_s3cr3t".to_string(),
+                },
+                Protocol::Imap,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(principal.quota, 2048);
+        assert!(principal.member_of.is_empty());
+        assert_eq!(
+            handle.emails_by_name("alice").await.unwrap(),
+            vec![
+                "alice@example.org".to_string(),
+                "alice.doe@example.org".to_string()
+            ]
+        );
+        assert!(handle.rcpt("alice.doe@example.org").await.unwrap());
+
+        writer
+            .update_principal(
+                "alice",
+                vec![PrincipalUpdate::RemoveEmail(
+                    "alice.doe@example.org".to_string(),
+                )],
+            )
+            .await
+            .unwrap();
+        assert!(!handle.rcpt("alice.doe@example.org").await.unwrap());
+        assert!(matches!(
+            writer
+                .update_principal("bob", vec![PrincipalUpdate::Quota(1)])
+                .await,
+            Err(DirectoryError::NotFound(_))
+        ));
+
+        // Plain-text secrets that look like hashes are hashed too
+        let secrets = ["$ecret123", "{pass}word"];
+        writer
+            .update_principal(
+                "alice",
+                vec![PrincipalUpdate::Secrets(
+                    secrets.iter().map(|secret| secret.to_string()).collect(),
+                )],
+            )
+            .await
+            .unwrap();
+        let principal = handle.principal("alice").await.unwrap().unwrap();
+        assert!(principal
+            .secrets
+            .iter()
+            .all(|secret| !secrets.contains(&secret.as_str())));
+        for secret in secrets {
+            assert!(
+                handle
+                    .authenticate(
+                        &Credentials::Plain {
+                            username: "alice".to_string(),
+                            secret: secret.to_string(),
+                        },
+                        Protocol::Imap,
+                    )
+                    .await
+                    .unwrap()
+                    .is_some(),
+                "{secret}"
+            );
+        }
+
+        // Delete account and group
+        writer.delete_principal("alice").await.unwrap();
+        writer.delete_principal("staff").await.unwrap();
+        assert_eq!(handle.principal("alice").await.unwrap(), None);
+        assert!(!handle.rcpt("alice@example.org").await.unwrap());
+        assert!(matches!(
+            writer.delete_principal("alice").await,
+            Err(DirectoryError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use imap::core::compress::Inflater;
+use mail_send::smtp::tls::build_tls_connector;
+use rustls::ServerName;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+
+pub async fn test() {
+    println!("Running COMPRESS tests...");
+
+    // Decompressed output is limited to the maximum request size
+    let mut deflater = Compress::new(Compression::default(), false);
+    let mut bomb = Vec::with_capacity(1024);
+    deflater
+        .compress_vec(&[0; 100_000], &mut bomb, FlushCompress::Sync)
+        .unwrap();
+    assert_eq!(
+        Inflater::new(100_000).inflate(&bomb).unwrap(),
+        vec![0; 100_000]
+    );
+    assert!(Inflater::new(99_999).inflate(&bomb).is_err());
+
+    let mut stream = TcpStream::connect("127.0.0.1:9991").await.unwrap();
+    let mut deflater = Compress::new(Compression::default(), false);
+    let mut inflater = Decompress::new(false);
+
+    // Authenticate and enable compression
+    read_until(&mut stream, None, "* OK").await;
+    stream
+        .write_all(b"a AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0\r\n")
+        .await
+        .unwrap();
+    assert!(read_until(&mut stream, None, "a OK")
+        .await
+        .contains("COMPRESS=DEFLATE"));
+    stream.write_all(b"b COMPRESS DEFLATE\r\n").await.unwrap();
+    read_until(&mut stream, None, "b OK").await;
+
+    // Commands and responses are now compressed
+    write_deflate(&mut stream, &mut deflater, "c NOOP\r\n").await;
+    read_until(&mut stream, Some(&mut inflater), "c OK").await;
+    write_deflate(&mut stream, &mut deflater, "d LIST \"\" \"*\"\r\n").await;
+    assert!(read_until(&mut stream, Some(&mut inflater), "d OK")
+        .await
+        .contains("\"INBOX\""));
+
+    // Compression cannot be enabled twice
+    write_deflate(&mut stream, &mut deflater, "e COMPRESS DEFLATE\r\n").await;
+    assert!(read_until(&mut stream, Some(&mut inflater), "e NO")
+        .await
+        .contains("[COMPRESSIONACTIVE]"));
+
+    write_deflate(&mut stream, &mut deflater, "f LOGOUT\r\n").await;
+    read_until(&mut stream, Some(&mut inflater), "f OK").await;
+
+    // Compression can be enabled after STARTTLS
+    let mut stream = TcpStream::connect("127.0.0.1:9991").await.unwrap();
+    let mut deflater = Compress::new(Compression::default(), false);
+    let mut inflater = Decompress::new(false);
+    read_until(&mut stream, None, "* OK").await;
+    stream.write_all(b"a STARTTLS\r\n").await.unwrap();
+    read_until(&mut stream, None, "a OK").await;
+    let mut stream = build_tls_connector(true)
+        .connect(ServerName::try_from("imap.example.org").unwrap(), stream)
+        .await
+        .unwrap();
+    stream
+        .write_all(b"b AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0\r\n")
+        .await
+        .unwrap();
+    assert!(read_until(&mut stream, None, "b OK")
+        .await
+        .contains("COMPRESS=DEFLATE"));
+
+    // Commands sent in the same packet as COMPRESS are deflated
+    let mut buf = b"c COMPRESS DEFLATE\r\n".to_vec();
+    deflater
+        .compress_vec(b"d NOOP\r\n", &mut buf, FlushCompress::Sync)
+        .unwrap();
+    stream.write_all(&buf).await.unwrap();
+    let mut line = Vec::new();
+    while !line.ends_with(b"\r\n") {
+        line.push(stream.read_u8().await.unwrap());
+    }
+    assert!(
+        line.starts_with(b"c OK"),
+        "{}",
+        String::from_utf8_lossy(&line)
+    );
+    read_until(&mut stream, Some(&mut inflater), "d OK").await;
+    write_deflate(&mut stream, &mut deflater, "e LIST \"\" \"*\"\r\n").await;
+    assert!(read_until(&mut stream, Some(&mut inflater), "e OK")
+        .await
+        .contains("\"INBOX\""));
+    write_deflate(&mut stream, &mut deflater, "f LOGOUT\r\n").await;
+    read_until(&mut stream, Some(&mut inflater), "f OK").await;
+}
+
+async fn write_deflate(
+    stream: &mut (impl AsyncWrite + Unpin),
+    deflater: &mut Compress,
+    text: &str,
+) {
+    let mut buf = Vec::with_capacity(1024);
+    deflater
+        .compress_vec(text.as_bytes(), &mut buf, FlushCompress::Sync)
+        .unwrap();
+    stream.write_all(&buf).await.unwrap();
+}
+
+async fn read_until(
+    stream: &mut (impl AsyncRead + Unpin),
+    mut inflater: Option<&mut Decompress>,
+    text: &str,
+) -> String {
+    let mut response = Vec::new();
+    let mut buf = vec![0; 8192];
+
+    loop {
+        let bytes_read = tokio::time::timeout(Duration::from_millis(1500), stream.read(&mut buf))
+            .await
+            .expect("Timeout while waiting for server response")
+            .unwrap();
+        assert_ne!(bytes_read, 0, "Connection closed by server.");
+
+        if let Some(inflater) = inflater.as_mut() {
+            let mut output = Vec::with_capacity(bytes_read * 10 + 1024);
+            inflater
+                .decompress_vec(&buf[..bytes_read], &mut output, FlushDecompress::Sync)
+                .unwrap();
+            response.extend_from_slice(&output);
+        } else {
+            response.extend_from_slice(&buf[..bytes_read]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        if response.contains(text) {
+            return response.into_owned();
+        }
+    }
+}
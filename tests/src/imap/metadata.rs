@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    // METADATA should be advertised once authenticated
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(" METADATA ")
+        .assert_contains(" METADATA-SERVER");
+
+    // Set and obtain mailbox entries
+    imap.send(
+        "SETMETADATA INBOX (/private/comment \"My comment\" /shared/comment \"Our comment\")",
+    )
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA INBOX (/private/comment /Shared/Comment /shared/unknown)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* METADATA \"INBOX\" (")
+        .assert_contains("\"/private/comment\" \"My comment\"")
+        .assert_contains("\"/shared/comment\" \"Our comment\"")
+        .assert_count("/shared/unknown", 0);
+
+    // Entries larger than MAXSIZE are not returned
+    imap.send("GETMETADATA (MAXSIZE 5) INBOX /private/comment")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_response_code("METADATA LONGENTRIES 10")
+        .assert_count("* METADATA", 0);
+
+    // Test DEPTH
+    imap.send("SETMETADATA INBOX (/shared/vendor/test/a \"1\" /shared/vendor/test/a/b \"2\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA INBOX /shared/vendor/test").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* METADATA", 0);
+    imap.send("GETMETADATA (DEPTH 1) INBOX /shared/vendor/test")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"/shared/vendor/test/a\" \"1\"")
+        .assert_count("/shared/vendor/test/a/b", 0);
+    imap.send("GETMETADATA (DEPTH infinity) INBOX /shared/vendor/test")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"/shared/vendor/test/a\" \"1\"")
+        .assert_contains("\"/shared/vendor/test/a/b\" \"2\"");
+
+    // Remove entries
+    imap.send("SETMETADATA INBOX (/private/comment NIL /shared/vendor/test/a NIL)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA (DEPTH infinity) INBOX (/private/comment /shared)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("/private/comment", 0)
+        .assert_count("\"/shared/vendor/test/a\"", 0)
+        .assert_contains("\"/shared/comment\" \"Our comment\"");
+
+    // Invalid entry names and values exceeding the maximum size
+    imap.send("GETMETADATA INBOX /comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+    imap.send(&format!(
+        "SETMETADATA INBOX (/shared/large {{{}+}}\r\n{})",
+        65537,
+        "a".repeat(65537)
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("METADATA MAXSIZE 65536");
+
+    // Server entries
+    imap.send("SETMETADATA \"\" (/private/vendor/test \"Server value\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA \"\" /private/vendor/test").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* METADATA \"\" (\"/private/vendor/test\" \"Server value\")");
+    imap.send("SETMETADATA \"\" (/shared/comment \"Server comment\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NOPERM");
+
+    // Special-use attributes are exposed from the mailbox role
+    imap.send("CREATE \"Metadata Archive\" (USE (\\Archive))")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("GETMETADATA \"Metadata Archive\" /private/specialuse")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* METADATA \"Metadata Archive\" (\"/private/specialuse\" {8}")
+        .assert_contains("\\Archive)");
+    imap.send("GETMETADATA INBOX /private/specialuse").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* METADATA", 0);
+    imap.send("SETMETADATA INBOX (/private/specialuse \"\\\\Sent\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("CANNOT");
+    imap.send("DELETE \"Metadata Archive\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Virtual mailboxes do not support metadata
+    imap.send("GETMETADATA \"All Mail\" /shared/comment").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("GETMETADATA \"Does not exist\" /shared/comment")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NONEXISTENT");
+
+    // Jane shares a mailbox with John
+    let mut imap_jane = ImapConnection::connect(b"_w ").await;
+    imap_jane
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_jane
+        .send("AUTHENTICATE PLAIN {40+}\r\nAGphbmUuc21pdGhAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_jane.send("CREATE \"Metadata Test\"").await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_jane
+        .send("SETMETADATA \"Metadata Test\" (/shared/comment \"Jane's comment\" /private/comment \"Private\")")
+        .await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_jane
+        .send("SETACL \"Metadata Test\" jdoe@example.com l")
+        .await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // John can't read shared entries without the read right
+    let mailbox_name = "\"Shared Folders/jane.smith@example.com/Metadata Test\"";
+    imap.send(&format!(
+        "GETMETADATA {mailbox_name} (/shared/comment /private/comment)"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* METADATA", 0);
+
+    // Private entries are never visible to other users
+    imap_jane
+        .send("SETACL \"Metadata Test\" jdoe@example.com lr")
+        .await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(&format!(
+        "GETMETADATA {mailbox_name} (/shared/comment /private/comment)"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"/shared/comment\" \"Jane's comment\"")
+        .assert_count("/private/comment", 0);
+    imap.send(&format!(
+        "SETMETADATA {mailbox_name} (/private/comment \"John's comment\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("METADATA NOPRIVATE");
+
+    // Writing shared entries requires the write right
+    imap.send(&format!(
+        "SETMETADATA {mailbox_name} (/shared/comment \"John's comment\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NOPERM");
+    imap_jane
+        .send("SETACL \"Metadata Test\" jdoe@example.com lrw")
+        .await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send(&format!(
+        "SETMETADATA {mailbox_name} (/shared/comment \"John's comment\")"
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_jane
+        .send("GETMETADATA \"Metadata Test\" (/shared/comment /private/comment)")
+        .await;
+    imap_jane
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"/shared/comment\" \"John's comment\"")
+        .assert_contains("\"/private/comment\" \"Private\"");
+
+    imap_jane.send("DELETE \"Metadata Test\"").await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -25,12 +25,16 @@ pub mod acl;
 pub mod append;
 pub mod basic;
 pub mod body_structure;
+pub mod compress;
 pub mod condstore;
 pub mod copy_move;
 pub mod fetch;
 pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
+pub mod metadata;
+pub mod notify;
+pub mod quota;
 pub mod search;
 pub mod store;
 pub mod thread;
@@ -43,6 +47,7 @@ use imap::core::{ImapSessionManager, IMAP};
 use imap_proto::ResponseType;
 use jmap::{api::JmapSessionManager, services::IPC_CHANNEL_BUFFER, JMAP};
 use smtp::core::SMTP;
+use store::Store;
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf},
     net::TcpStream,
@@ -54,6 +59,7 @@ use crate::{
     add_test_certs,
     directory::sql::{
         add_to_group, create_test_directory, create_test_user, create_test_user_with_email,
+        set_test_quota,
     },
     store::TempDir,
 };
@@ -155,6 +161,9 @@ max-size = 5000000
 max-concurrent = 4
 ttl = "1m"
 
+[jmap.email]
+max-messages = 100000
+
 [jmap.protocol.upload.quota]
 files = 3
 size = 50000
@@ -232,15 +241,16 @@ async fn init_imap_tests(delete_if_exists: bool) -> IMAPTest {
     )
     .unwrap();
     let servers = config.parse_servers().unwrap();
-    let directory = config.parse_directory().unwrap();
+    let store = Arc::new(Store::open(&config).await.unwrap());
+    let directory = config.parse_directory_with_store(Some(&store)).unwrap();
 
     // Start JMAP and SMTP servers
     servers.bind(&config);
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &directory, delivery_tx)
+    let smtp = SMTP::init(&config, &servers, &directory, store.clone(), delivery_tx)
         .await
         .failed("Invalid configuration file");
-    let jmap = JMAP::init(&config, &directory, delivery_rx, smtp.clone())
+    let jmap = JMAP::init(&config, &directory, store, delivery_rx, smtp.clone())
         .await
         .failed("Invalid configuration file");
     let imap: Arc<IMAP> = IMAP::init(&config)
@@ -274,6 +284,12 @@ async fn init_imap_tests(delete_if_exists: bool) -> IMAPTest {
         "John Doe",
     )
     .await;
+    set_test_quota(
+        jmap.directory.as_ref(),
+        "jdoe@example.com",
+        50 * 1024 * 1024,
+    )
+    .await;
     create_test_user_with_email(
         jmap.directory.as_ref(),
         "jane.smith@example.com",
@@ -350,6 +366,9 @@ pub async fn imap_tests() {
     idle::test(&mut imap, &mut imap_check).await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    quota::test(&mut imap, &mut imap_check).await;
+    metadata::test(&mut imap, &mut imap_check).await;
+    notify::test(&mut imap, &mut imap_check).await;
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
@@ -360,6 +379,9 @@ pub async fn imap_tests() {
         imap.assert_read(Type::Untagged, ResponseType::Bye).await;
     }
 
+    // Run COMPRESS tests
+    compress::test().await;
+
     // Run ManageSieve tests
     managesieve::test().await;
 
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
+    // NOTIFY should be advertised once authenticated
+    imap_check.send("CAPABILITY").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(" NOTIFY");
+
+    // Unsupported events should be rejected
+    imap_check
+        .send("NOTIFY SET (personal (MessageNew MessageExpunge AnnotationChange))")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("BADEVENT");
+
+    // Select a mailbox and register for notifications
+    imap.send("CREATE Gorgonzola").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("SELECT Gorgonzola").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send(concat!(
+            "NOTIFY SET STATUS (selected (MessageNew (UID) MessageExpunge)) ",
+            "(subtree Mozzarella (MessageNew MessageExpunge MailboxName))"
+        ))
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* STATUS", 0);
+
+    // Expect mailbox name updates
+    imap.send("CREATE Mozzarella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Mozzarella\"");
+    imap.send("CREATE Mozzarella/Burrata").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Mozzarella/Burrata\"");
+    imap.send("DELETE Mozzarella/Burrata").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\NonExistent) \"/\" \"Mozzarella/Burrata\"");
+    imap.send("CREATE Mozzarella/Ricotta").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST () \"/\" \"Mozzarella/Ricotta\"");
+    imap.send("RENAME Mozzarella/Ricotta Mozzarella/Stracciatella")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains(
+            "LIST () \"/\" \"Mozzarella/Stracciatella\" (\"OLDNAME\" (\"Mozzarella/Ricotta\"))",
+        )
+        .assert_count("NonExistent", 0);
+    imap.send("DELETE Mozzarella/Stracciatella").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("LIST (\\NonExistent) \"/\" \"Mozzarella/Stracciatella\"");
+
+    // Expect a status update for a monitored mailbox
+    let message = "From: test@domain.com\nSubject: Test\n\nTest message\n";
+    imap.send(&format!("APPEND Mozzarella {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Mozzarella\"")
+        .assert_contains("MESSAGES 1")
+        .assert_contains("UIDNEXT 2");
+
+    // Expect new messages in the selected mailbox
+    imap.send(&format!("APPEND Gorgonzola {{{}}}", message.len()))
+        .await;
+    imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+    imap.send_untagged(message).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXISTS");
+    imap_check
+        .assert_read(Type::Status, ResponseType::Ok)
+        .await
+        .assert_contains("* 1 FETCH (")
+        .assert_contains("UID 1");
+
+    // Mailboxes outside the monitored set should not produce updates
+    imap_check.send("NOTIFY NONE").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* STATUS", 0);
+    imap_check
+        .send("NOTIFY SET STATUS (mailboxes Mozzarella (MessageNew MessageExpunge))")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("STATUS \"Mozzarella\"")
+        .assert_count("STATUS \"Gorgonzola\"", 0);
+    imap_check.send("NOTIFY NONE").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("UNSELECT").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
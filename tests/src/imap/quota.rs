@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use super::{append::assert_append_message, AssertResult, ImapConnection, Type};
+
+pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    // QUOTA should be advertised once authenticated
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(" QUOTA ")
+        .assert_contains("QUOTA=RES-STORAGE")
+        .assert_contains("QUOTA=RES-MESSAGE");
+
+    // Obtain the quota root of the Inbox
+    imap.send("GETQUOTAROOT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* QUOTAROOT \"INBOX\" \"\"")
+        .assert_contains("* QUOTA \"\" (STORAGE ")
+        .assert_contains(" 51200 MESSAGE ")
+        .assert_contains(" 100000)");
+
+    // Obtain the quota by root name
+    imap.send("GETQUOTA \"\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"\" (STORAGE ")
+        .assert_contains(" 51200 MESSAGE ")
+        .assert_contains(" 100000)");
+    imap.send("GETQUOTA \"Unknown Root\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NONEXISTENT");
+
+    // Quotas are managed by the directory
+    imap.send("SETQUOTA \"\" (STORAGE 512)").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("NOPERM");
+
+    // Unknown mailboxes have no quota root
+    imap.send("GETQUOTAROOT \"Does not exist\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Test DELETED-STORAGE status item
+    imap.send("CREATE \"Quota Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert_append_message(
+        imap,
+        "Quota Test",
+        "From: john\n\ncontents",
+        ResponseType::Ok,
+    )
+    .await;
+    imap.send("STATUS \"Quota Test\" (DELETED DELETED-STORAGE)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("DELETED 0 DELETED-STORAGE 0");
+    imap.send("SELECT \"Quota Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STORE 1 +FLAGS (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS \"Quota Test\" (DELETED DELETED-STORAGE)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("DELETED 1 DELETED-STORAGE 1");
+
+    imap.send("DELETE \"Quota Test\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+}
@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap::{mailbox::INBOX_ID, JMAP};
+use jmap_client::client::Client;
+use jmap_proto::types::id::Id;
+use serde_json::json;
+
+use crate::{
+    directory::sql::create_test_user_with_email,
+    jmap::{jmap_request, mailbox::destroy_all_mailboxes},
+};
+
+pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
+    println!("Running Blob tests...");
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    create_test_user_with_email(directory, "jane.smith@example.com", "abcde", "Jane Smith").await;
+    let account_id = Id::from(server.get_account_id("jdoe@example.com").await.unwrap());
+    let john = ("jdoe@example.com", "12345");
+
+    // Upload blobs from text, base64 and ranges of other blobs
+    let response = jmap_request(
+        john,
+        json!([["Blob/upload", {
+            "accountId": account_id.to_string(),
+            "create": {
+                "b1": {
+                    "data": [{"data:asText": "Hello, "}, {"data:asBase64": "d29ybGQh"}],
+                    "type": "text/plain"
+                },
+                "b2": {
+                    "data": [{"blobId": "#b1", "offset": 7, "length": 6}]
+                },
+                "b3": {
+                    "data": [{"blobId": "#b4"}]
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    let created = &response[0][1]["created"];
+    assert_eq!(created["b1"]["size"], 13, "{response:#?}");
+    assert_eq!(created["b1"]["type"], "text/plain");
+    assert_eq!(created["b2"]["size"], 6, "{response:#?}");
+    assert_eq!(
+        response[0][1]["notCreated"]["b3"]["type"], "notFound",
+        "{response:#?}"
+    );
+    let b1_id = created["b1"]["id"].as_str().unwrap().to_string();
+    let b2_id = created["b2"]["id"].as_str().unwrap().to_string();
+
+    // Fetch contents and digests
+    let response = jmap_request(
+        john,
+        json!([
+            ["Blob/get", {
+                "accountId": account_id.to_string(),
+                "ids": [&b1_id, &b2_id],
+                "properties": ["data:asText", "digest:sha", "digest:sha-256", "size"]
+            }, "0"],
+            ["Blob/get", {
+                "accountId": account_id.to_string(),
+                "ids": [&b1_id],
+                "properties": ["data:asBase64"],
+                "offset": 7,
+                "length": 100
+            }, "1"]
+        ]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list[0]["id"], b1_id, "{response:#?}");
+    assert_eq!(list[0]["data:asText"], "Hello, world!");
+    assert_eq!(list[0]["size"], 13);
+    assert_eq!(list[0]["digest:sha"], "lDpwLQbzRZmu4fjajvn3KWAx1pk=");
+    assert_eq!(
+        list[0]["digest:sha-256"],
+        "MV9b23bQeMQ7isAGTkoBZGErH853yGk0W/yUx1iU7dM="
+    );
+    assert_eq!(list[1]["data:asText"], "world!");
+    assert_eq!(
+        list[1]["digest:sha-256"],
+        "cR6WCTOekrA93AohGCfbpCHzj57YudgG4f/djBX/oD0="
+    );
+    let list = &response[1][1]["list"];
+    assert_eq!(list[0]["data:asBase64"], "d29ybGQh", "{response:#?}");
+    assert_eq!(list[0]["isTruncated"], true);
+
+    // Import a message and look up the objects referencing its blob
+    let inbox_id = Id::new(INBOX_ID as u64).to_string();
+    let response = jmap_request(
+        john,
+        json!([["Blob/upload", {
+            "accountId": account_id.to_string(),
+            "create": {
+                "m1": {
+                    "data": [{"data:asText": concat!(
+                        "From: bill@example.com\r\n",
+                        "To: jdoe@example.com\r\n",
+                        "Subject: TPS Report\r\n",
+                        "\r\n",
+                        "I'm going to need those TPS reports ASAP.\r\n"
+                    )}],
+                    "type": "message/rfc822"
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    let message_blob_id = response[0][1]["created"]["m1"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = jmap_request(
+        john,
+        json!([["Email/import", {
+            "accountId": account_id.to_string(),
+            "emails": {
+                "e1": {
+                    "blobId": message_blob_id,
+                    "mailboxIds": {&inbox_id: true}
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    let email = &response[0][1]["created"]["e1"];
+    let email_id = email["id"].as_str().unwrap().to_string();
+    let thread_id = email["threadId"].as_str().unwrap().to_string();
+    let email_blob_id = email["blobId"].as_str().unwrap().to_string();
+
+    let response = jmap_request(
+        john,
+        json!([
+            ["Blob/lookup", {
+                "accountId": account_id.to_string(),
+                "typeNames": ["Email", "Mailbox", "Thread"],
+                "ids": [&email_blob_id, &b1_id]
+            }, "0"],
+            ["Blob/lookup", {
+                "accountId": account_id.to_string(),
+                "typeNames": ["Identity"],
+                "ids": [&email_blob_id]
+            }, "1"]
+        ]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list[0]["id"], email_blob_id, "{response:#?}");
+    assert_eq!(list[0]["matchedIds"]["Email"], json!([email_id]));
+    assert_eq!(list[0]["matchedIds"]["Mailbox"], json!([inbox_id]));
+    assert_eq!(list[0]["matchedIds"]["Thread"], json!([thread_id]));
+    assert_eq!(list[1]["id"], b1_id);
+    assert_eq!(list[1]["matchedIds"]["Email"], json!([]));
+    assert_eq!(response[1][1]["type"], "unknownDataType", "{response:#?}");
+
+    // Blobs of other accounts are not visible
+    let response = jmap_request(
+        ("jane.smith@example.com", "abcde"),
+        json!([["Blob/get", {
+            "accountId": account_id.to_string(),
+            "ids": [&b1_id]
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(response[0][1]["notFound"], json!([b1_id]), "{response:#?}");
+
+    // Remove test data
+    admin_client.set_default_account_id(account_id.to_string());
+    destroy_all_mailboxes(admin_client).await;
+    server
+        .store
+        .delete_account_blobs(account_id.document_id())
+        .await
+        .unwrap();
+    server.store.assert_is_empty().await;
+}
@@ -0,0 +1,449 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap::JMAP;
+use jmap_client::client::Client;
+use jmap_proto::types::id::Id;
+use serde_json::json;
+
+use crate::{directory::sql::create_test_user_with_email, jmap::jmap_request};
+
+pub async fn test(server: Arc<JMAP>, _admin_client: &mut Client) {
+    println!("Running Calendar tests...");
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    let john_id = Id::from(server.get_account_id("jdoe@example.com").await.unwrap()).to_string();
+    let john = ("jdoe@example.com", "12345");
+
+    // A default calendar should be created on first access
+    let response = jmap_request(john, json!([["Calendar/get", {"accountId": john_id}, "0"]])).await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list.as_array().unwrap().len(), 1, "{response:#?}");
+    assert_eq!(list[0]["name"], "Calendar");
+    assert_eq!(list[0]["isDefault"], true);
+    assert_eq!(list[0]["isVisible"], true);
+    assert_eq!(list[0]["myRights"]["mayAdmin"], true);
+    let default_calendar_id = list[0]["id"].as_str().unwrap().to_string();
+
+    // Create a calendar, a weekly recurring event and a single event
+    let response = jmap_request(
+        john,
+        json!([
+            ["Calendar/set", {
+                "accountId": john_id,
+                "create": {"c1": {"name": "Work", "color": "#ff0000"}}
+            }, "0"],
+            ["CalendarEvent/set", {
+                "accountId": john_id,
+                "create": {
+                    "e1": {
+                        "calendarIds": {"#c1": true},
+                        "title": "Weekly sync",
+                        "start": "2023-06-05T10:00:00",
+                        "duration": "PT1H",
+                        "recurrenceRules": [{
+                            "@type": "RecurrenceRule",
+                            "frequency": "weekly",
+                            "byDay": [{"day": "mo"}, {"day": "th"}],
+                            "count": 6
+                        }],
+                        "recurrenceOverrides": {
+                            "2023-06-12T10:00:00": {"excluded": true}
+                        }
+                    },
+                    "e2": {
+                        "calendarIds": {default_calendar_id.clone(): true},
+                        "title": "Dentist appointment",
+                        "description": "Bring insurance card",
+                        "start": "2023-06-07T15:30:00",
+                        "duration": "PT45M"
+                    },
+                    "e3": {
+                        "calendarIds": {default_calendar_id.clone(): true},
+                        "title": "No start date"
+                    },
+                    "e4": {
+                        "title": "No calendar",
+                        "start": "2023-06-07T15:30:00"
+                    }
+                }
+            }, "1"]
+        ]),
+    )
+    .await;
+    let work_calendar_id = response[0][1]["created"]["c1"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let created = &response[1][1]["created"];
+    let event1_id = created["e1"]["id"].as_str().unwrap().to_string();
+    let event2_id = created["e2"]["id"].as_str().unwrap().to_string();
+    let not_created = &response[1][1]["notCreated"];
+    assert_eq!(
+        not_created["e3"]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+    assert_eq!(
+        not_created["e4"]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+
+    // Fetch events and make sure defaults were added
+    let response = jmap_request(
+        john,
+        json!([["CalendarEvent/get", {"accountId": john_id, "ids": [event1_id, event2_id]}, "0"]]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list[0]["@type"], "Event", "{response:#?}");
+    assert!(!list[0]["uid"].as_str().unwrap().is_empty());
+    assert_eq!(list[0]["calendarIds"][&work_calendar_id], true);
+    assert_eq!(list[1]["calendarIds"][&default_calendar_id], true);
+    assert!(list[0].get("fromDate").is_none());
+
+    // Query events
+    for (filter, expected) in [
+        (json!({"text": "dentist"}), vec![event2_id.as_str()]),
+        (json!({"title": "sync"}), vec![event1_id.as_str()]),
+        (
+            json!({"description": "insurance"}),
+            vec![event2_id.as_str()],
+        ),
+        (
+            json!({"inCalendars": [work_calendar_id, default_calendar_id]}),
+            vec![event1_id.as_str(), event2_id.as_str()],
+        ),
+        (
+            json!({"after": "2023-06-08T00:00:00Z"}),
+            vec![event1_id.as_str()],
+        ),
+        (
+            json!({"before": "2023-06-06T00:00:00Z"}),
+            vec![event1_id.as_str()],
+        ),
+        (json!({"after": "2023-06-23T00:00:00Z"}), vec![]),
+    ] {
+        let response = jmap_request(
+            john,
+            json!([["CalendarEvent/query", {
+                "accountId": john_id,
+                "filter": filter,
+                "sort": [{"property": "start"}]
+            }, "0"]]),
+        )
+        .await;
+        assert_eq!(
+            response[0][1]["ids"],
+            json!(expected),
+            "{filter:?} {response:#?}"
+        );
+    }
+
+    // Expand recurrences, the excluded occurrence should not be returned
+    let response = jmap_request(
+        john,
+        json!([
+            ["CalendarEvent/query", {
+                "accountId": john_id,
+                "filter": {
+                    "after": "2023-06-01T00:00:00Z",
+                    "before": "2023-07-01T00:00:00Z"
+                },
+                "sort": [{"property": "start"}],
+                "expandRecurrences": true
+            }, "0"],
+            ["CalendarEvent/get", {
+                "accountId": john_id,
+                "#ids": {"resultOf": "0", "name": "CalendarEvent/query", "path": "/ids"},
+                "properties": ["id", "title", "start", "recurrenceId", "recurrenceRules"]
+            }, "1"]
+        ]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["ids"].as_array().unwrap().len(),
+        6,
+        "{response:#?}"
+    );
+    let starts = response[1][1]["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["start"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        starts,
+        [
+            "2023-06-05T10:00:00",
+            "2023-06-07T15:30:00",
+            "2023-06-08T10:00:00",
+            "2023-06-15T10:00:00",
+            "2023-06-19T10:00:00",
+            "2023-06-22T10:00:00"
+        ],
+        "{response:#?}"
+    );
+    let occurrence = &response[1][1]["list"][3];
+    assert_eq!(occurrence["title"], "Weekly sync");
+    assert_eq!(occurrence["recurrenceId"], "2023-06-15T10:00:00");
+    assert!(occurrence.get("recurrenceRules").is_none());
+
+    // Expanding recurrences requires a date range
+    let response = jmap_request(
+        john,
+        json!([["CalendarEvent/query", {
+            "accountId": john_id,
+            "expandRecurrences": true
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(response[0][1]["type"], "invalidArguments", "{response:#?}");
+
+    // Occurrences cannot be modified directly
+    let occurrence_id = occurrence["id"].as_str().unwrap().to_string();
+    let response = jmap_request(
+        john,
+        json!([
+            ["CalendarEvent/set", {
+                "accountId": john_id,
+                "update": {occurrence_id.clone(): {"title": "Changed"}}
+            }, "0"],
+            ["CalendarEvent/set", {
+                "accountId": john_id,
+                "update": {
+                    event1_id.clone(): {
+                        "recurrenceOverrides/2023-06-15T10:00:00": {"title": "Planning"}
+                    }
+                }
+            }, "1"],
+            ["CalendarEvent/get", {
+                "accountId": john_id,
+                "ids": [occurrence_id],
+                "properties": ["title"]
+            }, "2"]
+        ]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["notUpdated"][&occurrence_id]["type"], "forbidden",
+        "{response:#?}"
+    );
+    assert!(
+        response[1][1]["updated"].get(&event1_id).is_some(),
+        "{response:#?}"
+    );
+    assert_eq!(response[2][1]["list"][0]["title"], "Planning");
+
+    // Occurrences far from the start are expanded and keep their ids
+    let response = jmap_request(
+        john,
+        json!([
+            ["CalendarEvent/set", {
+                "accountId": john_id,
+                "create": {
+                    "e5": {
+                        "calendarIds": {work_calendar_id.clone(): true},
+                        "title": "Daily standup",
+                        "start": "2020-01-01T09:00:00",
+                        "duration": "PT15M",
+                        "recurrenceRules": [{
+                            "@type": "RecurrenceRule",
+                            "frequency": "daily"
+                        }]
+                    }
+                }
+            }, "0"],
+            ["CalendarEvent/query", {
+                "accountId": john_id,
+                "filter": {
+                    "after": "2023-06-14T00:00:00Z",
+                    "before": "2023-06-16T00:00:00Z"
+                },
+                "sort": [{"property": "start"}],
+                "expandRecurrences": true
+            }, "1"],
+            ["CalendarEvent/get", {
+                "accountId": john_id,
+                "#ids": {"resultOf": "1", "name": "CalendarEvent/query", "path": "/ids"},
+                "properties": ["id", "title", "start"]
+            }, "2"]
+        ]),
+    )
+    .await;
+    let list = response[2][1]["list"].as_array().unwrap();
+    assert_eq!(
+        list.iter()
+            .map(|event| (
+                event["title"].as_str().unwrap(),
+                event["start"].as_str().unwrap()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("Daily standup", "2023-06-14T09:00:00"),
+            ("Daily standup", "2023-06-15T09:00:00"),
+            ("Planning", "2023-06-15T10:00:00")
+        ],
+        "{response:#?}"
+    );
+    assert_eq!(list[2]["id"], occurrence_id, "{response:#?}");
+
+    // Calendars with events cannot be deleted unless requested
+    let response = jmap_request(
+        john,
+        json!([["Calendar/set", {
+            "accountId": john_id,
+            "destroy": [work_calendar_id]
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["notDestroyed"][&work_calendar_id]["type"], "calendarHasEvent",
+        "{response:#?}"
+    );
+    let response = jmap_request(
+        john,
+        json!([
+            ["Calendar/set", {
+                "accountId": john_id,
+                "destroy": [work_calendar_id],
+                "onDestroyRemoveEvents": true
+            }, "0"],
+            ["CalendarEvent/get", {"accountId": john_id, "properties": ["id"]}, "1"]
+        ]),
+    )
+    .await;
+    assert_eq!(response[0][1]["destroyed"], json!([work_calendar_id]));
+    assert_eq!(
+        response[1][1]["list"],
+        json!([{"id": event2_id}]),
+        "{response:#?}"
+    );
+
+    // Replies are only applied when the sender is authenticated
+    let account_id = server.get_account_id("jdoe@example.com").await.unwrap();
+    assert!(server
+        .calendar_event_ingest_imip(
+            account_id,
+            "jdoe@example.com",
+            true,
+            imip_message("REQUEST", "NEEDS-ACTION").as_bytes(),
+        )
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        server
+            .calendar_event_ingest_imip(
+                account_id,
+                "bob@example.org",
+                false,
+                imip_message("REPLY", "ACCEPTED").as_bytes(),
+            )
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(bob_status(john, &john_id).await, "needs-action");
+    assert!(server
+        .calendar_event_ingest_imip(
+            account_id,
+            "bob@example.org",
+            true,
+            imip_message("REPLY", "ACCEPTED").as_bytes(),
+        )
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(bob_status(john, &john_id).await, "accepted");
+
+    // Remove test data
+    let response = jmap_request(
+        ("admin", "secret"),
+        json!([
+            ["Calendar/get", {"accountId": john_id, "properties": ["id"]}, "0"],
+            ["Calendar/set", {
+                "accountId": john_id,
+                "#destroy": {"resultOf": "0", "name": "Calendar/get", "path": "/list/*/id"},
+                "onDestroyRemoveEvents": true
+            }, "1"]
+        ]),
+    )
+    .await;
+    assert_eq!(
+        response[1][1]["destroyed"].as_array().unwrap().len(),
+        1,
+        "{response:#?}"
+    );
+    server.store.assert_is_empty().await;
+}
+
+fn imip_message(method: &str, status: &str) -> String {
+    format!(
+        concat!(
+            "From: sender@example.org\r\n",
+            "Subject: Design review\r\n",
+            "Content-Type: text/calendar; method={method}\r\n\r\n",
+            "BEGIN:VCALENDAR\r\n",
+            "VERSION:2.0\r\n",
+            "METHOD:{method}\r\n",
+            "BEGIN:VEVENT\r\n",
+            "UID:design-review@example.com\r\n",
+            "DTSTART:20230620T140000Z\r\n",
+            "SUMMARY:Design review\r\n",
+            "ORGANIZER:mailto:jdoe@example.com\r\n",
+            "ATTENDEE;PARTSTAT={status}:mailto:bob@example.org\r\n",
+            "END:VEVENT\r\n",
+            "END:VCALENDAR\r\n"
+        ),
+        method = method,
+        status = status
+    )
+}
+
+async fn bob_status(login: (&str, &str), account_id: &str) -> String {
+    let response = jmap_request(
+        login,
+        json!([
+            ["CalendarEvent/query", {
+                "accountId": account_id,
+                "filter": {"title": "Design review"}
+            }, "0"],
+            ["CalendarEvent/get", {
+                "accountId": account_id,
+                "#ids": {"resultOf": "0", "name": "CalendarEvent/query", "path": "/ids"},
+                "properties": ["participants"]
+            }, "1"]
+        ]),
+    )
+    .await;
+    response[1][1]["list"][0]["participants"]
+        .as_object()
+        .unwrap()
+        .values()
+        .find(|participant| participant["email"] == "bob@example.org")
+        .and_then(|participant| participant["participationStatus"].as_str())
+        .unwrap_or_else(|| panic!("{response:#?}"))
+        .to_string()
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap::JMAP;
+use jmap_client::client::Client;
+use jmap_proto::types::id::Id;
+use serde_json::json;
+
+use crate::{directory::sql::create_test_user_with_email, jmap::jmap_request};
+
+pub async fn test(server: Arc<JMAP>, _admin_client: &mut Client) {
+    println!("Running Contacts tests...");
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    create_test_user_with_email(directory, "jane.smith@example.com", "abcde", "Jane Smith").await;
+    let john_id = Id::from(server.get_account_id("jdoe@example.com").await.unwrap()).to_string();
+    let jane_id = Id::from(
+        server
+            .get_account_id("jane.smith@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let john = ("jdoe@example.com", "12345");
+    let jane = ("jane.smith@example.com", "abcde");
+
+    // A default address book should be created on first access
+    let response = jmap_request(
+        john,
+        json!([["AddressBook/get", {"accountId": john_id}, "0"]]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list.as_array().unwrap().len(), 1, "{response:#?}");
+    assert_eq!(list[0]["name"], "Personal");
+    assert_eq!(list[0]["isDefault"], true);
+    assert_eq!(list[0]["myRights"]["mayDelete"], true);
+    let default_book_id = list[0]["id"].as_str().unwrap().to_string();
+
+    // Create an address book and two cards
+    let response = jmap_request(
+        john,
+        json!([
+            ["AddressBook/set", {
+                "accountId": john_id,
+                "create": {"b1": {"name": "Work", "sortOrder": 1}}
+            }, "0"],
+            ["ContactCard/set", {
+                "accountId": john_id,
+                "create": {
+                    "c1": {
+                        "addressBookIds": {"#b1": true},
+                        "name": {"full": "Jane Smith"},
+                        "emails": {"e1": {"address": "Jane.Smith@Example.com"}},
+                        "phones": {"p1": {"number": "+1-555-0100"}}
+                    },
+                    "c2": {
+                        "uid": "urn:uuid:a1b2c3",
+                        "addressBookIds": {"#b1": true, default_book_id.clone(): true},
+                        "name": {"components": [
+                            {"kind": "given", "value": "Bill"},
+                            {"kind": "surname", "value": "Foobar"}
+                        ]}
+                    },
+                    "c3": {
+                        "addressBookIds": {default_book_id.clone(): true},
+                        "email": ["forbidden@example.com"]
+                    },
+                    "c4": {
+                        "name": {"full": "No Address Book"}
+                    }
+                }
+            }, "1"]
+        ]),
+    )
+    .await;
+    let work_book_id = response[0][1]["created"]["b1"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let created = &response[1][1]["created"];
+    let card1_id = created["c1"]["id"].as_str().unwrap().to_string();
+    let card2_id = created["c2"]["id"].as_str().unwrap().to_string();
+    let not_created = &response[1][1]["notCreated"];
+    assert_eq!(
+        not_created["c3"]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+    assert_eq!(
+        not_created["c4"]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+
+    // Fetch cards and make sure defaults were added
+    let response = jmap_request(
+        john,
+        json!([["ContactCard/get", {"accountId": john_id, "ids": [card1_id, card2_id]}, "0"]]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list[0]["@type"], "Card", "{response:#?}");
+    assert_eq!(list[0]["version"], "1.0");
+    assert!(list[0]["uid"].as_str().unwrap().starts_with("urn:uuid:"));
+    assert_eq!(list[0]["emails"]["e1"]["address"], "Jane.Smith@Example.com");
+    assert_eq!(list[0]["addressBookIds"][&work_book_id], true);
+    assert!(list[0].get("email").is_none());
+    assert!(list[0].get("fullName").is_none());
+    assert_eq!(list[1]["uid"], "urn:uuid:a1b2c3");
+    assert_eq!(list[1]["addressBookIds"][&default_book_id], true);
+
+    // Query cards
+    for (filter, expected) in [
+        (json!({"text": "jane"}), vec![card1_id.as_str()]),
+        (
+            json!({"email": "jane.smith@example.com"}),
+            vec![card1_id.as_str()],
+        ),
+        (json!({"name": "foobar"}), vec![card2_id.as_str()]),
+        (json!({"uid": "urn:uuid:a1b2c3"}), vec![card2_id.as_str()]),
+        (
+            json!({"inAddressBook": work_book_id}),
+            vec![card2_id.as_str(), card1_id.as_str()],
+        ),
+        (
+            json!({"inAddressBook": default_book_id}),
+            vec![card2_id.as_str()],
+        ),
+    ] {
+        let response = jmap_request(
+            john,
+            json!([["ContactCard/query", {
+                "accountId": john_id,
+                "filter": filter,
+                "sort": [{"property": "name"}]
+            }, "0"]]),
+        )
+        .await;
+        assert_eq!(
+            response[0][1]["ids"],
+            json!(expected),
+            "{filter:?} {response:#?}"
+        );
+    }
+
+    // The uid of a card cannot be changed
+    let response = jmap_request(
+        john,
+        json!([["ContactCard/set", {
+            "accountId": john_id,
+            "update": {card2_id.clone(): {"uid": "urn:uuid:changed"}}
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["notUpdated"][&card2_id]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+
+    // Update card using JSON patches
+    let response = jmap_request(
+        john,
+        json!([
+            ["ContactCard/set", {
+                "accountId": john_id,
+                "update": {
+                    card1_id.clone(): {
+                        "name/full": "Jane Doe",
+                        "emails/e1/address": "jane@example.org",
+                        "phones/p1": null,
+                        format!("addressBookIds/{default_book_id}"): true
+                    }
+                }
+            }, "0"],
+            ["ContactCard/get", {"accountId": john_id, "ids": [card1_id]}, "1"],
+            ["ContactCard/query", {
+                "accountId": john_id,
+                "filter": {"email": "jane@example.org"}
+            }, "2"]
+        ]),
+    )
+    .await;
+    assert!(
+        response[0][1]["updated"].get(&card1_id).is_some(),
+        "{response:#?}"
+    );
+    let card = &response[1][1]["list"][0];
+    assert_eq!(card["name"]["full"], "Jane Doe");
+    assert_eq!(card["emails"]["e1"]["address"], "jane@example.org");
+    assert!(card["phones"].get("p1").is_none());
+    assert_eq!(card["addressBookIds"][&default_book_id], true);
+    assert_eq!(response[2][1]["ids"], json!([card1_id]));
+
+    // Share the work address book with Jane
+    let response = jmap_request(
+        john,
+        json!([["AddressBook/set", {
+            "accountId": john_id,
+            "update": {
+                work_book_id.clone(): {
+                    "acl": {"jane.smith@example.com": ["read", "readItems"]}
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    assert!(
+        response[0][1]["updated"].get(&work_book_id).is_some(),
+        "{response:#?}"
+    );
+
+    // Jane should be able to read but not modify the shared cards
+    let response = jmap_request(
+        jane,
+        json!([
+            ["AddressBook/get", {"accountId": john_id}, "0"],
+            ["ContactCard/query", {"accountId": john_id}, "1"],
+            ["ContactCard/set", {
+                "accountId": john_id,
+                "create": {"c1": {"addressBookIds": {work_book_id.clone(): true}}},
+                "update": {card2_id.clone(): {"name/full": "Bill Smith"}}
+            }, "2"]
+        ]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list.as_array().unwrap().len(), 1, "{response:#?}");
+    assert_eq!(list[0]["id"], work_book_id);
+    assert_eq!(list[0]["myRights"]["mayRead"], true);
+    assert_eq!(list[0]["myRights"]["mayWrite"], false);
+    assert_eq!(response[1][1]["ids"].as_array().unwrap().len(), 2);
+    assert_eq!(response[2][1]["notCreated"]["c1"]["type"], "forbidden");
+    assert_eq!(response[2][1]["notUpdated"][&card2_id]["type"], "forbidden");
+    let response = jmap_request(
+        jane,
+        json!([["AddressBook/get", {"accountId": jane_id}, "0"]]),
+    )
+    .await;
+    assert_eq!(response[0][1]["list"][0]["name"], "Personal");
+
+    // Address books with contents cannot be deleted unless requested
+    let response = jmap_request(
+        john,
+        json!([["AddressBook/set", {
+            "accountId": john_id,
+            "destroy": [work_book_id, default_book_id]
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["notDestroyed"][&work_book_id]["type"], "addressBookHasContents",
+        "{response:#?}"
+    );
+    assert_eq!(
+        response[0][1]["notDestroyed"][&default_book_id]["type"], "forbidden",
+        "{response:#?}"
+    );
+    let response = jmap_request(
+        john,
+        json!([
+            ["AddressBook/set", {
+                "accountId": john_id,
+                "destroy": [work_book_id],
+                "onDestroyRemoveContents": true
+            }, "0"],
+            ["ContactCard/get", {"accountId": john_id}, "1"]
+        ]),
+    )
+    .await;
+    assert_eq!(response[0][1]["destroyed"], json!([work_book_id]));
+    let list = response[1][1]["list"].as_array().unwrap();
+    assert_eq!(list.len(), 2, "{response:#?}");
+    for card in list {
+        assert_eq!(
+            card["addressBookIds"],
+            json!({default_book_id.clone(): true})
+        );
+    }
+
+    // Remove test data
+    let response = jmap_request(
+        john,
+        json!([["ContactCard/set", {
+            "accountId": john_id,
+            "destroy": [card1_id, card2_id]
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["destroyed"].as_array().unwrap().len(),
+        2,
+        "{response:#?}"
+    );
+    let admin = ("admin", "secret");
+    for account_id in [john_id, jane_id] {
+        let response = jmap_request(
+            admin,
+            json!([
+                ["AddressBook/get", {"accountId": account_id, "properties": ["id"]}, "0"],
+                ["AddressBook/set", {
+                    "accountId": account_id,
+                    "#destroy": {"resultOf": "0", "name": "AddressBook/get", "path": "/list/*/id"},
+                    "onDestroyRemoveContents": true
+                }, "1"]
+            ]),
+        )
+        .await;
+        assert_eq!(
+            response[1][1]["destroyed"].as_array().unwrap().len(),
+            1,
+            "{response:#?}"
+        );
+    }
+    server.store.assert_is_empty().await;
+}
@@ -0,0 +1,380 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use jmap::JMAP;
+use jmap_client::client::Client;
+use reqwest::{header::HeaderMap, redirect::Policy, Method};
+
+use crate::directory::sql::create_test_user_with_email;
+
+const EVENT: &str = "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Example Corp//Test Client//EN
+BEGIN:VEVENT
+UID:dav-event-1
+DTSTAMP:20230601T080000Z
+DTSTART:20230610T090000Z
+DTEND:20230610T100000Z
+SUMMARY:Team meeting
+DESCRIPTION:Quarterly planning
+END:VEVENT
+END:VCALENDAR
+";
+
+const CARD: &str = "BEGIN:VCARD
+VERSION:4.0
+UID:dav-card-1
+FN:Jane Smith
+N:Smith;Jane;;;
+EMAIL;TYPE=work:jane.smith@example.com
+TEL;TYPE=cell:+1-555-555-5555
+END:VCARD
+";
+
+pub async fn test(server: Arc<JMAP>, _admin_client: &mut Client) {
+    println!("Running CalDAV/CardDAV tests...");
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    create_test_user_with_email(directory, "jane.smith@example.com", "abcde", "Jane Smith").await;
+    let john = Some(("jdoe@example.com", "12345"));
+    let jane = Some(("jane.smith@example.com", "abcde"));
+
+    // Service discovery
+    let (status, headers, _) = dav_request(None, "GET", "/.well-known/caldav", &[], "").await;
+    assert_eq!(status, 301);
+    assert_eq!(headers["location"], "/dav/");
+    let (status, _, _) = dav_request(None, "PROPFIND", "/dav/", &[], "").await;
+    assert_eq!(status, 401);
+    let (status, headers, _) = dav_request(john, "OPTIONS", "/dav/", &[], "").await;
+    assert_eq!(status, 200);
+    let dav = headers["dav"].to_str().unwrap();
+    assert!(dav.contains("calendar-access") && dav.contains("addressbook"));
+
+    // Obtain the principal and its home sets
+    let (status, _, body) = dav_request(
+        john,
+        "PROPFIND",
+        "/dav/",
+        &[("Depth", "0")],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <D:propfind xmlns:D="DAV:"><D:prop><D:current-user-principal/></D:prop></D:propfind>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    assert!(
+        body.contains("<D:href>/dav/principals/jdoe@example.com/</D:href>"),
+        "{body}"
+    );
+    let (status, _, body) = dav_request(
+        john,
+        "PROPFIND",
+        "/dav/principals/jdoe@example.com/",
+        &[("Depth", "0")],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" 
+                    xmlns:CR="urn:ietf:params:xml:ns:carddav">
+          <D:prop>
+            <C:calendar-home-set/>
+            <CR:addressbook-home-set/>
+            <C:calendar-user-address-set/>
+            <D:quota-used-bytes/>
+          </D:prop>
+        </D:propfind>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    for expected in [
+        "<D:href>/dav/calendars/jdoe@example.com/</D:href>",
+        "<D:href>/dav/addressbooks/jdoe@example.com/</D:href>",
+        "<D:href>mailto:jdoe@example.com</D:href>",
+        "HTTP/1.1 404 Not Found",
+    ] {
+        assert!(body.contains(expected), "{expected} not found in {body}");
+    }
+
+    // List the calendars and address books of the account
+    let calendar_href = collection_href(john, "/dav/calendars/jdoe@example.com/").await;
+    let addressbook_href = collection_href(john, "/dav/addressbooks/jdoe@example.com/").await;
+
+    // Create an event
+    let event_href = format!("{calendar_href}dav-event-1.ics");
+    let (status, headers, body) = dav_request(
+        john,
+        "PUT",
+        &event_href,
+        &[("If-None-Match", "*"), ("Content-Type", "text/calendar")],
+        EVENT,
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let etag = headers["etag"].to_str().unwrap().to_string();
+    let (status, _, _) = dav_request(
+        john,
+        "PUT",
+        &event_href,
+        &[("If-None-Match", "*"), ("Content-Type", "text/calendar")],
+        EVENT,
+    )
+    .await;
+    assert_eq!(status, 412);
+
+    // The name of the resource has to match the UID
+    let (status, _, _) = dav_request(
+        john,
+        "PUT",
+        &format!("{calendar_href}other-name.ics"),
+        &[("Content-Type", "text/calendar")],
+        EVENT,
+    )
+    .await;
+    assert_eq!(status, 409);
+
+    // Fetch the event
+    let (status, headers, body) = dav_request(john, "GET", &event_href, &[], "").await;
+    assert_eq!(status, 200);
+    assert_eq!(headers["etag"], etag.as_str());
+    assert!(
+        body.contains("UID:dav-event-1") && body.contains("SUMMARY:Team meeting"),
+        "{body}"
+    );
+
+    // Initial synchronization
+    let (status, _, body) = sync_collection(john, &calendar_href, "").await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&event_href), "{body}");
+    assert!(body.contains(&etag), "{body}");
+    let sync_token = sync_token(&body);
+
+    // Update the event using the wrong and then the right ETag
+    let updated_event = EVENT.replace("Team meeting", "Team lunch");
+    let (status, _, _) = dav_request(
+        john,
+        "PUT",
+        &event_href,
+        &[("If-Match", "\"1234\""), ("Content-Type", "text/calendar")],
+        &updated_event,
+    )
+    .await;
+    assert_eq!(status, 412);
+    let (status, headers, body) = dav_request(
+        john,
+        "PUT",
+        &event_href,
+        &[("If-Match", &etag), ("Content-Type", "text/calendar")],
+        &updated_event,
+    )
+    .await;
+    assert_eq!(status, 204, "{body}");
+    let new_etag = headers["etag"].to_str().unwrap().to_string();
+    assert_ne!(etag, new_etag);
+
+    // Only the modified event should be reported since the last sync
+    let (status, _, body) = sync_collection(john, &calendar_href, &sync_token).await;
+    assert_eq!(status, 207, "{body}");
+    assert!(body.contains(&event_href), "{body}");
+    assert!(body.contains(&new_etag), "{body}");
+    assert_ne!(sync_token, self::sync_token(&body));
+    let sync_token = self::sync_token(&body);
+    let (status, _, body) = sync_collection(john, &calendar_href, &sync_token).await;
+    assert_eq!(status, 207, "{body}");
+    assert!(!body.contains(&event_href), "{body}");
+
+    // Query by time range
+    for (start, end, expect_match) in [
+        ("20230610T000000Z", "20230611T000000Z", true),
+        ("20230601T000000Z", "20230602T000000Z", false),
+    ] {
+        let (status, _, body) = dav_request(
+            john,
+            "REPORT",
+            &calendar_href,
+            &[("Depth", "1")],
+            &format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+                <C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+                  <D:prop><D:getetag/><C:calendar-data/></D:prop>
+                  <C:filter>
+                    <C:comp-filter name="VCALENDAR">
+                      <C:comp-filter name="VEVENT">
+                        <C:time-range start="{start}" end="{end}"/>
+                      </C:comp-filter>
+                    </C:comp-filter>
+                  </C:filter>
+                </C:calendar-query>"#
+            ),
+        )
+        .await;
+        assert_eq!(status, 207, "{body}");
+        assert_eq!(body.contains("SUMMARY:Team lunch"), expect_match, "{body}");
+    }
+
+    // Create a contact and fetch it using a multiget report
+    let card_href = format!("{addressbook_href}dav-card-1.vcf");
+    let (status, _, body) = dav_request(
+        john,
+        "PUT",
+        &card_href,
+        &[("Content-Type", "text/vcard")],
+        CARD,
+    )
+    .await;
+    assert_eq!(status, 201, "{body}");
+    let missing_href = format!("{addressbook_href}missing.vcf");
+    let (status, _, body) = dav_request(
+        john,
+        "REPORT",
+        &addressbook_href,
+        &[("Depth", "1")],
+        &format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <CR:addressbook-multiget xmlns:D="DAV:" xmlns:CR="urn:ietf:params:xml:ns:carddav">
+              <D:prop><D:getetag/><CR:address-data/></D:prop>
+              <D:href>{card_href}</D:href>
+              <D:href>{missing_href}</D:href>
+            </CR:addressbook-multiget>"#
+        ),
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    for expected in [
+        "FN:Jane Smith",
+        "jane.smith@example.com",
+        "UID:dav-card-1",
+        &format!("<D:href>{missing_href}</D:href><D:status>HTTP/1.1 404 Not Found</D:status>"),
+    ] {
+        assert!(body.contains(expected), "{expected} not found in {body}");
+    }
+
+    // Delete the event, the sync token should no longer be valid
+    let (status, _, _) = dav_request(john, "DELETE", &event_href, &[("If-Match", &etag)], "").await;
+    assert_eq!(status, 412);
+    let (status, _, _) = dav_request(john, "DELETE", &event_href, &[], "").await;
+    assert_eq!(status, 204);
+    let (status, _, _) = dav_request(john, "GET", &event_href, &[], "").await;
+    assert_eq!(status, 404);
+    let (status, _, body) = sync_collection(john, &calendar_href, &sync_token).await;
+    assert_eq!(status, 403, "{body}");
+    assert!(body.contains("valid-sync-token"), "{body}");
+
+    // Other accounts are not accessible
+    let (status, _, _) = dav_request(
+        jane,
+        "PROPFIND",
+        "/dav/calendars/jdoe@example.com/",
+        &[("Depth", "1")],
+        "",
+    )
+    .await;
+    assert_eq!(status, 403);
+
+    // Remove test data
+    for href in [&calendar_href, &addressbook_href] {
+        let (status, _, body) = dav_request(john, "DELETE", href, &[], "").await;
+        assert_eq!(status, 204, "{body}");
+    }
+    server.store.assert_is_empty().await;
+}
+
+async fn collection_href(credentials: Option<(&str, &str)>, home_href: &str) -> String {
+    let (status, _, body) = dav_request(
+        credentials,
+        "PROPFIND",
+        home_href,
+        &[("Depth", "1")],
+        r#"<?xml version="1.0" encoding="utf-8"?>
+        <D:propfind xmlns:D="DAV:"><D:prop><D:resourcetype/><D:displayname/></D:prop></D:propfind>"#,
+    )
+    .await;
+    assert_eq!(status, 207, "{body}");
+    body.split("<D:href>")
+        .skip(1)
+        .filter_map(|href| href.split_once("</D:href>"))
+        .map(|(href, _)| href)
+        .find(|href| href.len() > home_href.len() && href.starts_with(home_href))
+        .unwrap_or_else(|| panic!("No collections found in {body}"))
+        .to_string()
+}
+
+async fn sync_collection(
+    credentials: Option<(&str, &str)>,
+    href: &str,
+    sync_token: &str,
+) -> (u16, HeaderMap, String) {
+    dav_request(
+        credentials,
+        "REPORT",
+        href,
+        &[],
+        &format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <D:sync-collection xmlns:D="DAV:">
+              <D:sync-token>{sync_token}</D:sync-token>
+              <D:sync-level>1</D:sync-level>
+              <D:prop><D:getetag/></D:prop>
+            </D:sync-collection>"#
+        ),
+    )
+    .await
+}
+
+fn sync_token(body: &str) -> String {
+    body.split_once("<D:sync-token>")
+        .and_then(|(_, token)| token.split_once("</D:sync-token>"))
+        .unwrap_or_else(|| panic!("No sync token found in {body}"))
+        .0
+        .to_string()
+}
+
+async fn dav_request(
+    credentials: Option<(&str, &str)>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (u16, HeaderMap, String) {
+    let mut request = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .redirect(Policy::none())
+        .build()
+        .unwrap_or_default()
+        .request(
+            Method::from_bytes(method.as_bytes()).unwrap(),
+            format!("https://127.0.0.1:8899{path}"),
+        );
+    if let Some((login, secret)) = credentials {
+        request = request.basic_auth(login, Some(secret));
+    }
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.body(body.to_string()).send().await.unwrap();
+
+    (
+        response.status().as_u16(),
+        response.headers().clone(),
+        response.text().await.unwrap(),
+    )
+}
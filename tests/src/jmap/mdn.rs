@@ -0,0 +1,252 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Instant};
+
+use jmap::JMAP;
+use jmap_client::{client::Client, mailbox::Role};
+use jmap_proto::types::id::Id;
+use serde_json::json;
+
+use crate::{
+    directory::sql::create_test_user_with_email,
+    jmap::{
+        email_set::assert_email_properties,
+        email_submission::{expect_message_delivery, spawn_mock_smtp_server},
+        jmap_request,
+        mailbox::destroy_all_mailboxes,
+    },
+};
+
+pub async fn test(server: Arc<JMAP>, client: &mut Client) {
+    println!("Running MDN tests...");
+
+    // Create test account
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    let account_id = Id::from(server.get_account_id("jdoe@example.com").await.unwrap());
+    client.set_default_account_id(account_id.to_string());
+    let john = ("jdoe@example.com", "12345");
+
+    // Start mock SMTP server
+    let (mut smtp_rx, smtp_settings) = spawn_mock_smtp_server();
+    server.smtp.resolvers.dns.ipv4_add(
+        "localhost",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + std::time::Duration::from_secs(10),
+    );
+
+    // Create an identity and import a message requesting a read receipt
+    let identity_id = client
+        .identity_create("John Doe", "jdoe@example.com")
+        .await
+        .unwrap()
+        .take_id();
+    let mailbox_id = client
+        .mailbox_create("JMAP MDN", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let email_id = client
+        .email_import(
+            concat!(
+                "From: bill@remote.org\r\n",
+                "To: jdoe@example.com\r\n",
+                "Subject: TPS Report\r\n",
+                "Message-ID: <tps-report-1@remote.org>\r\n",
+                "Disposition-Notification-To: Bill Lumbergh <bill@remote.org>\r\n",
+                "\r\n",
+                "I'm going to need those TPS reports ASAP.\r\n"
+            )
+            .as_bytes()
+            .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let email_no_mdn_id = client
+        .email_import(
+            concat!(
+                "From: bill@remote.org\r\n",
+                "To: jdoe@example.com\r\n",
+                "Subject: TPS Report -- friendly reminder\r\n",
+                "\r\n",
+                "Did you get the memo?\r\n"
+            )
+            .as_bytes()
+            .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+
+    // Send an MDN and update the email on success
+    smtp_settings.lock().do_stop = true;
+    let response = jmap_request(
+        john,
+        json!([["MDN/send", {
+            "accountId": account_id.to_string(),
+            "identityId": &identity_id,
+            "send": {
+                "k1": {
+                    "forEmailId": &email_id,
+                    "disposition": {
+                        "actionMode": "manual-action",
+                        "sendingMode": "mdn-sent-manually",
+                        "type": "displayed"
+                    },
+                    "extensionFields": {
+                        "X-Comment": "Yeah, I'm going to need you to go ahead and come in on Saturday"
+                    }
+                },
+                "k2": {
+                    "forEmailId": &email_no_mdn_id,
+                    "disposition": {
+                        "actionMode": "manual-action",
+                        "sendingMode": "mdn-sent-manually",
+                        "type": "displayed"
+                    }
+                }
+            },
+            "onSuccessUpdateEmail": {
+                "#k1": {
+                    "keywords/$seen": true
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    let sent = &response[0][1]["sent"]["k1"];
+    assert_eq!(
+        sent["finalRecipient"], "rfc822; jdoe@example.com",
+        "{response:#?}"
+    );
+    assert_eq!(sent["originalMessageId"], "<tps-report-1@remote.org>");
+    assert!(sent["subject"].as_str().unwrap().contains("TPS Report"));
+    assert_eq!(
+        response[0][1]["notSent"]["k2"]["type"], "invalidProperties",
+        "{response:#?}"
+    );
+    assert_eq!(response[1][0], "Email/set", "{response:#?}");
+    assert_email_properties(client, &email_id, &[&mailbox_id], &["$mdnsent", "$seen"]).await;
+
+    // Verify the MDN
+    let message = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(message.mail_from, "<jdoe@example.com>");
+    assert_eq!(message.rcpt_to, vec!["<bill@remote.org>".to_string()]);
+    for needle in [
+        "report-type=\"disposition-notification\"",
+        "Final-Recipient: rfc822; jdoe@example.com",
+        "Original-Message-ID: <tps-report-1@remote.org>",
+        "Disposition: manual-action/MDN-sent-manually; displayed",
+        "X-Comment: Yeah, I'm going to need you",
+    ] {
+        assert!(message.message.contains(needle), "{}", message.message);
+    }
+
+    // Sending a second MDN for the same email should fail
+    let response = jmap_request(
+        john,
+        json!([["MDN/send", {
+            "accountId": account_id.to_string(),
+            "identityId": &identity_id,
+            "send": {
+                "k3": {
+                    "forEmailId": &email_id,
+                    "disposition": {
+                        "actionMode": "manual-action",
+                        "sendingMode": "mdn-sent-manually",
+                        "type": "deleted"
+                    }
+                }
+            }
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["notSent"]["k3"]["type"], "mdnAlreadySent",
+        "{response:#?}"
+    );
+
+    // Parse the MDN that was sent
+    let response = jmap_request(
+        john,
+        json!([
+            ["Blob/upload", {
+                "accountId": account_id.to_string(),
+                "create": {
+                    "m1": {
+                        "data": [{"data:asText": message.message}],
+                        "type": "message/rfc822"
+                    }
+                }
+            }, "0"],
+        ]),
+    )
+    .await;
+    let blob_id = response[0][1]["created"]["m1"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = jmap_request(
+        john,
+        json!([["MDN/parse", {
+            "accountId": account_id.to_string(),
+            "blobIds": [&blob_id]
+        }, "0"]]),
+    )
+    .await;
+    let parsed = &response[0][1]["parsed"][&blob_id];
+    assert_eq!(parsed["forEmailId"], email_id, "{response:#?}");
+    assert_eq!(parsed["finalRecipient"], "rfc822; jdoe@example.com");
+    assert_eq!(parsed["originalMessageId"], "<tps-report-1@remote.org>");
+    assert_eq!(
+        parsed["disposition"],
+        json!({
+            "actionMode": "manual-action",
+            "sendingMode": "mdn-sent-manually",
+            "type": "displayed"
+        })
+    );
+    assert_eq!(
+        parsed["extensionFields"]["X-Comment"],
+        "Yeah, I'm going to need you to go ahead and come in on Saturday"
+    );
+    assert_eq!(parsed["includeOriginalMessage"], false);
+
+    // Remove test data
+    client.identity_destroy(&identity_id).await.unwrap();
+    destroy_all_mailboxes(client).await;
+    server
+        .store
+        .delete_account_blobs(account_id.document_id())
+        .await
+        .unwrap();
+    server.store.assert_is_empty().await;
+}
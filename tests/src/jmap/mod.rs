@@ -27,7 +27,9 @@ use directory::config::ConfigDirectory;
 use jmap::{api::JmapSessionManager, services::IPC_CHANNEL_BUFFER, JMAP};
 use jmap_client::client::{Client, Credentials};
 use jmap_proto::types::id::Id;
+use serde_json::{json, Value};
 use smtp::core::{SmtpSessionManager, SMTP};
+use store::Store;
 use tokio::sync::{mpsc, watch};
 use utils::{config::ServerProtocol, UnwrapFailure};
 
@@ -40,6 +42,10 @@ use crate::{
 pub mod auth_acl;
 pub mod auth_limits;
 pub mod auth_oauth;
+pub mod blob;
+pub mod calendar;
+pub mod contacts;
+pub mod dav;
 pub mod delivery;
 pub mod email_changes;
 pub mod email_copy;
@@ -52,9 +58,12 @@ pub mod email_set;
 pub mod email_submission;
 pub mod event_source;
 pub mod mailbox;
+pub mod mdn;
+pub mod principal;
 pub mod push_subscription;
 pub mod quota;
 pub mod sieve_script;
+pub mod spam;
 pub mod stress_test;
 pub mod thread_get;
 pub mod thread_merge;
@@ -172,6 +181,10 @@ throttle = "500ms"
 throttle = "500ms"
 attempts.interval = "500ms"
 
+[jmap.spam]
+enable = true
+min-learns = 3
+
 [directory."sql"]
 type = "sql"
 address = "sqlite::memory:"
@@ -196,6 +209,19 @@ email = "address"
 quota = "quota"
 type = "type"
 
+[directory."sql".write]
+list = "SELECT name FROM accounts ORDER BY name"
+insert-principal = "INSERT INTO accounts (name, type, secret, description, quota) VALUES (?, ?, ?, ?, ?)"
+update-principal = "UPDATE accounts SET type = ?, secret = ?, description = ?, quota = ? WHERE name = ?"
+delete-principal = "DELETE FROM accounts WHERE name = ?"
+insert-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+delete-email = "DELETE FROM emails WHERE name = ? AND address = ?"
+delete-emails = "DELETE FROM emails WHERE name = ?"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES (?, ?)"
+delete-member = "DELETE FROM group_members WHERE name = ? AND member_of = ?"
+delete-members = "DELETE FROM group_members WHERE name = ?"
+delete-group-members = "DELETE FROM group_members WHERE member_of = ?"
+
 [directory."local"]
 type = "memory"
 
@@ -233,6 +259,7 @@ pub async fn jmap_tests() {
     email_changes::test(params.server.clone(), &mut params.client).await;
     email_query_changes::test(params.server.clone(), &mut params.client).await;
     email_copy::test(params.server.clone(), &mut params.client).await;
+    blob::test(params.server.clone(), &mut params.client).await;
     thread_get::test(params.server.clone(), &mut params.client).await;
     thread_merge::test(params.server.clone(), &mut params.client).await;
     mailbox::test(params.server.clone(), &mut params.client).await;
@@ -243,10 +270,16 @@ pub async fn jmap_tests() {
     event_source::test(params.server.clone(), &mut params.client).await;
     push_subscription::test(params.server.clone(), &mut params.client).await;
     sieve_script::test(params.server.clone(), &mut params.client).await;
+    contacts::test(params.server.clone(), &mut params.client).await;
+    calendar::test(params.server.clone(), &mut params.client).await;
+    dav::test(params.server.clone(), &mut params.client).await;
     vacation_response::test(params.server.clone(), &mut params.client).await;
+    mdn::test(params.server.clone(), &mut params.client).await;
+    principal::test(params.server.clone()).await;
     email_submission::test(params.server.clone(), &mut params.client).await;
     websocket::test(params.server.clone(), &mut params.client).await;
     quota::test(params.server.clone(), &mut params.client).await;
+    spam::test(params.server.clone(), &mut params.client).await;
 
     if delete {
         params.temp_dir.delete();
@@ -284,15 +317,16 @@ async fn init_jmap_tests(delete_if_exists: bool) -> JMAPTest {
     )
     .unwrap();
     let servers = config.parse_servers().unwrap();
-    let directory = config.parse_directory().unwrap();
+    let store = Arc::new(Store::open(&config).await.unwrap());
+    let directory = config.parse_directory_with_store(Some(&store)).unwrap();
 
     // Start JMAP and SMTP servers
     servers.bind(&config);
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &directory, delivery_tx)
+    let smtp = SMTP::init(&config, &servers, &directory, store.clone(), delivery_tx)
         .await
         .failed("Invalid configuration file");
-    let jmap = JMAP::init(&config, &directory, delivery_rx, smtp.clone())
+    let jmap = JMAP::init(&config, &directory, store, delivery_rx, smtp.clone())
         .await
         .failed("Invalid configuration file");
     let shutdown_tx = servers.spawn(|server, shutdown_rx| {
@@ -399,3 +433,34 @@ pub async fn test_account_login(login: &str, secret: &str) -> Client {
         .await
         .unwrap()
 }
+
+// Sends raw method calls, for extensions not covered by the JMAP client
+pub async fn jmap_request(credentials: (&str, &str), method_calls: Value) -> Value {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .post("https://127.0.0.1:8899/jmap")
+        .basic_auth(credentials.0, Some(credentials.1))
+        .json(&json!({
+            "using": [
+                "urn:ietf:params:jmap:core",
+                "urn:ietf:params:jmap:mail",
+                "urn:ietf:params:jmap:blob",
+                "urn:ietf:params:jmap:mdn",
+                "urn:ietf:params:jmap:quota",
+                "urn:ietf:params:jmap:contacts",
+                "urn:ietf:params:jmap:calendars"
+            ],
+            "methodCalls": method_calls
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap();
+
+    response["methodResponses"].clone()
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use directory::{totp::Totp, Protocol};
+use jmap::JMAP;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+pub async fn test(server: Arc<JMAP>) {
+    println!("Running principal management tests...");
+
+    // Create account
+    assert_eq!(
+        manage_request(
+            Method::POST,
+            "",
+            json!({
+                "name": "jane",
+                "type": "individual",
+                "secrets": ["abcde"],
+                "description": "Jane Doe",
+                "emails": ["jane@example.com"]
+            })
+        )
+        .await["data"],
+        "success"
+    );
+
+    // Credential changes must keep the existing secrets
+    assert_eq!(
+        manage_request(
+            Method::PATCH,
+            "jane",
+            json!([{
+                "action": "addAppPassword",
+                "value": {"name": "phone", "protocols": ["imap"], "secret": "app-secret"}
+            }])
+        )
+        .await["data"],
+        "success"
+    );
+    let totp = Totp::parse(
+        manage_request(Method::PATCH, "jane", json!([{"action": "enableTotp"}])).await["data"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        manage_request(
+            Method::PATCH,
+            "jane",
+            json!([{"action": "setSecrets", "value": ["n3w_s3cr3t"]}])
+        )
+        .await["data"],
+        "success"
+    );
+
+    let response = manage_request(Method::GET, "jane", Value::Null).await;
+    assert_eq!(response["data"]["appPasswords"], json!(["phone"]));
+    assert_eq!(response["data"]["totpEnabled"], true);
+    assert_eq!(response["data"]["secrets"], Value::Null);
+
+    let principal = server.directory.principal("jane").await.unwrap().unwrap();
+    assert!(principal.verify_secret("app-secret", Protocol::Imap).await);
+    assert!(!principal.verify_secret("n3w_s3cr3t", Protocol::Imap).await);
+    assert!(!principal.verify_secret("abcde", Protocol::Imap).await);
+    assert!(
+        principal
+            .verify_secret(
+                &format!("n3w_s3cr3t${}", totp.current_code()),
+                Protocol::OAuth
+            )
+            .await
+    );
+
+    // Disabling the second factor restores password logins
+    assert_eq!(
+        manage_request(Method::PATCH, "jane", json!([{"action": "disableTotp"}])).await["data"],
+        "success"
+    );
+    let principal = server.directory.principal("jane").await.unwrap().unwrap();
+    assert!(principal.verify_secret("n3w_s3cr3t", Protocol::Imap).await);
+    assert!(principal.verify_secret("app-secret", Protocol::Imap).await);
+
+    // Remove test account
+    assert_eq!(
+        manage_request(Method::DELETE, "jane", Value::Null).await["data"],
+        "success"
+    );
+    assert!(server.directory.principal("jane").await.unwrap().is_none());
+}
+
+async fn manage_request(method: Method, name: &str, body: Value) -> Value {
+    let mut request = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .request(
+            method,
+            format!("https://127.0.0.1:8899/admin/principal/{name}"),
+        )
+        .basic_auth("admin", Some("secret"));
+    if !body.is_null() {
+        request = request.json(&body);
+    }
+    request.send().await.unwrap().json::<Value>().await.unwrap()
+}
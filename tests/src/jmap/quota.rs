@@ -30,10 +30,13 @@ use jmap_client::{
     email::EmailBodyPart,
 };
 use jmap_proto::types::{collection::Collection, id::Id};
+use serde_json::json;
 
 use crate::{
     directory::sql::{add_to_group, create_test_user_with_email, set_test_quota},
-    jmap::{delivery::SmtpConnection, mailbox::destroy_all_mailboxes, test_account_login},
+    jmap::{
+        delivery::SmtpConnection, jmap_request, mailbox::destroy_all_mailboxes, test_account_login,
+    },
 };
 
 pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
@@ -110,6 +113,59 @@ pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
         .await
         .unwrap();
 
+    // Test Quota/get
+    let robert = ("robert@example.com", "aabbcc");
+    let response = jmap_request(
+        robert,
+        json!([["Quota/get", {"accountId": account_id.to_string(), "ids": null}, "0"]]),
+    )
+    .await;
+    let list = &response[0][1]["list"];
+    assert_eq!(list.as_array().unwrap().len(), 1, "{response:#?}");
+    assert_eq!(list[0]["resourceType"], "octets");
+    assert_eq!(list[0]["scope"], "account");
+    assert_eq!(list[0]["hardLimit"], 1024);
+    assert_eq!(list[0]["used"], 0);
+    assert_eq!(list[0]["types"], json!(["Email", "SieveScript"]));
+    let quota_id = list[0]["id"].as_str().unwrap().to_string();
+    let quota_state = response[0][1]["state"].as_str().unwrap().to_string();
+
+    // Accounts without a quota have no Quota objects
+    let response = jmap_request(
+        ("jdoe@example.com", "12345"),
+        json!([["Quota/get", {"accountId": other_account_id.to_string()}, "0"]]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["list"].as_array().unwrap().len(),
+        0,
+        "{response:#?}"
+    );
+
+    // Test Quota/query
+    let response = jmap_request(
+        robert,
+        json!([
+            ["Quota/query", {
+                "accountId": account_id.to_string(),
+                "filter": {"scope": "account", "resourceType": "octets", "type": "Email"},
+                "sort": [{"property": "used"}]
+            }, "0"],
+            ["Quota/query", {
+                "accountId": account_id.to_string(),
+                "filter": {"operator": "NOT", "conditions": [{"scope": "domain"}]}
+            }, "1"],
+            ["Quota/query", {
+                "accountId": account_id.to_string(),
+                "filter": {"resourceType": "count"}
+            }, "2"]
+        ]),
+    )
+    .await;
+    assert_eq!(response[0][1]["ids"], json!([quota_id]), "{response:#?}");
+    assert_eq!(response[1][1]["ids"], json!([quota_id]), "{response:#?}");
+    assert_eq!(response[2][1]["ids"], json!([]), "{response:#?}");
+
     // Test Email/import quota
     let inbox_id = Id::new(INBOX_ID as u64).to_string();
     let mut message_ids = Vec::new();
@@ -143,6 +199,32 @@ pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
             .await,
     );
 
+    // Test Quota/changes
+    let response = jmap_request(
+        robert,
+        json!([
+            ["Quota/changes", {"accountId": account_id.to_string(), "sinceState": quota_state}, "0"],
+            ["Quota/get", {"accountId": account_id.to_string(), "ids": [&quota_id]}, "1"]
+        ]),
+    )
+    .await;
+    assert_eq!(
+        response[0][1]["updated"],
+        json!([quota_id]),
+        "{response:#?}"
+    );
+    assert_ne!(response[0][1]["newState"], quota_state);
+    assert_eq!(response[1][1]["list"][0]["used"], 1024);
+    let response = jmap_request(
+        robert,
+        json!([["Quota/changes", {
+            "accountId": account_id.to_string(),
+            "sinceState": response[0][1]["newState"]
+        }, "0"]]),
+    )
+    .await;
+    assert_eq!(response[0][1]["updated"], json!([]), "{response:#?}");
+
     // Delete messages and check available quota
     for message_id in message_ids {
         client.email_destroy(&message_id).await.unwrap();
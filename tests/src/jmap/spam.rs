@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap::JMAP;
+use jmap_client::{client::Client, mailbox::Role};
+use jmap_proto::types::id::Id;
+
+use crate::jmap::mailbox::destroy_all_mailboxes;
+
+pub async fn test(server: Arc<JMAP>, client: &mut Client) {
+    println!("Running spam classifier tests...");
+    let account_id = Id::new(1);
+    client.set_default_account_id(account_id.to_string());
+
+    // Create test mailboxes
+    let inbox_id = client
+        .mailbox_create("Spam Test", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let junk_id = client
+        .mailbox_create("Junk", None::<String>, Role::Junk)
+        .await
+        .unwrap()
+        .take_id();
+
+    // Classifier should not score messages until trained
+    let spam = build_message(
+        "Claim your lottery prize",
+        "Congratulations winner, claim your free lottery prize now with cheap pills.",
+    );
+    let ham = build_message(
+        "Quarterly report meeting",
+        "Please review the quarterly report agenda before our meeting on Monday.",
+    );
+    assert!(server
+        .spam_classify(Some(account_id.document_id()), spam.as_bytes())
+        .await
+        .unwrap()
+        .is_none());
+
+    // Train spam by moving messages to Junk
+    let mut spam_ids = Vec::new();
+    for num in 0..3 {
+        let email_id = client
+            .email_import(
+                build_message(
+                    &format!("Lottery winner #{num}"),
+                    "Congratulations winner, claim your free lottery prize with cheap pills.",
+                )
+                .into_bytes(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        client
+            .email_set_mailbox(&email_id, &junk_id, true)
+            .await
+            .unwrap();
+        client
+            .email_set_mailbox(&email_id, &inbox_id, false)
+            .await
+            .unwrap();
+        spam_ids.push(email_id);
+    }
+
+    // Train ham
+    for num in 0..3 {
+        server
+            .spam_train(
+                account_id.document_id(),
+                build_message(
+                    &format!("Meeting #{num}"),
+                    "The quarterly report and agenda for Monday's meeting are attached.",
+                )
+                .as_bytes(),
+                false,
+            )
+            .await;
+    }
+
+    // Classify messages
+    let result = server
+        .spam_classify(Some(account_id.document_id()), spam.as_bytes())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(result.is_spam, "{result:?}");
+    let result = server
+        .spam_classify(Some(account_id.document_id()), ham.as_bytes())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!result.is_spam, "{result:?}");
+
+    // Global counts are used for other accounts
+    let result = server
+        .spam_classify(None, spam.as_bytes())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(result.is_spam, "{result:?}");
+
+    // Moving a message out of Junk trains it as ham
+    client
+        .email_set_mailbox(&spam_ids[0], &inbox_id, true)
+        .await
+        .unwrap();
+    client
+        .email_set_mailbox(&spam_ids[0], &junk_id, false)
+        .await
+        .unwrap();
+
+    // Empty store
+    destroy_all_mailboxes(client).await;
+    server.store.assert_is_empty().await;
+}
+
+fn build_message(subject: &str, body: &str) -> String {
+    format!(
+        concat!(
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: {}\r\n",
+            "\r\n",
+            "{}\r\n"
+        ),
+        subject, body
+    )
+}
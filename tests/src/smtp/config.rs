@@ -420,6 +420,7 @@ fn parse_servers() {
             }],
             tls: None,
             tls_implicit: false,
+            tls_end_points: Default::default(),
             max_connections: 8192,
         },
         Server {
@@ -444,6 +445,7 @@ fn parse_servers() {
             ],
             tls: None,
             tls_implicit: true,
+            tls_end_points: Default::default(),
             max_connections: 1024,
         },
         Server {
@@ -460,6 +462,7 @@ fn parse_servers() {
             }],
             tls: None,
             tls_implicit: true,
+            tls_end_points: Default::default(),
             max_connections: 8192,
         },
     ];
@@ -92,6 +92,7 @@ async fn data() {
     config.data.add_received = config.data.add_auth_results.clone();
     config.data.add_return_path = config.data.add_auth_results.clone();
     config.data.add_received_spf = config.data.add_auth_results.clone();
+    config.data.add_spam = config.data.add_auth_results.clone();
     config.data.max_received_headers = IfBlock::new(3);
     config.data.max_messages = r"[{if = 'remote-ip', eq = '10.0.0.1', then = 1},
     {else = 100}]"
@@ -181,6 +182,32 @@ async fn data() {
         .assert_contains("Authentication-Results: ")
         .assert_contains("Received-SPF: ");
 
+    // Spam headers added by untrusted parties should be removed
+    session
+        .send_message(
+            "john@doe.org",
+            &["mike@test.com"],
+            concat!(
+                "From: john@doe.org\r\n",
+                "X-Spam-Status: No, score=0.00\r\n",
+                "X-Spam-Score: 0.00\r\n",
+                "\tfolded\r\n",
+                "Subject: spam headers\r\n\r\n",
+                "X-Spam-Status: body\r\n"
+            ),
+            "250",
+        )
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("Subject: spam headers")
+        .assert_contains("X-Spam-Status: body")
+        .assert_not_contains("X-Spam-Status: No")
+        .assert_not_contains("X-Spam-Score")
+        .assert_not_contains("folded");
+
     // Only one message is allowed in the queue from john@doe.org
     let mut queued_messages = vec![];
     session.data.remote_ip = "10.0.0.2".parse().unwrap();
@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use smtp::{
+    config::{session::ConfigSession, ConfigContext, EnvelopeKey, IfBlock},
+    core::{Session, SMTP},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use utils::config::Config;
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    session::TestSession,
+    TestConfig, TestSMTP,
+};
+
+const CONFIG: &str = r#"
+[session.data.milter."test"]
+enable = [ { if = "remote-ip", eq = "10.0.0.123", then = true }, 
+           { else = false } ]
+hostname = "127.0.0.1"
+port = 9332
+
+[session.data.milter."test".options]
+tempfail-on-error = true
+"#;
+
+#[tokio::test]
+async fn milter_session() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start stand-in milter
+    let listener = TcpListener::bind("127.0.0.1:9332").await.unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(accept_milter(stream));
+        }
+    });
+
+    // Prepare config
+    let mut core = SMTP::test();
+    let mut qr = core.init_test_queue("smtp_milter_test");
+    let config = Config::parse(CONFIG).unwrap();
+    let milters = config
+        .parse_milters(&ConfigContext::new(&[]), &[EnvelopeKey::RemoteIp])
+        .unwrap();
+    assert_eq!(milters.len(), 1);
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    core.session.config.data.milters = milters;
+    let quarantine_path = std::env::temp_dir().join("smtp_milter_quarantine");
+    let _ = std::fs::remove_dir_all(&quarantine_path);
+    std::fs::create_dir_all(&quarantine_path).unwrap();
+    core.session.config.data.quarantine_path = Some(quarantine_path.clone());
+
+    // Milters are not enabled for this IP
+    let mut session = Session::test(core);
+    session.data.remote_ip = "10.0.0.1".parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.doe.org").await;
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org"],
+            "From: john@doe.org\r\nSubject: reject\r\n\r\nTest message\r\n",
+            "250",
+        )
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("Subject: reject");
+    qr.assert_empty_queue();
+
+    // Messages can be rejected, tempfailed and discarded
+    session.data.remote_ip = "10.0.0.123".parse().unwrap();
+    session.data.remote_port = 52734;
+    session.eval_session_params().await;
+    for (subject, expected_code) in [
+        ("reject", "550 5.7.1 Rejected by milter"),
+        ("reject-plain", "550 5.7.1"),
+        ("tempfail", "451 4.3.5"),
+        ("discard", "250"),
+    ] {
+        session
+            .send_message(
+                "john@doe.org",
+                &["bill@foobar.org"],
+                &format!("From: john@doe.org\r\nSubject: {subject}\r\n\r\nTest message\r\n"),
+                expected_code,
+            )
+            .await;
+        qr.assert_empty_queue();
+    }
+
+    // Headers and body are modified
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org"],
+            concat!(
+                "From: john@doe.org\r\n",
+                "Subject: modify\r\n",
+                "X-Remove: true\r\n\r\n",
+                "Test message\r\n"
+            ),
+            "250",
+        )
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("X-Spam-Score: 5.0")
+        .assert_contains("Subject: [SPAM] modify")
+        .assert_contains("This message has been replaced")
+        .assert_not_contains("X-Remove")
+        .assert_not_contains("Test message");
+    qr.assert_empty_queue();
+
+    // Quarantined messages are held instead of queued
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org"],
+            "From: john@doe.org\r\nSubject: quarantine\r\n\r\nTest message\r\n",
+            "250",
+        )
+        .await;
+    qr.assert_empty_queue();
+    let quarantined = std::fs::read_dir(&quarantine_path)
+        .unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(quarantined.len(), 1);
+    assert!(quarantined[0].starts_with("X-Quarantine: Looks like spam\r\n"));
+    assert!(quarantined[0].contains("Subject: quarantine"));
+    std::fs::remove_dir_all(&quarantine_path).unwrap();
+
+    // Unmodified messages are delivered as-is
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org"],
+            "From: john@doe.org\r\nSubject: accept\r\n\r\nTest message\r\n",
+            "250",
+        )
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("Subject: accept")
+        .assert_contains("Test message");
+    qr.assert_empty_queue();
+}
+
+async fn accept_milter(mut stream: TcpStream) {
+    let mut subject = String::new();
+
+    loop {
+        let len = match tokio::time::timeout(Duration::from_secs(5), stream.read_u32()).await {
+            Ok(Ok(len)) => len as usize,
+            _ => return,
+        };
+        let mut frame = vec![0u8; len];
+        stream.read_exact(&mut frame).await.unwrap();
+
+        let response: Vec<Vec<u8>> = match frame[0] {
+            b'O' => {
+                // Version 6, add/change headers, replace body and quarantine, no protocol flags
+                let mut response = vec![b'O'];
+                response.extend_from_slice(&6u32.to_be_bytes());
+                response.extend_from_slice(&(0x01u32 | 0x02 | 0x10 | 0x20).to_be_bytes());
+                response.extend_from_slice(&0u32.to_be_bytes());
+                vec![response]
+            }
+            b'D' => vec![],
+            b'C' => {
+                // The client port follows the hostname and the address family
+                let port_pos = frame.iter().position(|&ch| ch == 0).unwrap() + 2;
+                assert_eq!(
+                    u16::from_be_bytes([frame[port_pos], frame[port_pos + 1]]),
+                    52734
+                );
+                vec![b"c".to_vec()]
+            }
+            b'H' | b'M' | b'R' | b'T' | b'N' | b'B' => vec![b"c".to_vec()],
+            b'L' => {
+                let mut parts = frame[1..].split(|&ch| ch == 0);
+                let name = parts.next().unwrap_or_default();
+                let value = parts.next().unwrap_or_default();
+                if name.eq_ignore_ascii_case(b"Subject") {
+                    subject = String::from_utf8_lossy(value).trim().to_string();
+                }
+                vec![b"c".to_vec()]
+            }
+            b'E' => match subject.as_str() {
+                "reject" => vec![b"y550 5.7.1 Rejected by milter\0".to_vec()],
+                "reject-plain" => vec![b"r".to_vec()],
+                "tempfail" => vec![b"t".to_vec()],
+                "discard" => vec![b"d".to_vec()],
+                "modify" => {
+                    let mut change_subject = vec![b'm'];
+                    change_subject.extend_from_slice(&1u32.to_be_bytes());
+                    change_subject.extend_from_slice(b"Subject\0[SPAM] modify\0");
+                    let mut remove_header = vec![b'm'];
+                    remove_header.extend_from_slice(&1u32.to_be_bytes());
+                    remove_header.extend_from_slice(b"X-Remove\0\0");
+                    vec![
+                        b"hX-Spam-Score\x005.0\0".to_vec(),
+                        change_subject,
+                        remove_header,
+                        b"bThis message has been replaced\r\n".to_vec(),
+                        b"a".to_vec(),
+                    ]
+                }
+                "quarantine" => vec![b"qLooks like spam\0".to_vec(), b"a".to_vec()],
+                _ => vec![b"a".to_vec()],
+            },
+            b'Q' => return,
+            b'A' => vec![],
+            command => panic!("Unexpected milter command {:?}", command as char),
+        };
+
+        for response in response {
+            stream
+                .write_all(&(response.len() as u32).to_be_bytes())
+                .await
+                .unwrap();
+            stream.write_all(&response).await.unwrap();
+        }
+        stream.flush().await.unwrap();
+    }
+}
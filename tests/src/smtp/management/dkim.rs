@@ -0,0 +1,290 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use directory::config::ConfigDirectory;
+use store::Store;
+use utils::config::{Config, ServerProtocol};
+
+use crate::smtp::{
+    inbound::TestQueueEvent,
+    make_temp_dir,
+    management::{send_manage_request, send_manage_request_raw},
+    outbound::start_test_server,
+    session::{TestSession, VerifyResponse},
+    TestConfig, TestSMTP,
+};
+use smtp::{
+    config::IfBlock,
+    core::{
+        dkim::{DkimKeyAlgorithm, DkimKeyStatus, DkimKeyStore},
+        management::{DkimDnsRecord, DkimKey},
+        Session, SMTP,
+    },
+};
+
+const DIRECTORY: &str = r#"
+[directory."local"]
+type = "memory"
+
+[directory."local".options]
+superuser-group = "superusers"
+
+[[directory."local".users]]
+name = "admin"
+description = "Superuser"
+secret = "secret"
+member-of = ["superusers"]
+
+"#;
+
+const CONFIG: &str = r#"
+[auth.dkim.managed]
+enable = true
+algorithm = "ed25519"
+selector = "test"
+rotate-after = "4s"
+overlap = "1s"
+"#;
+
+const MESSAGE: &str =
+    "From: john@example.org\r\nTo: bill@foobar.org\r\nSubject: test\r\n\r\nTest message\r\n";
+
+#[tokio::test]
+#[serial_test::serial]
+async fn manage_dkim_keys() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Create key store
+    let store_dir = make_temp_dir("smtp_dkim_keys_db", true);
+    let store = Arc::new(
+        Store::open(
+            &Config::parse(&format!(
+                concat!(
+                    "store.blob.type = \"local\"\n",
+                    "store.blob.local.path = \"{}\"\n",
+                    "store.db.path = \"{}/sqlite.db\"\n"
+                ),
+                store_dir.temp_dir.display(),
+                store_dir.temp_dir.display()
+            ))
+            .unwrap(),
+        )
+        .await
+        .unwrap(),
+    );
+    let mut core = SMTP::test();
+    let mut qr = core.init_test_queue("smtp_dkim_keys_test");
+    let directory = Config::parse(DIRECTORY).unwrap().parse_directory().unwrap();
+    core.queue.config.management_lookup = directory.directories.get("local").unwrap().clone();
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    core.dkim_keys = DkimKeyStore::init(&Config::parse(CONFIG).unwrap(), store).unwrap();
+    let keys = core.dkim_keys.as_ref().unwrap();
+    assert_eq!(keys.algorithm, DkimKeyAlgorithm::Ed25519);
+    assert_eq!(keys.rotate_after, Duration::from_secs(4));
+
+    // Start management service
+    let core = Arc::new(core);
+    let keys = core.dkim_keys.as_ref().unwrap();
+    let _rx_manage = start_test_server(core.clone(), &[ServerProtocol::Http]);
+
+    // The first key of a domain is active right away
+    let first_key = send_manage_request::<DkimKey>("/admin/dkim/generate?domain=Example.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(first_key.domain, "example.org");
+    assert_eq!(first_key.algorithm, DkimKeyAlgorithm::Ed25519);
+    assert_eq!(first_key.status, DkimKeyStatus::Active);
+    assert!(first_key.selector.starts_with("test-"), "{first_key:?}");
+    assert_eq!(
+        first_key.dns_record.name,
+        format!("{}._domainkey.example.org", first_key.selector)
+    );
+    assert_eq!(first_key.dns_record.type_, "TXT");
+    assert!(first_key
+        .dns_record
+        .value
+        .starts_with("v=DKIM1; k=ed25519; p="));
+    keys.maintain().await.unwrap();
+    assert!(keys.signer("example.org").is_some());
+    assert!(keys.signer("foobar.org").is_none());
+
+    // Messages from the domain are signed with the active key
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip = "10.0.0.1".parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.example.org").await;
+    session
+        .send_message("john@example.org", &["bill@foobar.org"], MESSAGE, "250")
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains(&format!(
+            "a=ed25519-sha256; s={}; d=example.org;",
+            first_key.selector
+        ));
+
+    // Keys are selected by the From header domain, not the envelope sender
+    session
+        .send_message(
+            "john@example.org",
+            &["bill@foobar.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_not_contains("d=example.org;");
+
+    // Generating a new key schedules a rotation
+    let second_key =
+        send_manage_request::<DkimKey>("/admin/dkim/generate?domain=example.org&selector=next")
+            .await
+            .unwrap()
+            .unwrap_data();
+    assert_eq!(second_key.status, DkimKeyStatus::Pending);
+    assert_eq!(second_key.selector, "next");
+    assert!(
+        send_manage_request_raw("/admin/dkim/generate?domain=example.org")
+            .await
+            .unwrap()
+            .contains("already scheduled")
+    );
+    assert!(
+        send_manage_request_raw("/admin/dkim/generate?domain=test.org&algorithm=dsa")
+            .await
+            .unwrap()
+            .contains("bad-parameters")
+    );
+    let keys_list = send_manage_request::<Vec<DkimKey>>("/admin/dkim/list?domain=example.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(keys_list.len(), 2);
+    assert_eq!(keys_list[0].id, first_key.id);
+    assert_eq!(keys_list[0].status, DkimKeyStatus::Active);
+    assert_eq!(keys_list[0].retire_at, Some(second_key.active_from.clone()));
+    assert!(keys_list[0].remove_at.is_some());
+    assert_eq!(keys_list[1].id, second_key.id);
+
+    // Both keys are published during the overlap period
+    let rsa_key = send_manage_request::<DkimKey>(
+        "/admin/dkim/generate?domain=test.org&algorithm=rsa&selector=rsa",
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    assert!(rsa_key.dns_record.value.starts_with("v=DKIM1; k=rsa; p="));
+    let records = send_manage_request::<Vec<DkimDnsRecord>>("/admin/dkim/dns")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(records.len(), 3);
+    assert_eq!(
+        send_manage_request::<Vec<DkimDnsRecord>>("/admin/dkim/dns?domain=example.org")
+            .await
+            .unwrap()
+            .unwrap_data(),
+        vec![first_key.dns_record, second_key.dns_record]
+    );
+
+    // Delete keys
+    assert!(
+        send_manage_request::<bool>(&format!("/admin/dkim/delete?id={}", rsa_key.id))
+            .await
+            .unwrap()
+            .unwrap_data()
+    );
+    assert!(
+        !send_manage_request::<bool>(&format!("/admin/dkim/delete?id={}", rsa_key.id))
+            .await
+            .unwrap()
+            .unwrap_data()
+    );
+    keys.maintain().await.unwrap();
+    assert!(keys.signer("test.org").is_none());
+
+    // The new key takes over once the overlap period ends
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    keys.maintain().await.unwrap();
+    let keys_list = send_manage_request::<Vec<DkimKey>>("/admin/dkim/list?domain=example.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(
+        keys_list.iter().map(|key| key.status).collect::<Vec<_>>(),
+        vec![DkimKeyStatus::Retired, DkimKeyStatus::Active]
+    );
+    session
+        .send_message("john@example.org", &["bill@foobar.org"], MESSAGE, "250")
+        .await;
+    qr.read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("a=ed25519-sha256; s=next; d=example.org;");
+
+    // Retired keys are removed after the overlap period
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    keys.maintain().await.unwrap();
+    let keys_list = send_manage_request::<Vec<DkimKey>>("/admin/dkim/list?domain=example.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(keys_list.len(), 1);
+    assert_eq!(keys_list[0].id, second_key.id);
+
+    // Keys are rotated automatically ahead of their expiration
+    let auto_key = send_manage_request::<DkimKey>("/admin/dkim/generate?domain=auto.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    tokio::time::sleep(Duration::from_millis(3100)).await;
+    keys.maintain().await.unwrap();
+    let keys_list = send_manage_request::<Vec<DkimKey>>("/admin/dkim/list?domain=auto.org")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(keys_list.len(), 2);
+    assert_eq!(keys_list[0].id, auto_key.id);
+    assert_eq!(
+        keys_list.iter().map(|key| key.status).collect::<Vec<_>>(),
+        vec![DkimKeyStatus::Active, DkimKeyStatus::Pending]
+    );
+    assert_eq!(
+        keys_list[0].retire_at,
+        Some(keys_list[1].active_from.clone())
+    );
+}
@@ -0,0 +1,211 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use directory::config::ConfigDirectory;
+use store::Store;
+use utils::config::{Config, ServerProtocol};
+
+use crate::smtp::{
+    inbound::TestQueueEvent,
+    make_temp_dir,
+    management::{send_manage_request, send_manage_request_raw},
+    outbound::start_test_server,
+    session::TestSession,
+    TestConfig, TestSMTP,
+};
+use smtp::{
+    config::{AddressMatch, IfBlock},
+    core::{Session, SMTP},
+    reporting::store::{IncomingReport, IncomingReportData, ReportAggregate, ReportStore},
+};
+
+const DIRECTORY: &str = r#"
+[directory."local"]
+type = "memory"
+
+[directory."local".options]
+superuser-group = "superusers"
+
+[[directory."local".users]]
+name = "admin"
+description = "Superuser"
+secret = "secret"
+member-of = ["superusers"]
+
+"#;
+
+#[tokio::test]
+#[serial_test::serial]
+async fn manage_incoming_reports() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Create report store
+    let store_dir = make_temp_dir("smtp_incoming_report_db", true);
+    let store = Arc::new(
+        Store::open(
+            &Config::parse(&format!(
+                concat!(
+                    "store.blob.type = \"local\"\n",
+                    "store.blob.local.path = \"{}\"\n",
+                    "store.db.path = \"{}/sqlite.db\"\n"
+                ),
+                store_dir.temp_dir.display(),
+                store_dir.temp_dir.display()
+            ))
+            .unwrap(),
+        )
+        .await
+        .unwrap(),
+    );
+    let mut core = SMTP::test();
+    let mut qr = core.init_test_queue("smtp_incoming_report_test");
+    let directory = Config::parse(DIRECTORY).unwrap().parse_directory().unwrap();
+    core.queue.config.management_lookup = directory.directories.get("local").unwrap().clone();
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    core.session.config.data.max_messages = IfBlock::new(1024);
+    let config = &mut core.report.config.analysis;
+    config.addresses = vec![AddressMatch::StartsWith("reports@".to_string())];
+    config.forward = false;
+    core.report.store = ReportStore { store }.into();
+
+    // Start management service
+    let core = Arc::new(core);
+    let _rx_manage = start_test_server(core.clone(), &[ServerProtocol::Http]);
+
+    // Deliver DMARC and TLS reports
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip = "10.0.0.1".parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+    for (test, num_tests) in [("dmarc", 5), ("tls", 2)] {
+        for num_test in 1..=num_tests {
+            session
+                .send_message(
+                    "john@test.org",
+                    &["reports@foobar.org"],
+                    &format!("report:{test}{num_test}"),
+                    "250",
+                )
+                .await;
+            qr.assert_empty_queue();
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Test list search
+    for (query, expected_count) in [
+        ("/admin/incoming/list", 7),
+        ("/admin/incoming/list?type=dmarc", 5),
+        ("/admin/incoming/list?type=tls", 2),
+        ("/admin/incoming/list?domain=stalw.art", 5),
+        ("/admin/incoming/list?domain=STALW.ART&type=tls", 0),
+        ("/admin/incoming/list?domain=example.com", 1),
+        ("/admin/incoming/list?ip=50.223.129.194", 3),
+        ("/admin/incoming/list?ip=198.51.100.62", 1),
+        ("/admin/incoming/list?after=2020-01-01T00:00:00Z", 6),
+        ("/admin/incoming/list?before=2022-10-01T00:00:00Z", 3),
+        (
+            "/admin/incoming/list?domain=stalw.art&ip=50.223.129.194&after=2022-11-01T00:00:00Z",
+            2,
+        ),
+    ] {
+        assert_eq!(
+            send_manage_request::<Vec<u32>>(query)
+                .await
+                .unwrap()
+                .unwrap_data()
+                .len(),
+            expected_count,
+            "failed for {query}"
+        );
+    }
+    assert!(send_manage_request_raw("/admin/incoming/list?ip=invalid")
+        .await
+        .unwrap()
+        .contains("bad-parameters"));
+
+    // Fetch reports
+    let ids = send_manage_request::<Vec<u32>>("/admin/incoming/list?domain=example.com")
+        .await
+        .unwrap()
+        .unwrap_data();
+    let mut reports = send_manage_request::<Vec<Option<IncomingReport>>>(&format!(
+        "/admin/incoming/get?ids={},{}",
+        ids[0],
+        u32::MAX - 1
+    ))
+    .await
+    .unwrap()
+    .unwrap_data()
+    .into_iter();
+    let report = reports.next().unwrap().unwrap();
+    assert!(reports.next().unwrap().is_none());
+    assert_eq!(report.from, "tlsrpt@mail.sender.example.com");
+    assert_eq!(report.organization, "Google Inc.");
+    assert_eq!(report.domains, vec!["example.com".to_string()]);
+    match report.report {
+        IncomingReportData::Tls(tls) => {
+            assert_eq!(tls.policies[0].summary.total_failure, 1);
+        }
+        IncomingReportData::Dmarc(_) => panic!("Expected TLS report"),
+    }
+
+    // Aggregate DMARC reports
+    let aggregate =
+        send_manage_request::<ReportAggregate>("/admin/incoming/aggregate?domain=stalw.art")
+            .await
+            .unwrap()
+            .unwrap_data();
+    assert_eq!(aggregate.reports, 5);
+    assert_eq!(aggregate.dmarc.messages, 5);
+    assert_eq!(aggregate.dmarc.dkim_pass, 1);
+    assert_eq!(aggregate.dmarc.dkim_fail, 4);
+    assert_eq!(aggregate.dmarc.spf_pass, 1);
+    assert_eq!(aggregate.dmarc.spf_fail, 4);
+    assert_eq!(aggregate.dmarc.sources.len(), 3);
+    assert_eq!(aggregate.dmarc.sources[0].ip.to_string(), "50.223.129.194");
+    assert_eq!(aggregate.dmarc.sources[0].messages, 3);
+    assert_eq!(aggregate.dmarc.sources[0].spf_fail, 3);
+    assert_eq!(aggregate.tls.reporters.len(), 0);
+
+    // Aggregate TLS reports
+    let aggregate = send_manage_request::<ReportAggregate>("/admin/incoming/aggregate?type=tls")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(aggregate.reports, 2);
+    assert_eq!(aggregate.dmarc.messages, 0);
+    assert_eq!(aggregate.tls.sessions_success, 5349);
+    assert_eq!(aggregate.tls.sessions_failure, 304);
+    assert_eq!(aggregate.tls.reporters.len(), 2);
+    assert_eq!(aggregate.tls.reporters[0].organization, "Company-X");
+    assert_eq!(aggregate.tls.reporters[0].sessions_failure, 303);
+    assert_eq!(aggregate.tls.reporters[0].failures[0].sessions, 200);
+}
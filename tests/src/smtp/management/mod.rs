@@ -26,6 +26,8 @@ use std::time::Duration;
 use hyper::header::AUTHORIZATION;
 use serde::{de::DeserializeOwned, Deserialize};
 
+pub mod dkim;
+pub mod incoming;
 pub mod queue;
 pub mod report;
 
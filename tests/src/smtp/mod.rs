@@ -41,9 +41,10 @@ use smtp::{
         if_block::ConfigIf, queue::ConfigQueue, throttle::ConfigThrottle, AggregateReport,
         ArcAuthConfig, Auth, ConfigContext, Connect, Data, DkimAuthConfig, DmarcAuthConfig,
         DnsBlConfig, Dsn, Ehlo, EnvelopeKey, Extensions, IfBlock, IpRevAuthConfig, Mail,
-        MailAuthConfig, QueueConfig, QueueOutboundSourceIp, QueueOutboundTimeout, QueueOutboundTls,
-        QueueQuotas, QueueThrottle, Rcpt, Report, ReportAnalysis, ReportConfig, SessionConfig,
-        SessionThrottle, SpfAuthConfig, Throttle, VerifyStrategy,
+        MailAuthConfig, QueueConfig, QueueOutboundAdaptive, QueueOutboundRelay, QueueOutboundReuse,
+        QueueOutboundSourceIp, QueueOutboundTimeout, QueueOutboundTls, QueueQuotas, QueueThrottle,
+        Rcpt, RelayStrategy, Report, ReportAnalysis, ReportConfig, SessionConfig, SessionThrottle,
+        SpfAuthConfig, Throttle, VerifyStrategy,
     },
     core::{
         throttle::ThrottleKeyHasherBuilder, QueueCore, ReportCore, Resolvers, SessionCore,
@@ -158,6 +159,7 @@ impl TestConfig for SMTP {
             mail_auth: MailAuthConfig::test(),
             report: ReportCore::test(),
             sieve: SieveCore::test(),
+            dkim_keys: None,
             delivery_tx: mpsc::channel(1).0,
         }
     }
@@ -236,7 +238,9 @@ impl TestConfig for SessionConfig {
                 add_auth_results: IfBlock::new(true),
                 add_message_id: IfBlock::new(true),
                 add_date: IfBlock::new(true),
+                add_spam: IfBlock::new(false),
                 pipe_commands: vec![],
+                milters: vec![],
             },
         }
     }
@@ -256,12 +260,16 @@ impl TestConfig for QueueCore {
                 ThrottleKeyHasherBuilder::default(),
                 16,
             ),
+            adaptive: DashMap::default(),
             tx: mpsc::channel(1024).0,
             id_seq: 0.into(),
             connectors: TlsConnectors {
                 pki_verify: build_tls_connector(false),
                 dummy_verify: build_tls_connector(true),
             },
+            connections: Default::default(),
+            relays: Default::default(),
+            store: None,
         }
     }
 }
@@ -276,6 +284,10 @@ impl TestConfig for QueueConfig {
             expire: IfBlock::new(Duration::from_secs(10)),
             hostname: IfBlock::new("mx.example.org".to_string()),
             next_hop: Default::default(),
+            relay: QueueOutboundRelay {
+                strategy: IfBlock::new(RelayStrategy::Failover),
+                failure_backoff: IfBlock::new(Duration::from_secs(60)),
+            },
             max_mx: IfBlock::new(5),
             max_multihomed: IfBlock::new(5),
             source_ip: QueueOutboundSourceIp {
@@ -303,6 +315,15 @@ impl TestConfig for QueueConfig {
                 data: IfBlock::new(Duration::from_secs(1)),
                 mta_sts: IfBlock::new(Duration::from_secs(1)),
             },
+            reuse: QueueOutboundReuse {
+                idle_timeout: IfBlock::new(Duration::ZERO),
+                max_messages: IfBlock::new(100),
+            },
+            adaptive: QueueOutboundAdaptive {
+                enable: IfBlock::new(false),
+                initial_delay: IfBlock::new(Duration::from_secs(1)),
+                max_delay: IfBlock::new(Duration::from_secs(15 * 60)),
+            },
             throttle: QueueThrottle {
                 sender: vec![],
                 rcpt: vec![],
@@ -324,6 +345,7 @@ impl TestConfig for MailAuthConfig {
             dkim: DkimAuthConfig {
                 verify: IfBlock::new(VerifyStrategy::Relaxed),
                 sign: IfBlock::default(),
+                sign_managed: IfBlock::new(true),
             },
             arc: ArcAuthConfig {
                 verify: IfBlock::new(VerifyStrategy::Relaxed),
@@ -353,6 +375,7 @@ impl TestConfig for ReportCore {
         Self {
             config: ReportConfig::test(),
             tx: mpsc::channel(1024).0,
+            store: None,
         }
     }
 }
@@ -84,7 +84,7 @@ async fn lmtp_delivery() {
     config.parse_remote_hosts(&mut ctx).unwrap();
     core.queue.config.next_hop = "[{if = 'rcpt-domain', eq = 'foobar.org', then = 'lmtp'},
     {else = false}]"
-        .parse_if::<Option<String>>(&ctx)
+        .parse_if::<Vec<Option<String>>>(&ctx)
         .into_relay_host(&ctx)
         .unwrap();
     core.session.config.rcpt.relay = IfBlock::new(true);
@@ -34,6 +34,8 @@ pub mod dane;
 pub mod extensions;
 pub mod lmtp;
 pub mod mta_sts;
+pub mod relay;
+pub mod reuse;
 pub mod smtp;
 pub mod throttle;
 
@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::smtp::{
+    inbound::{TestMessage, TestQueueEvent},
+    outbound::start_test_server,
+    session::{TestSession, VerifyResponse},
+    ParseTestConfig, TestConfig, TestSMTP,
+};
+use smtp::{
+    config::{remote::ConfigHost, ConfigContext, IfBlock, RelayStrategy},
+    core::{Session, SMTP},
+    outbound::{relay::RelayState, NextHop},
+    queue::{manager::Queue, DeliveryAttempt},
+};
+use utils::config::{Config, ServerProtocol};
+
+const REMOTE: &str = "
+[remote.relay-a]
+address = relay-a.foobar.org
+port = 9926
+protocol = 'smtp'
+weight = 2
+
+[remote.relay-a.tls]
+implicit = false
+allow-invalid-certs = true
+
+[remote.relay-b]
+address = relay-b.foobar.org
+port = 9925
+protocol = 'smtp'
+
+[remote.relay-b.tls]
+implicit = false
+allow-invalid-certs = true
+";
+
+const PROBE_REMOTE: &str = "
+[remote.relay-down]
+address = 127.0.0.1
+port = 9928
+protocol = 'smtp'
+
+[remote.relay-up]
+address = 127.0.0.1
+port = 9927
+protocol = 'smtp'
+";
+
+#[tokio::test]
+#[serial_test::serial]
+async fn relay_failover() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Start test server
+    let mut core = SMTP::test();
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    let mut remote_qr = core.init_test_queue("smtp_relay_remote");
+    let _rx = start_test_server(core.into(), &[ServerProtocol::Smtp]);
+
+    // Add mock DNS entries
+    let mut core = SMTP::test();
+    for host in ["relay-a.foobar.org", "relay-b.foobar.org"] {
+        core.resolvers.dns.ipv4_add(
+            host,
+            vec!["127.0.0.1".parse().unwrap()],
+            Instant::now() + Duration::from_secs(10),
+        );
+    }
+
+    // Relay through 'relay-a', which is down, with 'relay-b' as a fallback
+    let mut local_qr = core.init_test_queue("smtp_relay_local");
+    let mut ctx = ConfigContext::new(&[]);
+    let config = Config::parse(REMOTE).unwrap();
+    config.parse_remote_hosts(&mut ctx).unwrap();
+    core.queue.config.next_hop = "['relay-a', 'relay-b']"
+        .parse_if::<Vec<Option<String>>>(&ctx)
+        .into_relay_host(&ctx)
+        .unwrap();
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    let core = Arc::new(core);
+    let mut queue = Queue::default();
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip = "10.0.0.1".parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.test.org").await;
+
+    // Message should be delivered through 'relay-b'
+    session
+        .send_message("john@test.org", &["bill@foobar.org"], "test:no_dkim", "250")
+        .await;
+    DeliveryAttempt::from(local_qr.read_event().await.unwrap_message())
+        .try_deliver(core.clone(), &mut queue)
+        .await;
+    local_qr.read_event().await.unwrap_done();
+    remote_qr
+        .read_event()
+        .await
+        .unwrap_message()
+        .read_lines()
+        .assert_contains("using TLSv1.3 with cipher");
+    remote_qr.assert_empty_queue();
+
+    // 'relay-a' should now be tried last
+    let hosts = &core.queue.config.next_hop.default;
+    assert_eq!(
+        relay_ids(core.queue.relays.next_hops(hosts, RelayStrategy::Failover)),
+        ["relay-b", "relay-a"]
+    );
+    assert_eq!(
+        relay_ids(
+            core.queue
+                .relays
+                .next_hops(hosts, RelayStrategy::RoundRobin)
+        ),
+        ["relay-b", "relay-a"]
+    );
+    core.queue.relays.set_available(&hosts[0]);
+    assert_eq!(
+        relay_ids(core.queue.relays.next_hops(hosts, RelayStrategy::Failover)),
+        ["relay-a", "relay-b"]
+    );
+
+    // Round-robin should distribute attempts according to host weights
+    let relays = RelayState::default();
+    let mut first_hops = Vec::new();
+    for _ in 0..6 {
+        first_hops.push(relay_ids(relays.next_hops(hosts, RelayStrategy::RoundRobin))[0]);
+    }
+    assert_eq!(
+        first_hops,
+        ["relay-a", "relay-a", "relay-b", "relay-a", "relay-a", "relay-b"]
+    );
+
+    // Failed relays should be probed until they accept connections again,
+    // 'relay-down' has to stay demoted after its backoff expires
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:9927")
+        .await
+        .unwrap();
+    let mut ctx = ConfigContext::new(&[]);
+    let config = Config::parse(PROBE_REMOTE).unwrap();
+    config.parse_remote_hosts(&mut ctx).unwrap();
+    let hosts = "['relay-down', 'relay-up']"
+        .parse_if::<Vec<Option<String>>>(&ctx)
+        .into_relay_host(&ctx)
+        .unwrap()
+        .default;
+    let relays = RelayState::default();
+    let span = tracing::info_span!("relay_probe");
+    for host in &hosts {
+        relays.set_unavailable(host, Duration::from_millis(100), &span);
+    }
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert_eq!(
+        relay_ids(relays.next_hops(&hosts, RelayStrategy::Failover)),
+        ["relay-up", "relay-down"]
+    );
+    drop(listener);
+}
+
+fn relay_ids(next_hops: Vec<NextHop<'_>>) -> Vec<&str> {
+    next_hops
+        .into_iter()
+        .map(|next_hop| match next_hop {
+            NextHop::Relay(host) => host.id.as_str(),
+            NextHop::MX(_) => unreachable!(),
+        })
+        .collect()
+}
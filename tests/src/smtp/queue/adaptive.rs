@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{Duration, Instant};
+
+use smtp::{
+    core::QueueCore,
+    queue::{
+        adaptive::AdaptiveBackoff, throttle, Error, ErrorDetails, HostResponse, Recipient, Status,
+    },
+};
+use smtp_proto::Response;
+
+use crate::smtp::TestConfig;
+
+#[test]
+fn adaptive_throttle() {
+    let core = QueueCore::test();
+    let span = tracing::info_span!("test");
+    let mx = "mx.test.org";
+    let backoff = AdaptiveBackoff {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(4),
+    };
+
+    // Hosts without deferrals are not slowed down
+    assert!(core.is_adaptive_allowed(mx, &span).is_ok());
+    core.adaptive_update(mx, backoff, &Status::Completed(()), [].iter(), &span);
+    assert!(core.adaptive.is_empty());
+
+    // Temporary failures unrelated to rate limiting are ignored
+    core.adaptive_update(
+        mx,
+        backoff,
+        &Status::TemporaryFailure(unexpected_response(451, [4, 3, 0])),
+        [].iter(),
+        &span,
+    );
+    assert!(core.adaptive.is_empty());
+
+    // A 421 response starts the backoff
+    core.adaptive_update(
+        mx,
+        backoff,
+        &Status::TemporaryFailure(unexpected_response(421, [4, 7, 0])),
+        [].iter(),
+        &span,
+    );
+    assert_eq!(core.adaptive.get(mx).unwrap().delay, Duration::from_secs(1));
+    match core.is_adaptive_allowed(mx, &span) {
+        Err(throttle::Error::Rate { retry_at }) => assert!(retry_at > Instant::now()),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    assert!(core.is_adaptive_allowed("mx.other.org", &span).is_ok());
+
+    // Deferred recipients double the delay up to the configured maximum
+    let recipients = [recipient(Status::TemporaryFailure(HostResponse {
+        hostname: mx.to_string(),
+        response: Response {
+            code: 450,
+            esc: [4, 7, 1],
+            message: "Try again later".to_string(),
+        },
+    }))];
+    for expected_delay in [2, 4, 4] {
+        core.adaptive_update(
+            mx,
+            backoff,
+            &Status::Completed(()),
+            recipients.iter(),
+            &span,
+        );
+        assert_eq!(
+            core.adaptive.get(mx).unwrap().delay,
+            Duration::from_secs(expected_delay)
+        );
+    }
+    let limiter = core.adaptive.get(mx).unwrap().clone();
+    assert_eq!(limiter.deferrals, 4);
+    assert!(limiter.last_response.contains("Try again later"));
+
+    // Successful deliveries gradually recover the host
+    let recipients = [recipient(Status::Completed(HostResponse {
+        hostname: mx.to_string(),
+        response: Response {
+            code: 250,
+            esc: [2, 1, 5],
+            message: "OK".to_string(),
+        },
+    }))];
+    for expected_delay in [2, 1] {
+        core.adaptive_update(
+            mx,
+            backoff,
+            &Status::Completed(()),
+            recipients.iter(),
+            &span,
+        );
+        assert_eq!(
+            core.adaptive.get(mx).unwrap().delay,
+            Duration::from_secs(expected_delay)
+        );
+    }
+    core.adaptive_update(
+        mx,
+        backoff,
+        &Status::Completed(()),
+        recipients.iter(),
+        &span,
+    );
+    assert!(core.adaptive.is_empty());
+    assert!(core.is_adaptive_allowed(mx, &span).is_ok());
+}
+
+fn unexpected_response(code: u16, esc: [u8; 3]) -> Error {
+    Error::UnexpectedResponse(HostResponse {
+        hostname: ErrorDetails {
+            entity: "mx.test.org".to_string(),
+            details: "RCPT TO:<john@test.org>".to_string(),
+        },
+        response: Response {
+            code,
+            esc,
+            message: "Rate limited".to_string(),
+        },
+    })
+}
+
+fn recipient(status: Status<HostResponse<String>, HostResponse<ErrorDetails>>) -> Recipient {
+    Recipient {
+        domain_idx: 0,
+        address: "john@test.org".to_string(),
+        address_lcase: "john@test.org".to_string(),
+        status,
+        flags: 0,
+        orcpt: None,
+    }
+}
@@ -21,7 +21,9 @@
  * for more details.
 */
 
+pub mod adaptive;
 pub mod dsn;
 pub mod manager;
 pub mod retry;
 pub mod serialize;
+pub mod store;
@@ -0,0 +1,207 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use smtp::{
+    core::SMTP,
+    queue::{
+        manager::Queue,
+        store::{QueueStore, QUEUE_COLLECTION},
+        DeliveryAttempt, Error, ErrorDetails, Schedule, Status,
+    },
+};
+use store::{
+    write::{key::KeySerializer, BatchBuilder, Operation, ValueClass},
+    Store,
+};
+use utils::config::Config;
+
+use crate::smtp::{inbound::TestQueueEvent, make_temp_dir, TestConfig, TestSMTP};
+
+#[tokio::test]
+async fn queue_store() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    // Create a store shared by two nodes
+    let store_dir = make_temp_dir("smtp_queue_store_db", true);
+    let store = Arc::new(
+        Store::open(
+            &Config::parse(&format!(
+                concat!(
+                    "store.blob.type = \"local\"\n",
+                    "store.blob.local.path = \"{}\"\n",
+                    "store.db.path = \"{}/sqlite.db\"\n"
+                ),
+                store_dir.temp_dir.display(),
+                store_dir.temp_dir.display()
+            ))
+            .unwrap(),
+        )
+        .await
+        .unwrap(),
+    );
+    let mut node_a = SMTP::test();
+    let mut qr_a = node_a.init_test_queue("smtp_queue_store_node_a");
+    node_a.queue.store = QueueStore {
+        store: store.clone(),
+        node_id: 1,
+        lease_time: Duration::from_secs(1),
+        poll_interval: Duration::from_secs(1),
+        poll_batch: 10,
+    }
+    .into();
+    let mut node_b = SMTP::test();
+    let _qr_b = node_b.init_test_queue("smtp_queue_store_node_b");
+    node_b.queue.store = QueueStore {
+        store: store.clone(),
+        node_id: 2,
+        lease_time: Duration::from_secs(1),
+        poll_interval: Duration::from_secs(1),
+        poll_batch: 10,
+    }
+    .into();
+    let queue_store = node_b.queue.store.as_ref().unwrap();
+
+    // Queue message on node A
+    let mut message =
+        smtp::queue::Message::new_boxed("sender@foobar.org", "sender@foobar.org", "foobar.org");
+    message
+        .add_recipient("rcpt@example.org", &node_a.queue.config)
+        .await;
+    assert!(
+        node_a
+            .queue
+            .queue_message(
+                message,
+                (&b"From: sender@foobar.org\r\n"[..]).into(),
+                b"Subject: test\r\n\r\ntest",
+                &tracing::info_span!("hi")
+            )
+            .await
+    );
+    let message_a = qr_a.read_event().await.unwrap_message();
+    assert_eq!(
+        queue_store.due_messages().await.unwrap(),
+        vec![message_a.id]
+    );
+
+    // Only the message contents are cached locally
+    assert_eq!(
+        std::fs::read(&message_a.path).unwrap(),
+        b"From: sender@foobar.org\r\nSubject: test\r\n\r\ntest"
+    );
+
+    // Node B should not pick up a message leased by node A
+    let mut queue_b = Queue::default();
+    node_b.queue.poll_store(&mut queue_b).await;
+    assert!(queue_b.messages.is_empty());
+    assert!(node_a.queue.lock_message(&message_a).await);
+
+    // Once the lease expires, node B takes over the message
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    node_b.queue.poll_store(&mut queue_b).await;
+    let mut message_b = queue_b.messages.remove(&message_a.id).unwrap();
+    assert!(!node_a.queue.lock_message(&message_a).await);
+    assert!(node_b.queue.lock_message(&message_b).await);
+    assert_eq!(message_b.return_path, message_a.return_path);
+    assert_eq!(message_b.recipients, message_a.recipients);
+    assert_eq!(message_b.size, message_a.size);
+    assert_eq!(
+        std::fs::read(&message_b.path).unwrap(),
+        b"From: sender@foobar.org\r\nSubject: test\r\n\r\ntest"
+    );
+
+    // Node A drops its local copy without removing the message from the store
+    node_a.queue.remove_message(&message_a).await;
+    assert!(!message_a.path.exists());
+    assert!(queue_store.read(message_a.id).await.unwrap().is_some());
+
+    // Save changes and make sure the message is no longer due
+    message_b.domains[0].status = Status::TemporaryFailure(Error::ConnectionError(ErrorDetails {
+        entity: "mx.example.org".to_string(),
+        details: "Connection timeout".to_string(),
+    }));
+    message_b.domains[0].retry = Schedule::later(Duration::from_secs(120));
+    message_b.domains[0].notify = Schedule::later(Duration::from_secs(240));
+    message_b.domains[0].expires = std::time::Instant::now() + Duration::from_secs(360);
+    message_b.domains[0].changed = true;
+    node_b.queue.save_changes(&mut message_b).await;
+    assert!(queue_store.due_messages().await.unwrap().is_empty());
+    let (message, _) = queue_store.read(message_b.id).await.unwrap().unwrap();
+    assert_eq!(message.domains[0].status, message_b.domains[0].status);
+
+    // Remove message
+    node_b.queue.remove_message(&message_b).await;
+    assert!(queue_store.read(message_b.id).await.unwrap().is_none());
+    assert!(!message_b.path.exists());
+
+    // Messages are released but kept in the store when their lease cannot be renewed
+    let mut message =
+        smtp::queue::Message::new_boxed("sender@foobar.org", "sender@foobar.org", "foobar.org");
+    message
+        .add_recipient("rcpt@example.org", &node_a.queue.config)
+        .await;
+    assert!(
+        node_a
+            .queue
+            .queue_message(
+                message,
+                None,
+                b"Subject: test\r\n\r\ntest",
+                &tracing::info_span!("hi")
+            )
+            .await
+    );
+    let message_a = qr_a.read_event().await.unwrap_message();
+    let (id, path) = (message_a.id, message_a.path.clone());
+    // Overwrite the lease with a value that fails to deserialize
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(u32::MAX)
+        .with_collection(QUEUE_COLLECTION)
+        .op(Operation::Value {
+            class: ValueClass::Custom {
+                bytes: KeySerializer::new(
+                    std::mem::size_of::<u32>() + std::mem::size_of::<u64>() + 1,
+                )
+                .write(u32::MAX)
+                .write(3u8)
+                .write(id)
+                .finalize(),
+            },
+            set: Some(vec![0]),
+        });
+    store.write(batch.build()).await.unwrap();
+    assert!(!node_a.queue.lock_message(&message_a).await);
+    DeliveryAttempt::from(message_a)
+        .try_deliver(Arc::new(node_a), &mut Queue::default())
+        .await;
+    assert!(!path.exists());
+    assert!(queue_store.read(id).await.unwrap().is_some());
+}
@@ -125,7 +125,11 @@ impl TestSession for Session<DummyIo> {
                 tx_buf: vec![],
                 tls: false,
             },
-            data: SessionData::new("127.0.0.1".parse().unwrap(), "127.0.0.1".parse().unwrap()),
+            data: SessionData::new(
+                "127.0.0.1".parse().unwrap(),
+                "127.0.0.1".parse().unwrap(),
+                0,
+            ),
             params: SessionParameters::default(),
             in_flight: vec![],
         }
@@ -348,6 +352,7 @@ impl TestServerInstance for ServerInstance {
             protocol: ServerProtocol::Smtp,
             data: "220 mx.example.org at your service.\r\n".to_string(),
             tls_acceptor: None,
+            tls_end_points: Default::default(),
             is_tls_implicit: false,
             limiter: ConcurrencyLimiter::new(100),
             shutdown_rx,
@@ -0,0 +1,210 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::io::{Cursor, Write};
+
+use store::fts::extract::{extract_text, DocumentType};
+use zip::{write::FileOptions, ZipWriter};
+
+#[test]
+fn extract_documents() {
+    // Type detection
+    for (content_type, file_name, expected) in [
+        (
+            Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+            None,
+            Some(DocumentType::Zip),
+        ),
+        (
+            Some("application/vnd.oasis.opendocument.text"),
+            None,
+            Some(DocumentType::Zip),
+        ),
+        (Some("application/zip"), None, Some(DocumentType::Zip)),
+        (
+            Some("application/octet-stream"),
+            Some("Report.XLSX"),
+            Some(DocumentType::Zip),
+        ),
+        (None, Some("backup.tar.gz"), Some(DocumentType::TarGz)),
+        (Some("application/x-tar"), None, Some(DocumentType::Tar)),
+        (
+            Some("application/octet-stream"),
+            Some("notes.txt"),
+            Some(DocumentType::Text),
+        ),
+        (Some("image/png"), Some("image.png"), None),
+        (Some("application/octet-stream"), None, None),
+    ] {
+        assert_eq!(
+            DocumentType::detect(content_type, file_name),
+            expected,
+            "{content_type:?} {file_name:?}"
+        );
+    }
+
+    // OOXML documents
+    let docx = build_zip(&[
+        ("[Content_Types].xml", b"<Types/>".as_slice()),
+        (
+            "word/document.xml",
+            concat!(
+                "<w:document xmlns:w=\"w\"><w:body>",
+                "<w:p><w:r><w:t>Quarterly</w:t></w:r><w:r><w:t xml:space=\"preserve\"> budget",
+                "</w:t></w:r></w:p><w:p><w:r><w:instrText>PAGE</w:instrText>",
+                "<w:t>Caf&#233; &amp; croissants</w:t></w:r></w:p>",
+                "</w:body></w:document>"
+            )
+            .as_bytes(),
+        ),
+        (
+            "word/styles.xml",
+            b"<w:styles><w:t>ignored</w:t></w:styles>".as_slice(),
+        ),
+    ]);
+    assert_eq!(
+        extract_text(&docx, DocumentType::Zip, usize::MAX).unwrap(),
+        "Quarterly budget\nCaf\u{e9} & croissants\n"
+    );
+
+    let xlsx = build_zip(&[
+        ("[Content_Types].xml", b"<Types/>".as_slice()),
+        (
+            "xl/sharedStrings.xml",
+            b"<sst><si><t>Revenue</t></si><si><t>Expenses</t></si></sst>".as_slice(),
+        ),
+        (
+            "xl/worksheets/sheet1.xml",
+            b"<worksheet><sheetData><row><c t=\"s\"><v>0</v></c><c t=\"inlineStr\"><is><t>Forecast</t></is></c></row></sheetData></worksheet>".as_slice(),
+        ),
+    ]);
+    assert_eq!(
+        extract_text(&xlsx, DocumentType::Zip, usize::MAX).unwrap(),
+        "Revenue\nExpenses\nForecast\n"
+    );
+
+    let pptx = build_zip(&[
+        ("[Content_Types].xml", b"<Types/>".as_slice()),
+        (
+            "ppt/slides/slide10.xml",
+            b"<p:sld><a:p><a:r><a:t>Conclusion</a:t></a:r></a:p></p:sld>".as_slice(),
+        ),
+        (
+            "ppt/slides/slide2.xml",
+            b"<p:sld><a:p><a:r><a:t>Introduction</a:t></a:r></a:p></p:sld>".as_slice(),
+        ),
+    ]);
+    assert_eq!(
+        extract_text(&pptx, DocumentType::Zip, usize::MAX).unwrap(),
+        "Introduction\nConclusion\n"
+    );
+
+    // ODF documents
+    let odt = build_zip(&[
+        (
+            "mimetype",
+            b"application/vnd.oasis.opendocument.text".as_slice(),
+        ),
+        (
+            "content.xml",
+            concat!(
+                "<office:document-content><office:body><office:text>",
+                "<text:h>Meeting minutes</text:h>",
+                "<text:p>Attendees:<text:s/>Alice<text:tab/>Bob</text:p>",
+                "</office:text></office:body></office:document-content>"
+            )
+            .as_bytes(),
+        ),
+    ]);
+    assert_eq!(
+        extract_text(&odt, DocumentType::Zip, usize::MAX).unwrap(),
+        "Meeting minutes\nAttendees: Alice Bob\n"
+    );
+
+    // Zip archives containing documents and other archives
+    let nested = build_zip(&[("deep/readme.md", b"Nested archive".as_slice())]);
+    let zip = build_zip(&[
+        ("docs/", b"".as_slice()),
+        ("docs/proposal.docx", docx.as_slice()),
+        ("notes.txt", b"Remember the milk".as_slice()),
+        ("photo.jpg", b"\xff\xd8\xff\xe0".as_slice()),
+        ("nested.zip", nested.as_slice()),
+    ]);
+    assert_eq!(
+        extract_text(&zip, DocumentType::Zip, usize::MAX).unwrap(),
+        concat!(
+            "proposal.docx\nQuarterly budget\nCaf\u{e9} & croissants\n",
+            "notes.txt\nRemember the milk\n",
+            "photo.jpg\n",
+            "nested.zip\nreadme.md\nNested archive\n"
+        )
+    );
+
+    // Tar archives
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, contents) in [
+        ("project/todo.txt", b"Fix the build".as_slice()),
+        ("project/slides.pptx", pptx.as_slice()),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, contents).unwrap();
+    }
+    let tar = builder.into_inner().unwrap();
+    let mut tar_gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    tar_gz.write_all(&tar).unwrap();
+    let tar_gz = tar_gz.finish().unwrap();
+    for (bytes, typ) in [(tar, DocumentType::Tar), (tar_gz, DocumentType::TarGz)] {
+        assert_eq!(
+            extract_text(&bytes, typ, usize::MAX).unwrap(),
+            "todo.txt\nFix the build\nslides.pptx\nIntroduction\nConclusion\n"
+        );
+    }
+
+    // Decompressed data is limited
+    assert_eq!(
+        extract_text(&zip, DocumentType::Zip, 10).unwrap(),
+        "proposal.docx\n"
+    );
+
+    // Invalid documents
+    assert_eq!(
+        extract_text(b"not a zip file", DocumentType::Zip, usize::MAX),
+        None
+    );
+}
+
+fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        if name.ends_with('/') {
+            zip.add_directory(*name, FileOptions::default()).unwrap();
+        } else {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+    }
+    zip.finish().unwrap().into_inner()
+}
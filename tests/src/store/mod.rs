@@ -24,6 +24,7 @@
 #[cfg(feature = "foundationdb")]
 pub mod assign_id;
 pub mod blob;
+pub mod extract;
 pub mod query;
 
 use std::{io::Read, sync::Arc};